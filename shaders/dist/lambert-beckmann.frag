@@ -9,7 +9,8 @@ struct Light {
     vec3 position_or_direction;
     float intensity;
     vec3 color;
-    float attenuation;
+    // (constant, linear, quadratic, range), see the Attenuation docs
+    vec4 attenuation;
 };
 
 // User-defined uniforms
@@ -17,6 +18,15 @@ uniform sampler2D u_tex_diffuse;
 uniform sampler2D u_tex_normal;
 uniform float u_roughness;
 
+#ifdef ALPHA_CUTOFF
+uniform float u_alpha_cutoff;
+#endif
+
+#ifdef ENVIRONMENT_MAP
+uniform samplerCube u_environment_map;
+uniform float u_reflectivity;
+#endif
+
 // Lights
 
 uniform vec3 u_camera_position;
@@ -30,6 +40,9 @@ uniform Light u_point_lights[NUM_POINT_LIGHTS];
 #endif
 
 uniform vec4 u_ambiant_light;
+uniform vec3 u_hemisphere_sky_color;
+uniform vec3 u_hemisphere_ground_color;
+uniform vec3 u_hemisphere_up;
 
 // Varyings
 varying vec2 v_tex_coordinates;
@@ -66,6 +79,16 @@ vec4 light_value(vec3 light_direction, vec3 light_color, float light_intensity,
     return vec4(light_color*light_intensity,power);
 }
 
+float light_attenuation(vec4 attenuation, float light_distance) {
+    float falloff = 1.0 / max(attenuation.x + attenuation.y * light_distance + attenuation.z * light_distance * light_distance, 0.0001);
+    if (attenuation.w > 0.0) {
+        float ratio = light_distance / attenuation.w;
+        float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+        falloff *= window * window;
+    }
+    return falloff;
+}
+
 vec3 get_normal(){
     vec3 normal = texture2D(u_tex_normal,vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y)).rgb;
     normal = normalize(normal * 2.0 - 1.0);
@@ -76,8 +99,20 @@ void main() {
     vec3 normal = get_normal();
     vec3 view_direction = normalize(u_camera_position - v_position);
     vec4 diffuse = texture2D(u_tex_diffuse, vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y));
+#ifdef ALPHA_CUTOFF
+    if (diffuse.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+#ifdef ALPHA_HASH
+    if (diffuse.a < alpha_hash(gl_FragCoord.xy)) {
+        discard;
+    }
+    diffuse.a = 1.0;
+#endif
     vec3 computed_light_color = u_ambiant_light.rgb*u_ambiant_light.a;
     float total_intensity = u_ambiant_light.a;
+    computed_light_color += mix(u_hemisphere_ground_color, u_hemisphere_sky_color, dot(normal, u_hemisphere_up)*0.5+0.5);
 #if NUM_DIR_LIGHTS > 0
     for(int i = 0; i < NUM_DIR_LIGHTS; i++){
         vec4 dir_light = light_value(u_dir_lights[i].position_or_direction, u_dir_lights[i].color, u_dir_lights[i].intensity,normal,view_direction);
@@ -87,9 +122,15 @@ void main() {
 #if NUM_POINT_LIGHTS > 0
     for(int i = 0; i < NUM_POINT_LIGHTS; i++){
         vec3 direction = v_position - u_point_lights[i].position_or_direction;
-        vec4 point_light = light_value(direction, u_point_lights[i].color, u_point_lights[i].intensity,normal,view_direction);
+        float falloff = light_attenuation(u_point_lights[i].attenuation, length(direction));
+        vec4 point_light = light_value(direction, u_point_lights[i].color, u_point_lights[i].intensity*falloff,normal,view_direction);
         computed_light_color += point_light.rgb*point_light.a;
     }
 #endif
-    gl_FragColor = vec4(diffuse.rgb*computed_light_color,diffuse.a);
+    vec3 color = diffuse.rgb*computed_light_color;
+#ifdef ENVIRONMENT_MAP
+    vec3 reflection = reflect(-view_direction, normal);
+    color = mix(color, textureCube(u_environment_map, reflection).rgb, u_reflectivity);
+#endif
+    gl_FragColor = vec4(color,diffuse.a);
 }
\ No newline at end of file
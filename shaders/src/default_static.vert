@@ -5,10 +5,17 @@ attribute vec3 a_normal;
 attribute vec2 a_tex_coordinates;
 attribute vec3 a_tangent;
 
+#ifdef USE_VERTEX_AO
+// Baked with `Scene.bake_vertex_ao`
+attribute float a_ao;
+varying float v_ao;
+#endif
+
 uniform mat4 u_view_matrix;
 uniform mat4 u_projection_matrix;
 uniform mat4 u_world_transform;
 uniform mat4 u_transpose_inverse;
+uniform vec4 u_uv_transform;
 
 varying vec2 v_tex_coordinates;
 varying vec3 v_normal;
@@ -30,8 +37,12 @@ void main() {
     gl_Position = (u_projection_matrix * view_model_matrix) * a_position;
     vec4 position = (u_world_transform * a_position);
     v_position = position.xyz/position.w;
-    v_tex_coordinates = a_tex_coordinates;
+    vec2 uv_scale = u_uv_transform.zw == vec2(0.0) ? vec2(1.0) : u_uv_transform.zw;
+    v_tex_coordinates = a_tex_coordinates * uv_scale + u_uv_transform.xy;
     vec4 normal = u_world_transform * vec4(a_normal,1.0);
     v_normal = normal.xyz/normal.w;
     v_tbn_matrix = compute_tbn_matrix();
+#ifdef USE_VERTEX_AO
+    v_ao = a_ao;
+#endif
 }
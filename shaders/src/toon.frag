@@ -0,0 +1,40 @@
+precision mediump float;
+
+// Base pass of the toon example: cel shading with a few flat bands.
+// Use `toon_outline.vert` and `toon_outline.frag` as its second pass, with
+// `Scene.add_material_pass` and `Scene.set_material_cull_front_faces`.
+
+#define NUM_DIR_LIGHTS 0
+#define TOON_BANDS 3.0
+
+struct Light {
+    vec3 position_or_direction;
+    float intensity;
+    vec3 color;
+    vec4 attenuation;
+};
+
+uniform sampler2D u_tex_diffuse;
+
+#if NUM_DIR_LIGHTS > 0
+uniform Light u_dir_lights[NUM_DIR_LIGHTS];
+#endif
+
+uniform vec4 u_ambiant_light;
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_normal;
+
+void main() {
+    vec4 color = texture2D(u_tex_diffuse, vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y));
+    vec3 normal = normalize(v_normal);
+    vec3 light = u_ambiant_light.rgb * u_ambiant_light.a;
+#if NUM_DIR_LIGHTS > 0
+    for (int i = 0; i < NUM_DIR_LIGHTS; i++) {
+        float power = max(dot(normal, normalize(-u_dir_lights[i].position_or_direction)), 0.0);
+        power = ceil(power * TOON_BANDS) / TOON_BANDS;
+        light += u_dir_lights[i].color * u_dir_lights[i].intensity * power;
+    }
+#endif
+    gl_FragColor = vec4(color.rgb * light, color.a);
+}
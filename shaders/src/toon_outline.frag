@@ -0,0 +1,9 @@
+precision mediump float;
+
+// Outline pass of the toon example, with a flat color.
+
+uniform vec3 u_outline_color;
+
+void main() {
+    gl_FragColor = vec4(u_outline_color, 1.0);
+}
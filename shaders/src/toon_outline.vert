@@ -0,0 +1,17 @@
+// Outline pass of the toon example: an inverted hull, pushed along the normals.
+// The pass material must cull front faces.
+
+attribute vec4 a_position;
+attribute vec3 a_normal;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+uniform float u_outline_thickness;
+
+void main() {
+    vec4 position = u_world_transform * a_position;
+    vec3 normal = normalize((u_world_transform * vec4(a_normal, 0.0)).xyz);
+    position.xyz += normal * u_outline_thickness * position.w;
+    gl_Position = u_projection_matrix * u_view_matrix * position;
+}
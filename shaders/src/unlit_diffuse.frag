@@ -3,6 +3,22 @@ precision mediump float;
 uniform sampler2D u_tex_diffuse;
 varying vec2 v_tex_coordinates;
 
+#ifdef ALPHA_CUTOFF
+uniform float u_alpha_cutoff;
+#endif
+
 void main() {
-    gl_FragColor = texture2D(u_tex_diffuse, vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y));;
+    vec4 color = texture2D(u_tex_diffuse, vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y));
+#ifdef ALPHA_CUTOFF
+    if (color.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+#ifdef ALPHA_HASH
+    if (color.a < alpha_hash(gl_FragCoord.xy)) {
+        discard;
+    }
+    color.a = 1.0;
+#endif
+    gl_FragColor = color;
 }
\ No newline at end of file
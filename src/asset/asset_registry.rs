@@ -1,11 +1,18 @@
 //! Asset registry module
 
+use super::{Fallbacks, MemoryBudget};
+use crate::error::{W3DError, W3DErrorKind};
 use crate::renderer::MeshData;
-use crate::renderer::{Material, MaterialInstance};
+use crate::renderer::{
+    create_fallback_cube, create_fallback_material, create_fallback_texture, CubeTexture, Material,
+    MaterialInstance, FALLBACK_MATERIAL_ID, FALLBACK_MATERIAL_INSTANCE_ID, FALLBACK_MESH_DATA_ID,
+    FALLBACK_TEXTURE_ID, FALLBACK_TEXTURE_SIZE,
+};
+use crate::scene::FileType;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use web_sys::{HtmlImageElement, WebGlRenderingContext, WebGlTexture};
+use web_sys::{HtmlImageElement, ImageBitmap, WebGlRenderingContext, WebGlTexture};
 
 #[non_exhaustive]
 pub enum Asset {
@@ -13,9 +20,24 @@ pub enum Asset {
     Material(Rc<RefCell<Material>>),
     MaterialInstance(Rc<RefCell<MaterialInstance>>),
     Texture(Rc<WebGlTexture>),
+    CubeTexture(Rc<CubeTexture>),
     None,
 }
 
+/// A missing asset that was replaced by a fallback, and what referenced it.
+#[derive(Clone, PartialEq)]
+pub struct MissingAssetReference {
+    /// Id of the missing asset
+    pub id: String,
+
+    /// Type of the missing asset: `"mesh data"`, `"material"`, `"material instance"` or
+    /// `"texture"`
+    pub asset_type: &'static str,
+
+    /// What referenced it, e.g. `"material instance 'rock'"`
+    pub referenced_by: String,
+}
+
 /// Registry holding the `MeshData`, `Material`s, `MaterialInstance`s and Textures
 /// to be used by the renderer at render time.
 pub struct AssetRegistry {
@@ -26,6 +48,23 @@ pub struct AssetRegistry {
 
     /// Index linking each initial String ID to an internal usize ID.
     index: HashMap<String, usize>,
+
+    /// If `true`, mesh data registered from files keep a CPU copy of their positions and
+    /// indexes. `false` by default.
+    retain_cpu_data: bool,
+
+    /// If `true`, missing assets are errors instead of being replaced by fallbacks.
+    /// `false` by default.
+    strict: bool,
+
+    /// Missing assets replaced by fallbacks so far, without duplicates.
+    missing_assets: Vec<MissingAssetReference>,
+
+    /// Width and height of the textures, in pixels. Cube textures have the size of a face.
+    texture_sizes: HashMap<String, (u32, u32)>,
+
+    /// Memory limits and the state needed to evict mesh data, disabled by default.
+    memory_budget: MemoryBudget,
 }
 
 impl AssetRegistry {
@@ -34,55 +73,493 @@ impl AssetRegistry {
         AssetRegistry {
             assets: Vec::new(),
             index: HashMap::new(),
+            retain_cpu_data: false,
+            strict: false,
+            missing_assets: Vec::new(),
+            texture_sizes: HashMap::new(),
+            memory_budget: Default::default(),
         }
     }
 
-    /// Register mesh data from the byte array from a `MeshFile`
+    /// Register mesh data from the byte array from a `MeshFile`, under `id` if given or the
+    /// file's id otherwise. See `replace_or_push_asset` if the id is already registered.
     pub fn register_mesh_data(
         &mut self,
         context: &WebGlRenderingContext,
         wmesh_data: &[u8],
-    ) -> Result<String, String> {
-        let mesh_data_result = super::deserialize_wmesh(context, wmesh_data);
-        if let Ok(mesh_data) = mesh_data_result {
-            let id = mesh_data.get_id().to_owned();
-            self.index.insert(id.clone(), self.assets.len());
-            self.assets
-                .push(Asset::MeshData(Rc::new(RefCell::new(mesh_data))));
-            Ok(id)
+        id: Option<String>,
+        replace: bool,
+    ) -> Result<String, W3DError> {
+        let mut mesh_data = super::deserialize_wmesh(context, wmesh_data, self.retain_cpu_data)?;
+        if let Some(id) = id {
+            mesh_data.set_id(id);
+        }
+        let id = mesh_data.get_id().to_owned();
+        let index = self.replace_or_push_asset(
+            context,
+            id.clone(),
+            Asset::MeshData(Rc::new(RefCell::new(mesh_data))),
+            replace,
+        )?;
+        if self.memory_budget.is_enabled() {
+            self.memory_budget
+                .sources
+                .insert(index, wmesh_data.to_vec());
+            self.memory_budget.evicted.remove(&index);
+            self.enforce_memory_budget(context, Some(index));
+        }
+        Ok(id)
+    }
+
+    /// Sets the memory limits of the assets, in bytes, `None` for no limit. Only mesh data
+    /// registered from files while a limit is set can be evicted, see `MemoryBudget`.  
+    /// Evicts mesh data right away if the new GPU limit is exceeded.
+    pub fn set_memory_budget(
+        &mut self,
+        context: &WebGlRenderingContext,
+        gpu_bytes: Option<usize>,
+        cpu_bytes: Option<usize>,
+    ) -> () {
+        self.memory_budget.gpu_bytes = gpu_bytes;
+        self.memory_budget.cpu_bytes = cpu_bytes;
+        self.enforce_memory_budget(context, None);
+    }
+
+    /// Pins or unpins the asset registered under `id`: pinned assets are never evicted.  
+    /// Fails if no asset is registered under `id`.
+    pub fn set_pinned(&mut self, id: &str, pinned: bool) -> Result<(), W3DError> {
+        let index = self.get_id_from_str(id).ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Asset could not be found. Has it been registered yet?",
+                id,
+            )
+        })?;
+        if pinned {
+            self.memory_budget.pinned.insert(index);
         } else {
-            Err(String::from("Could not parse the mesh file!"))
+            self.memory_budget.pinned.remove(&index);
+        }
+        Ok(())
+    }
+
+    /// Starts a new frame for the least-recently-used bookkeeping.
+    pub fn begin_frame(&mut self) -> () {
+        self.memory_budget.frame += 1;
+    }
+
+    /// Marks the mesh data at `index` as drawn this frame, uploading its buffers again if
+    /// they were evicted, then evicts other mesh data if that exceeds the GPU limit.  
+    /// Fails if the mesh data can't be uploaded again.
+    pub fn use_mesh_data(
+        &mut self,
+        context: &WebGlRenderingContext,
+        index: usize,
+    ) -> Result<(), W3DError> {
+        let budget = &mut self.memory_budget;
+        budget.last_used.insert(index, budget.frame);
+        if !budget.evicted.contains(&index) {
+            return Ok(());
+        }
+        let mesh_data = match self.assets.get(index) {
+            Some(Asset::MeshData(mesh_data)) => mesh_data.clone(),
+            _ => return Ok(()),
+        };
+        let source = &self.memory_budget.sources[&index];
+        let uploaded = super::deserialize_wmesh(context, source, false)?;
+        mesh_data.borrow_mut().restore_buffers(uploaded);
+        self.memory_budget.evicted.remove(&index);
+        self.memory_budget.reconstructions += 1;
+        log_debug!(
+            "Mesh data {} was uploaded again.",
+            mesh_data.borrow().get_id()
+        );
+        self.enforce_memory_budget(context, None);
+        Ok(())
+    }
+
+    /// Returns the number of evictions and reconstructions since the last call.
+    pub fn take_memory_counters(&mut self) -> (u32, u32) {
+        self.memory_budget.take_counters()
+    }
+
+    /// Returns the memory used by the assets on the GPU and on the CPU, in bytes. Textures
+    /// are counted as uncompressed RGBA without mipmaps, and evicted mesh data as free on
+    /// the GPU.
+    pub fn get_memory_usage(&self) -> (usize, usize) {
+        let mut gpu_bytes = 0;
+        let mut cpu_bytes: usize = self.memory_budget.sources.values().map(Vec::len).sum();
+        for (id, index) in &self.index {
+            match &self.assets[*index] {
+                Asset::MeshData(mesh_data) => {
+                    let mesh_data = mesh_data.borrow();
+                    if !self.memory_budget.evicted.contains(index) {
+                        gpu_bytes += mesh_data.get_gpu_byte_size();
+                    }
+                    cpu_bytes += mesh_data
+                        .get_cpu_data()
+                        .map_or(0, |data| data.get_byte_size());
+                }
+                Asset::Texture(_) | Asset::CubeTexture(_) => {
+                    let faces = match &self.assets[*index] {
+                        Asset::CubeTexture(_) => 6,
+                        _ => 1,
+                    };
+                    if let Some((width, height)) = self.texture_sizes.get(id) {
+                        gpu_bytes += faces * *width as usize * *height as usize * 4;
+                    }
+                }
+                _ => {}
+            }
+        }
+        (gpu_bytes, cpu_bytes)
+    }
+
+    /// Evicts the least recently used mesh data until the GPU limit is honored, sparing
+    /// `spared`, and warns if a limit can't be honored.
+    fn enforce_memory_budget(&mut self, context: &WebGlRenderingContext, spared: Option<usize>) {
+        let (mut gpu_bytes, cpu_bytes) = self.get_memory_usage();
+        if let Some(limit) = self.memory_budget.gpu_bytes {
+            while gpu_bytes > limit {
+                let budget = &self.memory_budget;
+                let candidate = budget
+                    .sources
+                    .keys()
+                    .filter(|index| Some(**index) != spared && budget.is_evictable(**index))
+                    .min_by_key(|index| budget.get_last_used(**index))
+                    .cloned();
+                let (index, mesh_data) = match candidate.map(|index| (index, &self.assets[index])) {
+                    Some((index, Asset::MeshData(mesh_data))) => (index, mesh_data.clone()),
+                    _ => {
+                        warn_throttled!(
+                            5000,
+                            "The GPU memory budget is exceeded ({} > {} bytes) and nothing \
+                             else can be evicted.",
+                            gpu_bytes,
+                            limit
+                        );
+                        break;
+                    }
+                };
+                gpu_bytes -= mesh_data.borrow().get_gpu_byte_size();
+                mesh_data.borrow_mut().evict_buffers(context);
+                self.memory_budget.evicted.insert(index);
+                self.memory_budget.evictions += 1;
+                log_debug!("Mesh data {} was evicted.", mesh_data.borrow().get_id());
+            }
+        }
+        if let Some(limit) = self.memory_budget.cpu_bytes {
+            if cpu_bytes > limit {
+                warn_throttled!(
+                    5000,
+                    "The CPU memory budget is exceeded ({} > {} bytes).",
+                    cpu_bytes,
+                    limit
+                );
+            }
+        }
+    }
+
+    /// Sets whether mesh data registered from now on keep a CPU copy of their positions and
+    /// indexes, needed to read their triangles.
+    pub fn set_retain_cpu_data(&mut self, retain_cpu_data: bool) -> () {
+        self.retain_cpu_data = retain_cpu_data;
+    }
+
+    /// Register a material from the byte array of a `MaterialFile`, under `id` if given or
+    /// the file's id otherwise. See `replace_or_push_asset` if the id is already registered.
+    pub fn register_material(
+        &mut self,
+        context: &WebGlRenderingContext,
+        wmaterial_data: &[u8],
+        id: Option<String>,
+        replace: bool,
+    ) -> Result<String, W3DError> {
+        let mut fallbacks = self.get_fallbacks(context)?;
+        let mut material = super::deserialize_wmaterial(&self, wmaterial_data, &mut fallbacks)?;
+        if let Some(id) = id {
+            material.set_id(id);
+        }
+        let id = material.get_id().to_owned();
+        self.record_fallbacks(fallbacks, &format!("material '{}'", id));
+        self.replace_or_push_asset(
+            context,
+            id.clone(),
+            Asset::Material(Rc::new(RefCell::new(material))),
+            replace,
+        )?;
+        Ok(id)
+    }
+
+    /// Register a material isntance from the byte array of a `MaterialInstanceFile`, under
+    /// `id` if given or the file's id otherwise. See `replace_or_push_asset` if the id is
+    /// already registered.
+    pub fn register_material_instance(
+        &mut self,
+        context: &WebGlRenderingContext,
+        wmaterial_data: &[u8],
+        id: Option<String>,
+        replace: bool,
+    ) -> Result<String, W3DError> {
+        let mut fallbacks = self.get_fallbacks(context)?;
+        let mut matinstance =
+            super::deserialize_wmatinstance(&self, wmaterial_data, &mut fallbacks)?;
+        if let Some(id) = id {
+            matinstance.set_id(id);
+        }
+        let id = matinstance.get_id().to_owned();
+        self.record_fallbacks(fallbacks, &format!("material instance '{}'", id));
+        self.replace_or_push_asset(
+            context,
+            id.clone(),
+            Asset::MaterialInstance(Rc::new(RefCell::new(matinstance))),
+            replace,
+        )?;
+        Ok(id)
+    }
+
+    /// Sets whether missing assets are errors, as they used to be, instead of being replaced
+    /// by fallbacks. Useful to make automated tests fail on broken references.
+    pub fn set_strict(&mut self, strict: bool) -> () {
+        self.strict = strict;
+    }
+
+    /// Returns the missing assets replaced by fallbacks so far.
+    pub fn get_missing_assets(&self) -> &[MissingAssetReference] {
+        &self.missing_assets
+    }
+
+    /// Returns the index of the mesh data registered under `id`.  
+    /// If there is none, returns the fallback cube's instead and records the missing asset,
+    /// unless the registry is strict, in which case it fails.
+    pub fn resolve_mesh_data(
+        &mut self,
+        context: &WebGlRenderingContext,
+        id: &str,
+        referenced_by: &str,
+    ) -> Result<usize, W3DError> {
+        if self.get_mesh_data(id).is_some() {
+            return Ok(self.index[id]);
+        }
+        if self.strict {
+            return Err(W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Mesh data could not be found. Has it been registered yet?",
+                id,
+            ));
+        }
+        self.record_missing_asset(id, "mesh data", referenced_by);
+        match self.get_id_from_str(FALLBACK_MESH_DATA_ID) {
+            Some(index) => Ok(index),
+            None => Ok(self.add_mesh_data(create_fallback_cube(context)?)),
+        }
+    }
+
+    /// Returns the indexes of the material instance registered under `id` and of its parent
+    /// material.  
+    /// If there is none, returns the fallback material instance's instead and records the
+    /// missing asset, unless the registry is strict, in which case it fails.
+    pub fn resolve_material_instance(
+        &mut self,
+        id: &str,
+        referenced_by: &str,
+    ) -> Result<(usize, usize), W3DError> {
+        if let Some(material_instance) = self.get_material_instance(id) {
+            let parent_material = material_instance.borrow().get_parent().clone();
+            let parent_index = self.get_id_from_str(parent_material.borrow().get_id());
+            return match parent_index {
+                Some(parent_index) => Ok((self.index[id], parent_index)),
+                None => Err(W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "The parent material of this material instance is not registered.",
+                    id,
+                )),
+            };
         }
+        if self.strict {
+            return Err(W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Material instance could not be found. Has it been registered yet?",
+                id,
+            ));
+        }
+        self.record_missing_asset(id, "material instance", referenced_by);
+        let material = self.get_fallback_material();
+        let material_index = self.index[FALLBACK_MATERIAL_ID];
+        let instance_index = match self.get_id_from_str(FALLBACK_MATERIAL_INSTANCE_ID) {
+            Some(index) => index,
+            None => self.add_material_instance(MaterialInstance::new(
+                material,
+                FALLBACK_MATERIAL_INSTANCE_ID,
+            )),
+        };
+        Ok((instance_index, material_index))
+    }
+
+    /// Returns the fallbacks to deserialize material and material instance files with: none
+    /// if the registry is strict. The fallback texture is registered the first time.
+    fn get_fallbacks(&mut self, context: &WebGlRenderingContext) -> Result<Fallbacks, W3DError> {
+        if self.strict {
+            return Ok(Fallbacks::none());
+        }
+        let texture = self.get_fallback_texture(context)?;
+        // Only registered if used, so that it isn't compiled needlessly.
+        let material = self
+            .get_material(FALLBACK_MATERIAL_ID)
+            .unwrap_or_else(|| Rc::new(RefCell::new(create_fallback_material())));
+        Ok(Fallbacks {
+            texture: Some(texture),
+            material: Some(material),
+            replaced: Vec::new(),
+        })
+    }
+
+    /// Registers the fallback texture that missing textures referenced by id are drawn
+    /// with, unless the registry is strict or it is already registered.
+    pub fn prepare_fallback_texture(
+        &mut self,
+        context: &WebGlRenderingContext,
+    ) -> Result<(), W3DError> {
+        if !self.strict {
+            self.get_fallback_texture(context)?;
+        }
+        Ok(())
     }
 
-    /// Register a material from the byte array of a `MaterialFile`
-    pub fn register_material(&mut self, wmaterial_data: &[u8]) -> Result<String, String> {
-        let mat_data_result = super::deserialize_wmaterial(&self, wmaterial_data);
-        match mat_data_result {
-            Ok(material) => {
-                let id = material.get_id().to_owned();
-                self.index.insert(id.clone(), self.assets.len());
-                self.assets
-                    .push(Asset::Material(Rc::new(RefCell::new(material))));
-                Ok(id)
+    /// Returns the fallback texture, registering it the first time.
+    fn get_fallback_texture(
+        &mut self,
+        context: &WebGlRenderingContext,
+    ) -> Result<Rc<WebGlTexture>, W3DError> {
+        if let Some(texture) = self.get_texture(FALLBACK_TEXTURE_ID) {
+            return Ok(texture);
+        }
+        let texture = Rc::new(create_fallback_texture(context)?);
+        let size = FALLBACK_TEXTURE_SIZE as u32;
+        self.texture_sizes
+            .insert(FALLBACK_TEXTURE_ID.to_owned(), (size, size));
+        self.push_asset(
+            FALLBACK_TEXTURE_ID.to_owned(),
+            Asset::Texture(texture.clone()),
+        );
+        Ok(texture)
+    }
+
+    /// Records the assets replaced while deserializing a file, and registers the fallback
+    /// material if it was used.
+    fn record_fallbacks(&mut self, fallbacks: Fallbacks, referenced_by: &str) -> () {
+        for (id, asset_type) in &fallbacks.replaced {
+            if *asset_type == "material" && self.get_id_from_str(FALLBACK_MATERIAL_ID).is_none() {
+                if let Some(material) = &fallbacks.material {
+                    self.push_asset(
+                        FALLBACK_MATERIAL_ID.to_owned(),
+                        Asset::Material(material.clone()),
+                    );
+                }
             }
-            Err(message) => Err(message),
+            self.record_missing_asset(id, asset_type, referenced_by);
         }
     }
 
-    /// Register a material isntance from the byte array of a `MaterialInstanceFile`
-    pub fn register_material_instance(&mut self, wmaterial_data: &[u8]) -> Result<String, String> {
-        let mat_data_result = super::deserialize_wmatinstance(&self, wmaterial_data);
-        match mat_data_result {
-            Ok(matinstance) => {
-                let id = matinstance.get_id().to_owned();
-                self.index.insert(id.clone(), self.assets.len());
-                self.assets
-                    .push(Asset::MaterialInstance(Rc::new(RefCell::new(matinstance))));
-                Ok(id)
+    /// Returns the fallback material, registering it the first time.
+    fn get_fallback_material(&mut self) -> Rc<RefCell<Material>> {
+        match self.get_material(FALLBACK_MATERIAL_ID) {
+            Some(material) => material,
+            None => {
+                let index = self.add_material(create_fallback_material());
+                self.get_material_with_index(index).unwrap()
+            }
+        }
+    }
+
+    /// Adds a missing asset to the report, logging a warning the first time it's referenced
+    /// from a given place.
+    fn record_missing_asset(&mut self, id: &str, asset_type: &'static str, referenced_by: &str) {
+        let reference = MissingAssetReference {
+            id: id.to_owned(),
+            asset_type: asset_type,
+            referenced_by: referenced_by.to_owned(),
+        };
+        if !self.missing_assets.contains(&reference) {
+            log_warn!(
+                "Missing {} '{}', referenced by {}, was replaced by a fallback.",
+                asset_type,
+                id,
+                referenced_by
+            );
+            self.missing_assets.push(reference);
+        }
+    }
+
+    /// Registers an asset under `id`. If an asset of the same type is already registered
+    /// under it and `replace` is `true`, it is replaced in place: its GPU resources are
+    /// deleted and the new data is moved into the existing `Rc`, so that entities, material
+    /// instances and mesh data referencing it pick up the new version on the next frame,
+    /// looking up their locations again. Material instances of a replaced material keep it
+    /// as their parent.  
+    /// Fails if `id` is already registered and `replace` is `false`, or if it is used by an
+    /// asset of another type.
+    fn replace_or_push_asset(
+        &mut self,
+        context: &WebGlRenderingContext,
+        id: String,
+        asset: Asset,
+        replace: bool,
+    ) -> Result<usize, W3DError> {
+        let index = match self.index.get(&id) {
+            Some(index) => *index,
+            None => return Ok(self.push_asset(id, asset)),
+        };
+        if !replace {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "An asset is already registered under this id. Use `replace_asset` to replace it.",
+                &id,
+            ));
+        }
+        match (&self.assets[index], asset) {
+            (Asset::MeshData(current), Asset::MeshData(new)) => {
+                current.borrow().delete_buffers(context);
+                current.swap(&new);
+            }
+            (Asset::Material(current), Asset::Material(new)) => {
+                current.borrow_mut().delete_program(context);
+                current.swap(&new);
+                // The new material has yet to learn the attribute locations of mesh data.
+                for asset in &self.assets {
+                    if let Asset::MeshData(mesh_data) = asset {
+                        mesh_data.borrow_mut().forget_lookup(&id);
+                    }
+                }
+            }
+            (Asset::MaterialInstance(current), Asset::MaterialInstance(new)) => {
+                current.swap(&new);
+            }
+            _ => {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::InvalidArgument,
+                    "This id is already used by another type of asset.",
+                    &id,
+                ))
             }
-            Err(message) => Err(message),
         }
+        Ok(index)
+    }
+
+    /// Returns the ids of every registered asset of a type, in registration order.
+    pub fn get_ids(&self, file_type: &FileType) -> Vec<String> {
+        let mut ids: Vec<(&String, &usize)> = self
+            .index
+            .iter()
+            .filter(|(_, index)| match (&self.assets[**index], file_type) {
+                (Asset::MeshData(_), FileType::WMesh) => true,
+                (Asset::Material(_), FileType::WMaterial) => true,
+                (Asset::MaterialInstance(_), FileType::WMatInstance) => true,
+                _ => false,
+            })
+            .collect();
+        ids.sort_by_key(|(_, index)| **index);
+        ids.into_iter().map(|(id, _)| id.clone()).collect()
     }
 
     /// Register a new texture from an Image reference
@@ -91,9 +568,13 @@ impl AssetRegistry {
         context: &WebGlRenderingContext,
         image: &HtmlImageElement,
         id: String,
-    ) -> Result<String, String> {
+    ) -> Result<String, W3DError> {
         match context.create_texture() {
-            None => Err(String::from("Could not create texture")),
+            None => Err(W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Could not create texture",
+                &id,
+            )),
             Some(texture) => {
                 context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
                 let res = context.tex_image_2d_with_u32_and_u32_and_image(
@@ -105,8 +586,14 @@ impl AssetRegistry {
                     image,
                 );
                 match res {
-                    Err(_) => Err(String::from("Texture binding failed.")),
+                    Err(_) => Err(W3DError::with_source(
+                        W3DErrorKind::GlResource,
+                        "Texture upload failed.",
+                        &id,
+                    )),
                     Ok(_) => {
+                        self.texture_sizes
+                            .insert(id.clone(), (image.natural_width(), image.natural_height()));
                         self.index.insert(id.clone(), self.assets.len());
                         self.assets.push(Asset::Texture(Rc::new(texture)));
                         Ok(id)
@@ -116,6 +603,347 @@ impl AssetRegistry {
         }
     }
 
+    /// Registers a 1x1 opaque white texture under `id`, sampled until the image decoded for
+    /// it is uploaded by `upload_texture_bitmap`.
+    pub fn register_placeholder_texture(
+        &mut self,
+        context: &WebGlRenderingContext,
+        id: String,
+    ) -> Result<String, W3DError> {
+        let texture = context.create_texture().ok_or_else(|| {
+            W3DError::with_source(W3DErrorKind::GlResource, "Could not create texture", &id)
+        })?;
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
+        let result = context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGlRenderingContext::TEXTURE_2D,
+                0,
+                WebGlRenderingContext::RGBA as i32,
+                1,
+                1,
+                0,
+                WebGlRenderingContext::RGBA,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                Some(&[255, 255, 255, 255]),
+            );
+        if result.is_err() {
+            context.delete_texture(Some(&texture));
+            return Err(W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Texture upload failed.",
+                &id,
+            ));
+        }
+        self.texture_sizes.insert(id.clone(), (1, 1));
+        self.index.insert(id.clone(), self.assets.len());
+        self.assets.push(Asset::Texture(Rc::new(texture)));
+        Ok(id)
+    }
+
+    /// Uploads a decoded image to the texture registered under `id`, replacing its content
+    /// for every material instance using it.
+    pub fn upload_texture_bitmap(
+        &mut self,
+        context: &WebGlRenderingContext,
+        id: &str,
+        bitmap: &ImageBitmap,
+    ) -> Result<(), W3DError> {
+        let texture = self.get_texture(id).ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Texture could not be found. Was it unregistered while decoding?",
+                id,
+            )
+        })?;
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
+        context
+            .tex_image_2d_with_u32_and_u32_and_image_bitmap(
+                WebGlRenderingContext::TEXTURE_2D,
+                0,
+                WebGlRenderingContext::RGBA as i32,
+                WebGlRenderingContext::RGBA,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                bitmap,
+            )
+            .map_err(|_| {
+                W3DError::with_source(W3DErrorKind::GlResource, "Texture upload failed.", id)
+            })?;
+        self.texture_sizes
+            .insert(id.to_owned(), (bitmap.width(), bitmap.height()));
+        Ok(())
+    }
+
+    /// Register a new cube texture from the images of its six faces, in the order
+    /// +X, -X, +Y, -Y, +Z, -Z.
+    pub fn register_cube_texture(
+        &mut self,
+        context: &WebGlRenderingContext,
+        faces: &[HtmlImageElement],
+        id: String,
+    ) -> Result<String, W3DError> {
+        if faces.len() != 6 {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "A cube texture needs exactly six faces.",
+                &id,
+            ));
+        }
+        let texture = context.create_texture().ok_or_else(|| {
+            W3DError::with_source(W3DErrorKind::GlResource, "Could not create texture", &id)
+        })?;
+        context.bind_texture(WebGlRenderingContext::TEXTURE_CUBE_MAP, Some(&texture));
+        for (face_index, image) in faces.iter().enumerate() {
+            let res = context.tex_image_2d_with_u32_and_u32_and_image(
+                WebGlRenderingContext::TEXTURE_CUBE_MAP_POSITIVE_X + face_index as u32,
+                0,
+                WebGlRenderingContext::RGBA as i32,
+                WebGlRenderingContext::RGBA,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                image,
+            );
+            if res.is_err() {
+                context.delete_texture(Some(&texture));
+                return Err(W3DError::with_source(
+                    W3DErrorKind::GlResource,
+                    "Cube texture upload failed.",
+                    &id,
+                ));
+            }
+        }
+        for (parameter, value) in &[
+            (
+                WebGlRenderingContext::TEXTURE_MAG_FILTER,
+                WebGlRenderingContext::LINEAR,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_MIN_FILTER,
+                WebGlRenderingContext::LINEAR,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_WRAP_S,
+                WebGlRenderingContext::CLAMP_TO_EDGE,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_WRAP_T,
+                WebGlRenderingContext::CLAMP_TO_EDGE,
+            ),
+        ] {
+            context.tex_parameteri(
+                WebGlRenderingContext::TEXTURE_CUBE_MAP,
+                *parameter,
+                *value as i32,
+            );
+        }
+        self.texture_sizes.insert(
+            id.clone(),
+            (faces[0].natural_width(), faces[0].natural_height()),
+        );
+        self.push_asset(
+            id.clone(),
+            Asset::CubeTexture(Rc::new(CubeTexture(texture))),
+        );
+        Ok(id)
+    }
+
+    /// Register `MeshData` built at runtime rather than loaded from a file.  
+    /// Returns its index in the registry.
+    pub fn add_mesh_data(&mut self, mesh_data: MeshData) -> usize {
+        let id = mesh_data.get_id().to_owned();
+        self.push_asset(id, Asset::MeshData(Rc::new(RefCell::new(mesh_data))))
+    }
+
+    /// Register a texture created at runtime, like the color texture of a render target, of
+    /// `size` pixels. Returns its index in the registry.
+    pub fn add_texture(
+        &mut self,
+        id: String,
+        texture: Rc<WebGlTexture>,
+        size: (u32, u32),
+    ) -> usize {
+        self.texture_sizes.insert(id.clone(), size);
+        self.push_asset(id, Asset::Texture(texture))
+    }
+
+    /// Unregisters the texture registered under `id` and deletes it.
+    /// Returns `false` if there is none.
+    pub fn remove_texture(&mut self, context: &WebGlRenderingContext, id: &str) -> bool {
+        match (self.index.get(id).cloned(), self.get_texture(id)) {
+            (Some(index), Some(_)) => {
+                self.remove_asset(context, id, index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Register a `Material` built at runtime rather than loaded from a file.  
+    /// Returns its index in the registry.
+    pub fn add_material(&mut self, material: Material) -> usize {
+        let id = material.get_id().to_owned();
+        self.push_asset(id, Asset::Material(Rc::new(RefCell::new(material))))
+    }
+
+    /// Register a `MaterialInstance` built at runtime rather than loaded from a file.  
+    /// Returns its index in the registry.
+    pub fn add_material_instance(&mut self, material_instance: MaterialInstance) -> usize {
+        let id = material_instance.get_id().to_owned();
+        self.push_asset(
+            id,
+            Asset::MaterialInstance(Rc::new(RefCell::new(material_instance))),
+        )
+    }
+
+    /// Registers `count` material instances of the material registered under `parent_id`,
+    /// with no uniforms of their own, at consecutive indexes. Their ids are the parent's
+    /// followed by `#` and their index. Returns the ids.  
+    /// Fails if the material isn't registered, or if one of the ids is already used.
+    pub fn add_material_instance_pool(
+        &mut self,
+        parent_id: &str,
+        count: usize,
+    ) -> Result<Vec<String>, W3DError> {
+        let parent = self.get_material(parent_id).ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Material instances can't be created for a material that is not registered.",
+                parent_id,
+            )
+        })?;
+        let first_index = self.assets.len();
+        let ids: Vec<String> = (first_index..first_index + count)
+            .map(|index| format!("{}#{}", parent_id, index))
+            .collect();
+        if let Some(id) = ids.iter().find(|id| self.index.contains_key(*id)) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The id of a pooled material instance is already used.",
+                id,
+            ));
+        }
+        self.assets.reserve(count);
+        for id in &ids {
+            self.add_material_instance(MaterialInstance::new(parent.clone(), id));
+        }
+        Ok(ids)
+    }
+
+    fn push_asset(&mut self, id: String, asset: Asset) -> usize {
+        let index = self.assets.len();
+        self.index.insert(id, index);
+        self.assets.push(asset);
+        index
+    }
+
+    /// Removes the unused assets for which `is_collectable` returns `true`, given their index,
+    /// and deletes their GPU resources. Material instances are collected, then if
+    /// `all_types` is `true` the materials, mesh data, textures and cube textures they no
+    /// longer hold.  
+    /// Pinned assets are kept, as are the ones something still holds: materials parenting a
+    /// material instance or used as a pass, textures held by a material, material instance
+    /// or the renderer, or referenced by id by a material instance.  
+    /// Returns the `(id, type)` of the removed assets, in removal order.
+    pub fn collect_assets<F>(
+        &mut self,
+        context: &WebGlRenderingContext,
+        all_types: bool,
+        mut is_collectable: F,
+    ) -> Vec<(String, &'static str)>
+    where
+        F: FnMut(usize) -> bool,
+    {
+        let mut removed = Vec::new();
+        let asset_types: &[&'static str] = if all_types {
+            &["material instance", "material", "mesh data", "texture"]
+        } else {
+            &["material instance"]
+        };
+        for asset_type in asset_types {
+            let passes: Vec<usize> = self
+                .get_all_materials()
+                .iter()
+                .flat_map(|material| material.borrow().get_passes().to_vec())
+                .collect();
+            let texture_references: Vec<String> = self
+                .get_all_material_instances()
+                .iter()
+                .flat_map(|material_instance| {
+                    material_instance
+                        .borrow()
+                        .get_texture_references()
+                        .into_iter()
+                        .map(|(_, id)| id.to_owned())
+                        .collect::<Vec<String>>()
+                })
+                .collect();
+            let mut ids: Vec<(&String, &usize)> = self.index.iter().collect();
+            ids.sort_by_key(|(_, index)| **index);
+            let candidates: Vec<(String, usize)> = ids
+                .into_iter()
+                .filter(|(id, index)| {
+                    let unheld = match (&self.assets[**index], *asset_type) {
+                        (Asset::MaterialInstance(rc), "material instance") => {
+                            Rc::strong_count(rc) == 1
+                        }
+                        (Asset::Material(rc), "material") => {
+                            Rc::strong_count(rc) == 1 && !passes.contains(index)
+                        }
+                        (Asset::MeshData(rc), "mesh data") => Rc::strong_count(rc) == 1,
+                        (Asset::Texture(rc), "texture") => {
+                            Rc::strong_count(rc) == 1 && !texture_references.contains(id)
+                        }
+                        (Asset::CubeTexture(rc), "texture") => Rc::strong_count(rc) == 1,
+                        _ => false,
+                    };
+                    unheld && !self.memory_budget.pinned.contains(index)
+                })
+                .map(|(id, index)| (id.clone(), *index))
+                .collect();
+            for (id, index) in candidates {
+                if is_collectable(index) {
+                    self.remove_asset(context, &id, index);
+                    removed.push((id, *asset_type));
+                }
+            }
+        }
+        removed
+    }
+
+    /// Removes the asset at `index`, registered under `id`, and deletes its GPU resources.
+    /// Its index is never reused, so that stale references find nothing.
+    fn remove_asset(&mut self, context: &WebGlRenderingContext, id: &str, index: usize) -> () {
+        match &self.assets[index] {
+            Asset::MeshData(mesh_data) => mesh_data.borrow().delete_buffers(context),
+            Asset::Material(material) => material.borrow_mut().delete_program(context),
+            Asset::Texture(texture) => context.delete_texture(Some(texture)),
+            Asset::CubeTexture(texture) => context.delete_texture(Some(&texture.0)),
+            Asset::MaterialInstance(_) | Asset::None => {}
+        }
+        self.assets[index] = Asset::None;
+        self.index.remove(id);
+        self.texture_sizes.remove(id);
+        let budget = &mut self.memory_budget;
+        budget.sources.remove(&index);
+        budget.last_used.remove(&index);
+        budget.evicted.remove(&index);
+    }
+
+    /// Returns every registered asset with its id, in registration order.
+    pub fn get_registered_assets(&self) -> Vec<(&str, &Asset)> {
+        let mut assets: Vec<(&str, &Asset)> = self
+            .index
+            .iter()
+            .map(|(id, index)| (id.as_str(), &self.assets[*index]))
+            .collect();
+        assets.sort_by_key(|(id, _)| self.index[*id]);
+        assets
+    }
+
+    /// Returns the width and height of the texture or cube texture registered under `id`,
+    /// in pixels.
+    pub fn get_texture_size(&self, id: &str) -> Option<(u32, u32)> {
+        self.texture_sizes.get(id).cloned()
+    }
+
     pub fn get_id_from_str(&self, str_id: &str) -> Option<usize> {
         self.index.get(str_id).map(|id| id.to_owned())
     }
@@ -155,6 +983,35 @@ impl AssetRegistry {
         }
     }
 
+    pub fn get_cube_texture(&self, id: &str) -> Option<Rc<CubeTexture>> {
+        match self.get_asset(id) {
+            Asset::CubeTexture(rc) => Some(rc.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns every registered `Material`, in registration order.
+    pub fn get_all_materials(&self) -> Vec<Rc<RefCell<Material>>> {
+        self.assets
+            .iter()
+            .filter_map(|asset| match asset {
+                Asset::Material(rc) => Some(rc.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns every registered `MaterialInstance`, in registration order.
+    pub fn get_all_material_instances(&self) -> Vec<Rc<RefCell<MaterialInstance>>> {
+        self.assets
+            .iter()
+            .filter_map(|asset| match asset {
+                Asset::MaterialInstance(rc) => Some(rc.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn get_mesh_data_with_index(&self, id: usize) -> Option<Rc<RefCell<MeshData>>> {
         if id < self.assets.len() {
             match &self.assets[id] {
@@ -214,3 +1071,72 @@ impl AssetRegistry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen::{JsCast, JsValue};
+
+    /// Context that must not be called: materials without a program don't need it.
+    fn context() -> WebGlRenderingContext {
+        JsValue::NULL.unchecked_into()
+    }
+
+    fn material_asset(vertex_shader: &str) -> Asset {
+        let material = Material::new(vertex_shader, "", "material");
+        Asset::Material(Rc::new(RefCell::new(material)))
+    }
+
+    fn register(
+        registry: &mut AssetRegistry,
+        vertex_shader: &str,
+        replace: bool,
+    ) -> Result<usize, W3DError> {
+        registry.replace_or_push_asset(
+            &context(),
+            "material".to_owned(),
+            material_asset(vertex_shader),
+            replace,
+        )
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected_unless_replacing() {
+        let mut registry = AssetRegistry::new();
+        let index = register(&mut registry, "first", false).unwrap();
+        let error = register(&mut registry, "second", false).unwrap_err();
+        assert_eq!(error.get_kind(), W3DErrorKind::InvalidArgument);
+        assert_eq!(error.get_source(), Some("material"));
+        assert_eq!(register(&mut registry, "second", true).unwrap(), index);
+        assert_eq!(register(&mut registry, "third", true).unwrap(), index);
+        let material = registry.get_material("material").unwrap();
+        assert_eq!(material.borrow().get_shader_sources().0, "third");
+        assert_eq!(registry.get_ids(&FileType::WMaterial).len(), 1);
+    }
+
+    #[test]
+    fn material_instance_survives_parent_replacement() {
+        let mut registry = AssetRegistry::new();
+        register(&mut registry, "first", false).unwrap();
+        let parent = registry.get_material("material").unwrap();
+        registry.add_material_instance(MaterialInstance::new(parent, "instance"));
+        register(&mut registry, "second", true).unwrap();
+        let instance = registry.get_material_instance("instance").unwrap();
+        let instance = instance.borrow();
+        let parent = instance.get_parent().borrow();
+        assert_eq!(parent.get_shader_sources().0, "second");
+        assert_eq!(instance.get_parent_id(), "material");
+    }
+
+    #[test]
+    fn id_used_by_another_asset_type_is_rejected() {
+        let mut registry = AssetRegistry::new();
+        let instance = MaterialInstance::new(
+            Rc::new(RefCell::new(Material::new("", "", "a"))),
+            "material",
+        );
+        registry.add_material_instance(instance);
+        let error = register(&mut registry, "first", true).unwrap_err();
+        assert_eq!(error.get_kind(), W3DErrorKind::InvalidArgument);
+    }
+}
@@ -0,0 +1,90 @@
+//! Regions of a texture atlas, in normalized texture coordinates.
+
+use nalgebra::Vector4;
+
+/// ## AtlasRegion
+///
+/// Rectangle of a texture, in texture coordinates between 0 and 1, `(0, 0)` being the
+/// top-left corner of the image. Lets many sprites or material instances share one atlas
+/// texture, and thus one texture bind.
+///
+/// Its four coordinates convert to and from a `[f32; 4]`, which is how regions are stored
+/// next to the texture metadata they belong to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// Left edge
+    pub u0: f32,
+
+    /// Top edge
+    pub v0: f32,
+
+    /// Right edge
+    pub u1: f32,
+
+    /// Bottom edge
+    pub v1: f32,
+}
+
+impl AtlasRegion {
+    /// Constructor from normalized texture coordinates.
+    pub fn new(u0: f32, v0: f32, u1: f32, v1: f32) -> AtlasRegion {
+        AtlasRegion {
+            u0: u0,
+            v0: v0,
+            u1: u1,
+            v1: v1,
+        }
+    }
+
+    /// Region covering the whole texture.
+    pub fn full() -> AtlasRegion {
+        AtlasRegion::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    /// Constructor from a rectangle in pixels, `(x, y)` being its top-left corner, in a
+    /// texture of `texture_width` by `texture_height` pixels.
+    pub fn from_pixels(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        texture_width: f32,
+        texture_height: f32,
+    ) -> AtlasRegion {
+        AtlasRegion::new(
+            x / texture_width,
+            y / texture_height,
+            (x + width) / texture_width,
+            (y + height) / texture_height,
+        )
+    }
+
+    /// Returns the value of the `u_uv_transform` uniform mapping the `[0, 1]` texture
+    /// coordinates of a mesh to this region: `(offset.x, offset.y, scale.x, scale.y)`.
+    pub fn to_uv_transform(&self) -> Vector4<f32> {
+        Vector4::new(self.u0, self.v0, self.u1 - self.u0, self.v1 - self.v0)
+    }
+}
+
+impl Default for AtlasRegion {
+    fn default() -> AtlasRegion {
+        AtlasRegion::full()
+    }
+}
+
+impl From<[f32; 4]> for AtlasRegion {
+    fn from(coordinates: [f32; 4]) -> AtlasRegion {
+        AtlasRegion::new(
+            coordinates[0],
+            coordinates[1],
+            coordinates[2],
+            coordinates[3],
+        )
+    }
+}
+
+impl From<AtlasRegion> for [f32; 4] {
+    fn from(region: AtlasRegion) -> [f32; 4] {
+        [region.u0, region.v0, region.u1, region.v1]
+    }
+}
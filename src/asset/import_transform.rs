@@ -0,0 +1,338 @@
+//! Rotation, scaling, recentering and texture coordinate flipping of mesh files at import,
+//! for assets authored with other conventions.
+
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::Aabb;
+use crate::utils::constants::{NORMAL_BUFFER_NAME, UV_BUFFER_NAME, VERTEX_BUFFER_NAME};
+use crate::utils::{ImportOptions, PivotMode, RotationOrder};
+use bincode::{deserialize, serialize};
+use nalgebra::{UnitQuaternion, Vector3};
+use wtvr3d_file::{FileValue, MeshFile};
+
+/// Tangent buffer name used in built-in shaders
+const TANGENT_BUFFER_NAME: &str = "a_tangent";
+
+/// Applies the vertex transforms of `options` to a mesh file, and returns the converted
+/// file, or `None` if the options leave the vertices unchanged.
+/// Fails if the file can't be deserialized or if `options.uniform_scale` isn't positive.
+pub fn transform_mesh_file(
+    data: &[u8],
+    options: &ImportOptions,
+) -> Result<Option<Vec<u8>>, W3DError> {
+    if !options.transforms_vertices() {
+        return Ok(None);
+    }
+    let mut mesh_file = deserialize::<MeshFile>(data).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not deserialize the given mesh file.",
+            &error.to_string(),
+        )
+    })?;
+    transform_vertices(&mut mesh_file, options)?;
+    let data = serialize(&mesh_file).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not serialize the transformed mesh file.",
+            &error.to_string(),
+        )
+    })?;
+    Ok(Some(data))
+}
+
+/// Rotates the positions, normals and tangents of a mesh file, scales its positions and
+/// moves them to the new pivot, then flips its texture coordinates.
+///
+/// Normals and tangents stored as normalized `i16` stay so, as rotating unit vectors keeps
+/// them in range. The fourth component of tangents, if any, is left untouched: flipping V
+/// mirrors the texture, so normal maps should be flipped along with it.
+fn transform_vertices(mesh_file: &mut MeshFile, options: &ImportOptions) -> Result<(), W3DError> {
+    let scale = options.uniform_scale;
+    if !(scale > 0. && scale.is_finite()) {
+        return Err(W3DError::with_source(
+            W3DErrorKind::InvalidArgument,
+            "The import scale must be positive.",
+            &scale.to_string(),
+        ));
+    }
+    let rotation = RotationOrder::Xyz.to_unit_quaternion(&options.pre_rotation_euler.to_vector3());
+    for buffer in &mut mesh_file.buffers {
+        let components = buffer.data_type.get_size() as usize;
+        match (buffer.name.as_str(), &mut buffer.data) {
+            (VERTEX_BUFFER_NAME, FileValue::F32Array(values)) if components >= 3 => {
+                for vector in values.chunks_exact_mut(components) {
+                    let position = rotation * Vector3::new(vector[0], vector[1], vector[2]);
+                    vector[..3].copy_from_slice((position * scale).as_slice());
+                }
+            }
+            (NORMAL_BUFFER_NAME, values) | (TANGENT_BUFFER_NAME, values) if components >= 3 => {
+                rotate_directions(values, components, &rotation);
+            }
+            (UV_BUFFER_NAME, FileValue::F32Array(values)) if options.flip_uv_v => {
+                for uv in values.chunks_exact_mut(components.max(2)) {
+                    uv[1] = 1. - uv[1];
+                }
+            }
+            (UV_BUFFER_NAME, values) if options.flip_uv_v => {
+                if let FileValue::I16Array(quantized) = values {
+                    let scale = std::i16::MAX as f32;
+                    let mut uvs: Vec<f32> = quantized
+                        .iter()
+                        .map(|value| (*value as f32 / scale).max(-1.))
+                        .collect();
+                    for uv in uvs.chunks_exact_mut(components.max(2)) {
+                        uv[1] = 1. - uv[1];
+                    }
+                    *values = FileValue::F32Array(uvs);
+                }
+            }
+            _ => {}
+        }
+    }
+    if options.pivot_mode != PivotMode::Unchanged {
+        move_pivot(mesh_file, options.pivot_mode);
+    }
+    Ok(())
+}
+
+/// Rotates the 3 component directions of a buffer, from floats or normalized `i16`.
+fn rotate_directions(values: &mut FileValue, components: usize, rotation: &UnitQuaternion<f32>) {
+    match values {
+        FileValue::F32Array(values) => {
+            for vector in values.chunks_exact_mut(components) {
+                let direction = rotation * Vector3::new(vector[0], vector[1], vector[2]);
+                vector[..3].copy_from_slice(direction.as_slice());
+            }
+        }
+        FileValue::I16Array(values) => {
+            let scale = std::i16::MAX as f32;
+            for vector in values.chunks_exact_mut(components) {
+                let direction = rotation
+                    * Vector3::new(vector[0], vector[1], vector[2]).map(|value| value as f32);
+                for (value, rotated) in vector.iter_mut().zip(direction.iter()) {
+                    *value = rotated.max(-scale).min(scale).round() as i16;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Translates the positions of a mesh file so that its origin is at the pivot given by
+/// `pivot_mode`.
+fn move_pivot(mesh_file: &mut MeshFile, pivot_mode: PivotMode) -> () {
+    let buffer = match mesh_file
+        .buffers
+        .iter_mut()
+        .find(|buffer| buffer.name == VERTEX_BUFFER_NAME)
+    {
+        Some(buffer) => buffer,
+        None => return,
+    };
+    let components = buffer.data_type.get_size() as usize;
+    let values = match &mut buffer.data {
+        FileValue::F32Array(values) if components >= 3 => values,
+        _ => return,
+    };
+    let aabb = match Aabb::from_points(values, components) {
+        Some(aabb) => aabb,
+        None => return,
+    };
+    let mut pivot = (aabb.min + aabb.max) / 2.;
+    if pivot_mode == PivotMode::BottomCenter {
+        pivot.y = aabb.min.y;
+    }
+    for vector in values.chunks_exact_mut(components) {
+        for (value, offset) in vector.iter_mut().zip(pivot.iter()) {
+            *value -= offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::fallback::cube_geometry;
+    use crate::utils::Vector3Data;
+    use std::f32::consts::FRAC_PI_2;
+    use wtvr3d_file::{BufferFile, ShaderDataType, Triangle};
+
+    /// A 2 units wide cube centered on `(1, 2, 3)`.
+    fn cube_mesh_file() -> MeshFile {
+        let (positions, normals, uvs, indexes) = cube_geometry();
+        let positions = positions
+            .chunks_exact(3)
+            .flat_map(|position| {
+                vec![
+                    position[0] * 2. + 1.,
+                    position[1] * 2. + 2.,
+                    position[2] * 2. + 3.,
+                ]
+            })
+            .collect();
+        MeshFile {
+            id: "cube".to_owned(),
+            triangles: indexes
+                .chunks_exact(3)
+                .map(|triangle| Triangle {
+                    vertices: (triangle[0], triangle[1], triangle[2]),
+                })
+                .collect(),
+            buffers: vec![
+                buffer(VERTEX_BUFFER_NAME, ShaderDataType::Vector3, positions),
+                buffer(NORMAL_BUFFER_NAME, ShaderDataType::Vector3, normals),
+                buffer(UV_BUFFER_NAME, ShaderDataType::Vector2, uvs),
+            ],
+        }
+    }
+
+    fn buffer(name: &str, data_type: ShaderDataType, values: Vec<f32>) -> BufferFile {
+        BufferFile {
+            name: name.to_owned(),
+            data_type: data_type,
+            data: FileValue::F32Array(values),
+        }
+    }
+
+    fn values<'a>(mesh_file: &'a MeshFile, name: &str) -> &'a [f32] {
+        match &mesh_file
+            .buffers
+            .iter()
+            .find(|buffer| buffer.name == name)
+            .unwrap()
+            .data
+        {
+            FileValue::F32Array(values) => values,
+            _ => panic!("{} isn't a float buffer", name),
+        }
+    }
+
+    fn bounds(mesh_file: &MeshFile) -> (Vector3<f32>, Vector3<f32>) {
+        let aabb = Aabb::from_points(values(mesh_file, VERTEX_BUFFER_NAME), 3).unwrap();
+        (aabb.min, aabb.max)
+    }
+
+    fn transformed(options: &ImportOptions) -> MeshFile {
+        let mut mesh_file = cube_mesh_file();
+        transform_vertices(&mut mesh_file, options).unwrap();
+        mesh_file
+    }
+
+    fn assert_vector(actual: Vector3<f32>, expected: [f32; 3]) {
+        assert!(
+            (actual - Vector3::from(expected)).amax() < 1.0e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn default_options_leave_the_file_unchanged() {
+        let data = serialize(&cube_mesh_file()).unwrap();
+        assert!(transform_mesh_file(&data, &ImportOptions::default())
+            .unwrap()
+            .is_none());
+        let mut options = ImportOptions::default();
+        options.uniform_scale = 2.;
+        assert!(transform_mesh_file(&data, &options).unwrap().is_some());
+    }
+
+    #[test]
+    fn pivot_modes_move_the_bounds() {
+        let (min, max) = bounds(&cube_mesh_file());
+        assert_vector(min, [0., 1., 2.]);
+        assert_vector(max, [2., 3., 4.]);
+
+        let mut options = ImportOptions::default();
+        options.pivot_mode = PivotMode::BoundsCenter;
+        let (min, max) = bounds(&transformed(&options));
+        assert_vector(min, [-1., -1., -1.]);
+        assert_vector(max, [1., 1., 1.]);
+
+        options.pivot_mode = PivotMode::BottomCenter;
+        let (min, max) = bounds(&transformed(&options));
+        assert_vector(min, [-1., 0., -1.]);
+        assert_vector(max, [1., 2., 1.]);
+    }
+
+    #[test]
+    fn uniform_scale_scales_positions_only() {
+        let mut options = ImportOptions::default();
+        options.uniform_scale = 0.5;
+        let mesh_file = transformed(&options);
+        let (min, max) = bounds(&mesh_file);
+        assert_vector(min, [0., 0.5, 1.]);
+        assert_vector(max, [1., 1.5, 2.]);
+        let original = cube_mesh_file();
+        assert_eq!(
+            values(&mesh_file, NORMAL_BUFFER_NAME),
+            values(&original, NORMAL_BUFFER_NAME)
+        );
+
+        for scale in &[0., -1., std::f32::NAN] {
+            options.uniform_scale = *scale;
+            assert!(transform_vertices(&mut cube_mesh_file(), &options).is_err());
+        }
+    }
+
+    #[test]
+    fn pre_rotation_turns_z_up_into_y_up() {
+        let mut options = ImportOptions::default();
+        options.pre_rotation_euler = Vector3Data::new(-FRAC_PI_2, 0., 0.);
+        let mesh_file = transformed(&options);
+        let (min, max) = bounds(&mesh_file);
+        assert_vector(min, [0., 2., -3.]);
+        assert_vector(max, [2., 4., -1.]);
+
+        let original = cube_mesh_file();
+        let normals = values(&original, NORMAL_BUFFER_NAME).chunks_exact(3);
+        let rotated = values(&mesh_file, NORMAL_BUFFER_NAME).chunks_exact(3);
+        for (normal, rotated) in normals.zip(rotated) {
+            // (x, y, z) becomes (x, z, -y) around X.
+            assert_vector(
+                Vector3::new(rotated[0], rotated[1], rotated[2]),
+                [normal[0], normal[2], -normal[1]],
+            );
+        }
+    }
+
+    #[test]
+    fn rotation_applies_before_scale_and_pivot() {
+        let mut options = ImportOptions::default();
+        options.pre_rotation_euler = Vector3Data::new(-FRAC_PI_2, 0., 0.);
+        options.uniform_scale = 2.;
+        options.pivot_mode = PivotMode::BottomCenter;
+        let (min, max) = bounds(&transformed(&options));
+        assert_vector(min, [-2., 0., -2.]);
+        assert_vector(max, [2., 4., 2.]);
+    }
+
+    #[test]
+    fn flip_uv_v_mirrors_texture_coordinates() {
+        let mut options = ImportOptions::default();
+        options.flip_uv_v = true;
+        let mesh_file = transformed(&options);
+        let original = cube_mesh_file();
+        let uvs = values(&original, UV_BUFFER_NAME).chunks_exact(2);
+        let flipped = values(&mesh_file, UV_BUFFER_NAME).chunks_exact(2);
+        for (uv, flipped) in uvs.zip(flipped) {
+            assert_eq!(flipped[0], uv[0]);
+            assert_eq!(flipped[1], 1. - uv[1]);
+        }
+        assert_eq!(bounds(&mesh_file), bounds(&original));
+    }
+
+    #[test]
+    fn quantized_normals_are_rotated_in_place() {
+        let mut mesh_file = cube_mesh_file();
+        mesh_file.buffers[1].data = FileValue::I16Array(vec![0, 0, std::i16::MAX]);
+        let mut options = ImportOptions::default();
+        options.pre_rotation_euler = Vector3Data::new(-FRAC_PI_2, 0., 0.);
+        transform_vertices(&mut mesh_file, &options).unwrap();
+        match &mesh_file.buffers[1].data {
+            FileValue::I16Array(values) => assert_eq!(values, &vec![0, std::i16::MAX, 0]),
+            _ => panic!("Normals should stay quantized"),
+        }
+    }
+}
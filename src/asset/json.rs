@@ -0,0 +1,470 @@
+//! Human-readable JSON versions of the asset file formats, for diffing and hand-tweaking
+//! assets during development. Binary files remain the format to ship.
+//!
+//! The JSON layout is the one `serde` derives for the file types: enums are externally
+//! tagged (`{ "F32Array": [...] }`) and tuples are arrays. Float arrays may also be written
+//! compactly as `{ "F32Base64": "..." }`, the base64 of their little-endian bytes, so that
+//! large meshes stay a reasonable size.
+
+use crate::error::{W3DError, W3DErrorKind};
+use crate::scene::FileType;
+use bincode::{deserialize, serialize};
+use js_sys::{Array, Object, Reflect, JSON};
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+use wtvr3d_file::{
+    BufferFile, FileValue, MaterialFile, MaterialInstanceFile, MeshFile, ShaderDataType, Triangle,
+};
+
+/// Float arrays longer than this are written in base64 in compact mode.
+const COMPACT_ARRAY_THRESHOLD: usize = 16;
+
+/// Key of base64-encoded float arrays, alongside the `FileValue` variants.
+const F32_BASE64_KEY: &str = "F32Base64";
+
+/// Characters of the standard base64 alphabet.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Converts a binary asset file to indented JSON. If `compact` is `true`, long float arrays
+/// are written in base64.
+pub fn asset_file_to_json(
+    data: &[u8],
+    file_type: &FileType,
+    compact: bool,
+) -> Result<String, W3DError> {
+    let value = match file_type {
+        FileType::WMesh => mesh_to_js(
+            &deserialize::<MeshFile>(data).map_err(deserialization_error)?,
+            compact,
+        ),
+        FileType::WMaterial => material_to_js(
+            &deserialize::<MaterialFile>(data).map_err(deserialization_error)?,
+            compact,
+        ),
+        FileType::WMatInstance => material_instance_to_js(
+            &deserialize::<MaterialInstanceFile>(data).map_err(deserialization_error)?,
+            compact,
+        ),
+    };
+    JSON::stringify_with_replacer_and_space(&value, &JsValue::NULL, &JsValue::from_f64(2.0))
+        .ok()
+        .and_then(|json| json.as_string())
+        .ok_or_else(|| {
+            W3DError::new(
+                W3DErrorKind::Deserialization,
+                "Could not write the asset as JSON.",
+            )
+        })
+}
+
+/// Converts the JSON version of an asset file back to the binary format.
+pub fn asset_file_from_json(text: &str, file_type: &FileType) -> Result<Vec<u8>, W3DError> {
+    let value = JSON::parse(text).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "The asset is not valid JSON.",
+            &error.as_string().unwrap_or_default(),
+        )
+    })?;
+    let file_data = match file_type {
+        FileType::WMesh => serialize(&mesh_from_js(&value)?),
+        FileType::WMaterial => serialize(&material_from_js(&value)?),
+        FileType::WMatInstance => serialize(&material_instance_from_js(&value)?),
+    };
+    file_data.map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not serialize the asset file.",
+            &error.to_string(),
+        )
+    })
+}
+
+fn deserialization_error(error: bincode::Error) -> W3DError {
+    W3DError::with_source(
+        W3DErrorKind::Deserialization,
+        "Could not deserialize the given asset file.",
+        &error.to_string(),
+    )
+}
+
+fn mesh_to_js(mesh: &MeshFile, compact: bool) -> JsValue {
+    let object = Object::new();
+    set(&object, "id", mesh.id.as_str().into());
+    let triangles = Array::new();
+    for triangle in &mesh.triangles {
+        let vertices = triangle.vertices;
+        let entry = Object::new();
+        set(
+            &entry,
+            "vertices",
+            numbers_to_js(&[vertices.0, vertices.1, vertices.2]),
+        );
+        triangles.push(&entry);
+    }
+    set(&object, "triangles", triangles.into());
+    let buffers = Array::new();
+    for buffer in &mesh.buffers {
+        let entry = Object::new();
+        set(&entry, "name", buffer.name.as_str().into());
+        set(&entry, "data_type", data_type_to_js(buffer.data_type));
+        set(&entry, "data", file_value_to_js(&buffer.data, compact));
+        buffers.push(&entry);
+    }
+    set(&object, "buffers", buffers.into());
+    object.into()
+}
+
+fn material_to_js(material: &MaterialFile, compact: bool) -> JsValue {
+    let object = Object::new();
+    set(&object, "id", material.id.as_str().into());
+    set(
+        &object,
+        "vertex_shader",
+        material.vertex_shader.as_str().into(),
+    );
+    set(
+        &object,
+        "framgent_shader",
+        material.framgent_shader.as_str().into(),
+    );
+    set(&object, "transparent", material.transparent.into());
+    set(&object, "lit", material.lit.into());
+    set(
+        &object,
+        "global_uniforms",
+        uniforms_to_js(&material.global_uniforms, compact),
+    );
+    object.into()
+}
+
+fn material_instance_to_js(material_instance: &MaterialInstanceFile, compact: bool) -> JsValue {
+    let object = Object::new();
+    set(&object, "id", material_instance.id.as_str().into());
+    set(
+        &object,
+        "parent_id",
+        material_instance.parent_id.as_str().into(),
+    );
+    set(
+        &object,
+        "uniforms",
+        uniforms_to_js(&material_instance.uniforms, compact),
+    );
+    object.into()
+}
+
+/// Writes uniforms sorted by name, so that exports of the same file are identical.
+fn uniforms_to_js(
+    uniforms: &HashMap<String, (ShaderDataType, FileValue)>,
+    compact: bool,
+) -> JsValue {
+    let object = Object::new();
+    let mut names: Vec<&String> = uniforms.keys().collect();
+    names.sort();
+    for name in names {
+        let (data_type, value) = &uniforms[name];
+        let entry = Array::new();
+        entry.push(&data_type_to_js(*data_type));
+        entry.push(&file_value_to_js(value, compact));
+        set(&object, name, entry.into());
+    }
+    object.into()
+}
+
+fn data_type_to_js(data_type: ShaderDataType) -> JsValue {
+    format!("{:?}", data_type).into()
+}
+
+fn file_value_to_js(value: &FileValue, compact: bool) -> JsValue {
+    let object = Object::new();
+    match value {
+        FileValue::F32Array(data) if compact && data.len() > COMPACT_ARRAY_THRESHOLD => {
+            set(&object, F32_BASE64_KEY, encode_f32_base64(data).into());
+        }
+        FileValue::F32Array(data) => {
+            // Going through the shortest decimal form keeps `0.1f32` from being written as
+            // `0.10000000149011612`.
+            let array = Array::new();
+            for value in data {
+                let shortest = value.to_string().parse::<f64>().unwrap_or(*value as f64);
+                array.push(&shortest.into());
+            }
+            set(&object, "F32Array", array.into());
+        }
+        FileValue::I16Array(data) => set(&object, "I16Array", numbers_to_js(data)),
+        FileValue::U8Array(data) => set(&object, "U8Array", numbers_to_js(data)),
+        FileValue::U16Array(data) => set(&object, "U16Array", numbers_to_js(data)),
+        FileValue::AssetID(id) => set(&object, "AssetID", id.as_str().into()),
+    }
+    object.into()
+}
+
+fn numbers_to_js<T: Copy + Into<f64>>(numbers: &[T]) -> JsValue {
+    let array = Array::new();
+    for number in numbers {
+        array.push(&JsValue::from_f64((*number).into()));
+    }
+    array.into()
+}
+
+fn mesh_from_js(value: &JsValue) -> Result<MeshFile, W3DError> {
+    let mut triangles = Vec::new();
+    for triangle in array_field(value, "triangles")?.iter() {
+        let vertices = numbers_from_js(&field(&triangle, "vertices")?, "vertices")?;
+        if vertices.len() != 3 {
+            return Err(invalid_field("vertices"));
+        }
+        triangles.push(Triangle {
+            vertices: (vertices[0] as u16, vertices[1] as u16, vertices[2] as u16),
+        });
+    }
+    let mut buffers = Vec::new();
+    for buffer in array_field(value, "buffers")?.iter() {
+        buffers.push(BufferFile {
+            name: string_field(&buffer, "name")?,
+            data_type: data_type_from_js(&field(&buffer, "data_type")?)?,
+            data: file_value_from_js(&field(&buffer, "data")?)?,
+        });
+    }
+    Ok(MeshFile {
+        id: string_field(value, "id")?,
+        triangles: triangles,
+        buffers: buffers,
+    })
+}
+
+fn material_from_js(value: &JsValue) -> Result<MaterialFile, W3DError> {
+    Ok(MaterialFile {
+        id: string_field(value, "id")?,
+        vertex_shader: string_field(value, "vertex_shader")?,
+        framgent_shader: string_field(value, "framgent_shader")?,
+        transparent: bool_field(value, "transparent")?,
+        lit: bool_field(value, "lit")?,
+        global_uniforms: uniforms_from_js(&field(value, "global_uniforms")?)?,
+    })
+}
+
+fn material_instance_from_js(value: &JsValue) -> Result<MaterialInstanceFile, W3DError> {
+    Ok(MaterialInstanceFile {
+        id: string_field(value, "id")?,
+        parent_id: string_field(value, "parent_id")?,
+        uniforms: uniforms_from_js(&field(value, "uniforms")?)?,
+    })
+}
+
+fn uniforms_from_js(
+    value: &JsValue,
+) -> Result<HashMap<String, (ShaderDataType, FileValue)>, W3DError> {
+    if !value.is_object() {
+        return Err(invalid_field("uniforms"));
+    }
+    let mut uniforms = HashMap::new();
+    for name in Object::keys(&Object::from(value.clone())).iter() {
+        let name = name.as_string().unwrap_or_default();
+        let entry = field(value, &name)?;
+        if !Array::is_array(&entry) {
+            return Err(invalid_field(&name));
+        }
+        let entry = Array::from(&entry);
+        if entry.length() != 2 {
+            return Err(invalid_field(&name));
+        }
+        let data_type = data_type_from_js(&entry.get(0))?;
+        let file_value = file_value_from_js(&entry.get(1))?;
+        uniforms.insert(name, (data_type, file_value));
+    }
+    Ok(uniforms)
+}
+
+fn data_type_from_js(value: &JsValue) -> Result<ShaderDataType, W3DError> {
+    match value.as_string().as_ref().map(String::as_str) {
+        Some("Single") => Ok(ShaderDataType::Single),
+        Some("Vector2") => Ok(ShaderDataType::Vector2),
+        Some("Vector3") => Ok(ShaderDataType::Vector3),
+        Some("Vector4") => Ok(ShaderDataType::Vector4),
+        Some("Matrix2") => Ok(ShaderDataType::Matrix2),
+        Some("Matrix3") => Ok(ShaderDataType::Matrix3),
+        Some("Matrix4") => Ok(ShaderDataType::Matrix4),
+        Some("Sampler2D") => Ok(ShaderDataType::Sampler2D),
+        _ => Err(invalid_field("data_type")),
+    }
+}
+
+fn file_value_from_js(value: &JsValue) -> Result<FileValue, W3DError> {
+    let get = |key: &str| {
+        Reflect::get(value, &key.into())
+            .ok()
+            .filter(|value| !value.is_undefined())
+    };
+    if let Some(data) = get(F32_BASE64_KEY) {
+        let text = data
+            .as_string()
+            .ok_or_else(|| invalid_field(F32_BASE64_KEY))?;
+        return Ok(FileValue::F32Array(decode_f32_base64(&text)?));
+    }
+    if let Some(data) = get("F32Array") {
+        let numbers = numbers_from_js(&data, "F32Array")?;
+        return Ok(FileValue::F32Array(
+            numbers.into_iter().map(|number| number as f32).collect(),
+        ));
+    }
+    if let Some(data) = get("I16Array") {
+        let numbers = numbers_from_js(&data, "I16Array")?;
+        return Ok(FileValue::I16Array(
+            numbers.into_iter().map(|number| number as i16).collect(),
+        ));
+    }
+    if let Some(data) = get("U8Array") {
+        let numbers = numbers_from_js(&data, "U8Array")?;
+        return Ok(FileValue::U8Array(
+            numbers.into_iter().map(|number| number as u8).collect(),
+        ));
+    }
+    if let Some(data) = get("U16Array") {
+        let numbers = numbers_from_js(&data, "U16Array")?;
+        return Ok(FileValue::U16Array(
+            numbers.into_iter().map(|number| number as u16).collect(),
+        ));
+    }
+    match get("AssetID").and_then(|id| id.as_string()) {
+        Some(id) => Ok(FileValue::AssetID(id)),
+        None => Err(invalid_field("data")),
+    }
+}
+
+fn numbers_from_js(value: &JsValue, name: &str) -> Result<Vec<f64>, W3DError> {
+    if !Array::is_array(value) {
+        return Err(invalid_field(name));
+    }
+    Array::from(value)
+        .iter()
+        .map(|number| number.as_f64().ok_or_else(|| invalid_field(name)))
+        .collect()
+}
+
+fn field(value: &JsValue, name: &str) -> Result<JsValue, W3DError> {
+    Reflect::get(value, &name.into())
+        .ok()
+        .filter(|field| !field.is_undefined())
+        .ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::Deserialization,
+                "A field is missing from the JSON asset.",
+                name,
+            )
+        })
+}
+
+fn array_field(value: &JsValue, name: &str) -> Result<Array, W3DError> {
+    let array = field(value, name)?;
+    if Array::is_array(&array) {
+        Ok(Array::from(&array))
+    } else {
+        Err(invalid_field(name))
+    }
+}
+
+fn string_field(value: &JsValue, name: &str) -> Result<String, W3DError> {
+    field(value, name)?
+        .as_string()
+        .ok_or_else(|| invalid_field(name))
+}
+
+fn bool_field(value: &JsValue, name: &str) -> Result<bool, W3DError> {
+    field(value, name)?
+        .as_bool()
+        .ok_or_else(|| invalid_field(name))
+}
+
+fn invalid_field(name: &str) -> W3DError {
+    W3DError::with_source(
+        W3DErrorKind::Deserialization,
+        "A field of the JSON asset has the wrong type.",
+        name,
+    )
+}
+
+fn set(object: &Object, key: &str, value: JsValue) -> () {
+    Reflect::set(object, &key.into(), &value).ok();
+}
+
+/// Encodes floats as the base64 of their little-endian bytes.
+fn encode_f32_base64(data: &[f32]) -> String {
+    let bytes: Vec<u8> = data
+        .iter()
+        .flat_map(|value| value.to_bits().to_le_bytes().to_vec())
+        .collect();
+    let mut text = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Decodes floats written by `encode_f32_base64`.
+fn decode_f32_base64(text: &str) -> Result<Vec<f32>, W3DError> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut group = 0u32;
+    let mut bits = 0;
+    for character in text.bytes().filter(|character| *character != b'=') {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|candidate| *candidate == character)
+            .ok_or_else(|| invalid_field(F32_BASE64_KEY))?;
+        group = group << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits & 0xff) as u8);
+        }
+    }
+    if bytes.len() % 4 != 0 {
+        return Err(invalid_field(F32_BASE64_KEY));
+    }
+    Ok(bytes
+        .chunks(4)
+        .map(|chunk| f32::from_bits(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f32_base64_round_trips() {
+        for length in 0..5 {
+            let data: Vec<f32> = (0..length).map(|i| i as f32 * -1.5 + 0.1).collect();
+            let text = encode_f32_base64(&data);
+            assert_eq!(text.len() % 4, 0);
+            assert_eq!(decode_f32_base64(&text).unwrap(), data);
+        }
+        let special = [std::f32::INFINITY, -0., std::f32::MIN_POSITIVE];
+        let decoded = decode_f32_base64(&encode_f32_base64(&special)).unwrap();
+        assert_eq!(decoded[0], std::f32::INFINITY);
+        assert!(decoded[1] == 0. && decoded[1].is_sign_negative());
+        assert_eq!(decoded[2], std::f32::MIN_POSITIVE);
+    }
+
+    #[test]
+    fn f32_base64_matches_standard_alphabet() {
+        assert_eq!(encode_f32_base64(&[1.]), "AACAPw==");
+    }
+
+    #[test]
+    fn f32_base64_rejects_invalid_text() {
+        assert!(decode_f32_base64("AAC*Pw==").is_err());
+        assert!(decode_f32_base64("AACA").is_err());
+    }
+}
@@ -0,0 +1,73 @@
+//! Bookkeeping for the optional memory budget of the asset registry.
+
+use std::collections::{HashMap, HashSet};
+
+/// ## MemoryBudget
+///
+/// Limits on the memory used by the registered assets, and what the registry needs to
+/// evict and reconstruct mesh data when the GPU limit is exceeded.
+///
+/// Only mesh data registered from files while a limit is set can be evicted: their file is
+/// kept on the CPU so that their buffers can be uploaded again. Textures and materials are
+/// counted but never evicted, since their GL objects are shared with material instances.
+/// Nothing is evicted to honor the CPU limit, as that would lose the data needed to
+/// reconstruct evicted assets; exceeding it only logs a warning.
+#[derive(Default)]
+pub struct MemoryBudget {
+    /// Largest number of bytes the assets may use on the GPU, `None` for no limit
+    pub gpu_bytes: Option<usize>,
+
+    /// Largest number of bytes the assets may use on the CPU, `None` for no limit
+    pub cpu_bytes: Option<usize>,
+
+    /// File of each evictable mesh data, by asset index
+    pub sources: HashMap<usize, Vec<u8>>,
+
+    /// Last frame each asset was drawn with, by asset index
+    pub last_used: HashMap<usize, u64>,
+
+    /// Indexes of the assets that are never evicted
+    pub pinned: HashSet<usize>,
+
+    /// Indexes of the mesh data whose buffers are evicted
+    pub evicted: HashSet<usize>,
+
+    /// Current frame, increased by `AssetRegistry::begin_frame`
+    pub frame: u64,
+
+    /// Number of evictions since the counters were last taken
+    pub evictions: u32,
+
+    /// Number of reconstructions since the counters were last taken
+    pub reconstructions: u32,
+}
+
+impl MemoryBudget {
+    /// Returns `true` if a GPU or CPU limit is set.
+    pub fn is_enabled(&self) -> bool {
+        self.gpu_bytes.is_some() || self.cpu_bytes.is_some()
+    }
+
+    /// Returns `true` if the asset at `index` can be evicted now: its file is kept, it's
+    /// resident, not pinned and wasn't drawn during the current frame.
+    pub fn is_evictable(&self, index: usize) -> bool {
+        self.sources.contains_key(&index)
+            && !self.evicted.contains(&index)
+            && !self.pinned.contains(&index)
+            && self.last_used.get(&index) != Some(&self.frame)
+    }
+
+    /// Returns the last frame the asset at `index` was drawn with, `0` if never.
+    pub fn get_last_used(&self, index: usize) -> u64 {
+        self.last_used.get(&index).cloned().unwrap_or(0)
+    }
+
+    /// Returns the number of evictions and reconstructions since the last call, and resets
+    /// them.
+    pub fn take_counters(&mut self) -> (u32, u32) {
+        let counters = (self.evictions, self.reconstructions);
+        self.evictions = 0;
+        self.reconstructions = 0;
+        counters
+    }
+}
@@ -1,51 +1,143 @@
 //! Deserializer for files generated using the wtvr3d Asset Converter
 mod asset_registry;
 
-pub use asset_registry::AssetRegistry;
+mod atlas_region;
 
-use crate::renderer::{Buffer, Material, MaterialInstance, MeshData, Uniform, UniformValue};
+mod import_transform;
+
+mod json;
+
+mod memory_budget;
+
+#[cfg(feature = "editor")]
+mod quantize;
+
+mod winding;
+
+pub use asset_registry::{Asset, AssetRegistry, MissingAssetReference};
+pub use atlas_region::AtlasRegion;
+pub use import_transform::transform_mesh_file;
+pub use json::{asset_file_from_json, asset_file_to_json};
+pub use memory_budget::MemoryBudget;
+#[cfg(feature = "editor")]
+pub use quantize::{quantize_mesh_file, QuantizationReport};
+pub use winding::{check_winding, Winding, WindingReport};
+
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::Aabb;
+use crate::renderer::{
+    AttributeData, Buffer, Material, MaterialInstance, MeshCpuData, MeshData, Uniform, UniformValue,
+};
 use bincode::deserialize;
-use web_sys::WebGlRenderingContext;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlTexture};
 use wtvr3d_file::{FileValue, MaterialFile, MaterialInstanceFile, MeshFile, ShaderDataType};
 
-pub fn deserialize_wmesh(context: &WebGlRenderingContext, data: &[u8]) -> Result<MeshData, String> {
-    let mesh_files_result = deserialize::<MeshFile>(data);
-    match mesh_files_result {
-        Err(_) => Err(String::from("Could not deserialize the given mesh file.")),
-        Ok(mesh_file) => Ok(make_mesh_data_from(context, &mesh_file)),
+/// Deserializes a mesh file and uploads its buffers. If `retain_cpu_data` is `true`, a copy
+/// of its positions and indexes is kept in the `MeshData`.
+pub fn deserialize_wmesh(
+    context: &WebGlRenderingContext,
+    data: &[u8],
+    retain_cpu_data: bool,
+) -> Result<MeshData, W3DError> {
+    let mesh_file = deserialize_mesh_file(data)?;
+    let mut mesh_data = make_mesh_data_from(context, &mesh_file)?;
+    if retain_cpu_data {
+        mesh_data.set_cpu_data(make_mesh_cpu_data_from(&mesh_file));
+    }
+    Ok(mesh_data)
+}
+
+/// Reads the positions and indexes of a mesh file without uploading anything, e.g. to
+/// iterate over its triangles with `MeshCpuData::triangles`.  
+/// Fails if the file can't be deserialized or has no positions.
+pub fn deserialize_wmesh_cpu_data(data: &[u8]) -> Result<MeshCpuData, W3DError> {
+    let mesh_file = deserialize_mesh_file(data)?;
+    make_mesh_cpu_data_from(&mesh_file).ok_or_else(|| {
+        W3DError::with_source(
+            W3DErrorKind::MissingAsset,
+            "The mesh file has no positions.",
+            &mesh_file.id,
+        )
+    })
+}
+
+fn deserialize_mesh_file(data: &[u8]) -> Result<MeshFile, W3DError> {
+    deserialize::<MeshFile>(data).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not deserialize the given mesh file.",
+            &error.to_string(),
+        )
+    })
+}
+
+/// Assets substituted for the missing assets a material or material instance file refers to.
+/// Missing assets are errors for the fallbacks left to `None`.
+pub struct Fallbacks {
+    /// Texture replacing missing textures
+    pub texture: Option<Rc<WebGlTexture>>,
+
+    /// Material replacing a missing parent material
+    pub material: Option<Rc<RefCell<Material>>>,
+
+    /// Ids and types of the assets that were replaced, filled while deserializing
+    pub replaced: Vec<(String, &'static str)>,
+}
+
+impl Fallbacks {
+    /// No fallbacks: every missing asset is an error.
+    pub fn none() -> Fallbacks {
+        Fallbacks {
+            texture: None,
+            material: None,
+            replaced: Vec::new(),
+        }
     }
 }
 
 pub fn deserialize_wmaterial(
     asset_registry: &AssetRegistry,
     data: &[u8],
-) -> Result<Material, String> {
+    fallbacks: &mut Fallbacks,
+) -> Result<Material, W3DError> {
     let material_files_result = deserialize::<MaterialFile>(data);
     match material_files_result {
-        Err(_) => Err(String::from(
+        Err(error) => Err(W3DError::with_source(
+            W3DErrorKind::Deserialization,
             "Could not deserialize the given material file.",
+            &error.to_string(),
         )),
-        Ok(material_file) => Ok(make_material_from(asset_registry, &material_file)),
+        Ok(material_file) => make_material_from(asset_registry, &material_file, fallbacks),
     }
 }
 
 pub fn deserialize_wmatinstance(
     asset_registry: &AssetRegistry,
     data: &[u8],
-) -> Result<MaterialInstance, String> {
+    fallbacks: &mut Fallbacks,
+) -> Result<MaterialInstance, W3DError> {
     let material_files_result = deserialize::<MaterialInstanceFile>(data);
     match material_files_result {
-        Err(_) => Err(String::from(
-            "Could not deserialize the given material file.",
+        Err(error) => Err(W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not deserialize the given material instance file.",
+            &error.to_string(),
         )),
         Ok(material_instance_file) => {
-            make_material_instance_from(asset_registry, &material_instance_file)
+            make_material_instance_from(asset_registry, &material_instance_file, fallbacks)
         }
     }
 }
 
-// ⭕ TODO : handle other FileValue types if anything else is provided
-fn make_mesh_data_from(context: &WebGlRenderingContext, mesh_file: &MeshFile) -> MeshData {
+/// Uploads the buffers of a mesh file. `I16Array` and `U8Array` buffers are normalized
+/// attributes, `U16Array` buffers integer ones: see `ComponentType`.
+fn make_mesh_data_from(
+    context: &WebGlRenderingContext,
+    mesh_file: &MeshFile,
+) -> Result<MeshData, W3DError> {
     let mut v_indexes = Vec::new();
     for triangle in &mesh_file.triangles {
         v_indexes.push(triangle.vertices.0);
@@ -54,35 +146,74 @@ fn make_mesh_data_from(context: &WebGlRenderingContext, mesh_file: &MeshFile) ->
     }
     let mut mesh_data = MeshData::new(mesh_file.id.clone(), mesh_file.triangles.len() as i32 * 3);
     for buffer in &mesh_file.buffers {
-        if let FileValue::F32Array(buffer_data) = &buffer.data {
-            let indexes = match buffer.name.as_str() {
-                crate::utils::constants::VERTEX_BUFFER_NAME => Some(v_indexes.as_slice()),
-                _ => None,
-            };
-            let buf = Buffer::from_f32_data_view(
-                context,
-                &buffer.name,
-                buffer.data_type,
-                buffer_data,
-                indexes,
-            );
-            mesh_data.push_buffer(buf);
+        let data = match &buffer.data {
+            FileValue::F32Array(buffer_data) => AttributeData::F32(buffer_data),
+            FileValue::I16Array(buffer_data) => AttributeData::I16Norm(buffer_data),
+            FileValue::U8Array(buffer_data) => AttributeData::U8Norm(buffer_data),
+            FileValue::U16Array(buffer_data) => AttributeData::U16(buffer_data),
+            FileValue::AssetID(_) => continue,
+        };
+        let indexes = match buffer.name.as_str() {
+            crate::utils::constants::VERTEX_BUFFER_NAME => {
+                if let AttributeData::F32(buffer_data) = data {
+                    mesh_data.set_bounds(
+                        Aabb::from_points(buffer_data, buffer.data_type.get_size() as usize)
+                            .map(|aabb| aabb.to_bounding_sphere()),
+                    );
+                }
+                Some(v_indexes.as_slice())
+            }
+            _ => None,
+        };
+        let buf = Buffer::from_data_view(context, &buffer.name, buffer.data_type, data, indexes)?;
+        mesh_data.push_buffer(buf);
+    }
+    Ok(mesh_data)
+}
+
+/// Copies the positions and triangle indexes of a mesh file, if it has positions.
+fn make_mesh_cpu_data_from(mesh_file: &MeshFile) -> Option<MeshCpuData> {
+    let position_buffer = mesh_file
+        .buffers
+        .iter()
+        .find(|buffer| buffer.name == crate::utils::constants::VERTEX_BUFFER_NAME)?;
+    match &position_buffer.data {
+        FileValue::F32Array(positions) => {
+            let mut indexes = Vec::with_capacity(mesh_file.triangles.len() * 3);
+            for triangle in &mesh_file.triangles {
+                indexes.push(triangle.vertices.0);
+                indexes.push(triangle.vertices.1);
+                indexes.push(triangle.vertices.2);
+            }
+            Some(MeshCpuData {
+                positions: positions.clone(),
+                components: position_buffer.data_type.get_size() as usize,
+                indexes: indexes,
+            })
         }
+        _ => None,
     }
-    mesh_data
 }
 
-fn make_material_from(asset_registry: &AssetRegistry, mat_file: &MaterialFile) -> Material {
+fn make_material_from(
+    asset_registry: &AssetRegistry,
+    mat_file: &MaterialFile,
+    fallbacks: &mut Fallbacks,
+) -> Result<Material, W3DError> {
     let mut material = Material::new(
         &mat_file.vertex_shader,
         &mat_file.framgent_shader,
         &mat_file.id,
     );
+    material.set_transparent(mat_file.transparent);
     let mut max_texture = 0;
-    for uniform_data in &mat_file.global_uniforms {
-        let value =
-            make_uniform_value_from((uniform_data.1).0, &(uniform_data.1).1, asset_registry)
-                .unwrap();
+    for uniform_data in sorted_by_name(&mat_file.global_uniforms) {
+        let value = make_uniform_value_from(
+            (uniform_data.1).0,
+            &(uniform_data.1).1,
+            asset_registry,
+            fallbacks,
+        )?;
         let mut uniform = Uniform::new(uniform_data.0, value);
         if (uniform_data.1).0 == ShaderDataType::Sampler2D {
             uniform.set_texture_index(max_texture);
@@ -90,30 +221,40 @@ fn make_material_from(asset_registry: &AssetRegistry, mat_file: &MaterialFile) -
         }
         material.set_uniform(uniform);
     }
-    material
+    Ok(material)
 }
 
 fn make_material_instance_from(
     asset_registry: &AssetRegistry,
     mat_instance_file: &MaterialInstanceFile,
-) -> Result<MaterialInstance, String> {
-    match asset_registry.get_material(&mat_instance_file.parent_id) {
+    fallbacks: &mut Fallbacks,
+) -> Result<MaterialInstance, W3DError> {
+    let parent = match asset_registry.get_material(&mat_instance_file.parent_id) {
+        Some(mat) => Some(mat),
+        None => fallbacks.material.clone().map(|fallback| {
+            fallbacks
+                .replaced
+                .push((mat_instance_file.parent_id.clone(), "material"));
+            fallback
+        }),
+    };
+    match parent {
         Some(mat) => {
             let mut mat_instance = MaterialInstance::new(mat.clone(), &mat_instance_file.id);
-            let parent_texture_indexes = &mat.borrow().get_texture_indexes().unwrap();
+            let parent_texture_indexes = &mat.borrow().get_texture_indexes()?;
             let mut next_index = 0;
             for (_, index) in parent_texture_indexes {
                 if index >= &next_index {
                     next_index = index + 1;
                 }
             }
-            for uniform_data in &mat_instance_file.uniforms {
+            for uniform_data in sorted_by_name(&mat_instance_file.uniforms) {
                 let value = make_uniform_value_from(
                     (uniform_data.1).0,
                     &(uniform_data.1).1,
                     asset_registry,
-                )
-                .unwrap();
+                    fallbacks,
+                )?;
                 let mut uniform = Uniform::new(uniform_data.0, value);
                 if (uniform_data.1).0 == ShaderDataType::Sampler2D {
                     if parent_texture_indexes.contains_key(uniform_data.0) {
@@ -129,28 +270,175 @@ fn make_material_instance_from(
             }
             Ok(mat_instance)
         }
-        None => Err(String::from(
+        None => Err(W3DError::with_source(
+            W3DErrorKind::MissingAsset,
             "Could not find parent material. Has it been registered yet?",
+            &mat_instance_file.parent_id,
         )),
     }
 }
 
+/// Returns the uniforms of a material or material instance file sorted by name.  
+/// Files store them in a `HashMap`: iterating in a fixed order keeps the texture unit
+/// assignment and the uniform upload order identical from one run to the next.
+fn sorted_by_name(
+    uniforms: &HashMap<String, (ShaderDataType, FileValue)>,
+) -> Vec<(&String, &(ShaderDataType, FileValue))> {
+    let mut sorted: Vec<_> = uniforms.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    sorted
+}
+
 fn make_uniform_value_from(
     value_type: ShaderDataType,
     fv: &FileValue,
     asset_registry: &AssetRegistry,
-) -> Result<Box<dyn UniformValue>, String> {
+    fallbacks: &mut Fallbacks,
+) -> Result<Box<dyn UniformValue>, W3DError> {
     match fv {
         FileValue::F32Array(fvec) => Ok(Box::new((value_type, fvec.clone()))),
         FileValue::I16Array(ivec) => Ok(Box::new((value_type, ivec.clone()))),
         FileValue::U8Array(uvec) => Ok(Box::new((value_type, uvec.clone()))),
         FileValue::AssetID(id) => match asset_registry.get_texture(&id) {
             Some(rc) => Ok(Box::new(rc)),
-            None => Err(format!(
-                "Texture with id {} does not exist. Has it been registered yet?",
-                id
+            None if fallbacks.texture.is_some() => {
+                fallbacks.replaced.push((id.clone(), "texture"));
+                Ok(Box::new(fallbacks.texture.clone().unwrap()))
+            }
+            None => Err(W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Texture does not exist. Has it been registered yet?",
+                id,
             )),
         },
-        _ => Err(String::from("Unknown FileValue reached.")),
+        _ => Err(W3DError::new(
+            W3DErrorKind::Deserialization,
+            "Unknown FileValue reached.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen::{JsCast, JsValue};
+
+    /// Fallbacks replacing every texture, since textures can't be created natively.
+    fn texture_fallbacks() -> Fallbacks {
+        Fallbacks {
+            texture: Some(Rc::new(JsValue::NULL.unchecked_into())),
+            material: None,
+            replaced: Vec::new(),
+        }
+    }
+
+    /// Uniforms of a material file, inserted in the given order.
+    fn uniforms(names: &[&str]) -> HashMap<String, (ShaderDataType, FileValue)> {
+        names
+            .iter()
+            .map(|name| {
+                let uniform = if name.ends_with("_map") {
+                    (
+                        ShaderDataType::Sampler2D,
+                        FileValue::AssetID(name.to_string()),
+                    )
+                } else {
+                    (ShaderDataType::Single, FileValue::F32Array(vec![1.]))
+                };
+                (name.to_string(), uniform)
+            })
+            .collect()
+    }
+
+    fn material_file(names: &[&str]) -> MaterialFile {
+        MaterialFile {
+            id: "material".to_owned(),
+            vertex_shader: String::new(),
+            framgent_shader: String::new(),
+            transparent: false,
+            lit: false,
+            global_uniforms: uniforms(names),
+        }
+    }
+
+    #[test]
+    fn materials_from_same_file_are_identical() {
+        let names = [
+            "u_roughness",
+            "u_normal_map",
+            "u_albedo_map",
+            "u_emissive_map",
+            "u_a",
+        ];
+        let mut reversed = names;
+        reversed.reverse();
+        let registry = AssetRegistry::new();
+        let first = make_material_from(&registry, &material_file(&names), &mut texture_fallbacks())
+            .unwrap();
+        for other_names in &[names, reversed] {
+            let other = make_material_from(
+                &registry,
+                &material_file(other_names),
+                &mut texture_fallbacks(),
+            )
+            .unwrap();
+            assert_eq!(
+                first.get_texture_indexes().unwrap(),
+                other.get_texture_indexes().unwrap()
+            );
+            assert_eq!(first.get_uniform_names(), other.get_uniform_names());
+        }
+        let indexes = first.get_texture_indexes().unwrap();
+        let units: Vec<(&str, u32)> = indexes
+            .iter()
+            .map(|(name, unit)| (name.as_str(), *unit))
+            .collect();
+        assert_eq!(
+            units,
+            vec![
+                ("u_albedo_map", 0),
+                ("u_emissive_map", 1),
+                ("u_normal_map", 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn material_instances_from_same_file_are_identical() {
+        let mut registry = AssetRegistry::new();
+        let parent = make_material_from(
+            &registry,
+            &material_file(&["u_albedo_map", "u_tint"]),
+            &mut texture_fallbacks(),
+        )
+        .unwrap();
+        registry.add_material(parent);
+        let instance_file = |names: &[&str]| MaterialInstanceFile {
+            id: "instance".to_owned(),
+            parent_id: "material".to_owned(),
+            uniforms: uniforms(names),
+        };
+        let names = ["u_detail_map", "u_albedo_map", "u_mask_map", "u_tint"];
+        let mut reversed = names;
+        reversed.reverse();
+        let first = make_material_instance_from(
+            &registry,
+            &instance_file(&names),
+            &mut texture_fallbacks(),
+        )
+        .unwrap();
+        let other = make_material_instance_from(
+            &registry,
+            &instance_file(&reversed),
+            &mut texture_fallbacks(),
+        )
+        .unwrap();
+        assert_eq!(first.get_uniform_names(), other.get_uniform_names());
+        for name in &names {
+            assert_eq!(first.get_texture_unit(name), other.get_texture_unit(name));
+        }
+        assert_eq!(first.get_texture_unit("u_albedo_map"), 0);
+        assert_eq!(first.get_texture_unit("u_detail_map"), 1);
+        assert_eq!(first.get_texture_unit("u_mask_map"), 2);
     }
 }
@@ -0,0 +1,223 @@
+//! Conversion of the float vertex attributes of mesh files to compact normalized types.
+
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::constants::{COLOR_BUFFER_NAME, NORMAL_BUFFER_NAME, UV_BUFFER_NAME};
+use bincode::{deserialize, serialize};
+use wtvr3d_file::{FileValue, MeshFile};
+
+/// ## QuantizationReport
+///
+/// Size of the vertex buffers of a mesh file before and after quantization.
+pub struct QuantizationReport {
+    /// Bytes used by the vertex buffers before quantization
+    pub original_bytes: usize,
+
+    /// Bytes used by the vertex buffers after quantization
+    pub quantized_bytes: usize,
+
+    /// Names of the attributes that were quantized
+    pub attributes: Vec<String>,
+}
+
+/// Converts the normals, colors and texture coordinates of a mesh file to normalized
+/// integers, which the shaders read back as floats: normals and texture coordinates to
+/// `I16Array` (`ComponentType::I16Norm`), colors to `U8Array` (`ComponentType::U8Norm`).
+/// Returns the converted file and the size saved.
+///
+/// Attributes are only converted if all their values fit the normalized range, `-1` to `1`
+/// or `0` to `1`: texture coordinates of tiled meshes are kept as floats. Values then stay
+/// within half a step of the original ones, `1 / 65534` for `I16Array` and `1 / 510` for
+/// `U8Array`.
+/// Fails if the file can't be deserialized.
+pub fn quantize_mesh_file(data: &[u8]) -> Result<(Vec<u8>, QuantizationReport), W3DError> {
+    let mut mesh_file = deserialize::<MeshFile>(data).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not deserialize the given mesh file.",
+            &error.to_string(),
+        )
+    })?;
+    let mut report = QuantizationReport {
+        original_bytes: 0,
+        quantized_bytes: 0,
+        attributes: Vec::new(),
+    };
+    for buffer in &mut mesh_file.buffers {
+        report.original_bytes += get_byte_size(&buffer.data);
+        let quantized = match (&buffer.data, buffer.name.as_str()) {
+            (FileValue::F32Array(values), NORMAL_BUFFER_NAME)
+            | (FileValue::F32Array(values), UV_BUFFER_NAME) => quantize_signed(values),
+            (FileValue::F32Array(values), COLOR_BUFFER_NAME) => quantize_unsigned(values),
+            _ => None,
+        };
+        if let Some(quantized) = quantized {
+            buffer.data = quantized;
+            report.attributes.push(buffer.name.clone());
+        }
+        report.quantized_bytes += get_byte_size(&buffer.data);
+    }
+    let data = serialize(&mesh_file).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not serialize the quantized mesh file.",
+            &error.to_string(),
+        )
+    })?;
+    Ok((data, report))
+}
+
+/// Converts values between `-1` and `1` to normalized `i16`, or returns `None` if some
+/// don't fit.
+fn quantize_signed(values: &[f32]) -> Option<FileValue> {
+    if values.iter().any(|value| !(*value >= -1. && *value <= 1.)) {
+        return None;
+    }
+    let scale = std::i16::MAX as f32;
+    Some(FileValue::I16Array(
+        values
+            .iter()
+            .map(|value| (value * scale).round() as i16)
+            .collect(),
+    ))
+}
+
+/// Converts values between `0` and `1` to normalized `u8`, or returns `None` if some don't
+/// fit.
+fn quantize_unsigned(values: &[f32]) -> Option<FileValue> {
+    if values.iter().any(|value| !(*value >= 0. && *value <= 1.)) {
+        return None;
+    }
+    let scale = std::u8::MAX as f32;
+    Some(FileValue::U8Array(
+        values
+            .iter()
+            .map(|value| (value * scale).round() as u8)
+            .collect(),
+    ))
+}
+
+/// Returns the size of a buffer's values in bytes.
+fn get_byte_size(value: &FileValue) -> usize {
+    match value {
+        FileValue::F32Array(values) => values.len() * 4,
+        FileValue::I16Array(values) => values.len() * 2,
+        FileValue::U8Array(values) => values.len(),
+        FileValue::U16Array(values) => values.len() * 2,
+        FileValue::AssetID(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::fallback::cube_geometry;
+    use crate::utils::constants::VERTEX_BUFFER_NAME;
+    use wtvr3d_file::{BufferFile, ShaderDataType, Triangle};
+
+    /// The fallback cube, with a color per vertex and texture coordinates scaled by
+    /// `uv_scale`.
+    fn cube_file(uv_scale: f32) -> (MeshFile, Vec<u8>) {
+        let (positions, normals, uvs, indexes) = cube_geometry();
+        let colors = (0..positions.len() / 3 * 4)
+            .map(|index| (index % 7) as f32 / 6.)
+            .collect();
+        let uvs = uvs.iter().map(|uv| uv * uv_scale).collect();
+        let buffer = |name: &str, data_type, values| BufferFile {
+            name: name.to_owned(),
+            data_type: data_type,
+            data: FileValue::F32Array(values),
+        };
+        let mesh_file = MeshFile {
+            id: "cube".to_owned(),
+            triangles: indexes
+                .chunks_exact(3)
+                .map(|triangle| Triangle {
+                    vertices: (triangle[0], triangle[1], triangle[2]),
+                })
+                .collect(),
+            buffers: vec![
+                buffer(VERTEX_BUFFER_NAME, ShaderDataType::Vector3, positions),
+                buffer(NORMAL_BUFFER_NAME, ShaderDataType::Vector3, normals),
+                buffer(UV_BUFFER_NAME, ShaderDataType::Vector2, uvs),
+                buffer(COLOR_BUFFER_NAME, ShaderDataType::Vector4, colors),
+            ],
+        };
+        let data = serialize(&mesh_file).unwrap();
+        (mesh_file, data)
+    }
+
+    /// Returns the values of a buffer as the shaders read them.
+    fn read_back(mesh_file: &MeshFile, name: &str) -> Vec<f32> {
+        let buffer = mesh_file
+            .buffers
+            .iter()
+            .find(|buffer| buffer.name == name)
+            .unwrap();
+        match &buffer.data {
+            FileValue::F32Array(values) => values.clone(),
+            FileValue::I16Array(values) => values
+                .iter()
+                .map(|value| (*value as f32 / std::i16::MAX as f32).max(-1.))
+                .collect(),
+            FileValue::U8Array(values) => values
+                .iter()
+                .map(|value| *value as f32 / std::u8::MAX as f32)
+                .collect(),
+            _ => panic!("Unexpected buffer type"),
+        }
+    }
+
+    #[test]
+    fn quantized_attributes_render_within_half_a_step() {
+        let (original, data) = cube_file(1.);
+        let (quantized, report) = quantize_mesh_file(&data).unwrap();
+        let quantized = deserialize::<MeshFile>(&quantized).unwrap();
+        assert_eq!(
+            report.attributes,
+            vec![NORMAL_BUFFER_NAME, UV_BUFFER_NAME, COLOR_BUFFER_NAME]
+        );
+        let tolerances = [
+            (VERTEX_BUFFER_NAME, 0.),
+            (NORMAL_BUFFER_NAME, 0.5 / std::i16::MAX as f32),
+            (UV_BUFFER_NAME, 0.5 / std::i16::MAX as f32),
+            (COLOR_BUFFER_NAME, 0.5 / std::u8::MAX as f32),
+        ];
+        for (name, tolerance) in &tolerances {
+            let expected = read_back(&original, name);
+            let actual = read_back(&quantized, name);
+            assert_eq!(actual.len(), expected.len());
+            for (actual, expected) in actual.iter().zip(&expected) {
+                assert!(
+                    (actual - expected).abs() <= tolerance + std::f32::EPSILON,
+                    "{}: {} != {}",
+                    name,
+                    actual,
+                    expected
+                );
+            }
+        }
+        assert_eq!(quantized.triangles.len(), original.triangles.len());
+    }
+
+    #[test]
+    fn report_counts_the_bytes_saved() {
+        let (_, data) = cube_file(1.);
+        let (_, report) = quantize_mesh_file(&data).unwrap();
+        // 24 vertices: positions and normals have 3 floats, UVs 2 and colors 4.
+        assert_eq!(report.original_bytes, 24 * (3 + 3 + 2 + 4) * 4);
+        assert_eq!(report.quantized_bytes, 24 * (3 * 4 + 3 * 2 + 2 * 2 + 4));
+    }
+
+    #[test]
+    fn out_of_range_texture_coordinates_stay_floats() {
+        let (original, data) = cube_file(4.);
+        let (quantized, report) = quantize_mesh_file(&data).unwrap();
+        let quantized = deserialize::<MeshFile>(&quantized).unwrap();
+        assert!(!report.attributes.iter().any(|name| name == UV_BUFFER_NAME));
+        assert_eq!(
+            read_back(&quantized, UV_BUFFER_NAME),
+            read_back(&original, UV_BUFFER_NAME)
+        );
+        assert!(quantize_mesh_file(&[1, 2, 3]).is_err());
+    }
+}
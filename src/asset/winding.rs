@@ -0,0 +1,313 @@
+//! Detection and correction of the triangle winding of mesh files, whose front faces must be
+//! counter-clockwise to survive back-face culling.
+
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::constants::{NORMAL_BUFFER_NAME, VERTEX_BUFFER_NAME};
+use bincode::{deserialize, serialize};
+use nalgebra::Vector3;
+use wtvr3d_file::{FileValue, MeshFile};
+
+/// Signed volumes closer to `0` than this, relative to the cube of the mesh size, don't
+/// tell the winding of meshes without normals.
+const VOLUME_EPSILON: f32 = 1.0e-6;
+
+/// Winding of the triangles of a mesh file, as detected by `check_winding`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Winding {
+    /// Every triangle is counter-clockwise when seen from the side its normals point to
+    CounterClockwise,
+
+    /// Every triangle is clockwise
+    Clockwise,
+
+    /// Some triangles are clockwise, others counter-clockwise
+    Mixed,
+
+    /// The mesh has no normals and encloses no volume, e.g. a flat mesh, or has no triangles
+    Unknown,
+}
+
+impl Winding {
+    /// Returns the camelCase name of this winding, as reported to JS.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Winding::CounterClockwise => "counterClockwise",
+            Winding::Clockwise => "clockwise",
+            Winding::Mixed => "mixed",
+            Winding::Unknown => "unknown",
+        }
+    }
+}
+
+/// ## WindingReport
+///
+/// Winding of the triangles of a mesh file before `check_winding` fixed it.
+pub struct WindingReport {
+    /// Detected winding
+    pub winding: Winding,
+
+    /// Number of triangles whose winding was flipped
+    pub flipped_triangles: usize,
+}
+
+/// Detects the winding of the triangles of a mesh file. If `fix` is `true`, the clockwise
+/// triangles are flipped and the converted file is returned as well.
+///
+/// With normals, each triangle is compared to the sum of its vertex normals: triangles whose
+/// geometric normal points away from them are clockwise. Without normals, the whole mesh is
+/// assumed to be closed: a negative signed volume means every triangle is clockwise.
+/// Degenerate triangles are ignored.
+///
+/// Tangents are left untouched: flipping the order of the vertices changes neither their
+/// normals nor their texture coordinates, so the tangent frame stays the same.
+/// Fails if the file can't be deserialized.
+pub fn check_winding(data: &[u8], fix: bool) -> Result<(Option<Vec<u8>>, WindingReport), W3DError> {
+    let mut mesh_file = deserialize::<MeshFile>(data).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not deserialize the given mesh file.",
+            &error.to_string(),
+        )
+    })?;
+    let (winding, clockwise) = find_clockwise_triangles(&mesh_file);
+    let mut report = WindingReport {
+        winding: winding,
+        flipped_triangles: 0,
+    };
+    if !fix || clockwise.is_empty() {
+        return Ok((None, report));
+    }
+    for index in clockwise {
+        let vertices = &mut mesh_file.triangles[index].vertices;
+        std::mem::swap(&mut vertices.1, &mut vertices.2);
+        report.flipped_triangles += 1;
+    }
+    let data = serialize(&mesh_file).map_err(|error| {
+        W3DError::with_source(
+            W3DErrorKind::Deserialization,
+            "Could not serialize the fixed mesh file.",
+            &error.to_string(),
+        )
+    })?;
+    Ok((Some(data), report))
+}
+
+/// Returns the winding of a mesh file and the indexes of its clockwise triangles.
+fn find_clockwise_triangles(mesh_file: &MeshFile) -> (Winding, Vec<usize>) {
+    let positions = match read_vectors(mesh_file, VERTEX_BUFFER_NAME) {
+        Some(positions) => positions,
+        None => return (Winding::Unknown, Vec::new()),
+    };
+    let normals = read_vectors(mesh_file, NORMAL_BUFFER_NAME);
+    let mut clockwise = Vec::new();
+    let (mut counter_clockwise_count, mut volume) = (0, 0.);
+    for (index, triangle) in mesh_file.triangles.iter().enumerate() {
+        let (a, b, c) = triangle.vertices;
+        let (a, b, c) = (a as usize, b as usize, c as usize);
+        if a.max(b).max(c) >= positions.len() {
+            continue;
+        }
+        let (pa, pb, pc) = (positions[a], positions[b], positions[c]);
+        volume += pa.dot(&pb.cross(&pc));
+        let face_normal = (pb - pa).cross(&(pc - pa));
+        let vertex_normals = match &normals {
+            Some(normals) if a.max(b).max(c) < normals.len() => {
+                normals[a] + normals[b] + normals[c]
+            }
+            _ => continue,
+        };
+        let agreement = face_normal.dot(&vertex_normals);
+        if agreement < 0. {
+            clockwise.push(index);
+        } else if agreement > 0. {
+            counter_clockwise_count += 1;
+        }
+    }
+    if normals.is_none() {
+        let size = positions
+            .iter()
+            .map(|position| position.amax())
+            .fold(0., f32::max);
+        let threshold = VOLUME_EPSILON * size * size * size;
+        return if volume > threshold {
+            (Winding::CounterClockwise, Vec::new())
+        } else if volume < -threshold {
+            (Winding::Clockwise, (0..mesh_file.triangles.len()).collect())
+        } else {
+            (Winding::Unknown, Vec::new())
+        };
+    }
+    let winding = match (counter_clockwise_count, clockwise.len()) {
+        (0, 0) => Winding::Unknown,
+        (_, 0) => Winding::CounterClockwise,
+        (0, _) => Winding::Clockwise,
+        _ => Winding::Mixed,
+    };
+    (winding, clockwise)
+}
+
+/// Reads a 3 component buffer of a mesh file as vectors, from floats or normalized `i16`.
+fn read_vectors(mesh_file: &MeshFile, name: &str) -> Option<Vec<Vector3<f32>>> {
+    let buffer = mesh_file
+        .buffers
+        .iter()
+        .find(|buffer| buffer.name == name)?;
+    let components = buffer.data_type.get_size() as usize;
+    if components < 3 {
+        return None;
+    }
+    let values: Vec<f32> = match &buffer.data {
+        FileValue::F32Array(values) => values.clone(),
+        FileValue::I16Array(values) => values
+            .iter()
+            .map(|value| (*value as f32 / std::i16::MAX as f32).max(-1.))
+            .collect(),
+        _ => return None,
+    };
+    Some(
+        values
+            .chunks_exact(components)
+            .map(|vector| Vector3::new(vector[0], vector[1], vector[2]))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::fallback::cube_geometry;
+    use crate::utils::constants::UV_BUFFER_NAME;
+    use crate::utils::FrontFace;
+    use nalgebra::{Isometry3, Perspective3, Point3};
+    use web_sys::WebGlRenderingContext;
+    use wtvr3d_file::{BufferFile, ShaderDataType, Triangle};
+
+    /// The fallback cube, with its triangles wound clockwise if `clockwise` is set.
+    fn cube_file(clockwise: bool, with_normals: bool) -> MeshFile {
+        let (positions, normals, uvs, indexes) = cube_geometry();
+        let buffer = |name: &str, data_type, values| BufferFile {
+            name: name.to_owned(),
+            data_type: data_type,
+            data: FileValue::F32Array(values),
+        };
+        let mut buffers = vec![
+            buffer(VERTEX_BUFFER_NAME, ShaderDataType::Vector3, positions),
+            buffer(UV_BUFFER_NAME, ShaderDataType::Vector2, uvs),
+        ];
+        if with_normals {
+            buffers.push(buffer(NORMAL_BUFFER_NAME, ShaderDataType::Vector3, normals));
+        }
+        MeshFile {
+            id: "cube".to_owned(),
+            triangles: indexes
+                .chunks_exact(3)
+                .map(|triangle| Triangle {
+                    vertices: if clockwise {
+                        (triangle[0], triangle[2], triangle[1])
+                    } else {
+                        (triangle[0], triangle[1], triangle[2])
+                    },
+                })
+                .collect(),
+            buffers: buffers,
+        }
+    }
+
+    fn check(mesh_file: &MeshFile, fix: bool) -> (Option<MeshFile>, WindingReport) {
+        let data = serialize(mesh_file).unwrap();
+        let (fixed, report) = check_winding(&data, fix).unwrap();
+        let fixed = fixed.map(|fixed| deserialize::<MeshFile>(&fixed).unwrap());
+        (fixed, report)
+    }
+
+    /// Returns the indexes of the triangles a camera at `eye` looking at the origin draws
+    /// with back-face culling, as the GL does: by the winding of their projection.
+    fn drawn_triangles(
+        mesh_file: &MeshFile,
+        front_face: FrontFace,
+        eye: Point3<f32>,
+    ) -> Vec<usize> {
+        let positions = read_vectors(mesh_file, VERTEX_BUFFER_NAME).unwrap();
+        let view = Isometry3::look_at_rh(&eye, &Point3::origin(), &Vector3::y());
+        let view_projection =
+            Perspective3::new(1., 1., 0.1, 100.).as_matrix() * view.to_homogeneous();
+        let project = |index: u16| {
+            let position =
+                view_projection.transform_point(&Point3::from(positions[index as usize]));
+            (position.x, position.y)
+        };
+        let counter_clockwise_front = front_face.get_gl_mode() == WebGlRenderingContext::CCW;
+        mesh_file
+            .triangles
+            .iter()
+            .enumerate()
+            .filter(|(_, triangle)| {
+                let (a, b, c) = triangle.vertices;
+                let ((ax, ay), (bx, by), (cx, cy)) = (project(a), project(b), project(c));
+                let area = (bx - ax) * (cy - ay) - (cx - ax) * (by - ay);
+                (area > 0.) == counter_clockwise_front
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    #[test]
+    fn winding_is_detected_with_and_without_normals() {
+        for with_normals in &[true, false] {
+            let (_, report) = check(&cube_file(false, *with_normals), false);
+            assert_eq!(report.winding, Winding::CounterClockwise);
+            let (fixed, report) = check(&cube_file(true, *with_normals), false);
+            assert_eq!(report.winding, Winding::Clockwise);
+            assert!(fixed.is_none(), "Nothing is fixed unless asked");
+        }
+        let mut mixed = cube_file(false, true);
+        let vertices = &mut mixed.triangles[0].vertices;
+        std::mem::swap(&mut vertices.1, &mut vertices.2);
+        let (fixed, report) = check(&mixed, true);
+        assert_eq!(report.winding, Winding::Mixed);
+        assert_eq!(report.flipped_triangles, 1);
+        assert_eq!(
+            fixed.unwrap().triangles[0].vertices,
+            cube_file(false, true).triangles[0].vertices
+        );
+    }
+
+    #[test]
+    fn flat_mesh_without_normals_has_unknown_winding() {
+        let mut quad = cube_file(false, false);
+        quad.buffers[0].data =
+            FileValue::F32Array(vec![-1., -1., 0., 1., -1., 0., 1., 1., 0., -1., 1., 0.]);
+        quad.triangles.truncate(2);
+        assert_eq!(check(&quad, true).1.winding, Winding::Unknown);
+        quad.triangles.clear();
+        assert_eq!(check(&quad, true).1.winding, Winding::Unknown);
+    }
+
+    #[test]
+    fn clockwise_cube_renders_with_the_fix_or_the_front_face() {
+        let eye = Point3::new(2., 3., 4.);
+        let expected = drawn_triangles(&cube_file(false, true), FrontFace::CounterClockwise, eye);
+        assert_eq!(expected.len(), 6, "Three faces of the cube are seen");
+
+        let clockwise = cube_file(true, true);
+        let culled = drawn_triangles(&clockwise, FrontFace::CounterClockwise, eye);
+        assert!(
+            culled.iter().all(|index| !expected.contains(index)),
+            "The back faces are drawn"
+        );
+
+        let (fixed, report) = check(&clockwise, true);
+        assert_eq!(report.flipped_triangles, 12);
+        let fixed = fixed.unwrap();
+        assert_eq!(
+            drawn_triangles(&fixed, FrontFace::CounterClockwise, eye),
+            expected
+        );
+        assert_eq!(check(&fixed, false).1.winding, Winding::CounterClockwise);
+
+        assert_eq!(
+            drawn_triangles(&clockwise, FrontFace::Clockwise, eye),
+            expected
+        );
+    }
+}
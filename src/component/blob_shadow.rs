@@ -0,0 +1,77 @@
+//! Cheap soft shadow drawn as a dark ellipse on the ground under an entity.
+
+use nalgebra::Vector3;
+use specs::{Component, DenseVecStorage, Entity};
+
+/// ## BlobShadow
+///
+/// Darkens a disc of the ground plane under its entity's world position, with a soft radial
+/// falloff. A quick alternative to shadow mapping for stylized scenes.
+///
+/// The ground is the horizontal plane at `ground_height`, or at the world height of
+/// `ground_entity` if it's set. The shadow stays on that plane whatever the entity's height,
+/// and fades out as the entity rises, until it disappears at `fade_height` above the ground.
+/// Nothing is drawn while the entity is below the ground.
+///
+/// The projected position and opacity are updated by the `BlobShadowSystem` every frame.
+#[derive(Clone)]
+pub struct BlobShadow {
+    /// Radius of the shadow on the ground, in world units
+    pub radius: f32,
+
+    /// Opacity of the shadow's center when the entity touches the ground, between 0 and 1
+    pub opacity: f32,
+
+    /// World height of the ground plane, used if `ground_entity` isn't set
+    pub ground_height: f32,
+
+    /// Entity whose world height is the ground plane's
+    pub ground_entity: Option<Entity>,
+
+    /// Height above the ground at which the shadow has completely faded out
+    pub fade_height: f32,
+
+    /// Center of the shadow on the ground and its opacity for this frame, if visible
+    projection: Option<(Vector3<f32>, f32)>,
+}
+
+impl BlobShadow {
+    /// Constructor. The ground is at height `0` and the shadow fades out at four times its
+    /// radius above it.
+    pub fn new(radius: f32, opacity: f32) -> BlobShadow {
+        BlobShadow {
+            radius: radius,
+            opacity: opacity,
+            ground_height: 0.0,
+            ground_entity: None,
+            fade_height: radius * 4.0,
+            projection: None,
+        }
+    }
+
+    /// Projects the shadow of an entity at `position` on the ground plane at `ground_height`.
+    pub fn update(&mut self, position: &Vector3<f32>, ground_height: f32) -> () {
+        let height = position.y - ground_height;
+        let fade = if self.fade_height > 0.0 {
+            1.0 - height / self.fade_height
+        } else {
+            1.0
+        };
+        let opacity = self.opacity.max(0.0).min(1.0) * fade.max(0.0).min(1.0);
+        self.projection = if height >= 0.0 && opacity > 0.0 && self.radius > 0.0 {
+            Some((Vector3::new(position.x, ground_height, position.z), opacity))
+        } else {
+            None
+        };
+    }
+
+    /// Returns the center of the shadow on the ground and its opacity, or `None` if it
+    /// shouldn't be drawn this frame.
+    pub fn get_projection(&self) -> Option<(Vector3<f32>, f32)> {
+        self.projection
+    }
+}
+
+impl Component for BlobShadow {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -0,0 +1,41 @@
+//! Visibility related components, overriding what is computed from mesh data.
+
+use crate::math::{Aabb, BoundingSphere};
+use specs::{Component, DenseVecStorage, NullStorage};
+
+/// User-specified bounds of an entity, in local space.
+///
+/// Preferred over the bounds computed from the vertices of the entity's `MeshData` when
+/// present. Useful for meshes deformed at render time (skinning, vertex animation), whose
+/// static bounds are wrong.
+#[derive(Clone)]
+pub struct Bounds {
+    /// Local space bounding sphere
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    /// Creates bounds from a local space sphere.
+    pub fn from_sphere(sphere: BoundingSphere) -> Bounds {
+        Bounds { sphere: sphere }
+    }
+
+    /// Creates bounds from a local space box, using the sphere enclosing it.
+    pub fn from_aabb(aabb: &Aabb) -> Bounds {
+        Bounds {
+            sphere: aabb.to_bounding_sphere(),
+        }
+    }
+}
+
+impl Component for Bounds {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Flag component for entities that must never be culled (skyboxes, full-screen effects...).
+#[derive(Default)]
+pub struct AlwaysVisible;
+
+impl Component for AlwaysVisible {
+    type Storage = NullStorage<Self>;
+}
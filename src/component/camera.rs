@@ -1,8 +1,35 @@
 //! Camera component. Used as the point of vue to render the scene.
 
-use nalgebra::{Isometry3, Matrix4, Perspective3, Point3, Vector3};
+use crate::utils::ToneMapping;
+use nalgebra::{Isometry3, Matrix4, Perspective3, Point3, Translation3, Vector3};
 use specs::{Component, VecStorage};
 
+/// Matrices and position of a camera, computed once per frame and read by the renderer for
+/// every material, instead of converting the projection and view again for each one.
+#[derive(Clone, Debug)]
+pub struct FrameMatrices {
+    /// View matrix, including the shake offset
+    pub view: Matrix4<f32>,
+
+    /// Projection matrix
+    pub projection: Matrix4<f32>,
+
+    /// Product of the projection and view matrices
+    pub view_projection: Matrix4<f32>,
+
+    /// Position of the camera's eye in world space
+    pub eye_position: Vector3<f32>,
+
+    /// Factor applied to colors before tone mapping
+    pub exposure: f32,
+}
+
+impl Default for FrameMatrices {
+    fn default() -> FrameMatrices {
+        Camera::default().get_frame_matrices()
+    }
+}
+
 /// Represents a Camera in the scene, with its projection data.
 /// Might be improved in the future to include orthographic mode.
 #[derive(Clone)]
@@ -14,6 +41,19 @@ pub struct Camera {
     /// ⚠ Will be removed in favor of a normal transform component for the camera
     // ⭕ TODO : move this in a transform component
     view: Isometry3<f32>,
+
+    /// Offset applied on top of the view, in the camera's own space, e.g. by a `CameraShake`.
+    /// Never accumulated into `view`.
+    shake_offset: Isometry3<f32>,
+
+    /// Factor applied to colors before tone mapping, `1` by default.
+    exposure: f32,
+
+    /// Operator applied by `tone_map` in fragment shaders, when rendering from this camera.
+    tone_mapping: ToneMapping,
+
+    /// If `true`, the aspect ratio is kept when the viewport is resized.
+    aspect_ratio_locked: bool,
 }
 
 impl Camera {
@@ -31,29 +71,142 @@ impl Camera {
         Camera {
             projection: projection,
             view: view,
+            shake_offset: Isometry3::identity(),
+            exposure: 1.0,
+            tone_mapping: ToneMapping::None,
+            aspect_ratio_locked: false,
         }
     }
 
-    /// Setter for the aspect_ration of this camera. Useful when the viewport size changes.
+    /// Returns the distances of the near and far clipping planes.
+    pub fn get_near_far(&self) -> (f32, f32) {
+        (self.projection.znear(), self.projection.zfar())
+    }
+
+    /// Setter for the aspect_ration of this camera. Useful when the viewport size changes.  
+    /// Ratios that are not positive and finite, e.g. from an area of zero size, are ignored,
+    /// since they would make the projection matrix NaN.
     pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) -> () {
+        if !(aspect_ratio.is_finite() && aspect_ratio > 0.0) {
+            warn_throttled!(
+                5000,
+                "Ignored invalid camera aspect ratio {}.",
+                aspect_ratio
+            );
+            return;
+        }
         self.projection.set_aspect(aspect_ratio);
     }
 
-    /// Getter for the view-projection matrix. Returns None if the `vp_matrix` is marked as `dirty`.
+    /// Getter for the aspect ratio of the projection.
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.projection.aspect()
+    }
+
+    /// Sets whether the aspect ratio is kept when the viewport is resized, instead of
+    /// following it.
+    pub fn set_aspect_ratio_locked(&mut self, locked: bool) -> () {
+        self.aspect_ratio_locked = locked;
+    }
+
+    /// Returns `true` if the aspect ratio is kept when the viewport is resized.
+    pub fn is_aspect_ratio_locked(&self) -> bool {
+        self.aspect_ratio_locked
+    }
+
+    /// Computes the view-projection matrix, including the shake offset.
     pub fn get_vp_matrix(&self) -> Matrix4<f32> {
-        self.projection.to_homogeneous() * self.view.to_homogeneous()
+        self.projection.to_homogeneous() * self.get_offset_view().to_homogeneous()
     }
 
+    /// Computes the view, projection and view-projection matrices and the eye position at
+    /// once, converting each matrix a single time.
+    pub fn get_frame_matrices(&self) -> FrameMatrices {
+        let offset_view = self.get_offset_view();
+        let view = offset_view.to_homogeneous();
+        let projection = self.projection.to_homogeneous();
+        debug_assert!(
+            projection
+                .iter()
+                .chain(view.iter())
+                .all(|value| value.is_finite()),
+            "The camera matrices are not finite."
+        );
+        FrameMatrices {
+            view: view,
+            projection: projection,
+            view_projection: projection * view,
+            eye_position: offset_view.inverse().translation.vector,
+            exposure: self.exposure,
+        }
+    }
+
+    /// Computes the projection matrix.
     pub fn get_projection_matrix(&self) -> Matrix4<f32> {
         self.projection.to_homogeneous()
     }
+
+    /// Computes the view matrix, including the shake offset.
     pub fn get_view_matrix(&self) -> Matrix4<f32> {
-        self.view.to_homogeneous()
+        self.get_offset_view().to_homogeneous()
     }
 
+    /// Sets the offset applied on top of the view, in the camera's own space.
+    pub fn set_shake_offset(&mut self, offset: &Isometry3<f32>) -> () {
+        self.shake_offset = offset.clone();
+    }
+
+    /// Getter for the offset applied on top of the view.
+    pub fn get_shake_offset(&self) -> &Isometry3<f32> {
+        &self.shake_offset
+    }
+
+    /// Setter for `exposure`
+    pub fn set_exposure(&mut self, exposure: f32) -> () {
+        self.exposure = exposure;
+    }
+
+    /// Getter for `exposure`
+    pub fn get_exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Setter for `tone_mapping`
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) -> () {
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Getter for `tone_mapping`
+    pub fn get_tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    /// Getter for the view, without the shake offset.
+    pub fn get_view(&self) -> &Isometry3<f32> {
+        &self.view
+    }
+
+    /// Getter for the translation of the view, which is not the eye position; see
+    /// `get_eye_position`.
     pub fn get_position(&self) -> &Vector3<f32> {
         &self.view.translation.vector
     }
+
+    /// Returns the position of the camera's eye in world space.
+    pub fn get_eye_position(&self) -> Vector3<f32> {
+        self.get_offset_view().inverse().translation.vector
+    }
+
+    /// Moves the camera by `-shift` in world space, when the scene's origin is moved to
+    /// `shift`.
+    pub fn shift_origin(&mut self, shift: &Vector3<f32>) -> () {
+        self.view = self.view * Translation3::from(*shift);
+    }
+
+    /// Returns the view with the shake offset applied in camera space.
+    fn get_offset_view(&self) -> Isometry3<f32> {
+        self.shake_offset.inverse() * self.view
+    }
 }
 
 impl Default for Camera {
@@ -72,3 +225,50 @@ impl Default for Camera {
 impl Component for Camera {
     type Storage = VecStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new(
+            2.,
+            1.,
+            0.1,
+            100.,
+            &Point3::new(1., 2., 5.),
+            &Point3::origin(),
+        )
+    }
+
+    #[test]
+    fn frame_matrices_match_the_separate_getters() {
+        let mut camera = camera();
+        camera.set_shake_offset(&Isometry3::translation(0.1, -0.2, 0.));
+        camera.set_exposure(0.5);
+        let frame_matrices = camera.get_frame_matrices();
+        assert_eq!(frame_matrices.view, camera.get_view_matrix());
+        assert_eq!(frame_matrices.projection, camera.get_projection_matrix());
+        assert_eq!(frame_matrices.view_projection, camera.get_vp_matrix());
+        assert_eq!(frame_matrices.eye_position, camera.get_eye_position());
+        assert_eq!(frame_matrices.exposure, 0.5);
+    }
+
+    #[test]
+    fn invalid_aspect_ratios_are_ignored() {
+        let mut camera = camera();
+        let projection = camera.get_projection_matrix();
+        for aspect_ratio in &[0., std::f32::NAN, std::f32::INFINITY, -1.] {
+            camera.set_aspect_ratio(*aspect_ratio);
+        }
+        assert_eq!(camera.get_aspect_ratio(), 2.);
+        assert_eq!(camera.get_projection_matrix(), projection);
+    }
+
+    #[test]
+    fn eye_position_is_the_camera_position() {
+        let camera = camera();
+        let eye_position = camera.get_frame_matrices().eye_position;
+        assert!((eye_position - Vector3::new(1., 2., 5.)).norm() < 1e-5);
+    }
+}
@@ -0,0 +1,88 @@
+//! Procedural camera shake, applied on top of a camera's view without altering it.
+
+use specs::{Component, HashMapStorage};
+
+/// Default number of noise oscillations per second.
+pub const DEFAULT_SHAKE_FREQUENCY: f32 = 15.0;
+
+/// Default rotation amplitude, in radians per world unit of positional amplitude.
+pub const DEFAULT_SHAKE_ROTATION_RATIO: f32 = 0.1;
+
+/// ## CameraShake
+///
+/// Shakes the `Camera` of its entity with smooth noise, as an offset in the camera's own space
+/// that is recomputed every frame by the `CameraShakeSystem`. The camera's view itself is never
+/// modified, so the shake composes with whatever positions the camera and stops cleanly.
+///
+/// The intensity starts at `1`, decays exponentially at `decay` per second and, if `duration`
+/// is set, fades linearly to `0` at the end of it. The component is then removed.
+#[derive(Clone)]
+pub struct CameraShake {
+    /// Maximum positional offset, in world units
+    pub amplitude: f32,
+
+    /// Maximum rotational offset around each axis, in radians
+    pub rotation_amplitude: f32,
+
+    /// Number of noise oscillations per second
+    pub frequency: f32,
+
+    /// Exponential decay rate of the intensity, per second. `0` keeps it constant.
+    pub decay: f32,
+
+    /// Seed of the noise, so that several shakes don't move in sync
+    pub seed: u32,
+
+    /// Total duration of the shake in seconds, `None` to shake until the component is removed
+    pub duration: Option<f32>,
+
+    /// Time elapsed since the shake started, in seconds
+    elapsed: f32,
+}
+
+impl CameraShake {
+    /// Constructor. Shakes endlessly, with a rotation amplitude proportional to `amplitude`.
+    pub fn new(amplitude: f32, frequency: f32, decay: f32, seed: u32) -> CameraShake {
+        CameraShake {
+            amplitude: amplitude,
+            rotation_amplitude: amplitude * DEFAULT_SHAKE_ROTATION_RATIO,
+            frequency: frequency,
+            decay: decay,
+            seed: seed,
+            duration: None,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Advances the shake by `delta` seconds.
+    pub fn advance(&mut self, delta: f32) -> () {
+        self.elapsed += delta;
+    }
+
+    /// Returns the time elapsed since the shake started, in seconds.
+    pub fn get_elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Returns the current intensity, between 0 and 1.
+    pub fn get_intensity(&self) -> f32 {
+        let decay = (-self.decay.max(0.0) * self.elapsed).exp();
+        match self.duration {
+            Some(duration) if duration > 0.0 => decay * (1.0 - self.elapsed / duration).max(0.0),
+            Some(_) => 0.0,
+            None => decay,
+        }
+    }
+
+    /// Returns `true` once the shake has run for its whole duration.
+    pub fn is_finished(&self) -> bool {
+        match self.duration {
+            Some(duration) => self.elapsed >= duration,
+            None => false,
+        }
+    }
+}
+
+impl Component for CameraShake {
+    type Storage = HashMapStorage<Self>;
+}
@@ -0,0 +1,58 @@
+//! Constraint components, making an entity track another one.
+
+use nalgebra::Vector3;
+use specs::{Component, Entity, HashMapStorage};
+
+/// Makes an entity follow a target entity, at a given offset.
+///
+/// The follower's local `Transform` is driven by the `ConstraintSystem` from the target's
+/// world matrix, so followers are meant to be root entities (without `TransformParent`).  
+/// If the target entity is deleted, the `Follow` component is removed.
+#[derive(Clone)]
+pub struct Follow {
+    /// Entity to follow
+    pub target: Entity,
+
+    /// Offset from the target's world position. Expressed in the target's space if
+    /// `follow_rotation` is `true`, in world space otherwise.
+    pub offset: Vector3<f32>,
+
+    /// If `true`, the follower also copies the target's rotation.
+    pub follow_rotation: bool,
+
+    /// Smoothing time constant in seconds. `0` snaps the follower to its target every frame;
+    /// greater values make it catch up more slowly (about 63% of the distance per `smoothing` seconds).
+    pub smoothing: f32,
+}
+
+impl Follow {
+    /// Constructor. The rotation is not followed by default.
+    pub fn new(target: Entity, offset: Vector3<f32>, smoothing: f32) -> Follow {
+        Follow {
+            target: target,
+            offset: offset,
+            follow_rotation: false,
+            smoothing: smoothing,
+        }
+    }
+}
+
+/// Makes an entity rotate so that its local +Z axis points towards a target entity.
+///
+/// If the target entity is deleted, the `LookAtTarget` component is removed.
+#[derive(Clone)]
+pub struct LookAtTarget {
+    /// Entity to look at
+    pub target: Entity,
+
+    /// Up vector used to compute the rotation, in world space.
+    pub up: Vector3<f32>,
+}
+
+impl Component for Follow {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Component for LookAtTarget {
+    type Storage = HashMapStorage<Self>;
+}
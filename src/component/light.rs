@@ -1,25 +1,141 @@
 //! Light components for lighting the scene
 
-use nalgebra::Vector3;
+use nalgebra::{Vector3, Vector4};
 use specs::{Component, HashMapStorage};
+use std::f32::consts::PI;
 
 /// Directional lights. Does not depend on position and lights the scene in an uniform way
 #[derive(Clone)]
 pub struct Light {
     pub color: Vector3<f32>,
     pub intensity: f32,
-    pub attenuation: f32,
+
+    /// How the light fades with distance. Only used by point lights.
+    pub attenuation: Attenuation,
+}
+
+/// ## Attenuation
+///
+/// Falloff of a point light with the distance `d` to the lit fragment.  
+/// Every model is sent to shaders as a single `vec4 attenuation` Light field holding
+/// `(constant, linear, quadratic, range)`, from which the built-in lit shaders compute:
+///
+/// ```glsl
+/// float falloff = 1.0 / max(c + l * d + q * d * d, 0.0001);
+/// if (range > 0.0) {
+///     float ratio = d / range;
+///     float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+///     falloff *= window * window;
+/// }
+/// ```
+///
+/// Lights using different models can therefore be mixed in the same shader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Attenuation {
+    /// The light doesn't fade: `(1, 0, 0, 0)`
+    None,
+
+    /// Classic `1 / (constant + linear * d + quadratic * d²)` falloff, as found in most DCC
+    /// tools and older engines. The light never reaches zero.
+    Coefficients {
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+
+    /// Physically based inverse square falloff `1 / (1 + d²)`, smoothly windowed so that it
+    /// reaches zero at `range` world units: `(1, 0, 1, range)`.
+    Range(f32),
+}
+
+impl Attenuation {
+    /// Returns the `(constant, linear, quadratic, range)` uniform value for this model.
+    pub fn to_vector4(&self) -> Vector4<f32> {
+        match *self {
+            Attenuation::None => Vector4::new(1.0, 0.0, 0.0, 0.0),
+            Attenuation::Coefficients {
+                constant,
+                linear,
+                quadratic,
+            } => Vector4::new(constant, linear, quadratic, 0.0),
+            Attenuation::Range(range) => Vector4::new(1.0, 0.0, 1.0, range.max(0.0)),
+        }
+    }
+
+    /// Returns the distance beyond which the light has no effect, if it has one.
+    pub fn get_range(&self) -> Option<f32> {
+        match *self {
+            Attenuation::Range(range) if range > 0.0 => Some(range),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Direction(pub Vector3<f32>);
 
+/// Turns an ambient `Light` into a hemisphere light: surfaces facing `up` receive the light's
+/// color (the sky), those facing away receive `ground_color`, with a smooth gradient between.
+#[derive(Clone)]
+pub struct Hemisphere {
+    pub ground_color: Vector3<f32>,
+    pub up: Vector3<f32>,
+}
+
+/// Turns a `Light` with a `Direction` and a `Transform` into a spot light, lighting a cone
+/// around its direction.
 #[derive(Clone)]
 pub struct Cone {
+    /// Fraction of the cone, from its edge, over which the light fades out: `0` for a hard
+    /// edge, `1` to fade from the axis
     pub blend: f32,
+
+    /// Angle between the axis of the cone and its edge, in radians
     pub angle: f32,
 }
 
+impl Cone {
+    /// Constructor.
+    pub fn new(angle: f32, blend: f32) -> Cone {
+        Cone {
+            blend: blend,
+            angle: angle,
+        }
+    }
+
+    /// Creates a cone fully lit up to `inner` radians from its axis, and fading out up to
+    /// `outer` radians. `inner` must not be larger than `outer`.
+    pub fn from_angles(inner: f32, outer: f32) -> Cone {
+        let blend = if outer > 0.0 {
+            (outer - inner) / outer
+        } else {
+            0.0
+        };
+        Cone::new(outer, blend)
+    }
+}
+
+/// Returns the luminous intensity in candela of a point light emitting `lumens` uniformly
+/// in every direction: `lumens / 4π`. A 1700 lm bulb is about 135.3 cd.
+pub fn point_lumens_to_candela(lumens: f32) -> f32 {
+    lumens / (4.0 * PI)
+}
+
+/// Returns the luminous intensity in candela of a spot light emitting `lumens` uniformly in
+/// a cone of half angle `angle` radians: `lumens / (2π (1 - cos(angle)))`. A 1000 lm spot
+/// with a 30° half angle is about 1188 cd; a 180° half angle matches a point light.
+pub fn spot_lumens_to_candela(lumens: f32, angle: f32) -> f32 {
+    let solid_angle = 2.0 * PI * (1.0 - angle.min(PI).cos());
+    lumens / solid_angle.max(std::f32::EPSILON)
+}
+
+/// Returns the exposure matching an `ev100` exposure value, for lights in physical units:
+/// `1 / (1.2 · 2^ev100)`, the factor under which a luminance saturates the sensor. A sunny
+/// day (EV100 15) gives about 2.54e-5, an indoor scene (EV100 7) about 6.51e-3.
+pub fn ev100_to_exposure(ev100: f32) -> f32 {
+    1.0 / (1.2 * 2f32.powf(ev100))
+}
+
 impl Component for Light {
     type Storage = HashMapStorage<Light>;
 }
@@ -28,6 +144,53 @@ impl Component for Direction {
     type Storage = HashMapStorage<Direction>;
 }
 
+impl Component for Hemisphere {
+    type Storage = HashMapStorage<Hemisphere>;
+}
+
 impl Component for Cone {
     type Storage = HashMapStorage<Cone>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) -> () {
+        assert!(
+            ((value - expected) / expected).abs() < 1e-3,
+            "{} instead of {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn lumens_convert_to_candela() {
+        assert_close(point_lumens_to_candela(1700.), 135.28);
+        assert_close(spot_lumens_to_candela(1000., PI / 6.), 1187.9);
+        assert_close(
+            spot_lumens_to_candela(1700., PI),
+            point_lumens_to_candela(1700.),
+        );
+        assert!(spot_lumens_to_candela(1000., 0.).is_finite());
+    }
+
+    #[test]
+    fn ev100_converts_to_exposure() {
+        assert_close(ev100_to_exposure(15.), 2.543e-5);
+        assert_close(ev100_to_exposure(7.), 6.510e-3);
+        assert_close(ev100_to_exposure(0.), 1. / 1.2);
+    }
+
+    #[test]
+    fn only_positive_ranges_limit_lights() {
+        assert_eq!(Attenuation::Range(5.).get_range(), Some(5.));
+        assert_eq!(Attenuation::Range(0.).get_range(), None);
+        assert_eq!(Attenuation::None.get_range(), None);
+        assert_eq!(
+            Attenuation::Range(-1.).to_vector4(),
+            Vector4::new(1., 0., 1., 0.)
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Level of detail component, switching the mesh data of an entity with its distance to the camera.
+
+use specs::{Component, DenseVecStorage};
+
+/// Relative margin around each switching distance, to avoid popping back and forth when the
+/// camera stays close to a boundary.
+const LOD_HYSTERESIS: f32 = 0.05;
+
+/// ## Lod
+///
+/// Holds the levels of detail of an entity's `Mesh`, ordered from the most detailed:
+/// each level is a `MeshData` index and the maximum distance to the camera it's used at.  
+/// Beyond the last level's distance, the last level is kept unless a cull distance is set,
+/// in which case the entity is not rendered at all.
+///
+/// Every `MeshData` must be compatible with the `Mesh`'s material.
+#[derive(Clone)]
+pub struct Lod {
+    /// `MeshData` index of each level, from the most detailed
+    mesh_data_ids: Vec<usize>,
+
+    /// Maximum distance of each level, increasing
+    distances: Vec<f32>,
+
+    /// Distance beyond which the entity is not rendered
+    pub cull_distance: Option<f32>,
+
+    /// Index of the level currently used
+    current_level: usize,
+
+    /// `true` if the entity is beyond its cull distance
+    culled: bool,
+}
+
+impl Lod {
+    /// Constructor, from `(mesh data index, max distance)` pairs.
+    /// Levels are sorted by increasing distance.
+    pub fn new(mut levels: Vec<(usize, f32)>, cull_distance: Option<f32>) -> Lod {
+        levels.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        Lod {
+            mesh_data_ids: levels.iter().map(|level| level.0).collect(),
+            distances: levels.iter().map(|level| level.1).collect(),
+            cull_distance: cull_distance,
+            current_level: 0,
+            culled: false,
+        }
+    }
+
+    /// Returns the `MeshData` indexes of every level.
+    pub fn get_mesh_data_ids(&self) -> &[usize] {
+        &self.mesh_data_ids
+    }
+
+    /// Returns the `MeshData` index of the level currently used, if any.
+    pub fn get_current_mesh_data_id(&self) -> Option<usize> {
+        self.mesh_data_ids.get(self.current_level).cloned()
+    }
+
+    /// Returns `true` if the entity is beyond its cull distance and shouldn't be rendered.
+    pub fn is_culled(&self) -> bool {
+        self.culled
+    }
+
+    /// Selects the level to use at `distance` from the camera.
+    ///
+    /// Switching to a farther level only happens once the distance exceeds the boundary by
+    /// `LOD_HYSTERESIS`, and switching back once it is below it by the same margin.
+    pub fn update(&mut self, distance: f32) -> () {
+        let mut level = 0;
+        for (index, max_distance) in self.distances.iter().enumerate() {
+            if index + 1 == self.distances.len() {
+                break;
+            }
+            if distance > threshold(*max_distance, self.current_level > index) {
+                level = index + 1;
+            } else {
+                break;
+            }
+        }
+        self.current_level = level;
+        self.culled = match self.cull_distance {
+            Some(cull_distance) => distance > threshold(cull_distance, self.culled),
+            None => false,
+        };
+    }
+}
+
+/// Returns the distance at which a boundary is crossed, given which side of it we are on.
+fn threshold(boundary: f32, beyond: bool) -> f32 {
+    if beyond {
+        boundary * (1.0 - LOD_HYSTERESIS)
+    } else {
+        boundary * (1.0 + LOD_HYSTERESIS)
+    }
+}
+
+impl Component for Lod {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -1,5 +1,6 @@
 //! Representation of a mesh in a scene
 
+use crate::error::{W3DError, W3DErrorKind};
 use crate::renderer::{LightConfiguration, Renderer};
 use specs::{Component, VecStorage};
 use std::cell::RefCell;
@@ -7,9 +8,13 @@ use std::rc::Rc;
 
 /// Mesh component for an entity in the 3D scene.  
 /// Links some `MeshData` to some `MaterialInstance`.
+///
+/// If the `MeshData` is split in sub-meshes, each sub-mesh is drawn with its own
+/// `MaterialInstance`: the first one is used for sub-mesh 0, the second for sub-mesh 1, etc.
+#[derive(Clone)]
 pub struct Mesh {
-    material: usize,
-    material_instance: usize,
+    /// `(MaterialInstance id, Material id)` of each sub-mesh, never empty
+    materials: Vec<(usize, usize)>,
     mesh_data: usize,
 }
 
@@ -18,18 +23,33 @@ impl Mesh {
     pub fn new(mesh_data_id: usize, material_instance_id: usize, material_id: usize) -> Mesh {
         Mesh {
             mesh_data: mesh_data_id,
-            material: material_id,
-            material_instance: material_instance_id,
+            materials: vec![(material_instance_id, material_id)],
         }
     }
-    /// Getter for material
+
+    /// Constructor for a `MeshData` split in sub-meshes. Uses a `MeshData` id and the
+    /// `(MaterialInstance id, Material id)` of each sub-mesh, which must not be empty.
+    pub fn with_sub_meshes(mesh_data_id: usize, materials: Vec<(usize, usize)>) -> Mesh {
+        assert!(!materials.is_empty(), "A mesh needs at least one material.");
+        Mesh {
+            mesh_data: mesh_data_id,
+            materials: materials,
+        }
+    }
+
+    /// Getter for material instance of the first sub-mesh
     pub fn get_material_instance_id(&self) -> &usize {
-        &self.material_instance
+        &self.materials[0].0
     }
 
-    /// Getter for material
+    /// Getter for material of the first sub-mesh
     pub fn get_material_id(&self) -> &usize {
-        &self.material
+        &self.materials[0].1
+    }
+
+    /// Returns the `(MaterialInstance id, Material id)` of every sub-mesh, in order.
+    pub fn get_sub_mesh_materials(&self) -> &[(usize, usize)] {
+        &self.materials
     }
 
     /// Getter for mesh_data
@@ -37,50 +57,75 @@ impl Mesh {
         &self.mesh_data
     }
 
-    /// Compiles the material and fetches all the necessary uniform and attribute locations
+    /// Setter for mesh_data. The new `MeshData` must use the same attributes as the
+    /// previous one, since the material is kept.
+    pub fn set_mesh_data_id(&mut self, mesh_data_id: usize) -> () {
+        self.mesh_data = mesh_data_id;
+    }
+
+    /// Compiles the material and its passes, and fetches all the necessary uniform and
+    /// attribute locations.  
+    /// Attribute locations are also looked up for `other_mesh_data_ids`, the `MeshData` this
+    /// mesh may switch to (e.g. its levels of detail), so that switching costs nothing.
     pub fn compile_material(
         &self,
         renderer_ref: Rc<RefCell<Renderer>>,
         light_config: &LightConfiguration,
-    ) -> Result<(), String> {
+        other_mesh_data_ids: &[usize],
+    ) -> Result<(), W3DError> {
         let renderer = renderer_ref.borrow();
-        if let Some(material_rc) = renderer
-            .get_asset_registry()
-            .get_material_with_index(self.material)
-        {
+        for (material_instance_id, material_id) in &self.materials {
+            let passes = match renderer
+                .get_asset_registry()
+                .get_material_with_index(*material_id)
             {
-                let mut material = material_rc.borrow_mut();
-                if material.should_compile(light_config) {
-                    match material.compile(renderer.get_webgl_context(), light_config) {
-                        Err(message) => {
-                            return Err(message);
+                Some(material_rc) => material_rc.borrow().get_passes().to_vec(),
+                None => {
+                    return Err(W3DError::new(
+                        W3DErrorKind::MissingAsset,
+                        "Material could not be found. Has it been registered yet?",
+                    ))
+                }
+            };
+            for material_id in std::iter::once(material_id).chain(&passes) {
+                if let Some(material_rc) = renderer
+                    .get_asset_registry()
+                    .get_material_with_index(*material_id)
+                {
+                    material_rc
+                        .borrow_mut()
+                        .compile_if_needed(renderer.get_webgl_context(), light_config)?;
+                    let mesh_data_ids = std::iter::once(&self.mesh_data).chain(other_mesh_data_ids);
+                    for mesh_data_id in mesh_data_ids {
+                        if let Some(mesh) = renderer
+                            .get_asset_registry()
+                            .get_mesh_data_with_index(*mesh_data_id)
+                        {
+                            mesh.borrow_mut().lookup_locations(
+                                renderer.get_webgl_context(),
+                                material_rc.clone(),
+                            );
                         }
-                        _ => {}
                     }
+                } else {
+                    return Err(W3DError::new(
+                        W3DErrorKind::MissingAsset,
+                        "Material pass could not be found. Has it been registered yet?",
+                    ));
                 }
-                material.lookup_locations(renderer.get_webgl_context(), light_config);
-                material.light_configuration = light_config.clone();
             }
-            if let Some(mesh) = renderer
+            if let Some(material_instance_rc) = renderer
                 .get_asset_registry()
-                .get_mesh_data_with_index(self.mesh_data)
+                .get_material_instance_with_index(*material_instance_id)
             {
-                mesh.borrow_mut()
-                    .lookup_locations(renderer.get_webgl_context(), material_rc.clone());
+                let mut material_instance = material_instance_rc.borrow_mut();
+                material_instance.lookup_locations(renderer.get_webgl_context(), light_config);
+            } else {
+                return Err(W3DError::new(
+                    W3DErrorKind::MissingAsset,
+                    "Material Instance could not be found. Has it been registered yet?",
+                ));
             }
-        } else {
-            return Err("Material could not be found. Has it been registered yet?".to_owned());
-        }
-        if let Some(material_instance_rc) = renderer
-            .get_asset_registry()
-            .get_material_instance_with_index(self.material_instance)
-        {
-            let mut material_instance = material_instance_rc.borrow_mut();
-            material_instance.lookup_locations(renderer.get_webgl_context(), light_config);
-        } else {
-            return Err(
-                "Material Instance could not be found. Has it been registered yet?".to_owned(),
-            );
         }
         Ok(())
     }
@@ -1,11 +1,40 @@
 //! Components that are attached to entities in the 3D scene.
 
+mod blob_shadow;
+mod bounds;
 mod camera;
+mod camera_shake;
+mod constraint;
 mod light;
+mod lod;
 mod mesh;
+mod opacity;
+mod overlay;
+mod particle;
+mod path_follower;
+mod selected;
+mod sprite;
 mod transform;
+mod uniform_overrides;
+mod velocity;
 
-pub use camera::Camera;
-pub use light::{Cone, Direction, Light};
+pub use blob_shadow::BlobShadow;
+pub use bounds::{AlwaysVisible, Bounds};
+pub use camera::{Camera, FrameMatrices};
+pub use camera_shake::{CameraShake, DEFAULT_SHAKE_FREQUENCY};
+pub use constraint::{Follow, LookAtTarget};
+pub use light::{
+    ev100_to_exposure, point_lumens_to_candela, spot_lumens_to_candela, Attenuation, Cone,
+    Direction, Hemisphere, Light,
+};
+pub use lod::Lod;
 pub use mesh::Mesh;
-pub use transform::{DirtyTransform, Enabled, Transform, TransformParent};
+pub use opacity::Opacity;
+pub use overlay::Overlay;
+pub use particle::{Particle, ParticleEmitter, PARTICLE_VERTEX_SIZE};
+pub use path_follower::PathFollower;
+pub use selected::{Selected, Unpickable};
+pub use sprite::Sprite;
+pub use transform::{DirtyTransform, EffectivelyDisabled, Enabled, Transform, TransformParent};
+pub use uniform_overrides::{UniformOverrideValue, UniformOverrides};
+pub use velocity::Velocity;
@@ -0,0 +1,69 @@
+//! Per-entity opacity, fading meshes in and out whatever their materials.
+
+use specs::{Component, HashMapStorage};
+
+/// ## Opacity
+///
+/// Opacity of a mesh entity, multiplied into the alpha of its materials through the
+/// `u_entity_opacity` uniform. Below `1`, the entity is sorted back to front with the
+/// transparent materials, alpha blended without depth writes, even if its materials are opaque. Entities without
+/// this component are fully opaque.
+#[derive(Clone)]
+pub struct Opacity {
+    /// Current opacity, between `0` and `1`
+    pub value: f32,
+
+    /// Opacity being faded to and its change per second, if fading
+    fade: Option<(f32, f32)>,
+}
+
+impl Opacity {
+    /// Constructor, not fading. `value` is clamped between `0` and `1`.
+    pub fn new(value: f32) -> Opacity {
+        Opacity {
+            value: value.max(0.).min(1.),
+            fade: None,
+        }
+    }
+
+    /// Starts fading to `target`, clamped between `0` and `1`, over `duration` seconds.
+    /// A duration of `0` or less sets it right away.
+    pub fn fade_to(&mut self, target: f32, duration: f32) -> () {
+        let target = target.max(0.).min(1.);
+        if duration > 0. && target != self.value {
+            self.fade = Some((target, (target - self.value).abs() / duration));
+        } else {
+            self.value = target;
+            self.fade = None;
+        }
+    }
+
+    /// Moves the opacity towards the faded to one by `delta` seconds.
+    pub fn advance(&mut self, delta: f32) -> () {
+        if let Some((target, speed)) = self.fade {
+            let step = speed * delta;
+            if (target - self.value).abs() <= step {
+                self.value = target;
+                self.fade = None;
+            } else if target > self.value {
+                self.value += step;
+            } else {
+                self.value -= step;
+            }
+        }
+    }
+
+    /// Returns `true` if the opacity is being faded.
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Returns `true` if the entity is drawn as usual, with its materials' blending.
+    pub fn is_opaque(&self) -> bool {
+        self.value >= 1.
+    }
+}
+
+impl Component for Opacity {
+    type Storage = HashMapStorage<Self>;
+}
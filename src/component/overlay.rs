@@ -0,0 +1,55 @@
+//! Representation of a 2D element drawn over the 3D scene
+
+use specs::{Component, DenseVecStorage};
+
+/// Overlay component for HUD elements (crosshairs, health bars...).  
+/// Displays a texture on a screen-aligned rectangle positioned in CSS pixels from the
+/// top-left corner of the canvas. Overlays are drawn after the 3D scene, without depth test,
+/// in increasing `order`.
+#[derive(Clone)]
+pub struct Overlay {
+    /// Texture index in the asset registry
+    texture: usize,
+
+    /// Horizontal position of the top-left corner, in CSS pixels
+    pub x: f32,
+
+    /// Vertical position of the top-left corner, in CSS pixels
+    pub y: f32,
+
+    /// Width, in CSS pixels
+    pub width: f32,
+
+    /// Height, in CSS pixels
+    pub height: f32,
+
+    /// Opacity multiplied with the texture's alpha
+    pub opacity: f32,
+
+    /// Draw order; overlays with a higher order are drawn on top
+    pub order: i32,
+}
+
+impl Overlay {
+    /// Constructor. Creates a fully opaque overlay with order `0`.
+    pub fn new(texture_id: usize, x: f32, y: f32, width: f32, height: f32) -> Overlay {
+        Overlay {
+            texture: texture_id,
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+            opacity: 1.0,
+            order: 0,
+        }
+    }
+
+    /// Getter for texture
+    pub fn get_texture_id(&self) -> &usize {
+        &self.texture
+    }
+}
+
+impl Component for Overlay {
+    type Storage = DenseVecStorage<Self>;
+}
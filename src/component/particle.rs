@@ -0,0 +1,236 @@
+//! CPU particle emitter, simulated by the `ParticleSystem` and rendered as point sprites.
+
+use nalgebra::{UnitQuaternion, Vector3, Vector4};
+use specs::{Component, DenseVecStorage};
+
+/// Number of floats written per particle in the vertex data:
+/// position (3), color (4) and size (1).
+pub const PARTICLE_VERTEX_SIZE: usize = 8;
+
+/// A single simulated particle, in world space.
+#[derive(Clone)]
+pub struct Particle {
+    /// World position
+    pub position: Vector3<f32>,
+
+    /// World velocity, in units per second
+    pub velocity: Vector3<f32>,
+
+    /// Time since the particle was spawned, in seconds
+    pub age: f32,
+
+    /// Total life duration of the particle, in seconds
+    pub lifetime: f32,
+}
+
+/// ## ParticleEmitter
+///
+/// Spawns particles from its entity's world position and keeps them alive for their lifetime.
+/// Particles are simulated in world space, so moving the emitter leaves a trail.
+///
+/// Color and size are interpolated linearly from their start to end value over each
+/// particle's life. The vertex data for the renderer is rebuilt by the `ParticleSystem`
+/// every frame.
+#[derive(Clone)]
+pub struct ParticleEmitter {
+    /// Maximum number of live particles. Spawning stops while this limit is reached.
+    pub max_particles: usize,
+
+    /// Number of particles spawned per second. `0` only emits through bursts.
+    pub spawn_rate: f32,
+
+    /// Minimum and maximum lifetime of a particle, in seconds
+    pub lifetime: (f32, f32),
+
+    /// Main emission direction
+    pub direction: Vector3<f32>,
+
+    /// Half angle of the emission cone around `direction`, in radians
+    pub cone_angle: f32,
+
+    /// Minimum and maximum initial speed, in units per second
+    pub speed: (f32, f32),
+
+    /// Constant acceleration applied to every particle
+    pub gravity: Vector3<f32>,
+
+    /// Color (with alpha) of a particle when it's spawned
+    pub start_color: Vector4<f32>,
+
+    /// Color (with alpha) of a particle at the end of its life
+    pub end_color: Vector4<f32>,
+
+    /// World size of a particle when it's spawned
+    pub start_size: f32,
+
+    /// World size of a particle at the end of its life
+    pub end_size: f32,
+
+    /// Live particles
+    particles: Vec<Particle>,
+
+    /// Fraction of particle left to spawn from the previous frames
+    spawn_accumulator: f32,
+
+    /// Particles to spawn at the next update regardless of `spawn_rate`
+    pending_burst: usize,
+
+    /// Interleaved vertex data for the live particles, see `PARTICLE_VERTEX_SIZE`
+    vertex_data: Vec<f32>,
+
+    /// State of the pseudo-random generator used to spawn particles
+    random_state: u32,
+}
+
+impl ParticleEmitter {
+    /// Constructor. Emits white particles upwards in a 30° cone, living one second,
+    /// without gravity.
+    pub fn new(max_particles: usize, spawn_rate: f32) -> ParticleEmitter {
+        ParticleEmitter {
+            max_particles: max_particles,
+            spawn_rate: spawn_rate,
+            lifetime: (1.0, 1.0),
+            direction: Vector3::new(0.0, 1.0, 0.0),
+            cone_angle: std::f32::consts::PI / 6.0,
+            speed: (1.0, 1.0),
+            gravity: Vector3::new(0.0, 0.0, 0.0),
+            start_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            end_color: Vector4::new(1.0, 1.0, 1.0, 0.0),
+            start_size: 0.1,
+            end_size: 0.1,
+            particles: Vec::with_capacity(max_particles),
+            spawn_accumulator: 0.0,
+            pending_burst: 0,
+            vertex_data: Vec::new(),
+            random_state: 0x9E37_79B9,
+        }
+    }
+
+    /// Requests `count` particles to be spawned at the next update.
+    pub fn burst(&mut self, count: usize) -> () {
+        self.pending_burst += count;
+    }
+
+    /// Moves the live particles by `-shift`, when the scene's origin is moved to `shift`.
+    pub fn shift_origin(&mut self, shift: &Vector3<f32>) -> () {
+        for particle in &mut self.particles {
+            particle.position -= shift;
+        }
+    }
+
+    /// Returns the number of live particles.
+    pub fn get_live_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Returns `true` if this emitter has nothing to simulate: no live particle,
+    /// no pending burst and no continuous emission.
+    pub fn is_idle(&self) -> bool {
+        self.particles.is_empty() && self.pending_burst == 0 && self.spawn_rate <= 0.0
+    }
+
+    /// Returns the interleaved vertex data built at the last update.
+    pub fn get_vertex_data(&self) -> &[f32] {
+        &self.vertex_data
+    }
+
+    /// Advances the simulation by `delta` seconds, spawning new particles at `origin`.
+    pub fn update(&mut self, delta: f32, origin: &Vector3<f32>) -> () {
+        let gravity = self.gravity;
+        let mut index = 0;
+        while index < self.particles.len() {
+            let particle = &mut self.particles[index];
+            particle.age += delta;
+            if particle.age >= particle.lifetime {
+                self.particles.swap_remove(index);
+                continue;
+            }
+            particle.velocity += gravity * delta;
+            particle.position += particle.velocity * delta;
+            index += 1;
+        }
+
+        self.spawn_accumulator += self.spawn_rate.max(0.0) * delta;
+        let continuous = self.spawn_accumulator.floor();
+        self.spawn_accumulator -= continuous;
+        let to_spawn = (continuous as usize + self.pending_burst)
+            .min(self.max_particles.saturating_sub(self.particles.len()));
+        self.pending_burst = 0;
+        for _ in 0..to_spawn {
+            let particle = self.spawn_particle(origin);
+            self.particles.push(particle);
+        }
+        self.build_vertex_data();
+    }
+
+    /// Creates a new particle at `origin`, with a random lifetime and velocity.
+    fn spawn_particle(&mut self, origin: &Vector3<f32>) -> Particle {
+        let lifetime = lerp(self.lifetime.0, self.lifetime.1, self.next_random());
+        let speed = lerp(self.speed.0, self.speed.1, self.next_random());
+        let direction = self.random_direction();
+        Particle {
+            position: origin.clone(),
+            velocity: direction * speed,
+            age: 0.0,
+            lifetime: lifetime.max(std::f32::EPSILON),
+        }
+    }
+
+    /// Returns a random unit vector in the emission cone.
+    fn random_direction(&mut self) -> Vector3<f32> {
+        let axis = if self.direction.norm() > std::f32::EPSILON {
+            self.direction.normalize()
+        } else {
+            Vector3::new(0.0, 1.0, 0.0)
+        };
+        // Uniform distribution over the spherical cap around +Y, then rotated towards `axis`.
+        let cos_angle = self.cone_angle.max(0.0).min(std::f32::consts::PI).cos();
+        let z = lerp(1.0, cos_angle, self.next_random());
+        let phi = self.next_random() * 2.0 * std::f32::consts::PI;
+        let radius = (1.0 - z * z).max(0.0).sqrt();
+        let local = Vector3::new(radius * phi.cos(), z, radius * phi.sin());
+        match UnitQuaternion::rotation_between(&Vector3::new(0.0, 1.0, 0.0), &axis) {
+            Some(rotation) => rotation * local,
+            None => Vector3::new(local.x, -local.y, local.z),
+        }
+    }
+
+    /// Returns a pseudo-random number in `[0, 1)` (xorshift32).
+    fn next_random(&mut self) -> f32 {
+        let mut state = self.random_state;
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        self.random_state = state;
+        (state >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Rebuilds `vertex_data` from the live particles.
+    fn build_vertex_data(&mut self) -> () {
+        self.vertex_data.clear();
+        for particle in &self.particles {
+            let progress = particle.age / particle.lifetime;
+            let color = self.start_color.lerp(&self.end_color, progress);
+            let size = lerp(self.start_size, self.end_size, progress);
+            self.vertex_data.extend_from_slice(&[
+                particle.position.x,
+                particle.position.y,
+                particle.position.z,
+                color.x,
+                color.y,
+                color.z,
+                color.w,
+                size,
+            ]);
+        }
+    }
+}
+
+impl Component for ParticleEmitter {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Linear interpolation between `a` and `b`.
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
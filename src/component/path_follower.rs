@@ -0,0 +1,121 @@
+//! Curve following, moving an entity along a spline without per-frame calls from JS.
+
+use crate::math::Spline;
+use crate::utils::PathLoop;
+use nalgebra::Vector3;
+use specs::{Component, HashMapStorage};
+
+/// ## PathFollower
+///
+/// Moves an entity's local `Transform` along a `Spline` at a constant speed, as advanced by
+/// the `PathFollowerSystem`.
+///
+/// The spline is expressed in the space of the entity's parent, or the world for root
+/// entities. It is followed with the scaled delta time, so followers stop while the scene is
+/// paused.
+#[derive(Clone)]
+pub struct PathFollower {
+    /// Curve followed
+    spline: Spline,
+
+    /// Speed along the curve, in units per second, not negative
+    pub speed: f32,
+
+    /// What happens at the end of the curve
+    pub loop_mode: PathLoop,
+
+    /// If `true`, the entity's local +Z axis is turned along the curve.
+    pub orient: bool,
+
+    /// Up vector used to orient the entity, in its parent's space
+    pub up: Vector3<f32>,
+
+    /// Distance travelled, in units. Up to twice the curve length in `PingPong` mode, the
+    /// second half coming back.
+    travelled: f32,
+}
+
+impl PathFollower {
+    /// Constructor, starting at the beginning of the curve with +Y up.
+    pub fn new(spline: Spline, speed: f32, loop_mode: PathLoop, orient: bool) -> PathFollower {
+        PathFollower {
+            spline: spline,
+            speed: speed,
+            loop_mode: loop_mode,
+            orient: orient,
+            up: Vector3::y(),
+            travelled: 0.,
+        }
+    }
+
+    /// Getter for the curve followed.
+    pub fn get_spline(&self) -> &Spline {
+        &self.spline
+    }
+
+    /// Sets the speed so that the whole curve is travelled in `duration` seconds.
+    pub fn set_duration(&mut self, duration: f32) -> () {
+        self.speed = self.spline.get_length() / duration;
+    }
+
+    /// Returns how far along the curve the entity is, from `0` at the start to `1` at the
+    /// end.
+    pub fn get_progress(&self) -> f32 {
+        let length = self.spline.get_length();
+        if length > 0. {
+            self.get_distance() / length
+        } else {
+            1.
+        }
+    }
+
+    /// Moves to `progress` along the curve, between `0` and `1`, heading towards its end.
+    pub fn set_progress(&mut self, progress: f32) -> () {
+        self.travelled = progress.max(0.).min(1.) * self.spline.get_length();
+    }
+
+    /// Returns `true` once a `Once` follower has reached the end of the curve.
+    pub fn is_finished(&self) -> bool {
+        self.loop_mode == PathLoop::Once && self.travelled >= self.spline.get_length()
+    }
+
+    /// Returns the distance from the start of the curve.
+    fn get_distance(&self) -> f32 {
+        let length = self.spline.get_length();
+        if self.travelled > length {
+            2. * length - self.travelled
+        } else {
+            self.travelled
+        }
+    }
+
+    /// Advances along the curve by `delta` seconds, wrapping or bouncing at its ends.
+    pub fn advance(&mut self, delta: f32) -> () {
+        let length = self.spline.get_length();
+        if length <= 0. {
+            self.travelled = 0.;
+            return;
+        }
+        let travelled = self.travelled + self.speed * delta;
+        self.travelled = match self.loop_mode {
+            PathLoop::Once => travelled.max(0.).min(length),
+            PathLoop::Repeat => travelled.rem_euclid(length),
+            PathLoop::PingPong => travelled.rem_euclid(2. * length),
+        };
+    }
+
+    /// Returns the position and unit tangent at the current distance, the tangent pointing
+    /// in the direction of travel.
+    pub fn sample(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let (position, tangent) = self.spline.sample(self.get_distance());
+        if self.travelled > self.spline.get_length() {
+            (position, -tangent)
+        } else {
+            (position, tangent)
+        }
+    }
+}
+
+impl Component for PathFollower {
+    type Storage = HashMapStorage<Self>;
+}
@@ -0,0 +1,19 @@
+//! Selection flags, for editors and tools.
+
+use specs::{Component, NullStorage};
+
+/// Flag component for selected entities, whose meshes are drawn with an outline.
+#[derive(Default)]
+pub struct Selected;
+
+impl Component for Selected {
+    type Storage = NullStorage<Self>;
+}
+
+/// Flag component for entities ignored by picking and surface snapping, like helpers.
+#[derive(Default)]
+pub struct Unpickable;
+
+impl Component for Unpickable {
+    type Storage = NullStorage<Self>;
+}
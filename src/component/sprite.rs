@@ -0,0 +1,44 @@
+//! Representation of a camera-facing textured quad in a scene
+
+use crate::asset::AtlasRegion;
+use specs::{Component, DenseVecStorage};
+
+/// Sprite component for an entity in the 3D scene.  
+/// Displays a region of a texture on a quad always facing the camera, centered on the
+/// entity's world position. Sprites sharing a texture are drawn in a single draw call,
+/// which makes a texture atlas (e.g. a bitmap font) the efficient way to display many of them.
+#[derive(Clone)]
+pub struct Sprite {
+    /// Texture index in the asset registry
+    texture: usize,
+
+    /// Width of the quad, in world units
+    pub width: f32,
+
+    /// Height of the quad, in world units
+    pub height: f32,
+
+    /// Texture region displayed on the quad
+    pub region: AtlasRegion,
+}
+
+impl Sprite {
+    /// Constructor. Displays the whole texture by default.
+    pub fn new(texture_id: usize, width: f32, height: f32) -> Sprite {
+        Sprite {
+            texture: texture_id,
+            width: width,
+            height: height,
+            region: AtlasRegion::full(),
+        }
+    }
+
+    /// Getter for texture
+    pub fn get_texture_id(&self) -> &usize {
+        &self.texture
+    }
+}
+
+impl Component for Sprite {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -1,9 +1,20 @@
 //! Representation of a transform in a scene
 
-use nalgebra::{Isometry3, Matrix4, Translation3, UnitQuaternion, Vector3};
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::RotationOrder;
+use nalgebra::{Isometry3, Matrix3, Matrix4, Rotation3, Translation3, UnitQuaternion, Vector3};
 use specs::{Component, DenseVecStorage, Entity, FlaggedStorage, NullStorage, VecStorage};
 use specs_hierarchy::Parent;
 
+/// Tolerance used when decomposing a matrix, on the orthogonality of its axes and on its
+/// last row.
+const DECOMPOSITION_EPSILON: f32 = 1e-4;
+
+/// Local translation, rotation and scale of an entity, and its world matrix.
+///
+/// Euler angles follow `RotationOrder::Xyz` unless given an order: X first, then Y, then Z,
+/// around the parent's axes.
+#[derive(Clone)]
 pub struct Transform {
     /// Translation in local space.
     local_translation: Translation3<f32>,
@@ -18,21 +29,31 @@ pub struct Transform {
     /// if `local_matrix` has changed, along with world matrix for
     /// all of this transform's children.
     world_matrix: Matrix4<f32>,
+
+    /// World matrix at the previous fixed step, interpolated from in fixed timestep mode.
+    previous_world_matrix: Matrix4<f32>,
 }
 
 impl Transform {
-    /// Constructor. Creates a new Transform from a translation, rotation and scale.
+    /// Constructor. Creates a new Transform from a translation, rotation (Euler angles in the
+    /// default `Xyz` order) and scale.
     pub fn new(
         translation: &Vector3<f32>,
         rotation: &Vector3<f32>,
         scale: &Vector3<f32>,
     ) -> Transform {
-        Transform {
+        let mut transform = Transform {
             local_translation: Translation3::from(translation.clone()),
-            local_rotation: UnitQuaternion::from_euler_angles(rotation.x, rotation.y, rotation.z),
+            local_rotation: RotationOrder::Xyz.to_unit_quaternion(rotation),
             local_scale: scale.clone(),
             world_matrix: Matrix4::identity(),
-        }
+            previous_world_matrix: Matrix4::identity(),
+        };
+        // Valid until the first refresh for unparented transforms, which matters when it
+        // is delayed to the next fixed step.
+        transform.refresh_world_matrix(None);
+        transform.previous_world_matrix = transform.world_matrix;
+        transform
     }
 
     /// Sets a new local translation for this Transform
@@ -40,10 +61,34 @@ impl Transform {
         self.local_translation = Translation3::from(new_translation.clone());
     }
 
-    /// Sets a new local rotation for this Transform
+    /// Getter for the local translation
+    pub fn get_translation(&self) -> &Vector3<f32> {
+        &self.local_translation.vector
+    }
+
+    /// Sets a new local rotation for this Transform, from Euler angles in the default `Xyz`
+    /// order.
     pub fn set_rotation(&mut self, new_rotation: &Vector3<f32>) -> () {
-        self.local_rotation =
-            UnitQuaternion::from_euler_angles(new_rotation.x, new_rotation.y, new_rotation.z);
+        self.set_rotation_with_order(new_rotation, RotationOrder::Xyz);
+    }
+
+    /// Sets a new local rotation for this Transform, from Euler angles applied in `order`.
+    pub fn set_rotation_with_order(
+        &mut self,
+        new_rotation: &Vector3<f32>,
+        order: RotationOrder,
+    ) -> () {
+        self.local_rotation = order.to_unit_quaternion(new_rotation);
+    }
+
+    /// Sets a new local rotation for this Transform from a quaternion
+    pub fn set_rotation_quaternion(&mut self, new_rotation: &UnitQuaternion<f32>) -> () {
+        self.local_rotation = new_rotation.clone();
+    }
+
+    /// Getter for the local rotation
+    pub fn get_rotation(&self) -> &UnitQuaternion<f32> {
+        &self.local_rotation
     }
 
     /// Sets a new local scale for this Transform
@@ -51,23 +96,121 @@ impl Transform {
         self.local_scale = new_scale.clone();
     }
 
-    /// Re-computes world matrix from its inner properties and a given parent world matrix.
-    pub fn refresh_world_matrix(&mut self, parent_world_matrix: Option<Matrix4<f32>>) -> () {
+    /// Getter for the local scale
+    pub fn get_scale(&self) -> &Vector3<f32> {
+        &self.local_scale
+    }
+
+    /// Sets the local translation, rotation and scale from a local matrix computed elsewhere.  
+    /// Fails if the matrix has a perspective part, a shear or a null scale, which a
+    /// translation, rotation and scale can't represent. A mirroring matrix gives a negative
+    /// X scale.
+    pub fn set_local_matrix(&mut self, matrix: &Matrix4<f32>) -> Result<(), W3DError> {
+        let last_row = matrix.row(3);
+        if (last_row[0].abs() + last_row[1].abs() + last_row[2].abs()) > DECOMPOSITION_EPSILON
+            || (last_row[3] - 1.).abs() > DECOMPOSITION_EPSILON
+        {
+            return Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "A local matrix can't have a perspective part.",
+            ));
+        }
+        let linear: Matrix3<f32> = matrix
+            .fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0)
+            .into();
+        let mut scale = Vector3::new(
+            linear.column(0).norm(),
+            linear.column(1).norm(),
+            linear.column(2).norm(),
+        );
+        if scale
+            .iter()
+            .any(|component| *component <= DECOMPOSITION_EPSILON)
+        {
+            return Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "A local matrix can't have a null scale.",
+            ));
+        }
+        if linear.determinant() < 0. {
+            scale.x = -scale.x;
+        }
+        let mut rotation = linear;
+        for (index, mut column) in rotation.column_iter_mut().enumerate() {
+            column /= scale[index];
+        }
+        let orthogonality = rotation.transpose() * rotation - Matrix3::identity();
+        if orthogonality
+            .iter()
+            .any(|value| value.abs() > DECOMPOSITION_EPSILON)
+        {
+            return Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "A local matrix can't have a shear.",
+            ));
+        }
+        self.local_translation = Translation3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+        self.local_rotation =
+            UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(rotation));
+        self.local_scale = scale;
+        Ok(())
+    }
+
+    /// Computes the local matrix, from the local translation, rotation and scale.
+    pub fn get_local_matrix(&self) -> Matrix4<f32> {
         let scale_matrix = Matrix4::new_nonuniform_scaling(&self.local_scale);
         let isometry =
             Isometry3::from_parts(self.local_translation.clone(), self.local_rotation.clone());
-        let local_matrix = isometry.to_homogeneous() * scale_matrix;
+        isometry.to_homogeneous() * scale_matrix
+    }
+
+    /// Re-computes world matrix from its inner properties and a given parent world matrix.
+    pub fn refresh_world_matrix(&mut self, parent_world_matrix: Option<Matrix4<f32>>) -> () {
+        let local_matrix = self.get_local_matrix();
         if let Some(parent_matrix) = parent_world_matrix {
             self.world_matrix = parent_matrix * local_matrix;
         } else {
             self.world_matrix = local_matrix;
         }
+        debug_assert!(
+            self.world_matrix.iter().all(|value| value.is_finite()),
+            "The world matrix is not finite."
+        );
     }
 
     /// Getter for the world matrix
     pub fn get_world_matrix(&self) -> Matrix4<f32> {
         self.world_matrix
     }
+
+    /// Keeps the current world matrix as the previous one, before a fixed step refreshes it.
+    pub fn store_previous_world_matrix(&mut self) -> () {
+        self.previous_world_matrix = self.world_matrix;
+    }
+
+    /// Moves the entity by `-shift` in world space, when the scene's origin is moved to
+    /// `shift`. The local translation is only changed for root entities, since children
+    /// follow their parent.
+    pub fn shift_origin(&mut self, shift: &Vector3<f32>, is_root: bool) -> () {
+        if is_root {
+            self.local_translation.vector -= shift;
+        }
+        for row in 0..3 {
+            self.world_matrix[(row, 3)] -= shift[row];
+            self.previous_world_matrix[(row, 3)] -= shift[row];
+        }
+    }
+
+    /// Returns the world matrix to render with: interpolated between the previous and the
+    /// current one by `interpolation` in fixed timestep mode, the current one otherwise.  
+    /// Matrices are interpolated component-wise, which is close enough for the small motions
+    /// of a single step.
+    pub fn get_rendered_world_matrix(&self, interpolation: Option<f32>) -> Matrix4<f32> {
+        match interpolation {
+            Some(alpha) => self.previous_world_matrix * (1. - alpha) + self.world_matrix * alpha,
+            None => self.world_matrix,
+        }
+    }
 }
 
 impl Component for Transform {
@@ -75,6 +218,7 @@ impl Component for Transform {
 }
 
 /// Component that represents a parent-child relationship between entities to help build a Scene-graph
+#[derive(Clone)]
 pub struct TransformParent {
     /// Represents the parent Entity of the other Entity to which this TransformParent is attached.
     entity: Entity,
@@ -101,9 +245,26 @@ impl Parent for TransformParent {
 }
 
 /// The Enabled component is a flag component stating that the object should be updated and rendered.
+///
+/// Every entity created by the `Scene` starts enabled. Disabling an entity also disables its
+/// whole subtree: its descendants are flagged `EffectivelyDisabled` by the
+/// `EnabledPropagationSystem`, and systems only process entities that are `Enabled` and not
+/// `EffectivelyDisabled`. A descendant keeps its own `Enabled` flag, so it's active again
+/// once every ancestor is enabled.  
+/// The scene graph is the exception: world matrices are refreshed regardless of `Enabled`.
+///
+/// For instance, with a hierarchy `root > middle > leaf` where only `middle` is disabled,
+/// `root` is still rendered and updated, while `middle` and `leaf` are neither rendered nor
+/// lit. Moving `middle` still refreshes the world matrices of both, so they are right as
+/// soon as `middle` is enabled again.
 #[derive(Default)]
 pub struct Enabled;
 
+/// Flag set on entities that have a disabled ancestor, maintained by the
+/// `EnabledPropagationSystem`. Should not be added or removed by hand.
+#[derive(Default)]
+pub struct EffectivelyDisabled;
+
 #[derive(Default)]
 pub struct DirtyTransform;
 
@@ -111,6 +272,47 @@ impl Component for Enabled {
     type Storage = NullStorage<Self>;
 }
 
+impl Component for EffectivelyDisabled {
+    type Storage = NullStorage<Self>;
+}
+
 impl Component for DirtyTransform {
     type Storage = NullStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Transform {
+        Transform::new(&Vector3::zeros(), &Vector3::zeros(), &Vector3::repeat(1.))
+    }
+
+    #[test]
+    fn local_matrix_round_trips_through_trs() {
+        let source = Transform::new(
+            &Vector3::new(1., -2., 3.),
+            &Vector3::new(0.1, 0.2, 0.3),
+            &Vector3::new(2., 0.5, 3.),
+        );
+        let mut transform = identity();
+        transform
+            .set_local_matrix(&source.get_local_matrix())
+            .unwrap();
+        assert!((transform.get_translation() - source.get_translation()).norm() < 1e-5);
+        assert!(transform.get_rotation().angle_to(source.get_rotation()) < 1e-5);
+        assert!((transform.get_scale() - source.get_scale()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn local_matrix_rejects_shear_and_perspective() {
+        let mut sheared = Matrix4::identity();
+        sheared[(0, 1)] = 0.5;
+        let mut perspective = Matrix4::identity();
+        perspective[(3, 2)] = -1.;
+        for matrix in &[sheared, perspective, Matrix4::zeros()] {
+            let error = identity().set_local_matrix(matrix).unwrap_err();
+            assert_eq!(error.get_kind(), W3DErrorKind::InvalidArgument);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Per-entity uniform values, overriding those of the entity's material instance.
+
+use nalgebra::{Vector2, Vector3, Vector4};
+use specs::{Component, HashMapStorage};
+
+/// Value of an overridden uniform. Textures can't be overridden per entity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniformOverrideValue {
+    Float(f32),
+    Vector2(Vector2<f32>),
+    Vector3(Vector3<f32>),
+    Vector4(Vector4<f32>),
+}
+
+/// ## UniformOverrides
+///
+/// Uniform values set for a single entity's draw calls, after its material's and material
+/// instance's uniforms. Once the entity is drawn, the overridden uniforms are set back to the
+/// material instance's (or material's) values.
+///
+/// This is the cheap way to vary a few values between entities, e.g. a tint or a
+/// highlight factor, without registering a `MaterialInstance` for each of them. Each
+/// override still costs a uniform upload per draw call, so entities sharing the same values
+/// are better served by a shared material instance.
+#[derive(Clone, Default)]
+pub struct UniformOverrides {
+    /// Overridden uniforms by name, in insertion order
+    values: Vec<(String, UniformOverrideValue)>,
+}
+
+impl UniformOverrides {
+    /// Constructor. Overrides nothing.
+    pub fn new() -> UniformOverrides {
+        Default::default()
+    }
+
+    /// Overrides a uniform, or updates its overridden value.
+    pub fn set(&mut self, name: &str, value: UniformOverrideValue) -> () {
+        match self.values.iter_mut().find(|(key, _)| key == name) {
+            Some((_, current)) => *current = value,
+            None => self.values.push((name.to_owned(), value)),
+        }
+    }
+
+    /// Stops overriding a uniform. Returns `true` if it was overridden.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.values.len();
+        self.values.retain(|(key, _)| key != name);
+        self.values.len() != count
+    }
+
+    /// Returns `true` if no uniform is overridden.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the overridden uniforms, in insertion order.
+    pub fn get_values(&self) -> &[(String, UniformOverrideValue)] {
+        &self.values
+    }
+}
+
+impl Component for UniformOverrides {
+    type Storage = HashMapStorage<Self>;
+}
@@ -0,0 +1,50 @@
+//! Kinematic velocity, moving an entity without per-frame calls from JS.
+
+use nalgebra::Vector3;
+use specs::{Component, HashMapStorage};
+
+/// Moves an entity's local `Transform` every update, as integrated by the `VelocitySystem`.
+///
+/// Velocities are expressed in the space of the entity's parent, or the world for root
+/// entities. They are integrated with the scaled delta time, so they stop while the scene is
+/// paused.
+#[derive(Clone)]
+pub struct Velocity {
+    /// Translation speed, in units per second
+    pub linear: Vector3<f32>,
+
+    /// Rotation speed, as an axis scaled by the angle in radians per second
+    pub angular: Vector3<f32>,
+
+    /// Rate at which the linear velocity decays: it is multiplied by `exp(-damping * delta)`
+    /// every update. `0` keeps it constant.
+    pub linear_damping: f32,
+
+    /// Rate at which the angular velocity decays, as `linear_damping`
+    pub angular_damping: f32,
+
+    /// Largest linear speed, in units per second, if any
+    pub max_speed: Option<f32>,
+}
+
+impl Velocity {
+    /// Constructor, without damping nor speed limit.
+    pub fn new(linear: Vector3<f32>, angular: Vector3<f32>) -> Velocity {
+        Velocity {
+            linear: linear,
+            angular: angular,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            max_speed: None,
+        }
+    }
+
+    /// Returns `true` if the velocity doesn't move the entity anymore.
+    pub fn is_zero(&self) -> bool {
+        self.linear == Vector3::zeros() && self.angular == Vector3::zeros()
+    }
+}
+
+impl Component for Velocity {
+    type Storage = HashMapStorage<Self>;
+}
@@ -0,0 +1,148 @@
+//! Error type shared by the whole crate.
+//!
+//! Every fallible operation in the renderer, the asset registry and the scene returns a
+//! `W3DError`, whose `kind` can be matched on programmatically. When crossing the wasm
+//! boundary, it is converted into a plain JS object with `kind`, `reason`, `source` and
+//! `description` fields.
+
+use js_sys::{Object, Reflect};
+use std::error::Error;
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+/// Machine-readable category of a `W3DError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum W3DErrorKind {
+    /// A vertex or fragment shader failed to compile.
+    ShaderCompile,
+
+    /// A program failed to link.
+    ProgramLink,
+
+    /// A requested asset (mesh data, material, texture...) is not registered.
+    MissingAsset,
+
+    /// A resource is used before it has been constructed (e.g. a material that isn't compiled).
+    UnconstructedResource,
+
+    /// The WebGL context could not create or update a resource (buffer, texture, shader object...).
+    GlResource,
+
+    /// An asset file could not be deserialized.
+    Deserialization,
+
+    /// The scene or renderer is used before it has been initialized.
+    Uninitialized,
+
+    /// An entity does not exist or lacks a required component.
+    InvalidEntity,
+
+    /// A value supplied by the caller is invalid.
+    InvalidArgument,
+
+    /// A uniform value could not be set to the context.
+    Uniform,
+}
+
+impl W3DErrorKind {
+    /// Returns the name of this kind, as exposed to JS.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            W3DErrorKind::ShaderCompile => "ShaderCompile",
+            W3DErrorKind::ProgramLink => "ProgramLink",
+            W3DErrorKind::MissingAsset => "MissingAsset",
+            W3DErrorKind::UnconstructedResource => "UnconstructedResource",
+            W3DErrorKind::GlResource => "GlResource",
+            W3DErrorKind::Deserialization => "Deserialization",
+            W3DErrorKind::Uninitialized => "Uninitialized",
+            W3DErrorKind::InvalidEntity => "InvalidEntity",
+            W3DErrorKind::InvalidArgument => "InvalidArgument",
+            W3DErrorKind::Uniform => "Uniform",
+        }
+    }
+}
+
+impl fmt::Display for W3DErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// ## W3DError
+///
+/// Error returned by wtvr3d. Holds a `kind` for programmatic handling, a human-readable
+/// `reason`, and an optional `source` describing the underlying cause (a shader info log,
+/// a deserializer message, an asset id...).
+#[derive(Clone, Debug)]
+pub struct W3DError {
+    /// Category of this error.
+    kind: W3DErrorKind,
+
+    /// Human-readable explanation of what went wrong.
+    reason: String,
+
+    /// Optional underlying cause.
+    source: Option<String>,
+}
+
+impl W3DError {
+    /// Constructor. Creates a new error of the given kind with a reason and no source.
+    pub fn new(kind: W3DErrorKind, reason: &str) -> W3DError {
+        W3DError {
+            kind: kind,
+            reason: reason.to_owned(),
+            source: None,
+        }
+    }
+
+    /// Constructor. Creates a new error of the given kind with a reason and an underlying cause.
+    pub fn with_source(kind: W3DErrorKind, reason: &str, source: &str) -> W3DError {
+        W3DError {
+            kind: kind,
+            reason: reason.to_owned(),
+            source: Some(source.to_owned()),
+        }
+    }
+
+    /// Getter for `kind`
+    pub fn get_kind(&self) -> W3DErrorKind {
+        self.kind
+    }
+
+    /// Getter for `reason`
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Getter for `source`
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_ref().map(|source| source.as_str())
+    }
+}
+
+impl fmt::Display for W3DError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.reason)?;
+        if let Some(source) = &self.source {
+            write!(f, " ({})", source)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for W3DError {}
+
+impl From<W3DError> for JsValue {
+    fn from(error: W3DError) -> JsValue {
+        let object = Object::new();
+        let source = match &error.source {
+            Some(source) => JsValue::from_str(source),
+            None => JsValue::NULL,
+        };
+        Reflect::set(&object, &"kind".into(), &error.kind.as_str().into()).ok();
+        Reflect::set(&object, &"reason".into(), &error.reason.as_str().into()).ok();
+        Reflect::set(&object, &"source".into(), &source).ok();
+        Reflect::set(&object, &"description".into(), &error.to_string().into()).ok();
+        object.into()
+    }
+}
@@ -4,9 +4,14 @@
 //!
 //! wtvr3d's purpose is to  offer a WebAssembly alternative to popular JS 3d engines on the web, while ensuring performance and a small overall footprint.
 
+#[macro_use]
+pub mod utils;
+
 pub mod asset;
 pub mod component;
+pub mod error;
+pub mod math;
 pub mod renderer;
+pub mod resource;
 pub mod scene;
 pub mod system;
-pub mod utils;
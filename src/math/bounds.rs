@@ -0,0 +1,267 @@
+//! Bounding volumes and view frustum, used for visibility tests.
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+/// A sphere containing some geometry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    /// Center of the sphere
+    pub center: Vector3<f32>,
+
+    /// Radius of the sphere
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Constructor.
+    pub fn new(center: Vector3<f32>, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: center,
+            radius: radius,
+        }
+    }
+
+    /// Returns a sphere containing this one once transformed by `matrix`.  
+    /// Non-uniform scales give a conservative (larger) sphere.
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+        let center = matrix * self.center.push(1.0);
+        let scale = (0..3)
+            .map(|i| {
+                matrix
+                    .fixed_slice::<nalgebra::U3, nalgebra::U1>(0, i)
+                    .norm()
+            })
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: Vector3::new(center.x, center.y, center.z) / center.w,
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// An axis-aligned box containing some geometry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    /// Corner with the lowest coordinates
+    pub min: Vector3<f32>,
+
+    /// Corner with the highest coordinates
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// Constructor.
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Aabb { min: min, max: max }
+    }
+
+    /// Computes the box containing a list of points, given as flat coordinates with
+    /// `components` floats per point (only the first three are used).  
+    /// Returns `None` if there are no points.
+    pub fn from_points(data: &[f32], components: usize) -> Option<Aabb> {
+        if components < 3 || data.len() < components {
+            return None;
+        }
+        let mut points = data.chunks(components).filter(|point| point.len() >= 3);
+        let first = points.next()?;
+        let mut aabb = Aabb::new(
+            Vector3::new(first[0], first[1], first[2]),
+            Vector3::new(first[0], first[1], first[2]),
+        );
+        for point in points {
+            let point = Vector3::new(point[0], point[1], point[2]);
+            aabb.min = aabb.min.zip_map(&point, f32::min);
+            aabb.max = aabb.max.zip_map(&point, f32::max);
+        }
+        Some(aabb)
+    }
+
+    /// Returns the box containing a sphere.
+    pub fn from_sphere(sphere: &BoundingSphere) -> Aabb {
+        let extent = Vector3::repeat(sphere.radius);
+        Aabb::new(sphere.center - extent, sphere.center + extent)
+    }
+
+    /// Returns the smallest box containing this one and `other`.
+    pub fn merged(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            self.min.zip_map(&other.min, f32::min),
+            self.max.zip_map(&other.max, f32::max),
+        )
+    }
+
+    /// Returns the squared distance from `point` to the box, `0` if it's inside.
+    pub fn distance_squared_to(&self, point: &Vector3<f32>) -> f32 {
+        let clamped = point.zip_zip_map(&self.min, &self.max, |value, min, max| {
+            value.max(min).min(max)
+        });
+        (point - clamped).norm_squared()
+    }
+
+    /// Returns the eight corners of the box. Bit 0 of their index selects the X side, bit 1
+    /// the Y side and bit 2 the Z side, the lowest coordinate being `0`.
+    pub fn get_corners(&self) -> [Vector3<f32>; 8] {
+        let mut corners = [Vector3::zeros(); 8];
+        for (index, corner) in corners.iter_mut().enumerate() {
+            let pick = |bit: usize, min: f32, max: f32| if index & bit != 0 { max } else { min };
+            *corner = Vector3::new(
+                pick(1, self.min.x, self.max.x),
+                pick(2, self.min.y, self.max.y),
+                pick(4, self.min.z, self.max.z),
+            );
+        }
+        corners
+    }
+
+    /// Returns the smallest sphere centered on the box that contains it.
+    pub fn to_bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::new(
+            (self.min + self.max) / 2.0,
+            (self.max - self.min).norm() / 2.0,
+        )
+    }
+}
+
+/// The six planes enclosing what a camera sees.
+pub struct Frustum {
+    /// Planes as `(normal, distance)` with normals pointing inwards
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a view-projection matrix.
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Frustum {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let mut planes = [w + x, w - x, w + y, w - y, w + z, w - z];
+        for plane in planes.iter_mut() {
+            let length = Vector3::new(plane.x, plane.y, plane.z).norm();
+            if length > std::f32::EPSILON {
+                *plane /= length;
+            }
+        }
+        Frustum { planes: planes }
+    }
+
+    /// Returns the eight corners of the frustum of a view-projection matrix in world space,
+    /// ordered like the corners of an `Aabb`: the four corners of the near plane come first.  
+    /// Returns `None` if the matrix can't be inverted.
+    pub fn get_corners(view_projection: &Matrix4<f32>) -> Option<[Vector3<f32>; 8]> {
+        let inverse = view_projection.try_inverse()?;
+        let ndc = Aabb::new(Vector3::repeat(-1.), Vector3::repeat(1.)).get_corners();
+        let mut corners = [Vector3::zeros(); 8];
+        for (corner, ndc_corner) in corners.iter_mut().zip(ndc.iter()) {
+            let point = inverse * ndc_corner.push(1.);
+            if point.w.abs() <= std::f32::EPSILON {
+                return None;
+            }
+            *corner = point.xyz() / point.w;
+        }
+        Some(corners)
+    }
+
+    /// Returns `true` if the sphere is at least partially inside the frustum.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| {
+            plane.x * sphere.center.x
+                + plane.y * sphere.center.y
+                + plane.z * sphere.center.z
+                + plane.w
+                >= -sphere.radius
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Isometry3, Perspective3};
+
+    #[test]
+    fn aabb_from_points_skips_extra_components() {
+        let data = [1., -2., 3., 9., -1., 4., -5., 9.];
+        let aabb = Aabb::from_points(&data, 4).unwrap();
+        assert_eq!(aabb.min, Vector3::new(-1., -2., -5.));
+        assert_eq!(aabb.max, Vector3::new(1., 4., 3.));
+        assert!(Aabb::from_points(&[1., 2.], 3).is_none());
+        assert!(Aabb::from_points(&data, 2).is_none());
+    }
+
+    #[test]
+    fn aabb_distance_is_zero_inside() {
+        let aabb = Aabb::new(Vector3::repeat(-1.), Vector3::repeat(1.));
+        assert_eq!(aabb.distance_squared_to(&Vector3::new(0.5, 0., -0.5)), 0.);
+        assert_eq!(aabb.distance_squared_to(&Vector3::new(3., 0., 0.)), 4.);
+        assert_eq!(aabb.distance_squared_to(&Vector3::new(2., 2., 1.)), 2.);
+    }
+
+    #[test]
+    fn aabb_corners_follow_index_bits() {
+        let aabb = Aabb::new(Vector3::new(0., 1., 2.), Vector3::new(3., 4., 5.));
+        let corners = aabb.get_corners();
+        assert_eq!(corners[0], aabb.min);
+        assert_eq!(corners[7], aabb.max);
+        assert_eq!(corners[1], Vector3::new(3., 1., 2.));
+        assert_eq!(corners[2], Vector3::new(0., 4., 2.));
+        assert_eq!(corners[4], Vector3::new(0., 1., 5.));
+    }
+
+    #[test]
+    fn aabb_merge_and_sphere_round_trip() {
+        let a = Aabb::new(Vector3::zeros(), Vector3::repeat(1.));
+        let b = Aabb::new(Vector3::repeat(-1.), Vector3::new(0., 2., 0.));
+        let merged = a.merged(&b);
+        assert_eq!(merged.min, Vector3::repeat(-1.));
+        assert_eq!(merged.max, Vector3::new(1., 2., 1.));
+        let sphere = a.to_bounding_sphere();
+        assert_eq!(sphere.center, Vector3::repeat(0.5));
+        assert!((sphere.radius - 3f32.sqrt() / 2.).abs() < 1e-6);
+        let aabb = Aabb::from_sphere(&BoundingSphere::new(Vector3::zeros(), 2.));
+        assert_eq!(aabb.min, Vector3::repeat(-2.));
+    }
+
+    #[test]
+    fn sphere_transform_uses_largest_scale() {
+        let sphere = BoundingSphere::new(Vector3::new(1., 0., 0.), 1.);
+        let matrix = Matrix4::new_translation(&Vector3::new(0., 5., 0.))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(2., 3., 1.));
+        let transformed = sphere.transformed(&matrix);
+        assert_eq!(transformed.center, Vector3::new(2., 5., 0.));
+        assert!((transformed.radius - 3.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn frustum_culls_spheres_outside() {
+        let projection = Perspective3::new(1., std::f32::consts::FRAC_PI_2, 0.1, 100.);
+        let view = Isometry3::look_at_rh(
+            &nalgebra::Point3::origin(),
+            &nalgebra::Point3::new(0., 0., -1.),
+            &Vector3::y(),
+        );
+        let frustum = Frustum::from_matrix(&(projection.as_matrix() * view.to_homogeneous()));
+        let inside = BoundingSphere::new(Vector3::new(0., 0., -10.), 1.);
+        let behind = BoundingSphere::new(Vector3::new(0., 0., 10.), 1.);
+        let beyond = BoundingSphere::new(Vector3::new(0., 0., -200.), 1.);
+        let beside = BoundingSphere::new(Vector3::new(20., 0., -10.), 1.);
+        let straddling = BoundingSphere::new(Vector3::new(10.5, 0., -10.), 1.);
+        assert!(frustum.intersects_sphere(&inside));
+        assert!(!frustum.intersects_sphere(&behind));
+        assert!(!frustum.intersects_sphere(&beyond));
+        assert!(!frustum.intersects_sphere(&beside));
+        assert!(frustum.intersects_sphere(&straddling));
+    }
+
+    #[test]
+    fn frustum_corners_start_with_near_plane() {
+        let projection = Perspective3::new(1., std::f32::consts::FRAC_PI_2, 1., 10.);
+        let corners = Frustum::get_corners(projection.as_matrix()).unwrap();
+        for corner in &corners[..4] {
+            assert!((corner.z + 1.).abs() < 1e-4);
+            assert!((corner.x.abs() - 1.).abs() < 1e-4);
+        }
+        for corner in &corners[4..] {
+            assert!((corner.z + 10.).abs() < 1e-3);
+        }
+        assert!(Frustum::get_corners(&Matrix4::zeros()).is_none());
+    }
+}
@@ -0,0 +1,218 @@
+//! Bounding volume hierarchy over the triangles of a mesh, for fast ray intersections.
+
+use super::{Aabb, Ray, TriangleHit};
+use nalgebra::Vector3;
+
+/// Maximum number of triangles in a leaf node.
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// A node of the hierarchy.
+struct BvhNode {
+    /// Box containing every triangle under the node
+    bounds: Aabb,
+
+    /// Index of the first child for inner nodes (the second one follows it), or of the
+    /// first triangle for leaves
+    first: usize,
+
+    /// Number of triangles of a leaf, `0` for inner nodes
+    count: usize,
+}
+
+/// ## TriangleBvh
+///
+/// Binary tree of boxes over a list of triangles, split at the median of the longest axis.
+/// Built once per mesh, it makes ray intersections logarithmic in the triangle count.
+pub struct TriangleBvh {
+    /// Nodes, the root first
+    nodes: Vec<BvhNode>,
+
+    /// Triangles, ordered so that every leaf's triangles are contiguous
+    triangles: Vec<[Vector3<f32>; 3]>,
+
+    /// Index of each triangle in the original list
+    face_indexes: Vec<usize>,
+}
+
+impl TriangleBvh {
+    /// Builds the hierarchy over `triangles`.
+    pub fn new(triangles: Vec<[Vector3<f32>; 3]>) -> TriangleBvh {
+        let mut faces: Vec<usize> = (0..triangles.len()).collect();
+        let mut nodes = Vec::new();
+        if !triangles.is_empty() {
+            nodes.push(BvhNode {
+                bounds: triangle_bounds(&triangles[0]),
+                first: 0,
+                count: 0,
+            });
+            build_node(&mut nodes, 0, &triangles, &mut faces, 0);
+        }
+        TriangleBvh {
+            nodes: nodes,
+            triangles: faces.iter().map(|face| triangles[*face]).collect(),
+            face_indexes: faces,
+        }
+    }
+
+    /// Returns the closest triangle hit by `ray` and its index in the original list.
+    pub fn intersect(&self, ray: &Ray) -> Option<(usize, TriangleHit)> {
+        let mut closest: Option<(usize, TriangleHit)> = None;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            match ray.intersect_aabb(&node.bounds) {
+                Some(distance) if closest.map_or(true, |(_, hit)| distance <= hit.distance) => {}
+                _ => continue,
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+            for triangle in node.first..node.first + node.count {
+                if let Some(hit) = ray.intersect_triangle(&self.triangles[triangle]) {
+                    if closest.map_or(true, |(_, closest)| hit.distance < closest.distance) {
+                        closest = Some((self.face_indexes[triangle], hit));
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// Computes the bounds of the node at `index`, which holds `faces` starting at `offset` in
+/// the final triangle order, and splits it if it holds too many triangles.
+fn build_node(
+    nodes: &mut Vec<BvhNode>,
+    index: usize,
+    triangles: &[[Vector3<f32>; 3]],
+    faces: &mut [usize],
+    offset: usize,
+) -> () {
+    let bounds = faces
+        .iter()
+        .map(|face| triangle_bounds(&triangles[*face]))
+        .fold(triangle_bounds(&triangles[faces[0]]), |bounds, other| {
+            bounds.merged(&other)
+        });
+    nodes[index].bounds = bounds;
+    if faces.len() <= MAX_LEAF_TRIANGLES {
+        nodes[index].first = offset;
+        nodes[index].count = faces.len();
+        return;
+    }
+    let extent = bounds.max - bounds.min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let centroid = |face: &usize| {
+        let triangle = &triangles[*face];
+        triangle[0][axis] + triangle[1][axis] + triangle[2][axis]
+    };
+    faces.sort_by(|a, b| {
+        centroid(a)
+            .partial_cmp(&centroid(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let first_child = nodes.len();
+    for _ in 0..2 {
+        nodes.push(BvhNode {
+            bounds: bounds,
+            first: 0,
+            count: 0,
+        });
+    }
+    nodes[index].first = first_child;
+    let middle = faces.len() / 2;
+    let (left, right) = faces.split_at_mut(middle);
+    build_node(nodes, first_child, triangles, left, offset);
+    build_node(nodes, first_child + 1, triangles, right, offset + middle);
+}
+
+/// Returns the box containing a triangle.
+fn triangle_bounds(triangle: &[Vector3<f32>; 3]) -> Aabb {
+    Aabb::new(
+        triangle[0]
+            .zip_map(&triangle[1], f32::min)
+            .zip_map(&triangle[2], f32::min),
+        triangle[0]
+            .zip_map(&triangle[1], f32::max)
+            .zip_map(&triangle[2], f32::max),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid of `size` by `size` quads on the XY plane at height `z`, two triangles each.
+    fn grid(size: usize, z: f32) -> Vec<[Vector3<f32>; 3]> {
+        let mut triangles = Vec::new();
+        for x in 0..size {
+            for y in 0..size {
+                let (x, y) = (x as f32, y as f32);
+                let corner = |dx: f32, dy: f32| Vector3::new(x + dx, y + dy, z);
+                triangles.push([corner(0., 0.), corner(1., 0.), corner(1., 1.)]);
+                triangles.push([corner(0., 0.), corner(1., 1.), corner(0., 1.)]);
+            }
+        }
+        triangles
+    }
+
+    /// Closest hit by testing every triangle.
+    fn brute_force(triangles: &[[Vector3<f32>; 3]], ray: &Ray) -> Option<(usize, f32)> {
+        triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, triangle)| {
+                ray.intersect_triangle(triangle)
+                    .map(|hit| (index, hit.distance))
+            })
+            .fold(None, |closest: Option<(usize, f32)>, hit| match closest {
+                Some(closest) if closest.1 <= hit.1 => Some(closest),
+                _ => Some(hit),
+            })
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut triangles = grid(8, 0.);
+        triangles.extend(grid(4, -2.));
+        let bvh = TriangleBvh::new(triangles.clone());
+        for i in 0..40 {
+            let x = (i as f32 * 0.37) % 9. - 0.5;
+            let y = (i as f32 * 0.53) % 9. - 0.5;
+            let ray = Ray::new(Vector3::new(x, y, 5.), Vector3::new(0.01, -0.02, -1.));
+            let expected = brute_force(&triangles, &ray);
+            let found = bvh
+                .intersect(&ray)
+                .map(|(index, hit)| (index, hit.distance));
+            match (expected, found) {
+                (Some(expected), Some(found)) => {
+                    assert!((expected.1 - found.1).abs() < 1e-5);
+                    assert_eq!(triangles[expected.0][0].z, triangles[found.0][0].z);
+                }
+                (None, None) => {}
+                _ => panic!("BVH and brute force disagree for ray {:?}", ray),
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_returns_original_face_index() {
+        let triangles = grid(3, 0.);
+        let bvh = TriangleBvh::new(triangles.clone());
+        let ray = Ray::new(Vector3::new(2.75, 1.25, 1.), Vector3::new(0., 0., -1.));
+        let (index, _) = bvh.intersect(&ray).unwrap();
+        assert_eq!(Some(index), brute_force(&triangles, &ray).map(|hit| hit.0));
+        assert!(TriangleBvh::new(Vec::new()).intersect(&ray).is_none());
+    }
+}
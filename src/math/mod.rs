@@ -0,0 +1,133 @@
+//! Math helpers complementing `nalgebra` for common 3d engine needs.
+
+mod bounds;
+
+mod bvh;
+
+mod ray;
+
+mod spline;
+
+pub use bounds::{Aabb, BoundingSphere, Frustum};
+pub use bvh::TriangleBvh;
+pub use ray::{Ray, TriangleHit};
+pub use spline::{Spline, SplineKind};
+
+use nalgebra::{Matrix3, Matrix4, UnitQuaternion, Vector3};
+
+/// Decomposes an affine transform matrix into its translation, rotation and scale.
+///
+/// Shear and perspective components are ignored; the rotation is the closest rotation
+/// to the matrix's normalized upper 3x3 block.
+pub fn decompose_matrix(
+    matrix: &Matrix4<f32>,
+) -> (Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>) {
+    let translation = Vector3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+    let mut basis: Matrix3<f32> = matrix
+        .fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0)
+        .into();
+    let scale = Vector3::new(
+        basis.column(0).norm(),
+        basis.column(1).norm(),
+        basis.column(2).norm(),
+    );
+    for i in 0..3 {
+        if scale[i] > std::f32::EPSILON {
+            let mut column = basis.column_mut(i);
+            column /= scale[i];
+        }
+    }
+    (translation, UnitQuaternion::from_matrix(&basis), scale)
+}
+
+/// Returns the interpolation factor to use for exponential smoothing, given a time constant
+/// and a time step, both in seconds. A time constant of `0` (or less) returns `1` (no smoothing).
+pub fn smoothing_factor(time_constant: f32, delta: f32) -> f32 {
+    if time_constant <= 0. {
+        1.
+    } else {
+        1. - (-delta / time_constant).exp()
+    }
+}
+
+/// Smooth 1D value noise, between -1 and 1, with one random value per integer `x`.  
+/// Different seeds give uncorrelated noise.
+pub fn value_noise(seed: u32, x: f32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let t = t * t * (3.0 - 2.0 * t);
+    let cell = cell as i32 as u32;
+    let from = hash_to_unit(seed, cell);
+    let to = hash_to_unit(seed, cell.wrapping_add(1));
+    from + (to - from) * t
+}
+
+/// Hashes a seed and an integer to a pseudo-random value between -1 and 1.
+fn hash_to_unit(seed: u32, value: u32) -> f32 {
+    let mut hash = value ^ seed.wrapping_mul(0x9e37_79b9);
+    hash = (hash ^ (hash >> 16)).wrapping_mul(0x7feb_352d);
+    hash = (hash ^ (hash >> 15)).wrapping_mul(0x846c_a68b);
+    hash ^= hash >> 16;
+    hash as f32 / std::u32::MAX as f32 * 2.0 - 1.0
+}
+
+/// Spherical interpolation between two rotations that never panics, falling back to `to`
+/// when both rotations are opposite.
+pub fn safe_slerp(
+    from: &UnitQuaternion<f32>,
+    to: &UnitQuaternion<f32>,
+    t: f32,
+) -> UnitQuaternion<f32> {
+    from.try_slerp(to, t, 1.0e-6).unwrap_or(*to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompose_matrix_round_trips() {
+        let rotation = UnitQuaternion::from_euler_angles(0.3, -0.7, 1.1);
+        let translation = Vector3::new(1., -2., 3.);
+        let scale = Vector3::new(2., 0.5, 3.);
+        let matrix = Matrix4::new_translation(&translation)
+            * rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&scale);
+        let (t, r, s) = decompose_matrix(&matrix);
+        assert!((t - translation).norm() < 1e-5);
+        assert!((s - scale).norm() < 1e-5);
+        assert!(r.angle_to(&rotation) < 1e-4);
+    }
+
+    #[test]
+    fn smoothing_factor_bounds() {
+        assert_eq!(smoothing_factor(0., 0.016), 1.);
+        assert_eq!(smoothing_factor(-1., 0.016), 1.);
+        let factor = smoothing_factor(1., 1.);
+        assert!((factor - (1. - (-1f32).exp())).abs() < 1e-6);
+        assert!(smoothing_factor(1., 0.) == 0.);
+    }
+
+    #[test]
+    fn value_noise_is_continuous_and_bounded() {
+        for i in 0..1000 {
+            let x = i as f32 * 0.013 - 5.;
+            let value = value_noise(7, x);
+            assert!(value >= -1. && value <= 1.);
+            assert!((value - value_noise(7, x + 0.001)).abs() < 0.01);
+        }
+        assert_eq!(value_noise(3, 2.), value_noise(3, 2.));
+        assert_ne!(value_noise(3, 2.), value_noise(4, 2.));
+    }
+
+    #[test]
+    fn safe_slerp_handles_opposite_rotations() {
+        let from = UnitQuaternion::identity();
+        let to = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::PI);
+        let result = safe_slerp(&from, &to, 0.5);
+        assert!(result.coords.iter().all(|value| value.is_finite()));
+        let quarter = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 1.);
+        let half = safe_slerp(&from, &quarter, 0.5);
+        assert!((half.angle() - 0.5).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,164 @@
+//! Rays and their intersections with boxes and triangles, used for picking.
+
+use super::Aabb;
+use nalgebra::{Matrix4, Point3, Vector3};
+
+/// A half-line starting at `origin`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    /// Starting point of the ray
+    pub origin: Vector3<f32>,
+
+    /// Direction of the ray. Distances along the ray are in multiples of its length.
+    pub direction: Vector3<f32>,
+}
+
+/// Where a ray hits a triangle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TriangleHit {
+    /// Distance along the ray, in multiples of its direction's length
+    pub distance: f32,
+
+    /// Barycentric coordinates of the hit point: the weights of the triangle's three vertices
+    pub barycentric: Vector3<f32>,
+}
+
+impl Ray {
+    /// Constructor.
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction,
+        }
+    }
+
+    /// Returns the point at `distance` along the ray.
+    pub fn point_at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Returns the ray transformed by `matrix`. Distances along the transformed ray match
+    /// the ones along this ray, since the direction is not normalized.
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Ray {
+        Ray::new(
+            matrix.transform_point(&Point3::from(self.origin)).coords,
+            matrix.transform_vector(&self.direction),
+        )
+    }
+
+    /// Returns the distance at which the ray enters the box, `0` if it starts inside, or
+    /// `None` if it misses it.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = std::f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let mut entry = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let mut exit = (aabb.max[axis] - self.origin[axis]) * inverse;
+            if entry > exit {
+                std::mem::swap(&mut entry, &mut exit);
+            }
+            // NaNs come from rays parallel to the slab and starting on its plane: keep them in.
+            if !entry.is_nan() {
+                near = near.max(entry);
+            }
+            if !exit.is_nan() {
+                far = far.min(exit);
+            }
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    /// Intersects the ray with a triangle, from both sides (Möller–Trumbore algorithm).
+    pub fn intersect_triangle(&self, triangle: &[Vector3<f32>; 3]) -> Option<TriangleHit> {
+        let edge1 = triangle[1] - triangle[0];
+        let edge2 = triangle[2] - triangle[0];
+        let p = self.direction.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        if determinant.abs() < std::f32::EPSILON * edge1.norm() * edge2.norm() {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let s = self.origin - triangle[0];
+        let u = s.dot(&p) * inverse;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+        let q = s.cross(&edge1);
+        let v = self.direction.dot(&q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(&q) * inverse;
+        if distance < 0.0 {
+            return None;
+        }
+        Some(TriangleHit {
+            distance: distance,
+            barycentric: Vector3::new(1.0 - u - v, u, v),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> [Vector3<f32>; 3] {
+        [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(1., 0., 0.),
+            Vector3::new(0., 1., 0.),
+        ]
+    }
+
+    #[test]
+    fn ray_hits_triangle_from_both_sides() {
+        let front = Ray::new(Vector3::new(0.25, 0.25, 2.), Vector3::new(0., 0., -1.));
+        let hit = front.intersect_triangle(&triangle()).unwrap();
+        assert!((hit.distance - 2.).abs() < 1e-6);
+        assert!((hit.barycentric - Vector3::new(0.5, 0.25, 0.25)).norm() < 1e-6);
+        let back = Ray::new(Vector3::new(0.25, 0.25, -2.), Vector3::new(0., 0., 1.));
+        assert!(back.intersect_triangle(&triangle()).is_some());
+    }
+
+    #[test]
+    fn ray_misses_triangle() {
+        let outside = Ray::new(Vector3::new(0.75, 0.75, 2.), Vector3::new(0., 0., -1.));
+        assert!(outside.intersect_triangle(&triangle()).is_none());
+        let away = Ray::new(Vector3::new(0.25, 0.25, 2.), Vector3::new(0., 0., 1.));
+        assert!(away.intersect_triangle(&triangle()).is_none());
+        let parallel = Ray::new(Vector3::new(-1., 0.25, 0.), Vector3::new(1., 0., 0.));
+        assert!(parallel.intersect_triangle(&triangle()).is_none());
+    }
+
+    #[test]
+    fn ray_enters_aabb() {
+        let aabb = Aabb::new(Vector3::repeat(-1.), Vector3::repeat(1.));
+        let ray = Ray::new(Vector3::new(-5., 0., 0.), Vector3::new(2., 0., 0.));
+        assert_eq!(ray.intersect_aabb(&aabb), Some(2.));
+        let inside = Ray::new(Vector3::zeros(), Vector3::new(0., 1., 0.));
+        assert_eq!(inside.intersect_aabb(&aabb), Some(0.));
+        let miss = Ray::new(Vector3::new(-5., 3., 0.), Vector3::new(1., 0., 0.));
+        assert_eq!(miss.intersect_aabb(&aabb), None);
+        let on_face = Ray::new(Vector3::new(-5., 1., 0.), Vector3::new(1., 0., 0.));
+        assert_eq!(on_face.intersect_aabb(&aabb), Some(4.));
+    }
+
+    #[test]
+    fn transformed_ray_keeps_distances() {
+        let ray = Ray::new(Vector3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
+        let matrix = Matrix4::new_translation(&Vector3::new(1., 2., 3.)) * Matrix4::new_scaling(2.);
+        let transformed = ray.transformed(&matrix);
+        assert_eq!(transformed.origin, Vector3::new(1., 2., 3.));
+        assert_eq!(
+            transformed.point_at(1.5),
+            matrix
+                .transform_point(&Point3::from(ray.point_at(1.5)))
+                .coords
+        );
+    }
+}
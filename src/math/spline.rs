@@ -0,0 +1,307 @@
+//! Curves through control points, sampled by distance along them for constant speed motion.
+
+use crate::error::{W3DError, W3DErrorKind};
+use nalgebra::Vector3;
+use std::cmp::Ordering;
+
+/// Number of samples per segment of the arc length lookup table.
+const SAMPLES_PER_SEGMENT: usize = 32;
+
+/// Kind of curve drawn through the control points of a `Spline`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplineKind {
+    /// Uniform Catmull-Rom curve, passing through every control point
+    CatmullRom,
+
+    /// Cubic Bézier segments: an anchor, two handles, and the next segment's anchor. Open
+    /// curves have `3n + 1` control points, closed ones `3n`, their last segment ending at the
+    /// first anchor.
+    Bezier,
+}
+
+/// ## Spline
+///
+/// A curve of cubic segments through control points, open or closed, with a lookup table of
+/// its arc length so that it can be sampled by distance.
+#[derive(Clone, Debug)]
+pub struct Spline {
+    kind: SplineKind,
+    points: Vec<Vector3<f32>>,
+    closed: bool,
+
+    /// Distance along the curve at each of the `SAMPLES_PER_SEGMENT` evenly spaced
+    /// parameters of each segment, starting with `0`
+    lengths: Vec<f32>,
+}
+
+impl Spline {
+    /// Constructor.
+    /// Fails if there are too few control points: two for Catmull-Rom curves, three for
+    /// closed ones, or if Bézier control points don't make whole segments.
+    pub fn new(
+        kind: SplineKind,
+        points: Vec<Vector3<f32>>,
+        closed: bool,
+    ) -> Result<Spline, W3DError> {
+        let valid = match (kind, closed) {
+            (SplineKind::CatmullRom, false) => points.len() >= 2,
+            (SplineKind::CatmullRom, true) => points.len() >= 3,
+            (SplineKind::Bezier, false) => points.len() >= 4 && points.len() % 3 == 1,
+            (SplineKind::Bezier, true) => points.len() >= 3 && points.len() % 3 == 0,
+        };
+        if !valid {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Too few control points for the spline, or Bézier segments are incomplete.",
+                &points.len().to_string(),
+            ));
+        }
+        let mut spline = Spline {
+            kind: kind,
+            points: points,
+            closed: closed,
+            lengths: Vec::new(),
+        };
+        spline.compute_lengths();
+        Ok(spline)
+    }
+
+    /// Getter for the control points.
+    pub fn get_points(&self) -> &[Vector3<f32>] {
+        &self.points
+    }
+
+    /// Returns `true` if the curve ends where it starts.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the number of cubic segments of the curve.
+    pub fn get_segment_count(&self) -> usize {
+        let count = self.points.len();
+        match (self.kind, self.closed) {
+            (SplineKind::CatmullRom, false) => count - 1,
+            (SplineKind::CatmullRom, true) => count,
+            (SplineKind::Bezier, false) => (count - 1) / 3,
+            (SplineKind::Bezier, true) => count / 3,
+        }
+    }
+
+    /// Returns the approximate length of the curve, from its lookup table.
+    pub fn get_length(&self) -> f32 {
+        self.lengths.last().cloned().unwrap_or(0.)
+    }
+
+    /// Returns the position and the derivative of the curve at `parameter`, going from `0`
+    /// at the start to the segment count at the end. The derivative is not normalized.
+    pub fn evaluate(&self, parameter: f32) -> (Vector3<f32>, Vector3<f32>) {
+        let segment_count = self.get_segment_count();
+        let parameter = parameter.max(0.).min(segment_count as f32);
+        let segment = (parameter.floor() as usize).min(segment_count - 1);
+        let t = parameter - segment as f32;
+        let [p0, p1, p2, p3] = self.get_segment_points(segment);
+        match self.kind {
+            SplineKind::CatmullRom => {
+                let a = p1 * 2.;
+                let b = p2 - p0;
+                let c = p0 * 2. - p1 * 5. + p2 * 4. - p3;
+                let d = p1 * 3. - p0 - p2 * 3. + p3;
+                let position = (a + b * t + c * (t * t) + d * (t * t * t)) * 0.5;
+                let derivative = (b + c * (2. * t) + d * (3. * t * t)) * 0.5;
+                (position, derivative)
+            }
+            SplineKind::Bezier => {
+                let s = 1. - t;
+                let position = p0 * (s * s * s)
+                    + p1 * (3. * s * s * t)
+                    + p2 * (3. * s * t * t)
+                    + p3 * (t * t * t);
+                let derivative =
+                    (p1 - p0) * (3. * s * s) + (p2 - p1) * (6. * s * t) + (p3 - p2) * (3. * t * t);
+                (position, derivative)
+            }
+        }
+    }
+
+    /// Returns the position and the unit tangent of the curve at `distance` from its start,
+    /// clamped to the curve. The tangent is zero where the curve doesn't move.
+    pub fn sample(&self, distance: f32) -> (Vector3<f32>, Vector3<f32>) {
+        let (position, derivative) = self.evaluate(self.get_parameter_at(distance));
+        let tangent = derivative
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(Vector3::zeros);
+        (position, tangent)
+    }
+
+    /// Converts a distance from the start of the curve to a curve parameter, interpolating
+    /// linearly between the samples of the lookup table.
+    pub fn get_parameter_at(&self, distance: f32) -> f32 {
+        let distance = distance.max(0.).min(self.get_length());
+        let sample = match self
+            .lengths
+            .binary_search_by(|length| length.partial_cmp(&distance).unwrap_or(Ordering::Less))
+        {
+            Ok(sample) => return sample as f32 / SAMPLES_PER_SEGMENT as f32,
+            Err(sample) => sample.max(1).min(self.lengths.len() - 1),
+        };
+        let (from, to) = (self.lengths[sample - 1], self.lengths[sample]);
+        let t = if to > from {
+            (distance - from) / (to - from)
+        } else {
+            0.
+        };
+        (sample - 1) as f32 / SAMPLES_PER_SEGMENT as f32 + t / SAMPLES_PER_SEGMENT as f32
+    }
+
+    /// Returns the four control points of a segment. Catmull-Rom curves clamp their end
+    /// points, or wrap around if closed.
+    fn get_segment_points(&self, segment: usize) -> [Vector3<f32>; 4] {
+        let count = self.points.len();
+        match self.kind {
+            SplineKind::CatmullRom => {
+                let point = |index: isize| {
+                    let index = if self.closed {
+                        index.rem_euclid(count as isize)
+                    } else {
+                        index.max(0).min(count as isize - 1)
+                    };
+                    self.points[index as usize]
+                };
+                let segment = segment as isize;
+                [
+                    point(segment - 1),
+                    point(segment),
+                    point(segment + 1),
+                    point(segment + 2),
+                ]
+            }
+            SplineKind::Bezier => {
+                let start = segment * 3;
+                [
+                    self.points[start],
+                    self.points[start + 1],
+                    self.points[start + 2],
+                    self.points[(start + 3) % count],
+                ]
+            }
+        }
+    }
+
+    /// Fills the arc length lookup table by summing the chords between samples.
+    fn compute_lengths(&mut self) -> () {
+        let sample_count = self.get_segment_count() * SAMPLES_PER_SEGMENT;
+        self.lengths = Vec::with_capacity(sample_count + 1);
+        self.lengths.push(0.);
+        let mut previous = self.evaluate(0.).0;
+        let mut length = 0.;
+        for sample in 1..=sample_count {
+            let position = self.evaluate(sample as f32 / SAMPLES_PER_SEGMENT as f32).0;
+            length += (position - previous).norm();
+            self.lengths.push(length);
+            previous = position;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// Distance between the handles and the anchors of the Bézier quarter circles.
+    const KAPPA: f32 = 0.552_284_8;
+
+    /// A closed Bézier circle of radius `radius` in the XZ plane, as four quarters.
+    fn bezier_circle(radius: f32) -> Spline {
+        let mut points = Vec::new();
+        for quarter in 0..4 {
+            let angle = quarter as f32 * PI / 2.;
+            let (anchor, tangent) = (
+                Vector3::new(angle.cos(), 0., angle.sin()),
+                Vector3::new(-angle.sin(), 0., angle.cos()),
+            );
+            let next = Vector3::new((angle + PI / 2.).cos(), 0., (angle + PI / 2.).sin());
+            let next_tangent = Vector3::new(-(angle + PI / 2.).sin(), 0., (angle + PI / 2.).cos());
+            points.push(anchor * radius);
+            points.push((anchor + tangent * KAPPA) * radius);
+            points.push((next - next_tangent * KAPPA) * radius);
+        }
+        Spline::new(SplineKind::Bezier, points, true).unwrap()
+    }
+
+    /// Returns the largest relative difference between the distances travelled over equal
+    /// distance steps along the spline and the step itself.
+    fn speed_error(spline: &Spline, steps: usize) -> f32 {
+        let step = spline.get_length() / steps as f32;
+        (0..steps)
+            .map(|index| {
+                let from = spline.sample(index as f32 * step).0;
+                let to = spline.sample((index + 1) as f32 * step).0;
+                ((to - from).norm() - step).abs() / step
+            })
+            .fold(0., f32::max)
+    }
+
+    #[test]
+    fn bezier_circle_is_sampled_at_constant_speed() {
+        let radius = 10.;
+        let circle = bezier_circle(radius);
+        assert!((circle.get_length() - 2. * PI * radius).abs() / (2. * PI * radius) < 1.0e-3);
+        assert!(speed_error(&circle, 100) < 0.02);
+        for index in 0..50 {
+            let (position, tangent) = circle.sample(index as f32 * circle.get_length() / 50.);
+            assert!((position.norm() - radius).abs() / radius < 1.0e-3);
+            assert!((tangent.norm() - 1.).abs() < 1.0e-4);
+            assert!(tangent.dot(&position).abs() / radius < 1.0e-2);
+        }
+    }
+
+    #[test]
+    fn unevenly_spaced_catmull_rom_is_sampled_at_constant_speed() {
+        // Control points crowded on one side of the circle: the curve parameter alone
+        // moves several times faster on the other side.
+        let angles = [0., 0.2, 0.4, 0.6, 0.8, 1.0, 2.5, 4.0, 5.5];
+        let points = angles
+            .iter()
+            .map(|angle: &f32| Vector3::new(angle.cos(), angle.sin(), 0.) * 5.)
+            .collect();
+        let spline = Spline::new(SplineKind::CatmullRom, points, true).unwrap();
+        let segment_count = spline.get_segment_count() as f32;
+        let parameter_speeds: Vec<f32> = (0..90)
+            .map(|index| spline.evaluate(index as f32 * segment_count / 90.).1.norm())
+            .collect();
+        let fastest = parameter_speeds.iter().cloned().fold(0., f32::max);
+        let slowest = parameter_speeds
+            .iter()
+            .cloned()
+            .fold(std::f32::MAX, f32::min);
+        assert!(fastest > 3. * slowest);
+
+        assert!(speed_error(&spline, 200) < 0.03);
+    }
+
+    #[test]
+    fn distances_are_clamped_to_the_curve() {
+        let points = vec![
+            Vector3::zeros(),
+            Vector3::new(3., 0., 0.),
+            Vector3::new(3., 4., 0.),
+        ];
+        let spline = Spline::new(SplineKind::CatmullRom, points, false).unwrap();
+        assert_eq!(spline.sample(-1.).0, Vector3::zeros());
+        assert!(
+            (spline.sample(spline.get_length() + 1.).0 - Vector3::new(3., 4., 0.)).norm() < 1.0e-5
+        );
+        assert!(spline.get_length() > 7. && spline.get_length() < 7.5);
+    }
+
+    #[test]
+    fn incomplete_control_points_are_rejected() {
+        let points = |count| vec![Vector3::zeros(); count];
+        assert!(Spline::new(SplineKind::CatmullRom, points(1), false).is_err());
+        assert!(Spline::new(SplineKind::CatmullRom, points(2), true).is_err());
+        assert!(Spline::new(SplineKind::Bezier, points(5), false).is_err());
+        assert!(Spline::new(SplineKind::Bezier, points(4), true).is_err());
+        assert!(Spline::new(SplineKind::Bezier, points(6), true).is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+//! Rendering of `BlobShadow`s as darkened quads lying on the ground, in a single draw call.
+
+use super::builtin_shaders::{
+    compile_builtin_material, BLOB_SHADOW_FRAGMENT_SHADER, BLOB_SHADOW_VERTEX_SHADER,
+};
+use super::{DynamicBatch, Material, Uniform};
+use crate::component::BlobShadow;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::constants::{
+    OPACITY_BUFFER_NAME, TEXTURE_NAME, UV_BUFFER_NAME, VERTEX_BUFFER_NAME,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlTexture, WebGlUniformLocation};
+use wtvr3d_file::ShaderDataType;
+
+/// Attributes of a shadow vertex, interleaved in this order.
+const BLOB_SHADOW_ATTRIBUTES: [(&str, ShaderDataType); 3] = [
+    (VERTEX_BUFFER_NAME, ShaderDataType::Vector3),
+    (UV_BUFFER_NAME, ShaderDataType::Vector2),
+    (OPACITY_BUFFER_NAME, ShaderDataType::Single),
+];
+
+/// Number of vertices per shadow (two triangles).
+const VERTICES_PER_BLOB_SHADOW: usize = 6;
+
+/// Width and height of the generated gradient texture, in pixels.
+const GRADIENT_TEXTURE_SIZE: usize = 64;
+
+/// Polygon offset keeping shadows in front of the ground they lie on.
+const BLOB_SHADOW_POLYGON_OFFSET: (f32, f32) = (-1.0, -4.0);
+
+/// ## BlobShadowRenderer
+///
+/// Holds the built-in blob shadow `Material`, the radial gradient texture it generates and a
+/// single dynamic vertex buffer shared by all shadows, drawn at once every frame.
+///
+/// Shadows are drawn after the opaque and decal meshes with multiplicative blending, so that
+/// overlapping shadows darken each other, with depth test on and depth writes off.
+pub struct BlobShadowRenderer {
+    /// Built-in unlit material
+    material: Rc<RefCell<Material>>,
+
+    /// Location of the texture sampler uniform
+    texture_location: Option<WebGlUniformLocation>,
+
+    /// Radial gradient, opaque at the center and transparent on the edge
+    gradient_texture: Rc<WebGlTexture>,
+
+    /// Interleaved position, texture coordinates and opacity buffers
+    batch: Option<DynamicBatch>,
+
+    /// Vertex data for the current frame, kept to avoid reallocating every frame
+    vertex_data: Vec<f32>,
+}
+
+impl BlobShadowRenderer {
+    /// Constructor. Compiles the built-in blob shadow material and generates its texture.
+    pub fn new(context: &WebGlRenderingContext) -> Result<BlobShadowRenderer, W3DError> {
+        let mut material = compile_builtin_material(
+            context,
+            BLOB_SHADOW_VERTEX_SHADER,
+            BLOB_SHADOW_FRAGMENT_SHADER,
+            "__wtvr3d_blob_shadows",
+            &[VERTEX_BUFFER_NAME, UV_BUFFER_NAME, OPACITY_BUFFER_NAME],
+        )?;
+        material.set_transparent(true);
+        let texture_location =
+            context.get_uniform_location(material.get_program().as_ref().unwrap(), TEXTURE_NAME);
+        Ok(BlobShadowRenderer {
+            material: Rc::new(RefCell::new(material)),
+            texture_location: texture_location,
+            gradient_texture: Rc::new(create_gradient_texture(context)?),
+            batch: None,
+            vertex_data: Vec::new(),
+        })
+    }
+
+    /// Returns the built-in blob shadow material.
+    pub fn get_material(&self) -> Rc<RefCell<Material>> {
+        self.material.clone()
+    }
+
+    /// Draws the visible `shadows` in a single draw call.
+    /// The camera uniforms must have been set on the blob shadow material beforehand.
+    pub fn render(
+        &mut self,
+        context: &WebGlRenderingContext,
+        shadows: &[&BlobShadow],
+    ) -> Result<(), W3DError> {
+        self.vertex_data.clear();
+        let mut count = 0;
+        for shadow in shadows {
+            if push_blob_shadow_vertices(&mut self.vertex_data, shadow) {
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        if self.batch.is_none() {
+            self.batch = Some(DynamicBatch::new(
+                context,
+                &BLOB_SHADOW_ATTRIBUTES,
+                self.vertex_data.len(),
+            )?);
+        }
+
+        let material = self.material.borrow();
+        context.use_program(material.get_program().as_ref());
+        material.disable_unused_attributes(context);
+        let mut texture_uniform = Uniform::new_with_location(
+            TEXTURE_NAME,
+            self.texture_location.clone(),
+            Box::new(self.gradient_texture.clone()),
+        );
+        texture_uniform.set_texture_index(0);
+        texture_uniform.set_to_context(context)?;
+
+        self.batch
+            .as_mut()
+            .unwrap()
+            .upload_and_bind(context, &material, &self.vertex_data);
+
+        context.enable(WebGlRenderingContext::BLEND);
+        context.blend_func(
+            WebGlRenderingContext::DST_COLOR,
+            WebGlRenderingContext::ZERO,
+        );
+        context.depth_mask(false);
+        context.enable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+        context.polygon_offset(BLOB_SHADOW_POLYGON_OFFSET.0, BLOB_SHADOW_POLYGON_OFFSET.1);
+        context.draw_arrays(
+            WebGlRenderingContext::TRIANGLES,
+            0,
+            (count * VERTICES_PER_BLOB_SHADOW) as i32,
+        );
+        context.disable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+        context.polygon_offset(0.0, 0.0);
+        context.depth_mask(true);
+        context.disable(WebGlRenderingContext::BLEND);
+        Ok(())
+    }
+}
+
+/// Appends the six vertices of a shadow's quad on the ground to `vertex_data`, if the
+/// shadow is visible this frame. Returns `true` if it is.
+fn push_blob_shadow_vertices(vertex_data: &mut Vec<f32>, shadow: &BlobShadow) -> bool {
+    let (center, opacity) = match shadow.get_projection() {
+        Some(projection) => projection,
+        None => return false,
+    };
+    let radius = shadow.radius;
+    // Counter-clockwise seen from above, so that the quad isn't culled.
+    let corners = [
+        (-radius, radius, 0.0, 1.0),
+        (radius, radius, 1.0, 1.0),
+        (radius, -radius, 1.0, 0.0),
+        (-radius, radius, 0.0, 1.0),
+        (radius, -radius, 1.0, 0.0),
+        (-radius, -radius, 0.0, 0.0),
+    ];
+    for (x, z, u, v) in &corners {
+        vertex_data.extend_from_slice(&[center.x + x, center.y, center.z + z, *u, *v, opacity]);
+    }
+    true
+}
+
+/// Creates the radial gradient texture shared by every shadow: white, with an alpha going
+/// smoothly from 1 at the center to 0 on the inscribed circle.
+fn create_gradient_texture(context: &WebGlRenderingContext) -> Result<WebGlTexture, W3DError> {
+    let texture = context.create_texture().ok_or_else(|| {
+        W3DError::new(
+            W3DErrorKind::GlResource,
+            "Could not create the blob shadow texture.",
+        )
+    })?;
+    let mut pixels = Vec::with_capacity(GRADIENT_TEXTURE_SIZE * GRADIENT_TEXTURE_SIZE * 4);
+    let half_size = GRADIENT_TEXTURE_SIZE as f32 / 2.0;
+    for y in 0..GRADIENT_TEXTURE_SIZE {
+        for x in 0..GRADIENT_TEXTURE_SIZE {
+            let dx = (x as f32 + 0.5 - half_size) / half_size;
+            let dy = (y as f32 + 0.5 - half_size) / half_size;
+            let t = (1.0 - (dx * dx + dy * dy).sqrt()).max(0.0).min(1.0);
+            let alpha = t * t * (3.0 - 2.0 * t);
+            pixels.extend_from_slice(&[255, 255, 255, (alpha * 255.0).round() as u8]);
+        }
+    }
+    context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
+    let result = context
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            WebGlRenderingContext::TEXTURE_2D,
+            0,
+            WebGlRenderingContext::RGBA as i32,
+            GRADIENT_TEXTURE_SIZE as i32,
+            GRADIENT_TEXTURE_SIZE as i32,
+            0,
+            WebGlRenderingContext::RGBA,
+            WebGlRenderingContext::UNSIGNED_BYTE,
+            Some(&pixels),
+        );
+    if result.is_err() {
+        context.delete_texture(Some(&texture));
+        return Err(W3DError::new(
+            W3DErrorKind::GlResource,
+            "Blob shadow texture upload failed.",
+        ));
+    }
+    for (parameter, value) in &[
+        (
+            WebGlRenderingContext::TEXTURE_MIN_FILTER,
+            WebGlRenderingContext::LINEAR,
+        ),
+        (
+            WebGlRenderingContext::TEXTURE_WRAP_S,
+            WebGlRenderingContext::CLAMP_TO_EDGE,
+        ),
+        (
+            WebGlRenderingContext::TEXTURE_WRAP_T,
+            WebGlRenderingContext::CLAMP_TO_EDGE,
+        ),
+    ] {
+        context.tex_parameteri(WebGlRenderingContext::TEXTURE_2D, *parameter, *value as i32);
+    }
+    Ok(texture)
+}
@@ -0,0 +1,372 @@
+//! Bloom effect of HDR rendering: the colors above a threshold are blurred over a chain of
+//! smaller and smaller targets, then added back by the tone mapping pass.
+
+use super::builtin_shaders::{compile_builtin_material, FULL_SCREEN_VERTEX_SHADER};
+use super::{Material, RenderTarget, Uniform};
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::constants::{TEXTURE_NAME, VERTEX_BUFFER_NAME};
+use crate::utils::ColorFormat;
+use nalgebra::Vector2;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlTexture, WebGlUniformLocation};
+
+/// Largest number of targets in the blur chain.
+const BLOOM_MIP_COUNT: usize = 5;
+
+/// Name of the uniform holding the size of a texel of the sampled texture.
+const TEXEL_SIZE_NAME: &str = "u_texel_size";
+
+/// Name of the brightness threshold uniform.
+const THRESHOLD_NAME: &str = "u_threshold";
+
+/// ## BloomSettings
+///
+/// How bloom looks, set per scene.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomSettings {
+    /// Brightness above which colors bloom, before the exposure is applied
+    pub threshold: f32,
+
+    /// Factor applied to the blurred colors added to the scene
+    pub intensity: f32,
+}
+
+/// Material of a step of the chain and the locations of its own uniforms.
+struct BloomStep {
+    material: Material,
+    texture_location: Option<WebGlUniformLocation>,
+    texel_size_location: Option<WebGlUniformLocation>,
+}
+
+impl BloomStep {
+    /// Compiles the built-in material of a step, using `fragment_shader`.
+    fn new(
+        context: &WebGlRenderingContext,
+        fragment_shader: &str,
+        id: &str,
+    ) -> Result<BloomStep, W3DError> {
+        let material = compile_builtin_material(
+            context,
+            FULL_SCREEN_VERTEX_SHADER,
+            fragment_shader,
+            id,
+            &[VERTEX_BUFFER_NAME],
+        )?;
+        let program = material.get_program().as_ref().unwrap();
+        let texture_location = context.get_uniform_location(program, TEXTURE_NAME);
+        let texel_size_location = context.get_uniform_location(program, TEXEL_SIZE_NAME);
+        Ok(BloomStep {
+            material: material,
+            texture_location: texture_location,
+            texel_size_location: texel_size_location,
+        })
+    }
+
+    /// Uses the step's program and sets its uniforms to sample `texture`, of `size` pixels.
+    fn use_step(
+        &self,
+        context: &WebGlRenderingContext,
+        texture: Rc<WebGlTexture>,
+        size: (u32, u32),
+    ) -> Result<(), W3DError> {
+        context.use_program(self.material.get_program().as_ref());
+        let mut texture_uniform = Uniform::new_with_location(
+            TEXTURE_NAME,
+            self.texture_location.clone(),
+            Box::new(texture),
+        );
+        texture_uniform.set_texture_index(0);
+        texture_uniform.set_to_context(context)?;
+        Uniform::new_with_location(
+            TEXEL_SIZE_NAME,
+            self.texel_size_location.clone(),
+            Box::new(Vector2::new(1.0 / size.0 as f32, 1.0 / size.1 as f32)),
+        )
+        .set_to_context(context)
+    }
+}
+
+/// ## BloomPass
+///
+/// Chain of `Rgba16F` targets, the first one half the size of the HDR target and each
+/// following one half the size of the previous one. Every frame, the colors of the HDR
+/// target above the threshold are written to the first one, then blurred down the chain
+/// with a dual filter and added back up to the first one, whose texture the tone mapping
+/// pass adds to the scene.
+pub struct BloomPass {
+    /// Blur chain, from the largest target to the smallest one
+    mips: Vec<RenderTarget>,
+
+    /// Size of the HDR target the chain is allocated for, in pixels
+    size: (u32, u32),
+
+    /// Extracts the colors above the threshold while halving the resolution
+    threshold_step: BloomStep,
+
+    /// Location of the threshold uniform of `threshold_step`
+    threshold_location: Option<WebGlUniformLocation>,
+
+    /// Blurs a target into the next, smaller one
+    downsample_step: BloomStep,
+
+    /// Blurs a target into the previous, larger one, added to its colors
+    upsample_step: BloomStep,
+
+    pub settings: BloomSettings,
+}
+
+impl BloomPass {
+    /// Constructor. Compiles the built-in materials and allocates the chain for an HDR target
+    /// of `size` pixels.
+    /// Fails if `Rgba16F` isn't in `renderable_formats`: colors above 1 would be clamped,
+    /// leaving nothing to extract.
+    pub fn new(
+        context: &WebGlRenderingContext,
+        renderable_formats: &[ColorFormat],
+        size: (u32, u32),
+        settings: BloomSettings,
+    ) -> Result<BloomPass, W3DError> {
+        if !renderable_formats.contains(&ColorFormat::Rgba16F) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Bloom needs half-float render targets, which the context doesn't support.",
+                ColorFormat::Rgba16F.get_name(),
+            ));
+        }
+        let threshold_step = BloomStep::new(
+            context,
+            THRESHOLD_FRAGMENT_SHADER,
+            "__wtvr3d_bloom_threshold",
+        )?;
+        let threshold_location = context.get_uniform_location(
+            threshold_step.material.get_program().as_ref().unwrap(),
+            THRESHOLD_NAME,
+        );
+        let mut bloom_pass = BloomPass {
+            mips: Vec::new(),
+            size: (0, 0),
+            threshold_step: threshold_step,
+            threshold_location: threshold_location,
+            downsample_step: BloomStep::new(
+                context,
+                DOWNSAMPLE_FRAGMENT_SHADER,
+                "__wtvr3d_bloom_downsample",
+            )?,
+            upsample_step: BloomStep::new(
+                context,
+                UPSAMPLE_FRAGMENT_SHADER,
+                "__wtvr3d_bloom_upsample",
+            )?,
+            settings: settings,
+        };
+        if let Err(error) = bloom_pass.resize(context, renderable_formats, size) {
+            bloom_pass.delete(context);
+            return Err(error);
+        }
+        Ok(bloom_pass)
+    }
+
+    /// Allocates the chain again for an HDR target of `size` pixels, if it's another size.
+    pub fn resize(
+        &mut self,
+        context: &WebGlRenderingContext,
+        renderable_formats: &[ColorFormat],
+        size: (u32, u32),
+    ) -> Result<(), W3DError> {
+        if size == self.size {
+            return Ok(());
+        }
+        self.delete_mips(context);
+        for mip_size in get_bloom_mip_sizes(size) {
+            self.mips.push(RenderTarget::new(
+                context,
+                renderable_formats,
+                mip_size,
+                ColorFormat::Rgba16F,
+            )?);
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    /// Renders the bloom of `source`, the HDR target's texture, through the chain. Returns
+    /// the texture to add to the scene, `None` if the target is too small to have a chain.
+    /// Leaves the first target of the chain bound, and the depth test and blending disabled.
+    /// `bind_full_screen_triangle` binds the vertices of the triangle to a material's
+    /// attributes, once its program is in use.
+    pub fn render<F>(
+        &self,
+        context: &WebGlRenderingContext,
+        source: Rc<WebGlTexture>,
+        bind_full_screen_triangle: F,
+    ) -> Result<Option<Rc<WebGlTexture>>, W3DError>
+    where
+        F: Fn(&Material),
+    {
+        let first_mip = match self.mips.first() {
+            Some(first_mip) => first_mip,
+            None => return Ok(None),
+        };
+        context.disable(WebGlRenderingContext::DEPTH_TEST);
+        context.disable(WebGlRenderingContext::BLEND);
+
+        BloomPass::bind_mip(context, first_mip);
+        self.threshold_step.use_step(context, source, self.size)?;
+        Uniform::new_with_location(
+            THRESHOLD_NAME,
+            self.threshold_location.clone(),
+            Box::new(self.settings.threshold),
+        )
+        .set_to_context(context)?;
+        bind_full_screen_triangle(&self.threshold_step.material);
+        context.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+
+        for pair in self.mips.windows(2) {
+            BloomPass::bind_mip(context, &pair[1]);
+            self.downsample_step
+                .use_step(context, pair[0].get_texture(), pair[0].get_size())?;
+            bind_full_screen_triangle(&self.downsample_step.material);
+            context.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+        }
+
+        context.enable(WebGlRenderingContext::BLEND);
+        context.blend_func(WebGlRenderingContext::ONE, WebGlRenderingContext::ONE);
+        for pair in self.mips.windows(2).rev() {
+            BloomPass::bind_mip(context, &pair[0]);
+            self.upsample_step
+                .use_step(context, pair[1].get_texture(), pair[1].get_size())?;
+            bind_full_screen_triangle(&self.upsample_step.material);
+            context.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+        }
+        context.disable(WebGlRenderingContext::BLEND);
+        Ok(Some(first_mip.get_texture()))
+    }
+
+    /// Releases the chain and the programs of the materials.
+    pub fn delete(&mut self, context: &WebGlRenderingContext) -> () {
+        self.delete_mips(context);
+        self.threshold_step.material.delete_program(context);
+        self.downsample_step.material.delete_program(context);
+        self.upsample_step.material.delete_program(context);
+    }
+
+    /// Releases the targets of the chain, which must be allocated again before rendering.
+    fn delete_mips(&mut self, context: &WebGlRenderingContext) -> () {
+        for mip in self.mips.drain(..) {
+            mip.delete_with_texture(context);
+        }
+        self.size = (0, 0);
+    }
+
+    /// Binds a target of the chain, with a viewport covering it.
+    fn bind_mip(context: &WebGlRenderingContext, mip: &RenderTarget) -> () {
+        let (width, height) = mip.get_size();
+        context.bind_framebuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            Some(mip.get_framebuffer()),
+        );
+        context.viewport(0, 0, width as i32, height as i32);
+    }
+}
+
+/// Returns the sizes of the targets of the blur chain for an HDR target of `size` pixels:
+/// each half the size of the previous one, starting at half `size`, until
+/// `BLOOM_MIP_COUNT` targets or a dimension of 1 pixel.
+pub fn get_bloom_mip_sizes(size: (u32, u32)) -> Vec<(u32, u32)> {
+    let mut sizes = Vec::new();
+    let (mut width, mut height) = size;
+    while sizes.len() < BLOOM_MIP_COUNT && width >= 2 && height >= 2 {
+        width /= 2;
+        height /= 2;
+        sizes.push((width, height));
+    }
+    sizes
+}
+
+/// Fragment shader of the threshold step: averages four texels of the HDR target, then keeps
+/// the part of the color whose brightness is above `u_threshold`.
+const THRESHOLD_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+uniform vec2 u_texel_size;
+uniform float u_threshold;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec4 offset = u_texel_size.xyxy * vec4(-0.5, -0.5, 0.5, 0.5);
+    vec3 color = texture2D(u_texture, v_tex_coordinates + offset.xy).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + offset.zy).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + offset.xw).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + offset.zw).rgb;
+    color *= 0.25;
+    float brightness = max(color.r, max(color.g, color.b));
+    color *= max(brightness - u_threshold, 0.0) / max(brightness, 0.0001);
+    gl_FragColor = vec4(color, 1.0);
+}
+"#;
+
+/// Fragment shader of the downsample steps: dual filter downsampling, from a target to the
+/// next one in the chain.
+const DOWNSAMPLE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+uniform vec2 u_texel_size;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec2 offset = u_texel_size;
+    vec3 color = texture2D(u_texture, v_tex_coordinates).rgb * 4.0;
+    color += texture2D(u_texture, v_tex_coordinates - offset).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + offset).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(offset.x, -offset.y)).rgb;
+    color += texture2D(u_texture, v_tex_coordinates - vec2(offset.x, -offset.y)).rgb;
+    gl_FragColor = vec4(color / 8.0, 1.0);
+}
+"#;
+
+/// Fragment shader of the upsample steps: dual filter upsampling, from a target to the
+/// previous one in the chain, added to its colors.
+const UPSAMPLE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+uniform vec2 u_texel_size;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec2 offset = u_texel_size * 0.5;
+    vec3 color = texture2D(u_texture, v_tex_coordinates + vec2(-offset.x * 2.0, 0.0)).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(0.0, offset.y * 2.0)).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(offset.x * 2.0, 0.0)).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(0.0, -offset.y * 2.0)).rgb;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(-offset.x, offset.y)).rgb * 2.0;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(offset.x, offset.y)).rgb * 2.0;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(offset.x, -offset.y)).rgb * 2.0;
+    color += texture2D(u_texture, v_tex_coordinates + vec2(-offset.x, -offset.y)).rgb * 2.0;
+    gl_FragColor = vec4(color / 12.0, 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mips_halve_down_to_the_mip_count() {
+        assert_eq!(
+            get_bloom_mip_sizes((1920, 1080)),
+            vec![(960, 540), (480, 270), (240, 135), (120, 67), (60, 33)]
+        );
+    }
+
+    #[test]
+    fn mips_stop_at_one_pixel() {
+        assert_eq!(get_bloom_mip_sizes((8, 3)), vec![(4, 1)]);
+        assert!(get_bloom_mip_sizes((1, 600)).is_empty());
+    }
+}
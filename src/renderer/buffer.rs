@@ -1,7 +1,9 @@
 //! Interface and implementations for managing WebGL Buffers and Attributes.
 
-use js_sys::{Float32Array, Uint16Array};
+use crate::error::{W3DError, W3DErrorKind};
+use js_sys::{Float32Array, Int16Array, Uint16Array, Uint8Array};
 use std::rc::Rc;
+use wasm_bindgen::JsCast;
 use web_sys::{WebGlBuffer, WebGlRenderingContext};
 use wtvr3d_file::ShaderDataType;
 
@@ -24,73 +26,246 @@ pub struct Buffer {
     /// Data type in the shader as defined in `ShaderDataType`
     data_type: ShaderDataType,
 
-    /// Numeric type (automatically set); can be Float32, Int16, and UInt8.
-    number_type: u32,
+    /// Type of the attribute's components in the `WebGlBuffer`
+    component_type: ComponentType,
 
     /// Custom stride to be used when setting the attribute pointer
     pub stride: i32,
 
     /// Offset in the giver buffer for the attribute pointer.
     pub offset: i32,
+
+    /// Number of components the underlying `WebGlBuffer` can hold without being reallocated.
+    capacity: usize,
+
+    /// Number of `u16` in the index `WebGlBuffer`, `0` if there is none.
+    index_count: usize,
 }
 
 impl Buffer {
+    /// Creates a new `Buffer` by uploading `data` (and optional `indexes`) to the GPU.
+    ///
+    /// The `ARRAY_BUFFER` and `ELEMENT_ARRAY_BUFFER` bindings that were active before the call
+    /// are restored once the upload is done, so construction never leaks bindings into
+    /// whatever is rendered or constructed next.  
+    /// Fails if the context could not allocate the buffers (e.g. after a context loss).
     pub fn from_f32_data_view(
         context: &WebGlRenderingContext,
         name: &str,
         data_type: ShaderDataType,
         data: &[f32],
         indexes: Option<&[u16]>,
-    ) -> Buffer {
-        let gl_buffer = context.create_buffer().unwrap();
+    ) -> Result<Buffer, W3DError> {
+        Buffer::from_data_view(context, name, data_type, AttributeData::F32(data), indexes)
+    }
+
+    /// Same as `from_f32_data_view` with data of any `ComponentType`, uploaded through the
+    /// matching typed array view.
+    pub fn from_data_view(
+        context: &WebGlRenderingContext,
+        name: &str,
+        data_type: ShaderDataType,
+        data: AttributeData,
+        indexes: Option<&[u16]>,
+    ) -> Result<Buffer, W3DError> {
+        let previous_array_buffer =
+            get_bound_buffer(context, WebGlRenderingContext::ARRAY_BUFFER_BINDING);
+        let previous_index_buffer =
+            get_bound_buffer(context, WebGlRenderingContext::ELEMENT_ARRAY_BUFFER_BINDING);
+        let result = Buffer::upload_data(context, name, data_type, data, indexes);
+        context.bind_buffer(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            previous_array_buffer.as_ref(),
+        );
+        context.bind_buffer(
+            WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
+            previous_index_buffer.as_ref(),
+        );
+        result
+    }
+
+    /// Creates the `WebGlBuffer`s and uploads the data, without caring about the previous bindings.
+    fn upload_data(
+        context: &WebGlRenderingContext,
+        name: &str,
+        data_type: ShaderDataType,
+        data: AttributeData,
+        indexes: Option<&[u16]>,
+    ) -> Result<Buffer, W3DError> {
+        let indexes = indexes.filter(|indexes_array| indexes_array.len() > 0);
+        let (gl_buffer, gl_index_buffer) = create_buffers(
+            || context.create_buffer(),
+            |buffer| context.delete_buffer(Some(&buffer)),
+            indexes.is_some(),
+            name,
+        )?;
         context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&gl_buffer));
+        data.upload(context);
 
-        unsafe {
-            let float_array = Float32Array::view(data);
-            context.buffer_data_with_array_buffer_view(
-                WebGlRenderingContext::ARRAY_BUFFER,
-                &float_array,
-                WebGlRenderingContext::STATIC_DRAW,
+        let mut indexes_buffer = None;
+        let mut index_count = 0;
+        if let (Some(indexes_array), Some(gl_index_buffer)) = (indexes, gl_index_buffer) {
+            context.bind_buffer(
+                WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
+                Some(&gl_index_buffer),
             );
-        }
 
-        let mut indexes_buffer = None;
-        if let Some(indexes_array) = indexes {
-            if indexes_array.len() > 0 {
-                let gl_index_buffer = context.create_buffer().unwrap();
-                context.bind_buffer(
+            unsafe {
+                let uint_array = Uint16Array::view(indexes_array);
+                context.buffer_data_with_array_buffer_view(
                     WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
-                    Some(&gl_index_buffer),
+                    &uint_array,
+                    WebGlRenderingContext::STATIC_DRAW,
                 );
-
-                unsafe {
-                    let uint_array = Uint16Array::view(indexes_array);
-                    context.buffer_data_with_array_buffer_view(
-                        WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
-                        &uint_array,
-                        WebGlRenderingContext::STATIC_DRAW,
-                    );
-                }
-                indexes_buffer = Some(Rc::new(gl_index_buffer));
             }
+            indexes_buffer = Some(Rc::new(gl_index_buffer));
+            index_count = indexes_array.len();
         }
 
-        Buffer {
+        Ok(Buffer {
             attribute_name: String::from(name),
             value: Rc::new(gl_buffer),
             indexes: indexes_buffer,
             data_type: data_type,
             stride: 0,
             offset: 0,
-            number_type: WebGlRenderingContext::FLOAT,
+            component_type: data.get_component_type(),
+            capacity: data.len(),
+            index_count: index_count,
+        })
+    }
+
+    /// Creates an empty `Buffer` able to hold `capacity` floats, meant to be updated
+    /// frequently with `update_f32_data` (particles, sprites...).
+    ///
+    /// The previous `ARRAY_BUFFER` binding is restored once the buffer is allocated.
+    pub fn new_dynamic(
+        context: &WebGlRenderingContext,
+        name: &str,
+        data_type: ShaderDataType,
+        capacity: usize,
+    ) -> Result<Buffer, W3DError> {
+        let previous_array_buffer =
+            get_bound_buffer(context, WebGlRenderingContext::ARRAY_BUFFER_BINDING);
+        let gl_buffer = context.create_buffer().ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Could not create a dynamic WebGL buffer.",
+                name,
+            )
+        })?;
+        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&gl_buffer));
+        context.buffer_data_with_i32(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            (capacity * F32_SIZE) as i32,
+            WebGlRenderingContext::DYNAMIC_DRAW,
+        );
+        context.bind_buffer(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            previous_array_buffer.as_ref(),
+        );
+        Ok(Buffer {
+            attribute_name: String::from(name),
+            value: Rc::new(gl_buffer),
+            indexes: None,
+            data_type: data_type,
+            stride: 0,
+            offset: 0,
+            component_type: ComponentType::F32,
+            capacity: capacity,
+            index_count: 0,
+        })
+    }
+
+    /// Returns a new `Buffer` for another attribute stored in the same `WebGlBuffer`.  
+    /// Used to describe interleaved data: `stride` and `offset` are in bytes.
+    pub fn share_with_attribute(
+        &self,
+        name: &str,
+        data_type: ShaderDataType,
+        stride: i32,
+        offset: i32,
+    ) -> Buffer {
+        Buffer {
+            attribute_name: String::from(name),
+            value: self.value.clone(),
+            indexes: self.indexes.clone(),
+            data_type: data_type,
+            stride: stride,
+            offset: offset,
+            component_type: self.component_type,
+            capacity: self.capacity,
+            index_count: self.index_count,
+        }
+    }
+
+    /// Replaces the start of this buffer's data with `data` using `bufferSubData`.  
+    /// If `data` doesn't fit, the buffer is reallocated with enough room for it.
+    ///
+    /// ⚠️ Leaves the buffer bound to `ARRAY_BUFFER`; meant to be called in the render loop,
+    /// just before binding attributes. Only meant for `F32` buffers.
+    pub fn update_f32_data(&mut self, context: &WebGlRenderingContext, data: &[f32]) -> () {
+        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&self.value));
+        unsafe {
+            let float_array = Float32Array::view(data);
+            if data.len() > self.capacity {
+                context.buffer_data_with_array_buffer_view(
+                    WebGlRenderingContext::ARRAY_BUFFER,
+                    &float_array,
+                    WebGlRenderingContext::DYNAMIC_DRAW,
+                );
+                self.capacity = data.len();
+            } else {
+                context.buffer_sub_data_with_i32_and_array_buffer_view(
+                    WebGlRenderingContext::ARRAY_BUFFER,
+                    0,
+                    &float_array,
+                );
+            }
         }
     }
 
+    /// Deletes the underlying `WebGlBuffer`s, shared with any `Buffer` made with
+    /// `share_with_attribute`. Deleting them several times is harmless.
+    pub fn delete(&self, context: &WebGlRenderingContext) -> () {
+        context.delete_buffer(Some(&self.value));
+        if let Some(indexes) = &self.indexes {
+            context.delete_buffer(Some(indexes));
+        }
+    }
+
+    /// Returns the size of the underlying `WebGlBuffer`s, in bytes.
+    pub fn get_byte_size(&self) -> usize {
+        self.capacity * self.component_type.get_byte_size() + self.index_count * U16_SIZE
+    }
+
+    /// Returns `true` if `other` uses the same `WebGlBuffer` as this buffer, which is the
+    /// case for buffers made with `share_with_attribute`.
+    pub fn shares_storage_with(&self, other: &Buffer) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+
     /// Returns the attribute name for this buffer
     pub fn get_attribute_name(&self) -> &str {
         self.attribute_name.as_str()
     }
 
+    /// Returns the data type of this buffer's attribute
+    pub fn get_data_type(&self) -> ShaderDataType {
+        self.data_type
+    }
+
+    /// Returns the type of the components of this buffer's attribute
+    pub fn get_component_type(&self) -> ComponentType {
+        self.component_type
+    }
+
+    /// Returns `true` if the attribute's integer components are read as floats between
+    /// `-1` and `1` (signed) or `0` and `1` (unsigned) by the shader.
+    pub fn is_normalized(&self) -> bool {
+        self.component_type.is_normalized()
+    }
+
     /// Enables and sets the attribute pointer at the context level.  
     /// Meant to be called just before rendering.
     pub fn enable_and_bind_attribute(&self, context: &WebGlRenderingContext, location: i32) {
@@ -107,11 +282,263 @@ impl Buffer {
             context.vertex_attrib_pointer_with_i32(
                 loc,
                 self.data_type.get_size(),
-                self.number_type,
-                false,
+                self.component_type.get_gl_type(),
+                self.is_normalized(),
                 self.stride,
                 self.offset,
             );
         }
     }
 }
+
+/// Type of the components of a vertex attribute, as stored in its `WebGlBuffer`.
+///
+/// Normalized types are read by the shader as floats between `-1` and `1` (`I16Norm`) or
+/// `0` and `1` (`U8Norm`), e.g. for normals and colors. Integer types are read as their
+/// float value, e.g. `3.0` for joint index 3, since WebGL 1 has no integer attributes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComponentType {
+    F32,
+    I16Norm,
+    U8Norm,
+    U16,
+    U8,
+}
+
+impl ComponentType {
+    /// Returns the WebGL constant of the type, as given to `vertexAttribPointer`.
+    pub fn get_gl_type(&self) -> u32 {
+        match self {
+            ComponentType::F32 => WebGlRenderingContext::FLOAT,
+            ComponentType::I16Norm => WebGlRenderingContext::SHORT,
+            ComponentType::U8Norm | ComponentType::U8 => WebGlRenderingContext::UNSIGNED_BYTE,
+            ComponentType::U16 => WebGlRenderingContext::UNSIGNED_SHORT,
+        }
+    }
+
+    /// Returns the size of a component in bytes.
+    pub fn get_byte_size(&self) -> usize {
+        match self {
+            ComponentType::F32 => F32_SIZE,
+            ComponentType::I16Norm | ComponentType::U16 => 2,
+            ComponentType::U8Norm | ComponentType::U8 => 1,
+        }
+    }
+
+    /// Returns `true` if the components are normalized.
+    pub fn is_normalized(&self) -> bool {
+        match self {
+            ComponentType::I16Norm | ComponentType::U8Norm => true,
+            _ => false,
+        }
+    }
+}
+
+/// Vertex attribute data to upload, in one of the `ComponentType`s.
+#[derive(Clone, Copy)]
+pub enum AttributeData<'a> {
+    F32(&'a [f32]),
+    I16Norm(&'a [i16]),
+    U8Norm(&'a [u8]),
+    U16(&'a [u16]),
+    U8(&'a [u8]),
+}
+
+impl<'a> AttributeData<'a> {
+    /// Returns the type of the components.
+    pub fn get_component_type(&self) -> ComponentType {
+        match self {
+            AttributeData::F32(_) => ComponentType::F32,
+            AttributeData::I16Norm(_) => ComponentType::I16Norm,
+            AttributeData::U8Norm(_) => ComponentType::U8Norm,
+            AttributeData::U16(_) => ComponentType::U16,
+            AttributeData::U8(_) => ComponentType::U8,
+        }
+    }
+
+    /// Returns the number of components.
+    pub fn len(&self) -> usize {
+        match self {
+            AttributeData::F32(data) => data.len(),
+            AttributeData::I16Norm(data) => data.len(),
+            AttributeData::U8Norm(data) | AttributeData::U8(data) => data.len(),
+            AttributeData::U16(data) => data.len(),
+        }
+    }
+
+    /// Returns `true` if there are no components.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Uploads the data to the buffer bound to `ARRAY_BUFFER`, through a typed array view.
+    fn upload(&self, context: &WebGlRenderingContext) -> () {
+        let target = WebGlRenderingContext::ARRAY_BUFFER;
+        let usage = WebGlRenderingContext::STATIC_DRAW;
+        // The views are only used before any allocation can move the wasm memory.
+        unsafe {
+            match self {
+                AttributeData::F32(data) => context.buffer_data_with_array_buffer_view(
+                    target,
+                    &Float32Array::view(data),
+                    usage,
+                ),
+                AttributeData::I16Norm(data) => context.buffer_data_with_array_buffer_view(
+                    target,
+                    &Int16Array::view(data),
+                    usage,
+                ),
+                AttributeData::U8Norm(data) | AttributeData::U8(data) => context
+                    .buffer_data_with_array_buffer_view(target, &Uint8Array::view(data), usage),
+                AttributeData::U16(data) => context.buffer_data_with_array_buffer_view(
+                    target,
+                    &Uint16Array::view(data),
+                    usage,
+                ),
+            }
+        }
+    }
+}
+
+/// Size of an `f32` in bytes, as used in buffer sizes and offsets.
+pub const F32_SIZE: usize = 4;
+
+/// Size of a `u16` in bytes, as used in index buffer offsets.
+pub const U16_SIZE: usize = 2;
+
+/// Returns the `WebGlBuffer` currently bound to the target described by `binding_name`
+/// (`ARRAY_BUFFER_BINDING` or `ELEMENT_ARRAY_BUFFER_BINDING`), if any.
+pub fn get_bound_buffer(context: &WebGlRenderingContext, binding_name: u32) -> Option<WebGlBuffer> {
+    context
+        .get_parameter(binding_name)
+        .ok()
+        .and_then(|value| value.dyn_into::<WebGlBuffer>().ok())
+}
+
+/// Creates a vertex buffer and, if `with_indexes` is `true`, an index buffer with `create`.
+/// If the index buffer can't be created, the vertex buffer is given back to `delete` so
+/// that a failed construction doesn't leak GPU memory.
+fn create_buffers<B, C, D>(
+    mut create: C,
+    mut delete: D,
+    with_indexes: bool,
+    name: &str,
+) -> Result<(B, Option<B>), W3DError>
+where
+    C: FnMut() -> Option<B>,
+    D: FnMut(B) -> (),
+{
+    let buffer = create().ok_or_else(|| {
+        W3DError::with_source(
+            W3DErrorKind::GlResource,
+            "Could not create a WebGL buffer.",
+            name,
+        )
+    })?;
+    if !with_indexes {
+        return Ok((buffer, None));
+    }
+    match create() {
+        Some(index_buffer) => Ok((buffer, Some(index_buffer))),
+        None => {
+            delete(buffer);
+            Err(W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Could not create a WebGL index buffer.",
+                name,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Simulates `create_buffer`, returning `None` once `available` buffers were created.
+    fn allocate(available: u32) -> (impl FnMut() -> Option<u32>, Rc<RefCell<Vec<u32>>>) {
+        let live = Rc::new(RefCell::new(Vec::new()));
+        let created = live.clone();
+        let mut next = 0;
+        let create = move || {
+            if next == available {
+                return None;
+            }
+            next += 1;
+            created.borrow_mut().push(next);
+            Some(next)
+        };
+        (create, live)
+    }
+
+    #[test]
+    fn create_buffers_fails_without_vertex_buffer() {
+        let (create, live) = allocate(0);
+        let result = create_buffers(create, |_| panic!("nothing to delete"), true, "a_position");
+        let error = result.unwrap_err();
+        assert_eq!(error.get_kind(), W3DErrorKind::GlResource);
+        assert_eq!(error.get_source(), Some("a_position"));
+        assert!(live.borrow().is_empty());
+    }
+
+    #[test]
+    fn create_buffers_deletes_vertex_buffer_when_index_buffer_fails() {
+        let (create, live) = allocate(1);
+        let deleted = RefCell::new(Vec::new());
+        let result = create_buffers(create, |buffer| deleted.borrow_mut().push(buffer), true, "");
+        assert!(result.is_err());
+        assert_eq!(*deleted.borrow(), *live.borrow());
+    }
+
+    #[test]
+    fn create_buffers_only_creates_requested_buffers() {
+        let (create, _) = allocate(1);
+        let (buffer, index_buffer) = create_buffers(create, |_| (), false, "").unwrap();
+        assert_eq!((buffer, index_buffer), (1, None));
+        let (create, _) = allocate(2);
+        assert_eq!(
+            create_buffers(create, |_| (), true, "").unwrap(),
+            (1, Some(2))
+        );
+    }
+
+    #[test]
+    fn component_types_match_their_attribute_pointer_arguments() {
+        let types = [
+            (ComponentType::F32, WebGlRenderingContext::FLOAT, false, 4),
+            (
+                ComponentType::I16Norm,
+                WebGlRenderingContext::SHORT,
+                true,
+                2,
+            ),
+            (
+                ComponentType::U8Norm,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                true,
+                1,
+            ),
+            (
+                ComponentType::U16,
+                WebGlRenderingContext::UNSIGNED_SHORT,
+                false,
+                2,
+            ),
+            (
+                ComponentType::U8,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                false,
+                1,
+            ),
+        ];
+        for (component_type, gl_type, normalized, byte_size) in &types {
+            assert_eq!(component_type.get_gl_type(), *gl_type);
+            assert_eq!(component_type.is_normalized(), *normalized);
+            assert_eq!(component_type.get_byte_size(), *byte_size);
+        }
+        let joints = AttributeData::U16(&[0, 3, 7]);
+        assert_eq!(joints.get_component_type(), ComponentType::U16);
+        assert_eq!(joints.len(), 3);
+    }
+}
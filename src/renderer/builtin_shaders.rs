@@ -0,0 +1,655 @@
+//! Shaders used by the renderer's built-in materials, which are not loaded from asset files.
+
+use super::{LightConfiguration, Material};
+use crate::error::W3DError;
+use web_sys::WebGlRenderingContext;
+
+/// Compiles a built-in, unlit `Material` and looks up its global uniforms and the locations
+/// of the given attributes.
+pub fn compile_builtin_material(
+    context: &WebGlRenderingContext,
+    vertex_shader: &str,
+    fragment_shader: &str,
+    id: &str,
+    attribute_names: &[&str],
+) -> Result<Material, W3DError> {
+    compile_builtin_material_variant(
+        context,
+        vertex_shader,
+        fragment_shader,
+        id,
+        attribute_names,
+        &LightConfiguration::default(),
+    )
+}
+
+/// Same as `compile_builtin_material`, compiling the variant of the shaders for
+/// `light_config`.
+pub fn compile_builtin_material_variant(
+    context: &WebGlRenderingContext,
+    vertex_shader: &str,
+    fragment_shader: &str,
+    id: &str,
+    attribute_names: &[&str],
+    light_config: &LightConfiguration,
+) -> Result<Material, W3DError> {
+    let mut material = Material::new(vertex_shader, fragment_shader, id);
+    material.compile(context, light_config)?;
+    material.lookup_locations(context, light_config);
+    for name in attribute_names {
+        material.register_new_attribute_location(context, name);
+    }
+    Ok(material)
+}
+
+/// Returns the number of vertex attributes supported by the context.
+pub fn get_max_vertex_attributes(context: &WebGlRenderingContext) -> u32 {
+    context
+        .get_parameter(WebGlRenderingContext::MAX_VERTEX_ATTRIBS)
+        .ok()
+        .and_then(|value| value.as_f64())
+        .unwrap_or(8.0) as u32
+}
+
+/// Vertex shader for particles, drawn as `POINTS` whose size is given in world units.
+pub const PARTICLE_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+attribute vec4 a_color;
+attribute float a_size;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform float u_viewport_height;
+
+varying vec4 v_color;
+
+void main() {
+    gl_Position = u_projection_matrix * u_view_matrix * vec4(a_position, 1.0);
+    gl_PointSize = a_size * u_projection_matrix[1][1] * u_viewport_height * 0.5 / gl_Position.w;
+    v_color = a_color;
+}
+"#;
+
+/// Fragment shader for particles: a soft disc, premultiplied for additive blending.  
+/// With soft particles, it fades out over half a world unit in front of opaque meshes.
+pub const PARTICLE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+varying vec4 v_color;
+
+void main() {
+    float falloff = 1.0 - smoothstep(0.25, 0.5, length(gl_PointCoord - vec2(0.5)));
+#ifdef USE_SOFT_PARTICLES
+    falloff *= soft_fade(0.5);
+#endif
+    gl_FragColor = vec4(v_color.rgb * v_color.a * falloff, 1.0);
+}
+"#;
+
+/// Vertex shader for sprites: quads centered on `a_position` and expanded by `a_corner`
+/// in view space, so that they always face the camera.
+pub const SPRITE_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+attribute vec2 a_corner;
+attribute vec2 a_tex_coordinates;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec4 view_position = u_view_matrix * vec4(a_position, 1.0);
+    view_position.xy += a_corner;
+    gl_Position = u_projection_matrix * view_position;
+    v_tex_coordinates = a_tex_coordinates;
+}
+"#;
+
+/// Fragment shader for sprites: unlit, alpha blended.
+pub const SPRITE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec4 color = texture2D(u_texture, v_tex_coordinates);
+    if (color.a <= 0.0) {
+        discard;
+    }
+    gl_FragColor = color;
+}
+"#;
+
+/// Vertex shader for overlays, whose positions are given in device pixels.
+pub const OVERLAY_VERTEX_SHADER: &str = r#"
+attribute vec2 a_position;
+attribute vec2 a_tex_coordinates;
+attribute float a_opacity;
+
+uniform mat4 u_projection_matrix;
+
+varying vec2 v_tex_coordinates;
+varying float v_opacity;
+
+void main() {
+    gl_Position = u_projection_matrix * vec4(a_position, 0.0, 1.0);
+    v_tex_coordinates = a_tex_coordinates;
+    v_opacity = a_opacity;
+}
+"#;
+
+/// Fragment shader for overlays: unlit, alpha blended.
+pub const OVERLAY_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+
+varying vec2 v_tex_coordinates;
+varying float v_opacity;
+
+void main() {
+    vec4 color = texture2D(u_texture, v_tex_coordinates);
+    gl_FragColor = vec4(color.rgb, color.a * v_opacity);
+}
+"#;
+
+/// Fragment shader for the depth pre-pass. Color writes are disabled during that pass.
+pub const DEPTH_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+void main() {
+    gl_FragColor = vec4(1.0);
+}
+"#;
+
+/// Vertex shader for decals: a textured quad placed by its world transform.  
+/// Its texture coordinates are mapped to the region given by `u_uv_transform`, then scroll
+/// by `u_uv_scroll` per second, `0` unless set.
+pub const DECAL_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+attribute vec2 a_tex_coordinates;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+uniform vec4 u_uv_transform;
+uniform vec2 u_uv_scroll;
+uniform float u_time_wrapped;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    mat4 view_model_matrix = (u_view_matrix * u_world_transform);
+    gl_Position = (u_projection_matrix * view_model_matrix) * vec4(a_position, 1.0);
+    vec2 uv_scale = u_uv_transform.zw == vec2(0.0) ? vec2(1.0) : u_uv_transform.zw;
+    v_tex_coordinates = a_tex_coordinates * uv_scale + u_uv_transform.xy
+        + fract(u_uv_scroll * u_time_wrapped);
+}
+"#;
+
+/// Fragment shader for decals: unlit, alpha blended, with an optional alpha cutoff.
+pub const DECAL_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+
+#ifdef ALPHA_CUTOFF
+uniform float u_alpha_cutoff;
+#endif
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec4 color = texture2D(u_texture, v_tex_coordinates);
+    if (color.a <= 0.0) {
+        discard;
+    }
+#ifdef ALPHA_CUTOFF
+    if (color.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+#ifdef ALPHA_HASH
+    if (color.a < alpha_hash(gl_FragCoord.xy)) {
+        discard;
+    }
+    color.a = 1.0;
+#endif
+    gl_FragColor = color;
+}
+"#;
+
+/// Vertex shader for blob shadows, whose vertices are given in world space.
+pub const BLOB_SHADOW_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+attribute vec2 a_tex_coordinates;
+attribute float a_opacity;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+
+varying vec2 v_tex_coordinates;
+varying float v_opacity;
+
+void main() {
+    gl_Position = u_projection_matrix * (u_view_matrix * vec4(a_position, 1.0));
+    v_tex_coordinates = a_tex_coordinates;
+    v_opacity = a_opacity;
+}
+"#;
+
+/// Fragment shader for blob shadows: outputs the factor the framebuffer is multiplied by.
+pub const BLOB_SHADOW_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+
+varying vec2 v_tex_coordinates;
+varying float v_opacity;
+
+void main() {
+    float darkness = texture2D(u_texture, v_tex_coordinates).a * v_opacity;
+    if (darkness <= 0.0) {
+        discard;
+    }
+    gl_FragColor = vec4(vec3(1.0 - darkness), 1.0);
+}
+"#;
+
+/// Declarations prepended to fragment shaders mentioning `TONE_MAPPING`, after the define
+/// selecting the operator. `tone_map` applies the camera's exposure, then the operator.  
+/// With `HDR_OUTPUT` defined, it returns colors as is: the tone mapping pass applies both.
+pub const TONE_MAPPING_FUNCTIONS: &str = r#"
+precision mediump float;
+
+uniform float u_exposure;
+
+vec3 tone_map(vec3 color) {
+#ifndef HDR_OUTPUT
+    color *= u_exposure;
+#if TONE_MAPPING == 1
+    color = color / (color + vec3(1.0));
+#elif TONE_MAPPING == 2
+    color = clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+#endif
+#endif
+    return color;
+}
+"#;
+
+/// Vertex shader for full screen passes (tone mapping, bloom): a triangle covering the
+/// viewport, given in clip space.
+pub const FULL_SCREEN_VERTEX_SHADER: &str = r#"
+attribute vec2 a_position;
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    v_tex_coordinates = a_position * 0.5 + 0.5;
+    gl_Position = vec4(a_position, 0.0, 1.0);
+}
+"#;
+
+/// Fragment shader for the tone mapping pass: converts the HDR colors of `u_texture` with
+/// the `TONE_MAPPING` operator. With `USE_BLOOM`, the bloom texture is added before.
+pub const TONE_MAP_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+
+#ifdef USE_BLOOM
+uniform sampler2D u_bloom_texture;
+uniform float u_bloom_intensity;
+#endif
+
+varying vec2 v_tex_coordinates;
+
+void main() {
+    vec4 color = texture2D(u_texture, v_tex_coordinates);
+#ifdef USE_BLOOM
+    color.rgb += texture2D(u_bloom_texture, v_tex_coordinates).rgb * u_bloom_intensity;
+#endif
+    gl_FragColor = vec4(tone_map(color.rgb), color.a);
+}
+"#;
+
+/// Declarations prepended to the fragment shaders of alpha hashed materials, after the
+/// `ALPHA_HASH` define. `alpha_hash` returns a threshold between 0 and 1 for a fragment
+/// position (interleaved gradient noise), under which the fragment's alpha should discard it.
+pub const ALPHA_HASH_FUNCTIONS: &str = r#"
+precision mediump float;
+
+float alpha_hash(vec2 position) {
+    return fract(52.9829189 * fract(dot(floor(position), vec2(0.06711056, 0.00583715))));
+}
+"#;
+
+/// Declarations prepended to fragment shaders mentioning `USE_SOFT_PARTICLES` while soft
+/// particles are enabled, after the define. `soft_fade` returns a factor between 0 and 1 to
+/// apply to the fragment's alpha, reaching 0 where it meets the opaque meshes and 1 at
+/// `fade_distance` world units in front of them.
+pub const SOFT_PARTICLES_FUNCTIONS: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_scene_depth;
+uniform vec2 u_camera_near_far;
+uniform vec2 u_viewport_size;
+
+float linearize_depth(float depth) {
+    float near = u_camera_near_far.x;
+    float far = u_camera_near_far.y;
+    return 2.0 * near * far / (far + near - (depth * 2.0 - 1.0) * (far - near));
+}
+
+float soft_fade(float fade_distance) {
+    float scene_depth = texture2D(u_scene_depth, gl_FragCoord.xy / u_viewport_size).r;
+    float distance = linearize_depth(scene_depth) - linearize_depth(gl_FragCoord.z);
+    return clamp(distance / fade_distance, 0.0, 1.0);
+}
+"#;
+
+/// Vertex shader for the fallback material standing in for missing materials.
+pub const FALLBACK_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+
+varying vec3 v_local_position;
+
+void main() {
+    mat4 view_model_matrix = (u_view_matrix * u_world_transform);
+    gl_Position = (u_projection_matrix * view_model_matrix) * vec4(a_position, 1.0);
+    v_local_position = a_position;
+}
+"#;
+
+/// Fragment shader for the fallback material: unlit magenta, with a checker pattern in local
+/// space so that the shape of the mesh stays readable.
+pub const FALLBACK_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+varying vec3 v_local_position;
+
+void main() {
+    vec3 cell = floor(v_local_position * 4.0 + 0.001);
+    float checker = mod(cell.x + cell.y + cell.z, 2.0);
+    gl_FragColor = vec4(mix(vec3(1.0, 0.0, 1.0), vec3(0.5, 0.0, 0.5), checker), 1.0);
+}
+"#;
+
+/// Vertex shader for debug lines, whose vertices are given in world space with a color.
+pub const DEBUG_LINE_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+attribute vec3 a_color;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+
+varying vec3 v_color;
+
+void main() {
+    gl_Position = u_projection_matrix * (u_view_matrix * vec4(a_position, 1.0));
+    v_color = a_color;
+}
+"#;
+
+/// Fragment shader for debug lines: unlit, with the color of their vertices.
+pub const DEBUG_LINE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+varying vec3 v_color;
+
+void main() {
+    gl_FragColor = vec4(v_color, 1.0);
+}
+"#;
+
+/// Vertex shader for helper lines (grids and axes): lines placed by their world transform,
+/// with the color of their vertices.
+pub const HELPER_LINE_VERTEX_SHADER: &str = r#"
+attribute vec3 a_position;
+attribute vec3 a_color;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+
+varying vec3 v_color;
+varying float v_view_distance;
+
+void main() {
+    vec4 view_position = u_view_matrix * (u_world_transform * vec4(a_position, 1.0));
+    gl_Position = u_projection_matrix * view_position;
+    v_color = a_color;
+    v_view_distance = length(view_position.xyz);
+}
+"#;
+
+/// Fragment shader for helper lines: unlit, with the color of their vertices.
+pub const HELPER_LINE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+varying vec3 v_color;
+varying float v_view_distance;
+
+void main() {
+    gl_FragColor = vec4(v_color, 1.0);
+}
+"#;
+
+/// Fragment shader for grid helpers: unlit, fading out between half `u_fade_distance` and
+/// `u_fade_distance` from the camera, unless it is `0`.
+pub const GRID_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform float u_fade_distance;
+
+varying vec3 v_color;
+varying float v_view_distance;
+
+void main() {
+    float alpha = 1.0;
+    if (u_fade_distance > 0.0) {
+        alpha = 1.0 - smoothstep(0.5 * u_fade_distance, u_fade_distance, v_view_distance);
+    }
+    if (alpha <= 0.0) {
+        discard;
+    }
+    gl_FragColor = vec4(v_color, alpha);
+}
+"#;
+
+/// Declarations of the vertex shaders generated from a `MaterialDescription`. Features are
+/// selected by the `USE_*` defines the generator writes before it.
+pub const DESCRIBED_VERTEX_DECLARATIONS: &str = r#"
+attribute vec3 a_position;
+attribute vec2 a_tex_coordinates;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+uniform vec4 u_uv_transform;
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_position;
+
+#ifdef USE_LIGHTING
+attribute vec3 a_normal;
+varying vec3 v_normal;
+#endif
+
+#ifdef USE_NORMAL_MAP
+attribute vec3 a_tangent;
+varying mat3 v_tbn_matrix;
+#endif
+"#;
+
+/// Vertex chunk of the `windSway` vertex animation. `wind_sway` returns the world space
+/// offset of a vertex, growing with its height above the mesh origin so that the base of
+/// the mesh stays in place.
+pub const WIND_SWAY_CHUNK: &str = r#"
+uniform float u_time;
+uniform float u_wind_strength;
+uniform float u_wind_frequency;
+
+vec3 wind_sway(vec3 world_position, float height) {
+    float phase = dot(world_position.xz, vec2(0.37, 0.71));
+    float sway = sin(u_time * u_wind_frequency + phase) * u_wind_strength * max(height, 0.0);
+    return vec3(sway, 0.0, sway * 0.5);
+}
+"#;
+
+/// `main` of the vertex shaders generated from a `MaterialDescription`.
+pub const DESCRIBED_VERTEX_MAIN: &str = r#"
+void main() {
+    vec4 position = u_world_transform * vec4(a_position, 1.0);
+#ifdef USE_WIND_SWAY
+    position.xyz += wind_sway(position.xyz, a_position.y) * position.w;
+#endif
+    v_position = position.xyz / position.w;
+    gl_Position = u_projection_matrix * (u_view_matrix * position);
+    vec2 uv_scale = u_uv_transform.zw == vec2(0.0) ? vec2(1.0) : u_uv_transform.zw;
+    v_tex_coordinates = a_tex_coordinates * uv_scale + u_uv_transform.xy;
+#ifdef USE_LIGHTING
+    v_normal = mat3(u_world_transform) * a_normal;
+#endif
+#ifdef USE_NORMAL_MAP
+    vec3 normal = normalize(mat3(u_world_transform) * a_normal);
+    vec3 tangent = normalize(mat3(u_world_transform) * a_tangent);
+    v_tbn_matrix = mat3(tangent, cross(normal, tangent), normal);
+#endif
+}
+"#;
+
+/// Declarations of the fragment shaders generated from a `MaterialDescription`. Features
+/// are selected by the `USE_*` defines the generator writes before it.
+pub const DESCRIBED_FRAGMENT_DECLARATIONS: &str = r#"
+precision mediump float;
+
+uniform vec4 u_base_color;
+uniform float u_entity_opacity;
+
+#ifdef USE_BASE_COLOR_MAP
+uniform sampler2D u_base_color_map;
+#endif
+
+#ifdef USE_NORMAL_MAP
+uniform sampler2D u_normal_map;
+varying mat3 v_tbn_matrix;
+#endif
+
+#ifdef USE_EMISSIVE
+uniform vec3 u_emissive;
+#endif
+
+#ifdef USE_EMISSIVE_MAP
+uniform sampler2D u_emissive_map;
+#endif
+
+#ifdef ALPHA_CUTOFF
+uniform float u_alpha_cutoff;
+#endif
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_position;
+
+#ifdef USE_LIGHTING
+varying vec3 v_normal;
+#endif
+"#;
+
+/// Fragment chunk of lit descriptions. `compute_lighting` returns the Lambert lighting of
+/// the ambient, directional and point lights at a world position.
+pub const LAMBERT_LIGHTING_CHUNK: &str = r#"
+#define NUM_DIR_LIGHTS 0
+#define NUM_POINT_LIGHTS 0
+
+struct Light {
+    vec3 position_or_direction;
+    float intensity;
+    vec3 color;
+    vec4 attenuation;
+};
+
+#if NUM_DIR_LIGHTS > 0
+uniform Light u_dir_lights[NUM_DIR_LIGHTS];
+#endif
+
+#if NUM_POINT_LIGHTS > 0
+uniform Light u_point_lights[NUM_POINT_LIGHTS];
+#endif
+
+uniform vec4 u_ambiant_light;
+
+vec3 compute_lighting(vec3 normal, vec3 position) {
+    vec3 light = u_ambiant_light.rgb * u_ambiant_light.a;
+#if NUM_DIR_LIGHTS > 0
+    for (int i = 0; i < NUM_DIR_LIGHTS; i++) {
+        float power = max(dot(normal, normalize(-u_dir_lights[i].position_or_direction)), 0.0);
+        light += u_dir_lights[i].color * u_dir_lights[i].intensity * power;
+    }
+#endif
+#if NUM_POINT_LIGHTS > 0
+    for (int i = 0; i < NUM_POINT_LIGHTS; i++) {
+        vec3 to_light = u_point_lights[i].position_or_direction - position;
+        float distance = length(to_light);
+        vec4 attenuation = u_point_lights[i].attenuation;
+        float falloff = 1.0 / max(attenuation.x + attenuation.y * distance
+            + attenuation.z * distance * distance, 0.0001);
+        if (attenuation.w > 0.0 && distance > attenuation.w) {
+            falloff = 0.0;
+        }
+        float power = max(dot(normal, to_light / max(distance, 0.0001)), 0.0);
+        light += u_point_lights[i].color * u_point_lights[i].intensity * falloff * power;
+    }
+#endif
+    return light;
+}
+"#;
+
+/// `main` of the fragment shaders generated from a `MaterialDescription`.
+pub const DESCRIBED_FRAGMENT_MAIN: &str = r#"
+void main() {
+    vec2 uv = vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y);
+    vec4 color = u_base_color;
+#ifdef USE_BASE_COLOR_MAP
+    color *= texture2D(u_base_color_map, uv);
+#endif
+#ifdef ALPHA_CUTOFF
+    if (color.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+#ifdef ALPHA_HASH
+    if (color.a < alpha_hash(gl_FragCoord.xy)) {
+        discard;
+    }
+    color.a = 1.0;
+#endif
+#ifdef USE_LIGHTING
+#ifdef USE_NORMAL_MAP
+    vec3 normal = normalize(v_tbn_matrix * (texture2D(u_normal_map, uv).xyz * 2.0 - 1.0));
+#else
+    vec3 normal = normalize(v_normal);
+#endif
+    color.rgb *= compute_lighting(normal, v_position);
+#endif
+#ifdef USE_EMISSIVE
+    vec3 emissive = u_emissive;
+#ifdef USE_EMISSIVE_MAP
+    emissive *= texture2D(u_emissive_map, uv).rgb;
+#endif
+    color.rgb += emissive;
+#endif
+#ifdef TONE_MAPPING
+    color.rgb = tone_map(color.rgb);
+#endif
+    color.a *= u_entity_opacity;
+    gl_FragColor = color;
+}
+"#;
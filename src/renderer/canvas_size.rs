@@ -0,0 +1,146 @@
+//! Display size of the renderer's canvas, tracked without reading the layout every frame, or
+//! size of the area to render to pushed by the owner of an external context.
+
+use js_sys::Array;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, ResizeObserver, ResizeObserverEntry};
+
+/// Number of frames between two reads of the canvas display size where `ResizeObserver` is
+/// not supported.
+const RESIZE_POLL_INTERVAL: u32 = 30;
+
+/// ## CanvasSize
+///
+/// Display size of a canvas in CSS pixels. A `ResizeObserver` records it whenever the canvas
+/// is resized; where it is not supported, `clientWidth` and `clientHeight` are read every
+/// `RESIZE_POLL_INTERVAL` frames, since reading them may force a layout.
+pub struct CanvasSize {
+    /// Latest size recorded by the observer and not consumed yet
+    observed: Rc<Cell<Option<(u32, u32)>>>,
+
+    /// The observer and its callback, `None` where `ResizeObserver` is not supported
+    observer: Option<(ResizeObserver, Closure<dyn FnMut(Array)>)>,
+
+    /// Display size as of the last `update`
+    display_size: (u32, u32),
+
+    /// Frames left before reading the size again when polling
+    frames_until_poll: u32,
+}
+
+impl CanvasSize {
+    /// Starts observing `canvas`, falling back to polling if `ResizeObserver` is not
+    /// supported.
+    pub fn new(canvas: &HtmlCanvasElement) -> CanvasSize {
+        let observed = Rc::new(Cell::new(None));
+        let callback_observed = observed.clone();
+        let callback = Closure::wrap(Box::new(move |entries: Array| {
+            // Only the canvas is observed: its latest entry is the one that matters.
+            let entry = entries.get(entries.length().saturating_sub(1));
+            if let Some(entry) = entry.dyn_ref::<ResizeObserverEntry>() {
+                let rect = entry.content_rect();
+                callback_observed.set(Some((
+                    rect.width().round() as u32,
+                    rect.height().round() as u32,
+                )));
+            }
+        }) as Box<dyn FnMut(Array)>);
+        let observer = match ResizeObserver::new(callback.as_ref().unchecked_ref()) {
+            Ok(observer) => {
+                observer.observe(canvas);
+                Some((observer, callback))
+            }
+            Err(_) => None,
+        };
+        CanvasSize {
+            observed: observed,
+            observer: observer,
+            display_size: (canvas.client_width() as u32, canvas.client_height() as u32),
+            frames_until_poll: RESIZE_POLL_INTERVAL,
+        }
+    }
+
+    /// Takes the size recorded by the observer since the last call, or reads it if it's
+    /// time to poll. Must be called once per frame.  
+    /// Returns the display size in CSS pixels.
+    pub fn update(&mut self, canvas: &HtmlCanvasElement) -> (u32, u32) {
+        if self.observer.is_some() {
+            if let Some(size) = self.observed.take() {
+                self.display_size = size;
+            }
+        } else if self.frames_until_poll == 0 {
+            self.display_size = (canvas.client_width() as u32, canvas.client_height() as u32);
+            self.frames_until_poll = RESIZE_POLL_INTERVAL;
+        } else {
+            self.frames_until_poll -= 1;
+        }
+        self.display_size
+    }
+
+    /// Returns the display size in CSS pixels as of the last `update`.
+    pub fn get_display_size(&self) -> (u32, u32) {
+        self.display_size
+    }
+}
+
+impl Drop for CanvasSize {
+    fn drop(&mut self) {
+        if let Some((observer, _)) = &self.observer {
+            observer.disconnect();
+        }
+    }
+}
+
+/// ## ManualSize
+///
+/// Size of the area to render to with an external context, in device pixels, pushed by the
+/// caller with `set` and applied at the next `update`.
+pub struct ManualSize {
+    width: u32,
+    height: u32,
+
+    /// `true` until the last size set has been applied
+    resized: bool,
+}
+
+impl ManualSize {
+    /// Constructor. The size is applied at the first `update`.
+    pub fn new(width: u32, height: u32) -> ManualSize {
+        ManualSize {
+            width: width,
+            height: height,
+            resized: true,
+        }
+    }
+
+    /// Sets the size, to be applied at the next `update`.
+    pub fn set(&mut self, width: u32, height: u32) -> () {
+        self.width = width;
+        self.height = height;
+        self.resized = true;
+    }
+
+    /// Applies the size last set, unless it has no pixels, in which case it stays pending
+    /// until a non-empty size is set.  
+    /// Returns the new aspect ratio if the size has been applied.
+    pub fn update(&mut self) -> Option<f32> {
+        if !self.resized || self.is_empty() {
+            return None;
+        }
+        self.resized = false;
+        Some(self.width as f32 / self.height as f32)
+    }
+
+    /// Returns the size last set, in device pixels.
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns `true` if the size last set has no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
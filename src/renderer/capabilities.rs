@@ -0,0 +1,137 @@
+//! Limits and extensions of the WebGL context, queried once when the renderer is created.
+
+use super::builtin_shaders::get_max_vertex_attributes;
+use super::debug_info::set;
+use crate::utils::ColorFormat;
+use js_sys::{Array, Object};
+use wasm_bindgen::JsValue;
+use web_sys::WebGlRenderingContext;
+
+/// Name of the extension enabling anisotropic filtering.
+const ANISOTROPIC_EXTENSION: &str = "EXT_texture_filter_anisotropic";
+
+/// `MAX_TEXTURE_MAX_ANISOTROPY_EXT`, which `web_sys` does not define.
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+/// Extensions needed to render to half-float textures in WebGL 1.
+pub const HALF_FLOAT_TARGET_EXTENSIONS: [&str; 2] =
+    ["OES_texture_half_float", "EXT_color_buffer_half_float"];
+
+/// ## Capabilities
+///
+/// What the context supports. The limits default to the minimums guaranteed by WebGL 1
+/// if a query fails.
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// Number of texture units available to fragment shaders
+    pub max_texture_image_units: u32,
+
+    /// Number of vertex attributes
+    pub max_vertex_attributes: u32,
+
+    /// Largest width or height of a texture, in pixels
+    pub max_texture_size: u32,
+
+    /// Number of `vec4` uniforms available to vertex shaders
+    pub max_vertex_uniform_vectors: u32,
+
+    /// Largest anisotropy level, `1` if anisotropic filtering is not supported
+    pub max_anisotropy: f32,
+
+    /// Names of the supported extensions
+    pub extensions: Vec<String>,
+
+    /// Color formats textures can be rendered to in: `Rgba8` always, `Rgba16F` with the
+    /// half-float extensions. `R11FG11FB10F` and `Rgb10A2` need WebGL 2 and never are.
+    pub renderable_formats: Vec<ColorFormat>,
+}
+
+impl Capabilities {
+    /// Queries the capabilities of `context`.
+    pub fn query(context: &WebGlRenderingContext) -> Capabilities {
+        let extensions: Vec<String> = context
+            .get_supported_extensions()
+            .map(|names| names.iter().filter_map(|name| name.as_string()).collect())
+            .unwrap_or_default();
+        let mut renderable_formats = vec![ColorFormat::Rgba8];
+        if HALF_FLOAT_TARGET_EXTENSIONS
+            .iter()
+            .all(|required| extensions.iter().any(|name| name == required))
+        {
+            renderable_formats.push(ColorFormat::Rgba16F);
+        }
+        let max_anisotropy = if extensions.iter().any(|name| name == ANISOTROPIC_EXTENSION) {
+            context.get_extension(ANISOTROPIC_EXTENSION).ok();
+            get_parameter(context, MAX_TEXTURE_MAX_ANISOTROPY, 1.0) as f32
+        } else {
+            1.0
+        };
+        Capabilities {
+            max_texture_image_units: get_parameter(
+                context,
+                WebGlRenderingContext::MAX_TEXTURE_IMAGE_UNITS,
+                8.0,
+            ) as u32,
+            max_vertex_attributes: get_max_vertex_attributes(context),
+            max_texture_size: get_parameter(context, WebGlRenderingContext::MAX_TEXTURE_SIZE, 64.0)
+                as u32,
+            max_vertex_uniform_vectors: get_parameter(
+                context,
+                WebGlRenderingContext::MAX_VERTEX_UNIFORM_VECTORS,
+                128.0,
+            ) as u32,
+            max_anisotropy: max_anisotropy,
+            extensions: extensions,
+            renderable_formats: renderable_formats,
+        }
+    }
+
+    /// Returns `true` if the extension called `name` is supported.
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == name)
+    }
+
+    /// Builds a JS object with the fields of this struct in camel case.
+    pub fn to_js_value(&self) -> JsValue {
+        let info = Object::new();
+        set(
+            &info,
+            "maxTextureImageUnits",
+            self.max_texture_image_units.into(),
+        );
+        set(
+            &info,
+            "maxVertexAttributes",
+            self.max_vertex_attributes.into(),
+        );
+        set(&info, "maxTextureSize", self.max_texture_size.into());
+        set(
+            &info,
+            "maxVertexUniformVectors",
+            self.max_vertex_uniform_vectors.into(),
+        );
+        set(&info, "maxAnisotropy", self.max_anisotropy.into());
+        let extensions: Array = self
+            .extensions
+            .iter()
+            .map(|name| JsValue::from(name.as_str()))
+            .collect();
+        set(&info, "extensions", extensions.into());
+        let renderable_formats: Array = self
+            .renderable_formats
+            .iter()
+            .map(|format| JsValue::from(format.get_name()))
+            .collect();
+        set(&info, "renderableFormats", renderable_formats.into());
+        info.into()
+    }
+}
+
+/// Returns a numeric parameter of the context, or `default` if it can't be read.
+fn get_parameter(context: &WebGlRenderingContext, name: u32, default: f64) -> f64 {
+    context
+        .get_parameter(name)
+        .ok()
+        .and_then(|value| value.as_f64())
+        .unwrap_or(default)
+}
@@ -0,0 +1,278 @@
+//! Debugging information about materials, exported to JS as plain objects.
+
+use super::{BatchStats, GlobalUniformValue, GlobalUniforms, Material};
+use crate::asset::{Asset, AssetRegistry, MissingAssetReference, WindingReport};
+use js_sys::{Array, Object, Reflect};
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+use web_sys::{WebGlActiveInfo, WebGlProgram, WebGlRenderingContext};
+
+/// Builds a JS object describing `material`:
+///
+/// - `id`, `vertexSource` and `fragmentSource`: its id and original shader sources;
+/// - `vertexLog`, `fragmentLog` and `programLog`: the info logs of its last compilation;
+/// - `translatedVertexSource` and `translatedFragmentSource`: the sources translated by the
+///   driver, `undefined` if `WEBGL_debug_shaders` is not available;
+/// - `attributes` and `uniforms`: the active attributes and uniforms of its program, as
+///   `{ name, type, size }` objects. Empty if the material has not been compiled yet.
+pub fn get_material_debug_info(context: &WebGlRenderingContext, material: &Material) -> JsValue {
+    let info = Object::new();
+    let (vertex_source, fragment_source) = material.get_shader_sources();
+    let logs = material.get_compilation_logs();
+    set(&info, "id", material.get_id().into());
+    set(&info, "vertexSource", vertex_source.into());
+    set(&info, "fragmentSource", fragment_source.into());
+    set(&info, "vertexLog", logs.vertex_log.as_str().into());
+    set(&info, "fragmentLog", logs.fragment_log.as_str().into());
+    set(&info, "programLog", logs.program_log.as_str().into());
+    set(
+        &info,
+        "translatedVertexSource",
+        optional_string(&logs.translated_vertex_source),
+    );
+    set(
+        &info,
+        "translatedFragmentSource",
+        optional_string(&logs.translated_fragment_source),
+    );
+    let (attributes, uniforms) = match material.get_program() {
+        Some(program) => (
+            get_active_infos(context, program, WebGlRenderingContext::ACTIVE_ATTRIBUTES),
+            get_active_infos(context, program, WebGlRenderingContext::ACTIVE_UNIFORMS),
+        ),
+        None => (Array::new(), Array::new()),
+    };
+    set(&info, "attributes", attributes.into());
+    set(&info, "uniforms", uniforms.into());
+    info.into()
+}
+
+/// Lists the active attributes or uniforms of `program`, depending on `parameter`.
+fn get_active_infos(
+    context: &WebGlRenderingContext,
+    program: &WebGlProgram,
+    parameter: u32,
+) -> Array {
+    let result = Array::new();
+    let count = context
+        .get_program_parameter(program, parameter)
+        .as_f64()
+        .unwrap_or(0.0) as u32;
+    for index in 0..count {
+        let active_info = if parameter == WebGlRenderingContext::ACTIVE_ATTRIBUTES {
+            context.get_active_attrib(program, index)
+        } else {
+            context.get_active_uniform(program, index)
+        };
+        if let Some(active_info) = active_info {
+            result.push(&describe_active_info(&active_info));
+        }
+    }
+    result
+}
+
+/// Converts a `WebGlActiveInfo` to a `{ name, type, size }` object.
+fn describe_active_info(active_info: &WebGlActiveInfo) -> JsValue {
+    let description = Object::new();
+    set(&description, "name", active_info.name().into());
+    set(&description, "type", active_info.type_().into());
+    set(&description, "size", active_info.size().into());
+    description.into()
+}
+
+/// Builds a JS array describing missing assets replaced by fallbacks, as
+/// `{ id, type, referencedBy }` objects.
+pub fn describe_missing_assets(missing_assets: &[MissingAssetReference]) -> JsValue {
+    let report = Array::new();
+    for missing_asset in missing_assets {
+        let entry = Object::new();
+        set(&entry, "id", missing_asset.id.as_str().into());
+        set(&entry, "type", missing_asset.asset_type.into());
+        set(
+            &entry,
+            "referencedBy",
+            missing_asset.referenced_by.as_str().into(),
+        );
+        report.push(&entry);
+    }
+    report.into()
+}
+
+/// Builds a JS array describing assets removed by `AssetRegistry::collect_assets`, as
+/// `{ id, type }` objects.
+pub fn describe_collected_assets(collected_assets: &[(String, &'static str)]) -> JsValue {
+    let report = Array::new();
+    for (id, asset_type) in collected_assets {
+        let entry = Object::new();
+        set(&entry, "id", id.as_str().into());
+        set(&entry, "type", (*asset_type).into());
+        report.push(&entry);
+    }
+    report.into()
+}
+
+/// Builds a JS object describing a mesh imported by `Renderer::import_mesh`, as `{ id,
+/// winding, flippedTriangles }`, `winding` being the one detected before any fix:
+/// `counterClockwise`, `clockwise`, `mixed` or `unknown`.
+pub fn describe_import(id: &str, winding_report: &WindingReport) -> JsValue {
+    let report = Object::new();
+    set(&report, "id", id.into());
+    set(&report, "winding", winding_report.winding.get_name().into());
+    set(
+        &report,
+        "flippedTriangles",
+        (winding_report.flipped_triangles as u32).into(),
+    );
+    report.into()
+}
+
+/// Builds a JS object mapping the name of each global uniform to its value: a number for
+/// floats, an array of numbers for vectors and column-major matrices.
+pub fn describe_global_uniforms(global_uniforms: &GlobalUniforms) -> JsValue {
+    let report = Object::new();
+    for (name, value) in global_uniforms.get_values() {
+        let value = match value {
+            GlobalUniformValue::Float(value) => JsValue::from(*value),
+            _ => value
+                .get_components()
+                .into_iter()
+                .map(JsValue::from)
+                .collect::<Array>()
+                .into(),
+        };
+        set(&report, name, value);
+    }
+    report.into()
+}
+
+/// Builds a JS array describing the main pass batches of the last frame, as `{ material,
+/// meshData, drawCalls, triangles }` objects, most draw calls first. Assets are given by id,
+/// `undefined` if they are no longer registered.
+pub fn describe_batch_stats(
+    asset_registry: &AssetRegistry,
+    batch_stats: &HashMap<(usize, usize), BatchStats>,
+) -> JsValue {
+    let mut batches: Vec<_> = batch_stats.iter().collect();
+    batches.sort_by(|(_, a), (_, b)| (b.draw_calls, b.triangles).cmp(&(a.draw_calls, a.triangles)));
+    let report = Array::new();
+    for ((material_index, mesh_data_index), stats) in batches {
+        let entry = Object::new();
+        let material_id = asset_registry
+            .get_material_with_index(*material_index)
+            .map(|material| material.borrow().get_id().to_owned());
+        let mesh_data_id = asset_registry
+            .get_mesh_data_with_index(*mesh_data_index)
+            .map(|mesh_data| mesh_data.borrow().get_id().to_owned());
+        set(&entry, "material", optional_string(&material_id));
+        set(&entry, "meshData", optional_string(&mesh_data_id));
+        set(&entry, "drawCalls", stats.draw_calls.into());
+        set(&entry, "triangles", stats.triangles.into());
+        report.push(&entry);
+    }
+    report.into()
+}
+
+/// Builds a JS object describing the contents of `asset_registry`, in registration order:
+///
+/// - `meshData`: `{ id, vertexCount, buffers, gpuBytes }` objects, `buffers` being the
+///   attribute names;
+/// - `materials`: `{ id, compiled, attributes, uniforms }` objects, `attributes` and
+///   `uniforms` being the registered attribute names and the shared uniform names;
+/// - `materialInstances`: `{ id, parent, uniforms, textureReferences }` objects, `uniforms`
+///   being the names of the overridden uniforms and `textureReferences` the ids of the
+///   textures referenced by id, by uniform name;
+/// - `textures`: `{ id, cube, width, height, bytes }` objects, assuming 4 bytes per pixel
+///   and no mipmaps.
+pub fn describe_asset_registry(asset_registry: &AssetRegistry) -> JsValue {
+    let (mesh_data_report, materials, material_instances, textures) =
+        (Array::new(), Array::new(), Array::new(), Array::new());
+    for (id, asset) in asset_registry.get_registered_assets() {
+        let entry = Object::new();
+        set(&entry, "id", id.into());
+        match asset {
+            Asset::MeshData(mesh_data) => {
+                let mesh_data = mesh_data.borrow();
+                set(&entry, "vertexCount", mesh_data.get_vertex_count().into());
+                let buffers: Array = mesh_data
+                    .get_buffers()
+                    .iter()
+                    .map(|buffer| JsValue::from(buffer.get_attribute_name()))
+                    .collect();
+                set(&entry, "buffers", buffers.into());
+                set(
+                    &entry,
+                    "gpuBytes",
+                    (mesh_data.get_gpu_byte_size() as u32).into(),
+                );
+                mesh_data_report.push(&entry);
+            }
+            Asset::Material(material) => {
+                let material = material.borrow();
+                set(&entry, "compiled", material.get_program().is_some().into());
+                set(
+                    &entry,
+                    "attributes",
+                    to_string_array(&material.get_attribute_names()),
+                );
+                set(
+                    &entry,
+                    "uniforms",
+                    to_string_array(&material.get_uniform_names()),
+                );
+                materials.push(&entry);
+            }
+            Asset::MaterialInstance(material_instance) => {
+                let material_instance = material_instance.borrow();
+                set(&entry, "parent", material_instance.get_parent_id().into());
+                set(
+                    &entry,
+                    "uniforms",
+                    to_string_array(&material_instance.get_uniform_names()),
+                );
+                let texture_references = Object::new();
+                for (name, texture_id) in material_instance.get_texture_references() {
+                    set(&texture_references, name, texture_id.into());
+                }
+                set(&entry, "textureReferences", texture_references.into());
+                material_instances.push(&entry);
+            }
+            Asset::Texture(_) | Asset::CubeTexture(_) => {
+                let cube = match asset {
+                    Asset::CubeTexture(_) => true,
+                    _ => false,
+                };
+                let (width, height) = asset_registry.get_texture_size(id).unwrap_or((0, 0));
+                let faces = if cube { 6 } else { 1 };
+                set(&entry, "cube", cube.into());
+                set(&entry, "width", width.into());
+                set(&entry, "height", height.into());
+                set(&entry, "bytes", (width * height * 4 * faces).into());
+                textures.push(&entry);
+            }
+            Asset::None => {}
+        }
+    }
+    let report = Object::new();
+    set(&report, "meshData", mesh_data_report.into());
+    set(&report, "materials", materials.into());
+    set(&report, "materialInstances", material_instances.into());
+    set(&report, "textures", textures.into());
+    report.into()
+}
+
+fn to_string_array(values: &[&str]) -> JsValue {
+    let array: Array = values.iter().map(|value| JsValue::from(*value)).collect();
+    array.into()
+}
+
+fn optional_string(value: &Option<String>) -> JsValue {
+    match value {
+        Some(value) => value.as_str().into(),
+        None => JsValue::UNDEFINED,
+    }
+}
+
+/// Sets a property on a freshly created object, which can't fail.
+pub(super) fn set(object: &Object, key: &str, value: JsValue) -> () {
+    Reflect::set(object, &key.into(), &value).ok();
+}
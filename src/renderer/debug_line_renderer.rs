@@ -0,0 +1,139 @@
+//! Rendering of the debug lines queued during a frame, in a single draw call.
+
+use super::builtin_shaders::{
+    compile_builtin_material, DEBUG_LINE_FRAGMENT_SHADER, DEBUG_LINE_VERTEX_SHADER,
+};
+use super::{DynamicBatch, Material};
+use crate::error::W3DError;
+use crate::utils::constants::{COLOR_BUFFER_NAME, VERTEX_BUFFER_NAME};
+use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::WebGlRenderingContext;
+use wtvr3d_file::ShaderDataType;
+
+/// Number of floats per line vertex: position (3) and color (3).
+pub const DEBUG_LINE_VERTEX_SIZE: usize = 6;
+
+/// Attributes of a line vertex, interleaved in this order.
+const DEBUG_LINE_ATTRIBUTES: [(&str, ShaderDataType); 2] = [
+    (VERTEX_BUFFER_NAME, ShaderDataType::Vector3),
+    (COLOR_BUFFER_NAME, ShaderDataType::Vector3),
+];
+
+/// Corners of a box joined by its edges, as pairs of indexes into eight corners ordered so
+/// that bit 0 of the index selects the X side, bit 1 the Y side and bit 2 the Z side.
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// ## DebugLineRenderer
+///
+/// Holds the built-in debug line `Material` and a single dynamic vertex buffer, filled with
+/// the lines queued on the `Renderer` since the last frame and drawn at once.
+///
+/// Lines are drawn over everything but overlays, without depth test, so that bounds and
+/// frustums stay visible behind the meshes they enclose.
+pub struct DebugLineRenderer {
+    /// Built-in unlit material
+    material: Rc<RefCell<Material>>,
+
+    /// Interleaved position and color buffers
+    batch: Option<DynamicBatch>,
+}
+
+impl DebugLineRenderer {
+    /// Constructor. Compiles the built-in debug line material.
+    pub fn new(context: &WebGlRenderingContext) -> Result<DebugLineRenderer, W3DError> {
+        let material = compile_builtin_material(
+            context,
+            DEBUG_LINE_VERTEX_SHADER,
+            DEBUG_LINE_FRAGMENT_SHADER,
+            "__wtvr3d_debug_lines",
+            &[VERTEX_BUFFER_NAME, COLOR_BUFFER_NAME],
+        )?;
+        Ok(DebugLineRenderer {
+            material: Rc::new(RefCell::new(material)),
+            batch: None,
+        })
+    }
+
+    /// Returns the built-in debug line material.
+    pub fn get_material(&self) -> Rc<RefCell<Material>> {
+        self.material.clone()
+    }
+
+    /// Draws the lines of `vertex_data`, made of `DEBUG_LINE_VERTEX_SIZE` floats per vertex
+    /// and two vertices per line, in a single draw call.  
+    /// The camera uniforms must have been set on the debug line material beforehand.
+    pub fn render(
+        &mut self,
+        context: &WebGlRenderingContext,
+        vertex_data: &[f32],
+    ) -> Result<(), W3DError> {
+        let vertex_count = vertex_data.len() / DEBUG_LINE_VERTEX_SIZE;
+        if vertex_count < 2 {
+            return Ok(());
+        }
+        if self.batch.is_none() {
+            self.batch = Some(DynamicBatch::new(
+                context,
+                &DEBUG_LINE_ATTRIBUTES,
+                vertex_data.len(),
+            )?);
+        }
+
+        let material = self.material.borrow();
+        context.use_program(material.get_program().as_ref());
+        material.disable_unused_attributes(context);
+        self.batch
+            .as_mut()
+            .unwrap()
+            .upload_and_bind(context, &material, vertex_data);
+
+        context.disable(WebGlRenderingContext::DEPTH_TEST);
+        context.disable(WebGlRenderingContext::BLEND);
+        context.draw_arrays(
+            WebGlRenderingContext::LINES,
+            0,
+            (vertex_count - vertex_count % 2) as i32,
+        );
+        context.enable(WebGlRenderingContext::DEPTH_TEST);
+        Ok(())
+    }
+}
+
+/// Appends a line from `from` to `to` to `vertex_data`.
+pub fn push_line_vertices(
+    vertex_data: &mut Vec<f32>,
+    from: &Vector3<f32>,
+    to: &Vector3<f32>,
+    color: &Vector3<f32>,
+) -> () {
+    for point in &[from, to] {
+        vertex_data.extend_from_slice(&[point.x, point.y, point.z, color.x, color.y, color.z]);
+    }
+}
+
+/// Appends the twelve edges of a box to `vertex_data`. `corners` must be ordered so that bit
+/// 0 of their index selects the X side, bit 1 the Y side and bit 2 the Z side.
+pub fn push_box_vertices(
+    vertex_data: &mut Vec<f32>,
+    corners: &[Vector3<f32>; 8],
+    color: &Vector3<f32>,
+) -> () {
+    for (first, second) in &BOX_EDGES {
+        push_line_vertices(vertex_data, &corners[*first], &corners[*second], color);
+    }
+}
@@ -0,0 +1,82 @@
+//! Built-in assets used by decal entities: a unit quad and an unlit, alpha blended material.
+
+use super::builtin_shaders::{DECAL_FRAGMENT_SHADER, DECAL_VERTEX_SHADER};
+use super::{Buffer, Material, MaterialInstance, MeshData, Uniform};
+use crate::error::W3DError;
+use crate::math::BoundingSphere;
+use crate::utils::constants::{TEXTURE_NAME, UV_BUFFER_NAME, VERTEX_BUFFER_NAME};
+use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlTexture};
+use wtvr3d_file::ShaderDataType;
+
+/// Id of the quad `MeshData` shared by every decal.
+pub const DECAL_QUAD_ID: &str = "__wtvr3d_decal_quad";
+
+/// Id of the `Material` shared by every decal.
+pub const DECAL_MATERIAL_ID: &str = "__wtvr3d_decal";
+
+/// Polygon offset `(factor, units)` of the built-in decal material. Negative values pull
+/// the decal towards the camera.
+pub const DECAL_POLYGON_OFFSET: (f32, f32) = (-1.0, -4.0);
+
+/// Returns the id of the `MaterialInstance` of decals using the texture `texture_index`.
+pub fn get_decal_material_instance_id(texture_index: usize) -> String {
+    format!("{}_{}", DECAL_MATERIAL_ID, texture_index)
+}
+
+/// Creates a 1 by 1 quad in the local XY plane, centered on the origin and facing +Z.
+pub fn create_decal_quad(context: &WebGlRenderingContext) -> Result<MeshData, W3DError> {
+    let positions = [
+        -0.5, -0.5, 0.0, 0.5, -0.5, 0.0, 0.5, 0.5, 0.0, -0.5, 0.5, 0.0,
+    ];
+    let tex_coordinates = [0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0];
+    let indexes = [0, 1, 2, 0, 2, 3];
+    let mut mesh_data = MeshData::new(DECAL_QUAD_ID.to_owned(), indexes.len() as i32);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        VERTEX_BUFFER_NAME,
+        ShaderDataType::Vector3,
+        &positions,
+        Some(&indexes),
+    )?);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        UV_BUFFER_NAME,
+        ShaderDataType::Vector2,
+        &tex_coordinates,
+        None,
+    )?);
+    mesh_data.set_bounds(Some(BoundingSphere::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        0.5f32.sqrt(),
+    )));
+    Ok(mesh_data)
+}
+
+/// Creates the decal `Material`. It is compiled like any other material, by the
+/// `ShaderCompilationSystem`.
+pub fn create_decal_material() -> Material {
+    let mut material = Material::new(
+        DECAL_VERTEX_SHADER,
+        DECAL_FRAGMENT_SHADER,
+        DECAL_MATERIAL_ID,
+    );
+    material.set_transparent(true);
+    material.set_polygon_offset(Some(DECAL_POLYGON_OFFSET));
+    material
+}
+
+/// Creates the `MaterialInstance` displaying `texture` with the decal material.
+pub fn create_decal_material_instance(
+    material: Rc<RefCell<Material>>,
+    texture: Rc<WebGlTexture>,
+    id: &str,
+) -> MaterialInstance {
+    let mut material_instance = MaterialInstance::new(material, id);
+    let mut texture_uniform = Uniform::new(TEXTURE_NAME, Box::new(texture));
+    texture_uniform.set_texture_index(0);
+    material_instance.set_uniform(texture_uniform);
+    material_instance
+}
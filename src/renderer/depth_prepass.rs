@@ -0,0 +1,180 @@
+//! Depth-only rendering of opaque meshes, run before the main pass when enabled.
+
+use super::buffer::U16_SIZE;
+use super::builtin_shaders::{compile_builtin_material, DEPTH_FRAGMENT_SHADER};
+use super::{Material, SortedMeshes, Uniform};
+use crate::asset::AssetRegistry;
+use crate::error::W3DError;
+use crate::utils::constants::{VERTEX_BUFFER_NAME, WORLD_TRANSFORM_NAME};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use web_sys::WebGlRenderingContext;
+
+/// ## DepthPrepass
+///
+/// Renders the opaque meshes with a minimal program writing depth only, so that the main
+/// pass can use `EQUAL` depth testing and shade each pixel once.
+///
+/// Depth programs only read the position attribute; one is generated and cached for each
+/// position layout (number of components) encountered.
+pub struct DepthPrepass {
+    /// Depth-only materials, by number of position components
+    materials: HashMap<i32, Rc<RefCell<Material>>>,
+}
+
+impl DepthPrepass {
+    /// Constructor. Programs are compiled lazily.
+    pub fn new() -> DepthPrepass {
+        DepthPrepass {
+            materials: HashMap::new(),
+        }
+    }
+
+    /// Renders the depth of every mesh using an opaque material in `sorted_meshes`, except
+    /// decals which are drawn with a polygon offset, and cutout materials whose depth depends
+    /// on their textures.  
+    /// `set_camera_uniforms` is called for each depth program used, and world matrices are
+    /// interpolated by `interpolation` in fixed timestep mode.
+    /// Returns the number of draw calls issued.
+    pub fn render<F>(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        sorted_meshes: &SortedMeshes,
+        interpolation: Option<f32>,
+        set_camera_uniforms: F,
+    ) -> u32
+    where
+        F: Fn(Rc<RefCell<Material>>),
+    {
+        let mut draw_calls = 0;
+        context.color_mask(false, false, false, false);
+        let mut current_components = None;
+        for (material_id, mesh_batches) in sorted_meshes {
+            let drawn = asset_registry
+                .get_material_with_index(**material_id)
+                .map(|material| {
+                    let material = material.borrow();
+                    let opaque =
+                        !material.is_transparent() && !material.is_decal() && !material.is_cutout();
+                    (opaque, material.get_front_face())
+                });
+            match drawn {
+                Some((true, front_face)) => context.front_face(front_face.get_gl_mode()),
+                _ => continue,
+            }
+            for (mesh_data_id, transforms) in mesh_batches {
+                let mesh_data = match asset_registry.get_mesh_data_with_index(**mesh_data_id) {
+                    Some(mesh_data) => mesh_data,
+                    None => continue,
+                };
+                let mesh_data = mesh_data.borrow();
+                let position_buffer = match mesh_data.get_buffer(VERTEX_BUFFER_NAME) {
+                    Some(buffer) => buffer,
+                    None => continue,
+                };
+                let components = position_buffer.get_data_type().get_size();
+                let material = match self.get_material(context, components) {
+                    Ok(material) => material,
+                    Err(error) => {
+                        error_once!("The depth pre-pass can't be rendered: {}", error);
+                        break;
+                    }
+                };
+                if current_components != Some(components) {
+                    context.use_program(material.borrow().get_program().as_ref());
+                    material.borrow().disable_unused_attributes(context);
+                    set_camera_uniforms(material.clone());
+                    current_components = Some(components);
+                }
+                let material = material.borrow();
+                if let Some(location) = material.get_attribute_location(VERTEX_BUFFER_NAME) {
+                    position_buffer.enable_and_bind_attribute(context, location);
+                }
+                for (_, transform, sub_mesh, _, _) in transforms {
+                    let (index_offset, index_count) = match mesh_data.get_sub_mesh_range(*sub_mesh)
+                    {
+                        Some(range) => range,
+                        None => continue,
+                    };
+                    Uniform::new_with_location(
+                        WORLD_TRANSFORM_NAME,
+                        material
+                            .global_uniform_locations
+                            .world_transform_location
+                            .clone(),
+                        Box::new(transform.get_rendered_world_matrix(interpolation)),
+                    )
+                    .set_to_context(context)
+                    .ok();
+                    context.draw_elements_with_i32(
+                        mesh_data.get_draw_mode(),
+                        index_count,
+                        WebGlRenderingContext::UNSIGNED_SHORT,
+                        index_offset * U16_SIZE as i32,
+                    );
+                    draw_calls += 1;
+                }
+            }
+        }
+        context.color_mask(true, true, true, true);
+        context.front_face(WebGlRenderingContext::CCW);
+        draw_calls
+    }
+
+    /// Returns the depth material for positions with `components` components, compiling it
+    /// the first time.
+    fn get_material(
+        &mut self,
+        context: &WebGlRenderingContext,
+        components: i32,
+    ) -> Result<Rc<RefCell<Material>>, W3DError> {
+        if let Some(material) = self.materials.get(&components) {
+            return Ok(material.clone());
+        }
+        let material = compile_builtin_material(
+            context,
+            &depth_vertex_shader(components),
+            DEPTH_FRAGMENT_SHADER,
+            &format!("__wtvr3d_depth_{}", components),
+            &[VERTEX_BUFFER_NAME],
+        )?;
+        let material = Rc::new(RefCell::new(material));
+        self.materials.insert(components, material.clone());
+        Ok(material)
+    }
+}
+
+/// Generates the depth-only vertex shader for positions with `components` components.  
+/// The position is transformed exactly like in the default vertex shaders, so that depths
+/// match in the main pass.
+fn depth_vertex_shader(components: i32) -> String {
+    let position = match components {
+        1 => "vec4(a_position, 0.0, 0.0, 1.0)",
+        2 => "vec4(a_position, 0.0, 1.0)",
+        3 => "vec4(a_position, 1.0)",
+        _ => "a_position",
+    };
+    let position_type = match components {
+        1 => "float",
+        2 => "vec2",
+        3 => "vec3",
+        _ => "vec4",
+    };
+    format!(
+        r#"
+attribute {} a_position;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+
+void main() {{
+    mat4 view_model_matrix = (u_view_matrix * u_world_transform);
+    gl_Position = (u_projection_matrix * view_model_matrix) * {};
+}}
+"#,
+        position_type, position
+    )
+}
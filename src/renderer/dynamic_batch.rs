@@ -0,0 +1,87 @@
+//! Interleaved vertices rewritten every frame by the built-in sub-renderers (overlays,
+//! sprites, particles, debug lines, blob shadows).
+
+use super::buffer::F32_SIZE;
+use super::{Buffer, Material};
+use crate::error::W3DError;
+use web_sys::WebGlRenderingContext;
+use wtvr3d_file::ShaderDataType;
+
+/// ## DynamicBatch
+///
+/// A dynamic `WebGlBuffer` of interleaved float vertices, and one `Buffer` per attribute
+/// reading it. The vertices are replaced with `bufferSubData` before each draw, and the
+/// buffer only grows when they don't fit.
+pub struct DynamicBatch {
+    /// One `Buffer` per attribute, in the order of the vertex layout
+    buffers: Vec<Buffer>,
+}
+
+impl DynamicBatch {
+    /// Constructor. Allocates room for `capacity` floats of vertices made of `attributes`,
+    /// given as `(name, type)` and interleaved in that order.
+    pub fn new(
+        context: &WebGlRenderingContext,
+        attributes: &[(&str, ShaderDataType)],
+        capacity: usize,
+    ) -> Result<DynamicBatch, W3DError> {
+        let (stride, offsets) = get_interleaved_layout(attributes);
+        let (first_name, first_type) = attributes[0];
+        let mut first = Buffer::new_dynamic(context, first_name, first_type, capacity)?;
+        first.stride = stride;
+        let mut buffers = vec![first];
+        for ((name, data_type), offset) in attributes.iter().zip(offsets).skip(1) {
+            let buffer = buffers[0].share_with_attribute(name, *data_type, stride, offset);
+            buffers.push(buffer);
+        }
+        Ok(DynamicBatch { buffers: buffers })
+    }
+
+    /// Replaces the vertices with `vertex_data`, then binds the attributes `material` uses.
+    /// The material's program must be in use.
+    pub fn upload_and_bind(
+        &mut self,
+        context: &WebGlRenderingContext,
+        material: &Material,
+        vertex_data: &[f32],
+    ) -> () {
+        self.buffers[0].update_f32_data(context, vertex_data);
+        for buffer in &self.buffers {
+            if let Some(location) = material.get_attribute_location(buffer.get_attribute_name()) {
+                buffer.enable_and_bind_attribute(context, location);
+            }
+        }
+    }
+
+    /// Deletes the `WebGlBuffer` shared by the attributes.
+    pub fn delete(&self, context: &WebGlRenderingContext) -> () {
+        self.buffers[0].delete(context);
+    }
+}
+
+/// Returns the stride of vertices made of `attributes`, and the offset of each attribute,
+/// in bytes.
+fn get_interleaved_layout(attributes: &[(&str, ShaderDataType)]) -> (i32, Vec<i32>) {
+    let mut offsets = Vec::with_capacity(attributes.len());
+    let mut stride = 0;
+    for (_, data_type) in attributes {
+        offsets.push(stride);
+        stride += data_type.get_size() * F32_SIZE as i32;
+    }
+    (stride, offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_are_laid_out_in_order() {
+        let attributes = [
+            ("a_position", ShaderDataType::Vector3),
+            ("a_tex_coordinates", ShaderDataType::Vector2),
+            ("a_opacity", ShaderDataType::Single),
+        ];
+        assert_eq!(get_interleaved_layout(&attributes), (24, vec![0, 12, 20]));
+    }
+}
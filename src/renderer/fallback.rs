@@ -0,0 +1,152 @@
+//! Built-in assets standing in for missing ones, so that broken references show up in the
+//! scene instead of silently drawing nothing: a magenta material, a unit cube and a
+//! checkerboard texture.
+
+use super::builtin_shaders::{FALLBACK_FRAGMENT_SHADER, FALLBACK_VERTEX_SHADER};
+use super::{Buffer, Material, MeshData};
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::BoundingSphere;
+use crate::utils::constants::{NORMAL_BUFFER_NAME, UV_BUFFER_NAME, VERTEX_BUFFER_NAME};
+use nalgebra::Vector3;
+use web_sys::{WebGlRenderingContext, WebGlTexture};
+use wtvr3d_file::ShaderDataType;
+
+/// Id of the cube `MeshData` replacing missing mesh data.
+pub const FALLBACK_MESH_DATA_ID: &str = "__wtvr3d_fallback_cube";
+
+/// Id of the `Material` replacing missing materials.
+pub const FALLBACK_MATERIAL_ID: &str = "__wtvr3d_fallback_material";
+
+/// Id of the `MaterialInstance` replacing missing material instances.
+pub const FALLBACK_MATERIAL_INSTANCE_ID: &str = "__wtvr3d_fallback_material_instance";
+
+/// Id of the texture replacing missing textures.
+pub const FALLBACK_TEXTURE_ID: &str = "__wtvr3d_fallback_texture";
+
+/// Width and height of the fallback texture, in pixels (one pixel per square).
+pub const FALLBACK_TEXTURE_SIZE: usize = 8;
+
+/// Creates a 1 by 1 by 1 cube centered on the origin, with normals and texture coordinates.
+pub fn create_fallback_cube(context: &WebGlRenderingContext) -> Result<MeshData, W3DError> {
+    let (positions, normals, tex_coordinates, indexes) = cube_geometry();
+    let mut mesh_data = MeshData::new(FALLBACK_MESH_DATA_ID.to_owned(), indexes.len() as i32);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        VERTEX_BUFFER_NAME,
+        ShaderDataType::Vector3,
+        &positions,
+        Some(&indexes),
+    )?);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        NORMAL_BUFFER_NAME,
+        ShaderDataType::Vector3,
+        &normals,
+        None,
+    )?);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        UV_BUFFER_NAME,
+        ShaderDataType::Vector2,
+        &tex_coordinates,
+        None,
+    )?);
+    mesh_data.set_bounds(Some(BoundingSphere::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        0.75f32.sqrt(),
+    )));
+    Ok(mesh_data)
+}
+
+/// Returns the positions, normals, texture coordinates and indexes of the fallback cube:
+/// four vertices and two counter-clockwise triangles per face.
+pub(crate) fn cube_geometry() -> (Vec<f32>, Vec<f32>, Vec<f32>, Vec<u16>) {
+    let mut positions = Vec::with_capacity(24 * 3);
+    let mut normals = Vec::with_capacity(24 * 3);
+    let mut tex_coordinates = Vec::with_capacity(24 * 2);
+    let mut indexes: Vec<u16> = Vec::with_capacity(36);
+    // Each face: its normal, then the axes its texture coordinates follow.
+    let faces = [
+        (Vector3::x(), -Vector3::z(), Vector3::y()),
+        (-Vector3::x(), Vector3::z(), Vector3::y()),
+        (Vector3::y(), Vector3::x(), -Vector3::z()),
+        (-Vector3::y(), Vector3::x(), Vector3::z()),
+        (Vector3::z(), Vector3::x(), Vector3::y()),
+        (-Vector3::z(), -Vector3::x(), Vector3::y()),
+    ];
+    for (normal, u_axis, v_axis) in &faces {
+        let first = (positions.len() / 3) as u16;
+        for (u, v) in &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let position: Vector3<f32> =
+                (normal + u_axis * (u * 2.0 - 1.0) + v_axis * (v * 2.0 - 1.0)) * 0.5;
+            positions.extend_from_slice(position.as_slice());
+            normals.extend_from_slice(normal.as_slice());
+            tex_coordinates.extend_from_slice(&[*u, 1.0 - v]);
+        }
+        indexes.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+    (positions, normals, tex_coordinates, indexes)
+}
+
+/// Creates the fallback `Material`: unlit magenta, ignoring every uniform but the camera's
+/// and the world transform. It is compiled like any other material, by the
+/// `ShaderCompilationSystem`.
+pub fn create_fallback_material() -> Material {
+    Material::new(
+        FALLBACK_VERTEX_SHADER,
+        FALLBACK_FRAGMENT_SHADER,
+        FALLBACK_MATERIAL_ID,
+    )
+}
+
+/// Creates the fallback texture: a magenta and black checkerboard, sampled without filtering
+/// so that it stays sharp.
+pub fn create_fallback_texture(context: &WebGlRenderingContext) -> Result<WebGlTexture, W3DError> {
+    let texture = context.create_texture().ok_or_else(|| {
+        W3DError::new(
+            W3DErrorKind::GlResource,
+            "Could not create the fallback texture.",
+        )
+    })?;
+    let mut pixels = Vec::with_capacity(FALLBACK_TEXTURE_SIZE * FALLBACK_TEXTURE_SIZE * 4);
+    for y in 0..FALLBACK_TEXTURE_SIZE {
+        for x in 0..FALLBACK_TEXTURE_SIZE {
+            if (x + y) % 2 == 0 {
+                pixels.extend_from_slice(&[255, 0, 255, 255]);
+            } else {
+                pixels.extend_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+    }
+    context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
+    let result = context
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            WebGlRenderingContext::TEXTURE_2D,
+            0,
+            WebGlRenderingContext::RGBA as i32,
+            FALLBACK_TEXTURE_SIZE as i32,
+            FALLBACK_TEXTURE_SIZE as i32,
+            0,
+            WebGlRenderingContext::RGBA,
+            WebGlRenderingContext::UNSIGNED_BYTE,
+            Some(&pixels),
+        );
+    if result.is_err() {
+        context.delete_texture(Some(&texture));
+        return Err(W3DError::new(
+            W3DErrorKind::GlResource,
+            "Fallback texture upload failed.",
+        ));
+    }
+    for parameter in &[
+        WebGlRenderingContext::TEXTURE_MIN_FILTER,
+        WebGlRenderingContext::TEXTURE_MAG_FILTER,
+    ] {
+        context.tex_parameteri(
+            WebGlRenderingContext::TEXTURE_2D,
+            *parameter,
+            WebGlRenderingContext::NEAREST as i32,
+        );
+    }
+    Ok(texture)
+}
@@ -0,0 +1,56 @@
+//! Statistics about the last rendered frame, for profiling from JS.
+
+use wasm_bindgen::prelude::*;
+use web_sys::WebGlRenderingContext;
+
+/// ## FrameStats
+///
+/// Counters measured while rendering the last frame. Times are CPU times spent issuing
+/// WebGL calls, in milliseconds; they don't include the GPU work, but show relative costs.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FrameStats {
+    /// Number of draw calls issued for the 3D meshes, including the depth pre-pass
+    pub draw_calls: u32,
+
+    /// Number of triangles drawn by the main mesh pass
+    pub triangles: u32,
+
+    /// Time spent in the depth pre-pass, `0` if it is disabled
+    pub depth_prepass_time: f64,
+
+    /// Time spent in the main mesh pass
+    pub main_pass_time: f64,
+
+    /// Number of mesh data evicted to honor the memory budget since the previous frame
+    pub evicted_assets: u32,
+
+    /// Number of evicted mesh data uploaded again to draw this frame
+    pub reconstructed_assets: u32,
+}
+
+/// ## BatchStats
+///
+/// Counters of a `(material, mesh data)` batch of the main mesh pass, summed over the views
+/// of the last frame. The draw calls of the material's passes are included.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BatchStats {
+    /// Number of draw calls issued for the batch
+    pub draw_calls: u32,
+
+    /// Number of triangles drawn by the batch
+    pub triangles: u32,
+}
+
+/// Returns the number of triangles drawn from `index_count` indices with `draw_mode`, `0`
+/// for points and lines.
+pub fn count_triangles(draw_mode: u32, index_count: i32) -> u32 {
+    let index_count = index_count.max(0) as u32;
+    match draw_mode {
+        WebGlRenderingContext::TRIANGLES => index_count / 3,
+        WebGlRenderingContext::TRIANGLE_STRIP | WebGlRenderingContext::TRIANGLE_FAN => {
+            index_count.saturating_sub(2)
+        }
+        _ => 0,
+    }
+}
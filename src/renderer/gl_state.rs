@@ -0,0 +1,608 @@
+//! Saving and restoring the WebGL state, to share a context with other renderers.
+
+use super::buffer::get_bound_buffer;
+use js_sys::{Array, Float32Array, Int32Array};
+use wasm_bindgen::JsCast;
+use web_sys::{WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderingContext, WebGlTexture};
+
+/// Capabilities the renderer enables or disables.
+const CAPABILITIES: [u32; 6] = [
+    WebGlRenderingContext::BLEND,
+    WebGlRenderingContext::CULL_FACE,
+    WebGlRenderingContext::DEPTH_TEST,
+    WebGlRenderingContext::POLYGON_OFFSET_FILL,
+    WebGlRenderingContext::SCISSOR_TEST,
+    WebGlRenderingContext::STENCIL_TEST,
+];
+
+/// Parameters of the stencil function and operations of a face:
+/// `[func, value mask, write mask, fail, depth fail, depth pass]`, and the reference value.
+type StencilFace = ([u32; 6], i32);
+
+/// ## GlStateGuard
+///
+/// Scope guard capturing the parts of the WebGL state the renderer mutates when it's
+/// created, and restoring them when it's dropped.
+///
+/// Saved state: the program in use, the framebuffer, `ARRAY_BUFFER` and
+/// `ELEMENT_ARRAY_BUFFER` bindings, the active texture unit and its `TEXTURE_2D` binding, and
+/// everything in `GlState`.
+/// Enabled vertex attribute arrays and the bindings of other texture units are not restored.
+pub struct GlStateGuard {
+    context: WebGlRenderingContext,
+    program: Option<WebGlProgram>,
+    framebuffer: Option<WebGlFramebuffer>,
+    array_buffer: Option<WebGlBuffer>,
+    element_array_buffer: Option<WebGlBuffer>,
+    active_texture: u32,
+    texture: Option<WebGlTexture>,
+    state: GlState,
+}
+
+impl GlStateGuard {
+    /// Captures the current state of `context`.
+    pub fn save(context: &WebGlRenderingContext) -> GlStateGuard {
+        GlStateGuard {
+            context: context.clone(),
+            program: get_object(context, WebGlRenderingContext::CURRENT_PROGRAM),
+            framebuffer: get_object(context, WebGlRenderingContext::FRAMEBUFFER_BINDING),
+            array_buffer: get_bound_buffer(context, WebGlRenderingContext::ARRAY_BUFFER_BINDING),
+            element_array_buffer: get_bound_buffer(
+                context,
+                WebGlRenderingContext::ELEMENT_ARRAY_BUFFER_BINDING,
+            ),
+            active_texture: context
+                .get_u32(WebGlRenderingContext::ACTIVE_TEXTURE)
+                .unwrap_or(WebGlRenderingContext::TEXTURE0),
+            texture: get_object(context, WebGlRenderingContext::TEXTURE_BINDING_2D),
+            state: GlState::read(context),
+        }
+    }
+}
+
+impl Drop for GlStateGuard {
+    fn drop(&mut self) {
+        let context = &self.context;
+        context.use_program(self.program.as_ref());
+        context.bind_framebuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            self.framebuffer.as_ref(),
+        );
+        context.bind_buffer(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            self.array_buffer.as_ref(),
+        );
+        context.bind_buffer(
+            WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
+            self.element_array_buffer.as_ref(),
+        );
+        context.active_texture(self.active_texture);
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, self.texture.as_ref());
+        self.state.restore(context);
+    }
+}
+
+/// ## GlState
+///
+/// The plain values of the state saved by `GlStateGuard`: the blend, cull face, depth test,
+/// polygon offset, scissor test and stencil test flags, the depth function and mask, the
+/// blend function, the color mask, the clear color, the viewport, the scissor box, and the
+/// stencil functions, masks, operations and clear value of both faces.
+#[derive(Clone, Debug, PartialEq)]
+struct GlState {
+    capabilities: [bool; 6],
+    depth_func: u32,
+    depth_mask: bool,
+    blend_func: [u32; 4],
+    color_mask: [bool; 4],
+    clear_color: [f32; 4],
+    viewport: [i32; 4],
+    scissor_box: [i32; 4],
+    stencil_front: StencilFace,
+    stencil_back: StencilFace,
+    stencil_clear: i32,
+}
+
+impl GlState {
+    /// Reads the current state of `context`.
+    fn read<C: StateContext>(context: &C) -> GlState {
+        let mut capabilities = [false; 6];
+        for (enabled, capability) in capabilities.iter_mut().zip(CAPABILITIES.iter()) {
+            *enabled = context.is_enabled(*capability);
+        }
+        GlState {
+            capabilities: capabilities,
+            depth_func: context
+                .get_u32(WebGlRenderingContext::DEPTH_FUNC)
+                .unwrap_or(WebGlRenderingContext::LESS),
+            depth_mask: context
+                .get_bool(WebGlRenderingContext::DEPTH_WRITEMASK)
+                .unwrap_or(true),
+            blend_func: [
+                context
+                    .get_u32(WebGlRenderingContext::BLEND_SRC_RGB)
+                    .unwrap_or(WebGlRenderingContext::ONE),
+                context
+                    .get_u32(WebGlRenderingContext::BLEND_DST_RGB)
+                    .unwrap_or(WebGlRenderingContext::ZERO),
+                context
+                    .get_u32(WebGlRenderingContext::BLEND_SRC_ALPHA)
+                    .unwrap_or(WebGlRenderingContext::ONE),
+                context
+                    .get_u32(WebGlRenderingContext::BLEND_DST_ALPHA)
+                    .unwrap_or(WebGlRenderingContext::ZERO),
+            ],
+            color_mask: context.get_color_mask(),
+            clear_color: context.get_clear_color(),
+            viewport: context.get_box(WebGlRenderingContext::VIEWPORT),
+            scissor_box: context.get_box(WebGlRenderingContext::SCISSOR_BOX),
+            stencil_front: read_stencil_face(
+                context,
+                [
+                    WebGlRenderingContext::STENCIL_FUNC,
+                    WebGlRenderingContext::STENCIL_VALUE_MASK,
+                    WebGlRenderingContext::STENCIL_WRITEMASK,
+                    WebGlRenderingContext::STENCIL_FAIL,
+                    WebGlRenderingContext::STENCIL_PASS_DEPTH_FAIL,
+                    WebGlRenderingContext::STENCIL_PASS_DEPTH_PASS,
+                ],
+                WebGlRenderingContext::STENCIL_REF,
+            ),
+            stencil_back: read_stencil_face(
+                context,
+                [
+                    WebGlRenderingContext::STENCIL_BACK_FUNC,
+                    WebGlRenderingContext::STENCIL_BACK_VALUE_MASK,
+                    WebGlRenderingContext::STENCIL_BACK_WRITEMASK,
+                    WebGlRenderingContext::STENCIL_BACK_FAIL,
+                    WebGlRenderingContext::STENCIL_BACK_PASS_DEPTH_FAIL,
+                    WebGlRenderingContext::STENCIL_BACK_PASS_DEPTH_PASS,
+                ],
+                WebGlRenderingContext::STENCIL_BACK_REF,
+            ),
+            stencil_clear: context
+                .get_u32(WebGlRenderingContext::STENCIL_CLEAR_VALUE)
+                .unwrap_or(0) as i32,
+        }
+    }
+
+    /// Sets this state back to `context`.
+    fn restore<C: StateContext>(&self, context: &C) -> () {
+        for (enabled, capability) in self.capabilities.iter().zip(CAPABILITIES.iter()) {
+            context.set_enabled(*capability, *enabled);
+        }
+        context.depth_func(self.depth_func);
+        context.depth_mask(self.depth_mask);
+        let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func;
+        context.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+        let [red, green, blue, alpha] = self.color_mask;
+        context.color_mask(red, green, blue, alpha);
+        let [red, green, blue, alpha] = self.clear_color;
+        context.clear_color(red, green, blue, alpha);
+        let [x, y, width, height] = self.viewport;
+        context.viewport(x, y, width, height);
+        let [x, y, width, height] = self.scissor_box;
+        context.scissor(x, y, width, height);
+        let faces = [
+            (WebGlRenderingContext::FRONT, &self.stencil_front),
+            (WebGlRenderingContext::BACK, &self.stencil_back),
+        ];
+        for (face, ([func, value_mask, write_mask, fail, depth_fail, pass], reference)) in &faces {
+            context.stencil_func_separate(*face, *func, *reference, *value_mask);
+            context.stencil_mask_separate(*face, *write_mask);
+            context.stencil_op_separate(*face, *fail, *depth_fail, *pass);
+        }
+        context.clear_stencil(self.stencil_clear);
+    }
+}
+
+/// Reads the stencil parameters of a face, given the names of the values of `StencilFace`.
+fn read_stencil_face<C: StateContext>(
+    context: &C,
+    names: [u32; 6],
+    reference_name: u32,
+) -> StencilFace {
+    let defaults = [
+        WebGlRenderingContext::ALWAYS,
+        std::u32::MAX,
+        std::u32::MAX,
+        WebGlRenderingContext::KEEP,
+        WebGlRenderingContext::KEEP,
+        WebGlRenderingContext::KEEP,
+    ];
+    let mut values = defaults;
+    for (value, name) in values.iter_mut().zip(names.iter()) {
+        if let Some(parameter) = context.get_u32(*name) {
+            *value = parameter;
+        }
+    }
+    let reference = context.get_u32(reference_name).unwrap_or(0) as i32;
+    (values, reference)
+}
+
+/// Reads and writes the state saved by a `GlStateGuard`, implemented by the WebGL context.
+/// Only needed to test `GlState` without a context.
+trait StateContext {
+    fn is_enabled(&self, capability: u32) -> bool;
+    fn set_enabled(&self, capability: u32, enabled: bool) -> ();
+    fn get_u32(&self, name: u32) -> Option<u32>;
+    fn get_bool(&self, name: u32) -> Option<bool>;
+    fn get_color_mask(&self) -> [bool; 4];
+    fn get_clear_color(&self) -> [f32; 4];
+    fn get_box(&self, name: u32) -> [i32; 4];
+    fn depth_func(&self, func: u32) -> ();
+    fn depth_mask(&self, flag: bool) -> ();
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32);
+    fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) -> ();
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) -> ();
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) -> ();
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32) -> ();
+    fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32) -> ();
+    fn stencil_mask_separate(&self, face: u32, mask: u32) -> ();
+    fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32) -> ();
+    fn clear_stencil(&self, value: i32) -> ();
+}
+
+impl StateContext for WebGlRenderingContext {
+    fn is_enabled(&self, capability: u32) -> bool {
+        WebGlRenderingContext::is_enabled(self, capability)
+    }
+
+    fn set_enabled(&self, capability: u32, enabled: bool) -> () {
+        if enabled {
+            self.enable(capability);
+        } else {
+            self.disable(capability);
+        }
+    }
+
+    fn get_u32(&self, name: u32) -> Option<u32> {
+        self.get_parameter(name)
+            .ok()
+            .and_then(|value| value.as_f64())
+            .map(|value| value as u32)
+    }
+
+    fn get_bool(&self, name: u32) -> Option<bool> {
+        self.get_parameter(name)
+            .ok()
+            .and_then(|value| value.as_bool())
+    }
+
+    /// Returns the current color mask, all `true` if it couldn't be read.
+    fn get_color_mask(&self) -> [bool; 4] {
+        let mut mask = [true; 4];
+        if let Some(array) = get_object::<Array>(self, WebGlRenderingContext::COLOR_WRITEMASK) {
+            for (index, value) in mask.iter_mut().enumerate() {
+                *value = array.get(index as u32).as_bool().unwrap_or(true);
+            }
+        }
+        mask
+    }
+
+    fn get_clear_color(&self) -> [f32; 4] {
+        let mut color = [0.0; 4];
+        if let Some(array) =
+            get_object::<Float32Array>(self, WebGlRenderingContext::COLOR_CLEAR_VALUE)
+        {
+            array.copy_to(&mut color);
+        }
+        color
+    }
+
+    /// Returns a box parameter (`VIEWPORT` or `SCISSOR_BOX`) as `[x, y, width, height]`.
+    fn get_box(&self, name: u32) -> [i32; 4] {
+        let mut rectangle = [0; 4];
+        if let Some(array) = get_object::<Int32Array>(self, name) {
+            array.copy_to(&mut rectangle);
+        }
+        rectangle
+    }
+
+    fn depth_func(&self, func: u32) -> () {
+        WebGlRenderingContext::depth_func(self, func)
+    }
+
+    fn depth_mask(&self, flag: bool) -> () {
+        WebGlRenderingContext::depth_mask(self, flag)
+    }
+
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+        WebGlRenderingContext::blend_func_separate(self, src_rgb, dst_rgb, src_alpha, dst_alpha)
+    }
+
+    fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) -> () {
+        WebGlRenderingContext::color_mask(self, red, green, blue, alpha)
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) -> () {
+        WebGlRenderingContext::clear_color(self, red, green, blue, alpha)
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) -> () {
+        WebGlRenderingContext::viewport(self, x, y, width, height)
+    }
+
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32) -> () {
+        WebGlRenderingContext::scissor(self, x, y, width, height)
+    }
+
+    fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32) -> () {
+        WebGlRenderingContext::stencil_func_separate(self, face, func, reference, mask)
+    }
+
+    fn stencil_mask_separate(&self, face: u32, mask: u32) -> () {
+        WebGlRenderingContext::stencil_mask_separate(self, face, mask)
+    }
+
+    fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32) -> () {
+        WebGlRenderingContext::stencil_op_separate(self, face, fail, depth_fail, pass)
+    }
+
+    fn clear_stencil(&self, value: i32) -> () {
+        WebGlRenderingContext::clear_stencil(self, value)
+    }
+}
+
+/// Returns the WebGL object of type `T` stored in the parameter `name`, if any.
+pub(super) fn get_object<T: JsCast>(context: &WebGlRenderingContext, name: u32) -> Option<T> {
+    context
+        .get_parameter(name)
+        .ok()
+        .and_then(|value| value.dyn_into::<T>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    type Gl = WebGlRenderingContext;
+
+    /// Parameter value of the fake context.
+    #[derive(Clone, Debug, PartialEq)]
+    enum Value {
+        U32(u32),
+        Bool(bool),
+        Bools([bool; 4]),
+        Floats([f32; 4]),
+        Box([i32; 4]),
+    }
+
+    /// Context storing parameters by name, updated by the setters like WebGL does.
+    #[derive(Default)]
+    struct FakeContext {
+        parameters: RefCell<HashMap<u32, Value>>,
+    }
+
+    impl FakeContext {
+        fn set(&self, name: u32, value: Value) -> () {
+            self.parameters.borrow_mut().insert(name, value);
+        }
+
+        fn get(&self, name: u32) -> Option<Value> {
+            self.parameters.borrow().get(&name).cloned()
+        }
+
+        /// Names of the front and back face parameters set for `face`.
+        fn faces(face: u32, front: u32, back: u32) -> Vec<u32> {
+            match face {
+                Gl::FRONT => vec![front],
+                Gl::BACK => vec![back],
+                _ => vec![front, back],
+            }
+        }
+
+        /// A context whose state differs from WebGL's defaults everywhere.
+        fn customized() -> FakeContext {
+            let context = FakeContext::default();
+            context.set_enabled(Gl::BLEND, true);
+            context.set_enabled(Gl::SCISSOR_TEST, true);
+            context.depth_func(Gl::LEQUAL);
+            context.depth_mask(false);
+            context.blend_func_separate(Gl::SRC_ALPHA, Gl::ONE, Gl::ZERO, Gl::DST_ALPHA);
+            context.color_mask(true, false, true, false);
+            context.clear_color(0.1, 0.2, 0.3, 0.4);
+            context.viewport(10, 20, 300, 200);
+            context.scissor(15, 25, 100, 50);
+            context.stencil_func_separate(Gl::FRONT, Gl::GEQUAL, 3, 0x0f);
+            context.stencil_func_separate(Gl::BACK, Gl::NEVER, 5, 0xf0);
+            context.stencil_mask_separate(Gl::FRONT_AND_BACK, 0x7f);
+            context.stencil_op_separate(Gl::FRONT, Gl::ZERO, Gl::INCR, Gl::INVERT);
+            context.stencil_op_separate(Gl::BACK, Gl::DECR, Gl::KEEP, Gl::REPLACE);
+            context.clear_stencil(2);
+            context
+        }
+    }
+
+    impl StateContext for FakeContext {
+        fn is_enabled(&self, capability: u32) -> bool {
+            self.get(capability) == Some(Value::Bool(true))
+        }
+
+        fn set_enabled(&self, capability: u32, enabled: bool) -> () {
+            self.set(capability, Value::Bool(enabled));
+        }
+
+        fn get_u32(&self, name: u32) -> Option<u32> {
+            match self.get(name) {
+                Some(Value::U32(value)) => Some(value),
+                _ => None,
+            }
+        }
+
+        fn get_bool(&self, name: u32) -> Option<bool> {
+            match self.get(name) {
+                Some(Value::Bool(value)) => Some(value),
+                _ => None,
+            }
+        }
+
+        fn get_color_mask(&self) -> [bool; 4] {
+            match self.get(Gl::COLOR_WRITEMASK) {
+                Some(Value::Bools(mask)) => mask,
+                _ => [true; 4],
+            }
+        }
+
+        fn get_clear_color(&self) -> [f32; 4] {
+            match self.get(Gl::COLOR_CLEAR_VALUE) {
+                Some(Value::Floats(color)) => color,
+                _ => [0.; 4],
+            }
+        }
+
+        fn get_box(&self, name: u32) -> [i32; 4] {
+            match self.get(name) {
+                Some(Value::Box(rectangle)) => rectangle,
+                _ => [0; 4],
+            }
+        }
+
+        fn depth_func(&self, func: u32) -> () {
+            self.set(Gl::DEPTH_FUNC, Value::U32(func));
+        }
+
+        fn depth_mask(&self, flag: bool) -> () {
+            self.set(Gl::DEPTH_WRITEMASK, Value::Bool(flag));
+        }
+
+        fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+            self.set(Gl::BLEND_SRC_RGB, Value::U32(src_rgb));
+            self.set(Gl::BLEND_DST_RGB, Value::U32(dst_rgb));
+            self.set(Gl::BLEND_SRC_ALPHA, Value::U32(src_alpha));
+            self.set(Gl::BLEND_DST_ALPHA, Value::U32(dst_alpha));
+        }
+
+        fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) -> () {
+            self.set(Gl::COLOR_WRITEMASK, Value::Bools([red, green, blue, alpha]));
+        }
+
+        fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) -> () {
+            self.set(
+                Gl::COLOR_CLEAR_VALUE,
+                Value::Floats([red, green, blue, alpha]),
+            );
+        }
+
+        fn viewport(&self, x: i32, y: i32, width: i32, height: i32) -> () {
+            self.set(Gl::VIEWPORT, Value::Box([x, y, width, height]));
+        }
+
+        fn scissor(&self, x: i32, y: i32, width: i32, height: i32) -> () {
+            self.set(Gl::SCISSOR_BOX, Value::Box([x, y, width, height]));
+        }
+
+        fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32) -> () {
+            for name in FakeContext::faces(face, Gl::STENCIL_FUNC, Gl::STENCIL_BACK_FUNC) {
+                self.set(name, Value::U32(func));
+            }
+            for name in FakeContext::faces(face, Gl::STENCIL_REF, Gl::STENCIL_BACK_REF) {
+                self.set(name, Value::U32(reference as u32));
+            }
+            for name in
+                FakeContext::faces(face, Gl::STENCIL_VALUE_MASK, Gl::STENCIL_BACK_VALUE_MASK)
+            {
+                self.set(name, Value::U32(mask));
+            }
+        }
+
+        fn stencil_mask_separate(&self, face: u32, mask: u32) -> () {
+            for name in FakeContext::faces(face, Gl::STENCIL_WRITEMASK, Gl::STENCIL_BACK_WRITEMASK)
+            {
+                self.set(name, Value::U32(mask));
+            }
+        }
+
+        fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32) -> () {
+            let operations = [
+                (Gl::STENCIL_FAIL, Gl::STENCIL_BACK_FAIL, fail),
+                (
+                    Gl::STENCIL_PASS_DEPTH_FAIL,
+                    Gl::STENCIL_BACK_PASS_DEPTH_FAIL,
+                    depth_fail,
+                ),
+                (
+                    Gl::STENCIL_PASS_DEPTH_PASS,
+                    Gl::STENCIL_BACK_PASS_DEPTH_PASS,
+                    pass,
+                ),
+            ];
+            for (front, back, operation) in &operations {
+                for name in FakeContext::faces(face, *front, *back) {
+                    self.set(name, Value::U32(*operation));
+                }
+            }
+        }
+
+        fn clear_stencil(&self, value: i32) -> () {
+            self.set(Gl::STENCIL_CLEAR_VALUE, Value::U32(value as u32));
+        }
+    }
+
+    #[test]
+    fn state_restores_stencil_after_outline_pass() {
+        let context = FakeContext::customized();
+        let before = context.parameters.borrow().clone();
+        let state = GlState::read(&context);
+        // What the stencil outline pass does.
+        context.set_enabled(Gl::STENCIL_TEST, true);
+        context.clear_stencil(0);
+        context.stencil_func_separate(Gl::FRONT_AND_BACK, Gl::ALWAYS, 1, 0xff);
+        context.stencil_op_separate(Gl::FRONT_AND_BACK, Gl::KEEP, Gl::KEEP, Gl::REPLACE);
+        context.stencil_func_separate(Gl::FRONT_AND_BACK, Gl::NOTEQUAL, 1, 0xff);
+        context.set_enabled(Gl::STENCIL_TEST, false);
+        state.restore(&context);
+        let after = context.parameters.borrow().clone();
+        for (name, value) in &before {
+            assert_eq!(after.get(name), Some(value), "parameter {:#x}", name);
+        }
+        assert_eq!(GlState::read(&context), state);
+    }
+
+    #[test]
+    fn state_restores_scissor_after_render_views() {
+        let context = FakeContext::customized();
+        context.set_enabled(Gl::SCISSOR_TEST, false);
+        let state = GlState::read(&context);
+        context.set_enabled(Gl::SCISSOR_TEST, true);
+        context.scissor(0, 0, 64, 64);
+        context.viewport(0, 0, 64, 64);
+        state.restore(&context);
+        assert!(!context.is_enabled(Gl::SCISSOR_TEST));
+        assert_eq!(context.get_box(Gl::SCISSOR_BOX), [15, 25, 100, 50]);
+        assert_eq!(context.get_box(Gl::VIEWPORT), [10, 20, 300, 200]);
+    }
+
+    #[test]
+    fn state_restores_blending_and_depth() {
+        let context = FakeContext::customized();
+        let state = GlState::read(&context);
+        context.set_enabled(Gl::BLEND, false);
+        context.set_enabled(Gl::DEPTH_TEST, true);
+        context.depth_func(Gl::LESS);
+        context.depth_mask(true);
+        context.blend_func_separate(Gl::ONE, Gl::ONE, Gl::ONE, Gl::ONE);
+        context.color_mask(true, true, true, true);
+        context.clear_color(0., 0., 0., 0.);
+        state.restore(&context);
+        assert!(context.is_enabled(Gl::BLEND));
+        assert!(!context.is_enabled(Gl::DEPTH_TEST));
+        assert_eq!(context.get_u32(Gl::DEPTH_FUNC), Some(Gl::LEQUAL));
+        assert_eq!(context.get_bool(Gl::DEPTH_WRITEMASK), Some(false));
+        assert_eq!(context.get_u32(Gl::BLEND_DST_ALPHA), Some(Gl::DST_ALPHA));
+        assert_eq!(context.get_color_mask(), [true, false, true, false]);
+        assert_eq!(context.get_clear_color(), [0.1, 0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn state_defaults_match_webgl() {
+        let state = GlState::read(&FakeContext::default());
+        assert_eq!(state.depth_func, Gl::LESS);
+        assert!(state.depth_mask);
+        assert_eq!(state.stencil_front, state.stencil_back);
+        assert_eq!(state.stencil_front.0[0], Gl::ALWAYS);
+        assert_eq!(state.stencil_front.0[5], Gl::KEEP);
+    }
+}
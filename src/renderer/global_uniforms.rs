@@ -0,0 +1,146 @@
+//! Custom uniforms shared by every material of a scene, such as a wind direction or the
+//! position of the player, uploaded to the programs that declare them.
+
+use super::material::Material;
+use super::uniform::UniformValue;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::constants::{
+    AMBIANT_LIGHT_NAME, CAMERA_NEAR_FAR_NAME, CAMERA_POSITION_NAME, DELTA_TIME_NAME,
+    DIRECTIONAL_LIGHTS_NAME, ENTITY_OPACITY_NAME, ENVIRONMENT_MAP_NAME, EXPOSURE_NAME,
+    FADE_DISTANCE_NAME, HEMISPHERE_GROUND_COLOR_NAME, HEMISPHERE_SKY_COLOR_NAME,
+    HEMISPHERE_UP_NAME, POINT_LIGHTS_NAME, PROJECTION_MATRIX_NAME, REFLECTIVITY_NAME,
+    SCENE_DEPTH_NAME, SPOT_LIGHTS_NAME, TIME_NAME, TIME_WRAPPED_NAME, UV_SCROLL_NAME,
+    UV_TRANSFORM_NAME, VIEWPORT_HEIGHT_NAME, VIEWPORT_SIZE_NAME, VIEW_MATRIX_NAME,
+    WORLD_TRANSFORM_NAME,
+};
+use nalgebra::{Matrix4, Vector3, Vector4};
+use std::collections::BTreeMap;
+use web_sys::WebGlRenderingContext;
+
+/// Uniforms set by the engine itself, which global uniforms can't replace.
+const BUILT_IN_NAMES: [&str; 25] = [
+    VIEW_MATRIX_NAME,
+    CAMERA_POSITION_NAME,
+    PROJECTION_MATRIX_NAME,
+    WORLD_TRANSFORM_NAME,
+    AMBIANT_LIGHT_NAME,
+    HEMISPHERE_SKY_COLOR_NAME,
+    HEMISPHERE_GROUND_COLOR_NAME,
+    HEMISPHERE_UP_NAME,
+    TIME_NAME,
+    DELTA_TIME_NAME,
+    TIME_WRAPPED_NAME,
+    UV_SCROLL_NAME,
+    FADE_DISTANCE_NAME,
+    ENTITY_OPACITY_NAME,
+    UV_TRANSFORM_NAME,
+    ENVIRONMENT_MAP_NAME,
+    REFLECTIVITY_NAME,
+    EXPOSURE_NAME,
+    POINT_LIGHTS_NAME,
+    SPOT_LIGHTS_NAME,
+    DIRECTIONAL_LIGHTS_NAME,
+    VIEWPORT_HEIGHT_NAME,
+    SCENE_DEPTH_NAME,
+    CAMERA_NEAR_FAR_NAME,
+    VIEWPORT_SIZE_NAME,
+];
+
+/// Value of a global uniform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlobalUniformValue {
+    Float(f32),
+    Vector3(Vector3<f32>),
+    Vector4(Vector4<f32>),
+    Matrix4(Matrix4<f32>),
+}
+
+impl GlobalUniformValue {
+    /// Returns the value to set to the context.
+    fn as_uniform_value(&self) -> &dyn UniformValue {
+        match self {
+            GlobalUniformValue::Float(value) => value,
+            GlobalUniformValue::Vector3(value) => value,
+            GlobalUniformValue::Vector4(value) => value,
+            GlobalUniformValue::Matrix4(value) => value,
+        }
+    }
+
+    /// Returns the components of the value, column-major for matrices.
+    pub fn get_components(&self) -> Vec<f32> {
+        match self {
+            GlobalUniformValue::Float(value) => vec![*value],
+            GlobalUniformValue::Vector3(value) => value.as_slice().to_vec(),
+            GlobalUniformValue::Vector4(value) => value.as_slice().to_vec(),
+            GlobalUniformValue::Matrix4(value) => value.as_slice().to_vec(),
+        }
+    }
+}
+
+/// ## GlobalUniforms
+///
+/// Custom uniforms of a scene, uploaded once per frame to each program that declares them.
+/// Materials having a uniform of the same name keep their own value.
+#[derive(Default)]
+pub struct GlobalUniforms {
+    values: BTreeMap<String, GlobalUniformValue>,
+
+    /// Incremented by `Renderer::begin_frame`, to upload the values once per frame
+    frame: u64,
+}
+
+impl GlobalUniforms {
+    /// Sets a global uniform, replacing any previous value.
+    /// Fails if `name` is empty or is the name of a uniform set by the engine.
+    pub fn set(&mut self, name: &str, value: GlobalUniformValue) -> Result<(), W3DError> {
+        if name.is_empty() || BUILT_IN_NAMES.contains(&name) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Global uniforms can't be empty or replace a uniform set by the engine.",
+                name,
+            ));
+        }
+        self.values.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    /// Removes a global uniform, returning `false` if it wasn't set.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    /// Returns the global uniforms, sorted by name.
+    pub fn get_values(&self) -> &BTreeMap<String, GlobalUniformValue> {
+        &self.values
+    }
+
+    /// Starts a new frame: every program gets the values again on its next use.
+    pub fn next_frame(&mut self) -> () {
+        self.frame += 1;
+    }
+
+    /// Sets the global uniforms declared by the program of `material`, unless it already got
+    /// them during this frame. The program must be in use.
+    pub fn set_to_context(
+        &self,
+        context: &WebGlRenderingContext,
+        material: &mut Material,
+    ) -> Result<(), W3DError> {
+        if self.values.is_empty() || !material.start_global_uniforms_frame(self.frame) {
+            return Ok(());
+        }
+        for (name, value) in &self.values {
+            if material.get_uniform_names().contains(&name.as_str()) {
+                continue;
+            }
+            if let Some(location) = material.get_override_location(context, name) {
+                value.as_uniform_value().set_to_context_at_location(
+                    context,
+                    Some(&location),
+                    None,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
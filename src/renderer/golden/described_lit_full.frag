@@ -0,0 +1,122 @@
+#define USE_LIGHTING
+#define USE_BASE_COLOR_MAP
+#define USE_NORMAL_MAP
+#define USE_EMISSIVE
+#define USE_EMISSIVE_MAP
+
+precision mediump float;
+
+uniform vec4 u_base_color;
+uniform float u_entity_opacity;
+
+#ifdef USE_BASE_COLOR_MAP
+uniform sampler2D u_base_color_map;
+#endif
+
+#ifdef USE_NORMAL_MAP
+uniform sampler2D u_normal_map;
+varying mat3 v_tbn_matrix;
+#endif
+
+#ifdef USE_EMISSIVE
+uniform vec3 u_emissive;
+#endif
+
+#ifdef USE_EMISSIVE_MAP
+uniform sampler2D u_emissive_map;
+#endif
+
+#ifdef ALPHA_CUTOFF
+uniform float u_alpha_cutoff;
+#endif
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_position;
+
+#ifdef USE_LIGHTING
+varying vec3 v_normal;
+#endif
+
+#define NUM_DIR_LIGHTS 0
+#define NUM_POINT_LIGHTS 0
+
+struct Light {
+    vec3 position_or_direction;
+    float intensity;
+    vec3 color;
+    vec4 attenuation;
+};
+
+#if NUM_DIR_LIGHTS > 0
+uniform Light u_dir_lights[NUM_DIR_LIGHTS];
+#endif
+
+#if NUM_POINT_LIGHTS > 0
+uniform Light u_point_lights[NUM_POINT_LIGHTS];
+#endif
+
+uniform vec4 u_ambiant_light;
+
+vec3 compute_lighting(vec3 normal, vec3 position) {
+    vec3 light = u_ambiant_light.rgb * u_ambiant_light.a;
+#if NUM_DIR_LIGHTS > 0
+    for (int i = 0; i < NUM_DIR_LIGHTS; i++) {
+        float power = max(dot(normal, normalize(-u_dir_lights[i].position_or_direction)), 0.0);
+        light += u_dir_lights[i].color * u_dir_lights[i].intensity * power;
+    }
+#endif
+#if NUM_POINT_LIGHTS > 0
+    for (int i = 0; i < NUM_POINT_LIGHTS; i++) {
+        vec3 to_light = u_point_lights[i].position_or_direction - position;
+        float distance = length(to_light);
+        vec4 attenuation = u_point_lights[i].attenuation;
+        float falloff = 1.0 / max(attenuation.x + attenuation.y * distance
+            + attenuation.z * distance * distance, 0.0001);
+        if (attenuation.w > 0.0 && distance > attenuation.w) {
+            falloff = 0.0;
+        }
+        float power = max(dot(normal, to_light / max(distance, 0.0001)), 0.0);
+        light += u_point_lights[i].color * u_point_lights[i].intensity * falloff * power;
+    }
+#endif
+    return light;
+}
+
+void main() {
+    vec2 uv = vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y);
+    vec4 color = u_base_color;
+#ifdef USE_BASE_COLOR_MAP
+    color *= texture2D(u_base_color_map, uv);
+#endif
+#ifdef ALPHA_CUTOFF
+    if (color.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+#ifdef ALPHA_HASH
+    if (color.a < alpha_hash(gl_FragCoord.xy)) {
+        discard;
+    }
+    color.a = 1.0;
+#endif
+#ifdef USE_LIGHTING
+#ifdef USE_NORMAL_MAP
+    vec3 normal = normalize(v_tbn_matrix * (texture2D(u_normal_map, uv).xyz * 2.0 - 1.0));
+#else
+    vec3 normal = normalize(v_normal);
+#endif
+    color.rgb *= compute_lighting(normal, v_position);
+#endif
+#ifdef USE_EMISSIVE
+    vec3 emissive = u_emissive;
+#ifdef USE_EMISSIVE_MAP
+    emissive *= texture2D(u_emissive_map, uv).rgb;
+#endif
+    color.rgb += emissive;
+#endif
+#ifdef TONE_MAPPING
+    color.rgb = tone_map(color.rgb);
+#endif
+    color.a *= u_entity_opacity;
+    gl_FragColor = color;
+}
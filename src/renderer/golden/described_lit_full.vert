@@ -0,0 +1,45 @@
+#define USE_LIGHTING
+#define USE_BASE_COLOR_MAP
+#define USE_NORMAL_MAP
+#define USE_EMISSIVE
+#define USE_EMISSIVE_MAP
+
+attribute vec3 a_position;
+attribute vec2 a_tex_coordinates;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+uniform vec4 u_uv_transform;
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_position;
+
+#ifdef USE_LIGHTING
+attribute vec3 a_normal;
+varying vec3 v_normal;
+#endif
+
+#ifdef USE_NORMAL_MAP
+attribute vec3 a_tangent;
+varying mat3 v_tbn_matrix;
+#endif
+
+void main() {
+    vec4 position = u_world_transform * vec4(a_position, 1.0);
+#ifdef USE_WIND_SWAY
+    position.xyz += wind_sway(position.xyz, a_position.y) * position.w;
+#endif
+    v_position = position.xyz / position.w;
+    gl_Position = u_projection_matrix * (u_view_matrix * position);
+    vec2 uv_scale = u_uv_transform.zw == vec2(0.0) ? vec2(1.0) : u_uv_transform.zw;
+    v_tex_coordinates = a_tex_coordinates * uv_scale + u_uv_transform.xy;
+#ifdef USE_LIGHTING
+    v_normal = mat3(u_world_transform) * a_normal;
+#endif
+#ifdef USE_NORMAL_MAP
+    vec3 normal = normalize(mat3(u_world_transform) * a_normal);
+    vec3 tangent = normalize(mat3(u_world_transform) * a_tangent);
+    v_tbn_matrix = mat3(tangent, cross(normal, tangent), normal);
+#endif
+}
@@ -0,0 +1,72 @@
+
+precision mediump float;
+
+uniform vec4 u_base_color;
+uniform float u_entity_opacity;
+
+#ifdef USE_BASE_COLOR_MAP
+uniform sampler2D u_base_color_map;
+#endif
+
+#ifdef USE_NORMAL_MAP
+uniform sampler2D u_normal_map;
+varying mat3 v_tbn_matrix;
+#endif
+
+#ifdef USE_EMISSIVE
+uniform vec3 u_emissive;
+#endif
+
+#ifdef USE_EMISSIVE_MAP
+uniform sampler2D u_emissive_map;
+#endif
+
+#ifdef ALPHA_CUTOFF
+uniform float u_alpha_cutoff;
+#endif
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_position;
+
+#ifdef USE_LIGHTING
+varying vec3 v_normal;
+#endif
+
+void main() {
+    vec2 uv = vec2(v_tex_coordinates.x, 1.0 - v_tex_coordinates.y);
+    vec4 color = u_base_color;
+#ifdef USE_BASE_COLOR_MAP
+    color *= texture2D(u_base_color_map, uv);
+#endif
+#ifdef ALPHA_CUTOFF
+    if (color.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+#ifdef ALPHA_HASH
+    if (color.a < alpha_hash(gl_FragCoord.xy)) {
+        discard;
+    }
+    color.a = 1.0;
+#endif
+#ifdef USE_LIGHTING
+#ifdef USE_NORMAL_MAP
+    vec3 normal = normalize(v_tbn_matrix * (texture2D(u_normal_map, uv).xyz * 2.0 - 1.0));
+#else
+    vec3 normal = normalize(v_normal);
+#endif
+    color.rgb *= compute_lighting(normal, v_position);
+#endif
+#ifdef USE_EMISSIVE
+    vec3 emissive = u_emissive;
+#ifdef USE_EMISSIVE_MAP
+    emissive *= texture2D(u_emissive_map, uv).rgb;
+#endif
+    color.rgb += emissive;
+#endif
+#ifdef TONE_MAPPING
+    color.rgb = tone_map(color.rgb);
+#endif
+    color.a *= u_entity_opacity;
+    gl_FragColor = color;
+}
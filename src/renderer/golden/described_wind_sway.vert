@@ -0,0 +1,51 @@
+#define USE_WIND_SWAY
+
+attribute vec3 a_position;
+attribute vec2 a_tex_coordinates;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+uniform vec4 u_uv_transform;
+
+varying vec2 v_tex_coordinates;
+varying vec3 v_position;
+
+#ifdef USE_LIGHTING
+attribute vec3 a_normal;
+varying vec3 v_normal;
+#endif
+
+#ifdef USE_NORMAL_MAP
+attribute vec3 a_tangent;
+varying mat3 v_tbn_matrix;
+#endif
+
+uniform float u_time;
+uniform float u_wind_strength;
+uniform float u_wind_frequency;
+
+vec3 wind_sway(vec3 world_position, float height) {
+    float phase = dot(world_position.xz, vec2(0.37, 0.71));
+    float sway = sin(u_time * u_wind_frequency + phase) * u_wind_strength * max(height, 0.0);
+    return vec3(sway, 0.0, sway * 0.5);
+}
+
+void main() {
+    vec4 position = u_world_transform * vec4(a_position, 1.0);
+#ifdef USE_WIND_SWAY
+    position.xyz += wind_sway(position.xyz, a_position.y) * position.w;
+#endif
+    v_position = position.xyz / position.w;
+    gl_Position = u_projection_matrix * (u_view_matrix * position);
+    vec2 uv_scale = u_uv_transform.zw == vec2(0.0) ? vec2(1.0) : u_uv_transform.zw;
+    v_tex_coordinates = a_tex_coordinates * uv_scale + u_uv_transform.xy;
+#ifdef USE_LIGHTING
+    v_normal = mat3(u_world_transform) * a_normal;
+#endif
+#ifdef USE_NORMAL_MAP
+    vec3 normal = normalize(mat3(u_world_transform) * a_normal);
+    vec3 tangent = normalize(mat3(u_world_transform) * a_tangent);
+    v_tbn_matrix = mat3(tangent, cross(normal, tangent), normal);
+#endif
+}
@@ -0,0 +1,167 @@
+//! Built-in assets used by helper entities: ground grids and reference axes, drawn as lines
+//! with an unlit material coloring them by vertex.
+
+use super::builtin_shaders::{
+    GRID_FRAGMENT_SHADER, HELPER_LINE_FRAGMENT_SHADER, HELPER_LINE_VERTEX_SHADER,
+};
+use super::{Buffer, Material, MaterialInstance, MeshData, Uniform};
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::BoundingSphere;
+use crate::utils::constants::{COLOR_BUFFER_NAME, FADE_DISTANCE_NAME, VERTEX_BUFFER_NAME};
+use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::WebGlRenderingContext;
+use wtvr3d_file::ShaderDataType;
+
+/// Id of the opaque `Material` shared by axes helpers.
+pub const HELPER_LINE_MATERIAL_ID: &str = "__wtvr3d_helper_line";
+
+/// Id of the alpha blended `Material` shared by grid helpers, which can fade with distance.
+pub const GRID_MATERIAL_ID: &str = "__wtvr3d_grid";
+
+/// Number of divisions between two major lines of a grid, counted from its center.
+pub const GRID_MAJOR_INTERVAL: u32 = 10;
+
+/// Largest number of divisions of a grid, keeping its vertices addressable by `u16` indexes.
+pub const MAX_GRID_DIVISIONS: u32 = 16000;
+
+/// Returns the id of the `MeshData` of grids built with these parameters.
+pub fn get_grid_id(
+    size: f32,
+    divisions: u32,
+    major: &Vector3<f32>,
+    minor: &Vector3<f32>,
+) -> String {
+    format!(
+        "{}_{}_{}_{}_{}_{}_{}_{}_{}",
+        GRID_MATERIAL_ID, size, divisions, major.x, major.y, major.z, minor.x, minor.y, minor.z
+    )
+}
+
+/// Returns the id of the `MeshData` of axes of length `size`.
+pub fn get_axes_id(size: f32) -> String {
+    format!("__wtvr3d_axes_{}", size)
+}
+
+/// Returns the id of the `MaterialInstance` shared by the helpers using `material_id`.
+pub fn get_helper_material_instance_id(material_id: &str) -> String {
+    format!("{}_instance", material_id)
+}
+
+/// Creates a square grid of `size` world units in the local XZ plane, centered on the
+/// origin and cut in `divisions` cells per side. Every `GRID_MAJOR_INTERVAL`th line from the
+/// center has the `major` color, the others the `minor` color.  
+/// Fails if `divisions` is `0` or above `MAX_GRID_DIVISIONS`.
+pub fn create_grid(
+    context: &WebGlRenderingContext,
+    id: &str,
+    size: f32,
+    divisions: u32,
+    major: &Vector3<f32>,
+    minor: &Vector3<f32>,
+) -> Result<MeshData, W3DError> {
+    if divisions == 0 || divisions > MAX_GRID_DIVISIONS {
+        return Err(W3DError::with_source(
+            W3DErrorKind::InvalidArgument,
+            "Invalid number of grid divisions.",
+            &divisions.to_string(),
+        ));
+    }
+    let half_size = size / 2.;
+    let step = size / divisions as f32;
+    let mut positions = Vec::with_capacity((divisions as usize + 1) * 12);
+    let mut colors = Vec::with_capacity(positions.capacity());
+    for line in 0..=divisions {
+        let offset = line as f32 * step - half_size;
+        let from_center = (2 * line as i64 - divisions as i64).abs() / 2;
+        let color = if from_center % GRID_MAJOR_INTERVAL as i64 == 0 {
+            major
+        } else {
+            minor
+        };
+        positions.extend_from_slice(&[offset, 0., -half_size, offset, 0., half_size]);
+        positions.extend_from_slice(&[-half_size, 0., offset, half_size, 0., offset]);
+        for _ in 0..4 {
+            colors.extend_from_slice(color.as_slice());
+        }
+    }
+    create_line_mesh(context, id, &positions, &colors, half_size * 2f32.sqrt())
+}
+
+/// Creates three lines of `size` world units from the origin along the local X, Y and Z
+/// axes, colored red, green and blue.
+pub fn create_axes(
+    context: &WebGlRenderingContext,
+    id: &str,
+    size: f32,
+) -> Result<MeshData, W3DError> {
+    let positions = [
+        0., 0., 0., size, 0., 0., 0., 0., 0., 0., size, 0., 0., 0., 0., 0., 0., size,
+    ];
+    let colors = [
+        1., 0., 0., 1., 0., 0., 0., 1., 0., 0., 1., 0., 0., 0., 1., 0., 0., 1.,
+    ];
+    create_line_mesh(context, id, &positions, &colors, size)
+}
+
+/// Creates a `MeshData` drawn as `LINES` from pairs of positions, with a color per vertex,
+/// bounded by a sphere of `radius` around the origin.
+fn create_line_mesh(
+    context: &WebGlRenderingContext,
+    id: &str,
+    positions: &[f32],
+    colors: &[f32],
+    radius: f32,
+) -> Result<MeshData, W3DError> {
+    let indexes: Vec<u16> = (0..(positions.len() / 3) as u16).collect();
+    let mut mesh_data = MeshData::new(id.to_owned(), indexes.len() as i32);
+    mesh_data.set_draw_mode(WebGlRenderingContext::LINES);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        VERTEX_BUFFER_NAME,
+        ShaderDataType::Vector3,
+        positions,
+        Some(&indexes),
+    )?);
+    mesh_data.push_buffer(Buffer::from_f32_data_view(
+        context,
+        COLOR_BUFFER_NAME,
+        ShaderDataType::Vector3,
+        colors,
+        None,
+    )?);
+    mesh_data.set_bounds(Some(BoundingSphere::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        radius,
+    )));
+    Ok(mesh_data)
+}
+
+/// Creates the `Material` of helpers registered under `material_id`: the grid material
+/// for `GRID_MATERIAL_ID`, the opaque line material otherwise. It is compiled like any
+/// other material, by the `ShaderCompilationSystem`.
+pub fn create_helper_material(material_id: &str) -> Material {
+    if material_id == GRID_MATERIAL_ID {
+        let mut material = Material::new(
+            HELPER_LINE_VERTEX_SHADER,
+            GRID_FRAGMENT_SHADER,
+            GRID_MATERIAL_ID,
+        );
+        material.set_transparent(true);
+        material.set_uniform(Uniform::new(FADE_DISTANCE_NAME, Box::new(0f32)));
+        material
+    } else {
+        Material::new(
+            HELPER_LINE_VERTEX_SHADER,
+            HELPER_LINE_FRAGMENT_SHADER,
+            material_id,
+        )
+    }
+}
+
+/// Creates the `MaterialInstance` shared by the helpers using `material`.
+pub fn create_helper_material_instance(material: Rc<RefCell<Material>>) -> MaterialInstance {
+    let id = get_helper_material_instance_id(material.borrow().get_id());
+    MaterialInstance::new(material, &id)
+}
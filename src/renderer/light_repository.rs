@@ -1,32 +1,108 @@
-use crate::component::{Cone, Light};
+use super::uniform::LightUniformLocations;
+use crate::component::{Cone, Hemisphere, Light};
+use crate::math::Aabb;
 use crate::renderer::{Material, Uniform};
-use nalgebra::{Vector3, Vector4};
+use crate::utils::{LightUnits, ToneMapping};
+use nalgebra::{Vector2, Vector3, Vector4};
 use std::cell::{Ref, RefCell};
 use std::rc::Rc;
 use web_sys::WebGlRenderingContext;
 
-/// Struct to hold the current light configuration in terms of number of lights of each type
+/// Struct to hold the current light configuration in terms of number of lights of each type,
+/// whether an environment map is available, the active camera's tone mapping operator and
+/// whether it's applied by the tone mapping pass instead of the materials (`hdr`)
 #[derive(Default, PartialEq, Eq, Clone)]
 pub struct LightConfiguration {
     pub directional: usize,
     pub point: usize,
     pub spot: usize,
+    pub environment_map: bool,
+    pub tone_mapping: ToneMapping,
+    pub soft_particles: bool,
+    pub hdr: bool,
+}
+
+/// Point and spot lights uploaded for a draw batch, as indexes in the `LightRepository`'s
+/// lists, from the most to the least relevant.
+#[derive(Default, Clone)]
+pub struct LightSelection {
+    pub point: Vec<usize>,
+    pub spot: Vec<usize>,
 }
 
 /// Resource for sharing light information between the light system and the rendering system
 #[derive(Default)]
 pub struct LightRepository {
     pub ambiant: Option<Light>,
+    pub hemisphere: Option<(Light, Hemisphere)>,
     pub directional: Vec<(Light, Vector3<f32>)>,
     pub point: Vec<(Light, Vector3<f32>)>,
     pub spot: Vec<(Light, Vector3<f32>, Vector3<f32>, Cone)>,
+
+    /// Unit of the intensity of the `Light` components. The lights above are converted to
+    /// intensities that shaders use as is.
+    pub units: LightUnits,
+
+    /// Largest number of point lights and of spot lights shaders are compiled for, below
+    /// `MAX_POINT_LIGHTS` and `MAX_SPOT_LIGHTS`. `None` to only use those.
+    pub max_lights: Option<usize>,
 }
 
 impl LightRepository {
+    /// Selects the point and spot lights to upload for a batch whose world bounds are
+    /// `bounds`: those whose range reaches the bounds, lights without a range always do.
+    /// When more than `max_point` point lights or `max_spot` spot lights remain, the closest
+    /// and strongest ones are kept.  
+    /// Without bounds, every light is a candidate and only the strongest are kept.
+    pub fn select_lights(
+        &self,
+        bounds: Option<&Aabb>,
+        max_point: usize,
+        max_spot: usize,
+    ) -> LightSelection {
+        let point = self.point.iter().map(|(light, position)| (light, position));
+        let spot = self
+            .spot
+            .iter()
+            .map(|(light, position, _, _)| (light, position));
+        LightSelection {
+            point: LightRepository::rank_lights(point, bounds, max_point),
+            spot: LightRepository::rank_lights(spot, bounds, max_spot),
+        }
+    }
+
+    /// Returns the indexes of the `max` lights affecting `bounds` the most, by decreasing
+    /// `intensity / (1 + distance²)`.
+    fn rank_lights<'a, I>(lights: I, bounds: Option<&Aabb>, max: usize) -> Vec<usize>
+    where
+        I: Iterator<Item = (&'a Light, &'a Vector3<f32>)>,
+    {
+        let mut candidates: Vec<(usize, f32)> = lights
+            .enumerate()
+            .filter_map(|(index, (light, position))| {
+                let distance_squared = match bounds {
+                    Some(bounds) => bounds.distance_squared_to(position),
+                    None => 0.0,
+                };
+                match light.attenuation.get_range() {
+                    Some(range) if distance_squared > range * range => None,
+                    _ => Some((index, light.intensity / (1.0 + distance_squared))),
+                }
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.truncate(max);
+        candidates.into_iter().map(|(index, _)| index).collect()
+    }
+
+    /// Sets the light uniforms of `material` for a batch: ambient, hemisphere and directional
+    /// lights, and the point and spot lights of `lights`. Unused point and spot light slots
+    /// get a zero intensity, so that lights of previous batches don't leak.
     pub fn set_material_uniforms(
         &self,
         context: &WebGlRenderingContext,
         material: Rc<RefCell<Material>>,
+        lights: &LightSelection,
     ) {
         let mat = material.borrow();
         if let Some(light) = &self.ambiant {
@@ -43,60 +119,199 @@ impl LightRepository {
             );
             ambiant_uniform.set_to_context(context).ok();
         }
+        self.set_hemisphere_uniforms(context, &mat);
 
+        let locations = &mat.global_uniform_locations;
         for (i, dir_light) in self.directional.iter().enumerate() {
-            LightRepository::set_light_uniform(context, &mat, &dir_light.0, false, dir_light.1, i)
+            if let Some(light_locations) =
+                LightRepository::get_light_locations(&locations.directional_lights_locations, i)
+            {
+                LightRepository::set_light_uniform(
+                    context,
+                    light_locations,
+                    &dir_light.0,
+                    dir_light.1,
+                );
+            }
         }
-        for (i, point_light) in self.point.iter().enumerate() {
-            LightRepository::set_light_uniform(
-                context,
-                &mat,
-                &point_light.0,
-                true,
-                point_light.1,
-                i,
-            )
+        for (i, point_light) in lights
+            .point
+            .iter()
+            .map(|index| &self.point[*index])
+            .enumerate()
+        {
+            if let Some(light_locations) =
+                LightRepository::get_light_locations(&locations.point_lights_locations, i)
+            {
+                LightRepository::set_light_uniform(
+                    context,
+                    light_locations,
+                    &point_light.0,
+                    point_light.1,
+                );
+            }
+        }
+        let spot_lights = lights.spot.iter().map(|index| &self.spot[*index]);
+        for (i, (light, position, direction, cone)) in spot_lights.enumerate() {
+            if let Some(light_locations) =
+                LightRepository::get_light_locations(&locations.spot_lights_locations, i)
+            {
+                LightRepository::set_light_uniform(context, light_locations, light, *position);
+                Uniform::new_with_location(
+                    "",
+                    light_locations.direction.clone(),
+                    Box::new(*direction),
+                )
+                .set_to_context(context)
+                .ok();
+                Uniform::new_with_location(
+                    "",
+                    light_locations.cone.clone(),
+                    Box::new(Vector2::new(cone.angle, cone.blend)),
+                )
+                .set_to_context(context)
+                .ok();
+            }
+        }
+        for (table, used) in &[
+            (&locations.point_lights_locations, lights.point.len()),
+            (&locations.spot_lights_locations, lights.spot.len()),
+        ] {
+            for light_locations in table.iter().skip(*used) {
+                Uniform::new_with_location("", light_locations.intensity.clone(), Box::new(0.0f32))
+                    .set_to_context(context)
+                    .ok();
+            }
+        }
+    }
+
+    /// Returns the locations of the `index`th light of a table, or `None` if the table is too
+    /// short, which happens if the lights changed since the locations were looked up.
+    fn get_light_locations(
+        locations: &[LightUniformLocations],
+        index: usize,
+    ) -> Option<&LightUniformLocations> {
+        let light_locations = locations.get(index);
+        if light_locations.is_none() {
+            warn_throttled!(
+                5000,
+                "Light {} was skipped: its uniform locations have not been looked up.",
+                index
+            );
+        }
+        light_locations
+    }
+
+    /// Sets the hemisphere light uniforms, to black if there is no hemisphere light so that a
+    /// removed one doesn't keep lighting the scene.
+    fn set_hemisphere_uniforms(&self, context: &WebGlRenderingContext, material: &Ref<Material>) {
+        let locations = &material.global_uniform_locations;
+        let (sky_color, ground_color, up) = match &self.hemisphere {
+            Some((light, hemisphere)) => (
+                light.color * light.intensity,
+                hemisphere.ground_color * light.intensity,
+                hemisphere.up,
+            ),
+            None => (
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::new(0.0, 1.0, 0.0),
+            ),
+        };
+        for (location, value) in &[
+            (&locations.hemisphere_sky_color_location, sky_color),
+            (&locations.hemisphere_ground_color_location, ground_color),
+            (&locations.hemisphere_up_location, up),
+        ] {
+            if location.is_some() {
+                Uniform::new_with_location("", (*location).clone(), Box::new(*value))
+                    .set_to_context(context)
+                    .ok();
+            }
         }
     }
 
     fn set_light_uniform(
         context: &WebGlRenderingContext,
-        material: &Ref<Material>,
+        locations: &LightUniformLocations,
         light: &Light,
-        point: bool,
         dir_or_pos: Vector3<f32>,
-        index: usize,
     ) -> () {
-        let locations = if point {
-            &material.global_uniform_locations.point_lights_locations
-        } else {
-            &material
-                .global_uniform_locations
-                .directional_lights_locations
-        };
         let color_uniform = Uniform::new_with_location(
             "",
-            locations[index].color.clone(),
+            locations.color.clone(),
             Box::new(Vector3::new(light.color.x, light.color.y, light.color.z)),
         );
         color_uniform.set_to_context(context).ok();
-        let intensity_uniform = Uniform::new_with_location(
-            "",
-            locations[index].intensity.clone(),
-            Box::new(light.intensity),
-        );
+        let intensity_uniform =
+            Uniform::new_with_location("", locations.intensity.clone(), Box::new(light.intensity));
         intensity_uniform.set_to_context(context).ok();
         let attenuation_uniform = Uniform::new_with_location(
             "",
-            locations[index].attenuation.clone(),
-            Box::new(light.attenuation),
+            locations.attenuation.clone(),
+            Box::new(light.attenuation.to_vector4()),
         );
         attenuation_uniform.set_to_context(context).ok();
         let dir_pos_uniform = Uniform::new_with_location(
             "",
-            locations[index].position_or_direction.clone(),
+            locations.position_or_direction.clone(),
             Box::new(dir_or_pos),
         );
         dir_pos_uniform.set_to_context(context).ok();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Attenuation;
+
+    fn point_light(intensity: f32, range: f32, x: f32) -> (Light, Vector3<f32>) {
+        let light = Light {
+            color: Vector3::repeat(1.0),
+            intensity,
+            attenuation: Attenuation::Range(range),
+        };
+        (light, Vector3::new(x, 0.0, 0.0))
+    }
+
+    fn unit_box_at(x: f32) -> Aabb {
+        Aabb::new(
+            Vector3::new(x - 0.5, -0.5, -0.5),
+            Vector3::new(x + 0.5, 0.5, 0.5),
+        )
+    }
+
+    #[test]
+    fn far_point_light_is_not_uploaded_for_a_distant_object() {
+        let mut repository = LightRepository::default();
+        repository.point = vec![point_light(1.0, 5.0, 0.0), point_light(1.0, 5.0, 100.0)];
+        let near_first = repository.select_lights(Some(&unit_box_at(1.0)), 8, 4);
+        assert_eq!(near_first.point, vec![0]);
+        let near_second = repository.select_lights(Some(&unit_box_at(99.0)), 8, 4);
+        assert_eq!(near_second.point, vec![1]);
+        let between = repository.select_lights(Some(&unit_box_at(50.0)), 8, 4);
+        assert!(between.point.is_empty());
+    }
+
+    #[test]
+    fn closest_and_strongest_lights_are_kept_over_the_cap() {
+        let mut repository = LightRepository::default();
+        repository.point = vec![
+            point_light(1.0, 0.0, 10.0),
+            point_light(1.0, 0.0, 1.0),
+            point_light(100.0, 0.0, 10.0),
+        ];
+        let selection = repository.select_lights(Some(&unit_box_at(0.0)), 2, 4);
+        assert_eq!(selection.point, vec![2, 1]);
+        let unbounded = repository.select_lights(None, 1, 4);
+        assert_eq!(unbounded.point, vec![2]);
+    }
+
+    #[test]
+    fn lights_past_the_location_table_are_skipped() {
+        let locations: Vec<LightUniformLocations> = (0..2).map(|_| Default::default()).collect();
+        assert!(LightRepository::get_light_locations(&locations, 1).is_some());
+        assert!(LightRepository::get_light_locations(&locations, 3).is_none());
+    }
+}
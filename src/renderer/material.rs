@@ -7,13 +7,27 @@
 //! while `MaterialInstance` can use the same underlying Material with
 //! different uniform and buffer values.
 
+use super::builtin_shaders::{
+    get_max_vertex_attributes, ALPHA_HASH_FUNCTIONS, SOFT_PARTICLES_FUNCTIONS,
+    TONE_MAPPING_FUNCTIONS,
+};
 use super::uniform::{GlobalUniformLocations, Uniform};
 use super::LightConfiguration;
-use crate::utils::console_warn;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::constants::{
+    ALPHA_CUTOFF_DEFINE, ALPHA_CUTOFF_NAME, ALPHA_HASH_DEFINE, ENVIRONMENT_MAP_DEFINE,
+    HDR_OUTPUT_DEFINE, NUM_DIR_LIGHTS_DEFINE, NUM_POINT_LIGHTS_DEFINE, NUM_SPOT_LIGHTS_DEFINE,
+    SOFT_PARTICLES_DEFINE, TONE_MAPPING_DEFINE,
+};
+use crate::utils::{FrontFace, TransparencyMode};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::rc::Rc;
-use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlShader};
+use wasm_bindgen::JsCast;
+use web_sys::{
+    WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlTexture, WebGlUniformLocation,
+    WebglDebugShaders,
+};
 
 /// ## Material
 ///
@@ -37,11 +51,12 @@ pub struct Material {
     /// Fragment shader text for this material, stored in memory for live re-compilation
     fragment_shader: String,
 
-    /// Buffers configuration, with common buffer names and locations.
-    attribute_locations: HashMap<String, i32>,
+    /// Buffers configuration, with common buffer names and locations, sorted by name.
+    attribute_locations: BTreeMap<String, i32>,
 
     /// Uniforms shared accross all `MaterialInstance`s sharing this parent material.  
-    /// Can be overriden in `MaterialInstance` uniforms if needed.
+    /// Can be overriden in `MaterialInstance` uniforms if needed.  
+    /// Kept in insertion order, which is the order they are uploaded in.
     shared_uniforms: Vec<(String, Uniform)>,
 
     /// Unique ID set for this material.
@@ -55,6 +70,64 @@ pub struct Material {
 
     /// Location lookup state to avoid doing it each frame once it has been done once.
     lookup_done: bool,
+
+    /// `(factor, units)` passed to `gl.polygonOffset` when drawing with this material.  
+    /// Materials with a polygon offset are decals, drawn after every other material.
+    polygon_offset: Option<(f32, f32)>,
+
+    /// Fragments whose alpha is below this value are discarded, if set.  
+    /// Cutout materials stay in the opaque pass and keep writing depth.
+    alpha_cutoff: Option<f32>,
+
+    /// How the material is blended when it's semi-transparent.
+    transparency_mode: TransparencyMode,
+
+    /// Set when a change requires the program to be compiled again.
+    needs_recompile: bool,
+
+    /// Logs of the last compilation, kept even when it succeeded.
+    compilation_logs: CompilationLogs,
+
+    /// Locations of the uniforms overridden per entity, looked up the first time they are
+    /// used. `None` if the program doesn't use the uniform.
+    override_locations: BTreeMap<String, Option<WebGlUniformLocation>>,
+
+    /// Indexes of the materials drawn after this one with the same meshes, in order.
+    passes: Vec<usize>,
+
+    /// If `true`, front faces are culled instead of back faces, as inverted hulls need.
+    cull_front_faces: bool,
+
+    /// Winding of the front faces of the triangles drawn with this material.
+    front_face: FrontFace,
+
+    /// Frame during which the program last got the global uniforms, `None` if it never did.
+    global_uniforms_frame: Option<u64>,
+
+    /// Number of vertex attributes supported by the context, queried when compiling.
+    max_vertex_attributes: u32,
+}
+
+/// ## CompilationLogs
+///
+/// Information given by the driver while compiling and linking a `Material`'s program.
+/// Logs are usually empty on success, but can hold warnings.
+#[derive(Default, Clone)]
+pub struct CompilationLogs {
+    /// Info log of the vertex shader
+    pub vertex_log: String,
+
+    /// Info log of the fragment shader
+    pub fragment_log: String,
+
+    /// Info log of the program
+    pub program_log: String,
+
+    /// Vertex shader as translated by the driver, if `WEBGL_debug_shaders` is available
+    pub translated_vertex_source: Option<String>,
+
+    /// Fragment shader as translated by the driver, if `WEBGL_debug_shaders` is available
+    pub translated_fragment_source: Option<String>,
 }
 
 impl Material {
@@ -64,15 +137,30 @@ impl Material {
         Material {
             program: None,
             opaque: true,
-            lit: vert.contains("Light") || frag.contains("Light"),
+            lit: vert.contains("Light")
+                || frag.contains("Light")
+                || frag.contains(ENVIRONMENT_MAP_DEFINE)
+                || frag.contains(TONE_MAPPING_DEFINE)
+                || frag.contains(SOFT_PARTICLES_DEFINE),
             vertex_shader: vert.to_owned(),
             fragment_shader: frag.to_owned(),
-            attribute_locations: HashMap::new(),
+            attribute_locations: BTreeMap::new(),
             shared_uniforms: Default::default(),
             id: id.to_owned(),
             global_uniform_locations: GlobalUniformLocations::new(),
             light_configuration: Default::default(),
             lookup_done: false,
+            polygon_offset: None,
+            alpha_cutoff: None,
+            transparency_mode: TransparencyMode::Sorted,
+            needs_recompile: false,
+            compilation_logs: Default::default(),
+            override_locations: BTreeMap::new(),
+            passes: Vec::new(),
+            cull_front_faces: false,
+            front_face: FrontFace::CounterClockwise,
+            global_uniforms_frame: None,
+            max_vertex_attributes: 0,
         }
     }
 
@@ -80,22 +168,91 @@ impl Material {
         &mut self,
         context: &WebGlRenderingContext,
         light_config: &LightConfiguration,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         self.lookup_done = false;
         let vertex_text = Material::replace_light_constants(&self.vertex_shader, light_config);
-        let fragment_text = Material::replace_light_constants(&self.fragment_shader, light_config);
-        let vertex = compile_shader(context, WebGlRenderingContext::VERTEX_SHADER, &vertex_text)?;
-        let fragment = compile_shader(
+        let mut fragment_text =
+            Material::replace_light_constants(&self.fragment_shader, light_config);
+        fragment_text = Material::add_tone_mapping(fragment_text, light_config);
+        if light_config.soft_particles && fragment_text.contains(SOFT_PARTICLES_DEFINE) {
+            fragment_text = format!(
+                "#define {}\n{}\n{}",
+                SOFT_PARTICLES_DEFINE, SOFT_PARTICLES_FUNCTIONS, fragment_text
+            );
+        }
+        if self.alpha_cutoff.is_some() {
+            fragment_text = format!("#define {}\n{}", ALPHA_CUTOFF_DEFINE, fragment_text);
+        }
+        if self.is_hashed() {
+            fragment_text = format!(
+                "#define {}\n{}\n{}",
+                ALPHA_HASH_DEFINE, ALPHA_HASH_FUNCTIONS, fragment_text
+            );
+        }
+        let (vertex, vertex_log) =
+            compile_shader(context, WebGlRenderingContext::VERTEX_SHADER, &vertex_text)?;
+        let (fragment, fragment_log) = compile_shader(
             context,
             WebGlRenderingContext::FRAGMENT_SHADER,
             &fragment_text,
         )?;
-        self.program = Some(link_program(context, &vertex, &fragment)?);
+        let (program, program_log) = link_program(context, &vertex, &fragment)?;
+        let debug_shaders = context
+            .get_extension("WEBGL_debug_shaders")
+            .ok()
+            .and_then(|extension| extension)
+            .map(|extension| extension.unchecked_into::<WebglDebugShaders>());
+        for log in &[&vertex_log, &fragment_log, &program_log] {
+            if !log.trim().is_empty() {
+                log_warn!("Material {} compiled with warnings: {}", self.id, log);
+            }
+        }
+        self.compilation_logs = CompilationLogs {
+            vertex_log: vertex_log,
+            fragment_log: fragment_log,
+            program_log: program_log,
+            translated_vertex_source: debug_shaders
+                .as_ref()
+                .map(|extension| extension.get_translated_shader_source(&vertex)),
+            translated_fragment_source: debug_shaders
+                .as_ref()
+                .map(|extension| extension.get_translated_shader_source(&fragment)),
+        };
+        // Locations belong to the previous program: look them up again.
+        for (name, location) in self.attribute_locations.iter_mut() {
+            *location = context.get_attrib_location(&program, name);
+        }
+        for (_, uniform) in &mut self.shared_uniforms {
+            uniform.reset_location();
+        }
+        self.global_uniform_locations = GlobalUniformLocations::new();
+        self.override_locations.clear();
+        self.global_uniforms_frame = None;
+        self.max_vertex_attributes = get_max_vertex_attributes(context);
+        self.program = Some(program);
+        self.needs_recompile = false;
+        Ok(())
+    }
+
+    /// Compiles this `Material` if it has never been compiled or if its variant is out of
+    /// date, then looks up its locations.
+    pub fn compile_if_needed(
+        &mut self,
+        context: &WebGlRenderingContext,
+        light_config: &LightConfiguration,
+    ) -> Result<(), W3DError> {
+        if self.should_compile(light_config) {
+            self.compile(context, light_config)?;
+        }
+        self.lookup_locations(context, light_config);
+        self.light_configuration = light_config.clone();
         Ok(())
     }
 
     pub fn should_compile(&self, light_config: &LightConfiguration) -> bool {
-        self.program == None || (self.lit && light_config != &self.light_configuration)
+        self.program == None
+            || self.needs_recompile
+            || (self.lit && light_config != &self.light_configuration)
     }
 
     /// Used by buffers to register new attributes to a material.
@@ -121,6 +278,21 @@ impl Material {
         }
     }
 
+    /// Disables every vertex attribute array that isn't used by this material.  
+    /// Arrays left enabled by previous draws would otherwise point at buffers that may be
+    /// too small for the next draw call.
+    pub fn disable_unused_attributes(&self, context: &WebGlRenderingContext) -> () {
+        for location in 0..self.max_vertex_attributes {
+            let used = self
+                .attribute_locations
+                .values()
+                .any(|used_location| *used_location == location as i32);
+            if !used {
+                context.disable_vertex_attrib_array(location);
+            }
+        }
+    }
+
     /// Location Lookup for this `Material`'s `shared_uniforms`  
     /// This should be called at initialization time.
     pub fn lookup_locations(
@@ -129,6 +301,12 @@ impl Material {
         light_config: &LightConfiguration,
     ) -> () {
         if self.lookup_done {
+            // The number of lights may have changed without the program being recompiled.
+            self.global_uniform_locations.lookup_light_locations(
+                context,
+                &self.program,
+                light_config,
+            );
             return;
         }
         self.global_uniform_locations
@@ -141,6 +319,9 @@ impl Material {
 
     /// `self.opaque` setter. Use if your `Material` is semi-transparent.
     pub fn set_transparent(&mut self, transparent: bool) -> () {
+        if transparent == self.opaque && self.transparency_mode == TransparencyMode::Hashed {
+            self.needs_recompile = true;
+        }
         self.opaque = !transparent;
     }
 
@@ -150,6 +331,124 @@ impl Material {
         !self.opaque
     }
 
+    /// Sets how the material is blended when it's semi-transparent. Switching to or from
+    /// `Hashed` recompiles the program with or without the `ALPHA_HASH` define.
+    pub fn set_transparency_mode(&mut self, transparency_mode: TransparencyMode) -> () {
+        if transparency_mode != self.transparency_mode {
+            self.needs_recompile = true;
+        }
+        self.transparency_mode = transparency_mode;
+    }
+
+    /// `self.transparency_mode` getter.
+    pub fn get_transparency_mode(&self) -> TransparencyMode {
+        self.transparency_mode
+    }
+
+    /// Returns true if the `Material` is semi-transparent and alpha hashed: it's then drawn
+    /// with the opaque materials, writing depth.
+    pub fn is_hashed(&self) -> bool {
+        !self.opaque && self.transparency_mode == TransparencyMode::Hashed
+    }
+
+    /// Returns true if the `Material` is semi-transparent and alpha blended, after the opaque
+    /// materials and without writing depth.
+    pub fn is_blended(&self) -> bool {
+        !self.opaque && self.transparency_mode == TransparencyMode::Sorted
+    }
+
+    /// `self.polygon_offset` setter. Setting an offset makes this `Material` a decal.
+    pub fn set_polygon_offset(&mut self, polygon_offset: Option<(f32, f32)>) -> () {
+        self.polygon_offset = polygon_offset;
+    }
+
+    /// `self.polygon_offset` getter.
+    pub fn get_polygon_offset(&self) -> Option<(f32, f32)> {
+        self.polygon_offset
+    }
+
+    /// Sets the alpha value under which fragments are discarded, or `None` to disable
+    /// alpha testing.  
+    /// Enabling or disabling it recompiles the program with or without the `ALPHA_CUTOFF`
+    /// define; changing the value only updates the `u_alpha_cutoff` uniform.
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: Option<f32>) -> () {
+        if alpha_cutoff.is_some() != self.alpha_cutoff.is_some() {
+            self.needs_recompile = true;
+        }
+        self.alpha_cutoff = alpha_cutoff;
+        self.lookup_done = false;
+        match alpha_cutoff {
+            Some(value) => self.set_uniform(Uniform::new(ALPHA_CUTOFF_NAME, Box::new(value))),
+            None => self
+                .shared_uniforms
+                .retain(|(name, _)| name != ALPHA_CUTOFF_NAME),
+        }
+    }
+
+    /// `self.alpha_cutoff` getter.
+    pub fn get_alpha_cutoff(&self) -> Option<f32> {
+        self.alpha_cutoff
+    }
+
+    /// Returns true if this `Material` discards fragments using an alpha cutoff.
+    pub fn is_cutout(&self) -> bool {
+        self.alpha_cutoff.is_some()
+    }
+
+    /// Adds a pass to this `Material`: meshes using it are drawn again with the material at
+    /// `material_index`, after it and after the passes added before.  
+    /// Passes use their own program, state and shared uniforms, but neither the uniforms of
+    /// the material instances nor the entities' overrides, which belong to this material.
+    pub fn add_pass(&mut self, material_index: usize) -> () {
+        self.passes.push(material_index);
+    }
+
+    /// Removes every pass of this `Material`.
+    pub fn clear_passes(&mut self) -> () {
+        self.passes.clear();
+    }
+
+    /// `self.passes` getter.
+    pub fn get_passes(&self) -> &[usize] {
+        &self.passes
+    }
+
+    /// `self.cull_front_faces` setter.
+    pub fn set_cull_front_faces(&mut self, cull_front_faces: bool) -> () {
+        self.cull_front_faces = cull_front_faces;
+    }
+
+    /// `self.cull_front_faces` getter.
+    pub fn get_cull_front_faces(&self) -> bool {
+        self.cull_front_faces
+    }
+
+    /// `self.front_face` setter.
+    pub fn set_front_face(&mut self, front_face: FrontFace) -> () {
+        self.front_face = front_face;
+    }
+
+    /// `self.front_face` getter.
+    pub fn get_front_face(&self) -> FrontFace {
+        self.front_face
+    }
+
+    /// Records that the program gets the global uniforms during `frame`. Returns `false` if
+    /// it already got them.
+    pub fn start_global_uniforms_frame(&mut self, frame: u64) -> bool {
+        if self.global_uniforms_frame == Some(frame) {
+            return false;
+        }
+        self.global_uniforms_frame = Some(frame);
+        true
+    }
+
+    /// Returns true if this `Material` is a decal, drawn with a polygon offset after every
+    /// other material.
+    pub fn is_decal(&self) -> bool {
+        self.polygon_offset.is_some()
+    }
+
     /// Adds a new set of `Uniform`s to the list of uniforms, as a batch.  
     /// Every `Uniform` present in the `WebGlProgram` have to be added before
     /// any rendering step.
@@ -171,34 +470,98 @@ impl Material {
             .push((uniform_to_set.name.clone(), uniform_to_set));
     }
 
-    /// Updates the context with all of this material's uniform.  
+    /// Updates the context with all of this material's uniform, in insertion order.  
     /// Should be called before rendering objects using this material.
-    pub fn set_uniforms_to_context(&self, context: &WebGlRenderingContext) -> Result<(), String> {
+    pub fn set_uniforms_to_context(&self, context: &WebGlRenderingContext) -> Result<(), W3DError> {
         for (_, uniform) in &self.shared_uniforms {
-            uniform.set_to_context(context).unwrap_or_else(|message| {
-                console_warn(&message[..]);
+            uniform.set_to_context(context).unwrap_or_else(|error| {
+                warn_throttled!(5000, "{}", error);
             });
         }
         Ok(())
     }
 
+    /// Sets the shared uniform called `name` to the context, if there is one.
+    /// Returns `true` if it was found.
+    pub fn set_uniform_to_context(&self, context: &WebGlRenderingContext, name: &str) -> bool {
+        set_named_uniform_to_context(&self.shared_uniforms, context, name)
+    }
+
+    /// Returns the location of a uniform overridden per entity or set globally, looking it up
+    /// the first time.
+    pub fn get_override_location(
+        &mut self,
+        context: &WebGlRenderingContext,
+        name: &str,
+    ) -> Option<WebGlUniformLocation> {
+        if let Some(location) = self.override_locations.get(name) {
+            return location.clone();
+        }
+        let location = self
+            .program
+            .as_ref()
+            .and_then(|program| context.get_uniform_location(program, name));
+        self.override_locations
+            .insert(name.to_owned(), location.clone());
+        location
+    }
+
     /// Returns a reference to this `Material`'s underlying `WebGlProgram`.
     pub fn get_program(&self) -> &Option<WebGlProgram> {
         &self.program
     }
 
+    /// Returns the names of the attributes whose locations were registered.
+    pub fn get_attribute_names(&self) -> Vec<&str> {
+        self.attribute_locations
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Returns the names of the uniforms shared by every instance of this material.
+    pub fn get_uniform_names(&self) -> Vec<&str> {
+        self.shared_uniforms
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Returns the vertex and fragment shader sources, before any define is injected.
+    pub fn get_shader_sources(&self) -> (&str, &str) {
+        (&self.vertex_shader, &self.fragment_shader)
+    }
+
+    /// Returns the logs of the last compilation.
+    pub fn get_compilation_logs(&self) -> &CompilationLogs {
+        &self.compilation_logs
+    }
+
     /// Getter for the private `id` attribute.
     pub fn get_id(&self) -> &str {
         &self.id
     }
 
-    /// Get a Hashmap of the Texture uniforms and their texture indexes
-    pub fn get_texture_indexes(&self) -> Result<HashMap<String, u32>, String> {
-        let mut result = HashMap::new();
+    /// Setter for the id, used when registering the material under a custom id.
+    pub fn set_id(&mut self, id: String) -> () {
+        self.id = id;
+    }
+
+    /// Deletes the `WebGlProgram` of this `Material`, which must be compiled again to be used.
+    pub fn delete_program(&mut self, context: &WebGlRenderingContext) -> () {
+        if let Some(program) = self.program.take() {
+            context.delete_program(Some(&program));
+        }
+        self.lookup_done = false;
+    }
+
+    /// Get a map of the Texture uniforms and their texture indexes, sorted by name
+    pub fn get_texture_indexes(&self) -> Result<BTreeMap<String, u32>, W3DError> {
+        let mut result = BTreeMap::new();
         for uniform_data in &self.shared_uniforms {
             match uniform_data.1.get_texture_index() {
                 None => {
-                    //return Err(String::from("Texture indexes for parent material have yet to be registered"));
+                    //return Err(W3DError::new(W3DErrorKind::UnconstructedResource, "Texture indexes for parent material have yet to be registered"));
                 }
                 Some(index) => {
                     result.insert(uniform_data.0.to_owned(), index);
@@ -208,14 +571,40 @@ impl Material {
         Ok(result)
     }
 
+    /// Prepends the `TONE_MAPPING` define and `tone_map` to fragment shaders mentioning it,
+    /// after `HDR_OUTPUT` while HDR rendering is enabled.
+    fn add_tone_mapping(shader: String, light_config: &LightConfiguration) -> String {
+        if !shader.contains(TONE_MAPPING_DEFINE) {
+            return shader;
+        }
+        let shader = format!(
+            "#define {} {}\n{}\n{}",
+            TONE_MAPPING_DEFINE, light_config.tone_mapping as u32, TONE_MAPPING_FUNCTIONS, shader
+        );
+        if light_config.hdr {
+            format!("#define {}\n{}", HDR_OUTPUT_DEFINE, shader)
+        } else {
+            shader
+        }
+    }
+
     fn replace_light_constants(shader: &str, light_config: &LightConfiguration) -> String {
+        let shader = if light_config.environment_map && shader.contains(ENVIRONMENT_MAP_DEFINE) {
+            format!("#define {}\n{}", ENVIRONMENT_MAP_DEFINE, shader)
+        } else {
+            shader.to_owned()
+        };
+        let mut shader = shader;
+        for (define, count) in &[
+            (NUM_DIR_LIGHTS_DEFINE, light_config.directional),
+            (NUM_POINT_LIGHTS_DEFINE, light_config.point),
+            (NUM_SPOT_LIGHTS_DEFINE, light_config.spot),
+        ] {
+            shader = shader
+                .replace(&format!("#define {}", define), "//")
+                .replace(define, &count.to_string());
+        }
         shader
-            .replace("#define NUM_DIR_LIGHTS", "//")
-            .replace("#define NUM_POINT_LIGHTS", "//")
-            .replace("#define NUM_SPOT_LIGHTS", "//")
-            .replace("NUM_DIR_LIGHTS", &format!("{}", light_config.directional))
-            .replace("NUM_POINT_LIGHTS", &format!("{}", light_config.point))
-            .replace("NUM_SPOT_LIGHTS", &format!("{}", light_config.spot))
     }
 }
 
@@ -230,7 +619,7 @@ pub struct MaterialInstance {
     /// Parent material shared reference.
     parent_material: Rc<RefCell<Material>>,
 
-    /// Instance-specific map of `Uniform`s.
+    /// Instance-specific `Uniform`s, in insertion order.
     uniforms: Vec<(String, Uniform)>,
 
     /// Unique ID for this material instance
@@ -238,6 +627,10 @@ pub struct MaterialInstance {
 
     /// Location lookup state to avoid doing it each frame once it has been done once.
     lookup_done: bool,
+
+    /// Parent program the uniform locations were looked up for.
+    /// Locations are looked up again when the parent is recompiled.
+    looked_up_program: Option<WebGlProgram>,
 }
 
 impl MaterialInstance {
@@ -248,25 +641,31 @@ impl MaterialInstance {
             uniforms: Default::default(),
             id: id.to_owned(),
             lookup_done: false,
+            looked_up_program: None,
         }
     }
 
     /// Lookup locations for this `MaterialInstance`.  
     /// If locations are missing from the parent material, they will be computed
-    /// automatically.
+    /// automatically. Locations are looked up again if the parent has been recompiled.
     pub fn lookup_locations(
         &mut self,
         context: &WebGlRenderingContext,
         light_config: &LightConfiguration,
     ) -> () {
-        if self.lookup_done {
+        let mut parent_mat = self.parent_material.borrow_mut();
+        let program_changed = &self.looked_up_program != parent_mat.get_program();
+        if self.lookup_done && !program_changed {
             return;
         }
-        let mut parent_mat = self.parent_material.borrow_mut();
         parent_mat.lookup_locations(context, light_config);
         for (_, uniform) in &mut self.uniforms {
+            if program_changed {
+                uniform.reset_location();
+            }
             uniform.lookup_location(context, parent_mat.get_program());
         }
+        self.looked_up_program = parent_mat.get_program().clone();
         self.lookup_done = true;
     }
 
@@ -284,8 +683,10 @@ impl MaterialInstance {
         self.parent_material.borrow().is_transparent()
     }
 
-    /// Adds or update a mesh-specific `Uniform`.
+    /// Adds or update a mesh-specific `Uniform`.  
+    /// Its location is looked up again before the next render.
     pub fn set_uniform(&mut self, uniform_to_set: Uniform) {
+        self.lookup_done = false;
         for mut uniform in &mut self.uniforms {
             if &uniform.0 == &uniform_to_set.name {
                 uniform.1 = uniform_to_set;
@@ -296,6 +697,12 @@ impl MaterialInstance {
             .push((uniform_to_set.name.clone(), uniform_to_set));
     }
 
+    /// Removes a mesh-specific `Uniform`, falling back to the parent's value if it has one.
+    pub fn remove_uniform(&mut self, name: &str) -> () {
+        self.uniforms
+            .retain(|(uniform_name, _)| uniform_name != name);
+    }
+
     /// Updates a global `Uniform` from this `MaterialInstance`'s parent `Material`.
     pub fn set_parent_uniform(&mut self, uniform_to_set: Uniform) {
         let mut parent_mat = self.parent_material.borrow_mut();
@@ -312,34 +719,133 @@ impl MaterialInstance {
         &self.id
     }
 
+    /// Setter for the id, used when registering the material instance under a custom id.
+    pub fn set_id(&mut self, id: String) -> () {
+        self.id = id;
+    }
+
+    /// Returns `true` if this instance overrides some uniforms of its parent. Instances that
+    /// don't are drawn exactly like their parent.
+    pub fn has_uniforms(&self) -> bool {
+        !self.uniforms.is_empty()
+    }
+
+    /// Returns the names of the uniforms this instance overrides.
+    pub fn get_uniform_names(&self) -> Vec<&str> {
+        self.uniforms
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Returns the `(uniform name, texture id)` of the uniforms referencing a texture by id.
+    pub fn get_texture_references(&self) -> Vec<(&str, &str)> {
+        self.uniforms
+            .iter()
+            .filter_map(|(name, uniform)| {
+                uniform
+                    .value
+                    .get_texture_reference()
+                    .map(|reference| (name.as_str(), reference.get_id()))
+            })
+            .collect()
+    }
+
+    /// Resolves the uniforms referencing a texture by id with `resolve`, which returns the
+    /// texture currently registered under an id. Called before the uniforms are set.
+    pub fn resolve_texture_references<F>(&self, resolve: F) -> ()
+    where
+        F: Fn(&str) -> Option<Rc<WebGlTexture>>,
+    {
+        for (_, uniform) in &self.uniforms {
+            if let Some(reference) = uniform.value.get_texture_reference() {
+                reference.resolve(resolve(reference.get_id()));
+            }
+        }
+    }
+
+    /// Returns the texture unit of the texture uniform `name`: the parent's if it samples
+    /// it, this instance's if it already overrides it, and the first unit neither of them
+    /// uses otherwise.
+    pub fn get_texture_unit(&self, name: &str) -> u32 {
+        let parent_indexes = self
+            .parent_material
+            .borrow()
+            .get_texture_indexes()
+            .unwrap_or_default();
+        if let Some(index) = parent_indexes.get(name) {
+            return *index;
+        }
+        let mut next_index = 0;
+        for index in parent_indexes.values() {
+            next_index = next_index.max(index + 1);
+        }
+        for (uniform_name, uniform) in &self.uniforms {
+            if let Some(index) = uniform.get_texture_index() {
+                if uniform_name == name {
+                    return index;
+                }
+                next_index = next_index.max(index + 1);
+            }
+        }
+        next_index
+    }
+
     /// Returns the id of this `MaterialInstance`'s parent for sorting purposes.
     pub fn get_parent_id(&self) -> String {
         self.parent_material.borrow().get_id().to_owned()
     }
 
+    /// Sets the uniform called `name` to the context, if this instance has one.
+    /// Returns `true` if it was found.
+    pub fn set_uniform_to_context(&self, context: &WebGlRenderingContext, name: &str) -> bool {
+        set_named_uniform_to_context(&self.uniforms, context, name)
+    }
+
     /// Updates the context with all of this material's uniform, not including the parent
     /// `Material`'s `Uniform`s.   
     /// Should be called before rendering the Mesh using this `MaterialInstance`.  
     /// ⚠️ The parent's `Uniforms` should be set before that step.
-    pub fn set_uniforms_to_context(&self, context: &WebGlRenderingContext) -> Result<(), String> {
+    pub fn set_uniforms_to_context(&self, context: &WebGlRenderingContext) -> Result<(), W3DError> {
         for (_, uniform) in &self.uniforms {
-            uniform.set_to_context(context).unwrap_or_else(|message| {
-                console_warn(&message[..]);
+            uniform.set_to_context(context).unwrap_or_else(|error| {
+                warn_throttled!(5000, "{}", error);
             });
         }
         Ok(())
     }
 }
 
-/// Boilerplate shader compilation function taken from the `wasm-bindgen` WebGL example.
+/// Sets the uniform called `name` in `uniforms` to the context, if there is one.
+fn set_named_uniform_to_context(
+    uniforms: &[(String, Uniform)],
+    context: &WebGlRenderingContext,
+    name: &str,
+) -> bool {
+    match uniforms
+        .iter()
+        .find(|(uniform_name, _)| uniform_name == name)
+    {
+        Some((_, uniform)) => {
+            uniform.set_to_context(context).unwrap_or_else(|error| {
+                warn_throttled!(5000, "{}", error);
+            });
+            true
+        }
+        None => false,
+    }
+}
+
+/// Boilerplate shader compilation function taken from the `wasm-bindgen` WebGL example.  
+/// Returns the shader with its info log, which may hold warnings.
 fn compile_shader(
     context: &WebGlRenderingContext,
     shader_type: u32,
     source: &str,
-) -> Result<WebGlShader, String> {
+) -> Result<(WebGlShader, String), W3DError> {
     let shader = context
         .create_shader(shader_type)
-        .ok_or_else(|| String::from("Unable to create shader object"))?;
+        .ok_or_else(|| W3DError::new(W3DErrorKind::GlResource, "Unable to create shader object"))?;
     context.shader_source(&shader, source);
     context.compile_shader(&shader);
 
@@ -348,25 +854,31 @@ fn compile_shader(
         .as_bool()
         .unwrap_or(false)
     {
-        Ok(shader)
+        let log = context.get_shader_info_log(&shader).unwrap_or_default();
+        Ok((shader, log))
     } else {
-        let err = Err(context
+        let log = context
             .get_shader_info_log(&shader)
-            .unwrap_or_else(|| String::from("Unknown error creating shader")));
+            .unwrap_or_else(|| String::from("Unknown error creating shader"));
         context.delete_shader(Some(&shader));
-        err
+        Err(W3DError::with_source(
+            W3DErrorKind::ShaderCompile,
+            "Shader compilation failed",
+            &log,
+        ))
     }
 }
 
-/// Boilerplate program linking function taken from the `wasm-bindgen` WebGL example.
+/// Boilerplate program linking function taken from the `wasm-bindgen` WebGL example.  
+/// Returns the program with its info log, which may hold warnings.
 fn link_program(
     context: &WebGlRenderingContext,
     vert_shader: &WebGlShader,
     frag_shader: &WebGlShader,
-) -> Result<WebGlProgram, String> {
-    let program = context
-        .create_program()
-        .ok_or_else(|| String::from("Unable to create shader object"))?;
+) -> Result<(WebGlProgram, String), W3DError> {
+    let program = context.create_program().ok_or_else(|| {
+        W3DError::new(W3DErrorKind::GlResource, "Unable to create program object")
+    })?;
 
     context.attach_shader(&program, vert_shader);
     context.attach_shader(&program, frag_shader);
@@ -377,12 +889,95 @@ fn link_program(
         .as_bool()
         .unwrap_or(false)
     {
-        Ok(program)
+        let log = context.get_program_info_log(&program).unwrap_or_default();
+        Ok((program, log))
     } else {
-        let err = Err(context
+        let log = context
             .get_program_info_log(&program)
-            .unwrap_or_else(|| String::from("Unknown error creating program object")));
+            .unwrap_or_else(|| String::from("Unknown error creating program object"));
         context.delete_program(Some(&program));
-        err
+        Err(W3DError::with_source(
+            W3DErrorKind::ProgramLink,
+            "Program linking failed",
+            &log,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material_with(names: &[&str]) -> Material {
+        let mut material = Material::new("", "", "material");
+        for name in names {
+            material.set_uniform(Uniform::new(name, Box::new(1.0f32)));
+        }
+        material
+    }
+
+    #[test]
+    fn hdr_output_is_defined_before_tone_map() {
+        let shader = String::from("#ifdef TONE_MAPPING\n#endif");
+        let hdr = LightConfiguration {
+            hdr: true,
+            ..Default::default()
+        };
+        let compiled = Material::add_tone_mapping(shader.clone(), &hdr);
+        assert!(compiled.starts_with("#define HDR_OUTPUT\n#define TONE_MAPPING 0\n"));
+        assert!(compiled.ends_with(&shader));
+        let compiled = Material::add_tone_mapping(shader, &Default::default());
+        assert!(compiled.starts_with("#define TONE_MAPPING 0\n"));
+        let unmapped = Material::add_tone_mapping(String::from("void main() {}"), &hdr);
+        assert_eq!(unmapped, "void main() {}");
+    }
+
+    #[test]
+    fn uniforms_keep_insertion_order() {
+        let mut material = material_with(&["u_b", "u_c", "u_a"]);
+        material.set_uniform(Uniform::new("u_c", Box::new(2.0f32)));
+        assert_eq!(material.get_uniform_names(), vec!["u_b", "u_c", "u_a"]);
+    }
+
+    #[test]
+    fn texture_indexes_are_sorted_by_name() {
+        let mut material = material_with(&[]);
+        for (name, index) in &[("u_z_map", 0), ("u_a_map", 2), ("u_m_map", 1)] {
+            let mut uniform = Uniform::new(name, Box::new(0.0f32));
+            uniform.set_texture_index(*index);
+            material.set_uniform(uniform);
+        }
+        let indexes: Vec<(String, u32)> = material
+            .get_texture_indexes()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            indexes,
+            vec![
+                ("u_a_map".to_owned(), 2),
+                ("u_m_map".to_owned(), 1),
+                ("u_z_map".to_owned(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn instance_texture_units_skip_parent_units() {
+        let mut parent = material_with(&[]);
+        let mut uniform = Uniform::new("u_albedo_map", Box::new(0.0f32));
+        uniform.set_texture_index(0);
+        parent.set_uniform(uniform);
+        let parent = Rc::new(RefCell::new(parent));
+        let mut instance = MaterialInstance::new(parent.clone(), "instance");
+        assert_eq!(instance.get_texture_unit("u_albedo_map"), 0);
+        assert_eq!(instance.get_texture_unit("u_detail_map"), 1);
+        let mut uniform = Uniform::new("u_detail_map", Box::new(0.0f32));
+        uniform.set_texture_index(1);
+        instance.set_uniform(uniform);
+        assert_eq!(instance.get_texture_unit("u_detail_map"), 1);
+        assert_eq!(instance.get_texture_unit("u_mask_map"), 2);
+        let other = MaterialInstance::new(parent, "other");
+        assert_eq!(other.get_texture_unit("u_detail_map"), 1);
     }
 }
@@ -0,0 +1,406 @@
+//! Data-driven material descriptions: a few feature blocks from which the shaders of a
+//! material are generated, by assembling the chunks of `builtin_shaders` behind defines.
+//!
+//! Only the features listed here are supported: arbitrary node graphs are out of scope.
+
+use super::builtin_shaders::{
+    DESCRIBED_FRAGMENT_DECLARATIONS, DESCRIBED_FRAGMENT_MAIN, DESCRIBED_VERTEX_DECLARATIONS,
+    DESCRIBED_VERTEX_MAIN, LAMBERT_LIGHTING_CHUNK, WIND_SWAY_CHUNK,
+};
+use super::{Material, Uniform};
+use crate::asset::AssetRegistry;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::TransparencyMode;
+use nalgebra::{Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+
+/// Name of the base color uniform of described materials, multiplied by the base color map
+const BASE_COLOR_NAME: &str = "u_base_color";
+
+/// Name of the base color texture uniform of described materials
+const BASE_COLOR_MAP_NAME: &str = "u_base_color_map";
+
+/// Name of the tangent space normal map uniform of described materials
+const NORMAL_MAP_NAME: &str = "u_normal_map";
+
+/// Name of the emissive color uniform of described materials
+const EMISSIVE_NAME: &str = "u_emissive";
+
+/// Name of the emissive texture uniform of described materials
+const EMISSIVE_MAP_NAME: &str = "u_emissive_map";
+
+/// Name of the sway amplitude uniform of the `windSway` animation
+const WIND_STRENGTH_NAME: &str = "u_wind_strength";
+
+/// Name of the sway frequency uniform of the `windSway` animation
+const WIND_FREQUENCY_NAME: &str = "u_wind_frequency";
+
+/// ## MaterialDescription
+///
+/// A material described by feature blocks rather than GLSL, deserialized from JSON with
+/// camelCase field names. Every field but `id` is optional:
+///
+/// ```json
+/// {
+///     "id": "grass",
+///     "baseColor": { "color": [0.4, 0.8, 0.3, 1.0], "texture": "grass_diffuse" },
+///     "normalMap": "grass_normal",
+///     "alphaMode": { "cutoff": 0.5 },
+///     "vertexAnimation": { "windSway": { "strength": 0.1, "frequency": 2.0 } }
+/// }
+/// ```
+///
+/// Textures are given by the id they were registered under.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MaterialDescription {
+    /// Id the material is registered under
+    pub id: String,
+
+    /// Base color, multiplied by the lighting
+    #[serde(default)]
+    pub base_color: BaseColor,
+
+    /// Tangent space normal map, needing `a_tangent` in the meshes. Ignored if unlit.
+    #[serde(default)]
+    pub normal_map: Option<String>,
+
+    /// Color added after lighting
+    #[serde(default)]
+    pub emissive: Option<Emissive>,
+
+    /// How the alpha of the base color is used
+    #[serde(default)]
+    pub alpha_mode: AlphaMode,
+
+    /// Animation applied to the vertices
+    #[serde(default)]
+    pub vertex_animation: VertexAnimation,
+
+    /// Whether the ambient, directional and point lights are applied, `true` by default
+    #[serde(default = "default_lit")]
+    pub lit: bool,
+}
+
+/// Base color of a described material: a constant color, multiplied by a texture if given.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BaseColor {
+    /// RGBA color, white by default
+    #[serde(default = "default_base_color")]
+    pub color: [f32; 4],
+
+    /// Id of a registered texture
+    #[serde(default)]
+    pub texture: Option<String>,
+}
+
+impl Default for BaseColor {
+    fn default() -> BaseColor {
+        BaseColor {
+            color: default_base_color(),
+            texture: None,
+        }
+    }
+}
+
+/// Emissive color of a described material: a constant color, multiplied by a texture if
+/// given.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Emissive {
+    /// RGB color, white by default so that a texture alone can be given
+    #[serde(default = "default_emissive_color")]
+    pub color: [f32; 3],
+
+    /// Id of a registered texture
+    #[serde(default)]
+    pub texture: Option<String>,
+}
+
+/// How a described material uses the alpha of its base color.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AlphaMode {
+    /// Alpha is ignored
+    Opaque,
+
+    /// Alpha blended, see `TransparencyMode::Sorted`
+    Blend,
+
+    /// Fragments whose alpha is below the given value are discarded
+    Cutoff(f32),
+
+    /// Alpha hashed, see `TransparencyMode::Hashed`
+    Hashed,
+}
+
+impl Default for AlphaMode {
+    fn default() -> AlphaMode {
+        AlphaMode::Opaque
+    }
+}
+
+/// Animation applied to the vertices of a described material.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VertexAnimation {
+    /// Vertices stay in place
+    None,
+
+    /// Vertices sway horizontally over time, more the higher they are above the mesh
+    /// origin, e.g. for grass and foliage
+    #[serde(rename_all = "camelCase")]
+    WindSway {
+        /// Offset in world units per world unit of height
+        strength: f32,
+
+        /// Speed of the sway, in radians per second
+        frequency: f32,
+    },
+}
+
+impl Default for VertexAnimation {
+    fn default() -> VertexAnimation {
+        VertexAnimation::None
+    }
+}
+
+fn default_lit() -> bool {
+    true
+}
+
+fn default_base_color() -> [f32; 4] {
+    [1.0, 1.0, 1.0, 1.0]
+}
+
+fn default_emissive_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl MaterialDescription {
+    /// Parses a description from JSON. Unknown fields are rejected, to catch typos.
+    pub fn from_json(json: &str) -> Result<MaterialDescription, W3DError> {
+        serde_json::from_str(json).map_err(|error| {
+            W3DError::with_source(
+                W3DErrorKind::Deserialization,
+                "Could not parse the material description.",
+                &error.to_string(),
+            )
+        })
+    }
+
+    /// Returns the `USE_*` defines selecting the features of this description, in a fixed
+    /// order so that the same description always generates the same shaders.
+    fn get_defines(&self) -> Vec<&'static str> {
+        let mut defines = Vec::new();
+        if self.lit {
+            defines.push("USE_LIGHTING");
+        }
+        if self.base_color.texture.is_some() {
+            defines.push("USE_BASE_COLOR_MAP");
+        }
+        if self.lit && self.normal_map.is_some() {
+            defines.push("USE_NORMAL_MAP");
+        }
+        if let Some(emissive) = &self.emissive {
+            defines.push("USE_EMISSIVE");
+            if emissive.texture.is_some() {
+                defines.push("USE_EMISSIVE_MAP");
+            }
+        }
+        if let VertexAnimation::WindSway { .. } = self.vertex_animation {
+            defines.push("USE_WIND_SWAY");
+        }
+        defines
+    }
+
+    /// Generates the vertex and fragment shaders of this description: its defines, followed
+    /// by the chunks of its features.
+    pub fn generate_shaders(&self) -> (String, String) {
+        let header: String = self
+            .get_defines()
+            .iter()
+            .map(|define| format!("#define {}\n", define))
+            .collect();
+        let mut vertex_shader = header.clone();
+        vertex_shader.push_str(DESCRIBED_VERTEX_DECLARATIONS);
+        if let VertexAnimation::WindSway { .. } = self.vertex_animation {
+            vertex_shader.push_str(WIND_SWAY_CHUNK);
+        }
+        vertex_shader.push_str(DESCRIBED_VERTEX_MAIN);
+        let mut fragment_shader = header;
+        fragment_shader.push_str(DESCRIBED_FRAGMENT_DECLARATIONS);
+        if self.lit {
+            fragment_shader.push_str(LAMBERT_LIGHTING_CHUNK);
+        }
+        fragment_shader.push_str(DESCRIBED_FRAGMENT_MAIN);
+        (vertex_shader, fragment_shader)
+    }
+
+    /// Builds the material of this description, with its uniforms set. It is compiled like
+    /// any other material, by the `ShaderCompilationSystem`.
+    /// Fails if one of its textures is not registered.
+    pub fn build_material(&self, asset_registry: &AssetRegistry) -> Result<Material, W3DError> {
+        let (vertex_shader, fragment_shader) = self.generate_shaders();
+        let mut material = Material::new(&vertex_shader, &fragment_shader, &self.id);
+        match self.alpha_mode {
+            AlphaMode::Opaque => {}
+            AlphaMode::Blend => material.set_transparent(true),
+            AlphaMode::Cutoff(cutoff) => material.set_alpha_cutoff(Some(cutoff)),
+            AlphaMode::Hashed => {
+                material.set_transparent(true);
+                material.set_transparency_mode(TransparencyMode::Hashed);
+            }
+        }
+        material.set_uniform(Uniform::new(
+            BASE_COLOR_NAME,
+            Box::new(Vector4::from(self.base_color.color)),
+        ));
+        let mut textures = vec![(BASE_COLOR_MAP_NAME, &self.base_color.texture)];
+        if self.lit {
+            textures.push((NORMAL_MAP_NAME, &self.normal_map));
+        }
+        if let Some(emissive) = &self.emissive {
+            material.set_uniform(Uniform::new(
+                EMISSIVE_NAME,
+                Box::new(Vector3::from(emissive.color)),
+            ));
+            textures.push((EMISSIVE_MAP_NAME, &emissive.texture));
+        }
+        let mut texture_index = 0;
+        for (name, texture_id) in textures {
+            if let Some(texture_id) = texture_id {
+                let texture = asset_registry.get_texture(texture_id).ok_or_else(|| {
+                    W3DError::with_source(
+                        W3DErrorKind::MissingAsset,
+                        "Texture does not exist. Has it been registered yet?",
+                        texture_id,
+                    )
+                })?;
+                let mut uniform = Uniform::new(name, Box::new(texture));
+                uniform.set_texture_index(texture_index);
+                texture_index += 1;
+                material.set_uniform(uniform);
+            }
+        }
+        if let VertexAnimation::WindSway {
+            strength,
+            frequency,
+        } = self.vertex_animation
+        {
+            material.set_uniform(Uniform::new(WIND_STRENGTH_NAME, Box::new(strength)));
+            material.set_uniform(Uniform::new(WIND_FREQUENCY_NAME, Box::new(frequency)));
+        }
+        Ok(material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNLIT: &str = r#"{ "id": "unlit", "lit": false }"#;
+
+    const LIT_FULL: &str = r#"{
+        "id": "full",
+        "baseColor": { "color": [0.5, 0.5, 0.5, 1.0], "texture": "diffuse" },
+        "normalMap": "normal",
+        "emissive": { "texture": "glow" },
+        "alphaMode": { "cutoff": 0.5 }
+    }"#;
+
+    const WIND_SWAY: &str = r#"{
+        "id": "grass",
+        "lit": false,
+        "vertexAnimation": { "windSway": { "strength": 0.1, "frequency": 2.0 } }
+    }"#;
+
+    fn assert_golden(json: &str, vertex_golden: &str, fragment_golden: &str) {
+        let (vertex_shader, fragment_shader) = MaterialDescription::from_json(json)
+            .unwrap()
+            .generate_shaders();
+        assert_eq!(vertex_shader, vertex_golden);
+        assert_eq!(fragment_shader, fragment_golden);
+    }
+
+    #[test]
+    fn generated_shaders_match_golden_files() {
+        assert_golden(
+            UNLIT,
+            include_str!("golden/described_unlit.vert"),
+            include_str!("golden/described_unlit.frag"),
+        );
+        assert_golden(
+            LIT_FULL,
+            include_str!("golden/described_lit_full.vert"),
+            include_str!("golden/described_lit_full.frag"),
+        );
+        assert_golden(
+            WIND_SWAY,
+            include_str!("golden/described_wind_sway.vert"),
+            include_str!("golden/described_wind_sway.frag"),
+        );
+    }
+
+    #[test]
+    fn defines_follow_the_features() {
+        let description = MaterialDescription::from_json(r#"{ "id": "plain" }"#).unwrap();
+        assert_eq!(description.get_defines(), vec!["USE_LIGHTING"]);
+        assert_eq!(
+            MaterialDescription::from_json(LIT_FULL)
+                .unwrap()
+                .get_defines(),
+            vec![
+                "USE_LIGHTING",
+                "USE_BASE_COLOR_MAP",
+                "USE_NORMAL_MAP",
+                "USE_EMISSIVE",
+                "USE_EMISSIVE_MAP"
+            ]
+        );
+        let unlit_normal_map =
+            MaterialDescription::from_json(r#"{ "id": "flat", "lit": false, "normalMap": "n" }"#)
+                .unwrap();
+        assert!(unlit_normal_map.get_defines().is_empty());
+    }
+
+    #[test]
+    fn invalid_descriptions_are_rejected() {
+        for json in &[
+            r#"{ "lit": false }"#,
+            r#"{ "id": "typo", "baseColour": {} }"#,
+            r#"{ "id": "mode", "alphaMode": "additive" }"#,
+            "not json",
+        ] {
+            assert!(MaterialDescription::from_json(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn alpha_modes_configure_the_material() {
+        let registry = AssetRegistry::new();
+        let build = |alpha_mode: &str| {
+            let json = format!(r#"{{ "id": "a", "alphaMode": {} }}"#, alpha_mode);
+            MaterialDescription::from_json(&json)
+                .unwrap()
+                .build_material(&registry)
+                .ok()
+                .unwrap()
+        };
+        assert!(!build(r#""opaque""#).is_blended());
+        assert!(build(r#""blend""#).is_blended());
+        assert_eq!(
+            build(r#"{ "cutoff": 0.25 }"#).get_alpha_cutoff(),
+            Some(0.25)
+        );
+        assert_eq!(
+            build(r#""hashed""#).get_transparency_mode(),
+            TransparencyMode::Hashed
+        );
+
+        let missing_texture = MaterialDescription::from_json(LIT_FULL).unwrap();
+        let error = missing_texture.build_material(&registry).err().unwrap();
+        assert_eq!(error.get_kind(), W3DErrorKind::MissingAsset);
+    }
+}
@@ -1,13 +1,24 @@
 //! Representation of mesh data with its vertices and all buffer data.
 
-use crate::renderer::buffer::Buffer;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::{BoundingSphere, Ray, TriangleBvh};
+use crate::renderer::buffer::{Buffer, F32_SIZE, U16_SIZE};
 use crate::renderer::Material;
+use crate::utils::constants::AO_BUFFER_NAME;
+use nalgebra::Vector3;
 use std::cell::RefCell;
+use std::f32::consts::PI;
 use std::rc::Rc;
 use std::vec::Vec;
 use web_sys::WebGlRenderingContext;
+use wtvr3d_file::ShaderDataType;
 
 /// Mesh data as the union of its `Buffers` and the number of vertices in the mesh
+///
+/// Vertex data only lives on the GPU by default: buffers are uploaded from the deserialized
+/// file, which is dropped right after registration. Anything needing the vertices on the
+/// CPU, like the bounds, is computed before the upload, unless the registry is asked to
+/// retain `MeshCpuData` for consumers reading triangles.
 pub struct MeshData {
     /// Unique identifier for this MeshData
     id: String,
@@ -18,8 +29,122 @@ pub struct MeshData {
     /// Indices array referencing each triangle for the indexed buffers
     vertex_count: i32,
 
-    /// Location lookup state to avoid doing it each frame once it has been done once.
-    lookup_done: bool,
+    /// Ids of the materials whose attribute locations have been looked up, to avoid doing
+    /// it each frame.
+    looked_up_materials: Vec<String>,
+
+    /// `(index offset, index count)` of each sub-mesh. Empty if the whole mesh is drawn at once.
+    sub_meshes: Vec<(i32, i32)>,
+
+    /// Local space bounds computed from the vertex positions, if known.
+    bounds: Option<BoundingSphere>,
+
+    /// Copy of the positions and indexes, if retained at registration.
+    cpu_data: Option<MeshCpuData>,
+
+    /// Hierarchy over the retained triangles, built on the first precise pick.
+    bvh: Option<TriangleBvh>,
+
+    /// Primitive the indexes are drawn as, `TRIANGLES` unless set otherwise.
+    draw_mode: u32,
+}
+
+/// Positions and triangle indexes of a `MeshData`, kept on the CPU for physics or navmesh
+/// baking.
+pub struct MeshCpuData {
+    /// Vertex positions, `components` floats per vertex
+    pub positions: Vec<f32>,
+
+    /// Number of floats per position: 2, 3 or 4. Missing coordinates are `0`.
+    pub components: usize,
+
+    /// Vertex indexes, three per triangle. Empty if the positions are not indexed, in which
+    /// case every three consecutive vertices form a triangle.
+    pub indexes: Vec<u16>,
+}
+
+impl MeshCpuData {
+    /// Returns the number of triangles.
+    pub fn triangle_count(&self) -> usize {
+        if self.indexes.is_empty() {
+            self.positions.len() / self.components.max(1) / 3
+        } else {
+            self.indexes.len() / 3
+        }
+    }
+
+    /// Iterates over the triangles, in local space. Triangles referencing vertices that don't
+    /// exist are skipped.
+    pub fn triangles<'a>(&'a self) -> impl Iterator<Item = [Vector3<f32>; 3]> + 'a {
+        (0..self.triangle_count()).filter_map(move |triangle| {
+            let vertex = |corner: usize| {
+                let index = if self.indexes.is_empty() {
+                    triangle * 3 + corner
+                } else {
+                    self.indexes[triangle * 3 + corner] as usize
+                };
+                self.get_position(index)
+            };
+            Some([vertex(0)?, vertex(1)?, vertex(2)?])
+        })
+    }
+
+    /// Returns the size of the positions and indexes, in bytes.
+    pub fn get_byte_size(&self) -> usize {
+        self.positions.len() * F32_SIZE + self.indexes.len() * U16_SIZE
+    }
+
+    /// Returns the number of vertices.
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / self.components.max(1)
+    }
+
+    /// Returns the index of each corner of a triangle.
+    fn corner_indexes(&self, triangle: usize) -> [usize; 3] {
+        let corner = |corner: usize| {
+            if self.indexes.is_empty() {
+                triangle * 3 + corner
+            } else {
+                self.indexes[triangle * 3 + corner] as usize
+            }
+        };
+        [corner(0), corner(1), corner(2)]
+    }
+
+    /// Computes smooth vertex normals in local space, averaging the normals of the triangles
+    /// around each vertex weighted by their area. Vertices outside any triangle get a null
+    /// normal.
+    pub fn vertex_normals(&self) -> Vec<Vector3<f32>> {
+        let mut normals = vec![Vector3::zeros(); self.vertex_count()];
+        for triangle in 0..self.triangle_count() {
+            let corners = self.corner_indexes(triangle);
+            let positions = match (
+                self.get_position(corners[0]),
+                self.get_position(corners[1]),
+                self.get_position(corners[2]),
+            ) {
+                (Some(a), Some(b), Some(c)) => [a, b, c],
+                _ => continue,
+            };
+            // The cross product's length is twice the area, which weights the sum.
+            let normal = (positions[1] - positions[0]).cross(&(positions[2] - positions[0]));
+            for corner in &corners {
+                normals[*corner] += normal;
+            }
+        }
+        for normal in normals.iter_mut() {
+            *normal = normal.try_normalize(0.).unwrap_or_else(Vector3::zeros);
+        }
+        normals
+    }
+
+    /// Returns the position of a vertex, or `None` if it doesn't exist.
+    fn get_position(&self, index: usize) -> Option<Vector3<f32>> {
+        let start = index * self.components;
+        let position = self.positions.get(start..start + self.components)?;
+        let coordinate = |i: usize| position.get(i).cloned().unwrap_or(0.0);
+        Some(Vector3::new(coordinate(0), coordinate(1), coordinate(2)))
+    }
 }
 
 impl MeshData {
@@ -30,10 +155,26 @@ impl MeshData {
             id: id,
             buffers: Vec::new(),
             vertex_count: vertex_count,
-            lookup_done: false,
+            looked_up_materials: Vec::new(),
+            sub_meshes: Vec::new(),
+            bounds: None,
+            cpu_data: None,
+            bvh: None,
+            draw_mode: WebGlRenderingContext::TRIANGLES,
         }
     }
 
+    /// Sets the primitive the indexes are drawn as, e.g. `LINES`. Only triangles can be
+    /// picked precisely: keep the CPU data of other meshes unretained.
+    pub fn set_draw_mode(&mut self, draw_mode: u32) -> () {
+        self.draw_mode = draw_mode;
+    }
+
+    /// Returns the primitive the indexes are drawn as.
+    pub fn get_draw_mode(&self) -> u32 {
+        self.draw_mode
+    }
+
     /// Add a buffer to this `MeshData`
     pub fn push_buffer(&mut self, buffer: Buffer) -> () {
         self.buffers.push(buffer);
@@ -53,23 +194,223 @@ impl MeshData {
         None
     }
 
+    /// Returns the size of this `MeshData`'s `WebGlBuffer`s in bytes, counting buffers shared
+    /// by several attributes once.
+    pub fn get_gpu_byte_size(&self) -> usize {
+        self.buffers
+            .iter()
+            .enumerate()
+            .filter(|(index, buffer)| {
+                !self.buffers[..*index]
+                    .iter()
+                    .any(|previous| previous.shares_storage_with(buffer))
+            })
+            .map(|(_, buffer)| buffer.get_byte_size())
+            .sum()
+    }
+
     /// Returns the number of vertices for this `MeshData`'s Buffers.
     pub fn get_vertex_count(&self) -> i32 {
         self.vertex_count
     }
 
+    /// Splits this `MeshData` in sub-meshes, each drawn with its own material, from their
+    /// `(index offset, index count)`. An empty list draws the whole mesh at once.  
+    /// Fails if a range goes beyond the vertex count.
+    pub fn set_sub_meshes(&mut self, sub_meshes: Vec<(i32, i32)>) -> Result<(), W3DError> {
+        for (offset, count) in &sub_meshes {
+            if *offset < 0 || *count < 0 || offset + count > self.vertex_count {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::InvalidArgument,
+                    "Sub-mesh range goes beyond the mesh's vertex count.",
+                    &format!("{} + {} > {}", offset, count, self.vertex_count),
+                ));
+            }
+        }
+        self.sub_meshes = sub_meshes;
+        Ok(())
+    }
+
+    /// Returns the `(index offset, index count)` of each sub-mesh. Empty if the mesh is
+    /// not split.
+    pub fn get_sub_meshes(&self) -> &[(i32, i32)] {
+        &self.sub_meshes
+    }
+
+    /// Returns the `(index offset, index count)` to draw for a sub-mesh, or `None` if it
+    /// doesn't exist. Sub-mesh `0` of a mesh that is not split is the whole mesh.
+    pub fn get_sub_mesh_range(&self, sub_mesh: usize) -> Option<(i32, i32)> {
+        if self.sub_meshes.is_empty() {
+            if sub_mesh == 0 {
+                Some((0, self.vertex_count))
+            } else {
+                None
+            }
+        } else {
+            self.sub_meshes.get(sub_mesh).cloned()
+        }
+    }
+
+    /// Getter for `bounds`
+    pub fn get_bounds(&self) -> Option<BoundingSphere> {
+        self.bounds
+    }
+
+    /// Setter for `bounds`
+    pub fn set_bounds(&mut self, bounds: Option<BoundingSphere>) -> () {
+        self.bounds = bounds;
+    }
+
+    /// Setter for the CPU copy of the positions and indexes.
+    pub fn set_cpu_data(&mut self, cpu_data: Option<MeshCpuData>) -> () {
+        self.cpu_data = cpu_data;
+        self.bvh = None;
+    }
+
+    /// Getter for the CPU copy of the positions and indexes, if it was retained.
+    pub fn get_cpu_data(&self) -> Option<&MeshCpuData> {
+        self.cpu_data.as_ref()
+    }
+
+    /// Returns the number of triangles, or `None` if the CPU data was not retained.
+    pub fn triangle_count(&self) -> Option<usize> {
+        self.cpu_data.as_ref().map(MeshCpuData::triangle_count)
+    }
+
+    /// Iterates over the triangles in local space, or returns `None` if the CPU data was not
+    /// retained.
+    pub fn triangles<'a>(&'a self) -> Option<impl Iterator<Item = [Vector3<f32>; 3]> + 'a> {
+        self.cpu_data.as_ref().map(MeshCpuData::triangles)
+    }
+
+    /// Returns the hierarchy over the triangles in local space, building it on first call,
+    /// or `None` if the CPU data was not retained.
+    pub fn get_or_build_bvh(&mut self) -> Option<&TriangleBvh> {
+        if self.bvh.is_none() {
+            let triangles = self.triangles()?.collect();
+            self.bvh = Some(TriangleBvh::new(triangles));
+        }
+        self.bvh.as_ref()
+    }
+
+    /// Bakes ambient occlusion into an `a_ao` buffer holding one float per vertex, `1` for
+    /// a fully open vertex and `0` for a fully occluded one. Replaces the previous bake.
+    ///
+    /// `rays_per_vertex` rays are cast from each vertex over the hemisphere around its
+    /// smooth normal, spread evenly and weighted by the cosine of their angle with it, and
+    /// the triangles of the mesh hit closer than `max_distance` occlude the vertex. Rays
+    /// are tested against the BVH, which is built if needed.  
+    /// Fails if the CPU data was not retained, or if the buffer can't be uploaded.
+    pub fn bake_vertex_ao(
+        &mut self,
+        context: &WebGlRenderingContext,
+        rays_per_vertex: u32,
+        max_distance: f32,
+    ) -> Result<(), W3DError> {
+        if self.cpu_data.is_none() {
+            return Err(W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Ambient occlusion can't be baked without the mesh's CPU data.",
+                &self.id,
+            ));
+        }
+        self.get_or_build_bvh();
+        let (cpu_data, bvh) = match (&self.cpu_data, &self.bvh) {
+            (Some(cpu_data), Some(bvh)) => (cpu_data, bvh),
+            _ => return Ok(()),
+        };
+        let directions = hemisphere_directions(rays_per_vertex.max(1));
+        // Keeps rays from hitting the triangles they start from.
+        let bias = max_distance * 1e-3;
+        let occlusion: Vec<f32> = cpu_data
+            .vertex_normals()
+            .iter()
+            .enumerate()
+            .map(|(vertex, normal)| {
+                let position = match cpu_data.get_position(vertex) {
+                    Some(position) if *normal != Vector3::zeros() => position,
+                    _ => return 1.,
+                };
+                let tangent = normal.cross(&orthogonal_axis(normal)).normalize();
+                let bitangent = normal.cross(&tangent);
+                let origin = position + normal * bias;
+                let hits = directions
+                    .iter()
+                    .filter(|local| {
+                        let direction = tangent * local.x + bitangent * local.y + normal * local.z;
+                        bvh.intersect(&Ray::new(origin, direction))
+                            .map_or(false, |(_, hit)| hit.distance < max_distance)
+                    })
+                    .count();
+                1. - hits as f32 / directions.len() as f32
+            })
+            .collect();
+        let buffer = Buffer::from_f32_data_view(
+            context,
+            AO_BUFFER_NAME,
+            ShaderDataType::Single,
+            &occlusion,
+            None,
+        )?;
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.get_attribute_name() == AO_BUFFER_NAME)
+        {
+            self.buffers.remove(index).delete(context);
+        }
+        self.buffers.push(buffer);
+        // Materials look their attribute locations up again to find the new buffer.
+        self.looked_up_materials.clear();
+        Ok(())
+    }
+
     /// Getter for `id`
     pub fn get_id(&self) -> &str {
         &self.id
     }
 
+    /// Setter for the id, used when registering the mesh data under a custom id.
+    pub fn set_id(&mut self, id: String) -> () {
+        self.id = id;
+    }
+
+    /// Deletes the GPU buffers of this `MeshData`, which can't be drawn anymore afterwards.
+    pub fn delete_buffers(&self, context: &WebGlRenderingContext) -> () {
+        for buffer in &self.buffers {
+            buffer.delete(context);
+        }
+    }
+
+    /// Deletes the buffers and forgets them, keeping everything else, to free GPU memory
+    /// until `restore_buffers` is called. Nothing must be drawn with this mesh data meanwhile.
+    pub fn evict_buffers(&mut self, context: &WebGlRenderingContext) -> () {
+        self.delete_buffers(context);
+        self.buffers.clear();
+        self.looked_up_materials.clear();
+    }
+
+    /// Takes the buffers of `uploaded`, the same mesh data uploaded again, after
+    /// `evict_buffers`.
+    pub fn restore_buffers(&mut self, uploaded: MeshData) -> () {
+        self.buffers = uploaded.buffers;
+        self.looked_up_materials.clear();
+    }
+
+    /// Forgets that the locations have been looked up for the material `material_id`, so that
+    /// they are looked up again. Needed when that material is replaced.
+    pub fn forget_lookup(&mut self, material_id: &str) -> () {
+        self.looked_up_materials.retain(|id| id != material_id);
+    }
+
     /// Function to lookup the locations for this meshdata;
     pub fn lookup_locations(
         &mut self,
         context: &WebGlRenderingContext,
         material: Rc<RefCell<Material>>,
     ) -> () {
-        if self.lookup_done {
+        let material_id = material.borrow().get_id().to_owned();
+        if self.looked_up_materials.contains(&material_id) {
             return;
         }
         for buffer in &self.buffers {
@@ -77,6 +418,91 @@ impl MeshData {
                 .borrow_mut()
                 .register_new_attribute_location(context, buffer.get_attribute_name())
         }
-        self.lookup_done = true;
+        self.looked_up_materials.push(material_id);
+    }
+}
+
+/// Returns `count` unit directions over the hemisphere around `+Z`, on a Fibonacci spiral
+/// denser near the pole so that they are weighted by the cosine of their angle with it.
+fn hemisphere_directions(count: u32) -> Vec<Vector3<f32>> {
+    let golden_angle = PI * (3. - 5f32.sqrt());
+    (0..count)
+        .map(|index| {
+            let u = (index as f32 + 0.5) / count as f32;
+            let radius = u.sqrt();
+            let angle = golden_angle * index as f32;
+            Vector3::new(radius * angle.cos(), radius * angle.sin(), (1. - u).sqrt())
+        })
+        .collect()
+}
+
+/// Returns the unit axis least aligned with `vector`, to build a basis around it.
+fn orthogonal_axis(vector: &Vector3<f32>) -> Vector3<f32> {
+    if vector.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::fallback::cube_geometry;
+
+    fn cube_cpu_data() -> MeshCpuData {
+        let (positions, _, _, indexes) = cube_geometry();
+        MeshCpuData {
+            positions,
+            components: 3,
+            indexes,
+        }
+    }
+
+    #[test]
+    fn cube_has_twelve_outward_triangles() {
+        let cpu_data = cube_cpu_data();
+        assert_eq!(cpu_data.triangle_count(), 12);
+        let triangles: Vec<[Vector3<f32>; 3]> = cpu_data.triangles().collect();
+        assert_eq!(triangles.len(), 12);
+        assert_eq!(
+            triangles[0],
+            [
+                Vector3::new(0.5, -0.5, 0.5),
+                Vector3::new(0.5, -0.5, -0.5),
+                Vector3::new(0.5, 0.5, -0.5)
+            ]
+        );
+        assert_eq!(triangles[11][2], Vector3::new(0.5, 0.5, -0.5));
+        for [a, b, c] in &triangles {
+            assert!([a, b, c].iter().all(|vertex| vertex.amax() == 0.5));
+            let normal = (b - a).cross(&(c - a));
+            assert!(normal.dot(&(a + b + c)) > 0., "Triangles face outward");
+        }
+    }
+
+    #[test]
+    fn deindexed_cube_has_the_same_triangles() {
+        let cpu_data = cube_cpu_data();
+        let mut positions = Vec::new();
+        for index in &cpu_data.indexes {
+            let start = *index as usize * 3;
+            positions.extend_from_slice(&cpu_data.positions[start..start + 3]);
+        }
+        let deindexed = MeshCpuData {
+            positions,
+            components: 3,
+            indexes: Vec::new(),
+        };
+        assert_eq!(deindexed.triangle_count(), 12);
+        assert!(deindexed.triangles().eq(cpu_data.triangles()));
+    }
+
+    #[test]
+    fn triangles_with_missing_vertices_are_skipped() {
+        let mut cpu_data = cube_cpu_data();
+        cpu_data.indexes.extend_from_slice(&[0, 1, 99]);
+        assert_eq!(cpu_data.triangle_count(), 13);
+        assert_eq!(cpu_data.triangles().count(), 12);
     }
 }
@@ -10,22 +10,167 @@ mod mesh_data;
 
 mod light_repository;
 
-pub use buffer::Buffer;
-pub use light_repository::{LightConfiguration, LightRepository};
-pub use material::{Material, MaterialInstance};
-pub use mesh_data::MeshData;
-pub use uniform::{GlobalUniformLocations, Uniform, UniformValue};
-
-use crate::asset::AssetRegistry;
-use crate::component::{Camera, Transform};
+mod builtin_shaders;
+
+mod particle_renderer;
+
+mod sprite_renderer;
+
+mod blob_shadow_renderer;
+
+mod overlay_renderer;
+
+mod depth_prepass;
+
+mod frame_stats;
+
+mod decal;
+
+pub(crate) mod fallback;
+
+mod gl_state;
+
+mod debug_info;
+
+mod shader_contract;
+
+mod capabilities;
+
+mod scene_depth;
+
+mod render_target;
+
+mod outline_renderer;
+
+mod debug_line_renderer;
+
+mod texture_upload;
+
+mod canvas_size;
+
+mod material_description;
+
+mod helpers;
+
+mod global_uniforms;
+
+mod tone_map_pass;
+
+mod bloom_pass;
+
+mod dynamic_batch;
+
+pub use blob_shadow_renderer::BlobShadowRenderer;
+pub use bloom_pass::{BloomPass, BloomSettings};
+use buffer::U16_SIZE;
+pub use buffer::{AttributeData, Buffer, ComponentType};
+use canvas_size::CanvasSize;
+pub use canvas_size::ManualSize;
+pub use capabilities::Capabilities;
+pub use debug_info::{
+    describe_asset_registry, describe_batch_stats, describe_collected_assets,
+    describe_global_uniforms, describe_import, describe_missing_assets, get_material_debug_info,
+};
+pub use debug_line_renderer::DebugLineRenderer;
+use debug_line_renderer::{push_box_vertices, push_line_vertices};
+pub use depth_prepass::DepthPrepass;
+pub use dynamic_batch::DynamicBatch;
+pub use fallback::{
+    create_fallback_cube, create_fallback_material, create_fallback_texture, FALLBACK_MATERIAL_ID,
+    FALLBACK_MATERIAL_INSTANCE_ID, FALLBACK_MESH_DATA_ID, FALLBACK_TEXTURE_ID,
+    FALLBACK_TEXTURE_SIZE,
+};
+use frame_stats::count_triangles;
+pub use frame_stats::{BatchStats, FrameStats};
+use gl_state::get_object;
+pub use gl_state::GlStateGuard;
+pub use global_uniforms::{GlobalUniformValue, GlobalUniforms};
+pub use light_repository::{LightConfiguration, LightRepository, LightSelection};
+pub use material::{CompilationLogs, Material, MaterialInstance};
+pub use material_description::{
+    AlphaMode, BaseColor, Emissive, MaterialDescription, VertexAnimation,
+};
+pub use mesh_data::{MeshCpuData, MeshData};
+pub use outline_renderer::{OutlineRenderer, OutlineStyle};
+pub use overlay_renderer::OverlayRenderer;
+pub use particle_renderer::ParticleRenderer;
+pub use render_target::{choose_color_format, RenderTarget};
+pub use scene_depth::SceneDepth;
+use scene_depth::DEPTH_TEXTURE_EXTENSION;
+pub use shader_contract::{describe_light_configuration, get_shader_contract};
+pub use sprite_renderer::SpriteRenderer;
+pub use texture_upload::TextureUploadQueue;
+pub use tone_map_pass::ToneMapPass;
+pub use uniform::{CubeTexture, GlobalUniformLocations, TextureReference, Uniform, UniformValue};
+
+use crate::asset::{check_winding, transform_mesh_file, AssetRegistry, Winding, WindingReport};
+use crate::component::{
+    BlobShadow, Camera, FrameMatrices, Mesh, Overlay, ParticleEmitter, Sprite, Transform,
+    UniformOverrideValue, UniformOverrides,
+};
+use crate::error::{W3DError, W3DErrorKind};
 use crate::scene::FileType;
-use crate::utils::console_error;
+use crate::utils::constants::{
+    CAMERA_NEAR_FAR_NAME, DELTA_TIME_NAME, ENTITY_OPACITY_NAME, ENVIRONMENT_MAP_NAME,
+    ENVIRONMENT_MAP_TEXTURE_INDEX, SCENE_DEPTH_NAME, SCENE_DEPTH_TEXTURE_INDEX, TIME_NAME,
+    TIME_WRAPPED_NAME, TIME_WRAP_PERIOD, VIEWPORT_SIZE_NAME,
+};
+use crate::utils::{ColorFormat, ImportOptions, ToneMapping};
+use js_sys::Function;
+use nalgebra::{Isometry3, Matrix4, Vector2, Vector3, Vector4};
 use std::cell::RefCell;
-use std::collections::hash_map::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
-use web_sys::{HtmlCanvasElement, HtmlImageElement, WebGlRenderingContext};
+use web_sys::{
+    HtmlCanvasElement, HtmlImageElement, ImageBitmap, WebGlFramebuffer, WebGlRenderingContext,
+    WebGlTexture,
+};
+
+/// A mesh to draw: `(material instance id, transform, sub-mesh index, uniform overrides,
+/// entity opacity)`.
+pub type MeshToDraw<'a> = (
+    &'a usize,
+    &'a Transform,
+    usize,
+    Option<&'a UniformOverrides>,
+    f32,
+);
+
+/// Meshes to draw with a material, by mesh data id.
+pub type MeshBatches<'a> = BTreeMap<&'a usize, Vec<MeshToDraw<'a>>>;
+
+/// Meshes to draw, by material id then mesh data id. Ordered maps, so that the same meshes
+/// are always drawn in the same order, from one frame or run to the next.
+pub type SortedMeshes<'a> = BTreeMap<&'a usize, MeshBatches<'a>>;
+
+/// Point and spot lights to upload for each batch, by material id and mesh data id.
+pub type LightSelections = HashMap<(usize, usize), LightSelection>;
+
+/// ## RenderView
+///
+/// A point of view rendered instead of the main camera's, such as one eye of an XR headset,
+/// with its own matrices and the area of the render target it's drawn to.
+#[derive(Clone, Debug)]
+pub struct RenderView {
+    /// View matrix, from world space to the view's space
+    pub view: Matrix4<f32>,
 
-pub type SortedMeshes<'a> = HashMap<&'a usize, HashMap<&'a usize, Vec<(&'a usize, &'a Transform)>>>;
+    /// Projection matrix
+    pub projection: Matrix4<f32>,
+
+    /// Area of the render target to draw to: x, y, width and height, in pixels
+    pub viewport: [i32; 4],
+}
+
+/// Source of the drawing buffer size used by the `Renderer`.
+enum Viewport {
+    /// The renderer owns the canvas and resizes it to its display size, as tracked by the
+    /// `CanvasSize`.
+    Canvas(HtmlCanvasElement, CanvasSize),
+
+    /// The context belongs to the caller, who pushes the size with `set_canvas_size`.
+    Manual(ManualSize),
+}
 
 /// ## Renderer
 ///
@@ -39,14 +184,101 @@ pub struct Renderer {
     /// The current WebGlRenderingContext to render to.
     webgl_context: WebGlRenderingContext,
 
-    /// The target `HtmlCanvasElement`
-    canvas: HtmlCanvasElement,
+    /// How the size of the drawing buffer is determined
+    viewport: Viewport,
 
     /// Camera reference used for rendering.
     main_camera: Rc<RefCell<Camera>>,
 
     /// Asset registry instance for use with this renderer
     asset_registry: AssetRegistry,
+
+    /// Particle rendering state, created the first time particles are drawn.
+    /// Holds the error instead if the built-in particle material failed to compile.
+    particle_renderer: Option<Result<ParticleRenderer, W3DError>>,
+
+    /// Sprite rendering state, created the first time sprites are drawn.
+    /// Holds the error instead if the built-in sprite material failed to compile.
+    sprite_renderer: Option<Result<SpriteRenderer, W3DError>>,
+
+    /// Blob shadow rendering state, created the first time blob shadows are drawn.
+    /// Holds the error instead if the built-in blob shadow material failed to compile.
+    blob_shadow_renderer: Option<Result<BlobShadowRenderer, W3DError>>,
+
+    /// Overlay rendering state, created the first time overlays are drawn.
+    /// Holds the error instead if the built-in overlay material failed to compile.
+    overlay_renderer: Option<Result<OverlayRenderer, W3DError>>,
+
+    /// Outline rendering state, created the first time outlines are drawn.
+    outline_renderer: Option<OutlineRenderer>,
+
+    /// Debug line rendering state, created the first time debug lines are drawn.
+    /// Holds the error instead if the built-in debug line material failed to compile.
+    debug_line_renderer: Option<Result<DebugLineRenderer, W3DError>>,
+
+    /// Vertices of the debug lines queued for the next frame.
+    debug_lines: Vec<f32>,
+
+    /// Textures decoded asynchronously, waiting to be uploaded.
+    texture_uploads: TextureUploadQueue,
+
+    /// How outlines around selected meshes look.
+    outline_style: OutlineStyle,
+
+    /// Depth pre-pass state, `Some` if the pre-pass is enabled.
+    depth_prepass: Option<DepthPrepass>,
+
+    /// Statistics about the last rendered frame.
+    frame_stats: FrameStats,
+
+    /// Counters of the main pass batches of the last frame, by material and mesh data index.
+    batch_stats: HashMap<(usize, usize), BatchStats>,
+
+    /// Cube map reflected by materials supporting environment mapping, if any.
+    environment_map: Option<Rc<CubeTexture>>,
+
+    /// Seconds elapsed since the scene's first update, and since the previous one.
+    time: (f64, f32),
+
+    /// Limits and extensions of the context, queried at creation.
+    capabilities: Capabilities,
+
+    /// Matrices of the view being rendered, computed by `begin_view`.
+    frame_matrices: FrameMatrices,
+
+    /// Offscreen depth of the opaque meshes, `Some` if soft particles are enabled.
+    scene_depth: Option<SceneDepth>,
+
+    /// How far the frame is between the last two fixed steps, `None` outside of fixed
+    /// timestep mode.
+    interpolation: Option<f32>,
+
+    /// Views rendered instead of the main camera's, empty to render the main camera's.
+    render_views: Vec<RenderView>,
+
+    /// Framebuffer the render views are drawn to, `None` for the canvas.
+    render_target: Option<WebGlFramebuffer>,
+
+    /// Offscreen targets created with `create_render_target`, by the id of their texture.
+    render_targets: HashMap<String, RenderTarget>,
+
+    /// Id of the render target the main camera renders to instead of the canvas, if any.
+    output_target: Option<String>,
+
+    /// HDR target and tone mapping pass, `Some` if HDR rendering is enabled.
+    tone_map_pass: Option<ToneMapPass>,
+
+    /// Framebuffer and size the main view is tone mapped to, until the pass is drawn.
+    tone_map_destination: Option<(Option<WebGlFramebuffer>, (u32, u32))>,
+
+    /// Factor applied to the device pixel ratio when sizing the canvas.
+    resolution_scale: f32,
+
+    /// Custom uniforms shared by every material.
+    global_uniforms: GlobalUniforms,
+
+    /// Whether `render_objects` clears the color and depth buffers first.
+    auto_clear: bool,
 }
 
 impl Renderer {
@@ -57,11 +289,62 @@ impl Renderer {
         canvas: HtmlCanvasElement,
         context: WebGlRenderingContext,
     ) -> Renderer {
+        let canvas_size = CanvasSize::new(&canvas);
+        Renderer::with_viewport(camera, context, Viewport::Canvas(canvas, canvas_size))
+    }
+
+    /// Constructor for a context owned by the caller, possibly shared with other renderers.  
+    /// The canvas is never resized: the size of the area to render to is given here and
+    /// then pushed with `set_canvas_size`. The WebGL state is saved before rendering and
+    /// restored afterwards.
+    pub fn with_external_context(
+        camera: Camera,
+        context: WebGlRenderingContext,
+        width: u32,
+        height: u32,
+    ) -> Renderer {
+        let viewport = Viewport::Manual(ManualSize::new(width, height));
+        Renderer::with_viewport(camera, context, viewport)
+    }
+
+    fn with_viewport(
+        camera: Camera,
+        context: WebGlRenderingContext,
+        viewport: Viewport,
+    ) -> Renderer {
+        let capabilities = Capabilities::query(&context);
         Renderer {
             webgl_context: context,
-            canvas: canvas,
+            viewport: viewport,
             main_camera: Rc::new(RefCell::new(camera)),
             asset_registry: AssetRegistry::new(),
+            particle_renderer: None,
+            sprite_renderer: None,
+            blob_shadow_renderer: None,
+            overlay_renderer: None,
+            outline_renderer: None,
+            debug_line_renderer: None,
+            debug_lines: Vec::new(),
+            texture_uploads: Default::default(),
+            outline_style: Default::default(),
+            depth_prepass: None,
+            frame_stats: Default::default(),
+            batch_stats: HashMap::new(),
+            environment_map: None,
+            time: (0., 0.),
+            capabilities: capabilities,
+            frame_matrices: Default::default(),
+            scene_depth: None,
+            interpolation: None,
+            render_views: Vec::new(),
+            render_target: None,
+            render_targets: HashMap::new(),
+            output_target: None,
+            tone_map_pass: None,
+            tone_map_destination: None,
+            resolution_scale: 1.,
+            global_uniforms: Default::default(),
+            auto_clear: true,
         }
     }
 
@@ -70,86 +353,1125 @@ impl Renderer {
     }
 
     /// Resizes the canvas internal size to match the display resolution and ratio.  
-    /// Also updates the WebGl Viewport to match.
+    /// Also updates the WebGl Viewport to match.  
+    /// The display size is the one last recorded by the `CanvasSize`, so the layout is not
+    /// read every frame: call this once per frame, at its start.  
+    /// Returns the new aspect ratio if the canvas has been resized. A canvas or area of zero
+    /// width or height is never applied, so the aspect ratio is always positive.
+    ///
+    /// With an external context, the canvas is left alone and only sizes pushed with
+    /// `set_canvas_size` are applied.
     ///
     /// ⚠️ might be removed in favor of all-JS version.
-    pub fn resize_canvas(&mut self) -> () {
-        let pixel_ratio = web_sys::window().unwrap().device_pixel_ratio() as f32;
-        let display_width = self.canvas.client_width() as u32;
-        let display_height = self.canvas.client_height() as u32;
-        let resolution_x = (display_width as f32 * pixel_ratio) as u32;
-        let resolution_y = (display_height as f32 * pixel_ratio) as u32;
-        
-        if self.canvas.width() != resolution_x || self.canvas.height() != resolution_y {
-            self.canvas.set_width(resolution_x);
-            self.canvas.set_height(resolution_y);
-            let ratio = display_width as f32 / display_height as f32;
-            self.main_camera.borrow_mut().set_aspect_ratio(ratio);
-            self.webgl_context
-                .viewport(0, 0, resolution_x as i32, resolution_y as i32);
+    pub fn resize_canvas(&mut self) -> Option<f32> {
+        match &mut self.viewport {
+            Viewport::Canvas(canvas, canvas_size) => {
+                let pixel_ratio =
+                    web_sys::window().unwrap().device_pixel_ratio() as f32 * self.resolution_scale;
+                let (display_width, display_height) = canvas_size.update(canvas);
+                // A hidden canvas keeps its last size until it is displayed again.
+                if display_width == 0 || display_height == 0 {
+                    return None;
+                }
+                let resolution_x = (display_width as f32 * pixel_ratio) as u32;
+                let resolution_y = (display_height as f32 * pixel_ratio) as u32;
+
+                if canvas.width() != resolution_x || canvas.height() != resolution_y {
+                    canvas.set_width(resolution_x);
+                    canvas.set_height(resolution_y);
+                    let ratio = display_width as f32 / display_height as f32;
+                    self.webgl_context
+                        .viewport(0, 0, resolution_x as i32, resolution_y as i32);
+                    Some(ratio)
+                } else {
+                    None
+                }
+            }
+            Viewport::Manual(manual_size) => manual_size.update(),
+        }
+    }
+
+    /// Sets the size of the area to render to, in device pixels, when using an external
+    /// context. It's applied at the next `resize_canvas`.  
+    /// Does nothing if the renderer owns its canvas, since it then follows its display size.
+    pub fn set_canvas_size(&mut self, new_width: u32, new_height: u32) -> () {
+        match &mut self.viewport {
+            Viewport::Manual(manual_size) => manual_size.set(new_width, new_height),
+            Viewport::Canvas(..) => {
+                warn_once!("The canvas size can only be set when using an external context.")
+            }
+        }
+    }
+
+    /// Returns `true` if the area to render to has no pixels, e.g. when the canvas is hidden
+    /// with `display: none`. Frames are then simulated but not rendered.
+    pub fn is_viewport_empty(&self) -> bool {
+        match &self.viewport {
+            Viewport::Canvas(_, canvas_size) => {
+                let (width, height) = canvas_size.get_display_size();
+                width == 0 || height == 0
+            }
+            Viewport::Manual(manual_size) => manual_size.is_empty(),
+        }
+    }
+
+    /// Returns the size of the drawing area, in device pixels.
+    pub fn get_drawing_buffer_size(&self) -> (u32, u32) {
+        match &self.viewport {
+            Viewport::Canvas(canvas, _) => (canvas.width(), canvas.height()),
+            Viewport::Manual(manual_size) => manual_size.get_size(),
+        }
+    }
+
+    /// Saves the WebGL state if the context is shared with other renderers.  
+    /// The state is restored when the returned guard is dropped, so it should be kept
+    /// alive while rendering the frame.
+    pub fn save_gl_state(&self) -> Option<GlStateGuard> {
+        match &self.viewport {
+            Viewport::Canvas(..) => None,
+            Viewport::Manual(manual_size) => {
+                let (width, height) = manual_size.get_size();
+                let guard = GlStateGuard::save(&self.webgl_context);
+                self.webgl_context
+                    .viewport(0, 0, width as i32, height as i32);
+                Some(guard)
+            }
+        }
+    }
+
+    /// Renders the next frames from each of `views`, to `target` or to the canvas if `None`,
+    /// instead of the main camera's. An empty list renders the main camera again.  
+    /// Soft particles assume a single view covering the drawing buffer, and are wrong in
+    /// render views.
+    pub fn set_render_views(
+        &mut self,
+        target: Option<WebGlFramebuffer>,
+        views: Vec<RenderView>,
+    ) -> () {
+        self.render_target = target;
+        self.render_views = views;
+    }
+
+    /// Getter for the views rendered instead of the main camera's.
+    pub fn get_render_views(&self) -> &[RenderView] {
+        &self.render_views
+    }
+
+    /// Returns the number of views each frame is rendered from, `1` for the main camera.
+    pub fn get_view_count(&self) -> usize {
+        self.render_views.len().max(1)
+    }
+
+    /// Resets the statistics of the frame. Must be called before rendering its views.
+    pub fn begin_frame(&mut self) -> () {
+        self.frame_stats = Default::default();
+        self.batch_stats.clear();
+        self.global_uniforms.next_frame();
+        let (evicted_assets, reconstructed_assets) = self.asset_registry.take_memory_counters();
+        self.frame_stats.evicted_assets = evicted_assets;
+        self.frame_stats.reconstructed_assets = reconstructed_assets;
+    }
+
+    /// Prepares rendering the view at `index`: computes the matrices of the frame, and for
+    /// render views, binds their target and restricts drawing and clearing to their area.
+    /// The main camera's view binds the output target instead, if one is set.  
+    /// With HDR rendering, the main camera's view binds the HDR target, and the output target
+    /// or the canvas is only drawn to by the tone mapping pass.
+    pub fn begin_view(&mut self, index: usize) -> () {
+        let camera_matrices = self.main_camera.borrow().get_frame_matrices();
+        self.frame_matrices = match self.render_views.get(index) {
+            None => {
+                let output_target = self
+                    .output_target
+                    .as_ref()
+                    .and_then(|id| self.render_targets.get(id));
+                let mut size = self.get_drawing_buffer_size();
+                if let Some(target) = output_target {
+                    size = target.get_size();
+                    let context = &self.webgl_context;
+                    context.bind_framebuffer(
+                        WebGlRenderingContext::FRAMEBUFFER,
+                        Some(target.get_framebuffer()),
+                    );
+                    context.viewport(0, 0, size.0 as i32, size.1 as i32);
+                }
+                if let Some(tone_map_pass) = &mut self.tone_map_pass {
+                    let context = &self.webgl_context;
+                    let destination: Option<WebGlFramebuffer> =
+                        get_object(context, WebGlRenderingContext::FRAMEBUFFER_BINDING);
+                    let bound = tone_map_pass.bind_target(
+                        context,
+                        &self.capabilities.renderable_formats,
+                        size,
+                    );
+                    match bound {
+                        Ok(()) => self.tone_map_destination = Some((destination, size)),
+                        Err(error) => error_throttled!(5000, "{}", error),
+                    }
+                }
+                camera_matrices
+            }
+            Some(render_view) => {
+                let context = &self.webgl_context;
+                let [x, y, width, height] = render_view.viewport;
+                context.bind_framebuffer(
+                    WebGlRenderingContext::FRAMEBUFFER,
+                    self.render_target.as_ref(),
+                );
+                context.viewport(x, y, width, height);
+                context.enable(WebGlRenderingContext::SCISSOR_TEST);
+                context.scissor(x, y, width, height);
+                let eye_position = render_view
+                    .view
+                    .try_inverse()
+                    .map_or_else(Vector3::zeros, |inverse| {
+                        Vector3::new(inverse[(0, 3)], inverse[(1, 3)], inverse[(2, 3)])
+                    });
+                FrameMatrices {
+                    view: render_view.view,
+                    projection: render_view.projection,
+                    view_projection: render_view.projection * render_view.view,
+                    eye_position: eye_position,
+                    ..camera_matrices
+                }
+            }
+        };
+    }
+
+    /// Ends the frame: draws the tone mapping pass if it's still pending, empties the debug
+    /// line queue, and after render views or an output target, binds the canvas again with a
+    /// viewport covering it.
+    pub fn end_frame(&mut self) -> () {
+        self.render_tone_map_pass();
+        self.debug_lines.clear();
+        if !self.render_views.is_empty() || self.output_target.is_some() {
+            let (width, height) = self.get_drawing_buffer_size();
+            let context = &self.webgl_context;
+            context.disable(WebGlRenderingContext::SCISSOR_TEST);
+            context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, None);
+            context.viewport(0, 0, width as i32, height as i32);
         }
     }
 
     /// Renders all the objects registered in the Mesh Repository and prints them to the Canvas.component
     ///
-    /// The opaque objects will be rendered before the transparent ones (ordered by depth), and every object will be sorted
-    /// by `Material` id to optimize performance.
-    // ⭕ TODO handle semi-transparent objects separately
-    pub fn render_objects(&self, sorted_meshes: SortedMeshes, light_repository: &LightRepository) {
-        self.webgl_context.clear_color(0., 0., 0., 0.);
-        self.webgl_context.clear(
-            WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT,
-        );
+    /// The opaque objects will be rendered before the transparent ones, and the opaque objects
+    /// will be sorted by `Material` id to optimize performance. Transparent objects and
+    /// `sprites` are drawn back to front by the view depth of their origin instead.
+    ///
+    /// If the depth pre-pass is enabled, opaque objects are first rendered to the depth buffer
+    /// only, then shaded with an `EQUAL` depth test and no depth writes.  
+    /// Decal materials are drawn after the other opaque ones, with their polygon offset, and
+    /// transparent materials last, alpha blended without depth writes. Materials are
+    /// classified every frame, so toggling transparency takes effect on the next one.  
+    /// `faded_meshes` are the meshes of entities with an `Opacity` below `1`: they are left out
+    /// of the depth pre-pass and sorted with the transparent materials, alpha blended without
+    /// depth writes whatever their materials.
+    ///
+    /// Each `(material, mesh data)` batch only receives the point and spot lights selected
+    /// for it in `light_selections`; directional, hemisphere and ambient lights always apply.
+    pub fn render_objects(
+        &mut self,
+        sorted_meshes: SortedMeshes,
+        faded_meshes: SortedMeshes,
+        sprites: &[(&Sprite, &Transform)],
+        light_repository: &LightRepository,
+        light_selections: &LightSelections,
+    ) {
+        if self.auto_clear {
+            self.clear();
+        }
         self.webgl_context.enable(WebGlRenderingContext::CULL_FACE);
         self.webgl_context.enable(WebGlRenderingContext::DEPTH_TEST);
-        for (material_id, mesh_hash_map) in sorted_meshes {
-            self.draw_meshes_using_material(
-                material_id.to_owned(),
-                mesh_hash_map,
+        let drawing_buffer_size = self.get_drawing_buffer_size();
+        if let Some(scene_depth) = &mut self.scene_depth {
+            let context = &self.webgl_context;
+            let frame_matrices = &self.frame_matrices;
+            let result = scene_depth.render(
+                context,
+                &self.asset_registry,
+                &sorted_meshes,
+                drawing_buffer_size,
+                self.interpolation,
+                |material| {
+                    set_camera_uniforms(context, frame_matrices, material).ok();
+                },
+            );
+            match result {
+                Ok(draw_calls) => self.frame_stats.draw_calls += draw_calls,
+                Err(error) => error_throttled!(5000, "Soft particles can't be rendered: {}", error),
+            }
+        }
+        if let Some(depth_prepass) = &mut self.depth_prepass {
+            let start = crate::utils::now();
+            let context = &self.webgl_context;
+            let frame_matrices = &self.frame_matrices;
+            self.frame_stats.draw_calls += depth_prepass.render(
+                context,
+                &self.asset_registry,
+                &sorted_meshes,
+                self.interpolation,
+                |material| {
+                    set_camera_uniforms(context, frame_matrices, material).ok();
+                },
+            );
+            self.frame_stats.depth_prepass_time = crate::utils::now() - start;
+        }
+        let start = crate::utils::now();
+        let prepass_done = self.depth_prepass.is_some();
+        let asset_registry = &self.asset_registry;
+        let pass_of = |material_id: &usize| {
+            asset_registry
+                .get_material_with_index(*material_id)
+                .map(|material| {
+                    let material = material.borrow();
+                    (
+                        material.is_blended() && !material.is_decal(),
+                        material.is_decal(),
+                    )
+                })
+                .unwrap_or((false, false))
+        };
+        let (opaque_batches, blended_batches) = order_batches(sorted_meshes, faded_meshes, pass_of);
+        let view = self.frame_matrices.view;
+        let interpolation = self.interpolation;
+        let sprite_depths: Vec<f32> = sprites
+            .iter()
+            .map(|(_, transform)| (view * transform.get_world_matrix() * Vector4::w()).z)
+            .collect();
+        let blended_runs = sort_back_to_front(blended_batches, &sprite_depths, |transform| {
+            (view * transform.get_rendered_world_matrix(interpolation) * Vector4::w()).z
+        });
+        for batch in opaque_batches {
+            self.draw_batch(
+                batch,
+                false,
+                prepass_done,
                 light_repository,
+                light_selections,
             );
         }
+        for run in blended_runs {
+            match run {
+                BlendedRun::Meshes(batch, faded) => self.draw_batch(
+                    batch,
+                    faded,
+                    prepass_done,
+                    light_repository,
+                    light_selections,
+                ),
+                BlendedRun::Sprites(indexes) => {
+                    let run: Vec<(&Sprite, &Transform)> =
+                        indexes.iter().map(|index| sprites[*index]).collect();
+                    self.render_sprites(&run);
+                }
+            }
+        }
+        self.webgl_context.depth_func(WebGlRenderingContext::LESS);
+        self.webgl_context.depth_mask(true);
+        self.webgl_context
+            .disable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+        self.webgl_context.polygon_offset(0.0, 0.0);
+        self.webgl_context.disable(WebGlRenderingContext::BLEND);
+        self.frame_stats.main_pass_time = crate::utils::now() - start;
+    }
+
+    /// Draws a batch of meshes sharing a material, and adds its statistics to the frame's.
+    fn draw_batch(
+        &mut self,
+        (material_id, mesh_batches): (&usize, MeshBatches),
+        faded: bool,
+        prepass_done: bool,
+        light_repository: &LightRepository,
+        light_selections: &LightSelections,
+    ) -> () {
+        let batch_stats = self.draw_meshes_using_material(
+            material_id.to_owned(),
+            mesh_batches,
+            light_repository,
+            light_selections,
+            prepass_done && !faded,
+            faded,
+        );
+        for (mesh_data_id, stats) in batch_stats {
+            self.frame_stats.draw_calls += stats.draw_calls;
+            self.frame_stats.triangles += stats.triangles;
+            let total = self
+                .batch_stats
+                .entry((*material_id, mesh_data_id))
+                .or_default();
+            total.draw_calls += stats.draw_calls;
+            total.triangles += stats.triangles;
+        }
+    }
+
+    /// Enables or disables the depth pre-pass for opaque objects.  
+    /// Useful for scenes with a lot of overdraw and expensive fragment shaders; check the
+    /// `FrameStats` to know if it's a win.
+    pub fn set_depth_prepass(&mut self, enabled: bool) -> () {
+        match (enabled, &self.depth_prepass) {
+            (true, None) => self.depth_prepass = Some(DepthPrepass::new()),
+            (false, Some(_)) => self.depth_prepass = None,
+            _ => {}
+        }
+    }
+
+    /// Sets whether `render_objects` clears the color and depth buffers before drawing.
+    /// Hosts sharing their context may disable it to render the scene over their own drawing.
+    pub fn set_auto_clear(&mut self, auto_clear: bool) -> () {
+        self.auto_clear = auto_clear;
+    }
+
+    /// Clears the color and depth buffers of the area the scene renders to.  
+    /// With an external context, the clear is restricted to the scene's area rather than the
+    /// whole framebuffer; render views already restrict it to their own area.
+    fn clear(&self) -> () {
+        let context = &self.webgl_context;
+        let area = match &self.viewport {
+            Viewport::Manual(manual_size) if self.render_views.is_empty() => {
+                let (width, height) = manual_size.get_size();
+                Some((width as i32, height as i32))
+            }
+            _ => None,
+        };
+        if let Some((width, height)) = area {
+            context.enable(WebGlRenderingContext::SCISSOR_TEST);
+            context.scissor(0, 0, width, height);
+        }
+        context.clear_color(0., 0., 0., 0.);
+        context.clear(
+            WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT,
+        );
+        if area.is_some() {
+            context.disable(WebGlRenderingContext::SCISSOR_TEST);
+        }
+    }
+
+    /// Returns `true` if the depth pre-pass is enabled.
+    pub fn has_depth_prepass(&self) -> bool {
+        self.depth_prepass.is_some()
+    }
+
+    /// Sets the factor applied to the device pixel ratio when sizing the canvas, e.g. `0.5`
+    /// to render at half the display resolution on slow devices. It's applied at the next
+    /// `resize_canvas`.  
+    /// Has no effect with an external context, whose size is given in device pixels.
+    pub fn set_resolution_scale(&mut self, scale: f32) -> () {
+        self.resolution_scale = scale;
+    }
+
+    /// Returns the factor applied to the device pixel ratio when sizing the canvas.
+    pub fn get_resolution_scale(&self) -> f32 {
+        self.resolution_scale
+    }
+
+    /// Enables or disables soft particles: the depth of the opaque meshes is rendered to a
+    /// texture before the main pass, and given to the built-in particles and to the
+    /// materials declaring `USE_SOFT_PARTICLES` so that they fade out near intersections.  
+    /// Fails if the context doesn't support depth textures, in which case they stay disabled.
+    pub fn set_soft_particles(&mut self, enabled: bool) -> Result<(), W3DError> {
+        match (enabled, &self.scene_depth) {
+            (true, None) => {
+                if !self.capabilities.has_extension(DEPTH_TEXTURE_EXTENSION) {
+                    return Err(W3DError::with_source(
+                        W3DErrorKind::GlResource,
+                        "Soft particles need depth textures, which the context doesn't support.",
+                        DEPTH_TEXTURE_EXTENSION,
+                    ));
+                }
+                self.scene_depth = Some(SceneDepth::new(&self.webgl_context)?);
+            }
+            (false, Some(scene_depth)) => {
+                scene_depth.delete(&self.webgl_context);
+                self.scene_depth = None;
+            }
+            _ => return Ok(()),
+        }
+        // The built-in particle material is compiled again for the new variant.
+        self.particle_renderer = None;
+        Ok(())
+    }
+
+    /// Returns `true` if soft particles are enabled.
+    pub fn has_soft_particles(&self) -> bool {
+        self.scene_depth.is_some()
+    }
+
+    /// Enables or disables HDR rendering: the main camera's view is drawn to an `Rgba16F`
+    /// target, or `Rgba8` if half floats aren't renderable, then converted to the output
+    /// target or the canvas by a tone mapping pass applying the camera's exposure and
+    /// operator. Materials must be compiled with `LightConfiguration::hdr` meanwhile, so that
+    /// `tone_map` leaves their colors as is. Render views are drawn without it. Disabling it
+    /// disables bloom too.  
+    /// Returns the format of the HDR target, or `None` once disabled. Fails if the target or
+    /// the pass could not be created, in which case HDR rendering stays disabled.
+    pub fn set_hdr(&mut self, enabled: bool) -> Result<Option<ColorFormat>, W3DError> {
+        match (enabled, &mut self.tone_map_pass) {
+            (true, None) => {
+                let tone_map_pass = ToneMapPass::new(
+                    &self.webgl_context,
+                    &self.capabilities.renderable_formats,
+                    self.get_drawing_buffer_size(),
+                    self.main_camera.borrow().get_tone_mapping(),
+                )?;
+                self.tone_map_pass = Some(tone_map_pass);
+            }
+            (false, Some(tone_map_pass)) => {
+                tone_map_pass.delete(&self.webgl_context);
+                self.tone_map_pass = None;
+                self.tone_map_destination = None;
+            }
+            _ => {}
+        }
+        Ok(self.get_hdr_format())
+    }
+
+    /// Enables bloom with `settings`, or disables it if `None`: the colors of the HDR target
+    /// above the threshold are blurred and added to the scene by the tone mapping pass.  
+    /// Fails if HDR rendering is disabled, or if the bloom chain can't be created, e.g.
+    /// because half floats aren't renderable. Bloom then stays disabled.
+    pub fn set_bloom(&mut self, settings: Option<BloomSettings>) -> Result<(), W3DError> {
+        match &mut self.tone_map_pass {
+            Some(tone_map_pass) => tone_map_pass.set_bloom(
+                &self.webgl_context,
+                &self.capabilities.renderable_formats,
+                settings,
+            ),
+            None if settings.is_none() => Ok(()),
+            None => Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "Bloom needs HDR rendering to be enabled.",
+            )),
+        }
+    }
+
+    /// Returns the bloom settings, `None` if bloom is disabled.
+    pub fn get_bloom(&self) -> Option<BloomSettings> {
+        self.tone_map_pass
+            .as_ref()
+            .and_then(|tone_map_pass| tone_map_pass.get_bloom())
+    }
+
+    /// Returns `true` if HDR rendering is enabled.
+    pub fn has_hdr(&self) -> bool {
+        self.tone_map_pass.is_some()
+    }
+
+    /// Returns the format of the HDR target, `None` if HDR rendering is disabled.
+    pub fn get_hdr_format(&self) -> Option<ColorFormat> {
+        self.tone_map_pass
+            .as_ref()
+            .map(|tone_map_pass| tone_map_pass.get_format())
+    }
+
+    /// Creates an offscreen render target of `width` by `height` pixels, whose color texture
+    /// is registered under `id`, replacing the target registered under it if any.
+    /// `format` falls back to `Rgba8` if the device can't render to it: see
+    /// `RenderTarget::new`. Returns the format actually used.
+    pub fn create_render_target(
+        &mut self,
+        id: String,
+        width: u32,
+        height: u32,
+        format: ColorFormat,
+    ) -> Result<ColorFormat, W3DError> {
+        let max_size = self.capabilities.max_texture_size;
+        if width == 0 || height == 0 || width > max_size || height > max_size {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Render targets must be between 1 and the maximum texture size wide and high.",
+                &format!("{}x{}, maximum {}", width, height, max_size),
+            ));
+        }
+        let target = RenderTarget::new(
+            &self.webgl_context,
+            &self.capabilities.renderable_formats,
+            (width, height),
+            format,
+        )?;
+        self.delete_render_target(&id);
+        self.asset_registry
+            .add_texture(id.clone(), target.get_texture(), (width, height));
+        let format = target.get_format();
+        self.render_targets.insert(id, target);
+        Ok(format)
     }
 
+    /// Deletes the render target whose texture is registered under `id`, and unregisters
+    /// the texture. The canvas is rendered to again if it was the output target.
+    /// Returns `false` if there is none.
+    pub fn delete_render_target(&mut self, id: &str) -> bool {
+        match self.render_targets.remove(id) {
+            Some(target) => {
+                if self.output_target.as_ref().map(String::as_str) == Some(id) {
+                    self.output_target = None;
+                }
+                target.delete(&self.webgl_context);
+                self.asset_registry.remove_texture(&self.webgl_context, id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Getter for the render target whose texture is registered under `id`.
+    pub fn get_render_target(&self, id: &str) -> Option<&RenderTarget> {
+        self.render_targets.get(id)
+    }
+
+    /// Makes the main camera render to the render target whose texture is registered under
+    /// `id` instead of the canvas, or to the canvas again if `None`. Render views are still
+    /// drawn to their own target.  
+    /// Fails if there is no such render target.
+    pub fn set_output_target(&mut self, id: Option<String>) -> Result<(), W3DError> {
+        if let Some(id) = &id {
+            if !self.render_targets.contains_key(id) {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Render target could not be found.",
+                    id,
+                ));
+            }
+        }
+        self.output_target = id;
+        Ok(())
+    }
+
+    /// Getter for the id of the render target the main camera renders to, `None` for the
+    /// canvas.
+    pub fn get_output_target(&self) -> Option<&str> {
+        self.output_target.as_ref().map(String::as_str)
+    }
+
+    /// Getter for the custom uniforms shared by every material.
+    pub fn get_global_uniforms(&self) -> &GlobalUniforms {
+        &self.global_uniforms
+    }
+
+    /// Mutable getter for the custom uniforms shared by every material.
+    pub fn get_global_uniforms_mut(&mut self) -> &mut GlobalUniforms {
+        &mut self.global_uniforms
+    }
+
+    /// Sets the cube map reflected by materials supporting environment mapping.
+    pub fn set_environment_map(&mut self, environment_map: Option<Rc<CubeTexture>>) -> () {
+        self.environment_map = environment_map;
+    }
+
+    /// Draws a mesh to a single pixel with color writes disabled, so that the driver finishes
+    /// preparing its program before the mesh is actually displayed.  
+    /// The mesh's material must have been compiled.
+    pub fn warm_up_draw(&self, mesh: &Mesh, transform: &Transform) -> () {
+        let context = &self.webgl_context;
+        context.enable(WebGlRenderingContext::DEPTH_TEST);
+        context.enable(WebGlRenderingContext::SCISSOR_TEST);
+        context.scissor(0, 0, 1, 1);
+        context.color_mask(false, false, false, false);
+        let light_repository = LightRepository::default();
+        for (sub_mesh, (material_instance_id, material_id)) in
+            mesh.get_sub_mesh_materials().iter().enumerate()
+        {
+            let mut mesh_batches = BTreeMap::new();
+            mesh_batches.insert(
+                mesh.get_mesh_data_id(),
+                vec![(material_instance_id, transform, sub_mesh, None, 1.)],
+            );
+            self.draw_meshes_using_material(
+                *material_id,
+                mesh_batches,
+                &light_repository,
+                &HashMap::new(),
+                false,
+                false,
+            );
+        }
+        context.color_mask(true, true, true, true);
+        context.disable(WebGlRenderingContext::SCISSOR_TEST);
+        context.depth_func(WebGlRenderingContext::LESS);
+        context.depth_mask(true);
+        context.disable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+        context.disable(WebGlRenderingContext::BLEND);
+    }
+
+    /// Sets the offset applied on top of the camera's view, from the active camera entity.
+    pub fn set_camera_shake_offset(&mut self, offset: &Isometry3<f32>) -> () {
+        self.main_camera.borrow_mut().set_shake_offset(offset);
+    }
+
+    /// Sets the aspect ratio of the camera's projection, from the active camera entity.
+    pub fn set_camera_aspect_ratio(&mut self, aspect_ratio: f32) -> () {
+        self.main_camera.borrow_mut().set_aspect_ratio(aspect_ratio);
+    }
+
+    /// Sets the exposure uploaded to the `u_exposure` uniform, from the active camera entity.
+    pub fn set_camera_exposure(&mut self, exposure: f32) -> () {
+        self.main_camera.borrow_mut().set_exposure(exposure);
+    }
+
+    /// Sets the operator of the tone mapping pass, from the active camera entity.
+    pub fn set_camera_tone_mapping(&mut self, tone_mapping: ToneMapping) -> () {
+        self.main_camera.borrow_mut().set_tone_mapping(tone_mapping);
+    }
+
+    /// Sets the time uploaded to the `u_time`, `u_delta_time` and `u_time_wrapped` uniforms of
+    /// the materials declaring them, in seconds. Called before rendering every frame.
+    pub fn set_time(&mut self, elapsed: f64, delta: f32) -> () {
+        self.time = (elapsed, delta);
+    }
+
+    /// Sets how far the frame is between the last two fixed steps, to interpolate the world
+    /// matrices of meshes with, or `None` to render the current ones. Called before rendering
+    /// every frame.
+    pub fn set_interpolation(&mut self, interpolation: Option<f32>) -> () {
+        self.interpolation = interpolation;
+    }
+
+    /// Returns the statistics about the last rendered frame.
+    pub fn get_frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    /// Returns the counters of the main pass batches of the last frame, by material and
+    /// mesh data index.
+    pub fn get_batch_stats(&self) -> &HashMap<(usize, usize), BatchStats> {
+        &self.batch_stats
+    }
+
+    /// Returns the limits and extensions of the context.
+    pub fn get_capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Sets how outlines around selected meshes look.
+    pub fn set_outline_style(&mut self, style: OutlineStyle) -> () {
+        self.outline_style = style;
+    }
+
+    /// Getter for the outline style.
+    pub fn get_outline_style(&self) -> &OutlineStyle {
+        &self.outline_style
+    }
+
+    /// Renders outlines around the given `(mesh data index, transform)` pairs, with the
+    /// outline style. Must be called after `render_objects`. Does nothing if `outlined`
+    /// is empty.
+    pub fn render_outlines(&mut self, outlined: &[(usize, &Transform)]) -> () {
+        if outlined.is_empty() {
+            return;
+        }
+        if self.outline_renderer.is_none() {
+            self.outline_renderer = Some(OutlineRenderer::new(&self.webgl_context));
+        }
+        let outline_renderer = self.outline_renderer.as_mut().unwrap();
+        let context = &self.webgl_context;
+        let frame_matrices = &self.frame_matrices;
+        self.frame_stats.draw_calls += outline_renderer.render(
+            context,
+            &self.asset_registry,
+            outlined,
+            &self.outline_style,
+            self.interpolation,
+            |material| {
+                set_camera_uniforms(context, frame_matrices, material).ok();
+            },
+        );
+    }
+
+    /// Queues a world space line to be drawn by the next `render_debug_lines`.
+    pub fn push_debug_line(
+        &mut self,
+        from: &Vector3<f32>,
+        to: &Vector3<f32>,
+        color: &Vector3<f32>,
+    ) -> () {
+        push_line_vertices(&mut self.debug_lines, from, to, color);
+    }
+
+    /// Queues the twelve edges of a world space box to be drawn by the next
+    /// `render_debug_lines`. See `Aabb::get_corners` for the order of `corners`.
+    pub fn push_debug_box(&mut self, corners: &[Vector3<f32>; 8], color: &Vector3<f32>) -> () {
+        push_box_vertices(&mut self.debug_lines, corners, color);
+    }
+
+    /// Renders the debug lines queued during the frame over what has been drawn. The queue
+    /// is emptied by `end_frame`. Does nothing if no line was queued.
+    pub fn render_debug_lines(&mut self) -> () {
+        if self.debug_lines.is_empty() {
+            return;
+        }
+        if self.debug_line_renderer.is_none() {
+            self.debug_line_renderer = Some(DebugLineRenderer::new(&self.webgl_context));
+        }
+        let debug_line_renderer = match &mut self.debug_line_renderer {
+            Some(Ok(debug_line_renderer)) => debug_line_renderer,
+            Some(Err(error)) => {
+                error_once!("Debug lines can't be rendered: {}", error);
+                return;
+            }
+            None => return,
+        };
+        let material = debug_line_renderer.get_material();
+        self.webgl_context
+            .use_program(material.borrow().get_program().as_ref());
+        set_camera_uniforms(&self.webgl_context, &self.frame_matrices, material).ok();
+        if let Err(error) = debug_line_renderer.render(&self.webgl_context, &self.debug_lines) {
+            error_throttled!(5000, "{}", error);
+        }
+    }
+
+    /// Renders the given blob shadows on the ground, darkening what has been drawn.  
+    /// Must be called after `render_objects`. Does nothing if `shadows` is empty.
+    pub fn render_blob_shadows(&mut self, shadows: &[&BlobShadow]) -> () {
+        if shadows.is_empty() {
+            return;
+        }
+        if self.blob_shadow_renderer.is_none() {
+            self.blob_shadow_renderer = Some(BlobShadowRenderer::new(&self.webgl_context));
+        }
+        let blob_shadow_renderer = match &mut self.blob_shadow_renderer {
+            Some(Ok(blob_shadow_renderer)) => blob_shadow_renderer,
+            Some(Err(error)) => {
+                error_once!("Blob shadows can't be rendered: {}", error);
+                return;
+            }
+            None => return,
+        };
+        let material = blob_shadow_renderer.get_material();
+        self.webgl_context
+            .use_program(material.borrow().get_program().as_ref());
+        set_camera_uniforms(&self.webgl_context, &self.frame_matrices, material).ok();
+        if let Err(error) = blob_shadow_renderer.render(&self.webgl_context, shadows) {
+            error_throttled!(5000, "{}", error);
+        }
+    }
+
+    /// Renders the given sprites back to front with alpha blending, batched by texture.  
+    /// Called by `render_objects` for each run of sprites between the transparent meshes.
+    /// Does nothing if `sprites` is empty.
+    fn render_sprites(&mut self, sprites: &[(&Sprite, &Transform)]) -> () {
+        if sprites.is_empty() {
+            return;
+        }
+        if self.sprite_renderer.is_none() {
+            self.sprite_renderer = Some(SpriteRenderer::new(&self.webgl_context));
+        }
+        let sprite_renderer = match &mut self.sprite_renderer {
+            Some(Ok(sprite_renderer)) => sprite_renderer,
+            Some(Err(error)) => {
+                error_once!("Sprites can't be rendered: {}", error);
+                return;
+            }
+            None => return,
+        };
+        let material = sprite_renderer.get_material();
+        self.webgl_context
+            .use_program(material.borrow().get_program().as_ref());
+        set_camera_uniforms(&self.webgl_context, &self.frame_matrices, material).ok();
+        sprite_renderer.render(
+            &self.webgl_context,
+            &self.asset_registry,
+            &self.frame_matrices.view,
+            sprites,
+        );
+    }
+
+    /// Deletes the particle buffers of the emitters not in `emitter_ids`, which should hold
+    /// the entity id of every existing `ParticleEmitter`. Called once per frame.
+    pub fn release_particle_buffers(&mut self, emitter_ids: &HashSet<u32>) -> () {
+        if let Some(Ok(particle_renderer)) = &mut self.particle_renderer {
+            particle_renderer.release_buffers(&self.webgl_context, emitter_ids);
+        }
+    }
+
+    /// Renders the live particles of the given emitters, with additive blending.  
+    /// Must be called after `render_objects`. Does nothing if `emitters` is empty.
+    pub fn render_particles(&mut self, emitters: &[(u32, &ParticleEmitter)]) -> () {
+        if emitters.is_empty() {
+            return;
+        }
+        let drawing_buffer_size = self.get_drawing_buffer_size();
+        if self.particle_renderer.is_none() {
+            self.particle_renderer = Some(ParticleRenderer::new(
+                &self.webgl_context,
+                self.scene_depth.is_some(),
+            ));
+        }
+        let particle_renderer = match &mut self.particle_renderer {
+            Some(Ok(particle_renderer)) => particle_renderer,
+            Some(Err(error)) => {
+                error_once!("Particles can't be rendered: {}", error);
+                return;
+            }
+            None => return,
+        };
+        particle_renderer.begin(&self.webgl_context, drawing_buffer_size.1 as f32);
+        let camera = self.main_camera.borrow();
+        let material = particle_renderer.get_material();
+        set_camera_uniforms(&self.webgl_context, &self.frame_matrices, material.clone()).ok();
+        set_scene_depth_uniforms(
+            &self.webgl_context,
+            self.scene_depth.as_ref(),
+            &camera,
+            drawing_buffer_size,
+            &material.borrow(),
+        )
+        .ok();
+        for (entity_id, emitter) in emitters {
+            if let Err(error) =
+                particle_renderer.draw_emitter(&self.webgl_context, *entity_id, emitter)
+            {
+                error_throttled!(5000, "{}", error);
+            }
+        }
+        particle_renderer.end(&self.webgl_context);
+    }
+
+    /// Renders the given overlays on top of everything else, in screen space.  
+    /// Must be called last. Does nothing if `overlays` is empty.
+    pub fn render_overlays(&mut self, mut overlays: Vec<&Overlay>) -> () {
+        if overlays.is_empty() {
+            return;
+        }
+        // Overlays are drawn over the tone mapped scene, with their own colors.
+        self.render_tone_map_pass();
+        let (width, height) = self.get_drawing_buffer_size();
+        if self.overlay_renderer.is_none() {
+            self.overlay_renderer = Some(OverlayRenderer::new(&self.webgl_context));
+        }
+        let overlay_renderer = match &mut self.overlay_renderer {
+            Some(Ok(overlay_renderer)) => overlay_renderer,
+            Some(Err(error)) => {
+                error_once!("Overlays can't be rendered: {}", error);
+                return;
+            }
+            None => return,
+        };
+        let pixel_ratio = web_sys::window().unwrap().device_pixel_ratio() as f32;
+        let result = overlay_renderer.render(
+            &self.webgl_context,
+            &self.asset_registry,
+            &mut overlays,
+            width as f32,
+            height as f32,
+            pixel_ratio,
+        );
+        if let Err(error) = result {
+            error_throttled!(5000, "{}", error);
+        }
+    }
+
+    /// Draws the HDR target of the main view to the framebuffer it was bound in place of,
+    /// with its bloom and the camera's exposure and tone mapping operator. Does nothing if
+    /// the main view wasn't drawn to the HDR target since the last call.
+    fn render_tone_map_pass(&mut self) -> () {
+        let (destination, (width, height)) = match self.tone_map_destination.take() {
+            Some(destination) => destination,
+            None => return,
+        };
+        let context = &self.webgl_context;
+        if let Some(tone_map_pass) = &mut self.tone_map_pass {
+            let camera = self.main_camera.borrow();
+            let result = tone_map_pass.render(
+                context,
+                destination.as_ref(),
+                (width, height),
+                camera.get_tone_mapping(),
+                camera.get_exposure(),
+            );
+            if let Err(error) = result {
+                error_throttled!(5000, "{}", error);
+                context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, destination.as_ref());
+                context.viewport(0, 0, width as i32, height as i32);
+            }
+        }
+    }
+
+    /// Draws every mesh using a material, returning the draw calls issued and triangles drawn
+    /// for each mesh data, passes included.  
+    /// The lights are uploaded for each mesh data, from `light_selections`.  
+    /// If `prepass_done` is `true`, opaque materials are drawn with an `EQUAL` depth test and
+    /// no depth writes, since the depth buffer already holds their depth.  
+    /// The meshes are then drawn again with each pass of the material, in order. Passes are
+    /// not part of the depth pre-pass.  
+    /// If `faded` is `true`, the material and its passes are alpha blended without depth
+    /// writes, even if they are opaque.
     fn draw_meshes_using_material(
         &self,
         material_id: usize,
-        mesh_hash_map: HashMap<&usize, Vec<(&usize, &Transform)>>,
+        mesh_batches: MeshBatches,
         light_repository: &LightRepository,
-    ) {
+        light_selections: &LightSelections,
+        prepass_done: bool,
+        faded: bool,
+    ) -> Vec<(usize, BatchStats)> {
+        let no_lights = LightSelection::default();
+        let mut batch_stats: Vec<(usize, BatchStats)> = Vec::new();
         if let Some(material) = self.asset_registry.get_material_with_index(material_id) {
-            self.webgl_context
-                .use_program(Some(&material.borrow().get_program().as_ref().unwrap()));
-            material
-                .borrow()
-                .set_uniforms_to_context(&self.webgl_context)
-                .ok();
-            self.set_camera_uniforms(material.clone()).ok();
-            self.set_lights_uniforms(material.clone(), light_repository)
-                .ok();
-            for (mesh_data_id, transforms) in mesh_hash_map {
-                self.draw_meshes_using_mesh_data(&mesh_data_id, material.clone(), transforms);
+            let passes = material.borrow().get_passes().to_vec();
+            let pass_materials = passes.iter().filter_map(|pass_id| {
+                let pass_material = self.asset_registry.get_material_with_index(*pass_id);
+                if pass_material.is_none() {
+                    error_throttled!(
+                        5000,
+                        "A pass of material {} was not rendered because material {} is not registered.",
+                        &material_id,
+                        pass_id
+                    );
+                }
+                pass_material
+            });
+            let materials =
+                std::iter::once((material, false)).chain(pass_materials.map(|pass| (pass, true)));
+            for (material, is_pass) in materials {
+                self.use_material(&material, prepass_done && !is_pass, faded);
+                for (mesh_data_id, transforms) in &mesh_batches {
+                    let lights = light_selections
+                        .get(&(material_id, **mesh_data_id))
+                        .unwrap_or(&no_lights);
+                    self.set_lights_uniforms(material.clone(), light_repository, lights)
+                        .ok();
+                    let stats = self.draw_meshes_using_mesh_data(
+                        mesh_data_id,
+                        material.clone(),
+                        transforms.clone(),
+                        is_pass,
+                    );
+                    match batch_stats.iter_mut().find(|(id, _)| id == *mesh_data_id) {
+                        Some((_, total)) => {
+                            total.draw_calls += stats.draw_calls;
+                            total.triangles += stats.triangles;
+                        }
+                        None => batch_stats.push((**mesh_data_id, stats)),
+                    }
+                }
             }
+            self.webgl_context.cull_face(WebGlRenderingContext::BACK);
+            self.webgl_context.front_face(WebGlRenderingContext::CCW);
         } else {
-            console_error(&format!(
+            error_throttled!(
+                5000,
                 "Meshes were not rendered because material {} is not registered.",
                 &material_id
-            ));
+            );
+        }
+        batch_stats
+    }
+
+    /// Sets the state, program and shared uniforms used to draw with `material`, blended if
+    /// `faded` is `true`.
+    fn use_material(
+        &self,
+        material: &Rc<RefCell<Material>>,
+        prepass_done: bool,
+        faded: bool,
+    ) -> () {
+        self.set_material_state(&material.borrow(), prepass_done, faded);
+        self.webgl_context
+            .use_program(Some(&material.borrow().get_program().as_ref().unwrap()));
+        material
+            .borrow()
+            .set_uniforms_to_context(&self.webgl_context)
+            .ok();
+        self.set_camera_uniforms(material.clone()).ok();
+        self.set_time_uniforms(&material.borrow()).ok();
+        self.global_uniforms
+            .set_to_context(&self.webgl_context, &mut material.borrow_mut())
+            .ok();
+        self.set_entity_opacity_uniform(material, 1.);
+        set_scene_depth_uniforms(
+            &self.webgl_context,
+            self.scene_depth.as_ref(),
+            &self.main_camera.borrow(),
+            self.get_drawing_buffer_size(),
+            &material.borrow(),
+        )
+        .ok();
+    }
+
+    /// Sets the depth, blending, winding, culling and polygon offset state used to draw with
+    /// `material`, blended if `faded` is `true`.
+    fn set_material_state(&self, material: &Material, prepass_done: bool, faded: bool) -> () {
+        let context = &self.webgl_context;
+        let blended = material.is_blended() || faded;
+        context.front_face(material.get_front_face().get_gl_mode());
+        context.cull_face(if material.get_cull_front_faces() {
+            WebGlRenderingContext::FRONT
+        } else {
+            WebGlRenderingContext::BACK
+        });
+        let prepass_drawn =
+            !material.is_transparent() && !material.is_decal() && !material.is_cutout();
+        if prepass_done && prepass_drawn {
+            context.depth_func(WebGlRenderingContext::EQUAL);
+            context.depth_mask(false);
+        } else {
+            context.depth_func(WebGlRenderingContext::LESS);
+            context.depth_mask(true);
+        }
+        match material.get_polygon_offset() {
+            Some((factor, units)) => {
+                context.enable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+                context.polygon_offset(factor, units);
+                if blended {
+                    context.enable(WebGlRenderingContext::BLEND);
+                    context.blend_func(
+                        WebGlRenderingContext::SRC_ALPHA,
+                        WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+                    );
+                    context.depth_mask(false);
+                } else {
+                    context.disable(WebGlRenderingContext::BLEND);
+                }
+            }
+            None => {
+                context.disable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+                if blended {
+                    context.enable(WebGlRenderingContext::BLEND);
+                    context.blend_func(
+                        WebGlRenderingContext::SRC_ALPHA,
+                        WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+                    );
+                    context.depth_mask(false);
+                } else {
+                    context.disable(WebGlRenderingContext::BLEND);
+                }
+            }
         }
     }
 
+    /// Draws every instance of a mesh data, returning the number of draw calls issued and
+    /// of triangles drawn.  
+    /// The vertex buffers are bound once, then each instance draws the index range of its
+    /// sub-mesh, with its uniform overrides if it has some.  
+    /// Material instances without uniforms of their own are drawn first, with the parent's
+    /// uniforms, which are only set again after an instance that overrides some.  
+    /// If `is_pass` is `true`, `material` is a pass of the instances' material: only their
+    /// transform is uploaded.
     fn draw_meshes_using_mesh_data(
         &self,
         mesh_data_id: &usize,
         material: Rc<RefCell<Material>>,
-        mut transforms: Vec<(&usize, &Transform)>,
-    ) {
-        transforms.sort_by(|a, b| a.0.cmp(b.0));
-        let current_mat_instance_id = std::usize::MAX;
+        mut transforms: Vec<MeshToDraw>,
+        is_pass: bool,
+    ) -> BatchStats {
+        let mut stats = BatchStats::default();
+        let instance_key = |material_instance_id: &usize| {
+            self.asset_registry
+                .get_material_instance_with_index(*material_instance_id)
+                .filter(|material_instance| material_instance.borrow().has_uniforms())
+                .map(|_| *material_instance_id)
+        };
+        transforms.sort_by_cached_key(|(material_instance_id, _, sub_mesh, _, _)| {
+            (instance_key(material_instance_id), *sub_mesh)
+        });
+        // Key of the instance whose uniforms are set, `Some(None)` for the parent's.
+        let mut applied_key: Option<Option<usize>> = None;
+        let mut applied_instance: Option<Rc<RefCell<MaterialInstance>>> = None;
         if let Some(mesh_data) = self
             .asset_registry
             .get_mesh_data_with_index(mesh_data_id.to_owned())
         {
+            let draw_mode = mesh_data.borrow().get_draw_mode();
             for buffer in mesh_data.borrow().get_buffers() {
                 let location = material
                     .borrow()
@@ -157,75 +1479,276 @@ impl Renderer {
                 if let Some(loc) = location {
                     buffer.enable_and_bind_attribute(&self.webgl_context, loc);
                 } else {
-                    console_error("Could not bind some buffers because locations were missing.");
+                    warn_once!("Could not bind some buffers because locations were missing.");
                 }
             }
-            for (material_instance_id, transform) in transforms {
-                if material_instance_id != &current_mat_instance_id {
-                    if let Some(material_instance) = self
-                        .asset_registry
-                        .get_material_instance_with_index(material_instance_id.to_owned())
-                    {
-                        material_instance
-                            .borrow()
-                            .set_uniforms_to_context(&self.webgl_context)
-                            .ok();
-                        self.set_transform_uniform(material.clone(), transform).ok();
-                        self.webgl_context.draw_elements_with_i32(
-                            WebGlRenderingContext::TRIANGLES,
-                            mesh_data.borrow().get_vertex_count(),
-                            WebGlRenderingContext::UNSIGNED_SHORT,
-                            0,
-                        );
+            for (material_instance_id, transform, sub_mesh, overrides, opacity) in transforms {
+                let (index_offset, index_count) = match mesh_data
+                    .borrow()
+                    .get_sub_mesh_range(sub_mesh)
+                {
+                    Some(range) => range,
+                    None => {
+                        warn_throttled!(
+                                5000,
+                                "Sub-mesh {} of mesh_data {} was not rendered because it doesn't exist.",
+                                sub_mesh,
+                                &mesh_data_id
+                            );
+                        continue;
+                    }
+                };
+                if opacity < 1. {
+                    self.set_entity_opacity_uniform(&material, opacity);
+                }
+                if is_pass {
+                    self.set_transform_uniform(material.clone(), transform).ok();
+                    self.webgl_context.draw_elements_with_i32(
+                        draw_mode,
+                        index_count,
+                        WebGlRenderingContext::UNSIGNED_SHORT,
+                        index_offset * U16_SIZE as i32,
+                    );
+                    if opacity < 1. {
+                        self.set_entity_opacity_uniform(&material, 1.);
+                    }
+                    stats.draw_calls += 1;
+                    stats.triangles += count_triangles(draw_mode, index_count);
+                    continue;
+                }
+                if let Some(material_instance) = self
+                    .asset_registry
+                    .get_material_instance_with_index(material_instance_id.to_owned())
+                {
+                    let key = if material_instance.borrow().has_uniforms() {
+                        Some(*material_instance_id)
                     } else {
-                        console_error(&format!("Meshes were not rendered because material instance {} is not registered.",&material_instance_id));
+                        None
+                    };
+                    if applied_key != Some(key) {
+                        if let Some(previous) = applied_instance.take() {
+                            self.restore_instance_uniforms(&material, &previous.borrow());
+                        }
+                        // Instances may override the environment map: bind the scene's one again.
+                        self.set_environment_map_uniform(&material.borrow()).ok();
+                        {
+                            let material_instance = material_instance.borrow();
+                            material_instance.resolve_texture_references(|id| {
+                                self.resolve_texture_reference(id)
+                            });
+                            material_instance
+                                .set_uniforms_to_context(&self.webgl_context)
+                                .ok();
+                        }
+                        applied_key = Some(key);
+                        if key.is_some() {
+                            applied_instance = Some(material_instance.clone());
+                        }
                     }
+                    self.set_transform_uniform(material.clone(), transform).ok();
+                    if let Some(overrides) = overrides {
+                        self.set_uniform_overrides(&material, overrides, false);
+                    }
+                    self.webgl_context.draw_elements_with_i32(
+                        draw_mode,
+                        index_count,
+                        WebGlRenderingContext::UNSIGNED_SHORT,
+                        index_offset * U16_SIZE as i32,
+                    );
+                    if let Some(overrides) = overrides {
+                        self.restore_overridden_uniforms(
+                            &material,
+                            &material_instance.borrow(),
+                            overrides,
+                        );
+                    }
+                    if opacity < 1. {
+                        self.set_entity_opacity_uniform(&material, 1.);
+                    }
+                    stats.draw_calls += 1;
+                    stats.triangles += count_triangles(draw_mode, index_count);
+                } else {
+                    error_throttled!(
+                        5000,
+                        "Meshes were not rendered because material instance {} is not registered.",
+                        &material_instance_id
+                    );
                 }
             }
+            // The next mesh data is drawn with the same material, starting from its uniforms.
+            if let Some(previous) = applied_instance {
+                self.restore_instance_uniforms(&material, &previous.borrow());
+            }
         } else {
-            console_error(&format!(
+            error_throttled!(
+                5000,
                 "Meshes were not rendered because mesh_data {} is not registered.",
                 &mesh_data_id
-            ));
+            );
+        }
+        stats
+    }
+
+    /// Sets the uniforms overridden by an entity, or zeroes them if `reset` is `true`.  
+    /// Meant to be used by `Self.draw_meshes_using_mesh_data`
+    fn set_uniform_overrides(
+        &self,
+        material: &Rc<RefCell<Material>>,
+        overrides: &UniformOverrides,
+        reset: bool,
+    ) -> () {
+        for (name, value) in overrides.get_values() {
+            let location = material
+                .borrow_mut()
+                .get_override_location(&self.webgl_context, name);
+            if location.is_none() {
+                continue;
+            }
+            let value: Box<dyn UniformValue> = match (value, reset) {
+                (UniformOverrideValue::Float(value), false) => Box::new(*value),
+                (UniformOverrideValue::Float(_), true) => Box::new(0.0f32),
+                (UniformOverrideValue::Vector2(value), false) => Box::new(*value),
+                (UniformOverrideValue::Vector2(value), true) => Box::new(value * 0.0),
+                (UniformOverrideValue::Vector3(value), false) => Box::new(*value),
+                (UniformOverrideValue::Vector3(value), true) => Box::new(value * 0.0),
+                (UniformOverrideValue::Vector4(value), false) => Box::new(*value),
+                (UniformOverrideValue::Vector4(value), true) => Box::new(value * 0.0),
+            };
+            if let Err(error) = Uniform::new_with_location(name, location, value)
+                .set_to_context(&self.webgl_context)
+            {
+                warn_throttled!(5000, "{}", error);
+            }
+        }
+    }
+
+    /// Sets the opacity of the entity drawn next, if the material declares it.  
+    /// Its location is looked up and cached like those of uniform overrides.
+    fn set_entity_opacity_uniform(&self, material: &Rc<RefCell<Material>>, opacity: f32) -> () {
+        let location = material
+            .borrow_mut()
+            .get_override_location(&self.webgl_context, ENTITY_OPACITY_NAME);
+        if location.is_some() {
+            Uniform::new_with_location(ENTITY_OPACITY_NAME, location, Box::new(opacity))
+                .set_to_context(&self.webgl_context)
+                .ok();
+        }
+    }
+
+    /// Returns the texture registered under `id` for the uniforms referencing it, or the
+    /// fallback texture if there is none.  
+    /// Meant to be used by `Self.draw_meshes_using_mesh_data`
+    fn resolve_texture_reference(&self, id: &str) -> Option<Rc<WebGlTexture>> {
+        self.asset_registry.get_texture(id).or_else(|| {
+            warn_throttled!(
+                5000,
+                "Texture {} is referenced but not registered: the fallback texture is drawn instead.",
+                id
+            );
+            self.asset_registry.get_texture(FALLBACK_TEXTURE_ID)
+        })
+    }
+
+    /// Sets the uniforms overridden by a material instance back to the values of its parent
+    /// material. Uniforms the parent doesn't set are left as they are.  
+    /// Meant to be used by `Self.draw_meshes_using_mesh_data`
+    fn restore_instance_uniforms(
+        &self,
+        material: &Rc<RefCell<Material>>,
+        material_instance: &MaterialInstance,
+    ) -> () {
+        let material = material.borrow();
+        for name in material_instance.get_uniform_names() {
+            material.set_uniform_to_context(&self.webgl_context, name);
+        }
+    }
+
+    /// Sets the uniforms overridden by an entity back to the values of its material instance,
+    /// or of its material, so that they don't leak to the next draw call. Uniforms neither of
+    /// them sets are zeroed, their default value.  
+    /// Meant to be used by `Self.draw_meshes_using_mesh_data`
+    fn restore_overridden_uniforms(
+        &self,
+        material: &Rc<RefCell<Material>>,
+        material_instance: &MaterialInstance,
+        overrides: &UniformOverrides,
+    ) -> () {
+        let mut unset = UniformOverrides::new();
+        for (name, value) in overrides.get_values() {
+            let restored = material_instance.set_uniform_to_context(&self.webgl_context, name)
+                || material
+                    .borrow()
+                    .set_uniform_to_context(&self.webgl_context, name);
+            if !restored {
+                unset.set(name, *value);
+            }
+        }
+        if !unset.is_empty() {
+            self.set_uniform_overrides(material, &unset, true);
         }
     }
 
     /// Sets the global camera uniform for the whole scene  
     /// Meant to be used by `Self.render_objects`
-    fn set_camera_uniforms(&self, material: Rc<RefCell<Material>>) -> Result<(), String> {
-        let camera_view_uniform_location = material
-            .borrow_mut()
-            .global_uniform_locations
-            .view_matrix_location
-            .clone();
-        let camera_position_uniform_location = material
-            .borrow_mut()
-            .global_uniform_locations
-            .camera_position_location
-            .clone();
-        let camera_projection_uniform_location = material
-            .borrow_mut()
-            .global_uniform_locations
-            .projection_matrix_location
-            .clone();
-        let view_matrix_uniform = Uniform::new_with_location(
-            crate::utils::constants::VIEW_MATRIX_NAME,
-            camera_view_uniform_location,
-            Box::new(self.main_camera.borrow().get_view_matrix()),
-        );
-        let camera_position_uniform = Uniform::new_with_location(
-            crate::utils::constants::CAMERA_POSITION_NAME,
-            camera_position_uniform_location,
-            Box::new(self.main_camera.borrow().get_position().clone()),
-        );
-        let projection_matrix_uniform = Uniform::new_with_location(
-            crate::utils::constants::PROJECTION_MATRIX_NAME,
-            camera_projection_uniform_location,
-            Box::new(self.main_camera.borrow().get_projection_matrix()),
-        );
-        view_matrix_uniform.set_to_context(&self.webgl_context)?;
-        camera_position_uniform.set_to_context(&self.webgl_context)?;
-        projection_matrix_uniform.set_to_context(&self.webgl_context)
+    fn set_camera_uniforms(&self, material: Rc<RefCell<Material>>) -> Result<(), W3DError> {
+        set_camera_uniforms(&self.webgl_context, &self.frame_matrices, material)
+    }
+
+    /// Sets the time uniforms of the materials declaring them.  
+    /// Meant to be used by `Self.render_objects`
+    fn set_time_uniforms(&self, material: &Material) -> Result<(), W3DError> {
+        let locations = &material.global_uniform_locations;
+        let (elapsed, delta) = self.time;
+        if locations.time_location.is_some() {
+            Uniform::new_with_location(
+                TIME_NAME,
+                locations.time_location.clone(),
+                Box::new(elapsed as f32),
+            )
+            .set_to_context(&self.webgl_context)?;
+        }
+        if locations.delta_time_location.is_some() {
+            Uniform::new_with_location(
+                DELTA_TIME_NAME,
+                locations.delta_time_location.clone(),
+                Box::new(delta),
+            )
+            .set_to_context(&self.webgl_context)?;
+        }
+        if locations.time_wrapped_location.is_some() {
+            let wrapped = (elapsed % TIME_WRAP_PERIOD) as f32;
+            Uniform::new_with_location(
+                TIME_WRAPPED_NAME,
+                locations.time_wrapped_location.clone(),
+                Box::new(wrapped),
+            )
+            .set_to_context(&self.webgl_context)?;
+        }
+        Ok(())
+    }
+
+    /// Binds the environment map to its reserved texture unit, if the material samples it.  
+    /// Meant to be used by `Self.render_objects`
+    fn set_environment_map_uniform(&self, material: &Material) -> Result<(), W3DError> {
+        let location = &material.global_uniform_locations.environment_map_location;
+        if let (Some(environment_map), Some(_)) = (&self.environment_map, location) {
+            if ENVIRONMENT_MAP_TEXTURE_INDEX >= self.capabilities.max_texture_image_units {
+                warn_once!(
+                    "The environment map is not bound: texture unit {} is above the {} units supported.",
+                    ENVIRONMENT_MAP_TEXTURE_INDEX,
+                    self.capabilities.max_texture_image_units
+                );
+                return Ok(());
+            }
+            let mut uniform = Uniform::new_with_location(
+                ENVIRONMENT_MAP_NAME,
+                location.clone(),
+                Box::new(environment_map.clone()),
+            );
+            uniform.set_texture_index(ENVIRONMENT_MAP_TEXTURE_INDEX);
+            uniform.set_to_context(&self.webgl_context)?;
+        }
+        Ok(())
     }
 
     /// Sets the world transform uniform for a specific object
@@ -234,13 +1757,13 @@ impl Renderer {
         &self,
         material: Rc<RefCell<Material>>,
         transform: &Transform,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         let transfom_matrix_location = material
             .borrow_mut()
             .global_uniform_locations
             .world_transform_location
             .clone();
-        let world_matrix = transform.get_world_matrix();
+        let world_matrix = transform.get_rendered_world_matrix(self.interpolation);
         let transform_uniform = Uniform::new_with_location(
             crate::utils::constants::WORLD_TRANSFORM_NAME,
             transfom_matrix_location,
@@ -249,45 +1772,786 @@ impl Renderer {
         transform_uniform.set_to_context(&self.webgl_context)
     }
 
-    /// Sets the light uniforms from lights present in the scene
+    /// Sets the light uniforms from lights present in the scene, with the point and spot
+    /// lights selected for the current batch.
     /// Meant to be used by `Self.render_objects`
     fn set_lights_uniforms(
         &self,
         material: Rc<RefCell<Material>>,
         light_repository: &LightRepository,
-    ) -> Result<(), String> {
-        light_repository.set_material_uniforms(&self.webgl_context, material.clone());
+        lights: &LightSelection,
+    ) -> Result<(), W3DError> {
+        light_repository.set_material_uniforms(&self.webgl_context, material.clone(), lights);
         Ok(())
     }
 
+    /// Sets whether mesh data registered from now on keep a CPU copy of their positions and
+    /// indexes. See `AssetRegistry::set_retain_cpu_data`.
+    pub fn set_retain_mesh_cpu_data(&mut self, retain_cpu_data: bool) -> () {
+        self.asset_registry.set_retain_cpu_data(retain_cpu_data);
+    }
+
+    /// Sets whether missing assets are errors instead of being replaced by fallbacks.
+    /// See `AssetRegistry::set_strict`.
+    pub fn set_strict_assets(&mut self, strict: bool) -> () {
+        self.asset_registry.set_strict(strict);
+    }
+
+    /// Returns the index of the mesh data registered under `id`, or of the fallback cube.
+    /// See `AssetRegistry::resolve_mesh_data`.
+    pub fn resolve_mesh_data(&mut self, id: &str, referenced_by: &str) -> Result<usize, W3DError> {
+        self.asset_registry
+            .resolve_mesh_data(&self.webgl_context, id, referenced_by)
+    }
+
+    /// Returns the indexes of the material instance registered under `id` and of its parent
+    /// material, or of the fallbacks. See `AssetRegistry::resolve_material_instance`.
+    pub fn resolve_material_instance(
+        &mut self,
+        id: &str,
+        referenced_by: &str,
+    ) -> Result<(usize, usize), W3DError> {
+        self.asset_registry
+            .resolve_material_instance(id, referenced_by)
+    }
+
     /// Getter for the asset registry, immutable version
     pub fn get_asset_registry(&self) -> &AssetRegistry {
         &self.asset_registry
     }
 
-    /// Register an asset to the AssetRegistry associated with this Renderer
+    /// Returns the `MeshData`, `MaterialInstance` and `Material` indexes to use for a decal
+    /// displaying the texture `texture_index`.  
+    /// The built-in decal assets are registered the first time they are needed.
+    pub fn get_decal_assets(
+        &mut self,
+        texture_index: usize,
+    ) -> Result<(usize, usize, usize), W3DError> {
+        let registry = &mut self.asset_registry;
+        let mesh_data_index = match registry.get_id_from_str(decal::DECAL_QUAD_ID) {
+            Some(index) => index,
+            None => registry.add_mesh_data(decal::create_decal_quad(&self.webgl_context)?),
+        };
+        let material_index = match registry.get_id_from_str(decal::DECAL_MATERIAL_ID) {
+            Some(index) => index,
+            None => registry.add_material(decal::create_decal_material()),
+        };
+        let instance_id = decal::get_decal_material_instance_id(texture_index);
+        let instance_index = match registry.get_id_from_str(&instance_id) {
+            Some(index) => index,
+            None => {
+                let texture = registry
+                    .get_texture_with_index(texture_index)
+                    .ok_or_else(|| {
+                        W3DError::new(
+                            W3DErrorKind::MissingAsset,
+                            "Texture could not be found. Has it been registered yet?",
+                        )
+                    })?;
+                let material = registry.get_material_with_index(material_index).unwrap();
+                registry.add_material_instance(decal::create_decal_material_instance(
+                    material,
+                    texture,
+                    &instance_id,
+                ))
+            }
+        };
+        Ok((mesh_data_index, instance_index, material_index))
+    }
+
+    /// Makes the texture uniform `uniform_name` of a material instance reference the texture
+    /// registered under `texture_id`, resolved each time the instance is drawn. The fallback
+    /// texture is drawn while no texture is registered under that id, unless the registry is
+    /// strict.  
+    /// Fails if the material instance is not registered or if the fallback texture can't be
+    /// created.
+    pub fn set_instance_texture(
+        &mut self,
+        material_instance_id: &str,
+        uniform_name: &str,
+        texture_id: &str,
+    ) -> Result<(), W3DError> {
+        let material_instance = self
+            .asset_registry
+            .get_material_instance(material_instance_id)
+            .ok_or_else(|| {
+                W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Material instance could not be found. Has it been registered yet?",
+                    material_instance_id,
+                )
+            })?;
+        self.asset_registry
+            .prepare_fallback_texture(&self.webgl_context)?;
+        let mut material_instance = material_instance.borrow_mut();
+        let mut uniform = Uniform::new(uniform_name, Box::new(TextureReference::new(texture_id)));
+        uniform.set_texture_index(material_instance.get_texture_unit(uniform_name));
+        material_instance.set_uniform(uniform);
+        Ok(())
+    }
+
+    /// Returns the `MeshData`, `MaterialInstance` and `Material` indexes to use for a grid
+    /// helper, creating its mesh data on first use. See `helpers::create_grid`.  
+    /// Fails if the number of divisions is invalid or if the mesh data can't be uploaded.
+    pub fn get_grid_assets(
+        &mut self,
+        size: f32,
+        divisions: u32,
+        major: &Vector3<f32>,
+        minor: &Vector3<f32>,
+    ) -> Result<(usize, usize, usize), W3DError> {
+        let id = helpers::get_grid_id(size, divisions, major, minor);
+        let context = &self.webgl_context;
+        Renderer::get_helper_assets(
+            &mut self.asset_registry,
+            &id,
+            helpers::GRID_MATERIAL_ID,
+            |id| helpers::create_grid(context, id, size, divisions, major, minor),
+        )
+    }
+
+    /// Returns the `MeshData`, `MaterialInstance` and `Material` indexes to use for an axes
+    /// helper of length `size`, creating its mesh data on first use.  
+    /// Fails if the mesh data can't be uploaded.
+    pub fn get_axes_assets(&mut self, size: f32) -> Result<(usize, usize, usize), W3DError> {
+        let id = helpers::get_axes_id(size);
+        let context = &self.webgl_context;
+        Renderer::get_helper_assets(
+            &mut self.asset_registry,
+            &id,
+            helpers::HELPER_LINE_MATERIAL_ID,
+            |id| helpers::create_axes(context, id, size),
+        )
+    }
+
+    /// Returns the indexes of the helper mesh data `mesh_data_id`, built by `create` on first
+    /// use, and of the material `material_id` and its shared instance, registering them in
+    /// `registry` if needed.
+    fn get_helper_assets<F>(
+        registry: &mut AssetRegistry,
+        mesh_data_id: &str,
+        material_id: &str,
+        create: F,
+    ) -> Result<(usize, usize, usize), W3DError>
+    where
+        F: FnOnce(&str) -> Result<MeshData, W3DError>,
+    {
+        let mesh_data_index = match registry.get_id_from_str(mesh_data_id) {
+            Some(index) => index,
+            None => registry.add_mesh_data(create(mesh_data_id)?),
+        };
+        let material_index = match registry.get_id_from_str(material_id) {
+            Some(index) => index,
+            None => registry.add_material(helpers::create_helper_material(material_id)),
+        };
+        let instance_id = helpers::get_helper_material_instance_id(material_id);
+        let instance_index = match registry.get_id_from_str(&instance_id) {
+            Some(index) => index,
+            None => {
+                let material = registry.get_material_with_index(material_index).unwrap();
+                registry.add_material_instance(helpers::create_helper_material_instance(material))
+            }
+        };
+        Ok((mesh_data_index, instance_index, material_index))
+    }
+
+    /// Register an asset to the AssetRegistry associated with this Renderer, under `id` if
+    /// given or the id stored in the file otherwise. An asset already registered under that
+    /// id is replaced if `replace` is `true`, and is an error otherwise.
     pub fn register_asset(
         &mut self,
         file_data: &[u8],
         file_type: FileType,
-    ) -> Result<String, String> {
+        id: Option<String>,
+        replace: bool,
+    ) -> Result<String, W3DError> {
+        let context = &self.webgl_context;
         match file_type {
             FileType::WMesh => self
                 .asset_registry
-                .register_mesh_data(&self.webgl_context, file_data),
-            FileType::WMaterial => self.asset_registry.register_material(file_data),
-            FileType::WMatInstance => self.asset_registry.register_material_instance(file_data),
+                .register_mesh_data(context, file_data, id, replace),
+            FileType::WMaterial => {
+                let id = self
+                    .asset_registry
+                    .register_material(context, file_data, id, replace)?;
+                self.check_texture_units(&id);
+                Ok(id)
+            }
+            FileType::WMatInstance => self
+                .asset_registry
+                .register_material_instance(context, file_data, id, replace),
+        }
+    }
+
+    /// Registers a mesh file as `register_asset`, after transforming its vertices as set by
+    /// `options` (see `asset::transform_mesh_file`), detecting the winding of its triangles
+    /// and flipping the clockwise ones if `options.fix_winding` is set. Returns the id and
+    /// the winding detected. Meshes left clockwise or mixed are logged.
+    pub fn import_mesh(
+        &mut self,
+        file_data: &[u8],
+        id: Option<String>,
+        replace: bool,
+        options: &ImportOptions,
+    ) -> Result<(String, WindingReport), W3DError> {
+        let transformed_data = transform_mesh_file(file_data, options)?;
+        let file_data = transformed_data.as_ref().map_or(file_data, Vec::as_slice);
+        let (fixed_data, report) = check_winding(file_data, options.fix_winding)?;
+        let file_data = fixed_data.as_ref().map_or(file_data, Vec::as_slice);
+        let id =
+            self.asset_registry
+                .register_mesh_data(&self.webgl_context, file_data, id, replace)?;
+        match report.winding {
+            Winding::Clockwise | Winding::Mixed if report.flipped_triangles == 0 => log_warn!(
+                "Mesh data {} has {} triangles, which back-face culling hides.",
+                id,
+                report.winding.get_name()
+            ),
+            _ => {}
+        }
+        Ok((id, report))
+    }
+
+    /// Sets the memory limits of the assets, in bytes, `None` for no limit. See
+    /// `MemoryBudget` for what can be evicted.
+    pub fn set_memory_budget(&mut self, gpu_bytes: Option<usize>, cpu_bytes: Option<usize>) {
+        self.asset_registry
+            .set_memory_budget(&self.webgl_context, gpu_bytes, cpu_bytes);
+    }
+
+    /// Pins or unpins an asset, so that it is never evicted. Fails if it isn't registered.
+    pub fn set_asset_pinned(&mut self, id: &str, pinned: bool) -> Result<(), W3DError> {
+        self.asset_registry.set_pinned(id, pinned)
+    }
+
+    /// Removes the unused assets for which `is_collectable` returns `true`. See
+    /// `AssetRegistry::collect_assets`.
+    pub fn collect_assets<F>(
+        &mut self,
+        all_types: bool,
+        is_collectable: F,
+    ) -> Vec<(String, &'static str)>
+    where
+        F: FnMut(usize) -> bool,
+    {
+        self.asset_registry
+            .collect_assets(&self.webgl_context, all_types, is_collectable)
+    }
+
+    /// Marks the given mesh data as drawn this frame, uploading again those evicted by the
+    /// memory budget. Must be called before `render_objects` with every mesh data it draws.
+    pub fn use_mesh_data<I>(&mut self, mesh_data_indexes: I) -> ()
+    where
+        I: Iterator<Item = usize>,
+    {
+        self.asset_registry.begin_frame();
+        for index in mesh_data_indexes {
+            if let Err(error) = self
+                .asset_registry
+                .use_mesh_data(&self.webgl_context, index)
+            {
+                error_throttled!(5000, "{}", error);
+            }
+        }
+    }
+
+    /// Registers `count` material instances of the material registered under `parent_id`,
+    /// without uniforms of their own. See `AssetRegistry::add_material_instance_pool`.
+    pub fn create_material_instances(
+        &mut self,
+        parent_id: &str,
+        count: usize,
+    ) -> Result<Vec<String>, W3DError> {
+        self.asset_registry
+            .add_material_instance_pool(parent_id, count)
+    }
+
+    /// Builds the material of a description and registers it. Returns its index in the
+    /// registry.  
+    /// Fails if the id is already used or if one of its textures is not registered.
+    pub fn add_described_material(
+        &mut self,
+        description: &MaterialDescription,
+    ) -> Result<usize, W3DError> {
+        if self
+            .asset_registry
+            .get_id_from_str(&description.id)
+            .is_some()
+        {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The id of the described material is already used.",
+                &description.id,
+            ));
+        }
+        let material = description.build_material(&self.asset_registry)?;
+        let index = self.asset_registry.add_material(material);
+        self.check_texture_units(&description.id);
+        Ok(index)
+    }
+
+    /// Register the six face images of a cube texture, stored in the AssetRegistery used by
+    /// this Renderer.
+    pub fn register_cube_texture(
+        &mut self,
+        faces: &[HtmlImageElement],
+        id: String,
+    ) -> Result<String, W3DError> {
+        self.asset_registry
+            .register_cube_texture(&self.webgl_context, faces, id)
+    }
+
+    /// Warns if the material registered under `id` samples more textures than the context
+    /// has texture units: the ones above the limit are not bound.
+    fn check_texture_units(&self, id: &str) -> () {
+        let material = match self.asset_registry.get_material(id) {
+            Some(material) => material,
+            None => return,
+        };
+        let indexes = match material.borrow().get_texture_indexes() {
+            Ok(indexes) => indexes,
+            Err(_) => return,
+        };
+        let max_units = self.capabilities.max_texture_image_units;
+        for (name, index) in indexes {
+            if index >= max_units {
+                log_warn!(
+                    "Texture {} of material {} uses unit {}, above the {} units supported: it won't be sampled.",
+                    name,
+                    id,
+                    index,
+                    max_units
+                );
+            }
         }
     }
 
     /// Register an image for use as a texture by the Renderer, stored in the AssetRegistery
-    /// used by this Renderer.
+    /// used by this Renderer.  
+    /// Fails if the image is larger than the context's maximum texture size.
     pub fn register_texture(
         &mut self,
         image: &HtmlImageElement,
         id: String,
-    ) -> Result<String, String> {
+    ) -> Result<String, W3DError> {
+        let max_size = self.capabilities.max_texture_size;
+        if image.natural_width() > max_size || image.natural_height() > max_size {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                &format!(
+                    "Texture is {}x{}, larger than the maximum size of {} supported by the context.",
+                    image.natural_width(),
+                    image.natural_height(),
+                    max_size
+                ),
+                &id,
+            ));
+        }
         self.asset_registry
             .register_texture(&self.webgl_context, image, id)
     }
+
+    /// Registers a 1x1 placeholder texture under `id`, sampled until the image decoded for
+    /// it is uploaded with `queue_texture_upload`.
+    pub fn register_placeholder_texture(&mut self, id: String) -> Result<String, W3DError> {
+        self.asset_registry
+            .register_placeholder_texture(&self.webgl_context, id)
+    }
+
+    /// Queues a decoded image to be uploaded to the placeholder texture registered under
+    /// `id` by a following `upload_pending_textures`, which calls `resolve` with the id once
+    /// it's done or `reject` with the error.
+    pub fn queue_texture_upload(
+        &mut self,
+        id: String,
+        bitmap: ImageBitmap,
+        resolve: Function,
+        reject: Function,
+    ) -> () {
+        self.texture_uploads.push(id, bitmap, resolve, reject);
+    }
+
+    /// Uploads some of the textures queued with `queue_texture_upload`, spreading large
+    /// batches over several frames. Called by the `Scene` before rendering.
+    pub fn upload_pending_textures(&mut self) -> () {
+        self.texture_uploads.upload(
+            &self.webgl_context,
+            &mut self.asset_registry,
+            self.capabilities.max_texture_size,
+        );
+    }
+}
+
+/// Sets the camera uniforms (view and projection matrices, camera position, exposure) of
+/// `material` from the matrices of the current frame.  
+/// The material's program must be in use.
+fn set_camera_uniforms(
+    context: &WebGlRenderingContext,
+    frame_matrices: &FrameMatrices,
+    material: Rc<RefCell<Material>>,
+) -> Result<(), W3DError> {
+    let material = material.borrow();
+    let locations = &material.global_uniform_locations;
+    if locations.exposure_location.is_some() {
+        Uniform::new_with_location(
+            crate::utils::constants::EXPOSURE_NAME,
+            locations.exposure_location.clone(),
+            Box::new(frame_matrices.exposure),
+        )
+        .set_to_context(context)?;
+    }
+    Uniform::new_with_location(
+        crate::utils::constants::VIEW_MATRIX_NAME,
+        locations.view_matrix_location.clone(),
+        Box::new(frame_matrices.view),
+    )
+    .set_to_context(context)?;
+    Uniform::new_with_location(
+        crate::utils::constants::CAMERA_POSITION_NAME,
+        locations.camera_position_location.clone(),
+        Box::new(frame_matrices.eye_position),
+    )
+    .set_to_context(context)?;
+    Uniform::new_with_location(
+        crate::utils::constants::PROJECTION_MATRIX_NAME,
+        locations.projection_matrix_location.clone(),
+        Box::new(frame_matrices.projection),
+    )
+    .set_to_context(context)
+}
+
+/// Binds the scene depth texture to its reserved texture unit, along with the viewport size
+/// and the camera's clipping planes, if soft particles are enabled and `material` samples it.  
+/// The material's program must be in use.
+fn set_scene_depth_uniforms(
+    context: &WebGlRenderingContext,
+    scene_depth: Option<&SceneDepth>,
+    camera: &Camera,
+    viewport_size: (u32, u32),
+    material: &Material,
+) -> Result<(), W3DError> {
+    let locations = &material.global_uniform_locations;
+    if let (Some(scene_depth), Some(_)) = (scene_depth, &locations.scene_depth_location) {
+        let mut uniform = Uniform::new_with_location(
+            SCENE_DEPTH_NAME,
+            locations.scene_depth_location.clone(),
+            Box::new(scene_depth.get_texture()),
+        );
+        uniform.set_texture_index(SCENE_DEPTH_TEXTURE_INDEX);
+        uniform.set_to_context(context)?;
+        Uniform::new_with_location(
+            VIEWPORT_SIZE_NAME,
+            locations.viewport_size_location.clone(),
+            Box::new(Vector2::new(viewport_size.0 as f32, viewport_size.1 as f32)),
+        )
+        .set_to_context(context)?;
+        let (near, far) = camera.get_near_far();
+        Uniform::new_with_location(
+            CAMERA_NEAR_FAR_NAME,
+            locations.camera_near_far_location.clone(),
+            Box::new(Vector2::new(near, far)),
+        )
+        .set_to_context(context)?;
+    }
+    Ok(())
+}
+
+/// Returns the batches of a frame in drawing order: the opaque ones, opaque materials then
+/// decals, and the blended ones, transparent materials then faded meshes, each flagged `true`
+/// if faded. Each group is sorted by material id then mesh data id. `pass_of` tells whether a
+/// material is transparent and whether it is a decal.
+fn order_batches<'a, P>(
+    sorted_meshes: SortedMeshes<'a>,
+    faded_meshes: SortedMeshes<'a>,
+    pass_of: P,
+) -> (
+    Vec<(&'a usize, MeshBatches<'a>)>,
+    Vec<((&'a usize, MeshBatches<'a>), bool)>,
+)
+where
+    P: Fn(&usize) -> (bool, bool),
+{
+    let (transparents, opaques): (Vec<_>, Vec<_>) = sorted_meshes
+        .into_iter()
+        .partition(|(material_id, _)| pass_of(material_id).0);
+    let (decals, others): (Vec<_>, Vec<_>) = opaques
+        .into_iter()
+        .partition(|(material_id, _)| pass_of(material_id).1);
+    let blended = transparents
+        .into_iter()
+        .map(|batch| (batch, false))
+        .chain(faded_meshes.into_iter().map(|batch| (batch, true)))
+        .collect();
+    (others.into_iter().chain(decals).collect(), blended)
+}
+
+/// A run of consecutive blended draws, as returned by `sort_back_to_front`.
+enum BlendedRun<'a> {
+    /// Meshes sharing a material, a mesh data and fading, flagged `true` if faded
+    Meshes((&'a usize, MeshBatches<'a>), bool),
+
+    /// Indexes of sprites in the frame's sprite list
+    Sprites(Vec<usize>),
+}
+
+/// Splits blended batches and sprites into runs of consecutive meshes sharing a material, a
+/// mesh data and fading, and of consecutive sprites, drawn back to front. `sprite_depths` are
+/// the view space depths of the sprites, and `depth_of` returns the one of a mesh: view space
+/// looks down -Z, so the farthest draws have the lowest depth. The sort is stable, so meshes
+/// at the same depth keep their batch order, and sprites are drawn after them.
+fn sort_back_to_front<'a, D>(
+    blended_batches: Vec<((&'a usize, MeshBatches<'a>), bool)>,
+    sprite_depths: &[f32],
+    depth_of: D,
+) -> Vec<BlendedRun<'a>>
+where
+    D: Fn(&Transform) -> f32,
+{
+    let mut draws: Vec<(
+        f32,
+        Option<(&'a usize, &'a usize, MeshToDraw<'a>, bool)>,
+        usize,
+    )> = Vec::new();
+    for ((material_id, mesh_batches), faded) in blended_batches {
+        for (mesh_data_id, batch) in mesh_batches {
+            for mesh in batch {
+                draws.push((
+                    depth_of(mesh.1),
+                    Some((material_id, mesh_data_id, mesh, faded)),
+                    0,
+                ));
+            }
+        }
+    }
+    draws.extend(
+        sprite_depths
+            .iter()
+            .enumerate()
+            .map(|(index, depth)| (*depth, None, index)),
+    );
+    draws.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut runs: Vec<BlendedRun> = Vec::new();
+    for (_, mesh, sprite_index) in draws {
+        match (runs.last_mut(), mesh) {
+            (Some(BlendedRun::Sprites(indexes)), None) => indexes.push(sprite_index),
+            (_, None) => runs.push(BlendedRun::Sprites(vec![sprite_index])),
+            (
+                Some(BlendedRun::Meshes((last_material_id, batches), last_faded)),
+                Some((material_id, mesh_data_id, mesh, faded)),
+            ) if *last_material_id == material_id
+                && *last_faded == faded
+                && batches.contains_key(mesh_data_id) =>
+            {
+                batches.get_mut(mesh_data_id).unwrap().push(mesh)
+            }
+            (_, Some((material_id, mesh_data_id, mesh, faded))) => {
+                let mut batches = BTreeMap::new();
+                batches.insert(mesh_data_id, vec![mesh]);
+                runs.push(BlendedRun::Meshes((material_id, batches), faded));
+            }
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDS: [usize; 6] = [0, 1, 2, 3, 4, 5];
+
+    /// Groups `(material id, mesh data id, instance id)` meshes as the `RenderingSystem`
+    /// does, and returns the resulting draw sequence.
+    fn draw_sequence(
+        meshes: &[(usize, usize, usize)],
+        transform: &Transform,
+    ) -> Vec<(usize, usize, usize, bool)> {
+        let mut sorted_meshes: SortedMeshes = BTreeMap::new();
+        let mut faded_meshes: SortedMeshes = BTreeMap::new();
+        for (material_id, mesh_data_id, instance_id) in meshes {
+            let target = if *instance_id == 5 {
+                &mut faded_meshes
+            } else {
+                &mut sorted_meshes
+            };
+            target
+                .entry(&IDS[*material_id])
+                .or_insert_with(BTreeMap::new)
+                .entry(&IDS[*mesh_data_id])
+                .or_insert_with(Vec::new)
+                .push((&IDS[*instance_id], transform, 0, None, 1.));
+        }
+        // Material 3 is transparent, material 4 a decal.
+        let (opaque_batches, blended_batches) =
+            order_batches(sorted_meshes, faded_meshes, |material_id| {
+                (*material_id == 3, *material_id == 4)
+            });
+        let batches = opaque_batches
+            .into_iter()
+            .map(|batch| (batch, false))
+            .chain(
+                sort_back_to_front(blended_batches, &[], |transform| {
+                    transform.get_translation().z
+                })
+                .into_iter()
+                .filter_map(|run| match run {
+                    BlendedRun::Meshes(batch, faded) => Some((batch, faded)),
+                    BlendedRun::Sprites(_) => None,
+                }),
+            );
+        let mut sequence = Vec::new();
+        for ((material_id, mesh_batches), faded) in batches {
+            for (mesh_data_id, meshes) in mesh_batches {
+                for (instance_id, _, _, _, _) in meshes {
+                    sequence.push((*material_id, *mesh_data_id, *instance_id, faded));
+                }
+            }
+        }
+        sequence
+    }
+
+    #[test]
+    fn identical_frames_draw_in_the_same_order() {
+        let transform = Transform::new(&Vector3::zeros(), &Vector3::zeros(), &Vector3::repeat(1.));
+        let mut meshes = vec![
+            (3, 1, 0),
+            (2, 2, 1),
+            (0, 2, 2),
+            (4, 0, 3),
+            (2, 0, 4),
+            (0, 1, 5),
+            (0, 0, 0),
+        ];
+        let first = draw_sequence(&meshes, &transform);
+        assert_eq!(first, draw_sequence(&meshes, &transform));
+        assert_eq!(
+            first,
+            vec![
+                (0, 0, 0, false),
+                (0, 2, 2, false),
+                (2, 0, 4, false),
+                (2, 2, 1, false),
+                (4, 0, 3, false),
+                (3, 1, 0, false),
+                (0, 1, 5, true),
+            ]
+        );
+
+        // Entities joined in another order only change the order within a batch.
+        meshes.reverse();
+        let batches = |sequence: Vec<(usize, usize, usize, bool)>| {
+            let mut batches: Vec<_> = sequence
+                .into_iter()
+                .map(|(material_id, mesh_data_id, _, faded)| (material_id, mesh_data_id, faded))
+                .collect();
+            batches.dedup();
+            batches
+        };
+        assert_eq!(batches(draw_sequence(&meshes, &transform)), batches(first));
+    }
+
+    /// Returns the `(material id, mesh data id, instance id, faded)` meshes of a run, or
+    /// `(sprite index, 0, 0, false)` for each of its sprites.
+    fn run_sequence(run: BlendedRun) -> Vec<(usize, usize, usize, bool)> {
+        match run {
+            BlendedRun::Meshes((material_id, mesh_batches), faded) => mesh_batches
+                .into_iter()
+                .flat_map(|(mesh_data_id, meshes)| {
+                    meshes.into_iter().map(move |(instance_id, _, _, _, _)| {
+                        (*material_id, *mesh_data_id, *instance_id, faded)
+                    })
+                })
+                .collect(),
+            BlendedRun::Sprites(indexes) => indexes
+                .into_iter()
+                .map(|index| (index, 0, 0, false))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn blended_meshes_are_drawn_back_to_front() {
+        let at_depth = |z: f32| {
+            Transform::new(
+                &Vector3::new(0., 0., z),
+                &Vector3::zeros(),
+                &Vector3::repeat(1.),
+            )
+        };
+        let transforms = [
+            at_depth(-5.),
+            at_depth(-1.),
+            at_depth(-3.),
+            at_depth(-4.),
+            at_depth(-4.5),
+        ];
+        let batch = |meshes: &[(usize, usize)]| -> MeshBatches {
+            let mut batches: MeshBatches = BTreeMap::new();
+            for (mesh_data_id, instance_id) in meshes {
+                batches
+                    .entry(&IDS[*mesh_data_id])
+                    .or_insert_with(Vec::new)
+                    .push((&IDS[*instance_id], &transforms[*instance_id], 0, None, 1.));
+            }
+            batches
+        };
+        // Transparent materials 2 and 3, then material 0 faded.
+        let blended = vec![
+            ((&IDS[2], batch(&[(1, 2)])), false),
+            ((&IDS[3], batch(&[(0, 0), (0, 1)])), false),
+            ((&IDS[0], batch(&[(0, 3), (0, 4)])), true),
+        ];
+        let runs = sort_back_to_front(blended, &[], |transform| transform.get_translation().z);
+        let sequence: Vec<Vec<(usize, usize, usize, bool)>> =
+            runs.into_iter().map(run_sequence).collect();
+        assert_eq!(
+            sequence,
+            vec![
+                vec![(3, 0, 0, false)],
+                vec![(0, 0, 4, true), (0, 0, 3, true)],
+                vec![(2, 1, 2, false)],
+                vec![(3, 0, 1, false)],
+            ]
+        );
+    }
+
+    #[test]
+    fn sprites_are_sorted_between_transparent_meshes() {
+        let at_depth = |z: f32| {
+            Transform::new(
+                &Vector3::new(0., 0., z),
+                &Vector3::zeros(),
+                &Vector3::repeat(1.),
+            )
+        };
+        let transforms = [at_depth(-6.), at_depth(-2.), at_depth(-4.)];
+        let mut mesh_batches: MeshBatches = BTreeMap::new();
+        mesh_batches.insert(
+            &IDS[0],
+            (0..3)
+                .map(|instance_id| (&IDS[instance_id], &transforms[instance_id], 0, None, 1.))
+                .collect(),
+        );
+        // Sprite 2 is at the depth of mesh 2, and drawn after it.
+        let sprite_depths = [-5., -7., -4., -3., -1.];
+        let runs = sort_back_to_front(
+            vec![((&IDS[3], mesh_batches), false)],
+            &sprite_depths,
+            |transform| transform.get_translation().z,
+        );
+        let sequence: Vec<Vec<(usize, usize, usize, bool)>> =
+            runs.into_iter().map(run_sequence).collect();
+        assert_eq!(
+            sequence,
+            vec![
+                vec![(1, 0, 0, false)],
+                vec![(3, 0, 0, false)],
+                vec![(0, 0, 0, false)],
+                vec![(3, 0, 2, false)],
+                vec![(2, 0, 0, false), (3, 0, 0, false)],
+                vec![(3, 0, 1, false)],
+                vec![(4, 0, 0, false)],
+            ]
+        );
+    }
 }
@@ -0,0 +1,358 @@
+//! Outlines drawn around selected meshes, with a flat color.
+
+use super::builtin_shaders::compile_builtin_material;
+use super::{Material, Uniform};
+use crate::asset::AssetRegistry;
+use crate::component::Transform;
+use crate::error::W3DError;
+use crate::utils::constants::{NORMAL_BUFFER_NAME, VERTEX_BUFFER_NAME, WORLD_TRANSFORM_NAME};
+use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlUniformLocation};
+
+/// Name of the outline color uniform.
+const OUTLINE_COLOR_NAME: &str = "u_outline_color";
+
+/// Name of the outline thickness uniform.
+const OUTLINE_THICKNESS_NAME: &str = "u_outline_thickness";
+
+/// Stencil value written where a selected mesh is visible.
+const OUTLINE_STENCIL_REFERENCE: i32 = 1;
+
+/// ## OutlineStyle
+///
+/// How outlines look, set per scene.
+#[derive(Clone, Debug)]
+pub struct OutlineStyle {
+    /// Flat color of the outlines
+    pub color: Vector3<f32>,
+
+    /// Distance the outline extends beyond the mesh surface, in world units
+    pub thickness: f32,
+
+    /// If `true`, outlines are masked by the stencil buffer instead of relying on front-face
+    /// culling, which also outlines the inner edges of concave meshes correctly. Ignored if
+    /// the context has no stencil buffer.
+    pub use_stencil: bool,
+}
+
+impl Default for OutlineStyle {
+    fn default() -> OutlineStyle {
+        OutlineStyle {
+            color: Vector3::new(1.0, 0.6, 0.0),
+            thickness: 0.02,
+            use_stencil: false,
+        }
+    }
+}
+
+/// Outline material and the locations of its own uniforms.
+#[derive(Clone)]
+struct OutlineMaterial {
+    material: Rc<RefCell<Material>>,
+    color_location: Option<WebGlUniformLocation>,
+    thickness_location: Option<WebGlUniformLocation>,
+}
+
+/// ## OutlineRenderer
+///
+/// Draws selected meshes a second time after the main pass, pushed along their normals by
+/// the outline thickness and with a flat color. User materials are left untouched.
+///
+/// By default, only the back faces of the pushed meshes are drawn, so that the meshes
+/// themselves hide the outline except around their silhouette. In stencil mode, the meshes
+/// are first drawn into the stencil buffer, and the outline is then drawn wherever they did
+/// not mark it.
+///
+/// Outline programs read the position and normal attributes; one is generated and cached
+/// for each position layout (number of components) encountered. Meshes without normals are
+/// not outlined.
+pub struct OutlineRenderer {
+    /// Outline materials, by number of position components
+    materials: HashMap<i32, OutlineMaterial>,
+
+    /// `true` if the context has a stencil buffer
+    has_stencil: bool,
+}
+
+impl OutlineRenderer {
+    /// Constructor. Programs are compiled lazily.
+    pub fn new(context: &WebGlRenderingContext) -> OutlineRenderer {
+        let stencil_bits = context
+            .get_parameter(WebGlRenderingContext::STENCIL_BITS)
+            .ok()
+            .and_then(|value| value.as_f64())
+            .unwrap_or(0.);
+        OutlineRenderer {
+            materials: HashMap::new(),
+            has_stencil: stencil_bits > 0.,
+        }
+    }
+
+    /// Draws the outlines of the given `(mesh data index, transform)` pairs with `style`.
+    /// `set_camera_uniforms` is called for each outline program used, and world matrices are
+    /// interpolated by `interpolation` in fixed timestep mode.
+    /// Leaves back-face culling on, the stencil test off and the depth function to `LESS`.
+    /// Returns the number of draw calls issued.
+    pub fn render<F>(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        outlined: &[(usize, &Transform)],
+        style: &OutlineStyle,
+        interpolation: Option<f32>,
+        set_camera_uniforms: F,
+    ) -> u32
+    where
+        F: Fn(Rc<RefCell<Material>>),
+    {
+        let use_stencil = style.use_stencil && self.has_stencil;
+        if style.use_stencil && !self.has_stencil {
+            warn_once!("The context has no stencil buffer, outlines are drawn without it.");
+        }
+        context.disable(WebGlRenderingContext::BLEND);
+        context.disable(WebGlRenderingContext::POLYGON_OFFSET_FILL);
+        context.depth_func(WebGlRenderingContext::LESS);
+        let mut draw_calls = 0;
+        if use_stencil {
+            context.enable(WebGlRenderingContext::STENCIL_TEST);
+            context.clear_stencil(0);
+            context.clear(WebGlRenderingContext::STENCIL_BUFFER_BIT);
+            // Marks the pixels where the meshes are visible, writing neither color nor depth.
+            context.stencil_func(
+                WebGlRenderingContext::ALWAYS,
+                OUTLINE_STENCIL_REFERENCE,
+                0xff,
+            );
+            context.stencil_op(
+                WebGlRenderingContext::KEEP,
+                WebGlRenderingContext::KEEP,
+                WebGlRenderingContext::REPLACE,
+            );
+            context.color_mask(false, false, false, false);
+            context.depth_mask(false);
+            context.depth_func(WebGlRenderingContext::LEQUAL);
+            let mask_style = OutlineStyle {
+                thickness: 0.,
+                ..style.clone()
+            };
+            draw_calls += self.draw(
+                context,
+                asset_registry,
+                outlined,
+                &mask_style,
+                interpolation,
+                &set_camera_uniforms,
+            );
+            context.color_mask(true, true, true, true);
+            context.depth_mask(true);
+            context.depth_func(WebGlRenderingContext::LESS);
+            context.stencil_func(
+                WebGlRenderingContext::NOTEQUAL,
+                OUTLINE_STENCIL_REFERENCE,
+                0xff,
+            );
+            context.stencil_op(
+                WebGlRenderingContext::KEEP,
+                WebGlRenderingContext::KEEP,
+                WebGlRenderingContext::KEEP,
+            );
+            context.disable(WebGlRenderingContext::CULL_FACE);
+            draw_calls += self.draw(
+                context,
+                asset_registry,
+                outlined,
+                style,
+                interpolation,
+                &set_camera_uniforms,
+            );
+            context.enable(WebGlRenderingContext::CULL_FACE);
+            context.disable(WebGlRenderingContext::STENCIL_TEST);
+        } else {
+            context.depth_mask(true);
+            context.cull_face(WebGlRenderingContext::FRONT);
+            draw_calls += self.draw(
+                context,
+                asset_registry,
+                outlined,
+                style,
+                interpolation,
+                &set_camera_uniforms,
+            );
+            context.cull_face(WebGlRenderingContext::BACK);
+        }
+        draw_calls
+    }
+
+    /// Draws every outlined mesh with the current state. Returns the number of draw calls.
+    fn draw<F>(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        outlined: &[(usize, &Transform)],
+        style: &OutlineStyle,
+        interpolation: Option<f32>,
+        set_camera_uniforms: &F,
+    ) -> u32
+    where
+        F: Fn(Rc<RefCell<Material>>),
+    {
+        let mut draw_calls = 0;
+        let mut current_components = None;
+        for (mesh_data_id, transform) in outlined {
+            let mesh_data = match asset_registry.get_mesh_data_with_index(*mesh_data_id) {
+                Some(mesh_data) => mesh_data,
+                None => continue,
+            };
+            let mesh_data = mesh_data.borrow();
+            let (position_buffer, normal_buffer) = match (
+                mesh_data.get_buffer(VERTEX_BUFFER_NAME),
+                mesh_data.get_buffer(NORMAL_BUFFER_NAME),
+            ) {
+                (Some(position_buffer), Some(normal_buffer)) => (position_buffer, normal_buffer),
+                _ => {
+                    warn_once!(
+                        "Mesh data {} has no normals and can't be outlined.",
+                        mesh_data.get_id()
+                    );
+                    continue;
+                }
+            };
+            let components = position_buffer.get_data_type().get_size();
+            let outline_material = match self.get_material(context, components) {
+                Ok(outline_material) => outline_material,
+                Err(error) => {
+                    error_once!("Outlines can't be rendered: {}", error);
+                    break;
+                }
+            };
+            let material = outline_material.material;
+            if current_components != Some(components) {
+                context.use_program(material.borrow().get_program().as_ref());
+                material.borrow().disable_unused_attributes(context);
+                set_camera_uniforms(material.clone());
+                Uniform::new_with_location(
+                    OUTLINE_COLOR_NAME,
+                    outline_material.color_location.clone(),
+                    Box::new(style.color),
+                )
+                .set_to_context(context)
+                .ok();
+                Uniform::new_with_location(
+                    OUTLINE_THICKNESS_NAME,
+                    outline_material.thickness_location.clone(),
+                    Box::new(style.thickness),
+                )
+                .set_to_context(context)
+                .ok();
+                current_components = Some(components);
+            }
+            let material = material.borrow();
+            for (name, buffer) in &[
+                (VERTEX_BUFFER_NAME, position_buffer),
+                (NORMAL_BUFFER_NAME, normal_buffer),
+            ] {
+                if let Some(location) = material.get_attribute_location(name) {
+                    buffer.enable_and_bind_attribute(context, location);
+                }
+            }
+            Uniform::new_with_location(
+                WORLD_TRANSFORM_NAME,
+                material
+                    .global_uniform_locations
+                    .world_transform_location
+                    .clone(),
+                Box::new(transform.get_rendered_world_matrix(interpolation)),
+            )
+            .set_to_context(context)
+            .ok();
+            context.draw_elements_with_i32(
+                WebGlRenderingContext::TRIANGLES,
+                mesh_data.get_vertex_count(),
+                WebGlRenderingContext::UNSIGNED_SHORT,
+                0,
+            );
+            draw_calls += 1;
+        }
+        draw_calls
+    }
+
+    /// Returns the outline material for positions with `components` components, compiling
+    /// it the first time.
+    fn get_material(
+        &mut self,
+        context: &WebGlRenderingContext,
+        components: i32,
+    ) -> Result<OutlineMaterial, W3DError> {
+        if !self.materials.contains_key(&components) {
+            let material = compile_builtin_material(
+                context,
+                &outline_vertex_shader(components),
+                OUTLINE_FRAGMENT_SHADER,
+                &format!("__wtvr3d_outline_{}", components),
+                &[VERTEX_BUFFER_NAME, NORMAL_BUFFER_NAME],
+            )?;
+            let program = material.get_program().as_ref().unwrap();
+            let color_location = context.get_uniform_location(program, OUTLINE_COLOR_NAME);
+            let thickness_location = context.get_uniform_location(program, OUTLINE_THICKNESS_NAME);
+            self.materials.insert(
+                components,
+                OutlineMaterial {
+                    material: Rc::new(RefCell::new(material)),
+                    color_location: color_location,
+                    thickness_location: thickness_location,
+                },
+            );
+        }
+        Ok(self.materials[&components].clone())
+    }
+}
+
+/// Fragment shader for outlines.
+const OUTLINE_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform vec3 u_outline_color;
+
+void main() {
+    gl_FragColor = vec4(u_outline_color, 1.0);
+}
+"#;
+
+/// Generates the outline vertex shader for positions with `components` components.
+/// Vertices are pushed along their world-space normal by `u_outline_thickness`.
+fn outline_vertex_shader(components: i32) -> String {
+    let position = match components {
+        1 => "vec4(a_position, 0.0, 0.0, 1.0)",
+        2 => "vec4(a_position, 0.0, 1.0)",
+        3 => "vec4(a_position, 1.0)",
+        _ => "a_position",
+    };
+    let position_type = match components {
+        1 => "float",
+        2 => "vec2",
+        3 => "vec3",
+        _ => "vec4",
+    };
+    format!(
+        r#"
+attribute {} a_position;
+attribute vec3 a_normal;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection_matrix;
+uniform mat4 u_world_transform;
+uniform float u_outline_thickness;
+
+void main() {{
+    vec4 world_position = u_world_transform * {};
+    vec3 world_normal = normalize((u_world_transform * vec4(a_normal, 0.0)).xyz);
+    world_position.xyz += world_normal * u_outline_thickness * world_position.w;
+    gl_Position = u_projection_matrix * u_view_matrix * world_position;
+}}
+"#,
+        position_type, position
+    )
+}
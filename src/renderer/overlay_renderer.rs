@@ -0,0 +1,199 @@
+//! Rendering of `Overlay`s in screen space, after the 3D scene.
+
+use super::builtin_shaders::{
+    compile_builtin_material, OVERLAY_FRAGMENT_SHADER, OVERLAY_VERTEX_SHADER,
+};
+use super::{DynamicBatch, Material, Uniform};
+use crate::asset::AssetRegistry;
+use crate::component::Overlay;
+use crate::error::W3DError;
+use crate::utils::constants::{
+    OPACITY_BUFFER_NAME, PROJECTION_MATRIX_NAME, TEXTURE_NAME, UV_BUFFER_NAME, VERTEX_BUFFER_NAME,
+};
+use nalgebra::Orthographic3;
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlUniformLocation};
+use wtvr3d_file::ShaderDataType;
+
+/// Attributes of an overlay vertex, interleaved in this order.
+const OVERLAY_ATTRIBUTES: [(&str, ShaderDataType); 3] = [
+    (VERTEX_BUFFER_NAME, ShaderDataType::Vector2),
+    (UV_BUFFER_NAME, ShaderDataType::Vector2),
+    (OPACITY_BUFFER_NAME, ShaderDataType::Single),
+];
+
+/// Number of vertices per overlay (two triangles).
+const VERTICES_PER_OVERLAY: usize = 6;
+
+/// ## OverlayRenderer
+///
+/// Holds the built-in overlay `Material` and a single dynamic vertex buffer shared by all
+/// overlays. Every frame, overlays are sorted by order and written to the buffer at once;
+/// consecutive overlays sharing a texture are then drawn with a single draw call.
+///
+/// Overlays are positioned in CSS pixels but snapped to device pixels, using an implicit
+/// orthographic camera matching the canvas' drawing buffer, so that they stay crisp whatever
+/// the `devicePixelRatio`.
+pub struct OverlayRenderer {
+    /// Built-in unlit, alpha blended material
+    material: Rc<RefCell<Material>>,
+
+    /// Location of the texture sampler uniform
+    texture_location: Option<WebGlUniformLocation>,
+
+    /// Interleaved position, texture coordinates and opacity buffers
+    batch: Option<DynamicBatch>,
+
+    /// Vertex data for the current frame, kept to avoid reallocating every frame
+    vertex_data: Vec<f32>,
+}
+
+impl OverlayRenderer {
+    /// Constructor. Compiles the built-in overlay material.
+    pub fn new(context: &WebGlRenderingContext) -> Result<OverlayRenderer, W3DError> {
+        let mut material = compile_builtin_material(
+            context,
+            OVERLAY_VERTEX_SHADER,
+            OVERLAY_FRAGMENT_SHADER,
+            "__wtvr3d_overlays",
+            &[VERTEX_BUFFER_NAME, UV_BUFFER_NAME, OPACITY_BUFFER_NAME],
+        )?;
+        material.set_transparent(true);
+        let texture_location =
+            context.get_uniform_location(material.get_program().as_ref().unwrap(), TEXTURE_NAME);
+        Ok(OverlayRenderer {
+            material: Rc::new(RefCell::new(material)),
+            texture_location: texture_location,
+            batch: None,
+            vertex_data: Vec::new(),
+        })
+    }
+
+    /// Draws `overlays` over whatever has been rendered, in increasing order.
+    ///
+    /// `width` and `height` are the size of the drawing buffer in device pixels, and
+    /// `pixel_ratio` the number of device pixels per CSS pixel.
+    pub fn render(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        overlays: &mut Vec<&Overlay>,
+        width: f32,
+        height: f32,
+        pixel_ratio: f32,
+    ) -> Result<(), W3DError> {
+        overlays.sort_by_key(|overlay| overlay.order);
+        self.vertex_data.clear();
+        for overlay in overlays.iter() {
+            push_overlay_vertices(&mut self.vertex_data, overlay, pixel_ratio);
+        }
+        if self.batch.is_none() {
+            self.batch = Some(DynamicBatch::new(
+                context,
+                &OVERLAY_ATTRIBUTES,
+                self.vertex_data.len(),
+            )?);
+        }
+
+        let material = self.material.borrow();
+        context.use_program(material.get_program().as_ref());
+        material.disable_unused_attributes(context);
+        let projection_matrix =
+            Orthographic3::new(0.0, width, height, 0.0, -1.0, 1.0).to_homogeneous();
+        Uniform::new_with_location(
+            PROJECTION_MATRIX_NAME,
+            material
+                .global_uniform_locations
+                .projection_matrix_location
+                .clone(),
+            Box::new(projection_matrix),
+        )
+        .set_to_context(context)?;
+
+        self.batch
+            .as_mut()
+            .unwrap()
+            .upload_and_bind(context, &material, &self.vertex_data);
+
+        context.disable(WebGlRenderingContext::DEPTH_TEST);
+        context.disable(WebGlRenderingContext::CULL_FACE);
+        context.enable(WebGlRenderingContext::BLEND);
+        context.blend_func(
+            WebGlRenderingContext::SRC_ALPHA,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        let mut first = 0;
+        while first < overlays.len() {
+            let texture_id = overlays[first].get_texture_id();
+            let mut count = 1;
+            while first + count < overlays.len()
+                && overlays[first + count].get_texture_id() == texture_id
+            {
+                count += 1;
+            }
+            self.draw_range(context, asset_registry, *texture_id, first, count);
+            first += count;
+        }
+        context.disable(WebGlRenderingContext::BLEND);
+        context.enable(WebGlRenderingContext::CULL_FACE);
+        context.enable(WebGlRenderingContext::DEPTH_TEST);
+        Ok(())
+    }
+
+    /// Draws `count` overlays using the same texture, starting at the `first` one.
+    fn draw_range(
+        &self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        texture_id: usize,
+        first: usize,
+        count: usize,
+    ) -> () {
+        let texture = match asset_registry.get_texture_with_index(texture_id) {
+            Some(texture) => texture,
+            None => {
+                warn_throttled!(
+                    5000,
+                    "Overlays were not rendered because texture {} is not registered.",
+                    texture_id
+                );
+                return;
+            }
+        };
+        let mut texture_uniform = Uniform::new_with_location(
+            TEXTURE_NAME,
+            self.texture_location.clone(),
+            Box::new(texture),
+        );
+        texture_uniform.set_texture_index(0);
+        if let Err(error) = texture_uniform.set_to_context(context) {
+            warn_throttled!(5000, "{}", error);
+            return;
+        }
+        context.draw_arrays(
+            WebGlRenderingContext::TRIANGLES,
+            (first * VERTICES_PER_OVERLAY) as i32,
+            (count * VERTICES_PER_OVERLAY) as i32,
+        );
+    }
+}
+
+/// Appends the six vertices of an overlay's rectangle to `vertex_data`, in device pixels.
+fn push_overlay_vertices(vertex_data: &mut Vec<f32>, overlay: &Overlay, pixel_ratio: f32) -> () {
+    let left = (overlay.x * pixel_ratio).round();
+    let top = (overlay.y * pixel_ratio).round();
+    let right = ((overlay.x + overlay.width) * pixel_ratio).round();
+    let bottom = ((overlay.y + overlay.height) * pixel_ratio).round();
+    let corners = [
+        (left, top, 0.0, 0.0),
+        (left, bottom, 0.0, 1.0),
+        (right, bottom, 1.0, 1.0),
+        (left, top, 0.0, 0.0),
+        (right, bottom, 1.0, 1.0),
+        (right, top, 1.0, 0.0),
+    ];
+    for (x, y, u, v) in &corners {
+        vertex_data.extend_from_slice(&[*x, *y, *u, *v, overlay.opacity]);
+    }
+}
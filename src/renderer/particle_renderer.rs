@@ -0,0 +1,183 @@
+//! Rendering of `ParticleEmitter`s as additive point sprites.
+
+use super::builtin_shaders::{
+    compile_builtin_material_variant, PARTICLE_FRAGMENT_SHADER, PARTICLE_VERTEX_SHADER,
+};
+use super::{DynamicBatch, LightConfiguration, Material, Uniform};
+use crate::component::{ParticleEmitter, PARTICLE_VERTEX_SIZE};
+use crate::error::W3DError;
+use crate::utils::constants::{
+    COLOR_BUFFER_NAME, SIZE_BUFFER_NAME, VERTEX_BUFFER_NAME, VIEWPORT_HEIGHT_NAME,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlUniformLocation};
+use wtvr3d_file::ShaderDataType;
+
+/// Attributes of a particle vertex, interleaved in this order.
+const PARTICLE_ATTRIBUTES: [(&str, ShaderDataType); 3] = [
+    (VERTEX_BUFFER_NAME, ShaderDataType::Vector3),
+    (COLOR_BUFFER_NAME, ShaderDataType::Vector4),
+    (SIZE_BUFFER_NAME, ShaderDataType::Single),
+];
+
+/// ## ParticleRenderer
+///
+/// Holds the built-in particle `Material` and one dynamic vertex buffer per emitter entity.
+/// Buffers are created the first time an emitter has live particles and are then only
+/// updated with `bufferSubData`, until `release_buffers` deletes the ones of removed emitters.
+/// They are created again if the emitter's `max_particles` changes, e.g. when the id of a
+/// removed entity is reused by a new emitter.
+pub struct ParticleRenderer {
+    /// Built-in additive point sprite material
+    material: Rc<RefCell<Material>>,
+
+    /// Location of the viewport height uniform, used to scale point sizes
+    viewport_height_location: Option<WebGlUniformLocation>,
+
+    /// `max_particles` the buffers were created for, and interleaved position, color and
+    /// size attribute buffers, by emitter entity id
+    batches: HashMap<u32, (usize, DynamicBatch)>,
+}
+
+impl ParticleRenderer {
+    /// Constructor. Compiles the built-in particle material, fading out near opaque meshes
+    /// if `soft_particles` is `true`.
+    pub fn new(
+        context: &WebGlRenderingContext,
+        soft_particles: bool,
+    ) -> Result<ParticleRenderer, W3DError> {
+        let light_config = LightConfiguration {
+            soft_particles: soft_particles,
+            ..Default::default()
+        };
+        let mut material = compile_builtin_material_variant(
+            context,
+            PARTICLE_VERTEX_SHADER,
+            PARTICLE_FRAGMENT_SHADER,
+            "__wtvr3d_particles",
+            &[VERTEX_BUFFER_NAME, COLOR_BUFFER_NAME, SIZE_BUFFER_NAME],
+            &light_config,
+        )?;
+        material.set_transparent(true);
+        let viewport_height_location = context.get_uniform_location(
+            material.get_program().as_ref().unwrap(),
+            VIEWPORT_HEIGHT_NAME,
+        );
+        Ok(ParticleRenderer {
+            material: Rc::new(RefCell::new(material)),
+            viewport_height_location: viewport_height_location,
+            batches: HashMap::new(),
+        })
+    }
+
+    /// Returns the built-in particle material.
+    pub fn get_material(&self) -> Rc<RefCell<Material>> {
+        self.material.clone()
+    }
+
+    /// Uses the particle program and sets up additive blending without depth writes.
+    /// Attribute arrays left enabled by previous draws are disabled, since they would
+    /// point at buffers too small for the particles.
+    pub fn begin(&self, context: &WebGlRenderingContext, viewport_height: f32) -> () {
+        let material = self.material.borrow();
+        context.use_program(material.get_program().as_ref());
+        material.disable_unused_attributes(context);
+        Uniform::new_with_location(
+            VIEWPORT_HEIGHT_NAME,
+            self.viewport_height_location.clone(),
+            Box::new(viewport_height),
+        )
+        .set_to_context(context)
+        .ok();
+        context.enable(WebGlRenderingContext::BLEND);
+        context.blend_func(WebGlRenderingContext::ONE, WebGlRenderingContext::ONE);
+        context.depth_mask(false);
+    }
+
+    /// Restores the blending and depth state changed by `begin`.
+    pub fn end(&self, context: &WebGlRenderingContext) -> () {
+        context.depth_mask(true);
+        context.disable(WebGlRenderingContext::BLEND);
+    }
+
+    /// Uploads the emitter's vertex data to its dynamic buffer and draws its particles.
+    /// Must be called between `begin` and `end`.
+    pub fn draw_emitter(
+        &mut self,
+        context: &WebGlRenderingContext,
+        entity_id: u32,
+        emitter: &ParticleEmitter,
+    ) -> Result<(), W3DError> {
+        let vertex_data = emitter.get_vertex_data();
+        if vertex_data.is_empty() {
+            return Ok(());
+        }
+        let created_for = self
+            .batches
+            .get(&entity_id)
+            .map(|(max_particles, _)| *max_particles);
+        if created_for != Some(emitter.max_particles) {
+            let batch = DynamicBatch::new(
+                context,
+                &PARTICLE_ATTRIBUTES,
+                emitter.max_particles * PARTICLE_VERTEX_SIZE,
+            )?;
+            if let Some((_, previous)) = self
+                .batches
+                .insert(entity_id, (emitter.max_particles, batch))
+            {
+                previous.delete(context);
+            }
+        }
+        let (_, batch) = self.batches.get_mut(&entity_id).unwrap();
+        batch.upload_and_bind(context, &self.material.borrow(), vertex_data);
+        context.draw_arrays(
+            WebGlRenderingContext::POINTS,
+            0,
+            (vertex_data.len() / PARTICLE_VERTEX_SIZE) as i32,
+        );
+        Ok(())
+    }
+
+    /// Deletes the buffers of the emitters whose entity id is not in `emitter_ids`, which
+    /// should hold every existing emitter, drawn or not.
+    pub fn release_buffers(
+        &mut self,
+        context: &WebGlRenderingContext,
+        emitter_ids: &HashSet<u32>,
+    ) -> () {
+        for (_, batch) in take_unused(&mut self.batches, emitter_ids) {
+            batch.delete(context);
+        }
+    }
+}
+
+/// Removes and returns the values of `map` whose key is not in `used`.
+fn take_unused<T>(map: &mut HashMap<u32, T>, used: &HashSet<u32>) -> Vec<T> {
+    let unused: Vec<u32> = map
+        .keys()
+        .filter(|id| !used.contains(id))
+        .copied()
+        .collect();
+    unused.iter().filter_map(|id| map.remove(id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_unused_removes_missing_emitters() {
+        let mut buffers: HashMap<u32, &str> =
+            vec![(1, "a"), (2, "b"), (3, "c")].into_iter().collect();
+        let used: HashSet<u32> = vec![2, 4].into_iter().collect();
+        let mut removed = take_unused(&mut buffers, &used);
+        removed.sort();
+        assert_eq!(removed, vec!["a", "c"]);
+        assert_eq!(buffers.keys().collect::<Vec<_>>(), vec![&2]);
+        assert!(take_unused(&mut buffers, &used).is_empty());
+        assert_eq!(take_unused(&mut buffers, &HashSet::new()), vec!["b"]);
+    }
+}
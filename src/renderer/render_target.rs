@@ -0,0 +1,282 @@
+//! Offscreen framebuffers whose color texture is sampled by materials, e.g. to keep HDR
+//! colors until a tone mapping pass.
+
+use super::capabilities::HALF_FLOAT_TARGET_EXTENSIONS;
+use super::gl_state::get_object;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::ColorFormat;
+use std::rc::Rc;
+use web_sys::{WebGlFramebuffer, WebGlRenderbuffer, WebGlRenderingContext, WebGlTexture};
+
+/// `HALF_FLOAT_OES`, which `web_sys` does not define.
+const HALF_FLOAT_OES: u32 = 0x8D61;
+
+/// Extension needed to filter half-float textures linearly.
+const HALF_FLOAT_LINEAR_EXTENSION: &str = "OES_texture_half_float_linear";
+
+/// ## RenderTarget
+///
+/// Framebuffer with a color texture and a depth renderbuffer. The texture is registered in
+/// the asset registry so that material instances can sample it once the framebuffer is
+/// unbound; a material with `TONE_MAPPING` sampling it converts its HDR colors back to the
+/// 8-bit canvas. The `ToneMapPass` owns one for the main view while HDR rendering is
+/// enabled.
+pub struct RenderTarget {
+    framebuffer: WebGlFramebuffer,
+
+    color_texture: Rc<WebGlTexture>,
+
+    depth_renderbuffer: WebGlRenderbuffer,
+
+    /// Format of the color texture, after the fallbacks
+    format: ColorFormat,
+
+    /// Size of the attachments, in pixels
+    size: (u32, u32),
+}
+
+impl RenderTarget {
+    /// Constructor. Allocates attachments of `size` pixels, with a color texture in `format`
+    /// or in the format it falls back to: see `choose_color_format`. If the driver still
+    /// refuses to render to half floats, falls back to `Rgba8` with a warning.
+    /// Fails if the GL objects can't be created, or if even the `Rgba8` framebuffer is
+    /// incomplete.
+    pub fn new(
+        context: &WebGlRenderingContext,
+        renderable_formats: &[ColorFormat],
+        size: (u32, u32),
+        format: ColorFormat,
+    ) -> Result<RenderTarget, W3DError> {
+        let error = |resource| {
+            W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Could not create the render target.",
+                resource,
+            )
+        };
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or_else(|| error("framebuffer"))?;
+        let color_texture = context.create_texture().ok_or_else(|| error("texture"))?;
+        let depth_renderbuffer = context
+            .create_renderbuffer()
+            .ok_or_else(|| error("renderbuffer"))?;
+        let mut target = RenderTarget {
+            framebuffer: framebuffer,
+            color_texture: Rc::new(color_texture),
+            depth_renderbuffer: depth_renderbuffer,
+            format: choose_color_format(format, renderable_formats),
+            size: size,
+        };
+        if target.format == ColorFormat::Rgba16F {
+            for extension in &HALF_FLOAT_TARGET_EXTENSIONS {
+                context.get_extension(extension).ok();
+            }
+            if target.allocate(context).is_ok() {
+                return Ok(target);
+            }
+            log_warn!("Half-float render targets are incomplete on this device, using RGBA8.");
+            target.format = ColorFormat::Rgba8;
+        }
+        match target.allocate(context) {
+            Ok(()) => Ok(target),
+            Err(error) => {
+                target.delete(context);
+                context.delete_texture(Some(&target.color_texture));
+                Err(error)
+            }
+        }
+    }
+
+    /// Getter for the framebuffer, to be bound to render to the target.
+    pub fn get_framebuffer(&self) -> &WebGlFramebuffer {
+        &self.framebuffer
+    }
+
+    /// Returns the color texture.
+    pub fn get_texture(&self) -> Rc<WebGlTexture> {
+        self.color_texture.clone()
+    }
+
+    /// Getter for the format of the color texture, which may be a fallback of the one
+    /// requested.
+    pub fn get_format(&self) -> ColorFormat {
+        self.format
+    }
+
+    /// Getter for the size of the attachments, in pixels.
+    pub fn get_size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Releases the framebuffer and the depth renderbuffer. The color texture is deleted
+    /// when it's unregistered from the asset registry.
+    pub fn delete(&self, context: &WebGlRenderingContext) -> () {
+        context.delete_framebuffer(Some(&self.framebuffer));
+        context.delete_renderbuffer(Some(&self.depth_renderbuffer));
+    }
+
+    /// Releases the color texture too, for targets whose texture isn't registered in the
+    /// asset registry.
+    pub fn delete_with_texture(&self, context: &WebGlRenderingContext) -> () {
+        self.delete(context);
+        context.delete_texture(Some(&self.color_texture));
+    }
+
+    /// Allocates the attachments in the current format and size, then binds the framebuffer
+    /// bound before again.
+    fn allocate(&self, context: &WebGlRenderingContext) -> Result<(), W3DError> {
+        let (width, height) = (self.size.0 as i32, self.size.1 as i32);
+        let (texture_type, filter) = match self.format {
+            ColorFormat::Rgba16F => {
+                let filter = match context.get_extension(HALF_FLOAT_LINEAR_EXTENSION) {
+                    Ok(Some(_)) => WebGlRenderingContext::LINEAR,
+                    _ => WebGlRenderingContext::NEAREST,
+                };
+                (HALF_FLOAT_OES, filter)
+            }
+            _ => (
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                WebGlRenderingContext::LINEAR,
+            ),
+        };
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&self.color_texture));
+        for (parameter, value) in &[
+            (WebGlRenderingContext::TEXTURE_MIN_FILTER, filter),
+            (WebGlRenderingContext::TEXTURE_MAG_FILTER, filter),
+            (
+                WebGlRenderingContext::TEXTURE_WRAP_S,
+                WebGlRenderingContext::CLAMP_TO_EDGE,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_WRAP_T,
+                WebGlRenderingContext::CLAMP_TO_EDGE,
+            ),
+        ] {
+            context.tex_parameteri(WebGlRenderingContext::TEXTURE_2D, *parameter, *value as i32);
+        }
+        let uploaded = context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGlRenderingContext::TEXTURE_2D,
+                0,
+                WebGlRenderingContext::RGBA as i32,
+                width,
+                height,
+                0,
+                WebGlRenderingContext::RGBA,
+                texture_type,
+                None,
+            );
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, None);
+        uploaded.map_err(|_| {
+            W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Could not allocate the render target texture.",
+                self.format.get_name(),
+            )
+        })?;
+        context.bind_renderbuffer(
+            WebGlRenderingContext::RENDERBUFFER,
+            Some(&self.depth_renderbuffer),
+        );
+        context.renderbuffer_storage(
+            WebGlRenderingContext::RENDERBUFFER,
+            WebGlRenderingContext::DEPTH_COMPONENT16,
+            width,
+            height,
+        );
+        context.bind_renderbuffer(WebGlRenderingContext::RENDERBUFFER, None);
+        let previous_framebuffer: Option<WebGlFramebuffer> =
+            get_object(context, WebGlRenderingContext::FRAMEBUFFER_BINDING);
+        context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        context.framebuffer_texture_2d(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::COLOR_ATTACHMENT0,
+            WebGlRenderingContext::TEXTURE_2D,
+            Some(&self.color_texture),
+            0,
+        );
+        context.framebuffer_renderbuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::DEPTH_ATTACHMENT,
+            WebGlRenderingContext::RENDERBUFFER,
+            Some(&self.depth_renderbuffer),
+        );
+        let status = context.check_framebuffer_status(WebGlRenderingContext::FRAMEBUFFER);
+        context.bind_framebuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            previous_framebuffer.as_ref(),
+        );
+        if status != WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            return Err(W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "The render target framebuffer is incomplete.",
+                &format!("{}, status {:#x}", self.format.get_name(), status),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the format a render target requested in `requested` is created in, given the
+/// formats renderable on the device: `requested` if renderable, `Rgba16F` for
+/// `R11FG11FB10F` if renderable so that colors stay HDR, else `Rgba8`.
+pub fn choose_color_format(
+    requested: ColorFormat,
+    renderable_formats: &[ColorFormat],
+) -> ColorFormat {
+    let renderable = |format| renderable_formats.contains(&format);
+    if renderable(requested) {
+        requested
+    } else if requested == ColorFormat::R11FG11FB10F && renderable(ColorFormat::Rgba16F) {
+        ColorFormat::Rgba16F
+    } else {
+        ColorFormat::Rgba8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renderable_formats_are_kept() {
+        let formats = [ColorFormat::Rgba8, ColorFormat::Rgba16F];
+        assert_eq!(
+            choose_color_format(ColorFormat::Rgba16F, &formats),
+            ColorFormat::Rgba16F
+        );
+        assert_eq!(
+            choose_color_format(ColorFormat::Rgba8, &formats),
+            ColorFormat::Rgba8
+        );
+    }
+
+    #[test]
+    fn missing_formats_fall_back_to_rgba8() {
+        let formats = [ColorFormat::Rgba8];
+        for requested in &[
+            ColorFormat::Rgba16F,
+            ColorFormat::R11FG11FB10F,
+            ColorFormat::Rgb10A2,
+        ] {
+            assert_eq!(
+                choose_color_format(*requested, &formats),
+                ColorFormat::Rgba8
+            );
+        }
+    }
+
+    #[test]
+    fn packed_floats_fall_back_to_half_floats() {
+        let formats = [ColorFormat::Rgba8, ColorFormat::Rgba16F];
+        assert_eq!(
+            choose_color_format(ColorFormat::R11FG11FB10F, &formats),
+            ColorFormat::Rgba16F
+        );
+        assert_eq!(
+            choose_color_format(ColorFormat::Rgb10A2, &formats),
+            ColorFormat::Rgba8
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Offscreen depth of the opaque meshes, sampled by soft particles.
+
+use super::gl_state::get_object;
+use super::{DepthPrepass, Material, SortedMeshes};
+use crate::asset::AssetRegistry;
+use crate::error::{W3DError, W3DErrorKind};
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::{WebGlFramebuffer, WebGlRenderbuffer, WebGlRenderingContext, WebGlTexture};
+
+/// Extension needed to render depth to a texture in WebGL 1.
+pub const DEPTH_TEXTURE_EXTENSION: &str = "WEBGL_depth_texture";
+
+/// ## SceneDepth
+///
+/// Framebuffer whose depth attachment is a texture, filled every frame with the depth of
+/// the opaque meshes before the main pass. The framebuffer is unbound again before the
+/// texture is sampled, so it's never used as an attachment and a sampler at the same time.
+///
+/// WebGL 1 doesn't guarantee depth-only framebuffers to be complete, so it also has a
+/// color renderbuffer that is never read.
+pub struct SceneDepth {
+    framebuffer: WebGlFramebuffer,
+
+    /// Depth texture, sampled as `u_scene_depth`
+    depth_texture: Rc<WebGlTexture>,
+
+    color_renderbuffer: WebGlRenderbuffer,
+
+    /// Depth-only programs, as used by the depth pre-pass
+    depth_pass: DepthPrepass,
+
+    /// Size of the attachments, in pixels
+    size: (u32, u32),
+}
+
+impl SceneDepth {
+    /// Constructor. Fails if the context doesn't support depth textures.
+    /// The attachments are allocated by the first `render`.
+    pub fn new(context: &WebGlRenderingContext) -> Result<SceneDepth, W3DError> {
+        match context.get_extension(DEPTH_TEXTURE_EXTENSION) {
+            Ok(Some(_)) => {}
+            _ => {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::GlResource,
+                    "Depth textures are not supported by the context.",
+                    DEPTH_TEXTURE_EXTENSION,
+                ))
+            }
+        }
+        let error = |resource| {
+            W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "Could not create the scene depth target.",
+                resource,
+            )
+        };
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or_else(|| error("framebuffer"))?;
+        let depth_texture = context.create_texture().ok_or_else(|| error("texture"))?;
+        let color_renderbuffer = context
+            .create_renderbuffer()
+            .ok_or_else(|| error("renderbuffer"))?;
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&depth_texture));
+        for (parameter, value) in &[
+            (
+                WebGlRenderingContext::TEXTURE_MIN_FILTER,
+                WebGlRenderingContext::NEAREST,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_MAG_FILTER,
+                WebGlRenderingContext::NEAREST,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_WRAP_S,
+                WebGlRenderingContext::CLAMP_TO_EDGE,
+            ),
+            (
+                WebGlRenderingContext::TEXTURE_WRAP_T,
+                WebGlRenderingContext::CLAMP_TO_EDGE,
+            ),
+        ] {
+            context.tex_parameteri(WebGlRenderingContext::TEXTURE_2D, *parameter, *value as i32);
+        }
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, None);
+        Ok(SceneDepth {
+            framebuffer: framebuffer,
+            depth_texture: Rc::new(depth_texture),
+            color_renderbuffer: color_renderbuffer,
+            depth_pass: DepthPrepass::new(),
+            size: (0, 0),
+        })
+    }
+
+    /// Returns the depth texture.
+    pub fn get_texture(&self) -> Rc<WebGlTexture> {
+        self.depth_texture.clone()
+    }
+
+    /// Renders the depth of the opaque meshes of `sorted_meshes` to the depth texture, at
+    /// `size`, then binds the framebuffer bound before again.  
+    /// The depth mask must be enabled. See `DepthPrepass::render` for `interpolation`.
+    /// Returns the number of draw calls issued.
+    pub fn render<F>(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        sorted_meshes: &SortedMeshes,
+        size: (u32, u32),
+        interpolation: Option<f32>,
+        set_camera_uniforms: F,
+    ) -> Result<u32, W3DError>
+    where
+        F: Fn(Rc<RefCell<Material>>),
+    {
+        let previous_framebuffer: Option<WebGlFramebuffer> =
+            get_object(context, WebGlRenderingContext::FRAMEBUFFER_BINDING);
+        context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        let allocated = if size != self.size {
+            self.allocate(context, size)
+        } else {
+            Ok(())
+        };
+        let result = allocated.map(|_| {
+            context.clear(WebGlRenderingContext::DEPTH_BUFFER_BIT);
+            self.depth_pass.render(
+                context,
+                asset_registry,
+                sorted_meshes,
+                interpolation,
+                set_camera_uniforms,
+            )
+        });
+        context.bind_framebuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            previous_framebuffer.as_ref(),
+        );
+        result
+    }
+
+    /// Releases the GPU resources.
+    pub fn delete(&self, context: &WebGlRenderingContext) -> () {
+        context.delete_framebuffer(Some(&self.framebuffer));
+        context.delete_texture(Some(&self.depth_texture));
+        context.delete_renderbuffer(Some(&self.color_renderbuffer));
+    }
+
+    /// Resizes the attachments of the framebuffer, which must be bound.
+    fn allocate(
+        &mut self,
+        context: &WebGlRenderingContext,
+        size: (u32, u32),
+    ) -> Result<(), W3DError> {
+        let (width, height) = (size.0 as i32, size.1 as i32);
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&self.depth_texture));
+        context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGlRenderingContext::TEXTURE_2D,
+                0,
+                WebGlRenderingContext::DEPTH_COMPONENT as i32,
+                width,
+                height,
+                0,
+                WebGlRenderingContext::DEPTH_COMPONENT,
+                WebGlRenderingContext::UNSIGNED_SHORT,
+                None,
+            )
+            .map_err(|_| {
+                W3DError::new(
+                    W3DErrorKind::GlResource,
+                    "Could not allocate the scene depth texture.",
+                )
+            })?;
+        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, None);
+        context.framebuffer_texture_2d(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::DEPTH_ATTACHMENT,
+            WebGlRenderingContext::TEXTURE_2D,
+            Some(&self.depth_texture),
+            0,
+        );
+        context.bind_renderbuffer(
+            WebGlRenderingContext::RENDERBUFFER,
+            Some(&self.color_renderbuffer),
+        );
+        context.renderbuffer_storage(
+            WebGlRenderingContext::RENDERBUFFER,
+            WebGlRenderingContext::RGBA4,
+            width,
+            height,
+        );
+        context.bind_renderbuffer(WebGlRenderingContext::RENDERBUFFER, None);
+        context.framebuffer_renderbuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::COLOR_ATTACHMENT0,
+            WebGlRenderingContext::RENDERBUFFER,
+            Some(&self.color_renderbuffer),
+        );
+        let status = context.check_framebuffer_status(WebGlRenderingContext::FRAMEBUFFER);
+        if status != WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            return Err(W3DError::with_source(
+                W3DErrorKind::GlResource,
+                "The scene depth framebuffer is incomplete.",
+                &format!("status {:#x}", status),
+            ));
+        }
+        self.size = size;
+        Ok(())
+    }
+}
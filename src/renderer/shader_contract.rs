@@ -0,0 +1,295 @@
+//! Description of the names and types the engine expects in shaders, exported to JS as plain
+//! objects so that tooling can generate shader boilerplate.
+//!
+//! Everything is built from `utils::constants`, the names the engine actually uses.
+
+use super::debug_info::set;
+use super::LightConfiguration;
+use crate::utils::constants::*;
+use js_sys::{Array, Object};
+use wasm_bindgen::JsValue;
+
+/// Standard vertex attributes of mesh data: `(name, GLSL type, description)`.
+const ATTRIBUTES: &[(&str, &str, &str)] = &[
+    (
+        VERTEX_BUFFER_NAME,
+        "vec3",
+        "Vertex position in local space. Any of float to vec4 is accepted.",
+    ),
+    (NORMAL_BUFFER_NAME, "vec3", "Vertex normal in local space."),
+    (UV_BUFFER_NAME, "vec2", "Texture coordinates."),
+    (COLOR_BUFFER_NAME, "vec4", "Vertex color."),
+];
+
+/// Uniforms set by the engine on every material declaring them: `(name, GLSL type, description)`.
+const UNIFORMS: &[(&str, &str, &str)] = &[
+    (VIEW_MATRIX_NAME, "mat4", "World to view space matrix."),
+    (PROJECTION_MATRIX_NAME, "mat4", "View to clip space matrix."),
+    (
+        WORLD_TRANSFORM_NAME,
+        "mat4",
+        "Local to world space matrix of the drawn object.",
+    ),
+    (
+        CAMERA_POSITION_NAME,
+        "vec3",
+        "Camera position in world space.",
+    ),
+    (
+        AMBIANT_LIGHT_NAME,
+        "vec4",
+        "Flat ambient light color (rgb) and intensity (a).",
+    ),
+    (
+        HEMISPHERE_SKY_COLOR_NAME,
+        "vec3",
+        "Hemisphere light sky color, premultiplied by its intensity.",
+    ),
+    (
+        HEMISPHERE_GROUND_COLOR_NAME,
+        "vec3",
+        "Hemisphere light ground color, premultiplied by its intensity.",
+    ),
+    (
+        HEMISPHERE_UP_NAME,
+        "vec3",
+        "Hemisphere light up direction in world space.",
+    ),
+    (
+        TIME_NAME,
+        "float",
+        "Seconds since the scene's first update. Loses precision after a few hours.",
+    ),
+    (
+        DELTA_TIME_NAME,
+        "float",
+        "Seconds since the previous frame.",
+    ),
+    (
+        TIME_WRAPPED_NAME,
+        "float",
+        "Seconds since the scene's first update, modulo 3600.",
+    ),
+    (
+        UV_TRANSFORM_NAME,
+        "vec4",
+        "Texture coordinates offset (xy) and scale (zw). A zero scale means no transform.",
+    ),
+    (
+        ENVIRONMENT_MAP_NAME,
+        "samplerCube",
+        "Scene environment map. Only set if the ENVIRONMENT_MAP define is present.",
+    ),
+    (
+        EXPOSURE_NAME,
+        "float",
+        "Active camera's exposure. Declared by the engine with the tone_map function.",
+    ),
+    (
+        ENTITY_OPACITY_NAME,
+        "float",
+        "Opacity of the drawn entity, to multiply into the output alpha. 1 unless faded.",
+    ),
+    (
+        ALPHA_CUTOFF_NAME,
+        "float",
+        "Alpha under which fragments should be discarded. Only set if ALPHA_CUTOFF is defined.",
+    ),
+    (
+        SCENE_DEPTH_NAME,
+        "sampler2D",
+        "Depth of the opaque meshes. Declared by the engine with the soft_fade function.",
+    ),
+    (
+        CAMERA_NEAR_FAR_NAME,
+        "vec2",
+        "Active camera's near and far planes. Declared by the engine with soft_fade.",
+    ),
+    (
+        VIEWPORT_SIZE_NAME,
+        "vec2",
+        "Viewport size in pixels. Declared by the engine with soft_fade.",
+    ),
+];
+
+/// Light arrays: `(uniform name, define holding the array size, GLSL struct)`.
+const LIGHT_ARRAYS: &[(&str, &str, &str)] = &[
+    (DIRECTIONAL_LIGHTS_NAME, NUM_DIR_LIGHTS_DEFINE, "Light"),
+    (POINT_LIGHTS_NAME, NUM_POINT_LIGHTS_DEFINE, "Light"),
+    (SPOT_LIGHTS_NAME, NUM_SPOT_LIGHTS_DEFINE, "SpotLight"),
+];
+
+/// Fields of the `Light` GLSL struct: `(name, GLSL type, description)`.
+const LIGHT_FIELDS: &[(&str, &str, &str)] = &[
+    (
+        LIGHT_POSITION_DIRECTION_NAME,
+        "vec3",
+        "World position of point lights, direction of directional lights.",
+    ),
+    (LIGHT_INTENSITY_NAME, "float", "Light intensity."),
+    (LIGHT_COLOR_NAME, "vec3", "Light color."),
+    (
+        LIGHT_ATTENUATION_NAME,
+        "vec4",
+        "(constant, linear, quadratic, range) falloff parameters.",
+    ),
+];
+
+/// Fields the `SpotLight` GLSL struct adds to the `Light` ones: `(name, GLSL type, description)`.
+const SPOT_LIGHT_FIELDS: &[(&str, &str, &str)] = &[
+    (
+        LIGHT_DIRECTION_NAME,
+        "vec3",
+        "Direction the spot light points to.",
+    ),
+    (
+        LIGHT_CONE_NAME,
+        "vec2",
+        "Angle of the cone and blend of its edge, as in the Cone component.",
+    ),
+];
+
+/// Preprocessor symbols handled by the engine: `(name, description)`.
+const DEFINES: &[(&str, &str)] = &[
+    (
+        NUM_DIR_LIGHTS_DEFINE,
+        "Replaced by the number of directional lights. Its own #define is commented out.",
+    ),
+    (
+        NUM_POINT_LIGHTS_DEFINE,
+        "Replaced by the number of point lights. Its own #define is commented out.",
+    ),
+    (
+        NUM_SPOT_LIGHTS_DEFINE,
+        "Replaced by the number of spot lights. Its own #define is commented out.",
+    ),
+    (
+        ENVIRONMENT_MAP_DEFINE,
+        "Defined when the scene has an environment map and the shader mentions it.",
+    ),
+    (
+        ALPHA_CUTOFF_DEFINE,
+        "Defined in the fragment shader of cutout materials.",
+    ),
+    (
+        ALPHA_HASH_DEFINE,
+        "Defined in the fragment shader of alpha hashed materials, along with \
+         float alpha_hash(vec2 position): discard fragments whose alpha is below \
+         alpha_hash(gl_FragCoord.xy), then output an alpha of 1.",
+    ),
+    (
+        TONE_MAPPING_DEFINE,
+        "Defined to the active camera's operator (0: none, 1: Reinhard, 2: ACES) in fragment \
+         shaders mentioning it, along with vec3 tone_map(vec3 color).",
+    ),
+    (
+        HDR_OUTPUT_DEFINE,
+        "Defined in fragment shaders mentioning TONE_MAPPING while HDR rendering is enabled: \
+         tone_map then returns colors as is, and the tone mapping pass applies the exposure \
+         and the operator.",
+    ),
+    (
+        SOFT_PARTICLES_DEFINE,
+        "Defined in fragment shaders mentioning it while soft particles are enabled, along \
+         with float soft_fade(float fade_distance): multiply the alpha by its result to fade \
+         out over fade_distance world units in front of opaque meshes.",
+    ),
+];
+
+/// Builds a JS object describing the shader contract:
+///
+/// - `attributes` and `uniforms`: `{ name, type, description }` objects;
+/// - `lightArrays`: `{ name, sizeDefine, type }` objects, `type` being the GLSL struct;
+/// - `lightStruct`: the fields of the `Light` struct, as `{ name, type, description }` objects;
+/// - `spotLightStruct`: the fields of the `SpotLight` struct, the `Light` ones followed by its
+///   own;
+/// - `defines`: `{ name, description }` objects;
+/// - `environmentMapTextureUnit` and `sceneDepthTextureUnit`: the texture units reserved
+///   for the environment map and the scene depth.
+pub fn get_shader_contract() -> JsValue {
+    let contract = Object::new();
+    set(&contract, "attributes", describe_variables(ATTRIBUTES));
+    set(&contract, "uniforms", describe_variables(UNIFORMS));
+    let light_arrays = Array::new();
+    for (name, size_define, glsl_type) in LIGHT_ARRAYS {
+        let light_array = Object::new();
+        set(&light_array, "name", (*name).into());
+        set(&light_array, "sizeDefine", (*size_define).into());
+        set(&light_array, "type", (*glsl_type).into());
+        light_arrays.push(&light_array);
+    }
+    set(&contract, "lightArrays", light_arrays.into());
+    set(&contract, "lightStruct", describe_variables(LIGHT_FIELDS));
+    let spot_light_fields: Vec<(&str, &str, &str)> = LIGHT_FIELDS
+        .iter()
+        .chain(SPOT_LIGHT_FIELDS)
+        .cloned()
+        .collect();
+    set(
+        &contract,
+        "spotLightStruct",
+        describe_variables(&spot_light_fields),
+    );
+    let defines = Array::new();
+    for (name, description) in DEFINES {
+        let define = Object::new();
+        set(&define, "name", (*name).into());
+        set(&define, "description", (*description).into());
+        defines.push(&define);
+    }
+    set(&contract, "defines", defines.into());
+    set(
+        &contract,
+        "environmentMapTextureUnit",
+        ENVIRONMENT_MAP_TEXTURE_INDEX.into(),
+    );
+    set(
+        &contract,
+        "sceneDepthTextureUnit",
+        SCENE_DEPTH_TEXTURE_INDEX.into(),
+    );
+    contract.into()
+}
+
+/// Builds a `{ directional, point, spot, environmentMap, toneMapping, softParticles }` JS
+/// object from a light configuration, `toneMapping` being the `ToneMapping` value.
+pub fn describe_light_configuration(light_config: &LightConfiguration) -> JsValue {
+    let description = Object::new();
+    set(
+        &description,
+        "directional",
+        (light_config.directional as u32).into(),
+    );
+    set(&description, "point", (light_config.point as u32).into());
+    set(&description, "spot", (light_config.spot as u32).into());
+    set(
+        &description,
+        "environmentMap",
+        light_config.environment_map.into(),
+    );
+    set(
+        &description,
+        "toneMapping",
+        (light_config.tone_mapping as u32).into(),
+    );
+    set(
+        &description,
+        "softParticles",
+        light_config.soft_particles.into(),
+    );
+    set(&description, "hdr", light_config.hdr.into());
+    description.into()
+}
+
+/// Converts `(name, type, description)` triples to an array of objects.
+fn describe_variables(variables: &[(&str, &str, &str)]) -> JsValue {
+    let result = Array::new();
+    for (name, glsl_type, description) in variables {
+        let variable = Object::new();
+        set(&variable, "name", (*name).into());
+        set(&variable, "type", (*glsl_type).into());
+        set(&variable, "description", (*description).into());
+        result.push(&variable);
+    }
+    result.into()
+}
@@ -0,0 +1,260 @@
+//! Rendering of `Sprite`s as camera-facing quads, batched by texture.
+
+use super::builtin_shaders::{
+    compile_builtin_material, SPRITE_FRAGMENT_SHADER, SPRITE_VERTEX_SHADER,
+};
+use super::{DynamicBatch, Material, Uniform};
+use crate::asset::{AssetRegistry, AtlasRegion};
+use crate::component::{Sprite, Transform};
+use crate::error::W3DError;
+use crate::utils::constants::{
+    CORNER_BUFFER_NAME, TEXTURE_NAME, UV_BUFFER_NAME, VERTEX_BUFFER_NAME,
+};
+use nalgebra::{Matrix4, Vector4};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+use web_sys::{WebGlRenderingContext, WebGlUniformLocation};
+use wtvr3d_file::ShaderDataType;
+
+/// Attributes of a sprite vertex, interleaved in this order.
+const SPRITE_ATTRIBUTES: [(&str, ShaderDataType); 3] = [
+    (VERTEX_BUFFER_NAME, ShaderDataType::Vector3),
+    (CORNER_BUFFER_NAME, ShaderDataType::Vector2),
+    (UV_BUFFER_NAME, ShaderDataType::Vector2),
+];
+
+/// Number of vertices per sprite (two triangles).
+const VERTICES_PER_SPRITE: usize = 6;
+
+/// A sprite to draw this frame, with its world position and its depth in view space.
+struct SpriteToDraw<'a> {
+    sprite: &'a Sprite,
+    position: Vector4<f32>,
+    depth: f32,
+}
+
+/// ## SpriteRenderer
+///
+/// Holds the built-in sprite `Material` and one dynamic vertex buffer per texture.
+/// Every frame, all the sprites are sorted back to front, then each run of consecutive
+/// sprites using the same texture is written to that texture's buffer and drawn with a
+/// single draw call.
+pub struct SpriteRenderer {
+    /// Built-in unlit, alpha blended material
+    material: Rc<RefCell<Material>>,
+
+    /// Location of the texture sampler uniform
+    texture_location: Option<WebGlUniformLocation>,
+
+    /// Interleaved position, corner and texture coordinates buffers, by texture index
+    batches: HashMap<usize, DynamicBatch>,
+
+    /// Vertex data of the batch being built, kept to avoid reallocating every frame
+    vertex_data: Vec<f32>,
+}
+
+impl SpriteRenderer {
+    /// Constructor. Compiles the built-in sprite material.
+    pub fn new(context: &WebGlRenderingContext) -> Result<SpriteRenderer, W3DError> {
+        let mut material = compile_builtin_material(
+            context,
+            SPRITE_VERTEX_SHADER,
+            SPRITE_FRAGMENT_SHADER,
+            "__wtvr3d_sprites",
+            &[VERTEX_BUFFER_NAME, CORNER_BUFFER_NAME, UV_BUFFER_NAME],
+        )?;
+        material.set_transparent(true);
+        let texture_location =
+            context.get_uniform_location(material.get_program().as_ref().unwrap(), TEXTURE_NAME);
+        Ok(SpriteRenderer {
+            material: Rc::new(RefCell::new(material)),
+            texture_location: texture_location,
+            batches: HashMap::new(),
+            vertex_data: Vec::new(),
+        })
+    }
+
+    /// Returns the built-in sprite material.
+    pub fn get_material(&self) -> Rc<RefCell<Material>> {
+        self.material.clone()
+    }
+
+    /// Draws `sprites` back to front, with one draw call per run of consecutive sprites
+    /// sharing a texture: sprites with a single texture, or whose textures don't interleave in
+    /// depth, take one draw call per texture.
+    ///
+    /// Depth test is on and depth writes are off, so that sprites blend correctly over the
+    /// geometry drawn before them, including the transparent meshes farther away, which the
+    /// renderer sorts with the sprites.
+    /// The camera uniforms must have been set on the sprite material beforehand.
+    pub fn render(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        view_matrix: &Matrix4<f32>,
+        sprites: &[(&Sprite, &Transform)],
+    ) -> () {
+        let mut sprites_to_draw: Vec<SpriteToDraw> = sprites
+            .iter()
+            .map(|(sprite, transform)| {
+                let position = transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+                SpriteToDraw {
+                    sprite: sprite,
+                    position: position / position.w,
+                    depth: (view_matrix * position).z,
+                }
+            })
+            .collect();
+        let batches = sort_into_batches(&mut sprites_to_draw);
+
+        self.material.borrow().disable_unused_attributes(context);
+        context.enable(WebGlRenderingContext::BLEND);
+        context.blend_func(
+            WebGlRenderingContext::SRC_ALPHA,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        context.depth_mask(false);
+        for (texture_id, range) in batches {
+            let batch = &sprites_to_draw[range];
+            if let Err(error) = self.draw_batch(context, asset_registry, texture_id, batch) {
+                error_throttled!(5000, "{}", error);
+            }
+        }
+        context.depth_mask(true);
+        context.disable(WebGlRenderingContext::BLEND);
+    }
+
+    /// Writes a batch of sprites sharing a texture to its buffer and draws it.
+    fn draw_batch(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &AssetRegistry,
+        texture_id: usize,
+        batch: &[SpriteToDraw],
+    ) -> Result<(), W3DError> {
+        let texture = match asset_registry.get_texture_with_index(texture_id) {
+            Some(texture) => texture,
+            None => {
+                warn_throttled!(
+                    5000,
+                    "Sprites were not rendered because texture {} is not registered.",
+                    texture_id
+                );
+                return Ok(());
+            }
+        };
+        let mut texture_uniform = Uniform::new_with_location(
+            TEXTURE_NAME,
+            self.texture_location.clone(),
+            Box::new(texture),
+        );
+        texture_uniform.set_texture_index(0);
+        texture_uniform.set_to_context(context)?;
+
+        self.vertex_data.clear();
+        for sprite_to_draw in batch {
+            push_sprite_vertices(&mut self.vertex_data, sprite_to_draw);
+        }
+        if !self.batches.contains_key(&texture_id) {
+            let batch = DynamicBatch::new(context, &SPRITE_ATTRIBUTES, self.vertex_data.len())?;
+            self.batches.insert(texture_id, batch);
+        }
+        self.batches.get_mut(&texture_id).unwrap().upload_and_bind(
+            context,
+            &self.material.borrow(),
+            &self.vertex_data,
+        );
+        context.draw_arrays(
+            WebGlRenderingContext::TRIANGLES,
+            0,
+            (batch.len() * VERTICES_PER_SPRITE) as i32,
+        );
+        Ok(())
+    }
+}
+
+/// Sorts sprites back to front and returns the texture id and range of each run of
+/// consecutive sprites sharing a texture, in drawing order.
+fn sort_into_batches(sprites: &mut [SpriteToDraw]) -> Vec<(usize, Range<usize>)> {
+    // View space looks down -Z: the farthest sprites have the lowest depth. The sort is
+    // stable, so sprites at the same depth keep their order from one frame to the next.
+    sprites.sort_by(|a, b| {
+        a.depth
+            .partial_cmp(&b.depth)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut batches: Vec<(usize, Range<usize>)> = Vec::new();
+    for (index, sprite_to_draw) in sprites.iter().enumerate() {
+        let texture_id = *sprite_to_draw.sprite.get_texture_id();
+        match batches.last_mut() {
+            Some((last_texture_id, range)) if *last_texture_id == texture_id => {
+                range.end = index + 1
+            }
+            _ => batches.push((texture_id, index..index + 1)),
+        }
+    }
+    batches
+}
+
+/// Appends the six vertices of a sprite's quad to `vertex_data`.
+fn push_sprite_vertices(vertex_data: &mut Vec<f32>, sprite_to_draw: &SpriteToDraw) -> () {
+    let sprite = sprite_to_draw.sprite;
+    let position = &sprite_to_draw.position;
+    let (half_width, half_height) = (sprite.width / 2.0, sprite.height / 2.0);
+    let AtlasRegion { u0, v0, u1, v1 } = sprite.region;
+    let corners = [
+        (-half_width, -half_height, u0, v1),
+        (half_width, -half_height, u1, v1),
+        (half_width, half_height, u1, v0),
+        (-half_width, -half_height, u0, v1),
+        (half_width, half_height, u1, v0),
+        (-half_width, half_height, u0, v0),
+    ];
+    for (x, y, u, v) in &corners {
+        vertex_data.extend_from_slice(&[position.x, position.y, position.z, *x, *y, *u, *v]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_draw(sprite: &Sprite, depth: f32) -> SpriteToDraw<'_> {
+        SpriteToDraw {
+            sprite: sprite,
+            position: Vector4::new(0., 0., depth, 1.),
+            depth: depth,
+        }
+    }
+
+    #[test]
+    fn batches_follow_depth_across_textures() {
+        let (a, b) = (Sprite::new(1, 1., 1.), Sprite::new(2, 1., 1.));
+        let mut sprites = vec![
+            to_draw(&a, -1.),
+            to_draw(&b, -2.),
+            to_draw(&a, -10.),
+            to_draw(&b, -5.),
+            to_draw(&a, -6.),
+        ];
+        let batches = sort_into_batches(&mut sprites);
+        let depths: Vec<f32> = sprites.iter().map(|sprite| sprite.depth).collect();
+        assert_eq!(depths, vec![-10., -6., -5., -2., -1.]);
+        assert_eq!(batches, vec![(1, 0..2), (2, 2..4), (1, 4..5)]);
+    }
+
+    #[test]
+    fn separated_textures_take_one_batch_each() {
+        let (a, b) = (Sprite::new(1, 1., 1.), Sprite::new(2, 1., 1.));
+        let mut sprites = vec![
+            to_draw(&a, -1.),
+            to_draw(&b, -8.),
+            to_draw(&a, -2.),
+            to_draw(&b, -9.),
+        ];
+        assert_eq!(sort_into_batches(&mut sprites), vec![(2, 0..2), (1, 2..4)]);
+        assert!(sort_into_batches(&mut []).is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Textures decoded asynchronously, uploaded a few at a time between frames.
+
+use crate::asset::AssetRegistry;
+use crate::error::{W3DError, W3DErrorKind};
+use js_sys::Function;
+use std::collections::VecDeque;
+use wasm_bindgen::JsValue;
+use web_sys::{ImageBitmap, WebGlRenderingContext};
+
+/// Number of pixels uploaded per frame, beyond which the next textures wait for the next
+/// frame. A larger texture is still uploaded, on its own.
+const UPLOAD_PIXEL_BUDGET: u32 = 2048 * 2048;
+
+/// A decoded image waiting to be uploaded, and the functions settling its promise.
+struct PendingTexture {
+    /// Id of the placeholder texture to upload to
+    id: String,
+
+    bitmap: ImageBitmap,
+
+    /// Resolves the promise with the texture id
+    resolve: Function,
+
+    /// Rejects the promise with an error
+    reject: Function,
+}
+
+/// ## TextureUploadQueue
+///
+/// Images decoded off the main thread by `createImageBitmap`, uploaded at the start of the
+/// following frames so that decoding never stalls a frame, and so that a burst of textures
+/// is spread over several frames.
+#[derive(Default)]
+pub struct TextureUploadQueue {
+    pending: VecDeque<PendingTexture>,
+}
+
+impl TextureUploadQueue {
+    /// Queues `bitmap` for upload to the texture registered under `id`. `resolve` is called
+    /// with the id once it's uploaded, `reject` with the error if it fails.
+    pub fn push(&mut self, id: String, bitmap: ImageBitmap, resolve: Function, reject: Function) {
+        self.pending.push_back(PendingTexture {
+            id: id,
+            bitmap: bitmap,
+            resolve: resolve,
+            reject: reject,
+        });
+    }
+
+    /// Uploads the queued textures in order, until `UPLOAD_PIXEL_BUDGET` is exceeded, and
+    /// settles their promises. Bitmaps are closed once uploaded.
+    pub fn upload(
+        &mut self,
+        context: &WebGlRenderingContext,
+        asset_registry: &mut AssetRegistry,
+        max_texture_size: u32,
+    ) -> () {
+        let mut uploaded_pixels = 0;
+        while let Some(pending) = self.pending.front() {
+            let pixels = pending.bitmap.width() * pending.bitmap.height();
+            if uploaded_pixels > 0 && uploaded_pixels + pixels > UPLOAD_PIXEL_BUDGET {
+                break;
+            }
+            let pending = self.pending.pop_front().unwrap();
+            let (width, height) = (pending.bitmap.width(), pending.bitmap.height());
+            let result = if width > max_texture_size || height > max_texture_size {
+                Err(W3DError::with_source(
+                    W3DErrorKind::InvalidArgument,
+                    &format!(
+                        "Texture is {}x{}, larger than the maximum size of {} supported by the context.",
+                        width, height, max_texture_size
+                    ),
+                    &pending.id,
+                ))
+            } else {
+                asset_registry.upload_texture_bitmap(context, &pending.id, &pending.bitmap)
+            };
+            pending.bitmap.close();
+            match result {
+                Ok(_) => pending
+                    .resolve
+                    .call1(&JsValue::NULL, &JsValue::from_str(&pending.id)),
+                Err(error) => {
+                    log_error!("{}", error);
+                    pending.reject.call1(&JsValue::NULL, &error.into())
+                }
+            }
+            .ok();
+            uploaded_pixels += pixels;
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Final pass of HDR rendering: the main view is drawn to a half-float target, then tone
+//! mapped to the canvas' 8-bit buffer.
+
+use super::bloom_pass::{BloomPass, BloomSettings};
+use super::builtin_shaders::{
+    compile_builtin_material_variant, FULL_SCREEN_VERTEX_SHADER, TONE_MAP_FRAGMENT_SHADER,
+};
+use super::{Buffer, LightConfiguration, Material, RenderTarget, Uniform};
+use crate::error::W3DError;
+use crate::utils::constants::{EXPOSURE_NAME, TEXTURE_NAME, VERTEX_BUFFER_NAME};
+use crate::utils::{ColorFormat, ToneMapping};
+use web_sys::{WebGlFramebuffer, WebGlRenderingContext, WebGlUniformLocation};
+use wtvr3d_file::ShaderDataType;
+
+/// Corners of a triangle covering the viewport, in clip space.
+const FULL_SCREEN_TRIANGLE: [f32; 6] = [-1.0, -1.0, 3.0, -1.0, -1.0, 3.0];
+
+/// Name of the bloom texture uniform.
+const BLOOM_TEXTURE_NAME: &str = "u_bloom_texture";
+
+/// Name of the bloom intensity uniform.
+const BLOOM_INTENSITY_NAME: &str = "u_bloom_intensity";
+
+/// Preprocessor symbol defined in the tone mapping shader when bloom is enabled.
+const BLOOM_DEFINE: &str = "USE_BLOOM";
+
+/// Tone mapping material and the locations of its own uniforms.
+struct ToneMapMaterial {
+    material: Material,
+    texture_location: Option<WebGlUniformLocation>,
+    bloom_texture_location: Option<WebGlUniformLocation>,
+    bloom_intensity_location: Option<WebGlUniformLocation>,
+
+    /// Operator the material is compiled for
+    tone_mapping: ToneMapping,
+
+    /// Whether the material is compiled to add the bloom texture
+    bloom: bool,
+}
+
+/// ## ToneMapPass
+///
+/// Holds the `Rgba16F` target the main view is rendered to while HDR rendering is enabled,
+/// and the built-in material converting it to the 8-bit destination with the camera's
+/// exposure and tone mapping operator. Materials mentioning `TONE_MAPPING` are compiled
+/// with `HDR_OUTPUT` meanwhile, so that their colors reach the target as is.
+/// With bloom, a `BloomPass` first blurs the brightest colors of the target, which are then
+/// added to the scene before tone mapping.
+pub struct ToneMapPass {
+    /// Target the main view is rendered to, resized with the destination
+    target: RenderTarget,
+
+    /// Built-in material, compiled for the camera's operator
+    material: ToneMapMaterial,
+
+    /// Bloom chain, `Some` if bloom is enabled
+    bloom: Option<BloomPass>,
+
+    /// Vertices of the full screen triangle
+    buffer: Buffer,
+}
+
+impl ToneMapPass {
+    /// Constructor. Allocates a target of `size` pixels, in `Rgba16F` if renderable and
+    /// `Rgba8` otherwise, and compiles the built-in material for `tone_mapping`.
+    pub fn new(
+        context: &WebGlRenderingContext,
+        renderable_formats: &[ColorFormat],
+        size: (u32, u32),
+        tone_mapping: ToneMapping,
+    ) -> Result<ToneMapPass, W3DError> {
+        let material = ToneMapPass::compile(context, tone_mapping, false)?;
+        let buffer = Buffer::from_f32_data_view(
+            context,
+            VERTEX_BUFFER_NAME,
+            ShaderDataType::Vector2,
+            &FULL_SCREEN_TRIANGLE,
+            None,
+        )?;
+        let target = RenderTarget::new(
+            context,
+            renderable_formats,
+            (size.0.max(1), size.1.max(1)),
+            ColorFormat::Rgba16F,
+        );
+        let target = match target {
+            Ok(target) => target,
+            Err(error) => {
+                buffer.delete(context);
+                return Err(error);
+            }
+        };
+        Ok(ToneMapPass {
+            target: target,
+            material: material,
+            bloom: None,
+            buffer: buffer,
+        })
+    }
+
+    /// Getter for the format of the target, `Rgba8` if half floats aren't renderable.
+    pub fn get_format(&self) -> ColorFormat {
+        self.target.get_format()
+    }
+
+    /// Enables bloom with `settings`, or disables it if `None`.
+    /// Fails if the bloom chain can't be created, e.g. without half-float targets, in which
+    /// case bloom stays disabled.
+    pub fn set_bloom(
+        &mut self,
+        context: &WebGlRenderingContext,
+        renderable_formats: &[ColorFormat],
+        settings: Option<BloomSettings>,
+    ) -> Result<(), W3DError> {
+        match (settings, &mut self.bloom) {
+            (Some(settings), Some(bloom)) => bloom.settings = settings,
+            (Some(settings), None) => {
+                self.bloom = Some(BloomPass::new(
+                    context,
+                    renderable_formats,
+                    self.target.get_size(),
+                    settings,
+                )?);
+            }
+            (None, Some(bloom)) => {
+                bloom.delete(context);
+                self.bloom = None;
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    /// Getter for the bloom settings, `None` if bloom is disabled.
+    pub fn get_bloom(&self) -> Option<BloomSettings> {
+        self.bloom.as_ref().map(|bloom| bloom.settings)
+    }
+
+    /// Binds the target, reallocated first with the bloom chain if its size isn't `size`,
+    /// and sets a viewport covering it.
+    /// Fails if the targets could not be reallocated, in which case nothing is bound.
+    pub fn bind_target(
+        &mut self,
+        context: &WebGlRenderingContext,
+        renderable_formats: &[ColorFormat],
+        size: (u32, u32),
+    ) -> Result<(), W3DError> {
+        let size = (size.0.max(1), size.1.max(1));
+        if self.target.get_size() != size {
+            let target =
+                RenderTarget::new(context, renderable_formats, size, self.target.get_format())?;
+            self.target.delete_with_texture(context);
+            self.target = target;
+        }
+        if let Some(bloom) = &mut self.bloom {
+            bloom.resize(context, renderable_formats, size)?;
+        }
+        context.bind_framebuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            Some(self.target.get_framebuffer()),
+        );
+        context.viewport(0, 0, size.0 as i32, size.1 as i32);
+        Ok(())
+    }
+
+    /// Renders the bloom chain if enabled, then draws the target's colors to `destination`,
+    /// a framebuffer of `size` pixels, multiplied by `exposure` and converted with
+    /// `tone_mapping`. The material is compiled again if the operator changed or bloom was
+    /// toggled.
+    pub fn render(
+        &mut self,
+        context: &WebGlRenderingContext,
+        destination: Option<&WebGlFramebuffer>,
+        size: (u32, u32),
+        tone_mapping: ToneMapping,
+        exposure: f32,
+    ) -> Result<(), W3DError> {
+        let bloom_texture = match &self.bloom {
+            Some(bloom) => {
+                let buffer = &self.buffer;
+                bloom.render(context, self.target.get_texture(), |material| {
+                    bind_full_screen_triangle(context, material, buffer)
+                })?
+            }
+            None => None,
+        };
+        context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, destination);
+        context.viewport(0, 0, size.0 as i32, size.1 as i32);
+        let bloom = bloom_texture.is_some();
+        if tone_mapping != self.material.tone_mapping || bloom != self.material.bloom {
+            let material = ToneMapPass::compile(context, tone_mapping, bloom)?;
+            self.material.material.delete_program(context);
+            self.material = material;
+        }
+        let material = &self.material;
+        context.use_program(material.material.get_program().as_ref());
+        bind_full_screen_triangle(context, &material.material, &self.buffer);
+        let mut texture_uniform = Uniform::new_with_location(
+            TEXTURE_NAME,
+            material.texture_location.clone(),
+            Box::new(self.target.get_texture()),
+        );
+        texture_uniform.set_texture_index(0);
+        texture_uniform.set_to_context(context)?;
+        if let (Some(bloom_texture), Some(bloom)) = (bloom_texture, &self.bloom) {
+            let mut bloom_texture_uniform = Uniform::new_with_location(
+                BLOOM_TEXTURE_NAME,
+                material.bloom_texture_location.clone(),
+                Box::new(bloom_texture),
+            );
+            bloom_texture_uniform.set_texture_index(1);
+            bloom_texture_uniform.set_to_context(context)?;
+            Uniform::new_with_location(
+                BLOOM_INTENSITY_NAME,
+                material.bloom_intensity_location.clone(),
+                Box::new(bloom.settings.intensity),
+            )
+            .set_to_context(context)?;
+        }
+        Uniform::new_with_location(
+            EXPOSURE_NAME,
+            material
+                .material
+                .global_uniform_locations
+                .exposure_location
+                .clone(),
+            Box::new(exposure),
+        )
+        .set_to_context(context)?;
+        context.disable(WebGlRenderingContext::DEPTH_TEST);
+        context.disable(WebGlRenderingContext::BLEND);
+        context.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+        context.enable(WebGlRenderingContext::DEPTH_TEST);
+        Ok(())
+    }
+
+    /// Releases the targets, the programs of the materials and the vertex buffer.
+    pub fn delete(&mut self, context: &WebGlRenderingContext) -> () {
+        self.target.delete_with_texture(context);
+        self.material.material.delete_program(context);
+        if let Some(bloom) = &mut self.bloom {
+            bloom.delete(context);
+        }
+        self.buffer.delete(context);
+    }
+
+    /// Compiles the built-in material for `tone_mapping`, adding the bloom texture if `bloom`
+    /// is `true`, and looks up its own uniforms.
+    fn compile(
+        context: &WebGlRenderingContext,
+        tone_mapping: ToneMapping,
+        bloom: bool,
+    ) -> Result<ToneMapMaterial, W3DError> {
+        let light_config = LightConfiguration {
+            tone_mapping: tone_mapping,
+            ..Default::default()
+        };
+        let fragment_shader = if bloom {
+            format!("#define {}\n{}", BLOOM_DEFINE, TONE_MAP_FRAGMENT_SHADER)
+        } else {
+            TONE_MAP_FRAGMENT_SHADER.to_owned()
+        };
+        let material = compile_builtin_material_variant(
+            context,
+            FULL_SCREEN_VERTEX_SHADER,
+            &fragment_shader,
+            "__wtvr3d_tone_mapping",
+            &[VERTEX_BUFFER_NAME],
+            &light_config,
+        )?;
+        let program = material.get_program().as_ref().unwrap();
+        Ok(ToneMapMaterial {
+            texture_location: context.get_uniform_location(program, TEXTURE_NAME),
+            bloom_texture_location: context.get_uniform_location(program, BLOOM_TEXTURE_NAME),
+            bloom_intensity_location: context.get_uniform_location(program, BLOOM_INTENSITY_NAME),
+            material: material,
+            tone_mapping: tone_mapping,
+            bloom: bloom,
+        })
+    }
+}
+
+/// Binds the vertices of the full screen triangle in `buffer` to the attributes of
+/// `material`, whose program must be in use, and disables the other attribute arrays.
+fn bind_full_screen_triangle(
+    context: &WebGlRenderingContext,
+    material: &Material,
+    buffer: &Buffer,
+) -> () {
+    material.disable_unused_attributes(context);
+    if let Some(location) = material.get_attribute_location(buffer.get_attribute_name()) {
+        buffer.enable_and_bind_attribute(context, location);
+    }
+}
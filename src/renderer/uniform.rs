@@ -14,9 +14,13 @@
 //!     - `Matrix2<f32>`
 //!     - `Matrix3<f32>`
 //!     - `Matrix4<f32>`
+//!     - `Rc<WebGlTexture>` and `Rc<CubeTexture>`, with a texture index
+//!     - `TextureReference`, a texture referenced by registry id, with a texture index
 
+use crate::error::{W3DError, W3DErrorKind};
 use crate::renderer::LightConfiguration;
 use nalgebra::base::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use std::cell::RefCell;
 use std::rc::Rc;
 use std::slice;
 use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlTexture, WebGlUniformLocation};
@@ -84,9 +88,15 @@ impl Uniform {
         }
     }
 
+    /// Forgets the uniform location, so that it's looked up again by `lookup_location`.  
+    /// Needed when the program it was looked up for is replaced.
+    pub fn reset_location(&mut self) -> () {
+        self.location = None;
+    }
+
     /// Sets the uniform to the current WebGlContext (to be called at render time);  
     /// The appropriate WebGlProgram must have been set beforehand.
-    pub fn set_to_context(&self, context: &WebGlRenderingContext) -> Result<(), String> {
+    pub fn set_to_context(&self, context: &WebGlRenderingContext) -> Result<(), W3DError> {
         let result = self.value.set_to_context_at_location(
             context,
             if let Some(loc) = &self.location {
@@ -96,8 +106,12 @@ impl Uniform {
             },
             self.texture_index,
         );
-        if let Err(_) = result {
-            Err("Uniform couldn't be set".to_string())
+        if let Err(error) = result {
+            Err(W3DError::with_source(
+                W3DErrorKind::Uniform,
+                &format!("Uniform {} couldn't be set", self.name),
+                error.get_reason(),
+            ))
         } else {
             result
         }
@@ -113,7 +127,12 @@ pub trait UniformValue {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String>;
+    ) -> Result<(), W3DError>;
+
+    /// Returns this value if it references a texture by id, to resolve it before it's set.
+    fn get_texture_reference(&self) -> Option<&TextureReference> {
+        None
+    }
 }
 
 impl UniformValue for f32 {
@@ -122,7 +141,7 @@ impl UniformValue for f32 {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         context.uniform1fv_with_f32_array(location, slice::from_ref(self));
         Ok(())
     }
@@ -134,7 +153,7 @@ impl UniformValue for &[f32] {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Single, *self).set_to_context_at_location(
             context,
             location,
@@ -149,9 +168,10 @@ impl UniformValue for Rc<WebGlTexture> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         match texture_number {
-            None => Err(String::from(
+            None => Err(W3DError::new(
+                W3DErrorKind::Uniform,
                 "You must provide a texture number for Texture uniforms",
             )),
             Some(number) => {
@@ -174,13 +194,91 @@ impl UniformValue for Rc<WebGlTexture> {
     }
 }
 
+/// A texture referenced by the id it is registered under, rather than owned. It is resolved
+/// against the `AssetRegistry` each time the uniforms holding it are applied, so replacing
+/// the texture registered under that id updates every uniform referencing it.
+pub struct TextureReference {
+    /// Id of the referenced texture
+    id: String,
+
+    /// Texture the id was last resolved to, `None` if it wasn't or couldn't be
+    resolved: RefCell<Option<Rc<WebGlTexture>>>,
+}
+
+impl TextureReference {
+    /// Constructor. The reference is unresolved until `resolve` is called.
+    pub fn new(id: &str) -> TextureReference {
+        TextureReference {
+            id: id.to_owned(),
+            resolved: RefCell::new(None),
+        }
+    }
+
+    /// Getter for the id of the referenced texture
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// Sets the texture the id currently resolves to.
+    pub fn resolve(&self, texture: Option<Rc<WebGlTexture>>) -> () {
+        *self.resolved.borrow_mut() = texture;
+    }
+}
+
+impl UniformValue for TextureReference {
+    fn set_to_context_at_location(
+        &self,
+        context: &WebGlRenderingContext,
+        location: Option<&WebGlUniformLocation>,
+        texture_number: Option<u32>,
+    ) -> Result<(), W3DError> {
+        match &*self.resolved.borrow() {
+            Some(texture) => texture.set_to_context_at_location(context, location, texture_number),
+            None => Err(W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Referenced texture could not be resolved.",
+                &self.id,
+            )),
+        }
+    }
+
+    fn get_texture_reference(&self) -> Option<&TextureReference> {
+        Some(self)
+    }
+}
+
+/// A `WebGlTexture` holding the six faces of a cube map, bound to `TEXTURE_CUBE_MAP`.
+pub struct CubeTexture(pub WebGlTexture);
+
+impl UniformValue for Rc<CubeTexture> {
+    fn set_to_context_at_location(
+        &self,
+        context: &WebGlRenderingContext,
+        location: Option<&WebGlUniformLocation>,
+        texture_number: Option<u32>,
+    ) -> Result<(), W3DError> {
+        match texture_number {
+            None => Err(W3DError::new(
+                W3DErrorKind::Uniform,
+                "You must provide a texture number for Texture uniforms",
+            )),
+            Some(number) => {
+                context.active_texture(get_texture_pointer(number));
+                context.bind_texture(WebGlRenderingContext::TEXTURE_CUBE_MAP, Some(&self.0));
+                context.uniform1i(location, number as i32);
+                Ok(())
+            }
+        }
+    }
+}
+
 impl UniformValue for (ShaderDataType, &[f32]) {
     fn set_to_context_at_location(
         &self,
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         match self.0 {
             ShaderDataType::Single => {
                 context.uniform1fv_with_f32_array(location, self.1);
@@ -210,7 +308,10 @@ impl UniformValue for (ShaderDataType, &[f32]) {
                 context.uniform_matrix4fv_with_f32_array(location, false, self.1);
                 Ok(())
             }
-            _ => Err(String::from("Invalid value supplied to uniform")),
+            _ => Err(W3DError::new(
+                W3DErrorKind::Uniform,
+                "Invalid value supplied to uniform",
+            )),
         }
     }
 }
@@ -221,7 +322,7 @@ impl UniformValue for (ShaderDataType, Vec<f32>) {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (self.0, self.1.as_slice()).set_to_context_at_location(context, location, texture_number)
     }
 }
@@ -232,7 +333,7 @@ impl UniformValue for i32 {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         context.uniform1iv_with_i32_array(location, slice::from_ref(self));
         Ok(())
     }
@@ -244,7 +345,7 @@ impl UniformValue for &[i32] {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Single, *self).set_to_context_at_location(
             context,
             location,
@@ -259,7 +360,7 @@ impl UniformValue for (ShaderDataType, &[i32]) {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         match self.0 {
             ShaderDataType::Single => {
                 context.uniform1iv_with_i32_array(location, self.1);
@@ -277,7 +378,10 @@ impl UniformValue for (ShaderDataType, &[i32]) {
                 context.uniform4iv_with_i32_array(location, self.1);
                 Ok(())
             }
-            _ => Err(String::from("Invalid value supplied to uniform")),
+            _ => Err(W3DError::new(
+                W3DErrorKind::Uniform,
+                "Invalid value supplied to uniform",
+            )),
         }
     }
 }
@@ -288,7 +392,7 @@ impl UniformValue for (ShaderDataType, &[i16]) {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         let mut new_vec = Vec::new();
         for i in self.1 {
             new_vec.push(*i as i32);
@@ -302,7 +406,7 @@ impl UniformValue for (ShaderDataType, Vec<i16>) {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (self.0, self.1.as_slice()).set_to_context_at_location(context, location, texture_number)
     }
 }
@@ -313,7 +417,7 @@ impl UniformValue for (ShaderDataType, &[u8]) {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         let mut new_vec = Vec::new();
         for i in self.1 {
             new_vec.push(*i as i32);
@@ -327,7 +431,7 @@ impl UniformValue for (ShaderDataType, Vec<u8>) {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (self.0, self.1.as_slice()).set_to_context_at_location(context, location, texture_number)
     }
 }
@@ -338,7 +442,7 @@ impl UniformValue for Vector2<f32> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Vector2, self.as_slice()).set_to_context_at_location(
             context,
             location,
@@ -353,7 +457,7 @@ impl UniformValue for &[Vector2<f32>] {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         let mut vec: Vec<f32> = Vec::new();
         for vector in self.iter() {
             vec.splice(self.len()..self.len(), vector.as_slice().iter().cloned());
@@ -372,7 +476,7 @@ impl UniformValue for Vector3<f32> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Vector3, self.as_slice()).set_to_context_at_location(
             context,
             location,
@@ -387,7 +491,7 @@ impl UniformValue for &[Vector3<f32>] {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         let mut vec: Vec<f32> = Vec::new();
         for vector in self.iter() {
             vec.splice(self.len()..self.len(), vector.as_slice().iter().cloned());
@@ -406,7 +510,7 @@ impl UniformValue for Vector4<f32> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Vector4, self.as_slice()).set_to_context_at_location(
             context,
             location,
@@ -421,7 +525,7 @@ impl UniformValue for &[Vector4<f32>] {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         let mut vec: Vec<f32> = Vec::new();
         for vector in self.iter() {
             vec.splice(self.len()..self.len(), vector.as_slice().iter().cloned());
@@ -437,7 +541,7 @@ impl UniformValue for Matrix2<f32> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Matrix2, self.as_slice())
             .set_to_context_at_location(context, location, None)
     }
@@ -448,7 +552,7 @@ impl UniformValue for Matrix3<f32> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Matrix3, self.as_slice())
             .set_to_context_at_location(context, location, None)
     }
@@ -459,7 +563,7 @@ impl UniformValue for Matrix4<f32> {
         context: &WebGlRenderingContext,
         location: Option<&WebGlUniformLocation>,
         _texture_number: Option<u32>,
-    ) -> Result<(), String> {
+    ) -> Result<(), W3DError> {
         (ShaderDataType::Matrix4, self.as_slice())
             .set_to_context_at_location(context, location, None)
     }
@@ -476,9 +580,39 @@ pub struct GlobalUniformLocations {
 
     pub ambiant_light_location: Option<WebGlUniformLocation>,
 
+    pub hemisphere_sky_color_location: Option<WebGlUniformLocation>,
+
+    pub hemisphere_ground_color_location: Option<WebGlUniformLocation>,
+
+    pub hemisphere_up_location: Option<WebGlUniformLocation>,
+
+    pub time_location: Option<WebGlUniformLocation>,
+
+    pub delta_time_location: Option<WebGlUniformLocation>,
+
+    pub time_wrapped_location: Option<WebGlUniformLocation>,
+
+    /// Locations of the fields of each `u_point_lights` element
     pub point_lights_locations: Vec<LightUniformLocations>,
 
+    /// Locations of the fields of each `u_dir_lights` element
     pub directional_lights_locations: Vec<LightUniformLocations>,
+
+    /// Locations of the fields of each `u_spot_lights` element
+    pub spot_lights_locations: Vec<LightUniformLocations>,
+
+    /// Light configuration the light location tables were built for
+    light_configuration: Option<LightConfiguration>,
+
+    pub environment_map_location: Option<WebGlUniformLocation>,
+
+    pub exposure_location: Option<WebGlUniformLocation>,
+
+    pub scene_depth_location: Option<WebGlUniformLocation>,
+
+    pub camera_near_far_location: Option<WebGlUniformLocation>,
+
+    pub viewport_size_location: Option<WebGlUniformLocation>,
 }
 
 impl GlobalUniformLocations {
@@ -491,9 +625,29 @@ impl GlobalUniformLocations {
 
             ambiant_light_location: None,
 
+            hemisphere_sky_color_location: None,
+            hemisphere_ground_color_location: None,
+            hemisphere_up_location: None,
+
+            time_location: None,
+            delta_time_location: None,
+            time_wrapped_location: None,
+
             point_lights_locations: Default::default(),
 
             directional_lights_locations: Default::default(),
+
+            spot_lights_locations: Default::default(),
+
+            light_configuration: None,
+
+            environment_map_location: None,
+
+            exposure_location: None,
+
+            scene_depth_location: None,
+            camera_near_far_location: None,
+            viewport_size_location: None,
         }
     }
     pub fn lookup_locations(
@@ -525,29 +679,108 @@ impl GlobalUniformLocations {
                 context.get_uniform_location(pg, crate::utils::constants::AMBIANT_LIGHT_NAME)
         }
 
-        self.directional_lights_locations.clear();
-        for i in 0..light_config.directional {
-            let mut location: LightUniformLocations = Default::default();
-            location.lookup_locations(
-                crate::utils::constants::DIRECTIONAL_LIGHTS_NAME,
-                Some(i),
-                context,
-                pg,
-            );
-            self.directional_lights_locations.push(location);
+        if self.hemisphere_sky_color_location == None {
+            self.hemisphere_sky_color_location =
+                context.get_uniform_location(pg, crate::utils::constants::HEMISPHERE_SKY_COLOR_NAME)
+        }
+        if self.hemisphere_ground_color_location == None {
+            self.hemisphere_ground_color_location = context
+                .get_uniform_location(pg, crate::utils::constants::HEMISPHERE_GROUND_COLOR_NAME)
+        }
+        if self.hemisphere_up_location == None {
+            self.hemisphere_up_location =
+                context.get_uniform_location(pg, crate::utils::constants::HEMISPHERE_UP_NAME)
+        }
+
+        if self.time_location == None {
+            self.time_location =
+                context.get_uniform_location(pg, crate::utils::constants::TIME_NAME)
+        }
+        if self.delta_time_location == None {
+            self.delta_time_location =
+                context.get_uniform_location(pg, crate::utils::constants::DELTA_TIME_NAME)
+        }
+        if self.time_wrapped_location == None {
+            self.time_wrapped_location =
+                context.get_uniform_location(pg, crate::utils::constants::TIME_WRAPPED_NAME)
+        }
+
+        if self.environment_map_location == None {
+            self.environment_map_location =
+                context.get_uniform_location(pg, crate::utils::constants::ENVIRONMENT_MAP_NAME)
+        }
+
+        if self.exposure_location == None {
+            self.exposure_location =
+                context.get_uniform_location(pg, crate::utils::constants::EXPOSURE_NAME)
+        }
+
+        if self.scene_depth_location == None {
+            self.scene_depth_location =
+                context.get_uniform_location(pg, crate::utils::constants::SCENE_DEPTH_NAME)
+        }
+        if self.camera_near_far_location == None {
+            self.camera_near_far_location =
+                context.get_uniform_location(pg, crate::utils::constants::CAMERA_NEAR_FAR_NAME)
         }
+        if self.viewport_size_location == None {
+            self.viewport_size_location =
+                context.get_uniform_location(pg, crate::utils::constants::VIEWPORT_SIZE_NAME)
+        }
+
+        self.lookup_light_locations(context, program, light_config);
+    }
 
-        self.point_lights_locations.clear();
-        for i in 0..light_config.point {
-            let mut location: LightUniformLocations = Default::default();
-            location.lookup_locations(
+    /// Builds the light location tables, with one element per light of `light_config`, by
+    /// querying the location of every field of `u_dir_lights[i]`, `u_point_lights[i]` and
+    /// `u_spot_lights[i]`.  
+    /// Does nothing if the tables were already built for this configuration, so it can be
+    /// called every frame; they are rebuilt whenever the number of lights changes.
+    pub fn lookup_light_locations(
+        &mut self,
+        context: &WebGlRenderingContext,
+        program: &Option<WebGlProgram>,
+        light_config: &LightConfiguration,
+    ) -> () {
+        self.build_light_locations(light_config, |uniform_name| {
+            context.get_uniform_location(program.as_ref().unwrap(), uniform_name)
+        });
+    }
+
+    /// Builds the light location tables of `light_config` unless they already match it,
+    /// getting each location with `lookup`.
+    fn build_light_locations<F>(&mut self, light_config: &LightConfiguration, lookup: F) -> ()
+    where
+        F: Fn(&str) -> Option<WebGlUniformLocation>,
+    {
+        if self.light_configuration.as_ref() == Some(light_config) {
+            return;
+        }
+        for (locations, light_type, count) in &mut [
+            (
+                &mut self.directional_lights_locations,
+                crate::utils::constants::DIRECTIONAL_LIGHTS_NAME,
+                light_config.directional,
+            ),
+            (
+                &mut self.point_lights_locations,
                 crate::utils::constants::POINT_LIGHTS_NAME,
-                Some(i),
-                context,
-                pg,
-            );
-            self.point_lights_locations.push(location);
+                light_config.point,
+            ),
+            (
+                &mut self.spot_lights_locations,
+                crate::utils::constants::SPOT_LIGHTS_NAME,
+                light_config.spot,
+            ),
+        ] {
+            locations.clear();
+            for i in 0..*count {
+                let mut location: LightUniformLocations = Default::default();
+                location.lookup_locations_with(*light_type, Some(i), &lookup);
+                locations.push(location);
+            }
         }
+        self.light_configuration = Some(light_config.clone());
     }
 }
 
@@ -557,6 +790,8 @@ pub struct LightUniformLocations {
     pub intensity: Option<WebGlUniformLocation>,
     pub attenuation: Option<WebGlUniformLocation>,
     pub position_or_direction: Option<WebGlUniformLocation>,
+    pub direction: Option<WebGlUniformLocation>,
+    pub cone: Option<WebGlUniformLocation>,
 }
 
 impl LightUniformLocations {
@@ -566,6 +801,8 @@ impl LightUniformLocations {
             intensity: None,
             attenuation: None,
             position_or_direction: None,
+            direction: None,
+            cone: None,
         }
     }
 
@@ -576,56 +813,45 @@ impl LightUniformLocations {
         context: &WebGlRenderingContext,
         program: &WebGlProgram,
     ) -> () {
+        self.lookup_locations_with(light_type, light_index, |uniform_name| {
+            context.get_uniform_location(program, uniform_name)
+        });
+    }
+
+    /// Looks up the missing field locations of a light with `lookup`, which gets the full
+    /// uniform name, such as `u_point_lights[2].color`.
+    fn lookup_locations_with<F>(&mut self, light_type: &str, light_index: Option<usize>, lookup: F)
+    where
+        F: Fn(&str) -> Option<WebGlUniformLocation>,
+    {
+        let lookup_field = |field: &str| {
+            let uniform_name = match light_index {
+                Some(i) => format!("{}[{}].{}", light_type, i, field),
+                None => format!("{}.{}", light_type, field),
+            };
+            lookup(&uniform_name)
+        };
         if self.color == None {
-            self.color = LightUniformLocations::lookup_field_location(
-                light_type,
-                crate::utils::constants::LIGHT_COLOR_NAME,
-                light_index,
-                context,
-                program,
-            );
+            self.color = lookup_field(crate::utils::constants::LIGHT_COLOR_NAME);
         }
         if self.intensity == None {
-            self.intensity = LightUniformLocations::lookup_field_location(
-                light_type,
-                crate::utils::constants::LIGHT_INTENSITY_NAME,
-                light_index,
-                context,
-                program,
-            );
+            self.intensity = lookup_field(crate::utils::constants::LIGHT_INTENSITY_NAME);
         }
         if self.attenuation == None {
-            self.attenuation = LightUniformLocations::lookup_field_location(
-                light_type,
-                crate::utils::constants::LIGHT_ATTENUATION_NAME,
-                light_index,
-                context,
-                program,
-            );
+            self.attenuation = lookup_field(crate::utils::constants::LIGHT_ATTENUATION_NAME);
         }
         if self.position_or_direction == None {
-            self.position_or_direction = LightUniformLocations::lookup_field_location(
-                light_type,
-                crate::utils::constants::LIGHT_POSITION_DIRECTION_NAME,
-                light_index,
-                context,
-                program,
-            );
+            self.position_or_direction =
+                lookup_field(crate::utils::constants::LIGHT_POSITION_DIRECTION_NAME);
+        }
+        if light_type == crate::utils::constants::SPOT_LIGHTS_NAME {
+            if self.direction == None {
+                self.direction = lookup_field(crate::utils::constants::LIGHT_DIRECTION_NAME);
+            }
+            if self.cone == None {
+                self.cone = lookup_field(crate::utils::constants::LIGHT_CONE_NAME);
+            }
         }
-    }
-
-    fn lookup_field_location(
-        light_type: &str,
-        field: &str,
-        light_index: Option<usize>,
-        context: &WebGlRenderingContext,
-        program: &WebGlProgram,
-    ) -> Option<WebGlUniformLocation> {
-        let uniform_name = match light_index {
-            Some(i) => format!("{}[{}].{}", light_type, i, field),
-            None => format!("{}.{}", light_type, field),
-        };
-        context.get_uniform_location(program, &uniform_name)
     }
 }
 
@@ -642,3 +868,60 @@ fn get_texture_pointer(texture_number: u32) -> u32 {
         _ => WebGlRenderingContext::TEXTURE8,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::constants::{POINT_LIGHTS_NAME, SPOT_LIGHTS_NAME};
+    use std::cell::RefCell;
+    use wasm_bindgen::{JsCast, JsValue};
+
+    /// Builds the light tables of `light_config`, returning the uniform names looked up.
+    fn build(
+        locations: &mut GlobalUniformLocations,
+        light_config: &LightConfiguration,
+    ) -> Vec<String> {
+        let names = RefCell::new(Vec::new());
+        locations.build_light_locations(light_config, |uniform_name| {
+            names.borrow_mut().push(uniform_name.to_owned());
+            Some(JsValue::NULL.unchecked_into())
+        });
+        names.into_inner()
+    }
+
+    #[test]
+    fn light_tables_grow_with_the_light_count() {
+        let mut locations = GlobalUniformLocations::new();
+        let mut light_config = LightConfiguration::default();
+        light_config.point = 1;
+        build(&mut locations, &light_config);
+        assert_eq!(locations.point_lights_locations.len(), 1);
+
+        light_config.point = 4;
+        let names = build(&mut locations, &light_config);
+        assert_eq!(locations.point_lights_locations.len(), 4);
+        assert!(locations
+            .point_lights_locations
+            .iter()
+            .all(|light| light.color.is_some() && light.position_or_direction.is_some()));
+        assert!(names.contains(&format!("{}[3].color", POINT_LIGHTS_NAME)));
+        assert!(build(&mut locations, &light_config).is_empty());
+
+        light_config.point = 2;
+        build(&mut locations, &light_config);
+        assert_eq!(locations.point_lights_locations.len(), 2);
+    }
+
+    #[test]
+    fn spot_light_table_has_direction_and_cone() {
+        let mut locations = GlobalUniformLocations::new();
+        let mut light_config = LightConfiguration::default();
+        light_config.spot = 2;
+        let names = build(&mut locations, &light_config);
+        assert_eq!(names.len(), 12);
+        assert!(names.contains(&format!("{}[1].cone", SPOT_LIGHTS_NAME)));
+        let spot = &locations.spot_lights_locations[1];
+        assert!(spot.direction.is_some() && spot.cone.is_some());
+        assert!(locations.point_lights_locations.is_empty());
+    }
+}
@@ -0,0 +1,10 @@
+//! Resource designating the camera the scene is rendered from.
+
+use specs::Entity;
+
+/// Entity holding the `Camera` used to render the scene. Set when the `Scene` is initialized.
+#[derive(Default)]
+pub struct ActiveCamera {
+    /// Camera entity, `None` before initialization.
+    pub entity: Option<Entity>,
+}
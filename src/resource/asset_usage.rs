@@ -0,0 +1,84 @@
+//! Number of entities using each asset, to collect the assets no entity uses anymore.
+
+use crate::component::Mesh;
+use specs::Entity;
+use std::collections::HashMap;
+
+/// ## AssetUsage
+///
+/// Counts the entities using each mesh data, material instance and material through their
+/// `Mesh`, by asset index. Counted when the `Scene` creates a mesh entity and discounted
+/// when it deletes it, and remembers the frame each asset stopped being used.
+///
+/// The assets an entity was counted for are kept, so that switching its mesh data (e.g.
+/// by `Lod`) doesn't unbalance the counts.
+#[derive(Default)]
+pub struct AssetUsage {
+    /// Number of entities using each asset
+    users: HashMap<usize, u32>,
+
+    /// Frame each unused asset was last seen used, or found unused if it never was
+    released: HashMap<usize, u64>,
+
+    /// Assets each entity was counted for, by entity id
+    counted: HashMap<u32, Vec<usize>>,
+
+    /// Current frame, increased by `end_frame`
+    frame: u64,
+}
+
+impl AssetUsage {
+    /// Counts `entity` as a user of the assets of `mesh`.
+    pub fn add_mesh(&mut self, entity: Entity, mesh: &Mesh) -> () {
+        self.remove_entity(entity);
+        let mut indexes = vec![*mesh.get_mesh_data_id()];
+        for (material_instance_id, material_id) in mesh.get_sub_mesh_materials() {
+            indexes.push(*material_instance_id);
+            indexes.push(*material_id);
+        }
+        for index in &indexes {
+            *self.users.entry(*index).or_insert(0) += 1;
+            self.released.remove(index);
+        }
+        self.counted.insert(entity.id(), indexes);
+    }
+
+    /// Stops counting `entity` as a user of the assets it was counted for, if any.
+    pub fn remove_entity(&mut self, entity: Entity) -> () {
+        let indexes = match self.counted.remove(&entity.id()) {
+            Some(indexes) => indexes,
+            None => return,
+        };
+        for index in indexes {
+            if let Some(users) = self.users.get_mut(&index) {
+                *users -= 1;
+                if *users == 0 {
+                    self.users.remove(&index);
+                    self.released.insert(index, self.frame);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if no entity has used the asset at `index` for at least
+    /// `older_than_frames` frames. Assets never used start being timed the first time they
+    /// are checked.
+    pub fn check_unused(&mut self, index: usize, older_than_frames: u64) -> bool {
+        if self.users.contains_key(&index) {
+            return false;
+        }
+        let released = *self.released.entry(index).or_insert(self.frame);
+        self.frame - released >= older_than_frames
+    }
+
+    /// Forgets the asset at `index`, once it's removed from the registry.
+    pub fn forget(&mut self, index: usize) -> () {
+        self.users.remove(&index);
+        self.released.remove(&index);
+    }
+
+    /// Closes the current frame.
+    pub fn end_frame(&mut self) -> () {
+        self.frame += 1;
+    }
+}
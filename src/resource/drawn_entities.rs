@@ -0,0 +1,30 @@
+//! Record of the entities the rendering system drew in the last frame.
+
+use specs::world::Index;
+use specs::BitSet;
+
+/// Set of the visible mesh entities that were submitted for drawing last frame, with their
+/// mesh data and materials registered and their materials compiled. Filled by the
+/// `RenderingSystem`, and read to explain why an entity doesn't show up.
+#[derive(Default)]
+pub struct DrawnEntities {
+    /// Ids of the drawn entities
+    drawn: BitSet,
+}
+
+impl DrawnEntities {
+    /// Empties the set, before rendering a new frame.
+    pub fn clear(&mut self) -> () {
+        self.drawn.clear();
+    }
+
+    /// Marks an entity as drawn.
+    pub fn set_drawn(&mut self, id: Index) -> () {
+        self.drawn.add(id);
+    }
+
+    /// Returns `true` if the entity was drawn last frame.
+    pub fn was_drawn(&self, id: Index) -> bool {
+        self.drawn.contains(id)
+    }
+}
@@ -0,0 +1,82 @@
+//! Offset of the rendered world from the true world origin, for large worlds.
+
+use nalgebra::Vector3;
+
+/// Floating origin of the scene. While enabled, the world is moved back so that the active
+/// camera stays near the origin, where `f32` positions are precise enough to render.
+///
+/// The accumulated shift is kept in double precision: true world positions are the
+/// positions of the scene plus `offset`.
+pub struct FloatingOrigin {
+    /// Distance from the origin beyond which the camera triggers a shift, `None` if disabled
+    threshold: Option<f32>,
+
+    /// Sum of every shift applied so far
+    offset: Vector3<f64>,
+}
+
+impl Default for FloatingOrigin {
+    fn default() -> FloatingOrigin {
+        FloatingOrigin {
+            threshold: None,
+            offset: Vector3::zeros(),
+        }
+    }
+}
+
+impl FloatingOrigin {
+    /// Sets the distance beyond which the world is shifted, or `None` to disable shifting.
+    /// The offset accumulated so far is kept.
+    pub fn set_threshold(&mut self, threshold: Option<f32>) -> () {
+        self.threshold = threshold;
+    }
+
+    /// Getter for the shift threshold.
+    pub fn get_threshold(&self) -> Option<f32> {
+        self.threshold
+    }
+
+    /// Returns the shift to apply for a camera at `eye_position`: the eye position itself if
+    /// it's farther than the threshold from the origin, `None` otherwise.
+    pub fn get_shift(&self, eye_position: &Vector3<f32>) -> Option<Vector3<f32>> {
+        match self.threshold {
+            Some(threshold) if eye_position.norm() > threshold => Some(*eye_position),
+            _ => None,
+        }
+    }
+
+    /// Adds an applied shift to the offset.
+    pub fn add_shift(&mut self, shift: &Vector3<f32>) -> () {
+        self.offset += Vector3::new(shift.x as f64, shift.y as f64, shift.z as f64);
+    }
+
+    /// Getter for the accumulated offset of the world.
+    pub fn get_offset(&self) -> &Vector3<f64> {
+        &self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_only_beyond_the_threshold() {
+        let mut origin = FloatingOrigin::default();
+        let far = Vector3::new(3000., 0., 4000.);
+        assert_eq!(origin.get_shift(&far), None, "Disabled by default");
+        origin.set_threshold(Some(5000.));
+        assert_eq!(origin.get_shift(&far), None);
+        origin.set_threshold(Some(4999.));
+        assert_eq!(origin.get_shift(&far), Some(far));
+    }
+
+    #[test]
+    fn offset_is_accumulated_in_double_precision() {
+        let mut origin = FloatingOrigin::default();
+        origin.add_shift(&Vector3::new(16_777_216., 0., 0.));
+        origin.add_shift(&Vector3::new(1., 0., -0.5));
+        assert_eq!(16_777_216f32 + 1., 16_777_216., "Lost in single precision");
+        assert_eq!(*origin.get_offset(), Vector3::new(16_777_217., 0., -0.5));
+    }
+}
@@ -0,0 +1,89 @@
+//! State of the editor's transform manipulator.
+
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+use specs::Entity;
+
+/// Transformation applied by dragging the manipulator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ManipulatorMode {
+    /// Three arrows, moving the entity along an axis
+    Translate,
+
+    /// Three circles, rotating the entity around an axis
+    Rotate,
+
+    /// Three cubes, scaling the entity along one of its own axes
+    Scale,
+}
+
+/// An axis of the manipulator grabbed by the pointer, and the entity's state when it was.
+#[derive(Clone, Debug)]
+pub struct ManipulatorDrag {
+    /// Entity being transformed
+    pub entity: Entity,
+
+    /// Index of the grabbed axis: 0 for X, 1 for Y, 2 for Z
+    pub axis: usize,
+
+    /// World space unit direction of the axis
+    pub direction: Vector3<f32>,
+
+    /// World position of the manipulator
+    pub center: Vector3<f32>,
+
+    /// World position where the axis was grabbed, on the axis for translation and scale, on
+    /// the plane of the circle for rotation
+    pub grab_point: Vector3<f32>,
+
+    /// Local translation of the entity when grabbed
+    pub translation: Vector3<f32>,
+
+    /// Local rotation of the entity when grabbed
+    pub rotation: UnitQuaternion<f32>,
+
+    /// Local scale of the entity when grabbed
+    pub scale: Vector3<f32>,
+
+    /// Inverse of the world matrix of the entity's parent, identity for root entities
+    pub parent_inverse: Matrix4<f32>,
+}
+
+/// Mode and snapping of the manipulator drawn over the selected entity, and the axis being
+/// hovered or dragged.
+pub struct Manipulator {
+    /// Transformation applied by dragging
+    pub mode: ManipulatorMode,
+
+    /// Translation increment in world units, `0` to move freely
+    pub translation_step: f32,
+
+    /// Rotation increment in radians, `0` to rotate freely
+    pub rotation_step: f32,
+
+    /// Axis under the pointer, highlighted
+    pub hovered_axis: Option<usize>,
+
+    /// Axis being dragged, if any
+    pub drag: Option<ManipulatorDrag>,
+}
+
+impl Default for Manipulator {
+    fn default() -> Manipulator {
+        Manipulator {
+            mode: ManipulatorMode::Translate,
+            translation_step: 0.,
+            rotation_step: 0.,
+            hovered_axis: None,
+            drag: None,
+        }
+    }
+}
+
+/// Rounds `value` to the nearest multiple of `step`, or returns it as is if `step` is `0`.
+pub fn snap(value: f32, step: f32) -> f32 {
+    if step > 0. {
+        (value / step).round() * step
+    } else {
+        value
+    }
+}
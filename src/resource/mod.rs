@@ -0,0 +1,25 @@
+//! Resources shared between the systems of a `Scene`'s world.
+
+mod active_camera;
+mod asset_usage;
+mod drawn_entities;
+mod floating_origin;
+#[cfg(feature = "editor")]
+mod manipulator;
+mod structural_changes;
+mod time;
+mod transform_watch;
+mod viewport_info;
+mod visibility;
+
+pub use active_camera::ActiveCamera;
+pub use asset_usage::AssetUsage;
+pub use drawn_entities::DrawnEntities;
+pub use floating_origin::FloatingOrigin;
+#[cfg(feature = "editor")]
+pub use manipulator::{snap, Manipulator, ManipulatorDrag, ManipulatorMode};
+pub use structural_changes::StructuralChanges;
+pub use time::Time;
+pub use transform_watch::TransformWatch;
+pub use viewport_info::ViewportInfo;
+pub use visibility::Visibility;
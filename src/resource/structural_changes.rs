@@ -0,0 +1,32 @@
+//! Count of the structural changes made to the world, to only maintain it when needed.
+
+/// Number of entities created and deleted through the `Scene`, this frame and last frame.
+/// `World::maintain` only runs on frames where some were.
+#[derive(Default)]
+pub struct StructuralChanges {
+    /// Changes made since the last `end_frame`
+    pending: u32,
+
+    /// Changes made during the last frame
+    last_frame: u32,
+}
+
+impl StructuralChanges {
+    /// Records `count` entities created or deleted.
+    pub fn record(&mut self, count: usize) -> () {
+        self.pending += count as u32;
+    }
+
+    /// Closes the frame. Returns `true` if the world changed during it and must be
+    /// maintained.
+    pub fn end_frame(&mut self) -> bool {
+        self.last_frame = self.pending;
+        self.pending = 0;
+        self.last_frame > 0
+    }
+
+    /// Returns the number of entities created and deleted during the last frame.
+    pub fn get_last_frame(&self) -> u32 {
+        self.last_frame
+    }
+}
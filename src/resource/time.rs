@@ -0,0 +1,170 @@
+//! Time resource, updated by the `Scene` at the start of each update.
+
+/// Largest number of fixed steps taken in a single update, so that a long frame doesn't
+/// make the next ones slower and slower.
+const MAX_FIXED_STEPS: u32 = 5;
+
+/// Elapsed and delta time for the current frame, in seconds.
+///
+/// Simulation systems (particles, camera shake) read the scaled delta, `get_delta`, which is
+/// `0` while paused. Camera controllers such as constraints read the unscaled delta,
+/// `get_unscaled_delta`, or `get_unscaled_step_delta` in the step loop, so that they keep
+/// moving.
+///
+/// In fixed timestep mode, the logic systems run a whole number of fixed steps per update,
+/// and the time left over is used to interpolate the rendered transforms between the last
+/// two steps. The time scale then changes how many steps are taken, not their duration.
+pub struct Time {
+    /// Time elapsed since the first update, in seconds.
+    elapsed: f64,
+
+    /// Time elapsed since the previous update, in seconds. `0` on the first update.
+    delta: f32,
+
+    /// Timestamp of the previous update, in milliseconds.
+    last_timestamp: Option<f64>,
+
+    /// Duration of a fixed step in seconds, `None` to step once per update.
+    fixed_step: Option<f32>,
+
+    /// Time not consumed by fixed steps yet, in seconds.
+    accumulator: f32,
+
+    /// Number of fixed steps taken by the last update.
+    step_count: u32,
+
+    /// If `true`, the scaled delta is `0`.
+    paused: bool,
+
+    /// Factor applied to the scaled delta, `1` by default.
+    time_scale: f32,
+}
+
+impl Default for Time {
+    fn default() -> Time {
+        Time {
+            elapsed: 0.,
+            delta: 0.,
+            last_timestamp: None,
+            fixed_step: None,
+            accumulator: 0.,
+            step_count: 0,
+            paused: false,
+            time_scale: 1.,
+        }
+    }
+}
+
+impl Time {
+    /// Advances the time to a new timestamp, in milliseconds (as given by `performance.now()`).
+    pub fn advance(&mut self, timestamp: f64) -> () {
+        if let Some(last_timestamp) = self.last_timestamp {
+            let delta = ((timestamp - last_timestamp) / 1000.).max(0.);
+            self.delta = delta as f32;
+            self.elapsed += delta;
+        }
+        self.last_timestamp = Some(timestamp);
+        if let (Some(fixed_step), false) = (self.fixed_step, self.paused) {
+            self.accumulator = (self.accumulator + self.delta * self.time_scale)
+                .min(fixed_step * MAX_FIXED_STEPS as f32);
+        }
+    }
+
+    /// Pauses or resumes the simulation: while paused, the scaled delta is `0`.
+    pub fn set_paused(&mut self, paused: bool) -> () {
+        self.paused = paused;
+    }
+
+    /// Returns `true` if the simulation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Sets the factor applied to the scaled delta, `1` for real time.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> () {
+        self.time_scale = time_scale;
+    }
+
+    /// Getter for the factor applied to the scaled delta.
+    pub fn get_time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Sets the duration of a fixed step in seconds, or `None` to step once per update.
+    pub fn set_fixed_step(&mut self, fixed_step: Option<f32>) -> () {
+        self.fixed_step = fixed_step;
+        self.accumulator = 0.;
+    }
+
+    /// Getter for the duration of a fixed step, in seconds.
+    pub fn get_fixed_step(&self) -> Option<f32> {
+        self.fixed_step
+    }
+
+    /// Consumes the accumulated time by whole fixed steps and returns how many steps the
+    /// logic systems must run. Always `1` outside of fixed timestep mode and while paused,
+    /// so that transform changes are still applied.
+    pub fn take_steps(&mut self) -> u32 {
+        self.step_count = match self.fixed_step {
+            Some(fixed_step) if !self.paused => {
+                let steps = (self.accumulator / fixed_step) as u32;
+                self.accumulator -= steps as f32 * fixed_step;
+                steps
+            }
+            _ => 1,
+        };
+        self.step_count
+    }
+
+    /// Getter for the number of steps taken by the last update.
+    pub fn get_step_count(&self) -> u32 {
+        self.step_count
+    }
+
+    /// Returns how far the current frame is between the last two fixed steps, from `0` to
+    /// `1`, or `None` outside of fixed timestep mode and while paused.
+    pub fn get_interpolation(&self) -> Option<f32> {
+        match self.fixed_step {
+            Some(fixed_step) if !self.paused => Some((self.accumulator / fixed_step).min(1.)),
+            _ => None,
+        }
+    }
+
+    /// Getter for the scaled delta, for simulation systems: the time elapsed since the
+    /// previous update multiplied by the time scale, in seconds. `0` while paused.  
+    /// In fixed timestep mode, this is the duration of a step.
+    pub fn get_delta(&self) -> f32 {
+        if self.paused {
+            return 0.;
+        }
+        match self.fixed_step {
+            Some(fixed_step) => fixed_step,
+            None => self.delta * self.time_scale,
+        }
+    }
+
+    /// Getter for the unscaled delta, for camera controllers and UI: the time elapsed since
+    /// the previous update, in seconds, whatever the time scale, pause and timestep mode.
+    pub fn get_unscaled_delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Getter for the unscaled delta of a single step, for camera controllers running in the
+    /// step loop: the real time a fixed step stands for, i.e. its duration divided by the
+    /// time scale, in fixed timestep mode. The unscaled delta otherwise, and while paused or
+    /// with a time scale of `0`, since a single step is taken per update then.  
+    /// Summed over the steps of several updates, it follows the real time elapsed.
+    pub fn get_unscaled_step_delta(&self) -> f32 {
+        match self.fixed_step {
+            Some(fixed_step) if !self.paused && self.time_scale > 0. => {
+                fixed_step / self.time_scale
+            }
+            _ => self.delta,
+        }
+    }
+
+    /// Getter for the time elapsed since the first update, in seconds.
+    pub fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+}
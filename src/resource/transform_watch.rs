@@ -0,0 +1,49 @@
+//! Record of the watched entities whose world transform changed.
+
+use specs::world::Index;
+use specs::BitSet;
+
+/// Entities whose world matrix is reported to JS when it changes, and the ones among them
+/// refreshed by the `SceneGraphSystem` since the changes were last taken.
+#[derive(Default)]
+pub struct TransformWatch {
+    /// Ids of the watched entities
+    watched: BitSet,
+
+    /// Number of watched entities
+    watched_count: usize,
+
+    /// Ids of the watched entities whose world matrix changed
+    changed: BitSet,
+}
+
+impl TransformWatch {
+    /// Starts or stops watching an entity.
+    pub fn set_watched(&mut self, id: Index, watched: bool) -> () {
+        if watched {
+            if !self.watched.add(id) {
+                self.watched_count += 1;
+            }
+        } else if self.watched.remove(id) {
+            self.watched_count -= 1;
+            self.changed.remove(id);
+        }
+    }
+
+    /// Returns `true` if no entity is watched.
+    pub fn is_empty(&self) -> bool {
+        self.watched_count == 0
+    }
+
+    /// Marks an entity as changed, if it is watched.
+    pub fn set_changed(&mut self, id: Index) -> () {
+        if self.watched.contains(id) {
+            self.changed.add(id);
+        }
+    }
+
+    /// Returns the ids of the changed entities and forgets them.
+    pub fn take_changed(&mut self) -> BitSet {
+        std::mem::replace(&mut self.changed, BitSet::new())
+    }
+}
@@ -0,0 +1,17 @@
+//! Resource describing the area the scene is rendered to.
+
+/// Size of the drawing area, updated by the `Scene` every frame before running the systems.
+#[derive(Default)]
+pub struct ViewportInfo {
+    /// Width of the drawing area, in device pixels
+    pub width: u32,
+
+    /// Height of the drawing area, in device pixels
+    pub height: u32,
+
+    /// Width divided by height of the displayed area, `0` before the first frame
+    pub aspect_ratio: f32,
+
+    /// `true` if the size changed since the previous frame
+    pub resized: bool,
+}
@@ -0,0 +1,70 @@
+//! Result of the visibility tests of the current frame.
+
+use specs::hibitset::BitSetLike;
+use specs::world::Index;
+use specs::BitSet;
+
+/// Set of the entities that passed the culling tests this frame. Joined with the mesh
+/// storages by the `RenderingSystem` so that culled entities are skipped.
+///
+/// The previous frame's results are kept to report the entities entering or leaving the
+/// view. Only tested entities are reported: entities without bounds or flagged
+/// `AlwaysVisible` are always visible and never change.
+#[derive(Default)]
+pub struct Visibility {
+    /// Ids of the visible entities
+    visible: BitSet,
+
+    /// Ids of the entities whose visibility was actually tested this frame
+    tested: BitSet,
+
+    /// Ids of the entities that were visible and tested the previous frame
+    previous: BitSet,
+}
+
+impl Visibility {
+    /// Empties the set, before running the tests of a new frame. The tested entities that
+    /// were visible are kept as the previous frame's results.
+    pub fn clear(&mut self) -> () {
+        self.previous = (&self.visible & &self.tested).iter().collect();
+        self.visible.clear();
+        self.tested.clear();
+    }
+
+    /// Marks an entity as visible.
+    pub fn set_visible(&mut self, id: Index) -> () {
+        self.visible.add(id);
+    }
+
+    /// Marks an entity as tested: its visibility depends on the culling tests.
+    pub fn set_tested(&mut self, id: Index) -> () {
+        self.tested.add(id);
+    }
+
+    /// Returns `true` if the entity passed the culling tests this frame.
+    pub fn is_visible(&self, id: Index) -> bool {
+        self.visible.contains(id)
+    }
+
+    /// Returns the set of visible entities, to be used in joins.
+    pub fn get_visible(&self) -> &BitSet {
+        &self.visible
+    }
+
+    /// Returns the ids of the tested entities that became visible this frame, and of those
+    /// that were visible the previous frame and no longer are.
+    pub fn get_changes(&self) -> (Vec<Index>, Vec<Index>) {
+        let visible = &self.visible & &self.tested;
+        let shown = (&visible & &!&self.previous).iter().collect();
+        let hidden = (&self.previous & &!&visible).iter().collect();
+        (shown, hidden)
+    }
+
+    /// Forgets a removed entity, so that it's not reported as hidden nor its id reused
+    /// with its state.
+    pub fn remove(&mut self, id: Index) -> () {
+        self.visible.remove(id);
+        self.tested.remove(id);
+        self.previous.remove(id);
+    }
+}
@@ -0,0 +1,100 @@
+//! Handles to entities for the JS API, which can't be mistaken for asset indexes and detect
+//! entities that were removed since.
+
+use super::Scene;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::utils::Vector3Data;
+use specs::{Entities, Entity};
+use wasm_bindgen::prelude::*;
+
+/// ## EntityHandle
+///
+/// Reference to an entity: its id and the generation of its slot when the handle was made.
+/// Removed entities leave their slot to the next entity created with a higher generation, so
+/// using a handle to a removed entity fails instead of changing whatever reuses its id.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EntityHandle {
+    /// Id of the entity, as used by the `u32` methods of the `Scene`
+    id: u32,
+
+    /// Generation of the entity's slot
+    generation: i32,
+}
+
+impl EntityHandle {
+    /// Returns a handle to `entity`.
+    pub fn new(entity: Entity) -> EntityHandle {
+        EntityHandle {
+            id: entity.id(),
+            generation: entity.gen().id(),
+        }
+    }
+
+    /// Returns the id of the entity if it is still alive, or an `InvalidEntity` error.
+    pub(super) fn resolve(&self, scene: &Scene) -> Result<u32, W3DError> {
+        if self.is_alive(scene) {
+            Ok(self.id)
+        } else {
+            Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity of this handle was removed.",
+                &self.id.to_string(),
+            ))
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl EntityHandle {
+    /// Id of the entity, as used by the `u32` methods of the `Scene`.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns `true` if the entity hasn't been removed from `scene`.
+    pub fn is_alive(&self, scene: &Scene) -> bool {
+        let entities: Entities = scene.world.system_data();
+        let entity = entities.entity(self.id);
+        entity.gen().id() == self.generation && entities.is_alive(entity)
+    }
+
+    /// Sets the local translation of the entity.
+    pub fn set_translation(
+        &self,
+        scene: &mut Scene,
+        translation: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let id = self.resolve(scene)?;
+        scene.set_transform_translation(id, translation);
+        Ok(())
+    }
+
+    /// Sets the local rotation of the entity, as Euler angles.
+    pub fn set_rotation(&self, scene: &mut Scene, rotation: Vector3Data) -> Result<(), JsValue> {
+        let id = self.resolve(scene)?;
+        scene.set_transform_rotation(id, rotation);
+        Ok(())
+    }
+
+    /// Sets the local scale of the entity.
+    pub fn set_scale(&self, scene: &mut Scene, scale: Vector3Data) -> Result<(), JsValue> {
+        let id = self.resolve(scene)?;
+        scene.set_transform_scale(id, scale);
+        Ok(())
+    }
+
+    /// Parents the entity to the entity of `parent`.
+    pub fn set_parent(&self, scene: &mut Scene, parent: &EntityHandle) -> Result<(), JsValue> {
+        let id = self.resolve(scene)?;
+        let parent_id = parent.resolve(scene)?;
+        scene.set_parent(id, parent_id);
+        Ok(())
+    }
+
+    /// Removes the entity and its descendants from `scene`.
+    pub fn remove(&self, scene: &mut Scene) -> Result<(), JsValue> {
+        let id = self.resolve(scene)?;
+        scene.remove_entity(id)
+    }
+}
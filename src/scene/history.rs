@@ -0,0 +1,687 @@
+//! Undo and redo of the edits made to a scene: transforms, parenting, entity creation and
+//! deletion, and per-entity uniforms. Only built with the `editor` feature.
+
+use super::Scene;
+use crate::component::*;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::resource::ActiveCamera;
+use nalgebra::{UnitQuaternion, Vector3};
+use specs::{Builder, Component, Entity, RunNow, World, WorldExt};
+use specs_hierarchy::Parent;
+use std::collections::{HashMap, VecDeque};
+use wasm_bindgen::prelude::*;
+
+/// Default number of undo steps kept.
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// ## History
+///
+/// Undo and redo stacks of a `Scene`.
+///
+/// Each step is a list of commands holding the state an edit overwrote, so that applying
+/// them in reverse order reverts the step. Applying a command returns the command reverting
+/// it in turn, which is pushed to the other stack.
+pub(super) struct History {
+    /// Steps to undo, the most recent last
+    undo: VecDeque<Vec<Command>>,
+
+    /// Steps to redo, the most recent last
+    redo: Vec<Vec<Command>>,
+
+    /// Commands of the open transaction, if any
+    transaction: Option<Vec<Command>>,
+
+    /// Number of nested transactions open
+    depth: u32,
+
+    /// Maximum number of undo steps kept
+    limit: usize,
+
+    /// `false` to stop recording edits
+    enabled: bool,
+
+    /// Entities restored by an undo or a redo, by the entity they replace
+    restored: HashMap<Entity, Entity>,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            transaction: None,
+            depth: 0,
+            limit: DEFAULT_HISTORY_LIMIT,
+            enabled: true,
+            restored: HashMap::new(),
+        }
+    }
+}
+
+impl History {
+    /// Records a command reverting an edit about to be made, in the open transaction or as
+    /// a step of its own. Within a transaction, only the first state of each transform,
+    /// parent and uniform is kept.
+    fn record(&mut self, command: Command) -> () {
+        if !self.enabled {
+            return;
+        }
+        match &mut self.transaction {
+            Some(commands) => {
+                if !commands
+                    .iter()
+                    .any(|recorded| recorded.overwrites(&command))
+                {
+                    commands.push(command);
+                }
+            }
+            None => self.push_step(vec![command]),
+        }
+    }
+
+    /// Pushes a step to the undo stack, dropping the oldest one past the limit, and clears
+    /// the redo stack.
+    fn push_step(&mut self, step: Vec<Command>) -> () {
+        if step.is_empty() || self.limit == 0 {
+            return;
+        }
+        self.undo.push_back(step);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    /// Returns the entity currently standing for `entity`, which differs once it was
+    /// deleted and restored.
+    fn resolve(&self, mut entity: Entity) -> Entity {
+        while let Some(restored) = self.restored.get(&entity) {
+            entity = *restored;
+        }
+        entity
+    }
+
+    /// Applies the commands of a step in reverse order, returning the step reverting it.
+    fn apply_step(&mut self, world: &mut World, step: Vec<Command>) -> Vec<Command> {
+        let mut reverse = Vec::with_capacity(step.len());
+        for command in step.into_iter().rev() {
+            if let Some(command) = command.apply(world, self) {
+                reverse.push(command);
+            }
+        }
+        reverse
+    }
+}
+
+/// Local translation, rotation and scale of an entity.
+#[derive(Clone)]
+struct TransformState {
+    translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+    scale: Vector3<f32>,
+}
+
+/// State overwritten by an edit, and restored by applying the command.
+enum Command {
+    /// Local transform of an entity
+    Transform(Entity, TransformState),
+
+    /// Parent of an entity, `None` for root entities
+    Parent(Entity, Option<Entity>),
+
+    /// Overridden value of a uniform of an entity, `None` if it wasn't overridden
+    Uniform(Entity, String, Option<UniformOverrideValue>),
+
+    /// Entities to delete with their descendants, reverting their creation
+    Delete(Vec<Entity>),
+
+    /// Entities to create back from their snapshots, reverting their deletion
+    Restore(Vec<EntitySnapshot>),
+}
+
+impl Command {
+    /// Returns `true` if `other` records the same state as this command, which already
+    /// holds its older value.
+    fn overwrites(&self, other: &Command) -> bool {
+        match (self, other) {
+            (Command::Transform(entity, _), Command::Transform(other, _)) => entity == other,
+            (Command::Parent(entity, _), Command::Parent(other, _)) => entity == other,
+            (Command::Uniform(entity, name, _), Command::Uniform(other, other_name, _)) => {
+                entity == other && name == other_name
+            }
+            _ => false,
+        }
+    }
+
+    /// Restores the state held by the command, and returns the command restoring the state
+    /// it overwrote. Returns `None` if the command's entities don't exist anymore.
+    fn apply(self, world: &mut World, history: &mut History) -> Option<Command> {
+        match self {
+            Command::Transform(entity, state) => {
+                let entity = history.resolve(entity);
+                let current = get_transform_state(world, entity)?;
+                let mut transforms = world.write_storage::<Transform>();
+                let transform = transforms.get_mut(entity)?;
+                transform.set_translation(&state.translation);
+                transform.set_rotation_quaternion(&state.rotation);
+                transform.set_scale(&state.scale);
+                world
+                    .write_storage::<DirtyTransform>()
+                    .insert(entity, DirtyTransform)
+                    .ok();
+                Some(Command::Transform(entity, current))
+            }
+            Command::Parent(entity, parent) => {
+                let entity = history.resolve(entity);
+                if !world.is_alive(entity) {
+                    return None;
+                }
+                let current = get_parent(world, entity);
+                let parent = parent.map(|parent| history.resolve(parent));
+                set_parent(world, entity, parent);
+                Some(Command::Parent(entity, current))
+            }
+            Command::Uniform(entity, name, value) => {
+                let entity = history.resolve(entity);
+                if !world.is_alive(entity) {
+                    return None;
+                }
+                let current = get_uniform(world, entity, &name);
+                set_uniform(world, entity, &name, value);
+                Some(Command::Uniform(entity, name, current))
+            }
+            Command::Delete(entities) => {
+                let mut deleted = Vec::new();
+                for entity in entities {
+                    let entity = history.resolve(entity);
+                    if world.is_alive(entity) {
+                        for entity in Scene::get_entity_tree(world, entity) {
+                            if !deleted.contains(&entity) {
+                                deleted.push(entity);
+                            }
+                        }
+                    }
+                }
+                if deleted.is_empty() {
+                    return None;
+                }
+                let snapshots = deleted
+                    .iter()
+                    .map(|entity| EntitySnapshot::take(world, *entity))
+                    .collect();
+                Scene::delete_entities(world, &deleted).ok()?;
+                Some(Command::Restore(snapshots))
+            }
+            Command::Restore(snapshots) => {
+                let mut restored = Vec::with_capacity(snapshots.len());
+                for snapshot in &snapshots {
+                    let entity = Scene::build_entity(world).build();
+                    history.restored.insert(snapshot.entity, entity);
+                    restored.push(entity);
+                }
+                for (snapshot, entity) in snapshots.into_iter().zip(&restored) {
+                    snapshot.restore(world, *entity, history);
+                }
+                Some(Command::Delete(restored))
+            }
+        }
+    }
+}
+
+/// ## EntitySnapshot
+///
+/// Copy of the components of a deleted entity, to create it back. The components
+/// maintained by systems (`DirtyTransform`, `EffectivelyDisabled`) are recomputed instead.
+struct EntitySnapshot {
+    /// Deleted entity
+    entity: Entity,
+
+    transform: Option<Transform>,
+    parent: Option<Entity>,
+    enabled: bool,
+    selected: bool,
+    always_visible: bool,
+    active_camera: bool,
+    camera: Option<Camera>,
+    camera_shake: Option<CameraShake>,
+    mesh: Option<Mesh>,
+    lod: Option<Lod>,
+    bounds: Option<Bounds>,
+    uniform_overrides: Option<UniformOverrides>,
+    opacity: Option<Opacity>,
+    light: Option<Light>,
+    direction: Option<Direction>,
+    cone: Option<Cone>,
+    hemisphere: Option<Hemisphere>,
+    follow: Option<Follow>,
+    look_at: Option<LookAtTarget>,
+    velocity: Option<Velocity>,
+    path_follower: Option<PathFollower>,
+    particle_emitter: Option<ParticleEmitter>,
+    sprite: Option<Sprite>,
+    overlay: Option<Overlay>,
+    blob_shadow: Option<BlobShadow>,
+}
+
+impl EntitySnapshot {
+    /// Copies the components of `entity`.
+    fn take(world: &World, entity: Entity) -> EntitySnapshot {
+        EntitySnapshot {
+            entity: entity,
+            transform: get_component(world, entity),
+            parent: get_parent(world, entity),
+            enabled: world.read_storage::<Enabled>().contains(entity),
+            selected: world.read_storage::<Selected>().contains(entity),
+            always_visible: world.read_storage::<AlwaysVisible>().contains(entity),
+            active_camera: world.read_resource::<ActiveCamera>().entity == Some(entity),
+            camera: get_component(world, entity),
+            camera_shake: get_component(world, entity),
+            mesh: get_component(world, entity),
+            lod: get_component(world, entity),
+            bounds: get_component(world, entity),
+            uniform_overrides: get_component(world, entity),
+            opacity: get_component(world, entity),
+            light: get_component(world, entity),
+            direction: get_component(world, entity),
+            cone: get_component(world, entity),
+            hemisphere: get_component(world, entity),
+            follow: get_component(world, entity),
+            look_at: get_component(world, entity),
+            velocity: get_component(world, entity),
+            path_follower: get_component(world, entity),
+            particle_emitter: get_component(world, entity),
+            sprite: get_component(world, entity),
+            overlay: get_component(world, entity),
+            blob_shadow: get_component(world, entity),
+        }
+    }
+
+    /// Inserts the components copied to `entity`, which replaces the deleted one. Entities
+    /// referenced by the components are resolved through `history`, so they can be restored
+    /// in any order.
+    fn restore(self, world: &mut World, entity: Entity, history: &History) -> () {
+        if self.transform.is_some() {
+            insert_component(world, entity, Some(DirtyTransform));
+        }
+        insert_component(world, entity, self.transform);
+        if let Some(parent) = self.parent {
+            set_parent(world, entity, Some(history.resolve(parent)));
+        }
+        if self.enabled {
+            insert_component(world, entity, Some(Enabled));
+        }
+        if self.selected {
+            insert_component(world, entity, Some(Selected));
+        }
+        if self.always_visible {
+            insert_component(world, entity, Some(AlwaysVisible));
+        }
+        if self.active_camera {
+            world.write_resource::<ActiveCamera>().entity = Some(entity);
+        }
+        insert_component(world, entity, self.camera);
+        insert_component(world, entity, self.camera_shake);
+        insert_component(world, entity, self.mesh);
+        Scene::count_asset_users(world, entity);
+        insert_component(world, entity, self.lod);
+        insert_component(world, entity, self.bounds);
+        insert_component(world, entity, self.uniform_overrides);
+        insert_component(world, entity, self.opacity);
+        insert_component(world, entity, self.light);
+        insert_component(world, entity, self.direction);
+        insert_component(world, entity, self.cone);
+        insert_component(world, entity, self.hemisphere);
+        insert_component(
+            world,
+            entity,
+            self.follow.map(|mut follow| {
+                follow.target = history.resolve(follow.target);
+                follow
+            }),
+        );
+        insert_component(
+            world,
+            entity,
+            self.look_at.map(|mut look_at| {
+                look_at.target = history.resolve(look_at.target);
+                look_at
+            }),
+        );
+        insert_component(world, entity, self.velocity);
+        insert_component(world, entity, self.path_follower);
+        insert_component(world, entity, self.particle_emitter);
+        insert_component(world, entity, self.sprite);
+        insert_component(world, entity, self.overlay);
+        insert_component(world, entity, self.blob_shadow);
+    }
+}
+
+#[wasm_bindgen]
+impl Scene {
+    /// Reverts the last step of the edit history. Returns `false` if there is nothing to
+    /// undo.
+    /// Entities whose deletion is reverted are created back with their components, but
+    /// may get new ids. Components of other entities referring to them, like a `Follow`
+    /// target, are not restored.
+    /// Fails while a transaction is open.
+    pub fn undo(&mut self) -> Result<bool, JsValue> {
+        self.check_no_transaction()?;
+        let step = match self.history.undo.pop_back() {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        self.hierarchy_system.run_now(&self.world);
+        let reverse = self.history.apply_step(&mut self.world, step);
+        self.history.redo.push(reverse);
+        Ok(true)
+    }
+
+    /// Applies again the last step reverted by `undo`. Returns `false` if there is nothing
+    /// to redo. The redo stack is cleared by any new edit.
+    /// Fails while a transaction is open.
+    pub fn redo(&mut self) -> Result<bool, JsValue> {
+        self.check_no_transaction()?;
+        let step = match self.history.redo.pop() {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        self.hierarchy_system.run_now(&self.world);
+        let reverse = self.history.apply_step(&mut self.world, step);
+        self.history.undo.push_back(reverse);
+        Ok(true)
+    }
+
+    /// Returns `true` if there is a step to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    /// Returns `true` if there is a step to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Groups the edits made until the matching `end_transaction` in a single undo step,
+    /// e.g. all the moves of a drag. Transactions can be nested, the step being recorded
+    /// when the outermost one ends.
+    pub fn begin_transaction(&mut self) -> () {
+        if self.history.depth == 0 {
+            self.history.transaction = Some(Vec::new());
+        }
+        self.history.depth += 1;
+    }
+
+    /// Ends a transaction opened with `begin_transaction`.
+    /// Fails if no transaction is open.
+    pub fn end_transaction(&mut self) -> Result<(), JsValue> {
+        if self.history.depth == 0 {
+            return Err(
+                W3DError::new(W3DErrorKind::InvalidArgument, "No transaction is open.").into(),
+            );
+        }
+        self.history.depth -= 1;
+        if self.history.depth == 0 {
+            if let Some(step) = self.history.transaction.take() {
+                self.history.push_step(step);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the number of undo steps kept, dropping the oldest ones. `0` disables undo.
+    pub fn set_history_limit(&mut self, limit: u32) -> () {
+        let limit = limit as usize;
+        self.history.limit = limit;
+        while self.history.undo.len() > limit {
+            self.history.undo.pop_front();
+        }
+    }
+
+    /// Starts or stops recording edits, e.g. to keep the changes made by a running game out
+    /// of the history. Recording is enabled by default.
+    pub fn set_history_enabled(&mut self, enabled: bool) -> () {
+        self.history.enabled = enabled;
+    }
+
+    /// Forgets every undo and redo step.
+    pub fn clear_history(&mut self) -> () {
+        self.history.undo.clear();
+        self.history.redo.clear();
+    }
+}
+
+impl Scene {
+    /// Records the local transform of an entity before it's edited.
+    pub(super) fn record_transform_edit(&mut self, entity_id: u32) -> () {
+        let entity = self.world.entities().entity(entity_id);
+        if let Some(state) = get_transform_state(&self.world, entity) {
+            self.history.record(Command::Transform(entity, state));
+        }
+    }
+
+    /// Records the parent of an entity before it's changed.
+    pub(super) fn record_parent_edit(&mut self, entity_id: u32) -> () {
+        let entity = self.world.entities().entity(entity_id);
+        if self.world.is_alive(entity) {
+            let parent = get_parent(&self.world, entity);
+            self.history.record(Command::Parent(entity, parent));
+        }
+    }
+
+    /// Records the overridden value of an entity's uniform before it's changed.
+    pub(super) fn record_uniform_edit(&mut self, entity_id: u32, name: &str) -> () {
+        let entity = self.world.entities().entity(entity_id);
+        if self.world.is_alive(entity) {
+            let value = get_uniform(&self.world, entity, name);
+            self.history
+                .record(Command::Uniform(entity, name.to_owned(), value));
+        }
+    }
+
+    /// Records the creation of an entity.
+    pub(super) fn record_creation(&mut self, entity: Entity) -> () {
+        self.history.record(Command::Delete(vec![entity]));
+    }
+
+    /// Records an entity and its descendants before they're deleted.
+    pub(super) fn record_deletion(&mut self, entity_id: u32) -> () {
+        let entity = self.world.entities().entity(entity_id);
+        if self.history.enabled && self.world.is_alive(entity) {
+            let snapshots = Scene::get_entity_tree(&self.world, entity)
+                .into_iter()
+                .map(|entity| EntitySnapshot::take(&self.world, entity))
+                .collect();
+            self.history.record(Command::Restore(snapshots));
+        }
+    }
+
+    /// Fails if a transaction is open.
+    fn check_no_transaction(&self) -> Result<(), W3DError> {
+        if self.history.depth > 0 {
+            Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "The history can't be navigated while a transaction is open.",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Returns the local transform of `entity`, if it has one.
+fn get_transform_state(world: &World, entity: Entity) -> Option<TransformState> {
+    world
+        .read_storage::<Transform>()
+        .get(entity)
+        .map(|transform| TransformState {
+            translation: transform.get_translation().clone(),
+            rotation: transform.get_rotation().clone(),
+            scale: transform.get_scale().clone(),
+        })
+}
+
+/// Returns the parent of `entity`, `None` for root entities.
+fn get_parent(world: &World, entity: Entity) -> Option<Entity> {
+    world
+        .read_storage::<TransformParent>()
+        .get(entity)
+        .map(|parent| parent.parent_entity())
+}
+
+/// Sets the parent of `entity`, or makes it a root entity.
+fn set_parent(world: &World, entity: Entity, parent: Option<Entity>) -> () {
+    let mut parents = world.write_storage::<TransformParent>();
+    match parent {
+        Some(parent) => {
+            parents.insert(entity, TransformParent::new(parent)).ok();
+        }
+        None => {
+            parents.remove(entity);
+        }
+    }
+    world
+        .write_storage::<DirtyTransform>()
+        .insert(entity, DirtyTransform)
+        .ok();
+}
+
+/// Returns the overridden value of a uniform of `entity`, `None` if it isn't overridden.
+fn get_uniform(world: &World, entity: Entity, name: &str) -> Option<UniformOverrideValue> {
+    world
+        .read_storage::<UniformOverrides>()
+        .get(entity)
+        .and_then(|overrides| {
+            overrides
+                .get_values()
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| *value)
+        })
+}
+
+/// Overrides a uniform of `entity`, or stops overriding it if `value` is `None`.
+fn set_uniform(
+    world: &World,
+    entity: Entity,
+    name: &str,
+    value: Option<UniformOverrideValue>,
+) -> () {
+    let mut overrides = world.write_storage::<UniformOverrides>();
+    match (value, overrides.get_mut(entity)) {
+        (Some(value), Some(entity_overrides)) => entity_overrides.set(name, value),
+        (Some(value), None) => {
+            let mut entity_overrides = UniformOverrides::new();
+            entity_overrides.set(name, value);
+            overrides.insert(entity, entity_overrides).ok();
+        }
+        (None, Some(entity_overrides)) => {
+            entity_overrides.remove(name);
+            if entity_overrides.is_empty() {
+                overrides.remove(entity);
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+/// Returns a copy of a component of `entity`, if it has one.
+fn get_component<C: Component + Clone>(world: &World, entity: Entity) -> Option<C> {
+    world.read_storage::<C>().get(entity).cloned()
+}
+
+/// Inserts a component to `entity`, if any.
+fn insert_component<C: Component>(world: &World, entity: Entity, component: Option<C>) -> () {
+    if let Some(component) = component {
+        world.write_storage::<C>().insert(entity, component).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Vector3Data;
+    use specs::Join;
+
+    /// Alive entities with their local translation and parent id.
+    fn describe(scene: &Scene) -> Vec<(u32, Option<Vector3<f32>>, Option<u32>)> {
+        let transforms = scene.world.read_storage::<Transform>();
+        (&scene.world.entities())
+            .join()
+            .map(|entity| {
+                (
+                    entity.id(),
+                    transforms
+                        .get(entity)
+                        .map(|transform| *transform.get_translation()),
+                    get_parent(&scene.world, entity).map(|parent| parent.id()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undoing_create_move_parent_delete_restores_the_original_state() {
+        let mut scene = Scene::new();
+        let parent = scene.create_particle_emitter(1, 0., Vector3Data::new(1., 2., 3.));
+        scene.clear_history();
+        let original = describe(&scene);
+
+        let child = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene.set_transform_translation(child, Vector3Data::new(4., 0., 0.));
+        scene.set_parent(child, parent);
+        let before_deletion = describe(&scene);
+        scene.remove_entity(child).ok().unwrap();
+        assert_eq!(describe(&scene), original);
+
+        assert!(scene.undo().ok().unwrap());
+        let restored = describe(&scene);
+        assert_eq!(restored.len(), before_deletion.len());
+        let (_, translation, restored_parent) = restored
+            .iter()
+            .find(|(id, _, _)| *id != parent)
+            .cloned()
+            .unwrap();
+        assert_eq!(translation, Some(Vector3::new(4., 0., 0.)));
+        assert_eq!(restored_parent, Some(parent));
+
+        for _ in 0..3 {
+            assert!(scene.undo().ok().unwrap());
+        }
+        assert_eq!(describe(&scene), original);
+        assert!(!scene.undo().ok().unwrap());
+
+        for _ in 0..4 {
+            assert!(scene.redo().ok().unwrap());
+        }
+        assert_eq!(
+            describe(&scene),
+            original,
+            "Redoing deletes the child again"
+        );
+        assert!(!scene.can_redo());
+    }
+
+    #[test]
+    fn a_transaction_is_a_single_step_keeping_the_first_state() {
+        let mut scene = Scene::new();
+        let entity = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene.clear_history();
+
+        scene.begin_transaction();
+        for x in 1..100 {
+            scene.set_transform_translation(entity, Vector3Data::new(x as f32, 0., 0.));
+        }
+        assert!(
+            scene.check_no_transaction().is_err(),
+            "Can't undo during a transaction"
+        );
+        scene.end_transaction().ok().unwrap();
+        assert!(scene.check_no_transaction().is_ok());
+
+        assert!(scene.undo().ok().unwrap());
+        assert_eq!(describe(&scene)[0].1, Some(Vector3::zeros()));
+        assert!(!scene.can_undo());
+        assert!(scene.redo().ok().unwrap());
+        assert_eq!(describe(&scene)[0].1, Some(Vector3::new(99., 0., 0.)));
+    }
+}
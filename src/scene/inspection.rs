@@ -0,0 +1,307 @@
+//! Description of an entity's components and rendering state as a plain JS object, to find
+//! out from JS why an entity doesn't render as expected, and of the world's composition.
+
+use crate::asset::AssetRegistry;
+use crate::component::*;
+use crate::renderer::Renderer;
+use crate::resource::{DrawnEntities, StructuralChanges, Visibility};
+use js_sys::{Array, Object, Reflect};
+use nalgebra::Vector3;
+use specs::{Entity, Join, ReadStorage, World, WorldExt};
+use specs_hierarchy::Parent;
+use wasm_bindgen::JsValue;
+
+/// Builds a JS object describing an entity:
+///
+/// - `id` and `alive`;
+/// - `components`: the names of its components;
+/// - `transform`: local `translation`, `rotation` (quaternion as `[x, y, z, w]`) and `scale`,
+///   and `worldPosition`, if it has a `Transform`;
+/// - `parent`: the id of its parent entity, or `null`;
+/// - `enabled`, `effectivelyDisabled` and `dirty` flags;
+/// - `mesh`: `{ meshData, subMeshes }` if it has a `Mesh`, assets being described as
+///   `{ id, registered }` and each sub-mesh as `{ materialInstance, material }`, the material
+///   also having a `compiled` flag;
+/// - `light`: `{ kind, color, intensity }` if it has a `Light`;
+/// - `visible` and `drawn`: whether it passed culling and was drawn last frame.
+///
+/// The mesh's assets are reported as not registered if the scene is not initialized.
+pub(super) fn describe_entity(
+    world: &World,
+    renderer: Option<&Renderer>,
+    entity_id: u32,
+) -> JsValue {
+    let entities = world.entities();
+    let entity = entities.entity(entity_id);
+    let description = Object::new();
+    set(&description, "id", entity_id.into());
+    let alive = entities.is_alive(entity);
+    set(&description, "alive", alive.into());
+    if !alive {
+        return description.into();
+    }
+    set(&description, "components", component_names(world, entity));
+
+    let transforms = world.read_storage::<Transform>();
+    if let Some(transform) = transforms.get(entity) {
+        let rotation = transform.get_rotation().coords;
+        let world_position = transform.get_world_matrix().column(3).xyz();
+        let transform_description = Object::new();
+        set(
+            &transform_description,
+            "translation",
+            vector_to_js(transform.get_translation()),
+        );
+        set(
+            &transform_description,
+            "rotation",
+            numbers_to_js(&[rotation.x, rotation.y, rotation.z, rotation.w]),
+        );
+        set(
+            &transform_description,
+            "scale",
+            vector_to_js(transform.get_scale()),
+        );
+        set(
+            &transform_description,
+            "worldPosition",
+            vector_to_js(&world_position),
+        );
+        set(&description, "transform", transform_description.into());
+    }
+    let parent = world
+        .read_storage::<TransformParent>()
+        .get(entity)
+        .map(|parent| JsValue::from(parent.parent_entity().id()))
+        .unwrap_or(JsValue::NULL);
+    set(&description, "parent", parent);
+    set(
+        &description,
+        "enabled",
+        has::<Enabled>(world, entity).into(),
+    );
+    set(
+        &description,
+        "effectivelyDisabled",
+        has::<EffectivelyDisabled>(world, entity).into(),
+    );
+    set(
+        &description,
+        "dirty",
+        has::<DirtyTransform>(world, entity).into(),
+    );
+
+    if let Some(mesh) = world.read_storage::<Mesh>().get(entity) {
+        let asset_registry = renderer.map(Renderer::get_asset_registry);
+        set(&description, "mesh", describe_mesh(mesh, asset_registry));
+    }
+    if let Some(light) = world.read_storage::<Light>().get(entity) {
+        let light_description = Object::new();
+        set(&light_description, "kind", light_kind(world, entity).into());
+        set(&light_description, "color", vector_to_js(&light.color));
+        set(&light_description, "intensity", light.intensity.into());
+        set(&description, "light", light_description.into());
+    }
+    set(
+        &description,
+        "visible",
+        world
+            .read_resource::<Visibility>()
+            .is_visible(entity_id)
+            .into(),
+    );
+    set(
+        &description,
+        "drawn",
+        world
+            .read_resource::<DrawnEntities>()
+            .was_drawn(entity_id)
+            .into(),
+    );
+    description.into()
+}
+
+/// Components registered in a scene's world: `(name, has, count)`.
+const COMPONENTS: &[(&str, fn(&World, Entity) -> bool, fn(&World) -> usize)] = &[
+    ("Transform", has::<Transform>, count::<Transform>),
+    (
+        "TransformParent",
+        has::<TransformParent>,
+        count::<TransformParent>,
+    ),
+    ("Enabled", has::<Enabled>, count::<Enabled>),
+    (
+        "EffectivelyDisabled",
+        has::<EffectivelyDisabled>,
+        count::<EffectivelyDisabled>,
+    ),
+    (
+        "DirtyTransform",
+        has::<DirtyTransform>,
+        count::<DirtyTransform>,
+    ),
+    ("Camera", has::<Camera>, count::<Camera>),
+    ("CameraShake", has::<CameraShake>, count::<CameraShake>),
+    ("Mesh", has::<Mesh>, count::<Mesh>),
+    ("Lod", has::<Lod>, count::<Lod>),
+    ("Bounds", has::<Bounds>, count::<Bounds>),
+    (
+        "AlwaysVisible",
+        has::<AlwaysVisible>,
+        count::<AlwaysVisible>,
+    ),
+    ("Selected", has::<Selected>, count::<Selected>),
+    ("Unpickable", has::<Unpickable>, count::<Unpickable>),
+    (
+        "UniformOverrides",
+        has::<UniformOverrides>,
+        count::<UniformOverrides>,
+    ),
+    ("Opacity", has::<Opacity>, count::<Opacity>),
+    ("Light", has::<Light>, count::<Light>),
+    ("Direction", has::<Direction>, count::<Direction>),
+    ("Cone", has::<Cone>, count::<Cone>),
+    ("Hemisphere", has::<Hemisphere>, count::<Hemisphere>),
+    ("Follow", has::<Follow>, count::<Follow>),
+    ("LookAtTarget", has::<LookAtTarget>, count::<LookAtTarget>),
+    ("Velocity", has::<Velocity>, count::<Velocity>),
+    ("PathFollower", has::<PathFollower>, count::<PathFollower>),
+    (
+        "ParticleEmitter",
+        has::<ParticleEmitter>,
+        count::<ParticleEmitter>,
+    ),
+    ("Sprite", has::<Sprite>, count::<Sprite>),
+    ("Overlay", has::<Overlay>, count::<Overlay>),
+    ("BlobShadow", has::<BlobShadow>, count::<BlobShadow>),
+];
+
+/// Builds a JS object describing the composition of a world:
+///
+/// - `entities`: the number of living entities;
+/// - `components`: the number of entities having each component, by component name;
+/// - `structuralChanges`: the number of entities created and deleted during the last frame.
+pub(super) fn describe_world(world: &World) -> JsValue {
+    let description = Object::new();
+    let entity_count = world.entities().join().count();
+    set(&description, "entities", (entity_count as u32).into());
+    let components = Object::new();
+    for (name, _, count) in COMPONENTS {
+        set(&components, name, (count(world) as u32).into());
+    }
+    set(&description, "components", components.into());
+    set(
+        &description,
+        "structuralChanges",
+        world
+            .read_resource::<StructuralChanges>()
+            .get_last_frame()
+            .into(),
+    );
+    description.into()
+}
+
+/// Returns the names of the components an entity has.
+fn component_names(world: &World, entity: Entity) -> JsValue {
+    let names = Array::new();
+    for (name, has, _) in COMPONENTS {
+        if has(world, entity) {
+            names.push(&(*name).into());
+        }
+    }
+    names.into()
+}
+
+/// Describes a mesh's assets and whether they are registered.
+fn describe_mesh(mesh: &Mesh, asset_registry: Option<&AssetRegistry>) -> JsValue {
+    let mesh_data = asset_registry
+        .and_then(|registry| registry.get_mesh_data_with_index(*mesh.get_mesh_data_id()))
+        .map(|mesh_data| mesh_data.borrow().get_id().to_owned());
+    let description = Object::new();
+    set(
+        &description,
+        "meshData",
+        describe_asset(*mesh.get_mesh_data_id(), mesh_data).into(),
+    );
+    let sub_meshes = Array::new();
+    for (material_instance_id, material_id) in mesh.get_sub_mesh_materials() {
+        let material_instance = asset_registry
+            .and_then(|registry| registry.get_material_instance_with_index(*material_instance_id))
+            .map(|material_instance| material_instance.borrow().get_id().to_owned());
+        let material =
+            asset_registry.and_then(|registry| registry.get_material_with_index(*material_id));
+        let compiled = material
+            .as_ref()
+            .map_or(false, |material| material.borrow().get_program().is_some());
+        let material_description = describe_asset(
+            *material_id,
+            material.map(|material| material.borrow().get_id().to_owned()),
+        );
+        set(&material_description, "compiled", compiled.into());
+        let sub_mesh = Object::new();
+        set(
+            &sub_mesh,
+            "materialInstance",
+            describe_asset(*material_instance_id, material_instance).into(),
+        );
+        set(&sub_mesh, "material", material_description.into());
+        sub_meshes.push(&sub_mesh);
+    }
+    set(&description, "subMeshes", sub_meshes.into());
+    description.into()
+}
+
+/// Builds a `{ index, id, registered }` object, `id` being `null` if the asset at `index` is
+/// not registered.
+fn describe_asset(index: usize, id: Option<String>) -> Object {
+    let description = Object::new();
+    set(&description, "index", (index as u32).into());
+    set(&description, "registered", id.is_some().into());
+    set(
+        &description,
+        "id",
+        id.map(JsValue::from).unwrap_or(JsValue::NULL),
+    );
+    description
+}
+
+/// Returns the kind of light an entity is, from the components the `LightingSystem` uses.
+fn light_kind(world: &World, entity: Entity) -> &'static str {
+    if has::<Hemisphere>(world, entity) {
+        "hemisphere"
+    } else if has::<Cone>(world, entity) {
+        "spot"
+    } else if has::<Direction>(world, entity) {
+        "directional"
+    } else if has::<Transform>(world, entity) {
+        "point"
+    } else {
+        "ambient"
+    }
+}
+
+fn has<T: specs::Component>(world: &World, entity: Entity) -> bool {
+    let storage: ReadStorage<T> = world.system_data();
+    storage.contains(entity)
+}
+
+fn count<T: specs::Component>(world: &World) -> usize {
+    let storage: ReadStorage<T> = world.system_data();
+    storage.join().count()
+}
+
+fn vector_to_js(vector: &Vector3<f32>) -> JsValue {
+    numbers_to_js(&[vector.x, vector.y, vector.z])
+}
+
+fn numbers_to_js(numbers: &[f32]) -> JsValue {
+    let array = Array::new();
+    for number in numbers {
+        array.push(&JsValue::from_f64(*number as f64));
+    }
+    array.into()
+}
+
+fn set(object: &Object, key: &str, value: JsValue) -> () {
+    Reflect::set(object, &key.into(), &value).ok();
+}
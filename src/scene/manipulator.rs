@@ -0,0 +1,206 @@
+//! Editor API of the transform manipulator drawn over the selected entity. Only built with
+//! the `editor` feature.
+
+use super::Scene;
+use crate::component::Camera;
+use crate::component::{DirtyTransform, Selected, Transform};
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::Ray;
+use crate::resource::{snap, ActiveCamera, Manipulator, ManipulatorDrag, ManipulatorMode};
+use crate::system::{closest_points, get_manipulated_entity, intersect_plane, Gizmo};
+use nalgebra::{Matrix4, Unit, UnitQuaternion};
+use specs::{Entities, Entity, Read, ReadStorage, WorldExt, Write, WriteStorage};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl Scene {
+    /// Sets what dragging the manipulator does: `"translate"` (three arrows), `"rotate"`
+    /// (three circles) or `"scale"` (three cubes). Cancels the current drag.
+    /// Fails for any other mode.
+    pub fn set_manipulator_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        let mode = match mode {
+            "translate" => ManipulatorMode::Translate,
+            "rotate" => ManipulatorMode::Rotate,
+            "scale" => ManipulatorMode::Scale,
+            _ => {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::InvalidArgument,
+                    "Unknown manipulator mode, expected translate, rotate or scale.",
+                    mode,
+                )
+                .into())
+            }
+        };
+        self.manipulator_pointer_up();
+        let mut manipulator = self.world.write_resource::<Manipulator>();
+        manipulator.mode = mode;
+        manipulator.hovered_axis = None;
+        Ok(())
+    }
+
+    /// Sets the increments the manipulator snaps to: a translation step in world units and
+    /// a rotation step in degrees. `0` disables snapping. Scaling is never snapped.
+    pub fn set_snap(&mut self, translation_step: f32, rotation_step_deg: f32) -> () {
+        let mut manipulator = self.world.write_resource::<Manipulator>();
+        manipulator.translation_step = translation_step.max(0.);
+        manipulator.rotation_step = rotation_step_deg.max(0.).to_radians();
+    }
+
+    /// Starts dragging the axis of the manipulator under the pointer, at `x`, `y` in
+    /// normalized device coordinates. Returns `true` if an axis was grabbed, in which case
+    /// the pointer event shouldn't be used for picking or camera controls.
+    pub fn manipulator_pointer_down(&mut self, x: f32, y: f32) -> Result<bool, JsValue> {
+        let ray = self.get_picking_ray(x, y)?;
+        self.manipulator_pointer_up();
+        let grabbed = self.grab_manipulator(&ray);
+        if let Some(entity) = grabbed {
+            self.begin_transaction();
+            self.record_transform_edit(entity.id());
+        }
+        Ok(grabbed.is_some())
+    }
+
+    /// Moves the pointer to `x`, `y` in normalized device coordinates: transforms the
+    /// entity along the dragged axis, snapped to the increments set with `set_snap`, or
+    /// highlights the axis under the pointer if none is dragged.
+    pub fn manipulator_pointer_move(&mut self, x: f32, y: f32) -> Result<(), JsValue> {
+        let ray = self.get_picking_ray(x, y)?;
+        if !self.is_manipulating() {
+            let (entities, transforms, selecteds, cameras, active_camera, mut manipulator): (
+                Entities,
+                ReadStorage<Transform>,
+                ReadStorage<Selected>,
+                ReadStorage<Camera>,
+                Read<ActiveCamera>,
+                Write<Manipulator>,
+            ) = self.world.system_data();
+            let eye_position = match active_camera.entity.and_then(|entity| cameras.get(entity)) {
+                Some(camera) => camera.get_eye_position(),
+                None => return Ok(()),
+            };
+            manipulator.hovered_axis =
+                get_manipulated_entity(&manipulator, &entities, &transforms, &selecteds)
+                    .and_then(|entity| transforms.get(entity))
+                    .and_then(|transform| {
+                        Gizmo::new(transform, manipulator.mode, &eye_position)
+                            .pick(&ray, manipulator.mode)
+                    })
+                    .map(|(axis, _)| axis);
+            return Ok(());
+        }
+        let (mut transforms, mut dirty_transforms, manipulator): (
+            WriteStorage<Transform>,
+            WriteStorage<DirtyTransform>,
+            Write<Manipulator>,
+        ) = self.world.system_data();
+        let drag = match manipulator.drag.clone() {
+            Some(drag) => drag,
+            None => return Ok(()),
+        };
+        let transform = match transforms.get_mut(drag.entity) {
+            Some(transform) => transform,
+            None => return Ok(()),
+        };
+        match manipulator.mode {
+            ManipulatorMode::Translate => {
+                if let Some((_, along_axis)) = closest_points(&ray, &drag.center, &drag.direction) {
+                    let start = (drag.grab_point - drag.center).dot(&drag.direction);
+                    let offset = snap(along_axis - start, manipulator.translation_step);
+                    let local_offset = drag
+                        .parent_inverse
+                        .transform_vector(&(drag.direction * offset));
+                    transform.set_translation(&(drag.translation + local_offset));
+                }
+            }
+            ManipulatorMode::Rotate => {
+                if let Some(distance) = intersect_plane(&ray, &drag.center, &drag.direction) {
+                    let from = drag.grab_point - drag.center;
+                    let to = ray.point_at(distance) - drag.center;
+                    let angle = drag.direction.dot(&from.cross(&to)).atan2(from.dot(&to));
+                    let angle = snap(angle, manipulator.rotation_step);
+                    let local_axis = drag.parent_inverse.transform_vector(&drag.direction);
+                    if let Some(local_axis) = Unit::try_new(local_axis, std::f32::EPSILON) {
+                        let rotation = UnitQuaternion::from_axis_angle(&local_axis, angle);
+                        transform.set_rotation_quaternion(&(rotation * drag.rotation));
+                    }
+                }
+            }
+            ManipulatorMode::Scale => {
+                if let Some((_, along_axis)) = closest_points(&ray, &drag.center, &drag.direction) {
+                    let start = (drag.grab_point - drag.center).dot(&drag.direction);
+                    if start.abs() > std::f32::EPSILON {
+                        let mut scale = drag.scale;
+                        scale[drag.axis] *= along_axis / start;
+                        transform.set_scale(&scale);
+                    }
+                }
+            }
+        }
+        dirty_transforms.insert(drag.entity, DirtyTransform).ok();
+        Ok(())
+    }
+
+    /// Stops dragging the manipulator, keeping the transform it applied as a single undo
+    /// step.
+    pub fn manipulator_pointer_up(&mut self) -> () {
+        let dragged = self.world.write_resource::<Manipulator>().drag.take();
+        if dragged.is_some() {
+            self.end_transaction().ok();
+        }
+    }
+
+    /// Returns `true` while an axis of the manipulator is dragged.
+    pub fn is_manipulating(&self) -> bool {
+        self.world.read_resource::<Manipulator>().drag.is_some()
+    }
+}
+
+impl Scene {
+    /// Starts dragging the axis of the manipulator `ray` points at, if any. Returns the
+    /// manipulated entity.
+    fn grab_manipulator(&mut self, ray: &Ray) -> Option<Entity> {
+        let (entities, transforms, selecteds, cameras, active_camera, mut manipulator): (
+            Entities,
+            ReadStorage<Transform>,
+            ReadStorage<Selected>,
+            ReadStorage<Camera>,
+            Read<ActiveCamera>,
+            Write<Manipulator>,
+        ) = self.world.system_data();
+        let eye_position = match active_camera.entity.and_then(|entity| cameras.get(entity)) {
+            Some(camera) => camera.get_eye_position(),
+            None => return None,
+        };
+        let entity = match get_manipulated_entity(&manipulator, &entities, &transforms, &selecteds)
+        {
+            Some(entity) => entity,
+            None => return None,
+        };
+        let transform = transforms.get(entity).unwrap();
+        let gizmo = Gizmo::new(transform, manipulator.mode, &eye_position);
+        let (axis, grab_point) = match gizmo.pick(ray, manipulator.mode) {
+            Some(picked) => picked,
+            None => return None,
+        };
+        let parent_world_matrix = transform.get_world_matrix()
+            * transform
+                .get_local_matrix()
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity);
+        manipulator.drag = Some(ManipulatorDrag {
+            entity: entity,
+            axis: axis,
+            direction: gizmo.axes[axis],
+            center: gizmo.center,
+            grab_point: grab_point,
+            translation: transform.get_translation().clone(),
+            rotation: transform.get_rotation().clone(),
+            scale: transform.get_scale().clone(),
+            parent_inverse: parent_world_matrix
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity),
+        });
+        manipulator.hovered_axis = Some(axis);
+        Some(entity)
+    }
+}
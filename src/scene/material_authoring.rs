@@ -0,0 +1,24 @@
+//! Editor preview of the shaders generated from material descriptions. Only built with the
+//! `editor` feature.
+
+use super::Scene;
+use crate::renderer::MaterialDescription;
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl Scene {
+    /// Returns the shaders `create_material_from_description` would compile for a JSON
+    /// material description, as an object with `vertex` and `fragment` strings, without
+    /// registering anything. The engine's own defines, such as the number of lights, are
+    /// added when compiling.
+    /// Fails if the description is invalid.
+    pub fn generate_material_shaders(&self, json: &str) -> Result<JsValue, JsValue> {
+        let description = MaterialDescription::from_json(json)?;
+        let (vertex_shader, fragment_shader) = description.generate_shaders();
+        let result = Object::new();
+        Reflect::set(&result, &"vertex".into(), &vertex_shader.into())?;
+        Reflect::set(&result, &"fragment".into(), &fragment_shader.into())?;
+        Ok(result.into())
+    }
+}
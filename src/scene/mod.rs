@@ -2,21 +2,89 @@
 //! The scene has an udpate function to be called each frame.
 //! Under the hood, it uses `specs` to work.
 
+mod entity_handle;
+
+mod inspection;
+
+#[cfg(feature = "editor")]
+mod history;
+
+#[cfg(feature = "editor")]
+mod manipulator;
+
+#[cfg(feature = "editor")]
+mod material_authoring;
+
+#[cfg(feature = "editor")]
+mod quantization;
+
+mod performance_budget;
+
+mod prefab;
+
+mod settings;
+
+#[cfg(feature = "xr")]
+mod xr;
+
+pub use entity_handle::EntityHandle;
+pub use settings::RenderSettings;
+
 #[cfg(feature = "debug")]
 use console_error_panic_hook;
 
+use crate::asset::{asset_file_from_json, asset_file_to_json, AssetRegistry, AtlasRegion};
 use crate::component::*;
-use crate::renderer::{LightConfiguration, LightRepository, Renderer};
-use crate::system::{LightingSystem, RenderingSystem, SceneGraphSystem, ShaderCompilationSystem};
-use crate::utils::console_error;
-use crate::utils::{LightType, Vector3Data};
-use nalgebra::Vector3;
-use specs::{Builder, Entities, ReadStorage, RunNow, World, WorldExt, WriteStorage};
-use specs_hierarchy::HierarchySystem;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::math::{
+    safe_slerp, Aabb, BoundingSphere, Frustum, Ray, Spline, SplineKind, TriangleHit,
+};
+use crate::renderer::{
+    describe_asset_registry, describe_collected_assets, describe_global_uniforms, describe_import,
+    describe_light_configuration, describe_missing_assets, get_material_debug_info,
+    get_shader_contract, BloomSettings, CubeTexture, FrameStats, GlobalUniformValue,
+    LightConfiguration, LightRepository, Material, MaterialDescription, MaterialInstance,
+    MeshCpuData, MeshData, OutlineStyle, Renderer, Uniform,
+};
+use crate::resource::{
+    ActiveCamera, AssetUsage, DrawnEntities, FloatingOrigin, StructuralChanges, Time,
+    TransformWatch, ViewportInfo, Visibility,
+};
+use crate::system::{
+    get_local_bounds, BlobShadowSystem, CameraAspectSystem, CameraShakeSystem, ConstraintSystem,
+    CullingSystem, EnabledPropagationSystem, FadeSystem, LightingSystem, LodSystem, ParticleSystem,
+    PathFollowerSystem, RenderingSystem, SceneGraphSystem, ShaderCompilationSystem, VelocitySystem,
+};
+use crate::utils::constants::{
+    ENVIRONMENT_MAP_NAME, ENVIRONMENT_MAP_TEXTURE_INDEX, FADE_DISTANCE_NAME, PICKING_BVH_THRESHOLD,
+    REFLECTIVITY_NAME, UV_SCROLL_NAME, UV_TRANSFORM_NAME,
+};
+use crate::utils::{
+    call_in_microtask, parse_hex_color, ColorFormat, FrontFace, ImportOptions, LightType,
+    LightUnits, Matrix4Data, PathLoop, PickResult, QuaternionData, RotationOrder, ToneMapping,
+    TransparencyMode, Vector3Data,
+};
+#[cfg(feature = "editor")]
+use crate::{resource::Manipulator, system::ManipulatorSystem};
+use js_sys::{Array, Float32Array, Function, JsString, Promise};
+use nalgebra::{
+    Isometry3, Matrix4, Point3, Translation3, UnitQuaternion, Vector2, Vector3, Vector4,
+};
+use specs::{
+    Builder, Entities, Entity, EntityBuilder, Join, Read, ReadStorage, RunNow, World, WorldExt,
+    Write, WriteStorage,
+};
+use specs_hierarchy::{Hierarchy, HierarchySystem};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
-use web_sys::{HtmlCanvasElement, HtmlImageElement, WebGlRenderingContext};
+use wasm_bindgen::JsCast;
+use web_sys::{
+    HtmlCanvasElement, HtmlImageElement, ImageBitmapOptions, ImageOrientation, PremultiplyAlpha,
+    WebGlRenderingContext,
+};
 
 /// Scene representation, to be shared with JS.
 /// A scene holds a renderer and a `specs` world.
@@ -31,13 +99,58 @@ pub struct Scene {
 
     hierarchy_system: HierarchySystem<TransformParent>,
 
+    enabled_propagation_system: EnabledPropagationSystem,
+
     scene_graph_system: SceneGraphSystem,
 
+    constraint_system: ConstraintSystem,
+
+    camera_aspect_system: CameraAspectSystem,
+
+    camera_shake_system: CameraShakeSystem,
+
+    lod_system: LodSystem,
+
     lighting_system: LightingSystem,
 
+    culling_system: Option<CullingSystem>,
+
+    particle_system: ParticleSystem,
+
+    velocity_system: VelocitySystem,
+
+    path_follower_system: PathFollowerSystem,
+
+    fade_system: FadeSystem,
+
+    blob_shadow_system: BlobShadowSystem,
+
     shader_compilation_system: Option<ShaderCompilationSystem>,
 
     rendering_system: Option<RenderingSystem>,
+
+    #[cfg(feature = "editor")]
+    manipulator_system: Option<ManipulatorSystem>,
+
+    /// Undo and redo stacks of the edits
+    #[cfg(feature = "editor")]
+    history: history::History,
+
+    /// Registered prefabs, by id
+    prefabs: HashMap<String, prefab::PrefabNode>,
+
+    /// Called after each update with the entities entering and leaving the view, if set
+    visibility_callback: Option<Function>,
+
+    /// Called after each update in which the floating origin moved, if set
+    origin_callback: Option<Function>,
+
+    /// Draw call and triangle budgets checked after each rendered frame
+    performance_budget: performance_budget::PerformanceBudget,
+
+    /// Running XR session, if any
+    #[cfg(feature = "xr")]
+    xr: Option<xr::XrState>,
 }
 
 #[wasm_bindgen]
@@ -59,9 +172,30 @@ impl Scene {
             world: world,
             scene_graph_system: SceneGraphSystem::new(),
             hierarchy_system: hierarchy_system,
+            enabled_propagation_system: EnabledPropagationSystem,
+            constraint_system: ConstraintSystem,
+            camera_aspect_system: CameraAspectSystem,
+            camera_shake_system: CameraShakeSystem,
+            lod_system: LodSystem,
             lighting_system: LightingSystem {},
+            culling_system: None,
+            particle_system: ParticleSystem,
+            velocity_system: VelocitySystem,
+            path_follower_system: PathFollowerSystem,
+            fade_system: FadeSystem,
+            blob_shadow_system: BlobShadowSystem,
             shader_compilation_system: None,
             rendering_system: None,
+            #[cfg(feature = "editor")]
+            manipulator_system: None,
+            #[cfg(feature = "editor")]
+            history: Default::default(),
+            prefabs: HashMap::new(),
+            visibility_callback: None,
+            origin_callback: None,
+            performance_budget: Default::default(),
+            #[cfg(feature = "xr")]
+            xr: None,
         };
 
         #[cfg(feature = "debug")]
@@ -90,11 +224,18 @@ impl Scene {
             &position.to_point3(),
             &target.to_point3(),
         );
-        let entity = self.world.create_entity().with(camera).build();
+        let entity = Scene::build_entity(&mut self.world)
+            .with(camera)
+            .with(Enabled)
+            .build();
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
         entity.id()
     }
 
-    /// Creates an entity holding a light and an optional direction/position if supplied
+    /// Creates an entity holding a light and an optional direction/position if supplied.  
+    /// `attenuation` is the range of point lights in world units, `0` for lights that don't
+    /// fade with distance. Use `set_light_attenuation` for other falloff models.
     pub fn create_light_entity(
         &mut self,
         light_type: LightType,
@@ -106,20 +247,23 @@ impl Scene {
         let light = Light {
             color: color.to_vector3(),
             intensity: intensity,
-            attenuation: attenuation,
+            attenuation: if attenuation > 0.0 {
+                Attenuation::Range(attenuation)
+            } else {
+                Attenuation::None
+            },
         };
         let entity = match light_type {
-            LightType::Ambiant => self.world.create_entity().with(light).with(Enabled).build(),
-            LightType::Directional => self
-                .world
-                .create_entity()
+            LightType::Ambiant => Scene::build_entity(&mut self.world)
+                .with(light)
+                .with(Enabled)
+                .build(),
+            LightType::Directional => Scene::build_entity(&mut self.world)
                 .with(light)
                 .with(Direction(direction_or_position.to_vector3()))
                 .with(Enabled)
                 .build(),
-            LightType::Point => self
-                .world
-                .create_entity()
+            LightType::Point => Scene::build_entity(&mut self.world)
                 .with(light)
                 .with(Transform::new(
                     &direction_or_position.to_vector3(),
@@ -128,53 +272,383 @@ impl Scene {
                 ))
                 .with(Enabled)
                 .build(),
-            _ => panic!("Unsupported light type"),
+            LightType::Cone => {
+                log_error!("Spot lights must be created with `create_spot_light`.");
+                return u32::max_value();
+            }
         };
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
         entity.id()
     }
 
-    pub fn create_mesh_entity(&mut self, mesh_data_id: &str, material_instance_id: &str) -> u32 {
-        if let Some(renderer_rc) = &self.main_renderer {
-            let renderer = renderer_rc.borrow();
-            let asset_registry = renderer.get_asset_registry();
-            let mesh_data_option = asset_registry.get_mesh_data(mesh_data_id);
-            let material_instance_option =
-                asset_registry.get_material_instance(material_instance_id);
-            if let (Some(_), Some(material_instance)) = (mesh_data_option, material_instance_option)
-            {
-                let parent_material = material_instance.borrow().get_parent().clone();
-                let mesh = Mesh::new(
-                    asset_registry.get_id_from_str(mesh_data_id).unwrap(),
-                    asset_registry
-                        .get_id_from_str(material_instance_id)
-                        .unwrap(),
-                    asset_registry
-                        .get_id_from_str(parent_material.borrow().get_id())
-                        .unwrap(),
-                );
-                let entity = self
-                    .world
-                    .create_entity()
-                    .with(mesh)
-                    .with(Transform::new(
-                        &Vector3::new(0., 0., 0.),
-                        &Vector3::new(0., 0., 0.),
-                        &Vector3::new(1., 1., 1.),
-                    ))
-                    .with(DirtyTransform)
-                    .with(Enabled)
-                    .build();
-                entity.id()
+    /// Creates a spot light at `position` pointing to `direction`, fully lit up to
+    /// `inner_angle` radians from its axis and fading out up to `outer_angle`. `range` is the
+    /// distance at which it fades out, `0` for no falloff. Returns its Entity ID.  
+    /// Fails if the angles are not `0 <= inner_angle <= outer_angle < π / 2`.
+    pub fn create_spot_light(
+        &mut self,
+        color: Vector3Data,
+        intensity: f32,
+        range: f32,
+        position: Vector3Data,
+        direction: Vector3Data,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Result<u32, JsValue> {
+        let cone = spot_cone(inner_angle, outer_angle)?;
+        let light = Light {
+            color: color.to_vector3(),
+            intensity: intensity,
+            attenuation: if range > 0.0 {
+                Attenuation::Range(range)
             } else {
-                console_error("Provided material instance could not be found in registry. Did you forget to register it?");
-                u32::max_value()
+                Attenuation::None
+            },
+        };
+        let entity = Scene::build_entity(&mut self.world)
+            .with(light)
+            .with(Direction(direction.to_vector3()))
+            .with(cone)
+            .with(Transform::new(
+                &position.to_vector3(),
+                &Vector3::new(0.0, 0.0, 0.0),
+                &Vector3::new(1.0, 1.0, 1.0),
+            ))
+            .with(DirtyTransform)
+            .with(Enabled)
+            .build();
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Sets the direction a spot light points to.  
+    /// Fails if the entity is not a spot light.
+    pub fn set_spot_direction(
+        &mut self,
+        entity_id: u32,
+        direction: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let (cones, mut directions, entities): (
+            ReadStorage<Cone>,
+            WriteStorage<Direction>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !cones.contains(entity) {
+            return Err(missing_component_error("Cone", entity_id).into());
+        }
+        match directions.get_mut(entity) {
+            Some(current) => {
+                current.0 = direction.to_vector3();
+                Ok(())
             }
-        } else {
-            u32::max_value()
+            None => Err(missing_component_error("Direction", entity_id).into()),
+        }
+    }
+
+    /// Sets the angles of a spot light's cone, in radians: fully lit up to `inner_angle`
+    /// from its axis and fading out up to `outer_angle`.  
+    /// Fails if the entity is not a spot light, or if the angles are not
+    /// `0 <= inner_angle <= outer_angle < π / 2`.
+    pub fn set_spot_cone(
+        &mut self,
+        entity_id: u32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Result<(), JsValue> {
+        let cone = spot_cone(inner_angle, outer_angle)?;
+        let (mut cones, entities): (WriteStorage<Cone>, Entities) = self.world.system_data();
+        match cones.get_mut(entities.entity(entity_id)) {
+            Some(current) => {
+                *current = cone;
+                Ok(())
+            }
+            None => Err(missing_component_error("Cone", entity_id).into()),
+        }
+    }
+
+    /// Returns the current number of lights of each type, as
+    /// `{ directional, point, spot, environmentMap }`. Lit shaders are compiled with these
+    /// counts as their light array sizes. Updated every frame.
+    pub fn get_light_configuration(&self) -> JsValue {
+        describe_light_configuration(&self.world.read_resource::<LightConfiguration>())
+    }
+
+    /// Returns the names and types of the attributes, uniforms and defines the engine uses,
+    /// for tooling generating shader boilerplate. See `renderer::get_shader_contract`.
+    pub fn get_shader_contract(&self) -> JsValue {
+        get_shader_contract()
+    }
+
+    /// Creates a hemisphere ambient light, lighting surfaces facing up with `sky_hex` and
+    /// surfaces facing down with `ground_hex`, with a gradient between. Colors are given as
+    /// `#rrggbb`. Returns its Entity ID.  
+    /// It adds up with flat ambient lights; only one hemisphere light is used at a time.
+    pub fn create_hemisphere_light(
+        &mut self,
+        sky_hex: &str,
+        ground_hex: &str,
+        intensity: f32,
+    ) -> Result<u32, JsValue> {
+        let light = Light {
+            color: parse_hex_color(sky_hex)?,
+            intensity: intensity,
+            attenuation: Attenuation::None,
+        };
+        let hemisphere = Hemisphere {
+            ground_color: parse_hex_color(ground_hex)?,
+            up: Vector3::new(0.0, 1.0, 0.0),
+        };
+        let entity = Scene::build_entity(&mut self.world)
+            .with(light)
+            .with(hemisphere)
+            .with(Enabled)
+            .build();
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Sets the world direction a hemisphere light's sky color comes from. `+Y` by default.
+    pub fn set_hemisphere_light_up(
+        &mut self,
+        entity_id: u32,
+        up: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let (mut hemispheres, entities): (WriteStorage<Hemisphere>, Entities) =
+            self.world.system_data();
+        match hemispheres.get_mut(entities.entity(entity_id)) {
+            Some(hemisphere) => {
+                hemisphere.up = up.to_vector3();
+                Ok(())
+            }
+            None => Err(missing_component_error("Hemisphere", entity_id).into()),
+        }
+    }
+
+    /// Sets a light's falloff to `1 / (constant + linear * d + quadratic * d²)`, `d` being
+    /// the distance to the lit point. Only point lights are attenuated.
+    pub fn set_light_attenuation(
+        &mut self,
+        entity_id: u32,
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    ) -> Result<(), JsValue> {
+        self.with_light(entity_id, |light| {
+            light.attenuation = Attenuation::Coefficients {
+                constant: constant,
+                linear: linear,
+                quadratic: quadratic,
+            }
+        })
+    }
+
+    /// Sets a light's falloff to a physically based inverse square falloff reaching zero at
+    /// `range` world units. Only point lights are attenuated.
+    pub fn set_light_range(&mut self, entity_id: u32, range: f32) -> Result<(), JsValue> {
+        self.with_light(entity_id, |light| {
+            light.attenuation = Attenuation::Range(range)
+        })
+    }
+
+    /// Makes a light not fade with distance.
+    pub fn clear_light_attenuation(&mut self, entity_id: u32) -> Result<(), JsValue> {
+        self.with_light(entity_id, |light| light.attenuation = Attenuation::None)
+    }
+
+    /// Creates a mesh entity drawing a mesh data with a material instance. Returns its Entity
+    /// ID, or `u32::max_value()` if the scene is not initialized.  
+    /// Missing assets are replaced by fallbacks (see `get_missing_asset_report`), unless
+    /// strict assets are enabled, in which case `u32::max_value()` is returned too.
+    pub fn create_mesh_entity(&mut self, mesh_data_id: &str, material_instance_id: &str) -> u32 {
+        let mesh = match &self.main_renderer {
+            Some(renderer) => {
+                let mut renderer = renderer.borrow_mut();
+                let referenced_by = "create_mesh_entity";
+                let resolved = renderer
+                    .resolve_mesh_data(mesh_data_id, referenced_by)
+                    .and_then(|mesh_data_index| {
+                        let (instance_index, material_index) = renderer
+                            .resolve_material_instance(material_instance_id, referenced_by)?;
+                        Ok(Mesh::new(mesh_data_index, instance_index, material_index))
+                    });
+                match resolved {
+                    Ok(mesh) => mesh,
+                    Err(error) => {
+                        log_error!("{}", error);
+                        return u32::max_value();
+                    }
+                }
+            }
+            None => return u32::max_value(),
+        };
+        let entity = Scene::build_entity(&mut self.world)
+            .with(mesh)
+            .with(Transform::new(
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(1., 1., 1.),
+            ))
+            .with(DirtyTransform)
+            .with(Enabled)
+            .build();
+        Scene::count_asset_users(&self.world, entity);
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        entity.id()
+    }
+
+    /// Creates a mesh entity whose mesh data is split in sub-meshes, drawing sub-mesh `i`
+    /// with `material_instance_ids[i]`. Returns its Entity ID.  
+    /// Missing assets are replaced by fallbacks, as in `create_mesh_entity`.  
+    /// Fails if the scene is not initialized, if no material instance is given, or if an
+    /// asset is missing while strict assets are enabled.
+    pub fn create_mesh_entity_with_materials(
+        &mut self,
+        mesh_data_id: &str,
+        material_instance_ids: Vec<String>,
+    ) -> Result<u32, JsValue> {
+        if material_instance_ids.is_empty() {
+            return Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "A mesh entity needs at least one material instance.",
+            )
+            .into());
+        }
+        let mesh = match &self.main_renderer {
+            Some(renderer) => {
+                let mut renderer = renderer.borrow_mut();
+                let referenced_by = "create_mesh_entity_with_materials";
+                let mesh_data_index = renderer.resolve_mesh_data(mesh_data_id, referenced_by)?;
+                let mut materials = Vec::with_capacity(material_instance_ids.len());
+                for material_instance_id in &material_instance_ids {
+                    materials.push(
+                        renderer.resolve_material_instance(material_instance_id, referenced_by)?,
+                    );
+                }
+                Mesh::with_sub_meshes(mesh_data_index, materials)
+            }
+            None => {
+                return Err(W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Mesh entities can't be created before initializing the scene.",
+                )
+                .into())
+            }
+        };
+        let entity = Scene::build_entity(&mut self.world)
+            .with(mesh)
+            .with(Transform::new(
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(1., 1., 1.),
+            ))
+            .with(DirtyTransform)
+            .with(Enabled)
+            .build();
+        Scene::count_asset_users(&self.world, entity);
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Registers a prefab from a JS description of a tree of entities (see
+    /// `prefab::parse_prefab` for its layout) and returns its id, or an empty string on
+    /// failure.  
+    /// Its assets are resolved now: errors are reported here rather than at instantiation.
+    /// A prefab already registered under the same id is replaced.
+    pub fn register_prefab(&mut self, definition: JsValue) -> String {
+        let renderer = match &self.main_renderer {
+            Some(renderer) => renderer,
+            None => {
+                log_error!("Trying to register a prefab before initializing the renderer!");
+                return String::new();
+            }
+        };
+        match prefab::parse_prefab(&definition, &mut renderer.borrow_mut()) {
+            Ok((id, root)) => {
+                self.prefabs.insert(id.clone(), root);
+                id
+            }
+            Err(error) => {
+                log_error!("{}", error);
+                String::new()
+            }
+        }
+    }
+
+    /// Creates the entities of a registered prefab, its root being offset by `position`.
+    /// Returns the root's Entity ID.  
+    /// Fails if the prefab is not registered.
+    pub fn instantiate_prefab(
+        &mut self,
+        prefab_id: &str,
+        position: Vector3Data,
+    ) -> Result<u32, JsValue> {
+        let root = self.prefabs.get(prefab_id).ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Prefab could not be found. Has it been registered yet?",
+                prefab_id,
+            )
+        })?;
+        let entity = prefab::instantiate(&mut self.world, root, &position.to_vector3());
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Splits a registered mesh data in sub-meshes sharing its vertex buffers, from
+    /// `(index offset, index count)` pairs flattened in `ranges`. An empty array draws the
+    /// whole mesh at once again.  
+    /// Fails if the mesh data isn't registered or if a range is out of bounds.
+    pub fn set_mesh_data_sub_meshes(
+        &mut self,
+        mesh_data_id: &str,
+        ranges: &[u32],
+    ) -> Result<(), JsValue> {
+        if ranges.len() % 2 != 0 {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Expected (offset, count) pairs for the sub-mesh ranges.",
+                &format!("{} values", ranges.len()),
+            )
+            .into());
+        }
+        let renderer = match &self.main_renderer {
+            Some(renderer) => renderer.borrow(),
+            None => {
+                return Err(W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Sub-meshes can't be set before initializing the scene.",
+                )
+                .into())
+            }
+        };
+        match renderer.get_asset_registry().get_mesh_data(mesh_data_id) {
+            Some(mesh_data) => {
+                let sub_meshes = ranges
+                    .chunks(2)
+                    .map(|range| (range[0] as i32, range[1] as i32))
+                    .collect();
+                mesh_data.borrow_mut().set_sub_meshes(sub_meshes)?;
+                Ok(())
+            }
+            None => Err(W3DError::with_source(
+                W3DErrorKind::MissingAsset,
+                "Mesh data could not be found. Has it been registered yet?",
+                mesh_data_id,
+            )
+            .into()),
         }
     }
 
     pub fn set_transform_translation(&mut self, entity_id: u32, new_translation: Vector3Data) {
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
         let mut system_data: (
             WriteStorage<Transform>,
             Entities,
@@ -184,14 +658,16 @@ impl Scene {
         if let Some(transform) = system_data.0.get_mut(entity) {
             transform.set_translation(&new_translation.to_vector3());
         } else {
-            console_error("Could not find transform for entity.");
+            log_error!("Could not find transform for entity.");
         }
         if let Err(_) = system_data.2.insert(entity, DirtyTransform) {
-            console_error("Could not mark the entity as dirty");
+            log_error!("Could not mark the entity as dirty");
         }
     }
 
     pub fn set_transform_rotation(&mut self, entity_id: u32, new_rotation: Vector3Data) {
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
         let mut system_data: (
             WriteStorage<Transform>,
             Entities,
@@ -201,14 +677,16 @@ impl Scene {
         if let Some(transform) = system_data.0.get_mut(entity) {
             transform.set_rotation(&new_rotation.to_vector3());
         } else {
-            console_error("Could not find transform for entity.");
+            log_error!("Could not find transform for entity.");
         }
         if let Err(_) = system_data.2.insert(entity, DirtyTransform) {
-            console_error("Could not mark the entity as dirty");
+            log_error!("Could not mark the entity as dirty");
         }
     }
 
     pub fn set_transform_scale(&mut self, entity_id: u32, new_scale: Vector3Data) {
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
         let mut system_data: (
             WriteStorage<Transform>,
             Entities,
@@ -218,10 +696,139 @@ impl Scene {
         if let Some(transform) = system_data.0.get_mut(entity) {
             transform.set_scale(&new_scale.to_vector3());
         } else {
-            console_error("Could not find transform for entity.");
+            log_error!("Could not find transform for entity.");
+        }
+        if let Err(_) = system_data.2.insert(entity, DirtyTransform) {
+            log_error!("Could not mark the entity as dirty");
+        }
+    }
+
+    /// Sets the local rotation of an entity from Euler angles applied in `order`, instead of
+    /// the default order given by `get_rotation_order`.
+    pub fn set_transform_rotation_with_order(
+        &mut self,
+        entity_id: u32,
+        new_rotation: Vector3Data,
+        order: RotationOrder,
+    ) -> Result<(), JsValue> {
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
+        let (mut transforms, entities, mut dirty_transforms): (
+            WriteStorage<Transform>,
+            Entities,
+            WriteStorage<DirtyTransform>,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        match transforms.get_mut(entity) {
+            Some(transform) => {
+                transform.set_rotation_with_order(&new_rotation.to_vector3(), order);
+                dirty_transforms.insert(entity, DirtyTransform).ok();
+                Ok(())
+            }
+            None => Err(missing_component_error("Transform", entity_id).into()),
+        }
+    }
+
+    /// Returns the order in which the Euler angles given to the scene are applied:
+    /// `RotationOrder::Xyz`, X first, then Y, then Z, around the parent's axes. This is
+    /// Blender's `XYZ Euler` mode, and Three.js's `'ZYX'` order.
+    pub fn get_rotation_order(&self) -> RotationOrder {
+        RotationOrder::Xyz
+    }
+
+    /// Sets the local translation, rotation and scale of an entity from a 4x4 local matrix,
+    /// given as 16 column-major values.  
+    /// Fails if the entity has no `Transform`, or if the matrix has a perspective part, a
+    /// shear or a null scale.
+    pub fn set_local_matrix(&mut self, entity_id: u32, matrix: &[f32]) -> Result<(), JsValue> {
+        let matrix = Matrix4Data::new(matrix)?.to_matrix4();
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
+        let (mut transforms, entities, mut dirty_transforms): (
+            WriteStorage<Transform>,
+            Entities,
+            WriteStorage<DirtyTransform>,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        match transforms.get_mut(entity) {
+            Some(transform) => {
+                transform.set_local_matrix(&matrix)?;
+                dirty_transforms.insert(entity, DirtyTransform).ok();
+                Ok(())
+            }
+            None => Err(missing_component_error("Transform", entity_id).into()),
+        }
+    }
+
+    /// Sets the local rotation of an entity from a quaternion, bypassing Euler angles.
+    pub fn set_transform_rotation_quaternion(
+        &mut self,
+        entity_id: u32,
+        new_rotation: QuaternionData,
+    ) {
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
+        let mut system_data: (
+            WriteStorage<Transform>,
+            Entities,
+            WriteStorage<DirtyTransform>,
+        ) = self.world.system_data();
+        let entity = system_data.1.entity(entity_id);
+        if let Some(transform) = system_data.0.get_mut(entity) {
+            transform.set_rotation_quaternion(&new_rotation.to_unit_quaternion());
+        } else {
+            log_error!("Could not find transform for entity.");
         }
         if let Err(_) = system_data.2.insert(entity, DirtyTransform) {
-            console_error("Could not mark the entity as dirty");
+            log_error!("Could not mark the entity as dirty");
+        }
+    }
+
+    /// Returns the local rotation of an entity as a quaternion.
+    pub fn get_transform_rotation_quaternion(
+        &self,
+        entity_id: u32,
+    ) -> Result<QuaternionData, JsValue> {
+        let system_data: (ReadStorage<Transform>, Entities) = self.world.system_data();
+        let entity = system_data.1.entity(entity_id);
+        match system_data.0.get(entity) {
+            Some(transform) => Ok(QuaternionData::from_unit_quaternion(
+                transform.get_rotation(),
+            )),
+            None => Err(missing_component_error("Transform", entity_id).into()),
+        }
+    }
+
+    /// Returns the world matrix of an entity (column-major), as computed during the last update.
+    pub fn get_world_matrix(&self, entity_id: u32) -> Result<Matrix4Data, JsValue> {
+        let system_data: (ReadStorage<Transform>, Entities) = self.world.system_data();
+        let entity = system_data.1.entity(entity_id);
+        match system_data.0.get(entity) {
+            Some(transform) => Ok(Matrix4Data::from_matrix4(&transform.get_world_matrix())),
+            None => Err(missing_component_error("Transform", entity_id).into()),
+        }
+    }
+
+    /// Returns the view matrix (column-major) of a camera entity.
+    pub fn get_camera_view_matrix(&self, camera_entity_id: u32) -> Result<Matrix4Data, JsValue> {
+        let system_data: (ReadStorage<Camera>, Entities) = self.world.system_data();
+        let entity = system_data.1.entity(camera_entity_id);
+        match system_data.0.get(entity) {
+            Some(camera) => Ok(Matrix4Data::from_matrix4(&camera.get_view_matrix())),
+            None => Err(missing_component_error("Camera", camera_entity_id).into()),
+        }
+    }
+
+    /// Returns the projection matrix (column-major) of a camera entity.
+    pub fn get_camera_projection_matrix(
+        &self,
+        camera_entity_id: u32,
+    ) -> Result<Matrix4Data, JsValue> {
+        let system_data: (ReadStorage<Camera>, Entities) = self.world.system_data();
+        let entity = system_data.1.entity(camera_entity_id);
+        match system_data.0.get(entity) {
+            Some(camera) => Ok(Matrix4Data::from_matrix4(&camera.get_projection_matrix())),
+            None => Err(missing_component_error("Camera", camera_entity_id).into()),
         }
     }
 
@@ -232,6 +839,8 @@ impl Scene {
         new_rotation: Vector3Data,
         new_scale: Vector3Data,
     ) {
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
         let mut system_data: (
             WriteStorage<Transform>,
             Entities,
@@ -243,14 +852,53 @@ impl Scene {
             transform.set_rotation(&new_rotation.to_vector3());
             transform.set_scale(&new_scale.to_vector3());
         } else {
-            console_error("Could not find transform for entity.");
+            log_error!("Could not find transform for entity.");
         }
         if let Err(_) = system_data.2.insert(entity, DirtyTransform) {
-            console_error("Could not mark the entity as dirty");
+            log_error!("Could not mark the entity as dirty");
         }
     }
 
+    /// Updates the transforms of many entities in a single call.
+    ///
+    /// `data` holds 9 floats per entity, in the same order as `entity_ids`: translation (x, y, z),
+    /// Euler rotation (x, y, z) and scale (x, y, z).  
+    /// Every entity with a `Transform` is updated even if some ids are invalid; the ids that could
+    /// not be updated are reported at the end in a single error.
+    pub fn set_transforms_batch(
+        &mut self,
+        entity_ids: &[u32],
+        data: &[f32],
+    ) -> Result<(), JsValue> {
+        self.apply_transforms_batch(entity_ids, data, 9, |transform, values| {
+            transform.set_translation(&Vector3::new(values[0], values[1], values[2]));
+            transform.set_rotation(&Vector3::new(values[3], values[4], values[5]));
+            transform.set_scale(&Vector3::new(values[6], values[7], values[8]));
+        })?;
+        Ok(())
+    }
+
+    /// Same as `set_transforms_batch` with a quaternion rotation: `data` holds 10 floats per entity,
+    /// translation (x, y, z), rotation quaternion (x, y, z, w) and scale (x, y, z).
+    pub fn set_transforms_batch_quaternion(
+        &mut self,
+        entity_ids: &[u32],
+        data: &[f32],
+    ) -> Result<(), JsValue> {
+        self.apply_transforms_batch(entity_ids, data, 10, |transform, values| {
+            transform.set_translation(&Vector3::new(values[0], values[1], values[2]));
+            transform.set_rotation_quaternion(
+                &QuaternionData::new(values[3], values[4], values[5], values[6])
+                    .to_unit_quaternion(),
+            );
+            transform.set_scale(&Vector3::new(values[7], values[8], values[9]));
+        })?;
+        Ok(())
+    }
+
     pub fn set_parent(&mut self, entity_id: u32, parent_id: u32) {
+        #[cfg(feature = "editor")]
+        self.record_parent_edit(entity_id);
         let mut system_data: (
             WriteStorage<TransformParent>,
             Entities,
@@ -265,123 +913,4193 @@ impl Scene {
                 .0
                 .insert(entity, TransformParent::new(parent_entity))
             {
-                console_error("Could not add parent relationship.");
+                log_error!("Could not add parent relationship.");
             }
         }
         if let Err(_) = system_data.2.insert(entity, DirtyTransform) {
-            console_error("Could not mark the entity as dirty");
+            log_error!("Could not mark the entity as dirty");
         }
     }
 
-    pub fn register_asset(&mut self, file_data: &[u8], file_type: FileType) -> String {
-        match &mut self.main_renderer {
-            None => {
-                console_error("Trying to register asset before initializing renderer!");
-                String::new()
-            }
-            Some(renderer) => match renderer.borrow_mut().register_asset(file_data, file_type) {
-                Err(message) => {
-                    console_error(&message);
-                    String::new()
-                }
-                Ok(id) => id,
-            },
+    /// Returns a handle to a live entity, whose methods fail once the entity is removed
+    /// instead of acting on another entity reusing its id.  
+    /// Works with the ids returned by every `create_*` method.
+    pub fn get_entity_handle(&self, entity_id: u32) -> Result<EntityHandle, JsValue> {
+        let entities: Entities = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity does not exist.",
+                &entity_id.to_string(),
+            )
+            .into());
         }
+        Ok(EntityHandle::new(entity))
     }
 
-    pub fn register_texture(&mut self, image: &HtmlImageElement, id: String) -> String {
-        match &mut self.main_renderer {
-            None => {
-                console_error("Trying to register asset before initializing renderer!");
-                String::new()
-            }
-            Some(renderer) => match renderer.borrow_mut().register_texture(image, id) {
-                Err(message) => {
-                    console_error(&message);
-                    String::new()
-                }
-                Ok(id) => id,
-            },
+    /// Removes an entity and its descendants. Their ids are reused by the next entities
+    /// created, with a new generation so that older `EntityHandle`s detect it.
+    pub fn remove_entity(&mut self, entity_id: u32) -> Result<(), JsValue> {
+        let entity = self.world.entities().entity(entity_id);
+        if !self.world.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity does not exist.",
+                &entity_id.to_string(),
+            )
+            .into());
         }
+        #[cfg(feature = "editor")]
+        self.record_deletion(entity_id);
+        let removed = Scene::get_entity_tree(&self.world, entity);
+        Scene::delete_entities(&mut self.world, &removed)?;
+        Ok(())
     }
 
-    /// Initializes the renderer for this Scene. This might fail if no valid camera is supplied.
-    pub fn initialize(
-        &mut self,
-        canvas: HtmlCanvasElement,
-        context: WebGlRenderingContext,
-        camera_entity: u32,
-    ) -> () {
-        if let Some(_) = &self.main_renderer {
-            return;
+    /// Returns `true` if the entity passed the culling tests of the last update. Entities
+    /// without bounds are always visible, and entities that don't exist or have no mesh
+    /// never are.
+    pub fn was_visible_last_frame(&self, entity_id: u32) -> bool {
+        self.world
+            .read_resource::<Visibility>()
+            .is_visible(entity_id)
+    }
+
+    /// Sets a function called at the end of each update in which entities entered or left
+    /// the view, with an array of the ids of the newly visible entities and an array of the
+    /// newly hidden ones. Only entities whose visibility is tested are reported: entities
+    /// without bounds or flagged always visible never are. `None` removes it.  
+    /// The function is called once `update` has returned, so it can use the scene, for
+    /// instance to create or remove entities.
+    pub fn on_visibility_changed(&mut self, callback: Option<Function>) -> () {
+        self.visibility_callback = callback;
+    }
+
+    /// Enables the floating origin: whenever the active camera gets farther than `threshold`
+    /// from the origin, the whole scene is moved back so that the camera is at the origin
+    /// again, keeping positions near the camera precise. `None` disables it.  
+    /// Root translations, world matrices, cameras and live particles are shifted, and the
+    /// shift is accumulated in double precision, see `get_origin_offset`. Positions given to
+    /// the scene afterwards are relative to the current origin.
+    pub fn enable_floating_origin(&mut self, threshold: Option<f32>) -> () {
+        self.world
+            .write_resource::<FloatingOrigin>()
+            .set_threshold(threshold);
+    }
+
+    /// Returns the position of the scene's origin in the true world, as 3 doubles: true
+    /// world positions are scene positions plus this offset.
+    pub fn get_origin_offset(&self) -> Vec<f64> {
+        let origin = self.world.read_resource::<FloatingOrigin>();
+        origin.get_offset().iter().cloned().collect()
+    }
+
+    /// Sets a function called at the end of each update in which the floating origin moved,
+    /// with the x, y and z of the shift, subtracted from every position of the scene. Watched
+    /// transforms are reported as changed as well. `None` removes it.  
+    /// The function is called once `update` has returned, so it can use the scene.
+    pub fn on_origin_shifted(&mut self, callback: Option<Function>) -> () {
+        self.origin_callback = callback;
+    }
+
+    /// Starts or stops reporting the world transform changes of an entity through
+    /// `take_changed_transforms`.  
+    /// Fails if the entity does not exist or has no `Transform`.
+    pub fn watch_transform(&mut self, entity_id: u32, watched: bool) -> Result<(), JsValue> {
+        let (transforms, entities): (ReadStorage<Transform>, Entities) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) || !transforms.contains(entity) {
+            return Err(missing_component_error("Transform", entity_id).into());
         }
-        let camera_opt = self.get_camera_for_rendering(camera_entity);
-        match camera_opt {
-            Err(message) => {
-                console_error(message.clone().as_str());
-                panic!(message)
-            }
-            Ok(camera) => {
-                let renderer = Rc::new(RefCell::new(Renderer::new(camera, canvas, context)));
-                self.main_renderer = Some(renderer.clone());
-                self.rendering_system = Some(RenderingSystem::new(renderer.clone()));
-                self.shader_compilation_system =
-                    Some(ShaderCompilationSystem::new(renderer.clone()));
-            }
+        self.world
+            .write_resource::<TransformWatch>()
+            .set_watched(entity_id, watched);
+        Ok(())
+    }
+
+    /// Returns the watched entities whose world matrix changed since the last call, as 17
+    /// floats per entity: its id, then its world matrix in column-major order.  
+    /// Ids are exact as floats up to 2^24.
+    pub fn take_changed_transforms(&mut self) -> Float32Array {
+        let changed = self.world.write_resource::<TransformWatch>().take_changed();
+        let (transforms, entities): (ReadStorage<Transform>, Entities) = self.world.system_data();
+        let mut data = Vec::new();
+        for (entity, transform, _) in (&entities, &transforms, &changed).join() {
+            data.push(entity.id() as f32);
+            data.extend_from_slice(transform.get_world_matrix().as_slice());
         }
+        Float32Array::from(data.as_slice())
     }
 
-    /// Function to be called each frame.
-    pub fn update(&mut self) -> () {
-        if let (Some(renderer), Some(rendering_system), Some(shader_system)) = (
-            &mut self.main_renderer,
-            &mut self.rendering_system,
-            &mut self.shader_compilation_system,
-        ) {
-            renderer.borrow_mut().resize_canvas();
-            self.hierarchy_system.run_now(&self.world);
-            self.scene_graph_system.run_now(&self.world);
-            self.lighting_system.run_now(&self.world);
-            shader_system.run_now(&self.world);
-            rendering_system.run_now(&self.world);
-            self.world.maintain();
+    /// Enables or disables an entity. A disabled entity and its whole subtree are neither
+    /// rendered, lit nor simulated, starting next update. Their transforms are still
+    /// refreshed when moved, so that their world matrices are never stale.  
+    /// Descendants keep their own state, and are active again once every ancestor is enabled.
+    pub fn set_enabled(&mut self, entity_id: u32, enabled: bool) -> Result<(), JsValue> {
+        let (mut enableds, entities): (WriteStorage<Enabled>, Entities) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "Could not find entity.",
+                &entity_id.to_string(),
+            )
+            .into());
+        }
+        if enabled {
+            enableds.insert(entity, Enabled).ok();
         } else {
-            console_error("Trying to update before initializing the renderer!");
+            enableds.remove(entity);
         }
+        Ok(())
     }
-}
 
-impl Scene {
-    /// Registers every common component for the current world.
-    fn register_components(&mut self) -> () {
-        self.world.register::<Transform>();
-        self.world.register::<TransformParent>();
-        self.world.register::<Camera>();
-        self.world.register::<Mesh>();
-        self.world.register::<DirtyTransform>();
-        self.world.register::<Enabled>();
-        self.world.register::<Light>();
-        self.world.register::<Direction>();
-        self.world.register::<Cone>();
+    /// Returns `true` if an entity is enabled and none of its ancestors is disabled, as of the
+    /// last update.
+    pub fn is_enabled(&self, entity_id: u32) -> bool {
+        let (enableds, effectively_disabled, entities): (
+            ReadStorage<Enabled>,
+            ReadStorage<EffectivelyDisabled>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        enableds.contains(entity) && !effectively_disabled.contains(entity)
     }
 
-    /// Instanciates and registers the resources for the current world.
-    fn register_resources(&mut self) -> () {
-        let light_repo: LightRepository = Default::default();
-        let light_config: LightConfiguration = Default::default();
-        self.world.insert(light_repo);
-        self.world.insert(light_config);
+    /// Makes an entity follow a target entity at a given offset (in world space).
+    ///
+    /// `smoothing` is a time constant in seconds: `0` snaps the entity to its target every frame,
+    /// greater values make it catch up more slowly. The follower should be a root entity.  
+    /// The constraint is removed automatically if the target is deleted.
+    pub fn set_follow(
+        &mut self,
+        entity_id: u32,
+        target_id: u32,
+        offset: Vector3Data,
+        smoothing: f32,
+    ) -> Result<(), JsValue> {
+        let (mut follows, entities): (WriteStorage<Follow>, Entities) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        let target = entities.entity(target_id);
+        if !entities.is_alive(target) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity to follow does not exist.",
+                &target_id.to_string(),
+            )
+            .into());
+        }
+        let follow_rotation = follows
+            .get(entity)
+            .map(|follow| follow.follow_rotation)
+            .unwrap_or(false);
+        let mut follow = Follow::new(target, offset.to_vector3(), smoothing);
+        follow.follow_rotation = follow_rotation;
+        follows.insert(entity, follow).map_err(|_| {
+            W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "Could not add the Follow component.",
+                &entity_id.to_string(),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Sets whether a following entity also copies its target's rotation. The offset is then
+    /// expressed in the target's space.
+    pub fn set_follow_rotation(&mut self, entity_id: u32, follow_rotation: bool) {
+        let (mut follows, entities): (WriteStorage<Follow>, Entities) = self.world.system_data();
+        match follows.get_mut(entities.entity(entity_id)) {
+            Some(follow) => follow.follow_rotation = follow_rotation,
+            None => log_error!("Could not find Follow for entity."),
+        }
+    }
+
+    /// Removes the `Follow` constraint from an entity. Its transform is left where it was.
+    pub fn clear_follow(&mut self, entity_id: u32) {
+        let (mut follows, entities): (WriteStorage<Follow>, Entities) = self.world.system_data();
+        follows.remove(entities.entity(entity_id));
+    }
+
+    /// Makes an entity move by itself at `linear` units per second and rotate at `angular`
+    /// (an axis scaled by the angle in radians per second), in its parent's space. Damping
+    /// and speed limit set before are kept.  
+    /// The velocity is applied every update after the transform changes made from JS, which
+    /// it moves from, and before constraints, which override it.  
+    /// Fails if the entity has no `Transform`.
+    pub fn set_velocity(
+        &mut self,
+        entity_id: u32,
+        linear: Vector3Data,
+        angular: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let (transforms, mut velocities, entities): (
+            ReadStorage<Transform>,
+            WriteStorage<Velocity>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) || !transforms.contains(entity) {
+            return Err(missing_component_error("Transform", entity_id).into());
+        }
+        match velocities.get_mut(entity) {
+            Some(velocity) => {
+                velocity.linear = linear.to_vector3();
+                velocity.angular = angular.to_vector3();
+            }
+            None => {
+                velocities
+                    .insert(
+                        entity,
+                        Velocity::new(linear.to_vector3(), angular.to_vector3()),
+                    )
+                    .ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the rates at which an entity's linear and angular velocities decay, per second:
+    /// they are multiplied by `exp(-damping * delta)` every update.  
+    /// Fails if the entity has no velocity, or if a rate is negative.
+    pub fn set_velocity_damping(
+        &mut self,
+        entity_id: u32,
+        linear_damping: f32,
+        angular_damping: f32,
+    ) -> Result<(), JsValue> {
+        if !(linear_damping >= 0. && angular_damping >= 0.) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Damping rates can't be negative.",
+                &format!("{}, {}", linear_damping, angular_damping),
+            )
+            .into());
+        }
+        self.with_velocity(entity_id, |velocity| {
+            velocity.linear_damping = linear_damping;
+            velocity.angular_damping = angular_damping;
+        })
+    }
+
+    /// Limits the linear speed of an entity, in units per second, or removes the limit if
+    /// `None`.  
+    /// Fails if the entity has no velocity.
+    pub fn set_max_speed(&mut self, entity_id: u32, max_speed: Option<f32>) -> Result<(), JsValue> {
+        self.with_velocity(entity_id, |velocity| velocity.max_speed = max_speed)
+    }
+
+    /// Stops an entity moving by itself.
+    pub fn clear_velocity(&mut self, entity_id: u32) -> () {
+        let (mut velocities, entities): (WriteStorage<Velocity>, Entities) =
+            self.world.system_data();
+        velocities.remove(entities.entity(entity_id));
+    }
+
+    /// Makes an entity move along a Catmull-Rom curve through `points`, given as x, y, z
+    /// triplets in its parent's space, at `speed` units per second. Looped paths are closed,
+    /// going around from the last point to the first one, and repeat; other paths stop at
+    /// their last point. If `orient` is `true`, the entity's +Z axis is turned along the
+    /// curve.  
+    /// The path is followed every update after the velocity, which it overrides, and before
+    /// constraints. Replaces any path the entity was following.  
+    /// Fails if the entity has no `Transform`, if there are fewer than 2 points (3 if
+    /// looped), or if the speed is negative.
+    pub fn set_path(
+        &mut self,
+        entity_id: u32,
+        points: &[f32],
+        speed: f32,
+        looped: bool,
+        orient: bool,
+    ) -> Result<(), JsValue> {
+        self.set_spline_path(
+            entity_id,
+            SplineKind::CatmullRom,
+            points,
+            speed,
+            looped,
+            orient,
+        )
+    }
+
+    /// Makes an entity move along cubic Bézier segments, as `set_path`. `points` are an
+    /// anchor followed by two handles and the next anchor for each segment; looped paths end
+    /// at the first anchor, which is not repeated.  
+    /// Fails if the entity has no `Transform`, if the points don't make whole segments, or if
+    /// the speed is negative.
+    pub fn set_bezier_path(
+        &mut self,
+        entity_id: u32,
+        points: &[f32],
+        speed: f32,
+        looped: bool,
+        orient: bool,
+    ) -> Result<(), JsValue> {
+        self.set_spline_path(entity_id, SplineKind::Bezier, points, speed, looped, orient)
+    }
+
+    /// Sets what an entity does at the end of its path.  
+    /// Fails if the entity follows no path.
+    pub fn set_path_loop_mode(
+        &mut self,
+        entity_id: u32,
+        loop_mode: PathLoop,
+    ) -> Result<(), JsValue> {
+        self.with_path_follower(entity_id, |follower| follower.loop_mode = loop_mode)
+    }
+
+    /// Sets the speed of an entity along its path so that it is travelled in `duration`
+    /// seconds.  
+    /// Fails if the entity follows no path, or if the duration is not positive.
+    pub fn set_path_duration(&mut self, entity_id: u32, duration: f32) -> Result<(), JsValue> {
+        if !(duration > 0.) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The path duration must be positive.",
+                &duration.to_string(),
+            )
+            .into());
+        }
+        self.with_path_follower(entity_id, |follower| follower.set_duration(duration))
+    }
+
+    /// Moves an entity to `progress` along its path, between `0` at the start and `1` at the
+    /// end, heading towards the end. It is placed there at the next update.  
+    /// Fails if the entity follows no path.
+    pub fn set_path_progress(&mut self, entity_id: u32, progress: f32) -> Result<(), JsValue> {
+        self.with_path_follower(entity_id, |follower| follower.set_progress(progress))
+    }
+
+    /// Returns how far along its path an entity is, between `0` at the start and `1` at the
+    /// end.  
+    /// Fails if the entity follows no path.
+    pub fn get_path_progress(&self, entity_id: u32) -> Result<f32, JsValue> {
+        let (followers, entities): (ReadStorage<PathFollower>, Entities) = self.world.system_data();
+        followers
+            .get(entities.entity(entity_id))
+            .map(|follower| follower.get_progress())
+            .ok_or_else(|| missing_component_error("PathFollower", entity_id).into())
+    }
+
+    /// Stops an entity following its path. It stays where it was.
+    pub fn clear_path(&mut self, entity_id: u32) -> () {
+        let (mut followers, entities): (WriteStorage<PathFollower>, Entities) =
+            self.world.system_data();
+        followers.remove(entities.entity(entity_id));
+    }
+
+    /// Shakes a camera for `duration` seconds, with a positional amplitude in world units that
+    /// fades out over the duration. The shake is an offset on top of the camera's view, which
+    /// is left untouched. Replaces any shake already running on the camera.  
+    /// Fails if the entity has no `Camera`.
+    pub fn shake_camera(
+        &mut self,
+        entity_id: u32,
+        amplitude: f32,
+        duration: f32,
+    ) -> Result<(), JsValue> {
+        let (mut shakes, cameras, entities): (
+            WriteStorage<CameraShake>,
+            ReadStorage<Camera>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !cameras.contains(entity) {
+            return Err(missing_component_error("Camera", entity_id).into());
+        }
+        let mut shake = CameraShake::new(amplitude, DEFAULT_SHAKE_FREQUENCY, 0.0, entity_id);
+        shake.duration = Some(duration);
+        shakes.insert(entity, shake).map_err(|_| {
+            W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "Could not add the CameraShake component.",
+                &entity_id.to_string(),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Stops shaking a camera immediately, bringing it back to its view.
+    pub fn stop_camera_shake(&mut self, entity_id: u32) -> () {
+        let (mut shakes, mut cameras, entities): (
+            WriteStorage<CameraShake>,
+            WriteStorage<Camera>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        shakes.remove(entity);
+        if let Some(camera) = cameras.get_mut(entity) {
+            camera.set_shake_offset(&Isometry3::identity());
+        }
+    }
+
+    /// Makes an entity rotate each frame so that its local +Z axis points towards a target.
+    /// The constraint is removed automatically if the target is deleted.  
+    /// When the target is straight along `up`, another world axis is used as up; the entity
+    /// keeps its rotation while it's at the target's position.
+    pub fn set_look_at(
+        &mut self,
+        entity_id: u32,
+        target_id: u32,
+        up: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let (mut look_ats, entities): (WriteStorage<LookAtTarget>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        let target = entities.entity(target_id);
+        if !entities.is_alive(target) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity to look at does not exist.",
+                &target_id.to_string(),
+            )
+            .into());
+        }
+        let look_at = LookAtTarget {
+            target: target,
+            up: up.to_vector3(),
+        };
+        look_ats.insert(entity, look_at).map_err(|_| {
+            W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "Could not add the LookAtTarget component.",
+                &entity_id.to_string(),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Removes the `LookAtTarget` constraint from an entity.
+    pub fn clear_look_at(&mut self, entity_id: u32) {
+        let (mut look_ats, entities): (WriteStorage<LookAtTarget>, Entities) =
+            self.world.system_data();
+        look_ats.remove(entities.entity(entity_id));
+    }
+
+    /// Overrides the bounds computed from an entity's mesh data with a sphere, in local space.
+    /// Use it for meshes deformed at render time, whose static bounds are wrong.
+    pub fn set_entity_bounds_sphere(
+        &mut self,
+        entity_id: u32,
+        center: Vector3Data,
+        radius: f32,
+    ) -> Result<(), JsValue> {
+        let bounds = Bounds::from_sphere(BoundingSphere::new(center.to_vector3(), radius));
+        self.set_entity_bounds(entity_id, bounds)
+    }
+
+    /// Overrides the bounds computed from an entity's mesh data with a box, in local space.
+    pub fn set_entity_bounds_box(
+        &mut self,
+        entity_id: u32,
+        min: Vector3Data,
+        max: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let bounds = Bounds::from_aabb(&Aabb::new(min.to_vector3(), max.to_vector3()));
+        self.set_entity_bounds(entity_id, bounds)
+    }
+
+    /// Removes the bounds set with `set_entity_bounds_sphere` or `set_entity_bounds_box`, using
+    /// the bounds computed from the entity's mesh data again.
+    pub fn clear_entity_bounds(&mut self, entity_id: u32) {
+        let (mut bounds, entities): (WriteStorage<Bounds>, Entities) = self.world.system_data();
+        bounds.remove(entities.entity(entity_id));
+    }
+
+    /// Sets whether an entity is rendered even when outside the camera's view (skyboxes,
+    /// full-screen effects...).
+    pub fn set_always_visible(
+        &mut self,
+        entity_id: u32,
+        always_visible: bool,
+    ) -> Result<(), JsValue> {
+        let (mut always_visibles, entities): (WriteStorage<AlwaysVisible>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity does not exist.",
+                &entity_id.to_string(),
+            )
+            .into());
+        }
+        if always_visible {
+            always_visibles.insert(entity, AlwaysVisible).ok();
+        } else {
+            always_visibles.remove(entity);
+        }
+        Ok(())
+    }
+
+    /// Selects or deselects an entity. The meshes of selected entities are drawn with an
+    /// outline, set with `set_outline_style`.
+    pub fn set_selected(&mut self, entity_id: u32, selected: bool) -> Result<(), JsValue> {
+        let (mut selecteds, entities): (WriteStorage<Selected>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity does not exist.",
+                &entity_id.to_string(),
+            )
+            .into());
+        }
+        if selected {
+            selecteds.insert(entity, Selected).ok();
+        } else {
+            selecteds.remove(entity);
+        }
+        Ok(())
+    }
+
+    /// Sets whether an entity can be picked and snapped onto. Helpers are unpickable when
+    /// created.
+    pub fn set_pickable(&mut self, entity_id: u32, pickable: bool) -> Result<(), JsValue> {
+        let (mut unpickables, entities): (WriteStorage<Unpickable>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity does not exist.",
+                &entity_id.to_string(),
+            )
+            .into());
+        }
+        if pickable {
+            unpickables.remove(entity);
+        } else {
+            unpickables.insert(entity, Unpickable).ok();
+        }
+        Ok(())
+    }
+
+    /// Sets how outlines around selected entities look: a `#rrggbb` color and a thickness
+    /// in world units. With `use_stencil`, outlines are masked with the stencil buffer,
+    /// which handles concave meshes better but needs the context to have one (created with
+    /// `stencil: true`).  
+    /// Fails if the scene is not initialized, if the color is invalid or if the thickness
+    /// is negative.
+    pub fn set_outline_style(
+        &mut self,
+        color_hex: &str,
+        thickness: f32,
+        use_stencil: bool,
+    ) -> Result<(), JsValue> {
+        let renderer = match &self.main_renderer {
+            Some(renderer) => renderer,
+            None => {
+                return Err(W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "The outline style can't be set before initializing the scene.",
+                )
+                .into())
+            }
+        };
+        if thickness < 0. {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The outline thickness can't be negative.",
+                &thickness.to_string(),
+            )
+            .into());
+        }
+        renderer.borrow_mut().set_outline_style(OutlineStyle {
+            color: parse_hex_color(color_hex)?,
+            thickness: thickness,
+            use_stencil: use_stencil,
+        });
+        Ok(())
+    }
+
+    /// Draws a world space line with a `#rrggbb` color over the next frame, through the
+    /// meshes. Lines are only drawn once: call this every frame to keep them on screen.  
+    /// Fails if the scene is not initialized or if the color is invalid.
+    pub fn debug_draw_line(
+        &mut self,
+        from: Vector3Data,
+        to: Vector3Data,
+        color_hex: &str,
+    ) -> Result<(), JsValue> {
+        let color = parse_hex_color(color_hex)?;
+        self.get_renderer("Debug lines")?
+            .borrow_mut()
+            .push_debug_line(
+                &Vector3::new(from.x, from.y, from.z),
+                &Vector3::new(to.x, to.y, to.z),
+                &color,
+            );
+        Ok(())
+    }
+
+    /// Draws the twelve edges of a camera's frustum over the next frame, in yellow, as used
+    /// by the culling tests when it's the active camera. See `debug_draw_line`.  
+    /// Fails if the scene is not initialized, if the entity has no `Camera`, or if its
+    /// view-projection matrix can't be inverted.
+    pub fn debug_draw_frustum(&mut self, camera_entity_id: u32) -> Result<(), JsValue> {
+        let renderer = self.get_renderer("Debug lines")?;
+        let (cameras, entities): (ReadStorage<Camera>, Entities) = self.world.system_data();
+        let camera = cameras
+            .get(entities.entity(camera_entity_id))
+            .ok_or_else(|| missing_component_error("Camera", camera_entity_id))?;
+        let corners = Frustum::get_corners(&camera.get_vp_matrix()).ok_or_else(|| {
+            W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The view-projection matrix of the camera can't be inverted.",
+                &camera_entity_id.to_string(),
+            )
+        })?;
+        renderer
+            .borrow_mut()
+            .push_debug_box(&corners, &Vector3::new(1., 1., 0.));
+        Ok(())
+    }
+
+    /// Draws the world space box around the culling bounds of every active mesh entity over
+    /// the next frame: green if it passed the culling tests last frame, red otherwise.
+    /// Entities whose bounds are unknown are skipped. See `debug_draw_line`.  
+    /// Fails if the scene is not initialized.
+    pub fn debug_draw_all_bounds(&mut self) -> Result<(), JsValue> {
+        let mut renderer = self.get_renderer("Debug lines")?.borrow_mut();
+        let (entities, meshes, transforms, enableds, effectively_disabled, bounds, visibility): (
+            Entities,
+            ReadStorage<Mesh>,
+            ReadStorage<Transform>,
+            ReadStorage<Enabled>,
+            ReadStorage<EffectivelyDisabled>,
+            ReadStorage<Bounds>,
+            Read<Visibility>,
+        ) = self.world.system_data();
+        let (visible_color, culled_color) = (Vector3::new(0., 1., 0.), Vector3::new(1., 0., 0.));
+        let mut boxes = Vec::new();
+        let active = (&enableds, !&effectively_disabled);
+        for (entity, mesh, transform, _) in (&entities, &meshes, &transforms, active).join() {
+            let local_bounds =
+                get_local_bounds(bounds.get(entity), mesh, renderer.get_asset_registry());
+            if let Some(sphere) = local_bounds {
+                let world_bounds =
+                    Aabb::from_sphere(&sphere.transformed(&transform.get_world_matrix()));
+                let color = if visibility.is_visible(entity.id()) {
+                    &visible_color
+                } else {
+                    &culled_color
+                };
+                boxes.push((world_bounds.get_corners(), color));
+            }
+        }
+        for (corners, color) in &boxes {
+            renderer.push_debug_box(corners, color);
+        }
+        Ok(())
+    }
+
+    /// Sets the levels of detail of a mesh entity: `mesh_ids[i]` is used up to `distances[i]`
+    /// from the camera, and the farthest level beyond that. Every mesh must be compatible
+    /// with the entity's material.  
+    /// Fails if the entity has no `Mesh`, if a mesh isn't registered or if the lengths differ.
+    pub fn set_lods(
+        &mut self,
+        entity_id: u32,
+        mesh_ids: Vec<String>,
+        distances: Vec<f32>,
+    ) -> Result<(), JsValue> {
+        if mesh_ids.len() != distances.len() || mesh_ids.is_empty() {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Expected as many distances as meshes, and at least one level.",
+                &format!("{} meshes, {} distances", mesh_ids.len(), distances.len()),
+            )
+            .into());
+        }
+        let mut mesh_data_indexes = Vec::with_capacity(mesh_ids.len());
+        match &self.main_renderer {
+            Some(renderer) => {
+                let renderer = renderer.borrow();
+                let asset_registry = renderer.get_asset_registry();
+                for mesh_id in &mesh_ids {
+                    match asset_registry.get_mesh_data(mesh_id) {
+                        Some(_) => {
+                            mesh_data_indexes.push(asset_registry.get_id_from_str(mesh_id).unwrap())
+                        }
+                        None => {
+                            return Err(W3DError::with_source(
+                                W3DErrorKind::MissingAsset,
+                                "Mesh data could not be found. Has it been registered yet?",
+                                mesh_id,
+                            )
+                            .into())
+                        }
+                    }
+                }
+            }
+            None => {
+                return Err(W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Levels of detail can't be set before initializing the scene.",
+                )
+                .into())
+            }
+        }
+        let (meshes, mut lods, entities): (ReadStorage<Mesh>, WriteStorage<Lod>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !meshes.contains(entity) {
+            return Err(missing_component_error("Mesh", entity_id).into());
+        }
+        let cull_distance = lods.get(entity).and_then(|lod| lod.cull_distance);
+        let levels = mesh_data_indexes.into_iter().zip(distances).collect();
+        lods.insert(entity, Lod::new(levels, cull_distance))
+            .map_err(|_| missing_component_error("Mesh", entity_id))?;
+        Ok(())
+    }
+
+    /// Sets the distance from the camera beyond which an entity with levels of detail is not
+    /// rendered. `None` always renders it.
+    pub fn set_lod_cull_distance(
+        &mut self,
+        entity_id: u32,
+        cull_distance: Option<f32>,
+    ) -> Result<(), JsValue> {
+        let (mut lods, entities): (WriteStorage<Lod>, Entities) = self.world.system_data();
+        match lods.get_mut(entities.entity(entity_id)) {
+            Some(lod) => {
+                lod.cull_distance = cull_distance;
+                Ok(())
+            }
+            None => Err(missing_component_error("Lod", entity_id).into()),
+        }
+    }
+
+    /// Creates an entity displaying a texture on a camera-facing quad of `width` by `height`
+    /// world units. Returns its Entity ID.  
+    /// Fails if the scene is not initialized or if the texture is not registered.
+    pub fn create_sprite_entity(
+        &mut self,
+        texture_id: &str,
+        width: f32,
+        height: f32,
+    ) -> Result<u32, JsValue> {
+        let texture_index = self.get_texture_index(texture_id, "Sprites")?;
+        let entity = Scene::build_entity(&mut self.world)
+            .with(Sprite::new(texture_index, width, height))
+            .with(Transform::new(
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(1., 1., 1.),
+            ))
+            .with(DirtyTransform)
+            .with(Enabled)
+            .build();
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Sets the region of its texture a sprite displays. `(0, 0)` is the top-left corner of
+    /// the image and `(1, 1)` its bottom-right corner.
+    pub fn set_sprite_region(
+        &mut self,
+        entity_id: u32,
+        u0: f32,
+        v0: f32,
+        u1: f32,
+        v1: f32,
+    ) -> Result<(), JsValue> {
+        let (mut sprites, entities): (WriteStorage<Sprite>, Entities) = self.world.system_data();
+        match sprites.get_mut(entities.entity(entity_id)) {
+            Some(sprite) => {
+                sprite.region = AtlasRegion::new(u0, v0, u1, v1);
+                Ok(())
+            }
+            None => Err(missing_component_error("Sprite", entity_id).into()),
+        }
+    }
+
+    /// Sets the size of a sprite's quad, in world units.
+    pub fn set_sprite_size(
+        &mut self,
+        entity_id: u32,
+        width: f32,
+        height: f32,
+    ) -> Result<(), JsValue> {
+        let (mut sprites, entities): (WriteStorage<Sprite>, Entities) = self.world.system_data();
+        match sprites.get_mut(entities.entity(entity_id)) {
+            Some(sprite) => {
+                sprite.width = width;
+                sprite.height = height;
+                Ok(())
+            }
+            None => Err(missing_component_error("Sprite", entity_id).into()),
+        }
+    }
+
+    /// Adds a blob shadow under an entity, or updates its radius and opacity if it already has
+    /// one: a soft dark disc of `radius` world units on the ground, whose center has the given
+    /// `opacity` when the entity touches the ground. The ground is at height `0` by default.  
+    /// Fails if the entity has no `Transform`.
+    pub fn set_blob_shadow(
+        &mut self,
+        entity_id: u32,
+        radius: f32,
+        opacity: f32,
+    ) -> Result<(), JsValue> {
+        let (transforms, mut shadows, entities): (
+            ReadStorage<Transform>,
+            WriteStorage<BlobShadow>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !transforms.contains(entity) {
+            return Err(missing_component_error("Transform", entity_id).into());
+        }
+        match shadows.get_mut(entity) {
+            Some(shadow) => {
+                shadow.radius = radius;
+                shadow.opacity = opacity;
+            }
+            None => {
+                shadows
+                    .insert(entity, BlobShadow::new(radius, opacity))
+                    .map_err(|_| missing_component_error("Transform", entity_id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the ground a blob shadow lies on: the horizontal plane at the world height of
+    /// `ground_entity_id` if given, at `ground_height` otherwise. The shadow fades out
+    /// completely when its entity is `fade_height` above the ground.
+    pub fn set_blob_shadow_ground(
+        &mut self,
+        entity_id: u32,
+        ground_height: f32,
+        ground_entity_id: Option<u32>,
+        fade_height: f32,
+    ) -> Result<(), JsValue> {
+        let (mut shadows, entities): (WriteStorage<BlobShadow>, Entities) =
+            self.world.system_data();
+        let ground_entity = ground_entity_id.map(|id| entities.entity(id));
+        match shadows.get_mut(entities.entity(entity_id)) {
+            Some(shadow) => {
+                shadow.ground_height = ground_height;
+                shadow.ground_entity = ground_entity;
+                shadow.fade_height = fade_height;
+                Ok(())
+            }
+            None => Err(missing_component_error("BlobShadow", entity_id).into()),
+        }
+    }
+
+    /// Removes the blob shadow of an entity, if it has one.
+    pub fn remove_blob_shadow(&mut self, entity_id: u32) -> () {
+        let (mut shadows, entities): (WriteStorage<BlobShadow>, Entities) =
+            self.world.system_data();
+        shadows.remove(entities.entity(entity_id));
+    }
+
+    /// Creates a decal entity: a square of `size` world units displaying a texture, drawn
+    /// with a polygon offset after the opaque meshes so that it doesn't z-fight with the
+    /// surface it lies on. Returns its Entity ID.  
+    /// The quad lies in the entity's local XY plane and faces +Z; place and orient it with
+    /// the entity's transform. Its size is the transform's scale.  
+    /// Fails if the scene is not initialized or if the texture is not registered.
+    pub fn create_decal_entity(&mut self, texture_id: &str, size: f32) -> Result<u32, JsValue> {
+        let texture_index = self.get_texture_index(texture_id, "Decals")?;
+        let (mesh_data_index, material_instance_index, material_index) = self
+            .main_renderer
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .get_decal_assets(texture_index)?;
+        let entity = Scene::build_entity(&mut self.world)
+            .with(Mesh::new(
+                mesh_data_index,
+                material_instance_index,
+                material_index,
+            ))
+            .with(Transform::new(
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(size, size, size),
+            ))
+            .with(DirtyTransform)
+            .with(Enabled)
+            .build();
+        Scene::count_asset_users(&self.world, entity);
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Creates a ground grid helper: a square of `size` world units in the XZ plane, cut in
+    /// `divisions` cells per side, drawn as unlit lines. Every tenth line from the center has
+    /// the `color_major_hex` color, the others the `color_minor_hex` one. Returns its Entity
+    /// ID.  
+    /// The grid is alpha blended so that it can fade with distance, see `set_grid_fade`. Like
+    /// every helper, it is unpickable and casts no blob shadow.  
+    /// Fails if the scene is not initialized, if a color is invalid, or if `divisions` is `0`
+    /// or above 16000.
+    pub fn create_grid_helper(
+        &mut self,
+        size: f32,
+        divisions: u32,
+        color_major_hex: &str,
+        color_minor_hex: &str,
+    ) -> Result<u32, JsValue> {
+        let major = parse_hex_color(color_major_hex)?;
+        let minor = parse_hex_color(color_minor_hex)?;
+        let assets = self
+            .get_renderer("Grid helpers")?
+            .borrow_mut()
+            .get_grid_assets(size, divisions, &major, &minor)?;
+        Ok(self.create_helper_entity(assets))
+    }
+
+    /// Creates a reference axes helper: lines of `size` world units from the origin along
+    /// X, Y and Z, colored red, green and blue and drawn unlit. Returns its Entity ID.  
+    /// Like every helper, it is unpickable and casts no blob shadow.  
+    /// Fails if the scene is not initialized.
+    pub fn create_axes_helper(&mut self, size: f32) -> Result<u32, JsValue> {
+        let assets = self
+            .get_renderer("Axes helpers")?
+            .borrow_mut()
+            .get_axes_assets(size)?;
+        Ok(self.create_helper_entity(assets))
+    }
+
+    /// Makes a grid helper fade out between half `distance` and `distance` world units from
+    /// the camera; `0` disables the fade.  
+    /// Fails if the entity has no `Mesh`.
+    pub fn set_grid_fade(&mut self, entity_id: u32, distance: f32) -> Result<(), JsValue> {
+        self.set_entity_uniform(
+            entity_id,
+            FADE_DISTANCE_NAME,
+            UniformOverrideValue::Float(distance.max(0.)),
+        )
+    }
+
+    /// Scrolls the texture of every decal displaying `texture_id`, by `u_speed` and `v_speed`
+    /// texture coordinates per second; `0, 0` stops it. Driven by the `u_time_wrapped`
+    /// uniform, so the texture's wrap mode must be `REPEAT`, and speeds should be multiples
+    /// of 1/3600 to avoid a jump when the time wraps, every hour.  
+    /// Fails if the scene is not initialized or if the texture is not registered.
+    pub fn set_decal_uv_scroll(
+        &mut self,
+        texture_id: &str,
+        u_speed: f32,
+        v_speed: f32,
+    ) -> Result<(), JsValue> {
+        let texture_index = self.get_texture_index(texture_id, "Decals")?;
+        let mut renderer = self.main_renderer.as_ref().unwrap().borrow_mut();
+        let (_, material_instance_index, _) = renderer.get_decal_assets(texture_index)?;
+        let material_instance = renderer
+            .get_asset_registry()
+            .get_material_instance_with_index(material_instance_index)
+            .unwrap();
+        material_instance.borrow_mut().set_uniform(Uniform::new(
+            UV_SCROLL_NAME,
+            Box::new(Vector2::new(u_speed, v_speed)),
+        ));
+        Ok(())
+    }
+
+    /// Makes a material semi-transparent or opaque. Transparent materials are drawn after the
+    /// opaque ones, alpha blended and without writing depth; the change applies on the next
+    /// frame.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn set_material_transparent(
+        &mut self,
+        material_id: &str,
+        transparent: bool,
+    ) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| {
+            material.set_transparent(transparent)
+        })
+    }
+
+    /// Sets how a semi-transparent material is blended. `Sorted` materials are alpha blended
+    /// after the opaque ones, which goes wrong where transparent meshes intersect. `Hashed`
+    /// materials are drawn with the opaque ones and write depth, discarding fragments whose
+    /// alpha is below a screen space noise: order independent and stable from any angle, at
+    /// the cost of a dithered look.  
+    /// The material's fragment shader must handle the `ALPHA_HASH` define, like the default
+    /// shaders do. Only semi-transparent materials are affected.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn set_material_transparency_mode(
+        &mut self,
+        material_id: &str,
+        transparency_mode: TransparencyMode,
+    ) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| {
+            material.set_transparency_mode(transparency_mode)
+        })
+    }
+
+    /// Sets the polygon offset `(factor, units)` used to draw with a material, turning it
+    /// into a decal material drawn after every other one. Negative values pull the geometry
+    /// towards the camera.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn set_material_polygon_offset(
+        &mut self,
+        material_id: &str,
+        factor: f32,
+        units: f32,
+    ) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| {
+            material.set_polygon_offset(Some((factor, units)))
+        })
+    }
+
+    /// Removes the polygon offset of a material, which is then no longer drawn as a decal.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn clear_material_polygon_offset(&mut self, material_id: &str) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| material.set_polygon_offset(None))
+    }
+
+    /// Adds a pass to a material: meshes using it are drawn again with `pass_material_id`,
+    /// right after it and after its previous passes, as needed by effects such as toon
+    /// outlines or fur shells.  
+    /// Each pass keeps its own program, transparency, polygon offset, culling and shared
+    /// uniforms; the meshes' vertex buffers and transforms are shared, but the uniforms of
+    /// their material instances and their overrides are not. Passes of a pass are ignored.  
+    /// Fails if the scene is not initialized, if a material is not registered, or if both ids
+    /// are the same.
+    pub fn add_material_pass(
+        &mut self,
+        material_id: &str,
+        pass_material_id: &str,
+    ) -> Result<(), JsValue> {
+        let pass_index = {
+            let renderer = self.get_renderer("Material passes")?.borrow();
+            let asset_registry = renderer.get_asset_registry();
+            match asset_registry.get_material(pass_material_id) {
+                Some(_) => asset_registry.get_id_from_str(pass_material_id).unwrap(),
+                None => {
+                    return Err(W3DError::with_source(
+                        W3DErrorKind::MissingAsset,
+                        "Material could not be found. Has it been registered yet?",
+                        pass_material_id,
+                    )
+                    .into())
+                }
+            }
+        };
+        if material_id == pass_material_id {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "A material can't be its own pass.",
+                material_id,
+            )
+            .into());
+        }
+        self.with_material(material_id, |material| material.add_pass(pass_index))
+    }
+
+    /// Removes every pass of a material.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn clear_material_passes(&mut self, material_id: &str) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| material.clear_passes())
+    }
+
+    /// Makes a material cull front faces instead of back faces, which inverted hulls such as
+    /// toon outline passes need.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn set_material_cull_front_faces(
+        &mut self,
+        material_id: &str,
+        cull_front_faces: bool,
+    ) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| {
+            material.set_cull_front_faces(cull_front_faces)
+        })
+    }
+
+    /// Sets which winding makes the front faces of the triangles drawn with a material, e.g.
+    /// `Clockwise` for meshes exported with the opposite convention, when flipping them at
+    /// import with `ImportOptions.fix_winding` isn't desirable.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn set_material_front_face(
+        &mut self,
+        material_id: &str,
+        front_face: FrontFace,
+    ) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| material.set_front_face(front_face))
+    }
+
+    /// Returns debugging information about a material: its original shader sources, the
+    /// info logs of its last compilation (which may hold warnings even on success), the
+    /// sources translated by the driver when `WEBGL_debug_shaders` is available, and its
+    /// active attributes and uniforms.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn get_material_debug_info(&self, material_id: &str) -> Result<JsValue, JsValue> {
+        match &self.main_renderer {
+            Some(renderer) => {
+                let renderer = renderer.borrow();
+                match renderer.get_asset_registry().get_material(material_id) {
+                    Some(material) => Ok(get_material_debug_info(
+                        renderer.get_webgl_context(),
+                        &material.borrow(),
+                    )),
+                    None => Err(W3DError::with_source(
+                        W3DErrorKind::MissingAsset,
+                        "Material could not be found. Has it been registered yet?",
+                        material_id,
+                    )
+                    .into()),
+                }
+            }
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Materials can't be inspected before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Sets the alpha value under which a material's fragments are discarded, or `None` to
+    /// disable alpha testing. Cutout materials are drawn in the opaque pass and write depth,
+    /// which suits foliage or fences better than transparency.  
+    /// The material's fragment shader must handle the `ALPHA_CUTOFF` define and the
+    /// `u_alpha_cutoff` uniform, like the default shaders do.  
+    /// Fails if the scene is not initialized or if the material is not registered.
+    pub fn set_material_alpha_cutoff(
+        &mut self,
+        material_id: &str,
+        alpha_cutoff: Option<f32>,
+    ) -> Result<(), JsValue> {
+        self.with_material(material_id, |material| {
+            material.set_alpha_cutoff(alpha_cutoff)
+        })
+    }
+
+    /// Sets the cube texture reflected by lit materials supporting environment mapping, or
+    /// removes it with `None`. Shaders using it are recompiled with the `ENVIRONMENT_MAP`
+    /// define, and the others don't pay for the sampling.  
+    /// The amount of reflection is set per material instance by `set_instance_reflectivity`.  
+    /// Fails if the scene is not initialized or if the cube texture is not registered.
+    pub fn set_environment_map(&mut self, cube_texture_id: Option<String>) -> Result<(), JsValue> {
+        let environment_map = match &cube_texture_id {
+            Some(id) => Some(self.get_cube_texture(id)?),
+            None => None,
+        };
+        if let Some(renderer) = &self.main_renderer {
+            renderer.borrow_mut().set_environment_map(environment_map);
+        }
+        self.world
+            .write_resource::<LightConfiguration>()
+            .environment_map = cube_texture_id.is_some();
+        Ok(())
+    }
+
+    /// Sets the exposure of a camera, the factor applied to colors by the `tone_map` function
+    /// of fragment shaders when rendering from it. Takes effect without recompiling shaders.  
+    /// Fails if the entity has no `Camera`.
+    pub fn set_camera_exposure(&mut self, entity_id: u32, exposure: f32) -> Result<(), JsValue> {
+        let (mut cameras, entities): (WriteStorage<Camera>, Entities) = self.world.system_data();
+        let camera = cameras
+            .get_mut(entities.entity(entity_id))
+            .ok_or_else(|| missing_component_error("Camera", entity_id))?;
+        camera.set_exposure(exposure);
+        Ok(())
+    }
+
+    /// Sets the exposure of a camera from an exposure value at ISO 100, as photographers
+    /// use: `1 / (1.2 · 2^ev100)`. Suited to lights in physical units, see `set_light_units`:
+    /// about 15 for a sunny day, 7 indoors.  
+    /// Fails if the entity has no `Camera`.
+    pub fn set_camera_ev100(&mut self, entity_id: u32, ev100: f32) -> Result<(), JsValue> {
+        self.set_camera_exposure(entity_id, ev100_to_exposure(ev100))
+    }
+
+    /// Sets the unit of the intensity of lights, from the next update. In `Physical` units,
+    /// directional lights are given in lux and point and spot lights in lumens, converted to
+    /// candela using their cone; ambient and hemisphere lights are unchanged. The resulting
+    /// intensities are much larger than arbitrary ones: set the cameras' exposure with
+    /// `set_camera_ev100` so that materials calling `tone_map` are exposed consistently.
+    pub fn set_light_units(&mut self, units: LightUnits) -> () {
+        self.world.write_resource::<LightRepository>().units = units;
+    }
+
+    /// Locks the aspect ratio of a camera to `aspect_ratio`, e.g. for a camera rendering to
+    /// a texture, or unlocks it with `None` so that it follows the viewport's again. Unlocked
+    /// cameras are updated whenever the viewport is resized.  
+    /// Fails if the entity has no `Camera`.
+    pub fn lock_camera_aspect_ratio(
+        &mut self,
+        entity_id: u32,
+        aspect_ratio: Option<f32>,
+    ) -> Result<(), JsValue> {
+        let viewport_aspect_ratio = self.world.read_resource::<ViewportInfo>().aspect_ratio;
+        let (mut cameras, entities): (WriteStorage<Camera>, Entities) = self.world.system_data();
+        let camera = cameras
+            .get_mut(entities.entity(entity_id))
+            .ok_or_else(|| missing_component_error("Camera", entity_id))?;
+        camera.set_aspect_ratio_locked(aspect_ratio.is_some());
+        match aspect_ratio {
+            Some(aspect_ratio) => camera.set_aspect_ratio(aspect_ratio),
+            None if viewport_aspect_ratio > 0.0 => camera.set_aspect_ratio(viewport_aspect_ratio),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Sets the tone mapping operator of the active camera. Fragment shaders mentioning
+    /// `TONE_MAPPING` are recompiled with the matching define, and can call
+    /// `vec3 tone_map(vec3 color)` at their end to apply the exposure and the operator.  
+    /// Fails if the scene is not initialized.
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) -> Result<(), JsValue> {
+        let (mut cameras, active_camera): (WriteStorage<Camera>, Read<ActiveCamera>) =
+            self.world.system_data();
+        let camera = active_camera
+            .entity
+            .and_then(|entity| cameras.get_mut(entity))
+            .ok_or_else(|| {
+                W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Tone mapping can't be set before initializing the scene.",
+                )
+            })?;
+        camera.set_tone_mapping(tone_mapping);
+        Ok(())
+    }
+
+    /// Makes a material instance display only a region of its textures, e.g. a sub-image of
+    /// a texture atlas, by setting its `u_uv_transform` uniform. `(u0, v0)` and `(u1, v1)` are
+    /// the corners of the region in texture coordinates between 0 and 1; `(0, 0)` is the
+    /// top-left corner of the image for the built-in materials.  
+    /// Only has an effect on materials whose shaders apply `u_uv_transform`.  
+    /// Fails if the scene is not initialized or if the material instance is not registered.
+    pub fn set_instance_uv_rect(
+        &mut self,
+        material_instance_id: &str,
+        u0: f32,
+        v0: f32,
+        u1: f32,
+        v1: f32,
+    ) -> Result<(), JsValue> {
+        self.set_instance_atlas_region(material_instance_id, AtlasRegion::new(u0, v0, u1, v1))
+    }
+
+    /// Same as `set_instance_uv_rect`, with the region given in pixels: `(x, y)` is its
+    /// top-left corner, in a texture of `texture_width` by `texture_height` pixels.  
+    /// Fails if the scene is not initialized or if the material instance is not registered.
+    pub fn set_instance_uv_pixel_rect(
+        &mut self,
+        material_instance_id: &str,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        texture_width: f32,
+        texture_height: f32,
+    ) -> Result<(), JsValue> {
+        if texture_width <= 0.0 || texture_height <= 0.0 {
+            return Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "Texture width and height must be positive.",
+            )
+            .into());
+        }
+        let region = AtlasRegion::from_pixels(x, y, width, height, texture_width, texture_height);
+        self.set_instance_atlas_region(material_instance_id, region)
+    }
+
+    /// Makes a texture uniform of a material instance sample the texture registered under
+    /// `texture_id`. The id is resolved each time the instance is drawn, so registering
+    /// another texture under it, e.g. a reloaded one, updates every instance referencing
+    /// it. The fallback texture is drawn while the id is not registered.  
+    /// Fails if the scene is not initialized or if the material instance is not registered.
+    pub fn set_instance_texture(
+        &mut self,
+        material_instance_id: &str,
+        uniform_name: &str,
+        texture_id: &str,
+    ) -> Result<(), JsValue> {
+        self.get_renderer("Instance textures")?
+            .borrow_mut()
+            .set_instance_texture(material_instance_id, uniform_name, texture_id)?;
+        Ok(())
+    }
+
+    /// Overrides the environment map reflected by a single material instance, or goes back to
+    /// the scene's environment map with `None`.  
+    /// Only has an effect while the scene has an environment map, since the shader variant
+    /// sampling it is only used then.  
+    /// Fails if the scene is not initialized or if an asset is not registered.
+    pub fn set_instance_environment_map(
+        &mut self,
+        material_instance_id: &str,
+        cube_texture_id: Option<String>,
+    ) -> Result<(), JsValue> {
+        let environment_map = match &cube_texture_id {
+            Some(id) => Some(self.get_cube_texture(id)?),
+            None => None,
+        };
+        self.with_material_instance(
+            material_instance_id,
+            |material_instance| match environment_map {
+                Some(environment_map) => {
+                    let mut uniform = Uniform::new(ENVIRONMENT_MAP_NAME, Box::new(environment_map));
+                    uniform.set_texture_index(ENVIRONMENT_MAP_TEXTURE_INDEX);
+                    material_instance.set_uniform(uniform);
+                }
+                None => material_instance.remove_uniform(ENVIRONMENT_MAP_NAME),
+            },
+        )
+    }
+
+    /// Sets how much of the environment map a material instance reflects, from `0` (none,
+    /// the default) to `1` (a perfect mirror).  
+    /// Fails if the scene is not initialized or if the material instance is not registered.
+    pub fn set_instance_reflectivity(
+        &mut self,
+        material_instance_id: &str,
+        reflectivity: f32,
+    ) -> Result<(), JsValue> {
+        self.with_material_instance(material_instance_id, |material_instance| {
+            material_instance.set_uniform(Uniform::new(REFLECTIVITY_NAME, Box::new(reflectivity)))
+        })
+    }
+
+    /// Overrides a float uniform for a single mesh entity, without creating a material
+    /// instance. See `UniformOverrides`.  
+    /// Fails if the entity has no `Mesh`.
+    pub fn set_entity_uniform_float(
+        &mut self,
+        entity_id: u32,
+        name: &str,
+        value: f32,
+    ) -> Result<(), JsValue> {
+        self.set_entity_uniform(entity_id, name, UniformOverrideValue::Float(value))
+    }
+
+    /// Overrides a `vec2` uniform for a single mesh entity. See `set_entity_uniform_float`.
+    pub fn set_entity_uniform_vec2(
+        &mut self,
+        entity_id: u32,
+        name: &str,
+        x: f32,
+        y: f32,
+    ) -> Result<(), JsValue> {
+        let value = UniformOverrideValue::Vector2(Vector2::new(x, y));
+        self.set_entity_uniform(entity_id, name, value)
+    }
+
+    /// Overrides a `vec3` uniform for a single mesh entity. See `set_entity_uniform_float`.
+    pub fn set_entity_uniform_vec3(
+        &mut self,
+        entity_id: u32,
+        name: &str,
+        value: Vector3Data,
+    ) -> Result<(), JsValue> {
+        let value = UniformOverrideValue::Vector3(value.to_vector3());
+        self.set_entity_uniform(entity_id, name, value)
+    }
+
+    /// Overrides a `vec4` uniform for a single mesh entity. See `set_entity_uniform_float`.
+    pub fn set_entity_uniform_vec4(
+        &mut self,
+        entity_id: u32,
+        name: &str,
+        x: f32,
+        y: f32,
+        z: f32,
+        w: f32,
+    ) -> Result<(), JsValue> {
+        let value = UniformOverrideValue::Vector4(Vector4::new(x, y, z, w));
+        self.set_entity_uniform(entity_id, name, value)
+    }
+
+    /// Stops overriding a uniform for an entity, which goes back to its material instance's
+    /// value. Does nothing if the uniform isn't overridden.
+    pub fn remove_entity_uniform(&mut self, entity_id: u32, name: &str) -> () {
+        #[cfg(feature = "editor")]
+        self.record_uniform_edit(entity_id, name);
+        let (mut overrides, entities): (WriteStorage<UniformOverrides>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        let now_empty = match overrides.get_mut(entity) {
+            Some(entity_overrides) => {
+                entity_overrides.remove(name);
+                entity_overrides.is_empty()
+            }
+            None => false,
+        };
+        if now_empty {
+            overrides.remove(entity);
+        }
+    }
+
+    /// Sets the opacity of a mesh entity, clamped between `0` and `1`, stopping any fade.
+    /// Below `1`, the entity is drawn with the transparent materials, alpha blended, with
+    /// the opacity multiplied into the alpha of its materials: described materials support
+    /// it, and custom ones by declaring `uniform float u_entity_opacity`. At `1`, it goes
+    /// back to its materials' pass, without blending costs.  
+    /// Fails if the entity has no `Mesh`.
+    pub fn set_entity_opacity(&mut self, entity_id: u32, opacity: f32) -> Result<(), JsValue> {
+        let entity = self.world.entities().entity(entity_id);
+        if !self.world.read_storage::<Mesh>().contains(entity) {
+            return Err(missing_component_error("Mesh", entity_id).into());
+        }
+        let mut opacities = self.world.write_storage::<Opacity>();
+        if opacity >= 1. {
+            opacities.remove(entity);
+        } else {
+            opacities
+                .insert(entity, Opacity::new(opacity))
+                .map_err(|_| missing_component_error("Mesh", entity_id))?;
+        }
+        Ok(())
+    }
+
+    /// Fades the opacity of a mesh entity to `target` over `duration` seconds of scaled
+    /// time, from its current opacity. See `set_entity_opacity`. Fading to `1` returns the
+    /// entity to its materials' pass once done, so `fade_entity(id, 0, 1)` then
+    /// `fade_entity(id, 1, 1)` fades it out and back in.  
+    /// Fails if the entity has no `Mesh`.
+    pub fn fade_entity(
+        &mut self,
+        entity_id: u32,
+        target: f32,
+        duration: f32,
+    ) -> Result<(), JsValue> {
+        let entity = self.world.entities().entity(entity_id);
+        if !self.world.read_storage::<Mesh>().contains(entity) {
+            return Err(missing_component_error("Mesh", entity_id).into());
+        }
+        let mut opacities = self.world.write_storage::<Opacity>();
+        if !opacities.contains(entity) {
+            if target >= 1. {
+                return Ok(());
+            }
+            opacities
+                .insert(entity, Opacity::new(1.))
+                .map_err(|_| missing_component_error("Mesh", entity_id))?;
+        }
+        if let Some(opacity) = opacities.get_mut(entity) {
+            opacity.fade_to(target, duration);
+            if opacity.is_opaque() && !opacity.is_fading() {
+                opacities.remove(entity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the current opacity of an entity, `1` if it's not faded.
+    pub fn get_entity_opacity(&self, entity_id: u32) -> f32 {
+        let (opacities, entities): (ReadStorage<Opacity>, Entities) = self.world.system_data();
+        opacities
+            .get(entities.entity(entity_id))
+            .map_or(1., |opacity| opacity.value)
+    }
+
+    /// Sets a `float` uniform shared by every material, such as a global dissolve amount.
+    /// It is uploaded once per frame to each program declaring it, unless its material has a
+    /// uniform of the same name. Replaces any value previously set for `name`.  
+    /// Fails if the scene is not initialized, or if `name` is empty or is the name of a
+    /// uniform set by the engine, like `u_time` or `u_view_matrix`.
+    pub fn set_global_uniform_float(&mut self, name: &str, value: f32) -> Result<(), JsValue> {
+        self.set_global_uniform(name, GlobalUniformValue::Float(value))
+    }
+
+    /// Sets a `vec3` uniform shared by every material. See `set_global_uniform_float`.
+    pub fn set_global_uniform_vec3(
+        &mut self,
+        name: &str,
+        value: Vector3Data,
+    ) -> Result<(), JsValue> {
+        self.set_global_uniform(name, GlobalUniformValue::Vector3(value.to_vector3()))
+    }
+
+    /// Sets a `vec4` uniform shared by every material. See `set_global_uniform_float`.
+    pub fn set_global_uniform_vec4(
+        &mut self,
+        name: &str,
+        x: f32,
+        y: f32,
+        z: f32,
+        w: f32,
+    ) -> Result<(), JsValue> {
+        let value = GlobalUniformValue::Vector4(Vector4::new(x, y, z, w));
+        self.set_global_uniform(name, value)
+    }
+
+    /// Sets a `mat4` uniform shared by every material, given as 16 column-major values. See
+    /// `set_global_uniform_float`.  
+    /// Also fails if `matrix` doesn't have 16 values.
+    pub fn set_global_uniform_mat4(&mut self, name: &str, matrix: &[f32]) -> Result<(), JsValue> {
+        let matrix = Matrix4Data::new(matrix)?.to_matrix4();
+        self.set_global_uniform(name, GlobalUniformValue::Matrix4(matrix))
+    }
+
+    /// Removes a uniform shared by every material. Programs keep the last value they got
+    /// until they are compiled again. Returns `false` if the uniform wasn't set.  
+    /// Fails if the scene is not initialized.
+    pub fn remove_global_uniform(&mut self, name: &str) -> Result<bool, JsValue> {
+        let renderer = self.get_renderer("Global uniforms")?;
+        Ok(renderer.borrow_mut().get_global_uniforms_mut().remove(name))
+    }
+
+    /// Returns the uniforms shared by every material, as an object mapping their names to a
+    /// number for floats, or to an array of numbers for vectors and column-major matrices.  
+    /// Fails if the scene is not initialized.
+    pub fn get_global_uniforms(&self) -> Result<JsValue, JsValue> {
+        let renderer = self.get_renderer("Global uniforms")?.borrow();
+        Ok(describe_global_uniforms(renderer.get_global_uniforms()))
+    }
+
+    /// Creates an entity displaying a texture over the 3D scene, in a rectangle positioned in
+    /// CSS pixels from the top-left corner of the canvas. Returns its Entity ID.  
+    /// Fails if the scene is not initialized or if the texture is not registered.
+    pub fn create_overlay_quad(
+        &mut self,
+        texture_id: &str,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> Result<u32, JsValue> {
+        let texture_index = self.get_texture_index(texture_id, "Overlays")?;
+        let entity = Scene::build_entity(&mut self.world)
+            .with(Overlay::new(texture_index, x, y, width, height))
+            .with(Enabled)
+            .build();
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        Ok(entity.id())
+    }
+
+    /// Sets the position of an overlay's top-left corner, in CSS pixels.
+    pub fn set_overlay_position(&mut self, entity_id: u32, x: f32, y: f32) -> Result<(), JsValue> {
+        self.with_overlay(entity_id, |overlay| {
+            overlay.x = x;
+            overlay.y = y;
+        })
+    }
+
+    /// Sets the size of an overlay, in CSS pixels.
+    pub fn set_overlay_size(
+        &mut self,
+        entity_id: u32,
+        width: f32,
+        height: f32,
+    ) -> Result<(), JsValue> {
+        self.with_overlay(entity_id, |overlay| {
+            overlay.width = width;
+            overlay.height = height;
+        })
+    }
+
+    /// Sets the opacity of an overlay, multiplied with its texture's alpha.
+    pub fn set_overlay_opacity(&mut self, entity_id: u32, opacity: f32) -> Result<(), JsValue> {
+        self.with_overlay(entity_id, |overlay| overlay.opacity = opacity)
+    }
+
+    /// Sets the draw order of an overlay. Overlays with a higher order are drawn on top.
+    pub fn set_overlay_order(&mut self, entity_id: u32, order: i32) -> Result<(), JsValue> {
+        self.with_overlay(entity_id, |overlay| overlay.order = order)
+    }
+
+    /// Creates an entity emitting up to `max_particles` particles at `spawn_rate` particles
+    /// per second from `position`. Returns its Entity ID.  
+    /// Use a `spawn_rate` of `0` for an emitter that only emits bursts.
+    pub fn create_particle_emitter(
+        &mut self,
+        max_particles: u32,
+        spawn_rate: f32,
+        position: Vector3Data,
+    ) -> u32 {
+        let entity = Scene::build_entity(&mut self.world)
+            .with(ParticleEmitter::new(max_particles as usize, spawn_rate))
+            .with(Transform::new(
+                &position.to_vector3(),
+                &Vector3::new(0.0, 0.0, 0.0),
+                &Vector3::new(1.0, 1.0, 1.0),
+            ))
+            .with(Enabled)
+            .build();
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        entity.id()
+    }
+
+    /// Sets the number of particles an emitter spawns per second.
+    pub fn set_particle_spawn_rate(
+        &mut self,
+        entity_id: u32,
+        spawn_rate: f32,
+    ) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| emitter.spawn_rate = spawn_rate)
+    }
+
+    /// Sets the range of lifetimes of newly spawned particles, in seconds.
+    pub fn set_particle_lifetime(
+        &mut self,
+        entity_id: u32,
+        min_lifetime: f32,
+        max_lifetime: f32,
+    ) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| {
+            emitter.lifetime = (min_lifetime, max_lifetime)
+        })
+    }
+
+    /// Sets the initial velocity of newly spawned particles: a random direction in a cone of
+    /// half angle `cone_angle` (in radians) around `direction`, and a random speed.
+    pub fn set_particle_velocity(
+        &mut self,
+        entity_id: u32,
+        direction: Vector3Data,
+        cone_angle: f32,
+        min_speed: f32,
+        max_speed: f32,
+    ) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| {
+            emitter.direction = direction.to_vector3();
+            emitter.cone_angle = cone_angle;
+            emitter.speed = (min_speed, max_speed);
+        })
+    }
+
+    /// Sets the constant acceleration applied to an emitter's particles.
+    pub fn set_particle_gravity(
+        &mut self,
+        entity_id: u32,
+        gravity: Vector3Data,
+    ) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| emitter.gravity = gravity.to_vector3())
+    }
+
+    /// Sets the color and opacity of an emitter's particles at the start and end of their life.
+    pub fn set_particle_colors(
+        &mut self,
+        entity_id: u32,
+        start_color: Vector3Data,
+        start_opacity: f32,
+        end_color: Vector3Data,
+        end_opacity: f32,
+    ) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| {
+            emitter.start_color = start_color.to_vector3().push(start_opacity);
+            emitter.end_color = end_color.to_vector3().push(end_opacity);
+        })
+    }
+
+    /// Sets the world size of an emitter's particles at the start and end of their life.
+    pub fn set_particle_sizes(
+        &mut self,
+        entity_id: u32,
+        start_size: f32,
+        end_size: f32,
+    ) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| {
+            emitter.start_size = start_size;
+            emitter.end_size = end_size;
+        })
+    }
+
+    /// Spawns `count` particles at once at the next update, within the emitter's
+    /// `max_particles` limit.
+    pub fn emit_particles(&mut self, entity_id: u32, count: u32) -> Result<(), JsValue> {
+        self.with_particle_emitter(entity_id, |emitter| emitter.burst(count as usize))
+    }
+
+    /// Sets whether the color and depth buffers are cleared before rendering each frame
+    /// (`true` by default). With a context initialized by `initialize_with_external_context`,
+    /// only the area given to the scene is cleared: disable it to render the scene over what
+    /// the host application drew before.  
+    /// Fails if the scene is not initialized.
+    pub fn set_auto_clear(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.get_renderer("Auto clear")?
+            .borrow_mut()
+            .set_auto_clear(enabled);
+        Ok(())
+    }
+
+    /// Enables or disables the depth pre-pass: opaque meshes are first rendered to the
+    /// depth buffer only, so that expensive fragment shaders only run once per pixel.  
+    /// Fails if the scene is not initialized.
+    pub fn set_depth_prepass(&mut self, enabled: bool) -> Result<(), JsValue> {
+        match &self.main_renderer {
+            Some(renderer) => {
+                renderer.borrow_mut().set_depth_prepass(enabled);
+                Ok(())
+            }
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "The depth pre-pass can't be set before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Enables or disables soft particles: the built-in particles, and the transparent
+    /// materials whose fragment shader uses `USE_SOFT_PARTICLES`, fade out where they meet
+    /// opaque meshes. Costs an extra depth-only render of the opaque meshes every frame.  
+    /// Fails if the scene is not initialized or if the context doesn't support depth
+    /// textures, in which case soft particles stay disabled.
+    pub fn set_soft_particles(&mut self, enabled: bool) -> Result<(), JsValue> {
+        let renderer = match &self.main_renderer {
+            Some(renderer) => renderer,
+            None => {
+                return Err(W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Soft particles can't be set before initializing the scene.",
+                )
+                .into())
+            }
+        };
+        let result = renderer.borrow_mut().set_soft_particles(enabled);
+        self.world
+            .write_resource::<LightConfiguration>()
+            .soft_particles = renderer.borrow().has_soft_particles();
+        result.map_err(|error| error.into())
+    }
+
+    /// Enables or disables HDR rendering: the active camera renders to an `Rgba16F` target,
+    /// and a final tone mapping pass applies its exposure and tone mapping operator while
+    /// converting colors to the 8-bit canvas, or to the output target. Overlays are drawn
+    /// after it. Fragment shaders mentioning `TONE_MAPPING` are recompiled with
+    /// `HDR_OUTPUT`, which makes `tone_map` return colors as is. Disabling it disables bloom.  
+    /// Returns the format the active camera now renders to: `Rgba8` once disabled, or if the
+    /// device can't render to half floats.  
+    /// Fails if the scene is not initialized or if the pass can't be created, in which case
+    /// HDR rendering stays disabled.
+    pub fn set_hdr(&mut self, enabled: bool) -> Result<ColorFormat, JsValue> {
+        let renderer = self.get_renderer("HDR rendering")?;
+        let result = renderer.borrow_mut().set_hdr(enabled);
+        self.world.write_resource::<LightConfiguration>().hdr = renderer.borrow().has_hdr();
+        let format = result?;
+        Ok(format.unwrap_or(ColorFormat::Rgba8))
+    }
+
+    /// Enables or disables bloom: the colors brighter than `threshold` are blurred and added
+    /// to the scene, multiplied by `intensity`, before the tone mapping pass. Enables HDR
+    /// rendering if it isn't; disabling HDR rendering disables bloom too.  
+    /// If the device can't render to half floats, bloom stays disabled with a warning.
+    /// Returns `true` if bloom is enabled.  
+    /// Fails if the scene is not initialized, if `threshold` or `intensity` is negative, or
+    /// if HDR rendering can't be enabled.
+    pub fn set_bloom(
+        &mut self,
+        enabled: bool,
+        threshold: f32,
+        intensity: f32,
+    ) -> Result<bool, JsValue> {
+        if !enabled {
+            self.get_renderer("Bloom")?.borrow_mut().set_bloom(None)?;
+            return Ok(false);
+        }
+        if !(threshold >= 0. && intensity >= 0.) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The bloom threshold and intensity can't be negative.",
+                &format!("threshold {}, intensity {}", threshold, intensity),
+            )
+            .into());
+        }
+        let hdr_enabled = self.get_renderer("Bloom")?.borrow().has_hdr();
+        if !hdr_enabled {
+            self.set_hdr(true)?;
+        }
+        let settings = BloomSettings {
+            threshold: threshold,
+            intensity: intensity,
+        };
+        let result = self
+            .get_renderer("Bloom")?
+            .borrow_mut()
+            .set_bloom(Some(settings));
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) => {
+                log_warn!("Bloom stays disabled: {}", error);
+                if !hdr_enabled {
+                    self.set_hdr(false)?;
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Creates an offscreen render target of `width` by `height` pixels, whose color texture
+    /// is registered under `id` so that material instances can sample it with
+    /// `set_instance_texture`. Replaces the target registered under `id`, if any.  
+    /// `Rgba16F` keeps colors above 1 for HDR effects, and needs the half-float extensions;
+    /// `R11FG11FB10F` and `Rgb10A2` need WebGL 2. An unsupported format falls back to
+    /// `Rgba16F` for `R11FG11FB10F` if renderable, else to `Rgba8`: the format actually used
+    /// is returned, and `get_capabilities` lists the renderable ones beforehand. Render the
+    /// scene with `ToneMapping::None` to keep HDR colors in the target; the material drawing
+    /// its texture to the canvas is then where they are mapped back to 8 bits. With `set_hdr`,
+    /// the tone mapping pass converts them before they reach the target instead.  
+    /// Fails if the scene is not initialized, if the size is 0 or above the maximum texture
+    /// size, or if the framebuffer can't be created.
+    pub fn create_render_target(
+        &mut self,
+        id: String,
+        width: u32,
+        height: u32,
+        format: ColorFormat,
+    ) -> Result<ColorFormat, JsValue> {
+        let format = self
+            .get_renderer("Render targets")?
+            .borrow_mut()
+            .create_render_target(id, width, height, format)?;
+        Ok(format)
+    }
+
+    /// Deletes the render target whose texture is registered under `id`, and unregisters the
+    /// texture. The canvas is rendered to again if it was the output target.  
+    /// Returns `false` if there is no such render target or if the scene is not initialized.
+    pub fn delete_render_target(&mut self, id: &str) -> bool {
+        match &self.main_renderer {
+            Some(renderer) => renderer.borrow_mut().delete_render_target(id),
+            None => false,
+        }
+    }
+
+    /// Makes the next frames render the active camera to the render target whose texture is
+    /// registered under `id` instead of the canvas, or to the canvas again with `None`.
+    /// The camera keeps its aspect ratio. The target's texture must not be sampled by the
+    /// meshes rendered to it.  
+    /// Fails if the scene is not initialized or if there is no such render target.
+    pub fn set_output_target(&mut self, id: Option<String>) -> Result<(), JsValue> {
+        self.get_renderer("Output targets")?
+            .borrow_mut()
+            .set_output_target(id)?;
+        Ok(())
+    }
+
+    /// Returns the statistics measured while rendering the last frame.  
+    /// All counters are `0` before the scene is initialized.
+    pub fn get_frame_stats(&self) -> FrameStats {
+        match &self.main_renderer {
+            Some(renderer) => renderer.borrow().get_frame_stats(),
+            None => Default::default(),
+        }
+    }
+
+    /// Returns the limits and extensions of the WebGL context, queried when the scene was
+    /// initialized, as `{ maxTextureImageUnits, maxVertexAttributes, maxTextureSize,
+    /// maxVertexUniformVectors, maxAnisotropy, extensions, renderableFormats }`, the latter
+    /// listing the color formats textures can be rendered to, like `RGBA16F` for HDR.  
+    /// Fails if the scene is not initialized.
+    pub fn get_capabilities(&self) -> Result<JsValue, JsValue> {
+        match &self.main_renderer {
+            Some(renderer) => Ok(renderer.borrow().get_capabilities().to_js_value()),
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Capabilities can't be queried before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Registers an asset and returns its id, or an empty string on failure.  
+    /// Registering an asset under an id that is already used fails: see `replace_asset`.
+    pub fn register_asset(&mut self, file_data: &[u8], file_type: FileType) -> String {
+        self.register_asset_under(file_data, file_type, None, false)
+    }
+
+    /// Registers an asset under `id` rather than the id stored in the file, and returns it,
+    /// or an empty string on failure, as `register_asset`.
+    pub fn register_asset_with_id(
+        &mut self,
+        file_data: &[u8],
+        file_type: FileType,
+        id: String,
+    ) -> String {
+        self.register_asset_under(file_data, file_type, Some(id), false)
+    }
+
+    /// Registers an asset under `id` if given or the id stored in the file otherwise,
+    /// replacing the asset of the same type already registered under it, and returns the id,
+    /// or an empty string on failure. Meant for hot-reloading.  
+    /// The replaced asset is updated in place and its GPU resources are released: entities
+    /// using it display the new version from the next frame on, and the material instances
+    /// of a replaced material keep it as their parent, looking up their uniforms again.
+    pub fn replace_asset(
+        &mut self,
+        file_data: &[u8],
+        file_type: FileType,
+        id: Option<String>,
+    ) -> String {
+        self.register_asset_under(file_data, file_type, id, true)
+    }
+
+    /// Registers a mesh file under `id` if given or the id stored in the file otherwise,
+    /// after rotating, scaling and recentering its vertices as set by `options`, and
+    /// checking the winding of its triangles: front faces must be counter-clockwise, or
+    /// back-face culling hides them. Clockwise triangles are flipped if
+    /// `options.fix_winding` is set; otherwise see `set_material_front_face`.  
+    /// Returns `{ id, winding, flippedTriangles }`, `winding` being the one detected before
+    /// any fix: `counterClockwise`, `clockwise`, `mixed` or `unknown` for flat meshes without
+    /// normals. Meshes without normals are assumed to be closed.  
+    /// Fails if the scene is not initialized, if the file can't be deserialized or if the id
+    /// is already used.
+    pub fn import_mesh(
+        &mut self,
+        file_data: &[u8],
+        id: Option<String>,
+        options: &ImportOptions,
+    ) -> Result<JsValue, JsValue> {
+        let (id, report) = self
+            .get_renderer("Meshes")?
+            .borrow_mut()
+            .import_mesh(file_data, id, false, options)?;
+        Ok(describe_import(&id, &report))
+    }
+
+    /// Generates the shaders of a material from its JSON description, registers it and
+    /// returns its index. See `MaterialDescription` for the format and supported features.  
+    /// Fails if the scene is not initialized, if the description is invalid, if its id is
+    /// already used or if one of its textures is not registered.
+    pub fn create_material_from_description(&mut self, json: &str) -> Result<usize, JsValue> {
+        let description = MaterialDescription::from_json(json)?;
+        let index = self
+            .get_renderer("Described materials")?
+            .borrow_mut()
+            .add_described_material(&description)?;
+        Ok(index)
+    }
+
+    /// Creates `count` material instances of a registered material at once, and returns
+    /// their ids. They have no uniforms of their own until one is set, so they cost little
+    /// more than their id, and meshes using untouched instances are drawn without switching
+    /// uniforms between them.  
+    /// Fails if the material isn't registered.
+    pub fn create_material_instances(
+        &mut self,
+        parent_material_id: &str,
+        count: u32,
+    ) -> Result<Vec<JsString>, JsValue> {
+        let ids = self
+            .get_renderer("Material instances")?
+            .borrow_mut()
+            .create_material_instances(parent_material_id, count as usize)?;
+        Ok(ids.iter().map(|id| JsString::from(id.as_str())).collect())
+    }
+
+    /// Registers an asset from the JSON version of its file, as written by
+    /// `convert_asset_to_json`, and returns its id, or an empty string on failure.  
+    /// Meant for development: ship binary files.
+    pub fn register_asset_json(&mut self, text: &str, file_type: FileType) -> String {
+        match asset_file_from_json(text, &file_type) {
+            Ok(file_data) => self.register_asset_under(&file_data, file_type, None, false),
+            Err(error) => {
+                log_error!("{}", error);
+                String::new()
+            }
+        }
+    }
+
+    /// Converts a binary asset file to indented JSON, for diffing and hand-tweaking. If
+    /// `compact` is `true`, long float arrays such as vertex buffers are written in base64
+    /// rather than one number at a time.
+    pub fn convert_asset_to_json(
+        &self,
+        file_data: &[u8],
+        file_type: FileType,
+        compact: bool,
+    ) -> Result<String, JsValue> {
+        Ok(asset_file_to_json(file_data, &file_type, compact)?)
+    }
+
+    /// Sets whether mesh data registered from now on keep a copy of their positions and
+    /// indexes in memory, which `get_mesh_triangles` and `get_entity_world_triangles` need.
+    /// Off by default, since it holds every vertex position twice.  
+    /// Fails if the scene is not initialized.
+    pub fn set_retain_mesh_cpu_data(&mut self, retain_cpu_data: bool) -> Result<(), JsValue> {
+        match &self.main_renderer {
+            Some(renderer) => {
+                renderer
+                    .borrow_mut()
+                    .set_retain_mesh_cpu_data(retain_cpu_data);
+                Ok(())
+            }
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Mesh data retention can't be set before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Sets how much memory the assets may use on the GPU and on the CPU, in bytes, or
+    /// `undefined` for no limit. When the GPU limit is exceeded, the buffers of the least
+    /// recently drawn mesh data are deleted, and uploaded again the next time they are drawn;
+    /// the evictions and uploads are counted in the `FrameStats`.  
+    /// Only mesh data registered after a limit is set can be evicted, since their file is
+    /// kept for that, and the CPU limit only warns. Use `pin_asset` to spare critical assets.  
+    /// Fails if the scene is not initialized.
+    pub fn set_memory_budget(
+        &mut self,
+        gpu_bytes: Option<u32>,
+        cpu_bytes: Option<u32>,
+    ) -> Result<(), JsValue> {
+        let renderer = self.get_renderer("The memory budget")?;
+        renderer.borrow_mut().set_memory_budget(
+            gpu_bytes.map(|bytes| bytes as usize),
+            cpu_bytes.map(|bytes| bytes as usize),
+        );
+        Ok(())
+    }
+
+    /// Exempts an asset from eviction by the memory budget.  
+    /// Fails if the scene is not initialized or if the asset isn't registered.
+    pub fn pin_asset(&mut self, id: &str) -> Result<(), JsValue> {
+        let renderer = self.get_renderer("Assets")?;
+        let result = renderer.borrow_mut().set_asset_pinned(id, true);
+        result.map_err(|error| error.into())
+    }
+
+    /// Lets the memory budget evict an asset pinned with `pin_asset` again.  
+    /// Fails if the scene is not initialized or if the asset isn't registered.
+    pub fn unpin_asset(&mut self, id: &str) -> Result<(), JsValue> {
+        let renderer = self.get_renderer("Assets")?;
+        let result = renderer.borrow_mut().set_asset_pinned(id, false);
+        result.map_err(|error| error.into())
+    }
+
+    /// Removes the material instances no entity has used for at least `older_than_frames`
+    /// frames, and if `all_types` is `true` the mesh data, materials and textures nothing
+    /// uses anymore either, deleting their GPU resources. Returns the removed assets as
+    /// `{ id, type }` objects.  
+    /// Entities are counted as users of the assets of their mesh when created, and stop
+    /// being when deleted. Assets no entity ever used, and textures, are timed from the
+    /// first collection finding them unused. Pinned assets, assets used by a registered
+    /// prefab, by a sprite, an overlay or a level of detail, and assets still held by
+    /// another asset are kept. Undoing the deletion of an entity whose assets were
+    /// collected brings it back without a mesh to draw.  
+    /// Fails if the scene is not initialized.
+    pub fn collect_unused_assets(
+        &mut self,
+        older_than_frames: u32,
+        all_types: bool,
+    ) -> Result<JsValue, JsValue> {
+        let mut referenced = Vec::new();
+        for prefab in self.prefabs.values() {
+            prefab::collect_asset_indexes(prefab, &mut referenced);
+        }
+        let (sprites, overlays, lods): (
+            ReadStorage<Sprite>,
+            ReadStorage<Overlay>,
+            ReadStorage<Lod>,
+        ) = self.world.system_data();
+        referenced.extend(sprites.join().map(|sprite| *sprite.get_texture_id()));
+        referenced.extend(overlays.join().map(|overlay| *overlay.get_texture_id()));
+        for lod in lods.join() {
+            referenced.extend_from_slice(lod.get_mesh_data_ids());
+        }
+        let mut asset_usage = self.world.write_resource::<AssetUsage>();
+        let removed = self
+            .get_renderer("Asset collection")?
+            .borrow_mut()
+            .collect_assets(all_types, |index| {
+                let collectable = !referenced.contains(&index)
+                    && asset_usage.check_unused(index, older_than_frames as u64);
+                if collectable {
+                    asset_usage.forget(index);
+                }
+                collectable
+            });
+        Ok(describe_collected_assets(&removed))
+    }
+
+    /// Returns the number of triangles of a mesh data.  
+    /// Fails if the mesh data is not registered or was registered without retaining its
+    /// CPU data: see `set_retain_mesh_cpu_data`.
+    pub fn get_mesh_triangle_count(&self, mesh_data_id: &str) -> Result<u32, JsValue> {
+        self.with_mesh_cpu_data(mesh_data_id, |cpu_data| cpu_data.triangle_count() as u32)
+    }
+
+    /// Bakes ambient occlusion into a mesh data, as one float per vertex in an `a_ao`
+    /// attribute that materials can multiply their ambient and hemisphere light by (see
+    /// `USE_VERTEX_AO` in the default shaders). `rays_per_vertex` rays are cast from each
+    /// vertex against the mesh's own triangles, which occlude it when closer than
+    /// `max_distance`.  
+    /// Fails if the mesh data is not registered or was registered without retaining its
+    /// CPU data: see `set_retain_mesh_cpu_data`.
+    pub fn bake_vertex_ao(
+        &mut self,
+        mesh_data_id: &str,
+        rays_per_vertex: u32,
+        max_distance: f32,
+    ) -> Result<(), JsValue> {
+        let renderer = self.get_renderer("Ambient occlusion")?.borrow();
+        let mesh_data = renderer
+            .get_asset_registry()
+            .get_mesh_data(mesh_data_id)
+            .ok_or_else(|| {
+                W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Mesh data could not be found. Has it been registered yet?",
+                    mesh_data_id,
+                )
+            })?;
+        let result = mesh_data.borrow_mut().bake_vertex_ao(
+            renderer.get_webgl_context(),
+            rays_per_vertex,
+            max_distance,
+        );
+        result.map_err(|error| error.into())
+    }
+
+    /// Returns the triangles of a mesh data in local space, as 9 floats per triangle: the
+    /// positions of its three vertices.  
+    /// Fails if the mesh data is not registered or was registered without retaining its
+    /// CPU data: see `set_retain_mesh_cpu_data`.
+    pub fn get_mesh_triangles(&self, mesh_data_id: &str) -> Result<Float32Array, JsValue> {
+        self.with_mesh_cpu_data(mesh_data_id, |cpu_data| {
+            triangles_to_array(cpu_data.triangles(), &Matrix4::identity())
+        })
+    }
+
+    /// Returns the triangles of a mesh entity in world space, as 9 floats per triangle.  
+    /// Fails if the entity has no `Mesh` or `Transform`, or if its mesh data was registered
+    /// without retaining its CPU data.
+    pub fn get_entity_world_triangles(&self, entity_id: u32) -> Result<Float32Array, JsValue> {
+        let (meshes, transforms, entities): (ReadStorage<Mesh>, ReadStorage<Transform>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        let mesh = meshes
+            .get(entity)
+            .ok_or_else(|| missing_component_error("Mesh", entity_id))?;
+        let world_matrix = transforms
+            .get(entity)
+            .ok_or_else(|| missing_component_error("Transform", entity_id))?
+            .get_world_matrix();
+        let renderer = self.get_renderer("Triangles")?.borrow();
+        let mesh_data = renderer
+            .get_asset_registry()
+            .get_mesh_data_with_index(*mesh.get_mesh_data_id())
+            .ok_or_else(|| {
+                W3DError::new(
+                    W3DErrorKind::MissingAsset,
+                    "The entity's mesh data is not registered.",
+                )
+            })?;
+        let mesh_data = mesh_data.borrow();
+        let cpu_data = get_cpu_data(&mesh_data)?;
+        Ok(triangles_to_array(cpu_data.triangles(), &world_matrix))
+    }
+
+    /// Returns the closest enabled mesh entity under a point of the screen, given in
+    /// normalized device coordinates (from -1 to 1, y pointing up), seen from the active
+    /// camera. `None` if nothing is hit. `Unpickable` entities are ignored.
+    ///
+    /// Entities are hit through the world box containing their bounds, and entities without
+    /// bounds are ignored. If `precise` is `true`, the ray is then tested against the
+    /// triangles of the entities whose box it hits, and the result holds the triangle and
+    /// barycentric coordinates of the hit. Triangles of large meshes are indexed on their
+    /// first precise pick to keep later ones fast.  
+    /// Precise picking fails if a mesh data under the ray was registered without retaining
+    /// its CPU data: see `set_retain_mesh_cpu_data`.
+    pub fn pick(&self, x: f32, y: f32, precise: bool) -> Result<Option<PickResult>, JsValue> {
+        let ray = self.get_picking_ray(x, y)?;
+        let (meshes, transforms, bounds, enableds, effectively_disabled, unpickables, entities): (
+            ReadStorage<Mesh>,
+            ReadStorage<Transform>,
+            ReadStorage<Bounds>,
+            ReadStorage<Enabled>,
+            ReadStorage<EffectivelyDisabled>,
+            ReadStorage<Unpickable>,
+            Entities,
+        ) = self.world.system_data();
+        let renderer = self.get_renderer("Picking")?.borrow();
+        let mut closest: Option<PickResult> = None;
+        let active = (&enableds, !&effectively_disabled, !&unpickables);
+        let candidates = (&entities, &meshes, &transforms, bounds.maybe(), active);
+        for (entity, mesh, transform, entity_bounds, _) in candidates.join() {
+            let mesh_data = match renderer
+                .get_asset_registry()
+                .get_mesh_data_with_index(*mesh.get_mesh_data_id())
+            {
+                Some(mesh_data) => mesh_data,
+                None => continue,
+            };
+            let world_matrix = transform.get_world_matrix();
+            let local_bounds = match entity_bounds {
+                Some(entity_bounds) => Some(entity_bounds.sphere),
+                None => mesh_data.borrow().get_bounds(),
+            };
+            let world_box = match local_bounds {
+                Some(sphere) => Aabb::from_sphere(&sphere.transformed(&world_matrix)),
+                None => continue,
+            };
+            let box_distance = match ray.intersect_aabb(&world_box) {
+                Some(distance) => distance,
+                None => continue,
+            };
+            if closest.map_or(false, |closest| box_distance >= closest.distance) {
+                continue;
+            }
+            let result = if precise {
+                let mut mesh_data = mesh_data.borrow_mut();
+                match intersect_mesh_triangles(&mut mesh_data, &ray, &world_matrix)? {
+                    Some((face_index, hit)) => PickResult {
+                        entity_id: entity.id(),
+                        distance: hit.distance,
+                        point: vector3_data(&ray.point_at(hit.distance)),
+                        face_index: face_index as i32,
+                        barycentric: vector3_data(&hit.barycentric),
+                    },
+                    None => continue,
+                }
+            } else {
+                PickResult {
+                    entity_id: entity.id(),
+                    distance: box_distance,
+                    point: vector3_data(&ray.point_at(box_distance)),
+                    face_index: -1,
+                    barycentric: Vector3Data::default(),
+                }
+            };
+            if closest.map_or(true, |closest| result.distance < closest.distance) {
+                closest = Some(result);
+            }
+        }
+        Ok(closest)
+    }
+
+    /// Drops an entity onto the surface in `direction`, e.g. to place objects on a terrain.
+    /// A ray is cast from the entity's world position against the meshes of the other
+    /// entities, as with `pick`, ignoring the entity's descendants. If a surface is hit
+    /// within `max_distance`, the entity is moved along `direction` so that the lowest point
+    /// of its bounds and its descendants' rests on the hit point. Returns `false` if no
+    /// surface was hit, leaving the entity unchanged.
+    ///
+    /// `align_to_normal` rotates the entity's up axis (local +Y) towards the surface normal,
+    /// from `0` (rotation unchanged) to `1` (fully aligned), the entity being placed after
+    /// the rotation.
+    ///
+    /// Meshes whose CPU data is retained are hit and measured through their triangles,
+    /// others through their bounding box and bounding sphere, which is coarser: entities
+    /// may float above the surface, and boxes containing the entity's position are ignored.
+    /// Positions are those of the last update.  
+    /// Fails if the scene is not initialized, if the entity has no `Transform` or if
+    /// `direction` is null.
+    pub fn snap_to_surface(
+        &mut self,
+        entity_id: u32,
+        direction: Vector3Data,
+        max_distance: f32,
+        align_to_normal: f32,
+    ) -> Result<bool, JsValue> {
+        let direction = direction
+            .to_vector3()
+            .try_normalize(std::f32::EPSILON)
+            .ok_or_else(|| {
+                W3DError::new(
+                    W3DErrorKind::InvalidArgument,
+                    "The direction to snap entities in can't be null.",
+                )
+            })?;
+        let entity = self.world.entities().entity(entity_id);
+        let (world_matrix, local_matrix) = match self.world.read_storage::<Transform>().get(entity)
+        {
+            Some(transform) => (transform.get_world_matrix(), transform.get_local_matrix()),
+            None => return Err(missing_component_error("Transform", entity_id).into()),
+        };
+        let tree = Scene::get_entity_tree(&self.world, entity);
+        let position = world_matrix.column(3).xyz();
+        let (distance, normal) =
+            match self.cast_surface_ray(&Ray::new(position, direction), &tree)? {
+                Some((distance, normal)) if distance <= max_distance => (distance, normal),
+                _ => return Ok(false),
+            };
+        let mut rotation = UnitQuaternion::identity();
+        if align_to_normal > 0. {
+            let up = world_matrix.column(1).xyz();
+            if let Some(alignment) = UnitQuaternion::rotation_between(&up, &normal) {
+                rotation = safe_slerp(&rotation, &alignment, align_to_normal.min(1.));
+            }
+        }
+        let pivot = Translation3::from(position);
+        let rotated =
+            pivot.to_homogeneous() * rotation.to_homogeneous() * pivot.inverse().to_homogeneous();
+        let extent = self
+            .get_extent_along(&tree, &rotated, &position, &direction)?
+            .unwrap_or(0.);
+        let shift = Translation3::from(direction * (distance - extent)).to_homogeneous();
+        let parent_inverse = (world_matrix
+            * local_matrix.try_inverse().unwrap_or_else(Matrix4::identity))
+        .try_inverse()
+        .unwrap_or_else(Matrix4::identity);
+        let local_matrix = parent_inverse * shift * rotated * world_matrix;
+        #[cfg(feature = "editor")]
+        self.record_transform_edit(entity_id);
+        let (mut transforms, mut dirty_transforms): (
+            WriteStorage<Transform>,
+            WriteStorage<DirtyTransform>,
+        ) = self.world.system_data();
+        if let Some(transform) = transforms.get_mut(entity) {
+            transform.set_local_matrix(&local_matrix)?;
+            dirty_transforms.insert(entity, DirtyTransform).ok();
+        }
+        Ok(true)
+    }
+
+    /// Returns a description of an entity, to find out why it doesn't render as expected:
+    /// its components, its local transform, the assets of its mesh and whether they are
+    /// registered and compiled, its light parameters, and whether it passed culling and was
+    /// drawn last frame. See `inspection::describe_entity` for the exact layout.
+    pub fn inspect_entity(&self, entity_id: u32) -> JsValue {
+        match &self.main_renderer {
+            Some(renderer) => {
+                inspection::describe_entity(&self.world, Some(&renderer.borrow()), entity_id)
+            }
+            None => inspection::describe_entity(&self.world, None, entity_id),
+        }
+    }
+
+    /// Returns statistics about the scene's world: its number of entities, the number of
+    /// entities having each component, and the number of entities created and deleted
+    /// during the last frame. See `inspection::describe_world` for the exact layout.
+    pub fn get_world_stats(&self) -> JsValue {
+        inspection::describe_world(&self.world)
+    }
+
+    /// Sets whether missing assets are errors, as they used to be, instead of being replaced
+    /// by the built-in fallbacks: a magenta material, a unit cube and a checkerboard texture.
+    /// Useful to make automated tests fail on broken references.  
+    /// Fails if the scene is not initialized.
+    pub fn set_strict_assets(&mut self, strict: bool) -> Result<(), JsValue> {
+        match &self.main_renderer {
+            Some(renderer) => {
+                renderer.borrow_mut().set_strict_assets(strict);
+                Ok(())
+            }
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Strict assets can't be set before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Returns the missing assets replaced by fallbacks so far, as an array of
+    /// `{ id, type, referencedBy }` objects. `type` is one of `"mesh data"`, `"material"`,
+    /// `"material instance"` and `"texture"`. Empty if the scene is not initialized.
+    pub fn get_missing_asset_report(&self) -> JsValue {
+        match &self.main_renderer {
+            Some(renderer) => {
+                describe_missing_assets(renderer.borrow().get_asset_registry().get_missing_assets())
+            }
+            None => Array::new().into(),
+        }
+    }
+
+    /// Returns a report of every registered asset, for editors and devtools: mesh data with
+    /// their vertex count, buffers and GPU size, materials with their compilation state,
+    /// attributes and uniforms, material instances with their parent and overridden
+    /// uniforms, and textures with their dimensions and size.  
+    /// The report is empty before the scene is initialized.
+    pub fn get_registry_report(&self) -> JsValue {
+        match &self.main_renderer {
+            Some(renderer) => describe_asset_registry(renderer.borrow().get_asset_registry()),
+            None => describe_asset_registry(&AssetRegistry::new()),
+        }
+    }
+
+    /// Returns `true` if an asset of any type is registered under `id`.
+    pub fn has_asset(&self, id: &str) -> bool {
+        match &self.main_renderer {
+            Some(renderer) => renderer
+                .borrow()
+                .get_asset_registry()
+                .get_id_from_str(id)
+                .is_some(),
+            None => false,
+        }
+    }
+
+    /// Returns the ids of the registered assets of a type, in registration order.
+    pub fn get_asset_ids(&self, file_type: FileType) -> Vec<JsString> {
+        match &self.main_renderer {
+            Some(renderer) => renderer
+                .borrow()
+                .get_asset_registry()
+                .get_ids(&file_type)
+                .iter()
+                .map(|id| JsString::from(id.as_str()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn register_texture(&mut self, image: &HtmlImageElement, id: String) -> String {
+        match &mut self.main_renderer {
+            None => {
+                log_error!("Trying to register asset before initializing renderer!");
+                String::new()
+            }
+            Some(renderer) => match renderer.borrow_mut().register_texture(image, id) {
+                Err(error) => {
+                    log_error!("{}", error);
+                    String::new()
+                }
+                Ok(id) => id,
+            },
+        }
+    }
+
+    /// Registers a texture without blocking the frame: the image is decoded off the main
+    /// thread with `createImageBitmap`, then uploaded at the start of a following update,
+    /// large batches being spread over several updates. Until then, the texture is a 1x1
+    /// white placeholder, which material instances can already use.  
+    /// The promise resolves with the id once the texture is uploaded, or is rejected if the
+    /// scene is not initialized, if decoding fails or if the image is too large.
+    pub fn register_texture_async(&mut self, image: &HtmlImageElement, id: String) -> Promise {
+        let renderer = match self.get_renderer("Textures") {
+            Ok(renderer) => renderer.clone(),
+            Err(error) => return Promise::reject(&error.into()),
+        };
+        let decoding = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window to decode the texture in."))
+            .and_then(|window| {
+                let options = ImageBitmapOptions::new();
+                options.set_image_orientation(ImageOrientation::FromImage);
+                options.set_premultiply_alpha(PremultiplyAlpha::None);
+                window.create_image_bitmap_with_html_image_element_and_image_bitmap_options(
+                    image, &options,
+                )
+            });
+        let decoding = match decoding {
+            Ok(decoding) => decoding,
+            Err(error) => return Promise::reject(&error),
+        };
+        if let Err(error) = renderer
+            .borrow_mut()
+            .register_placeholder_texture(id.clone())
+        {
+            return Promise::reject(&error.into());
+        }
+        let mut settle = None;
+        let promise = Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+        let (resolve, reject) = settle.unwrap();
+        let on_failed_reject = reject.clone();
+        let on_decoded = Closure::once(move |bitmap: JsValue| {
+            renderer.borrow_mut().queue_texture_upload(
+                id,
+                bitmap.unchecked_into(),
+                resolve,
+                reject,
+            );
+        });
+        let on_failed = Closure::once(move |error: JsValue| {
+            on_failed_reject.call1(&JsValue::NULL, &error).ok();
+        });
+        let _ = decoding.then2(&on_decoded, &on_failed);
+        // Only one of them is ever called, and they must outlive this call.
+        on_decoded.forget();
+        on_failed.forget();
+        promise
+    }
+
+    /// Registers a cube texture from the images of its six faces, in the order
+    /// +X, -X, +Y, -Y, +Z, -Z. Returns its id, or an empty string on failure.
+    pub fn register_cube_texture(&mut self, faces: Array, id: String) -> String {
+        let faces: Result<Vec<HtmlImageElement>, _> = faces
+            .iter()
+            .map(|face| face.dyn_into::<HtmlImageElement>())
+            .collect();
+        let faces = match faces {
+            Ok(faces) => faces,
+            Err(_) => {
+                log_error!("Cube texture faces must be image elements.");
+                return String::new();
+            }
+        };
+        match &mut self.main_renderer {
+            None => {
+                log_error!("Trying to register asset before initializing renderer!");
+                String::new()
+            }
+            Some(renderer) => match renderer.borrow_mut().register_cube_texture(&faces, id) {
+                Err(error) => {
+                    log_error!("{}", error);
+                    String::new()
+                }
+                Ok(id) => id,
+            },
+        }
+    }
+
+    /// Compiles every registered material and performs the location lookups of every
+    /// material instance and mesh entity, so that objects don't cause a hitch the first frame
+    /// they appear. Mesh data and textures are already uploaded when they are registered.
+    ///
+    /// If `draw` is `true`, every mesh entity is also drawn once to a single pixel, with color
+    /// writes disabled, to force the driver to finish preparing its program.  
+    /// `progress` is called with `(done, total)` for each step, for loading screens. Like the
+    /// other scene callbacks, the calls are made once this method has returned, so that
+    /// `progress` can use the scene, and before the promise's handlers run.
+    ///
+    /// The work is done before returning; the promise resolves once it's done, or is rejected
+    /// with the first error met. Failing steps don't prevent the other ones from running.
+    pub fn warm_up_assets(&mut self, draw: bool, progress: Option<Function>) -> Promise {
+        let renderer = match &self.main_renderer {
+            Some(renderer) => renderer.clone(),
+            None => {
+                return Promise::reject(
+                    &W3DError::new(
+                        W3DErrorKind::Uninitialized,
+                        "Assets can't be warmed up before initializing the scene.",
+                    )
+                    .into(),
+                )
+            }
+        };
+        self.lighting_system.run_now(&self.world);
+        let light_config = self.world.read_resource::<LightConfiguration>().clone();
+        let (materials, material_instances) = {
+            let renderer = renderer.borrow();
+            let asset_registry = renderer.get_asset_registry();
+            (
+                asset_registry.get_all_materials(),
+                asset_registry.get_all_material_instances(),
+            )
+        };
+        let (meshes, transforms, lods): (
+            ReadStorage<Mesh>,
+            ReadStorage<Transform>,
+            ReadStorage<Lod>,
+        ) = self.world.system_data();
+        let total = materials.len() + material_instances.len() + meshes.join().count();
+        let mut done = 0;
+        let mut first_error: Option<W3DError> = None;
+        let mut step = |result: Result<(), W3DError>| {
+            done += 1;
+            if let Err(error) = result {
+                log_error!("{}", error);
+                first_error.get_or_insert(error);
+            }
+            if let Some(progress) = &progress {
+                let arguments = Array::of2(&(done as u32).into(), &(total as u32).into());
+                call_after_update(progress, arguments, "progress");
+            }
+        };
+        {
+            let renderer = renderer.borrow();
+            let context = renderer.get_webgl_context();
+            for material in materials {
+                step(
+                    material
+                        .borrow_mut()
+                        .compile_if_needed(context, &light_config),
+                );
+            }
+            for material_instance in material_instances {
+                let ready = material_instance
+                    .borrow()
+                    .get_parent()
+                    .borrow()
+                    .get_program()
+                    .is_some();
+                if ready {
+                    material_instance
+                        .borrow_mut()
+                        .lookup_locations(context, &light_config);
+                }
+                step(Ok(()));
+            }
+        }
+        for (mesh, transform, lod) in (&meshes, &transforms, lods.maybe()).join() {
+            let lod_mesh_data_ids = lod.map(|lod| lod.get_mesh_data_ids()).unwrap_or(&[]);
+            let result = mesh.compile_material(renderer.clone(), &light_config, lod_mesh_data_ids);
+            if draw && result.is_ok() {
+                renderer.borrow().warm_up_draw(mesh, transform);
+            }
+            step(result);
+        }
+        match first_error {
+            Some(error) => Promise::reject(&error.into()),
+            None => Promise::resolve(&JsValue::UNDEFINED),
+        }
+    }
+
+    /// Initializes the renderer for this Scene.
+    ///
+    /// Fails if `camera_entity` does not exist or has no `Camera` component. In that case the
+    /// Scene is left uninitialized and `initialize` can be called again with a correct id.
+    /// Calling it on an already initialized Scene does nothing.
+    pub fn initialize(
+        &mut self,
+        canvas: HtmlCanvasElement,
+        context: WebGlRenderingContext,
+        camera_entity: u32,
+    ) -> Result<(), JsValue> {
+        if let Some(camera) = self.get_camera_to_initialize(camera_entity)? {
+            self.set_renderer(Renderer::new(camera, canvas, context), camera_entity);
+        }
+        Ok(())
+    }
+
+    /// Initializes the renderer for this Scene with a context owned by the caller, which may
+    /// be shared with other renderers (e.g. a larger WebGL application).
+    ///
+    /// The canvas is never resized by the Scene: `width` and `height` are the size of the area
+    /// to render to, in device pixels, and later changes must be pushed with
+    /// `set_canvas_size`. The WebGL state mutated while rendering is saved and restored
+    /// around each frame, and only the scene's area is cleared (see `set_auto_clear`).
+    ///
+    /// Fails if `camera_entity` does not exist or has no `Camera` component.
+    /// Calling it on an already initialized Scene does nothing.
+    pub fn initialize_with_external_context(
+        &mut self,
+        context: WebGlRenderingContext,
+        width: u32,
+        height: u32,
+        camera_entity: u32,
+    ) -> Result<(), JsValue> {
+        if let Some(camera) = self.get_camera_to_initialize(camera_entity)? {
+            self.set_renderer(
+                Renderer::with_external_context(camera, context, width, height),
+                camera_entity,
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the size of the area to render to, in device pixels, for a scene initialized
+    /// with `initialize_with_external_context`. Scenes owning their canvas follow its display
+    /// size instead.
+    pub fn set_canvas_size(&mut self, width: u32, height: u32) -> () {
+        match &self.main_renderer {
+            Some(renderer) => renderer.borrow_mut().set_canvas_size(width, height),
+            None => log_error!("Trying to set the canvas size before initializing the renderer!"),
+        }
+    }
+
+    /// Returns the camera to create the renderer with when initializing, or `None` if the scene
+    /// is already initialized. Fails if `camera_entity` does not exist or has no `Camera`.
+    fn get_camera_to_initialize(&self, camera_entity: u32) -> Result<Option<Camera>, W3DError> {
+        match &self.main_renderer {
+            Some(_) => Ok(None),
+            None => self.get_camera_for_rendering(camera_entity).map(Some),
+        }
+    }
+
+    /// Sets up the rendering systems around a new renderer, and the active camera.
+    fn set_renderer(&mut self, renderer: Renderer, camera_entity: u32) -> () {
+        self.world.write_resource::<ActiveCamera>().entity =
+            Some(self.world.entities().entity(camera_entity));
+        let renderer = Rc::new(RefCell::new(renderer));
+        self.main_renderer = Some(renderer.clone());
+        self.rendering_system = Some(RenderingSystem::new(renderer.clone()));
+        self.shader_compilation_system = Some(ShaderCompilationSystem::new(renderer.clone()));
+        self.culling_system = Some(CullingSystem::new(renderer.clone()));
+        #[cfg(feature = "editor")]
+        {
+            self.manipulator_system = Some(ManipulatorSystem::new(renderer.clone()));
+        }
+    }
+
+    /// Makes the logic systems (scene graph, velocities, constraints, camera shake and
+    /// particles) run in fixed steps of `step` seconds, as many per update as the elapsed time
+    /// allows, or once per update if `None`. Meshes are then rendered with their world matrices interpolated
+    /// between the last two steps, which delays them by up to one step.  
+    /// Transform changes made from JS are applied by the next step.
+    pub fn set_fixed_timestep(&mut self, step: Option<f32>) -> Result<(), JsValue> {
+        if let Some(step) = step {
+            if !(step > 0.) {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::InvalidArgument,
+                    "The fixed timestep must be positive.",
+                    &step.to_string(),
+                )
+                .into());
+            }
+        }
+        self.world.write_resource::<Time>().set_fixed_step(step);
+        Ok(())
+    }
+
+    /// Pauses or resumes the simulation. While paused, particles and camera shakes stop
+    /// advancing, but the scene is still rendered and transform changes and constraints
+    /// still apply.
+    pub fn set_paused(&mut self, paused: bool) -> () {
+        self.world.write_resource::<Time>().set_paused(paused);
+    }
+
+    /// Returns `true` if the simulation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.world.read_resource::<Time>().is_paused()
+    }
+
+    /// Sets the speed of the simulation, `1` for real time and less for slow motion.
+    /// Constraints and the time uniforms of materials are not affected.  
+    /// Fails if `time_scale` is negative.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> Result<(), JsValue> {
+        if !(time_scale >= 0.) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The time scale can't be negative.",
+                &time_scale.to_string(),
+            )
+            .into());
+        }
+        self.world
+            .write_resource::<Time>()
+            .set_time_scale(time_scale);
+        Ok(())
+    }
+
+    /// Returns the number of steps taken by the last update: `1` outside of fixed timestep
+    /// mode, any number up to 5 in it.
+    pub fn get_last_step_count(&self) -> u32 {
+        self.world.read_resource::<Time>().get_step_count()
+    }
+
+    /// Function to be called each frame.  
+    /// The logic systems run once, or in fixed steps (see `set_fixed_timestep`), before
+    /// culling, lighting and rendering. Nothing is rendered while the canvas has no pixels,
+    /// e.g. when hidden, but the scene keeps being simulated.  
+    /// The functions given to `on_visibility_changed`, `on_origin_shifted` and
+    /// `on_budget_exceeded` are called in a microtask after it returns, in that order, so
+    /// every `Scene` method is safe to use from them.
+    pub fn update(&mut self) -> () {
+        #[cfg(feature = "xr")]
+        self.exit_xr_if_ended();
+        if let (Some(renderer), Some(rendering_system), Some(shader_system), Some(culling_system)) = (
+            &mut self.main_renderer,
+            &mut self.rendering_system,
+            &mut self.shader_compilation_system,
+            &mut self.culling_system,
+        ) {
+            let resized = renderer.borrow_mut().resize_canvas();
+            let size = renderer.borrow().get_drawing_buffer_size();
+            Scene::update_viewport(&self.world, resized, size);
+            self.camera_aspect_system.run_now(&self.world);
+            let steps = {
+                let mut time = self.world.write_resource::<Time>();
+                time.advance(crate::utils::now());
+                time.take_steps()
+            };
+            for _ in 0..steps {
+                self.hierarchy_system.run_now(&self.world);
+                self.enabled_propagation_system.run_now(&self.world);
+                self.velocity_system.run_now(&self.world);
+                self.path_follower_system.run_now(&self.world);
+                self.constraint_system.run_now(&self.world);
+                self.scene_graph_system.run_now(&self.world);
+                self.camera_shake_system.run_now(&self.world);
+                self.fade_system.run_now(&self.world);
+                self.particle_system.run_now(&self.world);
+            }
+            let origin_shift = Scene::shift_origin_if_needed(&self.world);
+            self.lod_system.run_now(&self.world);
+            culling_system.run_now(&self.world);
+            self.lighting_system.run_now(&self.world);
+            self.blob_shadow_system.run_now(&self.world);
+            #[cfg(feature = "editor")]
+            {
+                if let Some(manipulator_system) = &mut self.manipulator_system {
+                    manipulator_system.run_now(&self.world);
+                }
+            }
+            renderer.borrow_mut().upload_pending_textures();
+            shader_system.run_now(&self.world);
+            let rendered = !renderer.borrow().is_viewport_empty();
+            if rendered {
+                rendering_system.run_now(&self.world);
+            }
+            self.world.write_resource::<AssetUsage>().end_frame();
+            if self.world.write_resource::<StructuralChanges>().end_frame() {
+                self.world.maintain();
+            }
+            if let Some(callback) = &self.visibility_callback {
+                let (shown, hidden) = self.world.read_resource::<Visibility>().get_changes();
+                if !shown.is_empty() || !hidden.is_empty() {
+                    let to_array =
+                        |ids: Vec<u32>| -> Array { ids.into_iter().map(JsValue::from).collect() };
+                    let arguments = Array::of2(&to_array(shown), &to_array(hidden));
+                    call_after_update(callback, arguments, "visibility");
+                }
+            }
+            if let (Some(callback), Some(shift)) = (&self.origin_callback, origin_shift) {
+                let arguments = Array::of3(
+                    &JsValue::from(shift.x),
+                    &JsValue::from(shift.y),
+                    &JsValue::from(shift.z),
+                );
+                call_after_update(callback, arguments, "origin");
+            }
+            if rendered {
+                self.check_performance_budget();
+            }
+        } else {
+            warn_throttled!(5000, "Trying to update before initializing the renderer!");
+        }
+    }
+}
+
+impl Scene {
+    /// Applies `apply` to the `Transform` of every entity in `entity_ids` with its slice of
+    /// `values_per_entity` floats from `data`, in a single pass over the storages.
+    fn apply_transforms_batch<F>(
+        &mut self,
+        entity_ids: &[u32],
+        data: &[f32],
+        values_per_entity: usize,
+        apply: F,
+    ) -> Result<(), W3DError>
+    where
+        F: Fn(&mut Transform, &[f32]),
+    {
+        if data.len() != entity_ids.len() * values_per_entity {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                &format!(
+                    "Expected {} values per entity in the transform batch.",
+                    values_per_entity
+                ),
+                &format!("{} ids, {} values", entity_ids.len(), data.len()),
+            ));
+        }
+        #[cfg(feature = "editor")]
+        {
+            self.begin_transaction();
+            for entity_id in entity_ids {
+                self.record_transform_edit(*entity_id);
+            }
+            self.end_transaction().ok();
+        }
+        let (mut transforms, entities, mut dirty): (
+            WriteStorage<Transform>,
+            Entities,
+            WriteStorage<DirtyTransform>,
+        ) = self.world.system_data();
+        let mut failed_ids = Vec::new();
+        for (entity_id, values) in entity_ids.iter().zip(data.chunks(values_per_entity)) {
+            let entity = entities.entity(*entity_id);
+            if !entities.is_alive(entity) {
+                failed_ids.push(entity_id.to_string());
+                continue;
+            }
+            match transforms.get_mut(entity) {
+                Some(transform) => {
+                    apply(transform, values);
+                    dirty.insert(entity, DirtyTransform).ok();
+                }
+                None => failed_ids.push(entity_id.to_string()),
+            }
+        }
+        if failed_ids.is_empty() {
+            Ok(())
+        } else {
+            Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "Some entities of the transform batch have no Transform.",
+                &failed_ids.join(", "),
+            ))
+        }
+    }
+
+    /// Adds or replaces the `Bounds` of an entity.
+    fn set_entity_bounds(&mut self, entity_id: u32, bounds: Bounds) -> Result<(), JsValue> {
+        let (mut bounds_storage, entities): (WriteStorage<Bounds>, Entities) =
+            self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The entity does not exist.",
+                &entity_id.to_string(),
+            )
+            .into());
+        }
+        bounds_storage.insert(entity, bounds).ok();
+        Ok(())
+    }
+
+    /// Returns the asset registry index of a registered texture, for components referencing it.  
+    /// `usage` names what the texture is needed for, for the error message.
+    fn get_texture_index(&self, texture_id: &str, usage: &str) -> Result<usize, W3DError> {
+        match &self.main_renderer {
+            Some(renderer) => {
+                let renderer = renderer.borrow();
+                let asset_registry = renderer.get_asset_registry();
+                match asset_registry.get_texture(texture_id) {
+                    Some(_) => Ok(asset_registry.get_id_from_str(texture_id).unwrap()),
+                    None => Err(W3DError::with_source(
+                        W3DErrorKind::MissingAsset,
+                        "Texture could not be found. Has it been registered yet?",
+                        texture_id,
+                    )),
+                }
+            }
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                &format!("{} can't be created before initializing the scene.", usage),
+            )),
+        }
+    }
+
+    /// Applies `apply` to a registered `Material`.
+    fn with_material<F>(&mut self, material_id: &str, apply: F) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut Material),
+    {
+        match &self.main_renderer {
+            Some(renderer) => match renderer
+                .borrow()
+                .get_asset_registry()
+                .get_material(material_id)
+            {
+                Some(material) => {
+                    apply(&mut material.borrow_mut());
+                    Ok(())
+                }
+                None => Err(W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Material could not be found. Has it been registered yet?",
+                    material_id,
+                )
+                .into()),
+            },
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Materials can't be modified before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Creates an unpickable helper entity drawing the `(mesh data, material instance,
+    /// material)` indexes `assets` at the origin.
+    fn create_helper_entity(&mut self, assets: (usize, usize, usize)) -> u32 {
+        let (mesh_data_index, material_instance_index, material_index) = assets;
+        let entity = Scene::build_entity(&mut self.world)
+            .with(Mesh::new(
+                mesh_data_index,
+                material_instance_index,
+                material_index,
+            ))
+            .with(Transform::new(
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(0., 0., 0.),
+                &Vector3::new(1., 1., 1.),
+            ))
+            .with(DirtyTransform)
+            .with(Enabled)
+            .with(Unpickable)
+            .build();
+        Scene::count_asset_users(&self.world, entity);
+        #[cfg(feature = "editor")]
+        self.record_creation(entity);
+        entity.id()
+    }
+
+    /// Overrides a uniform for a mesh entity, adding its `UniformOverrides` if needed.
+    fn set_entity_uniform(
+        &mut self,
+        entity_id: u32,
+        name: &str,
+        value: UniformOverrideValue,
+    ) -> Result<(), JsValue> {
+        let entity = self.world.entities().entity(entity_id);
+        if !self.world.read_storage::<Mesh>().contains(entity) {
+            return Err(missing_component_error("Mesh", entity_id).into());
+        }
+        #[cfg(feature = "editor")]
+        self.record_uniform_edit(entity_id, name);
+        let mut overrides = self.world.write_storage::<UniformOverrides>();
+        match overrides.get_mut(entity) {
+            Some(entity_overrides) => entity_overrides.set(name, value),
+            None => {
+                let mut entity_overrides = UniformOverrides::new();
+                entity_overrides.set(name, value);
+                overrides
+                    .insert(entity, entity_overrides)
+                    .map_err(|_| missing_component_error("Mesh", entity_id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets a uniform shared by every material.
+    fn set_global_uniform(&mut self, name: &str, value: GlobalUniformValue) -> Result<(), JsValue> {
+        let renderer = self.get_renderer("Global uniforms")?;
+        renderer
+            .borrow_mut()
+            .get_global_uniforms_mut()
+            .set(name, value)?;
+        Ok(())
+    }
+
+    /// Applies `read` to the retained CPU data of a mesh data.
+    fn with_mesh_cpu_data<F, T>(&self, mesh_data_id: &str, read: F) -> Result<T, JsValue>
+    where
+        F: FnOnce(&MeshCpuData) -> T,
+    {
+        let renderer = self.get_renderer("Triangles")?.borrow();
+        let mesh_data = renderer
+            .get_asset_registry()
+            .get_mesh_data(mesh_data_id)
+            .ok_or_else(|| {
+                W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Mesh data could not be found. Has it been registered yet?",
+                    mesh_data_id,
+                )
+            })?;
+        let mesh_data = mesh_data.borrow();
+        Ok(read(get_cpu_data(&mesh_data)?))
+    }
+
+    /// Returns the renderer, or an error mentioning `usage` if the scene is not initialized.
+    fn get_renderer(&self, usage: &str) -> Result<&Rc<RefCell<Renderer>>, W3DError> {
+        self.main_renderer.as_ref().ok_or_else(|| {
+            W3DError::new(
+                W3DErrorKind::Uninitialized,
+                &format!("{} can't be read before initializing the scene.", usage),
+            )
+        })
+    }
+
+    /// Returns the world space ray going from the active camera through a point of the
+    /// screen in normalized device coordinates, with a unit direction.
+    fn get_picking_ray(&self, x: f32, y: f32) -> Result<Ray, W3DError> {
+        let (cameras, active_camera): (ReadStorage<Camera>, Read<ActiveCamera>) =
+            self.world.system_data();
+        let camera = active_camera
+            .entity
+            .and_then(|entity| cameras.get(entity))
+            .ok_or_else(|| {
+                W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Picking needs an active camera: initialize the scene first.",
+                )
+            })?;
+        let inverse = camera.get_vp_matrix().try_inverse().ok_or_else(|| {
+            W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "The camera's projection can't be inverted.",
+            )
+        })?;
+        let near = inverse.transform_point(&Point3::new(x, y, -1.0)).coords;
+        let far = inverse.transform_point(&Point3::new(x, y, 1.0)).coords;
+        Ok(Ray::new(near, (far - near).normalize()))
+    }
+
+    /// Casts a ray with a unit direction against the enabled meshes, except `excluded` and
+    /// the `Unpickable` ones.
+    /// Returns the distance to the closest surface hit and its world normal, facing the ray.
+    /// Meshes are hit through their triangles if their CPU data is retained, through the
+    /// world box containing their bounds otherwise.
+    fn cast_surface_ray(
+        &self,
+        ray: &Ray,
+        excluded: &[Entity],
+    ) -> Result<Option<(f32, Vector3<f32>)>, W3DError> {
+        let (meshes, transforms, bounds, enableds, effectively_disabled, unpickables, entities): (
+            ReadStorage<Mesh>,
+            ReadStorage<Transform>,
+            ReadStorage<Bounds>,
+            ReadStorage<Enabled>,
+            ReadStorage<EffectivelyDisabled>,
+            ReadStorage<Unpickable>,
+            Entities,
+        ) = self.world.system_data();
+        let renderer = self.get_renderer("Surface snapping")?.borrow();
+        let mut closest: Option<(f32, Vector3<f32>)> = None;
+        let active = (&enableds, !&effectively_disabled, !&unpickables);
+        let candidates = (&entities, &meshes, &transforms, bounds.maybe(), active);
+        for (entity, mesh, transform, entity_bounds, _) in candidates.join() {
+            if excluded.contains(&entity) {
+                continue;
+            }
+            let mesh_data = match renderer
+                .get_asset_registry()
+                .get_mesh_data_with_index(*mesh.get_mesh_data_id())
+            {
+                Some(mesh_data) => mesh_data,
+                None => continue,
+            };
+            let world_matrix = transform.get_world_matrix();
+            let local_bounds = match entity_bounds {
+                Some(entity_bounds) => Some(entity_bounds.sphere),
+                None => mesh_data.borrow().get_bounds(),
+            };
+            let world_box = match local_bounds {
+                Some(sphere) => Aabb::from_sphere(&sphere.transformed(&world_matrix)),
+                None => continue,
+            };
+            let box_distance = match ray.intersect_aabb(&world_box) {
+                Some(distance) => distance,
+                None => continue,
+            };
+            if closest.map_or(false, |(closest, _)| box_distance >= closest) {
+                continue;
+            }
+            let mut mesh_data = mesh_data.borrow_mut();
+            let hit = if mesh_data.get_cpu_data().is_some() {
+                match intersect_mesh_triangles(&mut mesh_data, ray, &world_matrix)? {
+                    Some((face_index, hit)) => {
+                        let triangle =
+                            get_cpu_data(&mesh_data)?
+                                .triangles()
+                                .nth(face_index)
+                                .map(|triangle| {
+                                    let corner = |index: usize| {
+                                        world_matrix
+                                            .transform_point(&Point3::from(triangle[index]))
+                                            .coords
+                                    };
+                                    (corner(1) - corner(0)).cross(&(corner(2) - corner(0)))
+                                });
+                        match triangle.and_then(|normal| normal.try_normalize(std::f32::EPSILON)) {
+                            Some(normal) => (hit.distance, normal),
+                            None => continue,
+                        }
+                    }
+                    None => continue,
+                }
+            } else if box_distance > 0. {
+                (box_distance, get_entry_normal(ray, &world_box))
+            } else {
+                continue;
+            };
+            let (distance, normal) = hit;
+            let normal = if normal.dot(&ray.direction) > 0. {
+                -normal
+            } else {
+                normal
+            };
+            if closest.map_or(true, |(closest, _)| distance < closest) {
+                closest = Some((distance, normal));
+            }
+        }
+        Ok(closest)
+    }
+
+    /// Returns how far the meshes of `tree` extend from `position` along the unit
+    /// `direction`, once their world matrices are transformed by `transform`, or `None` if
+    /// none of them has bounds. Measured on the triangles of meshes whose CPU data is
+    /// retained, on the bounding sphere of the others.
+    fn get_extent_along(
+        &self,
+        tree: &[Entity],
+        transform: &Matrix4<f32>,
+        position: &Vector3<f32>,
+        direction: &Vector3<f32>,
+    ) -> Result<Option<f32>, W3DError> {
+        let (meshes, transforms, bounds): (
+            ReadStorage<Mesh>,
+            ReadStorage<Transform>,
+            ReadStorage<Bounds>,
+        ) = self.world.system_data();
+        let renderer = self.get_renderer("Surface snapping")?.borrow();
+        let mut extent: Option<f32> = None;
+        for entity in tree {
+            let (mesh, entity_transform) = match (meshes.get(*entity), transforms.get(*entity)) {
+                (Some(mesh), Some(entity_transform)) => (mesh, entity_transform),
+                _ => continue,
+            };
+            let mesh_data = match renderer
+                .get_asset_registry()
+                .get_mesh_data_with_index(*mesh.get_mesh_data_id())
+            {
+                Some(mesh_data) => mesh_data,
+                None => continue,
+            };
+            let mesh_data = mesh_data.borrow();
+            let matrix = transform * entity_transform.get_world_matrix();
+            let entity_extent = match (mesh_data.get_cpu_data(), bounds.get(*entity)) {
+                (Some(cpu_data), None) => cpu_data
+                    .triangles()
+                    .flat_map(|triangle| triangle.to_vec())
+                    .map(|vertex| {
+                        (matrix.transform_point(&Point3::from(vertex)).coords - position)
+                            .dot(direction)
+                    })
+                    .fold(None, |extent: Option<f32>, distance| {
+                        Some(extent.map_or(distance, |extent| extent.max(distance)))
+                    }),
+                (_, entity_bounds) => entity_bounds
+                    .map(|entity_bounds| entity_bounds.sphere)
+                    .or_else(|| mesh_data.get_bounds())
+                    .map(|sphere| {
+                        let sphere = sphere.transformed(&matrix);
+                        (sphere.center - position).dot(direction) + sphere.radius
+                    }),
+            };
+            if let Some(entity_extent) = entity_extent {
+                extent = Some(extent.map_or(entity_extent, |extent| extent.max(entity_extent)));
+            }
+        }
+        Ok(extent)
+    }
+
+    /// Registers an asset under `id`, or the id stored in the file if `None`, replacing the
+    /// asset already registered under it if `replace` is `true`.
+    fn register_asset_under(
+        &mut self,
+        file_data: &[u8],
+        file_type: FileType,
+        id: Option<String>,
+        replace: bool,
+    ) -> String {
+        match &mut self.main_renderer {
+            None => {
+                log_error!("Trying to register asset before initializing renderer!");
+                String::new()
+            }
+            Some(renderer) => match renderer
+                .borrow_mut()
+                .register_asset(file_data, file_type, id, replace)
+            {
+                Err(error) => {
+                    log_error!("{}", error);
+                    String::new()
+                }
+                Ok(id) => id,
+            },
+        }
+    }
+
+    /// Sets the `u_uv_transform` uniform of a material instance from an atlas region.
+    fn set_instance_atlas_region(
+        &mut self,
+        material_instance_id: &str,
+        region: AtlasRegion,
+    ) -> Result<(), JsValue> {
+        self.with_material_instance(material_instance_id, |material_instance| {
+            material_instance.set_uniform(Uniform::new(
+                UV_TRANSFORM_NAME,
+                Box::new(region.to_uv_transform()),
+            ))
+        })
+    }
+
+    /// Applies `apply` to a registered `MaterialInstance`.
+    fn with_material_instance<F>(
+        &mut self,
+        material_instance_id: &str,
+        apply: F,
+    ) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut MaterialInstance),
+    {
+        match &self.main_renderer {
+            Some(renderer) => match renderer
+                .borrow()
+                .get_asset_registry()
+                .get_material_instance(material_instance_id)
+            {
+                Some(material_instance) => {
+                    apply(&mut material_instance.borrow_mut());
+                    Ok(())
+                }
+                None => Err(W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Material instance could not be found. Has it been registered yet?",
+                    material_instance_id,
+                )
+                .into()),
+            },
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Materials can't be modified before initializing the scene.",
+            )
+            .into()),
+        }
+    }
+
+    /// Returns a registered cube texture.
+    fn get_cube_texture(&self, cube_texture_id: &str) -> Result<Rc<CubeTexture>, W3DError> {
+        match &self.main_renderer {
+            Some(renderer) => renderer
+                .borrow()
+                .get_asset_registry()
+                .get_cube_texture(cube_texture_id)
+                .ok_or_else(|| {
+                    W3DError::with_source(
+                        W3DErrorKind::MissingAsset,
+                        "Cube texture could not be found. Has it been registered yet?",
+                        cube_texture_id,
+                    )
+                }),
+            None => Err(W3DError::new(
+                W3DErrorKind::Uninitialized,
+                "Environment maps can't be set before initializing the scene.",
+            )),
+        }
+    }
+
+    /// Applies `apply` to the `Overlay` of an entity.
+    fn with_overlay<F>(&mut self, entity_id: u32, apply: F) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut Overlay),
+    {
+        let (mut overlays, entities): (WriteStorage<Overlay>, Entities) = self.world.system_data();
+        match overlays.get_mut(entities.entity(entity_id)) {
+            Some(overlay) => {
+                apply(overlay);
+                Ok(())
+            }
+            None => Err(missing_component_error("Overlay", entity_id).into()),
+        }
+    }
+
+    /// Applies `apply` to the `Light` of an entity.
+    fn with_light<F>(&mut self, entity_id: u32, apply: F) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut Light),
+    {
+        let (mut lights, entities): (WriteStorage<Light>, Entities) = self.world.system_data();
+        match lights.get_mut(entities.entity(entity_id)) {
+            Some(light) => {
+                apply(light);
+                Ok(())
+            }
+            None => Err(missing_component_error("Light", entity_id).into()),
+        }
+    }
+
+    /// Applies `apply` to the `Velocity` of an entity.
+    fn with_velocity<F>(&mut self, entity_id: u32, apply: F) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut Velocity),
+    {
+        let (mut velocities, entities): (WriteStorage<Velocity>, Entities) =
+            self.world.system_data();
+        match velocities.get_mut(entities.entity(entity_id)) {
+            Some(velocity) => {
+                apply(velocity);
+                Ok(())
+            }
+            None => Err(missing_component_error("Velocity", entity_id).into()),
+        }
+    }
+
+    /// Gives an entity a `PathFollower` along a spline through `points`, for `set_path` and
+    /// `set_bezier_path`.
+    fn set_spline_path(
+        &mut self,
+        entity_id: u32,
+        kind: SplineKind,
+        points: &[f32],
+        speed: f32,
+        looped: bool,
+        orient: bool,
+    ) -> Result<(), JsValue> {
+        if !(speed >= 0.) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "The path speed can't be negative.",
+                &speed.to_string(),
+            )
+            .into());
+        }
+        if points.len() % 3 != 0 {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "Path points must be given as x, y, z triplets.",
+                &points.len().to_string(),
+            )
+            .into());
+        }
+        let points = points
+            .chunks(3)
+            .map(|point| Vector3::new(point[0], point[1], point[2]))
+            .collect();
+        let spline = Spline::new(kind, points, looped)?;
+        let (transforms, mut followers, entities): (
+            ReadStorage<Transform>,
+            WriteStorage<PathFollower>,
+            Entities,
+        ) = self.world.system_data();
+        let entity = entities.entity(entity_id);
+        if !entities.is_alive(entity) || !transforms.contains(entity) {
+            return Err(missing_component_error("Transform", entity_id).into());
+        }
+        let loop_mode = if looped {
+            PathLoop::Repeat
+        } else {
+            PathLoop::Once
+        };
+        followers
+            .insert(entity, PathFollower::new(spline, speed, loop_mode, orient))
+            .ok();
+        Ok(())
+    }
+
+    /// Applies `apply` to the `PathFollower` of an entity.
+    fn with_path_follower<F>(&mut self, entity_id: u32, apply: F) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut PathFollower),
+    {
+        let (mut followers, entities): (WriteStorage<PathFollower>, Entities) =
+            self.world.system_data();
+        match followers.get_mut(entities.entity(entity_id)) {
+            Some(follower) => {
+                apply(follower);
+                Ok(())
+            }
+            None => Err(missing_component_error("PathFollower", entity_id).into()),
+        }
+    }
+
+    /// Applies `apply` to the `ParticleEmitter` of an entity.
+    fn with_particle_emitter<F>(&mut self, entity_id: u32, apply: F) -> Result<(), JsValue>
+    where
+        F: FnOnce(&mut ParticleEmitter),
+    {
+        let (mut emitters, entities): (WriteStorage<ParticleEmitter>, Entities) =
+            self.world.system_data();
+        match emitters.get_mut(entities.entity(entity_id)) {
+            Some(emitter) => {
+                apply(emitter);
+                Ok(())
+            }
+            None => Err(missing_component_error("ParticleEmitter", entity_id).into()),
+        }
+    }
+
+    /// Registers every common component for the current world.
+    fn register_components(&mut self) -> () {
+        self.world.register::<Transform>();
+        self.world.register::<TransformParent>();
+        self.world.register::<Camera>();
+        self.world.register::<CameraShake>();
+        self.world.register::<Mesh>();
+        self.world.register::<DirtyTransform>();
+        self.world.register::<Enabled>();
+        self.world.register::<EffectivelyDisabled>();
+        self.world.register::<Light>();
+        self.world.register::<Direction>();
+        self.world.register::<Cone>();
+        self.world.register::<Hemisphere>();
+        self.world.register::<Follow>();
+        self.world.register::<LookAtTarget>();
+        self.world.register::<ParticleEmitter>();
+        self.world.register::<Sprite>();
+        self.world.register::<Overlay>();
+        self.world.register::<Lod>();
+        self.world.register::<Bounds>();
+        self.world.register::<AlwaysVisible>();
+        self.world.register::<Selected>();
+        self.world.register::<Unpickable>();
+        self.world.register::<BlobShadow>();
+        self.world.register::<UniformOverrides>();
+        self.world.register::<Opacity>();
+        self.world.register::<Velocity>();
+        self.world.register::<PathFollower>();
+    }
+
+    /// Returns an entity followed by all its descendants.
+    fn get_entity_tree(world: &World, entity: Entity) -> Vec<Entity> {
+        let mut tree = vec![entity];
+        tree.extend(
+            world
+                .read_resource::<Hierarchy<TransformParent>>()
+                .all_children_iter(entity),
+        );
+        tree
+    }
+
+    /// Starts building an entity, counting it as a structural change of the world.
+    fn build_entity(world: &mut World) -> EntityBuilder<'_> {
+        world.write_resource::<StructuralChanges>().record(1);
+        world.create_entity()
+    }
+
+    /// Counts an entity as a user of the assets of its `Mesh`, if it has one. See
+    /// `AssetUsage`.
+    fn count_asset_users(world: &World, entity: Entity) -> () {
+        if let Some(mesh) = world.read_storage::<Mesh>().get(entity) {
+            world.write_resource::<AssetUsage>().add_mesh(entity, mesh);
+        }
+    }
+
+    /// Deletes entities, and stops watching them.
+    fn delete_entities(world: &mut World, entities: &[Entity]) -> Result<(), W3DError> {
+        {
+            world
+                .write_resource::<StructuralChanges>()
+                .record(entities.len());
+            let mut asset_usage = world.write_resource::<AssetUsage>();
+            for entity in entities {
+                asset_usage.remove_entity(*entity);
+            }
+            let mut transform_watch = world.write_resource::<TransformWatch>();
+            let mut visibility = world.write_resource::<Visibility>();
+            for entity in entities {
+                transform_watch.set_watched(entity.id(), false);
+                visibility.remove(entity.id());
+            }
+        }
+        world.delete_entities(entities).map_err(|error| {
+            W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "Could not remove the entity.",
+                &error.to_string(),
+            )
+        })
+    }
+
+    /// Instanciates and registers the resources for the current world.
+    fn register_resources(&mut self) -> () {
+        let light_repo: LightRepository = Default::default();
+        let light_config: LightConfiguration = Default::default();
+        self.world.insert(light_repo);
+        self.world.insert(light_config);
+        self.world.insert(Time::default());
+        self.world.insert(ActiveCamera::default());
+        self.world.insert(ViewportInfo::default());
+        self.world.insert(Visibility::default());
+        self.world.insert(StructuralChanges::default());
+        self.world.insert(AssetUsage::default());
+        self.world.insert(DrawnEntities::default());
+        self.world.insert(TransformWatch::default());
+        self.world.insert(FloatingOrigin::default());
+        #[cfg(feature = "editor")]
+        self.world.insert(Manipulator::default());
+    }
+
+    /// Records in the `ViewportInfo` whether the drawing buffer has been resized this frame,
+    /// given the aspect ratio returned by `Renderer::resize_canvas`, and its new `size`.
+    fn update_viewport(world: &World, resized: Option<f32>, size: (u32, u32)) -> () {
+        let mut viewport = world.write_resource::<ViewportInfo>();
+        viewport.resized = resized.is_some();
+        if let Some(aspect_ratio) = resized {
+            viewport.width = size.0;
+            viewport.height = size.1;
+            viewport.aspect_ratio = aspect_ratio;
+        }
+    }
+
+    /// Moves the scene back to the origin if the floating origin is enabled and the active
+    /// camera is beyond its threshold. Returns the shift applied, if any.
+    fn shift_origin_if_needed(world: &World) -> Option<Vector3<f32>> {
+        let (
+            mut origin,
+            mut watch,
+            active_camera,
+            entities,
+            mut transforms,
+            parents,
+            mut cameras,
+            mut emitters,
+        ): (
+            Write<FloatingOrigin>,
+            Write<TransformWatch>,
+            Read<ActiveCamera>,
+            Entities,
+            WriteStorage<Transform>,
+            ReadStorage<TransformParent>,
+            WriteStorage<Camera>,
+            WriteStorage<ParticleEmitter>,
+        ) = world.system_data();
+        let eye_position = cameras.get(active_camera.entity?)?.get_eye_position();
+        let shift = origin.get_shift(&eye_position)?;
+        for (entity, transform) in (&entities, &mut transforms).join() {
+            transform.shift_origin(&shift, !parents.contains(entity));
+            watch.set_changed(entity.id());
+        }
+        for camera in (&mut cameras).join() {
+            camera.shift_origin(&shift);
+        }
+        for emitter in (&mut emitters).join() {
+            emitter.shift_origin(&shift);
+        }
+        origin.add_shift(&shift);
+        Some(shift)
     }
 
     /// Gets a camera from the system storage and clones it to pass it to the renderer.  
     /// This might fail if an incorrect ID is given.
-    fn get_camera_for_rendering(&self, camera_entity_id: u32) -> Result<Camera, String> {
+    fn get_camera_for_rendering(&self, camera_entity_id: u32) -> Result<Camera, W3DError> {
         let system_data: (ReadStorage<Camera>, Entities) = self.world.system_data();
         let entity = system_data.1.entity(camera_entity_id);
+        if !system_data.1.is_alive(entity) {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The camera entity does not exist. Was it created before calling initialize?",
+                &camera_entity_id.to_string(),
+            ));
+        }
         if let Some(camera) = system_data.0.get(entity) {
             Ok(camera.clone())
         } else {
-            Err(String::from("Could not find the requested Camera."))
+            Err(W3DError::with_source(
+                W3DErrorKind::InvalidEntity,
+                "The given entity has no Camera component.",
+                &camera_entity_id.to_string(),
+            ))
+        }
+    }
+}
+
+/// Returns the retained CPU data of a mesh data, or an error if it wasn't retained.
+fn get_cpu_data(mesh_data: &MeshData) -> Result<&MeshCpuData, W3DError> {
+    mesh_data.get_cpu_data().ok_or_else(|| {
+        W3DError::with_source(
+            W3DErrorKind::MissingAsset,
+            "Mesh data was registered without retaining its CPU data. \
+             Call set_retain_mesh_cpu_data(true) before registering it.",
+            mesh_data.get_id(),
+        )
+    })
+}
+
+/// Returns the floats of `transform_triangles` as a `Float32Array`.
+fn triangles_to_array<I>(triangles: I, matrix: &Matrix4<f32>) -> Float32Array
+where
+    I: Iterator<Item = [Vector3<f32>; 3]>,
+{
+    Float32Array::from(transform_triangles(triangles, matrix).as_slice())
+}
+
+/// Flattens triangles to 9 floats each, after transforming them by `matrix`.
+fn transform_triangles<I>(triangles: I, matrix: &Matrix4<f32>) -> Vec<f32>
+where
+    I: Iterator<Item = [Vector3<f32>; 3]>,
+{
+    let mut data = Vec::new();
+    for triangle in triangles {
+        for vertex in &triangle {
+            let position = matrix.transform_point(&Point3::from(*vertex));
+            data.extend_from_slice(&[position.x, position.y, position.z]);
+        }
+    }
+    data
+}
+
+/// Intersects a world space ray with the triangles of a mesh data transformed by
+/// `world_matrix`, and returns the closest triangle hit. Distances are in world units as long
+/// as the ray's direction is a unit vector.  
+/// Fails if the mesh data was registered without retaining its CPU data.
+fn intersect_mesh_triangles(
+    mesh_data: &mut MeshData,
+    ray: &Ray,
+    world_matrix: &Matrix4<f32>,
+) -> Result<Option<(usize, TriangleHit)>, W3DError> {
+    let local_ray = match world_matrix.try_inverse() {
+        Some(inverse) => ray.transformed(&inverse),
+        None => return Ok(None),
+    };
+    if get_cpu_data(mesh_data)?.triangle_count() > PICKING_BVH_THRESHOLD {
+        return Ok(mesh_data
+            .get_or_build_bvh()
+            .and_then(|bvh| bvh.intersect(&local_ray)));
+    }
+    let hits =
+        get_cpu_data(mesh_data)?
+            .triangles()
+            .enumerate()
+            .filter_map(|(face_index, triangle)| {
+                local_ray
+                    .intersect_triangle(&triangle)
+                    .map(|hit| (face_index, hit))
+            });
+    Ok(hits.fold(
+        None,
+        |closest: Option<(usize, TriangleHit)>, (face_index, hit)| match closest {
+            Some((_, closest_hit)) if closest_hit.distance <= hit.distance => closest,
+            _ => Some((face_index, hit)),
+        },
+    ))
+}
+
+/// Returns the normal of the face of `aabb` through which `ray` enters it, the ray starting
+/// outside of it.
+fn get_entry_normal(ray: &Ray, aabb: &Aabb) -> Vector3<f32> {
+    let mut entry_axis = 0;
+    let mut latest_entry = std::f32::NEG_INFINITY;
+    for axis in 0..3 {
+        if ray.direction[axis].abs() < std::f32::EPSILON {
+            continue;
+        }
+        let face = if ray.direction[axis] > 0. {
+            aabb.min[axis]
+        } else {
+            aabb.max[axis]
+        };
+        let entry = (face - ray.origin[axis]) / ray.direction[axis];
+        if entry > latest_entry {
+            latest_entry = entry;
+            entry_axis = axis;
+        }
+    }
+    let mut normal = Vector3::zeros();
+    normal[entry_axis] = -ray.direction[entry_axis].signum();
+    normal
+}
+
+/// Converts a `nalgebra` vector to its transfer type.
+fn vector3_data(vector: &Vector3<f32>) -> Vector3Data {
+    Vector3Data::new(vector.x, vector.y, vector.z)
+}
+
+/// Builds the cone of a spot light from its inner and outer angles, in radians.
+fn spot_cone(inner_angle: f32, outer_angle: f32) -> Result<Cone, W3DError> {
+    if !(inner_angle >= 0.0 && inner_angle <= outer_angle && outer_angle < FRAC_PI_2) {
+        return Err(W3DError::with_source(
+            W3DErrorKind::InvalidArgument,
+            "Spot light angles must verify 0 <= inner <= outer < π / 2.",
+            &format!("inner {}, outer {}", inner_angle, outer_angle),
+        ));
+    }
+    Ok(Cone::from_angles(inner_angle, outer_angle))
+}
+
+/// Builds the error returned when an entity lacks a component required by a Scene method.
+fn missing_component_error(component_name: &str, entity_id: u32) -> W3DError {
+    W3DError::with_source(
+        W3DErrorKind::InvalidEntity,
+        &format!("Could not find {} for entity.", component_name),
+        &entity_id.to_string(),
+    )
+}
+
+/// Calls a function given by JS with `arguments` in a microtask, once the current `Scene`
+/// method has returned. Until then, wasm-bindgen rejects any call to the scene, so a function
+/// called directly from `update` couldn't create or remove entities.
+fn call_after_update(callback: &Function, arguments: Array, name: &'static str) -> () {
+    let callback = callback.clone();
+    call_in_microtask(move || {
+        if let Err(error) = callback.apply(&JsValue::NULL, &arguments) {
+            error_throttled!(5000, "The {} callback failed: {:?}", name, error);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::ManualSize;
+
+    fn create_camera(scene: &mut Scene) -> u32 {
+        scene.create_camera_entity(
+            1.,
+            1.,
+            0.1,
+            100.,
+            Vector3Data::new(0., 0., 5.),
+            Vector3Data::default(),
+        )
+    }
+
+    /// Runs the systems maintaining the disabled state and the world matrices, in the order
+    /// of `update`.
+    fn run_scene_graph(scene: &mut Scene) -> () {
+        scene.hierarchy_system.run_now(&scene.world);
+        scene.enabled_propagation_system.run_now(&scene.world);
+        scene.scene_graph_system.run_now(&scene.world);
+        scene.world.maintain();
+    }
+
+    fn world_x(scene: &Scene, entity_id: u32) -> f32 {
+        let world_matrix = scene.get_world_matrix(entity_id).ok().unwrap();
+        world_matrix.get(0, 3).unwrap()
+    }
+
+    #[test]
+    fn disabled_entity_moved_while_disabled_is_up_to_date_when_enabled() {
+        let mut scene = Scene::new();
+        let entity = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        run_scene_graph(&mut scene);
+
+        scene.set_enabled(entity, false).ok().unwrap();
+        scene.set_transform_translation(entity, Vector3Data::new(3., 0., 0.));
+        run_scene_graph(&mut scene);
+        assert!(!scene.is_enabled(entity));
+        let world_matrix = scene.get_world_matrix(entity).ok().unwrap();
+        assert_eq!(world_matrix.get(0, 3), Some(3.));
+
+        scene.set_enabled(entity, true).ok().unwrap();
+        run_scene_graph(&mut scene);
+        assert!(scene.is_enabled(entity));
+        let world_matrix = scene.get_world_matrix(entity).ok().unwrap();
+        assert_eq!(world_matrix.get(0, 3), Some(3.));
+    }
+
+    #[test]
+    fn world_triangles_are_transformed() {
+        let triangle = [Vector3::new(0., 0., 0.), Vector3::x(), Vector3::y()];
+        let matrix = Matrix4::new_translation(&Vector3::new(0., 0., 2.))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(2., 1., 1.));
+        let data = transform_triangles(vec![triangle].into_iter(), &matrix);
+        assert_eq!(data, vec![0., 0., 2., 2., 0., 2., 0., 1., 2.]);
+    }
+
+    fn camera_projection(scene: &Scene, entity_id: u32) -> Matrix4<f32> {
+        let (cameras, entities): (ReadStorage<Camera>, Entities) = scene.world.system_data();
+        let camera = cameras.get(entities.entity(entity_id)).unwrap();
+        camera.get_projection_matrix()
+    }
+
+    #[test]
+    fn viewport_resize_updates_unlocked_cameras() {
+        let mut scene = Scene::new();
+        let camera = create_camera(&mut scene);
+        let locked_camera = create_camera(&mut scene);
+        scene
+            .lock_camera_aspect_ratio(locked_camera, Some(1.))
+            .ok()
+            .unwrap();
+        let projection = camera_projection(&scene, camera);
+        {
+            let mut viewport = scene.world.write_resource::<ViewportInfo>();
+            viewport.width = 200;
+            viewport.height = 100;
+            viewport.aspect_ratio = 2.;
+            viewport.resized = true;
+        }
+        scene.camera_aspect_system.run_now(&scene.world);
+        assert_ne!(camera_projection(&scene, camera), projection);
+        assert_eq!(camera_projection(&scene, locked_camera), projection);
+
+        scene
+            .lock_camera_aspect_ratio(locked_camera, None)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            camera_projection(&scene, locked_camera),
+            camera_projection(&scene, camera)
+        );
+    }
+
+    /// Advances the time to `timestamp` and runs the logic systems for each step due, as
+    /// `update` does.
+    fn advance_to(scene: &mut Scene, timestamp: f64) -> () {
+        let steps = {
+            let mut time = scene.world.write_resource::<Time>();
+            time.advance(timestamp);
+            time.take_steps()
+        };
+        for _ in 0..steps {
+            scene.hierarchy_system.run_now(&scene.world);
+            scene.enabled_propagation_system.run_now(&scene.world);
+            scene.velocity_system.run_now(&scene.world);
+            scene.path_follower_system.run_now(&scene.world);
+            scene.constraint_system.run_now(&scene.world);
+            scene.scene_graph_system.run_now(&scene.world);
+        }
+    }
+
+    /// Advances the time to `timestamp` and returns the x translation the entity is then
+    /// rendered at.
+    fn step_to(scene: &mut Scene, timestamp: f64, entity_id: u32) -> f32 {
+        advance_to(scene, timestamp);
+        let interpolation = scene.world.read_resource::<Time>().get_interpolation();
+        let (transforms, entities): (ReadStorage<Transform>, Entities) = scene.world.system_data();
+        let transform = transforms.get(entities.entity(entity_id)).unwrap();
+        transform.get_rendered_world_matrix(interpolation)[(0, 3)]
+    }
+
+    #[test]
+    fn fixed_timestep_renders_constant_motion_at_sub_step_positions() {
+        let mut scene = Scene::new();
+        scene.set_fixed_timestep(Some(0.125)).ok().unwrap();
+        let entity = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene
+            .set_velocity(entity, Vector3Data::new(1., 0., 0.), Vector3Data::default())
+            .ok()
+            .unwrap();
+        assert_eq!(step_to(&mut scene, 0., entity), 0.);
+        // Rendering lags one step behind the simulation, and moves smoothly between steps.
+        for (timestamp, rendered_x) in &[
+            (125., 0.),
+            (187.5, 0.0625),
+            (250., 0.125),
+            (281.25, 0.15625),
+            (375., 0.25),
+        ] {
+            let x = step_to(&mut scene, *timestamp, entity);
+            assert!((x - rendered_x).abs() < 1e-5, "{} at {}ms", x, timestamp);
+        }
+    }
+
+    #[test]
+    fn pause_stops_paths_but_not_camera_controllers() {
+        let mut scene = Scene::new();
+        let tweened = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene
+            .set_path(tweened, &[0., 0., 0., 10., 0., 0.], 1., false, false)
+            .ok()
+            .unwrap();
+        let target = scene.create_particle_emitter(1, 0., Vector3Data::new(10., 0., 0.));
+        // Stands for a camera controller, following the target with smoothing.
+        let camera = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene
+            .set_follow(camera, target, Vector3Data::default(), 0.5)
+            .ok()
+            .unwrap();
+        run_scene_graph(&mut scene);
+        let camera_x = world_x(&scene, camera);
+
+        scene.set_paused(true);
+        advance_to(&mut scene, 0.);
+        advance_to(&mut scene, 500.);
+        assert_eq!(scene.get_path_progress(tweened).ok().unwrap(), 0.);
+        assert!(world_x(&scene, camera) > camera_x);
+
+        scene.set_paused(false);
+        advance_to(&mut scene, 1000.);
+        assert!(scene.get_path_progress(tweened).ok().unwrap() > 0.);
+    }
+
+    #[test]
+    fn hiding_the_canvas_leaves_cameras_as_if_never_hidden() {
+        let mut hidden_scene = Scene::new();
+        let mut scene = Scene::new();
+        let hidden_camera = create_camera(&mut hidden_scene);
+        let camera = create_camera(&mut scene);
+        // Frame start of `update`, with the size pushed to an external context's renderer.
+        let start_frame = |scene: &mut Scene, size: &mut ManualSize| {
+            let resized = size.update();
+            Scene::update_viewport(&scene.world, resized, size.get_size());
+            scene.camera_aspect_system.run_now(&scene.world);
+        };
+        let mut hidden_size = ManualSize::new(200, 100);
+        start_frame(&mut hidden_scene, &mut hidden_size);
+        hidden_size.set(0, 0);
+        for _ in 0..3 {
+            start_frame(&mut hidden_scene, &mut hidden_size);
+            assert!(hidden_size.is_empty());
+            assert!(!hidden_scene.world.read_resource::<ViewportInfo>().resized);
+        }
+        hidden_size.set(200, 100);
+        start_frame(&mut hidden_scene, &mut hidden_size);
+        start_frame(&mut scene, &mut ManualSize::new(200, 100));
+        let projection = camera_projection(&hidden_scene, hidden_camera);
+        assert!(projection.iter().all(|value| value.is_finite()));
+        assert_eq!(projection, camera_projection(&scene, camera));
+    }
+
+    #[test]
+    fn physical_light_units_are_converted_for_shaders() {
+        let mut scene = Scene::new();
+        let white = Vector3Data::new(1., 1., 1.);
+        scene.create_light_entity(LightType::Point, white, 1700., 0., Vector3Data::default());
+        scene.create_light_entity(LightType::Directional, white, 1000., 0., white);
+        let intensities = |scene: &mut Scene| {
+            scene.lighting_system.run_now(&scene.world);
+            let repository = scene.world.read_resource::<LightRepository>();
+            (
+                repository.point[0].0.intensity,
+                repository.directional[0].0.intensity,
+            )
+        };
+        assert_eq!(intensities(&mut scene), (1700., 1000.));
+        scene.set_light_units(LightUnits::Physical);
+        let (point, directional) = intensities(&mut scene);
+        assert!((point - 135.28).abs() < 0.01);
+        assert_eq!(directional, 1000., "Lux are used as is");
+    }
+
+    #[test]
+    fn initialize_rejects_missing_camera_entity() {
+        let scene = Scene::new();
+        let error = scene.get_camera_to_initialize(42).err().unwrap();
+        assert_eq!(error.get_kind(), W3DErrorKind::InvalidEntity);
+        assert_eq!(error.get_source(), Some("42"));
+    }
+
+    #[test]
+    fn initialize_rejects_entity_without_camera() {
+        let mut scene = Scene::new();
+        let light = scene.create_light_entity(
+            LightType::Ambiant,
+            Vector3Data::new(1., 1., 1.),
+            1.,
+            0.,
+            Vector3Data::default(),
+        );
+        let error = scene.get_camera_to_initialize(light).err().unwrap();
+        assert_eq!(
+            error.get_reason(),
+            "The given entity has no Camera component."
+        );
+    }
+
+    #[test]
+    fn initialize_can_be_retried_with_a_camera() {
+        let mut scene = Scene::new();
+        assert!(scene.get_camera_to_initialize(0).is_err());
+        let camera = create_camera(&mut scene);
+        assert!(scene
+            .get_camera_to_initialize(camera)
+            .ok()
+            .unwrap()
+            .is_some());
+        scene.remove_entity(camera).unwrap();
+        scene.world.maintain();
+        assert!(scene.get_camera_to_initialize(camera).is_err());
+        let camera = create_camera(&mut scene);
+        assert!(scene
+            .get_camera_to_initialize(camera)
+            .ok()
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn floating_origin_rebases_a_deep_hierarchy() {
+        let mut scene = Scene::new();
+        let camera = scene.create_camera_entity(
+            1.,
+            1.,
+            0.1,
+            100.,
+            Vector3Data::new(5000., 0., 0.),
+            Vector3Data::new(5000., 0., -1.),
+        );
+        scene.world.write_resource::<ActiveCamera>().entity =
+            Some(scene.world.entities().entity(camera));
+        let mut chain = vec![scene.create_particle_emitter(1, 0., Vector3Data::default())];
+        scene.set_transform_translation(chain[0], Vector3Data::new(5002., 3., 0.));
+        for _ in 0..4 {
+            let child = scene.create_particle_emitter(1, 0., Vector3Data::default());
+            scene.set_transform_translation(child, Vector3Data::new(1., 0., 0.));
+            scene.set_transform_rotation(child, Vector3Data::new(0., 0.5, 0.));
+            scene.set_parent(child, *chain.last().unwrap());
+            chain.push(child);
+        }
+        let unwatched = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene.watch_transform(chain[0], true).ok().unwrap();
+        scene.watch_transform(chain[4], true).ok().unwrap();
+
+        // The sandboxed hierarchy can't sort the chain, so world matrices are composed here.
+        let refresh = |scene: &Scene| -> Vec<Matrix4<f32>> {
+            let mut transforms = scene.world.write_storage::<Transform>();
+            let mut parent_matrix = None;
+            let mut matrices = Vec::new();
+            for id in &chain {
+                let transform = transforms
+                    .get_mut(scene.world.entities().entity(*id))
+                    .unwrap();
+                transform.refresh_world_matrix(parent_matrix);
+                parent_matrix = Some(transform.get_world_matrix());
+                matrices.push(transform.get_world_matrix());
+            }
+            matrices
+        };
+        let before = refresh(&scene);
+        assert!(Scene::shift_origin_if_needed(&scene.world).is_none());
+
+        scene.enable_floating_origin(Some(1000.));
+        let shift = Scene::shift_origin_if_needed(&scene.world).unwrap();
+        assert!((shift - Vector3::new(5000., 0., 0.)).amax() < 1.0e-3);
+        let shifted: Vec<Matrix4<f32>> = chain
+            .iter()
+            .map(|id| scene.get_world_matrix(*id).ok().unwrap().to_matrix4())
+            .collect();
+        let recomputed = refresh(&scene);
+        for ((before, shifted), recomputed) in before.iter().zip(&shifted).zip(&recomputed) {
+            let expected = Translation3::from(-shift).to_homogeneous() * before;
+            assert!((shifted - expected).amax() < 1.0e-3);
+            assert!(
+                (shifted - recomputed).amax() < 1.0e-3,
+                "Shifted matrices must match the ones composed from the shifted roots"
+            );
         }
+        let local_x = |scene: &Scene, id: u32| {
+            let transforms = scene.world.read_storage::<Transform>();
+            let entity = scene.world.entities().entity(id);
+            transforms.get(entity).unwrap().get_translation().x
+        };
+        assert!((local_x(&scene, chain[0]) - 2.).abs() < 1.0e-3);
+        assert_eq!(
+            local_x(&scene, chain[3]),
+            1.,
+            "Children keep their local translation"
+        );
+
+        let changed = scene
+            .world
+            .write_resource::<TransformWatch>()
+            .take_changed();
+        assert!(changed.contains(chain[0]) && changed.contains(chain[4]));
+        assert!(!changed.contains(chain[2]) && !changed.contains(unwatched));
+
+        let cameras = scene.world.read_storage::<Camera>();
+        let eye_position = cameras
+            .get(scene.world.entities().entity(camera))
+            .unwrap()
+            .get_eye_position();
+        assert!(eye_position.amax() < 1.0e-3);
+        drop(cameras);
+        assert!(Scene::shift_origin_if_needed(&scene.world).is_none());
+        assert_eq!(scene.get_origin_offset()[0], shift.x as f64);
+    }
+
+    /// Makes the calls a visibility callback would make once `update` returned. The deferral
+    /// itself, `call_after_update`, needs a JS runtime and isn't run by the native tests.
+    #[test]
+    fn reported_entities_can_be_removed_and_replaced_after_update() {
+        let mut scene = Scene::new();
+        let removed = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        let kept = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        run_scene_graph(&mut scene);
+        {
+            let mut visibility = scene.world.write_resource::<Visibility>();
+            for id in &[removed, kept] {
+                visibility.set_tested(*id);
+                visibility.set_visible(*id);
+            }
+        }
+        let (shown, hidden) = scene.world.read_resource::<Visibility>().get_changes();
+        assert_eq!((shown.len(), hidden.len()), (2, 0));
+
+        // What a visibility callback may do with the ids it's given, once update returned.
+        scene.remove_entity(removed).ok().unwrap();
+        let created = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene.set_transform_translation(created, Vector3Data::new(2., 0., 0.));
+        scene.set_parent(created, kept);
+        scene.watch_transform(created, true).ok().unwrap();
+        run_scene_graph(&mut scene);
+        assert_eq!(world_x(&scene, created), 2.);
+        assert!(!scene.was_visible_last_frame(created));
+
+        let mut visibility = scene.world.write_resource::<Visibility>();
+        visibility.clear();
+        visibility.set_tested(kept);
+        visibility.set_visible(kept);
+        assert_eq!(
+            visibility.get_changes(),
+            (Vec::new(), Vec::new()),
+            "The removed entity isn't reported as hidden"
+        );
+    }
+
+    /// Makes the calls an origin callback would make once `update` returned, without going
+    /// through `call_after_update` either.
+    #[test]
+    fn entities_can_be_moved_to_the_new_origin_after_a_shift() {
+        let mut scene = Scene::new();
+        let camera = scene.create_camera_entity(
+            1.,
+            1.,
+            0.1,
+            100.,
+            Vector3Data::new(0., 0., 3000.),
+            Vector3Data::default(),
+        );
+        scene.world.write_resource::<ActiveCamera>().entity =
+            Some(scene.world.entities().entity(camera));
+        scene.enable_floating_origin(Some(1000.));
+        let shift = Scene::shift_origin_if_needed(&scene.world).unwrap();
+
+        // An application placing an entity at a true world position, from the callback.
+        let offset = scene.get_origin_offset();
+        let entity = scene.create_particle_emitter(1, 0., Vector3Data::default());
+        scene.set_transform_translation(
+            entity,
+            Vector3Data::new(0., 0., (3010. - offset[2]) as f32),
+        );
+        run_scene_graph(&mut scene);
+        let world_matrix = scene.get_world_matrix(entity).ok().unwrap();
+        assert!((world_matrix.get(2, 3).unwrap() - 10.).abs() < 1.0e-3);
+        assert_eq!(offset[2], shift.z as f64);
     }
 }
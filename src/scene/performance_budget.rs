@@ -0,0 +1,271 @@
+//! Draw call and triangle budgets, checked after each update to find out when a scene gets
+//! too heavy for the devices it targets, and why.
+
+use super::{call_after_update, Scene};
+use crate::renderer::{describe_batch_stats, BatchStats, FrameStats, Renderer};
+use js_sys::{Array, Function, Object, Reflect};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Number of materials and of mesh data listed as top offenders in budget reports.
+const REPORTED_OFFENDERS: usize = 5;
+
+/// Draw calls from which a single batch is suggested for instancing.
+const INSTANCING_HINT_DRAW_CALLS: u32 = 8;
+
+/// Minimum time between two budget reports, in milliseconds.
+const REPORT_INTERVAL: f64 = 5000.;
+
+/// Budgets of a scene, and where exceeding them is reported.
+#[derive(Default)]
+pub(super) struct PerformanceBudget {
+    /// Most draw calls a frame may issue, `None` for no limit
+    max_draw_calls: Option<u32>,
+
+    /// Most triangles the main pass of a frame may draw, `None` for no limit
+    max_triangles: Option<u32>,
+
+    /// Time of the last report, in milliseconds
+    last_report: Option<f64>,
+
+    /// Called with the report instead of logging a warning, if set
+    callback: Option<Function>,
+}
+
+/// Draw calls and triangles of the main pass summed for an asset.
+struct Offender {
+    id: String,
+    draw_calls: u32,
+    triangles: u32,
+}
+
+#[wasm_bindgen]
+impl Scene {
+    /// Sets the most draw calls a frame may issue and the most triangles its main pass may
+    /// draw, `None` for no limit. After each rendered frame exceeding them, a report is given
+    /// to the function set with `on_budget_exceeded`, or logged as a warning, at most every
+    /// 5 seconds.
+    pub fn set_performance_budget(
+        &mut self,
+        max_draw_calls: Option<u32>,
+        max_triangles: Option<u32>,
+    ) -> () {
+        self.performance_budget.max_draw_calls = max_draw_calls;
+        self.performance_budget.max_triangles = max_triangles;
+        self.performance_budget.last_report = None;
+    }
+
+    /// Sets a function called with the report of frames exceeding the performance budget,
+    /// instead of logging a warning. `None` removes it. The report is an object with:
+    ///
+    /// - `drawCalls`, `triangles`, `maxDrawCalls` and `maxTriangles`, the limits being `null`
+    ///   if not set;
+    /// - `materials` and `meshData`: the assets with the most draw calls in the main pass, as
+    ///   `{ id, drawCalls, triangles }` objects;
+    /// - `instancingCandidates`: the `(material, mesh data)` batches drawn many times, as
+    ///   `{ material, meshData, drawCalls, triangles }` objects, which could be merged or
+    ///   instanced.
+    ///
+    /// The function is called once `update` has returned, so it can use the scene.
+    pub fn on_budget_exceeded(&mut self, callback: Option<Function>) -> () {
+        self.performance_budget.callback = callback;
+    }
+
+    /// Returns the main pass batches of the last frame, as `{ material, meshData, drawCalls,
+    /// triangles }` objects, most draw calls first. The draw calls of material passes are
+    /// included.
+    /// Fails if the scene is not initialized.
+    pub fn get_batch_stats(&self) -> Result<JsValue, JsValue> {
+        let renderer = self.get_renderer("Batch statistics")?.borrow();
+        Ok(describe_batch_stats(
+            renderer.get_asset_registry(),
+            renderer.get_batch_stats(),
+        ))
+    }
+}
+
+impl Scene {
+    /// Reports the last frame if it exceeded the performance budget, unless a report was
+    /// made less than `REPORT_INTERVAL` ago.
+    pub(super) fn check_performance_budget(&mut self) -> () {
+        let budget = &mut self.performance_budget;
+        if budget.max_draw_calls.is_none() && budget.max_triangles.is_none() {
+            return;
+        }
+        let renderer = match &self.main_renderer {
+            Some(renderer) => renderer.borrow(),
+            None => return,
+        };
+        let frame_stats = renderer.get_frame_stats();
+        let exceeds = |max: Option<u32>, value: u32| max.map_or(false, |max| value > max);
+        if !exceeds(budget.max_draw_calls, frame_stats.draw_calls)
+            && !exceeds(budget.max_triangles, frame_stats.triangles)
+        {
+            return;
+        }
+        let now = crate::utils::now();
+        if let Some(last_report) = budget.last_report {
+            if now - last_report < REPORT_INTERVAL {
+                return;
+            }
+        }
+        budget.last_report = Some(now);
+        match &budget.callback {
+            Some(callback) => {
+                let report = describe_budget_report(budget, &frame_stats, &renderer);
+                call_after_update(callback, Array::of1(&report), "budget");
+            }
+            None => log_warn!(
+                "{}",
+                summarize_budget_report(budget, &frame_stats, &renderer)
+            ),
+        }
+    }
+}
+
+/// Sums the main pass batches of the last frame by material and by mesh data, returning the
+/// top offenders of each, and lists the batches drawn at least `INSTANCING_HINT_DRAW_CALLS`
+/// times as `(material id, mesh data id, stats)`, most draw calls first.
+fn find_offenders(
+    renderer: &Renderer,
+) -> (
+    Vec<Offender>,
+    Vec<Offender>,
+    Vec<(String, String, BatchStats)>,
+) {
+    let asset_registry = renderer.get_asset_registry();
+    let mut materials: HashMap<String, BatchStats> = HashMap::new();
+    let mut mesh_data: HashMap<String, BatchStats> = HashMap::new();
+    let mut candidates = Vec::new();
+    for ((material_index, mesh_data_index), stats) in renderer.get_batch_stats() {
+        let material_id = asset_registry
+            .get_material_with_index(*material_index)
+            .map_or_else(String::new, |material| {
+                material.borrow().get_id().to_owned()
+            });
+        let mesh_data_id = asset_registry
+            .get_mesh_data_with_index(*mesh_data_index)
+            .map_or_else(String::new, |mesh_data| {
+                mesh_data.borrow().get_id().to_owned()
+            });
+        for (totals, id) in vec![
+            (&mut materials, &material_id),
+            (&mut mesh_data, &mesh_data_id),
+        ] {
+            let total = totals.entry(id.clone()).or_default();
+            total.draw_calls += stats.draw_calls;
+            total.triangles += stats.triangles;
+        }
+        if stats.draw_calls >= INSTANCING_HINT_DRAW_CALLS {
+            candidates.push((material_id, mesh_data_id, *stats));
+        }
+    }
+    candidates.sort_by(|(_, _, a), (_, _, b)| b.draw_calls.cmp(&a.draw_calls));
+    (rank(materials), rank(mesh_data), candidates)
+}
+
+/// Returns the `REPORTED_OFFENDERS` assets with the most draw calls, then triangles.
+fn rank(totals: HashMap<String, BatchStats>) -> Vec<Offender> {
+    let mut offenders: Vec<Offender> = totals
+        .into_iter()
+        .map(|(id, stats)| Offender {
+            id: id,
+            draw_calls: stats.draw_calls,
+            triangles: stats.triangles,
+        })
+        .collect();
+    offenders.sort_by(|a, b| (b.draw_calls, b.triangles).cmp(&(a.draw_calls, a.triangles)));
+    offenders.truncate(REPORTED_OFFENDERS);
+    offenders
+}
+
+/// Builds the JS report given to the `on_budget_exceeded` function.
+fn describe_budget_report(
+    budget: &PerformanceBudget,
+    frame_stats: &FrameStats,
+    renderer: &Renderer,
+) -> JsValue {
+    let (materials, mesh_data, candidates) = find_offenders(renderer);
+    let describe_offenders = |offenders: Vec<Offender>| -> JsValue {
+        let array = Array::new();
+        for offender in offenders {
+            let entry = Object::new();
+            set(&entry, "id", offender.id.into());
+            set(&entry, "drawCalls", offender.draw_calls.into());
+            set(&entry, "triangles", offender.triangles.into());
+            array.push(&entry);
+        }
+        array.into()
+    };
+    let limit = |max: Option<u32>| max.map_or(JsValue::NULL, JsValue::from);
+    let instancing_candidates = Array::new();
+    for (material_id, mesh_data_id, stats) in candidates {
+        let entry = Object::new();
+        set(&entry, "material", material_id.into());
+        set(&entry, "meshData", mesh_data_id.into());
+        set(&entry, "drawCalls", stats.draw_calls.into());
+        set(&entry, "triangles", stats.triangles.into());
+        instancing_candidates.push(&entry);
+    }
+    let report = Object::new();
+    set(&report, "drawCalls", frame_stats.draw_calls.into());
+    set(&report, "triangles", frame_stats.triangles.into());
+    set(&report, "maxDrawCalls", limit(budget.max_draw_calls));
+    set(&report, "maxTriangles", limit(budget.max_triangles));
+    set(&report, "materials", describe_offenders(materials));
+    set(&report, "meshData", describe_offenders(mesh_data));
+    set(
+        &report,
+        "instancingCandidates",
+        instancing_candidates.into(),
+    );
+    report.into()
+}
+
+/// Builds the warning logged when no `on_budget_exceeded` function is set.
+fn summarize_budget_report(
+    budget: &PerformanceBudget,
+    frame_stats: &FrameStats,
+    renderer: &Renderer,
+) -> String {
+    let (materials, mesh_data, candidates) = find_offenders(renderer);
+    let limit = |max: Option<u32>| max.map_or_else(|| "none".to_owned(), |max| max.to_string());
+    let list = |offenders: Vec<Offender>| -> String {
+        offenders
+            .iter()
+            .map(|offender| format!("{} ({})", offender.id, offender.draw_calls))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut summary = format!(
+        "The frame exceeds the performance budget: {} draw calls (max {}), {} triangles (max {}). \
+         Most draw calls by material: {}. By mesh data: {}.",
+        frame_stats.draw_calls,
+        limit(budget.max_draw_calls),
+        frame_stats.triangles,
+        limit(budget.max_triangles),
+        list(materials),
+        list(mesh_data),
+    );
+    if !candidates.is_empty() {
+        let hints: Vec<String> = candidates
+            .iter()
+            .take(REPORTED_OFFENDERS)
+            .map(|(material_id, mesh_data_id, stats)| {
+                format!(
+                    "{} with {} ({})",
+                    mesh_data_id, material_id, stats.draw_calls
+                )
+            })
+            .collect();
+        summary.push_str(&format!(
+            " Drawn many times, could be instanced: {}.",
+            hints.join(", ")
+        ));
+    }
+    summary
+}
+
+fn set(object: &Object, key: &str, value: JsValue) -> () {
+    Reflect::set(object, &key.into(), &value).ok();
+}
@@ -0,0 +1,307 @@
+//! Prefabs: trees of entities described once from JS, with their assets resolved at
+//! registration, and instantiated in a single pass.
+
+use super::Scene;
+use crate::component::*;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::renderer::Renderer;
+use crate::utils::parse_hex_color;
+use js_sys::{Array, Reflect};
+use nalgebra::Vector3;
+use specs::{Builder, Entity, World};
+use wasm_bindgen::JsValue;
+
+/// Light held by a prefab node.
+enum PrefabLight {
+    Ambient(Light),
+    Directional(Light, Direction),
+    Point(Light),
+}
+
+/// A node of a prefab, with its assets resolved.
+pub(super) struct PrefabNode {
+    /// Local translation, rotation (Euler angles) and scale
+    transform: [Vector3<f32>; 3],
+
+    /// Mesh drawn by the node, if any
+    mesh: Option<Mesh>,
+
+    /// Sprite drawn by the node, if any
+    sprite: Option<Sprite>,
+
+    /// Light emitted by the node, if any
+    light: Option<PrefabLight>,
+
+    /// Child nodes, parented to this one
+    children: Vec<PrefabNode>,
+}
+
+/// Parses a prefab description and resolves its assets, returning the prefab's id and its
+/// root node.
+///
+/// The description is a node with an `id` string. Nodes are objects whose fields are all
+/// optional:
+///
+/// - `translation`, `rotation` (Euler angles) and `scale`: `[x, y, z]` arrays;
+/// - `mesh`: `{ meshData, materialInstance }` asset ids;
+/// - `sprite`: `{ texture, width, height }`;
+/// - `light`: `{ type, color, intensity, range, direction }`, `type` being `"ambient"`,
+///   `"directional"` or `"point"`, `color` a `#rrggbb` string and `range` the distance at
+///   which a point light fades out, `0` or absent for no falloff. Nodes holding an ambient
+///   light have no transform;
+/// - `children`: an array of nodes.
+///
+/// Missing meshes and material instances are replaced by fallbacks, unless strict assets
+/// are enabled.
+pub(super) fn parse_prefab(
+    definition: &JsValue,
+    renderer: &mut Renderer,
+) -> Result<(String, PrefabNode), W3DError> {
+    let id = get(definition, "id")
+        .as_string()
+        .ok_or_else(|| invalid_definition("A prefab needs an `id` string.", "id"))?;
+    let root = parse_node(definition, renderer, &id)?;
+    Ok((id, root))
+}
+
+/// Adds the indexes of the assets used by `node` and its children to `indexes`: those of
+/// their meshes and the textures of their sprites.
+pub(super) fn collect_asset_indexes(node: &PrefabNode, indexes: &mut Vec<usize>) -> () {
+    if let Some(mesh) = &node.mesh {
+        indexes.push(*mesh.get_mesh_data_id());
+        for (material_instance_id, material_id) in mesh.get_sub_mesh_materials() {
+            indexes.push(*material_instance_id);
+            indexes.push(*material_id);
+        }
+    }
+    if let Some(sprite) = &node.sprite {
+        indexes.push(*sprite.get_texture_id());
+    }
+    for child in &node.children {
+        collect_asset_indexes(child, indexes);
+    }
+}
+
+/// Creates the entities of a prefab, the root being placed at `position`. Returns the root.
+pub(super) fn instantiate(world: &mut World, root: &PrefabNode, position: &Vector3<f32>) -> Entity {
+    let entity = build_entity(world, root, root.transform[0] + position, None);
+    instantiate_children(world, root, entity);
+    entity
+}
+
+/// Creates the entities of the children of `node`, recursively, parented to `parent`.
+fn instantiate_children(world: &mut World, node: &PrefabNode, parent: Entity) -> () {
+    for child in &node.children {
+        let entity = build_entity(world, child, child.transform[0], Some(parent));
+        instantiate_children(world, child, entity);
+    }
+}
+
+/// Creates the entity of a single node.
+fn build_entity(
+    world: &mut World,
+    node: &PrefabNode,
+    translation: Vector3<f32>,
+    parent: Option<Entity>,
+) -> Entity {
+    let mut builder = Scene::build_entity(world).with(Enabled);
+    // Ambient lights are told apart from point lights by their lack of transform.
+    let is_ambient = match &node.light {
+        Some(PrefabLight::Ambient(_)) => true,
+        _ => false,
+    };
+    if !is_ambient {
+        builder = builder
+            .with(Transform::new(
+                &translation,
+                &node.transform[1],
+                &node.transform[2],
+            ))
+            .with(DirtyTransform);
+    }
+    if let Some(parent) = parent {
+        builder = builder.with(TransformParent::new(parent));
+    }
+    if let Some(mesh) = &node.mesh {
+        builder = builder.with(mesh.clone());
+    }
+    if let Some(sprite) = &node.sprite {
+        builder = builder.with(sprite.clone());
+    }
+    match &node.light {
+        Some(PrefabLight::Ambient(light)) | Some(PrefabLight::Point(light)) => {
+            builder = builder.with(light.clone());
+        }
+        Some(PrefabLight::Directional(light, direction)) => {
+            builder = builder.with(light.clone()).with(direction.clone());
+        }
+        None => {}
+    }
+    let entity = builder.build();
+    Scene::count_asset_users(world, entity);
+    entity
+}
+
+/// Parses a node and its children.
+fn parse_node(
+    definition: &JsValue,
+    renderer: &mut Renderer,
+    prefab_id: &str,
+) -> Result<PrefabNode, W3DError> {
+    let transform = [
+        get_vector3(definition, "translation", Vector3::new(0.0, 0.0, 0.0))?,
+        get_vector3(definition, "rotation", Vector3::new(0.0, 0.0, 0.0))?,
+        get_vector3(definition, "scale", Vector3::new(1.0, 1.0, 1.0))?,
+    ];
+    let referenced_by = format!("prefab {}", prefab_id);
+    let mesh = match get_object(definition, "mesh") {
+        Some(mesh) => {
+            let mesh_data_id = get_string(&mesh, "meshData")?;
+            let material_instance_id = get_string(&mesh, "materialInstance")?;
+            let mesh_data_index = renderer.resolve_mesh_data(&mesh_data_id, &referenced_by)?;
+            let (instance_index, material_index) =
+                renderer.resolve_material_instance(&material_instance_id, &referenced_by)?;
+            Some(Mesh::new(mesh_data_index, instance_index, material_index))
+        }
+        None => None,
+    };
+    let sprite = match get_object(definition, "sprite") {
+        Some(sprite) => {
+            let texture_id = get_string(&sprite, "texture")?;
+            let asset_registry = renderer.get_asset_registry();
+            if asset_registry.get_texture(&texture_id).is_none() {
+                return Err(W3DError::with_source(
+                    W3DErrorKind::MissingAsset,
+                    "Texture could not be found. Has it been registered yet?",
+                    &texture_id,
+                ));
+            }
+            Some(Sprite::new(
+                asset_registry.get_id_from_str(&texture_id).unwrap(),
+                get_f32(&sprite, "width", 1.0)?,
+                get_f32(&sprite, "height", 1.0)?,
+            ))
+        }
+        None => None,
+    };
+    let light = match get_object(definition, "light") {
+        Some(light) => Some(parse_light(&light)?),
+        None => None,
+    };
+    let mut children = Vec::new();
+    if let Some(child_definitions) = get_object(definition, "children") {
+        if !Array::is_array(&child_definitions) {
+            return Err(invalid_definition(
+                "Prefab `children` must be an array.",
+                prefab_id,
+            ));
+        }
+        for child in Array::from(&child_definitions).iter() {
+            children.push(parse_node(&child, renderer, prefab_id)?);
+        }
+    }
+    Ok(PrefabNode {
+        transform: transform,
+        mesh: mesh,
+        sprite: sprite,
+        light: light,
+        children: children,
+    })
+}
+
+/// Parses the light of a node.
+fn parse_light(definition: &JsValue) -> Result<PrefabLight, W3DError> {
+    let color = match get(definition, "color").as_string() {
+        Some(hex) => parse_hex_color(&hex)?,
+        None => Vector3::new(1.0, 1.0, 1.0),
+    };
+    let range = get_f32(definition, "range", 0.0)?;
+    let light = Light {
+        color: color,
+        intensity: get_f32(definition, "intensity", 1.0)?,
+        attenuation: if range > 0.0 {
+            Attenuation::Range(range)
+        } else {
+            Attenuation::None
+        },
+    };
+    let light_type = get_string(definition, "type")?;
+    match light_type.as_str() {
+        "ambient" => Ok(PrefabLight::Ambient(light)),
+        "directional" => {
+            let direction = get_vector3(definition, "direction", Vector3::new(0.0, -1.0, 0.0))?;
+            Ok(PrefabLight::Directional(light, Direction(direction)))
+        }
+        "point" => Ok(PrefabLight::Point(light)),
+        _ => Err(invalid_definition(
+            "Unsupported prefab light type.",
+            &light_type,
+        )),
+    }
+}
+
+/// Returns the field `key` of `object`, `undefined` if it is missing.
+fn get(object: &JsValue, key: &str) -> JsValue {
+    Reflect::get(object, &key.into()).unwrap_or(JsValue::UNDEFINED)
+}
+
+/// Returns the field `key` of `object`, or `None` if it is `undefined` or `null`.
+fn get_object(object: &JsValue, key: &str) -> Option<JsValue> {
+    let value = get(object, key);
+    if value.is_undefined() || value.is_null() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Returns the string field `key` of `object`, which must be present.
+fn get_string(object: &JsValue, key: &str) -> Result<String, W3DError> {
+    get(object, key)
+        .as_string()
+        .ok_or_else(|| invalid_definition("Missing string field in prefab.", key))
+}
+
+/// Returns the number field `key` of `object`, or `default` if it is missing.
+fn get_f32(object: &JsValue, key: &str, default: f32) -> Result<f32, W3DError> {
+    match get_object(object, key) {
+        Some(value) => value
+            .as_f64()
+            .map(|value| value as f32)
+            .ok_or_else(|| invalid_definition("Prefab field must be a number.", key)),
+        None => Ok(default),
+    }
+}
+
+/// Returns the `[x, y, z]` field `key` of `object`, or `default` if it is missing.
+fn get_vector3(
+    object: &JsValue,
+    key: &str,
+    default: Vector3<f32>,
+) -> Result<Vector3<f32>, W3DError> {
+    let value = match get_object(object, key) {
+        Some(value) => value,
+        None => return Ok(default),
+    };
+    let components: Vec<f32> = if Array::is_array(&value) {
+        Array::from(&value)
+            .iter()
+            .filter_map(|component| component.as_f64())
+            .map(|component| component as f32)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if components.len() != 3 {
+        return Err(invalid_definition(
+            "Prefab field must be an array of three numbers.",
+            key,
+        ));
+    }
+    Ok(Vector3::new(components[0], components[1], components[2]))
+}
+
+/// Builds the error reported for an invalid prefab description.
+fn invalid_definition(reason: &str, source: &str) -> W3DError {
+    W3DError::with_source(W3DErrorKind::InvalidArgument, reason, source)
+}
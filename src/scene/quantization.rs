@@ -0,0 +1,53 @@
+//! Editor conversion of mesh files to compact vertex attributes or to other axis, scale and
+//! pivot conventions. Only built with the `editor` feature.
+
+use super::Scene;
+use crate::asset::{quantize_mesh_file, transform_mesh_file};
+use crate::utils::ImportOptions;
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl Scene {
+    /// Converts the normals, colors and texture coordinates of a binary mesh file to
+    /// normalized integers, which render the same within a small tolerance (see
+    /// `asset::quantize_mesh_file`). Returns an object with:
+    ///
+    /// - `data`: the converted file, to save or register instead of the original;
+    /// - `originalBytes` and `quantizedBytes`: the size of the vertex buffers before and
+    ///   after conversion;
+    /// - `attributes`: the names of the attributes converted.
+    ///
+    /// Fails if the file can't be deserialized.
+    pub fn quantize_mesh_attributes(&self, file_data: &[u8]) -> Result<JsValue, JsValue> {
+        let (data, report) = quantize_mesh_file(file_data)?;
+        let result = Object::new();
+        let attributes: Array = report
+            .attributes
+            .iter()
+            .map(|name| JsValue::from(name.as_str()))
+            .collect();
+        for (key, value) in &[
+            ("data", Uint8Array::from(data.as_slice()).into()),
+            ("originalBytes", (report.original_bytes as f64).into()),
+            ("quantizedBytes", (report.quantized_bytes as f64).into()),
+            ("attributes", attributes.into()),
+        ] {
+            Reflect::set(&result, &(*key).into(), value)?;
+        }
+        Ok(result.into())
+    }
+
+    /// Transforms the vertices of a binary mesh file as `import_mesh` would with the same
+    /// options, for meshes already imported, and returns the converted file to save or
+    /// register instead of the original. `options.fix_winding` is ignored.  
+    /// Fails if the file can't be deserialized or if `options.uniform_scale` isn't positive.
+    pub fn transform_mesh(
+        &self,
+        file_data: &[u8],
+        options: &ImportOptions,
+    ) -> Result<Vec<u8>, JsValue> {
+        let data = transform_mesh_file(file_data, options)?;
+        Ok(data.unwrap_or_else(|| file_data.to_vec()))
+    }
+}
@@ -0,0 +1,184 @@
+//! Global rendering settings, applied and persisted as a whole, e.g. for the graphics
+//! options of an application.
+
+use super::Scene;
+use crate::component::Camera;
+use crate::error::{W3DError, W3DErrorKind};
+use crate::renderer::LightRepository;
+use crate::resource::ActiveCamera;
+use crate::utils::constants::{MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use crate::utils::{LightUnits, ToneMapping};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use specs::{Read, ReadStorage, WorldExt};
+use wasm_bindgen::prelude::*;
+
+/// ## RenderSettings
+///
+/// Rendering settings of a scene, each also available through its own setter. Serialized to
+/// JSON with camelCase field names.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderSettings {
+    /// Factor applied to the device pixel ratio when sizing the canvas
+    pub resolution_scale: f32,
+
+    /// Largest number of point lights and of spot lights lit shaders are compiled for
+    pub max_lights: usize,
+
+    /// Tone mapping operator of the active camera
+    pub tone_mapping: ToneMapping,
+
+    /// Unit of the intensity of lights
+    pub light_units: LightUnits,
+
+    /// Whether opaque meshes are rendered to the depth buffer before the main pass
+    pub depth_prepass: bool,
+
+    /// Whether particles fade out where they meet opaque meshes
+    pub soft_particles: bool,
+
+    /// Whether the scene is rendered to a half-float target, then tone mapped to the canvas
+    pub hdr: bool,
+}
+
+#[wasm_bindgen]
+impl Scene {
+    /// Applies rendering settings given as JSON, e.g. saved from `get_settings`. Fields
+    /// that are missing keep their current value, unknown fields are ignored with a warning.
+    /// Only the settings that changed are applied, so applying the current settings again
+    /// does nothing.
+    /// Fails if the scene is not initialized, if the JSON doesn't describe valid settings, if
+    /// soft particles are enabled but not supported by the context or if the HDR pass can't
+    /// be created.
+    pub fn apply_settings(&mut self, json: &str) -> Result<(), JsValue> {
+        let current = self.read_settings()?;
+        let given = serde_json::from_str::<Value>(json).map_err(|error| {
+            W3DError::with_source(
+                W3DErrorKind::Deserialization,
+                "Could not parse the render settings.",
+                &error.to_string(),
+            )
+        })?;
+        let given = match given {
+            Value::Object(fields) => fields,
+            _ => {
+                return Err(W3DError::new(
+                    W3DErrorKind::InvalidArgument,
+                    "Render settings must be a JSON object.",
+                )
+                .into())
+            }
+        };
+        let mut merged = match serde_json::to_value(&current) {
+            Ok(Value::Object(fields)) => fields,
+            _ => {
+                return Err(W3DError::new(
+                    W3DErrorKind::Deserialization,
+                    "Could not serialize the current render settings.",
+                )
+                .into())
+            }
+        };
+        for (name, value) in given {
+            if merged.contains_key(&name) {
+                merged.insert(name, value);
+            } else {
+                log_warn!("Unknown render setting {} is ignored.", name);
+            }
+        }
+        let settings =
+            serde_json::from_value::<RenderSettings>(Value::Object(merged)).map_err(|error| {
+                W3DError::with_source(
+                    W3DErrorKind::InvalidArgument,
+                    "Invalid render settings.",
+                    &error.to_string(),
+                )
+            })?;
+        if !(settings.resolution_scale > 0.) {
+            return Err(W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "The resolution scale must be positive.",
+            )
+            .into());
+        }
+        if settings.resolution_scale != current.resolution_scale {
+            self.get_renderer("The resolution scale")?
+                .borrow_mut()
+                .set_resolution_scale(settings.resolution_scale);
+        }
+        if settings.max_lights != current.max_lights {
+            let max_lights = if settings.max_lights >= MAX_POINT_LIGHTS.max(MAX_SPOT_LIGHTS) {
+                None
+            } else {
+                Some(settings.max_lights)
+            };
+            self.world.write_resource::<LightRepository>().max_lights = max_lights;
+        }
+        if settings.tone_mapping != current.tone_mapping {
+            self.set_tone_mapping(settings.tone_mapping)?;
+        }
+        if settings.light_units != current.light_units {
+            self.set_light_units(settings.light_units);
+        }
+        if settings.depth_prepass != current.depth_prepass {
+            self.set_depth_prepass(settings.depth_prepass)?;
+        }
+        if settings.soft_particles != current.soft_particles {
+            self.set_soft_particles(settings.soft_particles)?;
+        }
+        if settings.hdr != current.hdr {
+            self.set_hdr(settings.hdr)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the current rendering settings as JSON, to be given back to
+    /// `apply_settings`.
+    /// Fails if the scene is not initialized.
+    pub fn get_settings(&self) -> Result<String, JsValue> {
+        let settings = self.read_settings()?;
+        serde_json::to_string(&settings).map_err(|error| {
+            W3DError::with_source(
+                W3DErrorKind::Deserialization,
+                "Could not serialize the render settings.",
+                &error.to_string(),
+            )
+            .into()
+        })
+    }
+}
+
+impl Scene {
+    /// Gathers the current rendering settings from the renderer, the active camera and the
+    /// light repository.
+    fn read_settings(&self) -> Result<RenderSettings, W3DError> {
+        let renderer = self.get_renderer("Render settings")?.borrow();
+        let (cameras, active_camera, light_repository): (
+            ReadStorage<Camera>,
+            Read<ActiveCamera>,
+            Read<LightRepository>,
+        ) = self.world.system_data();
+        let tone_mapping = active_camera
+            .entity
+            .and_then(|entity| cameras.get(entity))
+            .map(|camera| camera.get_tone_mapping())
+            .ok_or_else(|| {
+                W3DError::new(
+                    W3DErrorKind::Uninitialized,
+                    "Render settings can't be read without an active camera.",
+                )
+            })?;
+        Ok(RenderSettings {
+            resolution_scale: renderer.get_resolution_scale(),
+            max_lights: light_repository
+                .max_lights
+                .unwrap_or_else(|| MAX_POINT_LIGHTS.max(MAX_SPOT_LIGHTS)),
+            tone_mapping: tone_mapping,
+            light_units: light_repository.units,
+            depth_prepass: renderer.has_depth_prepass(),
+            soft_particles: renderer.has_soft_particles(),
+            hdr: renderer.has_hdr(),
+        })
+    }
+}
@@ -0,0 +1,270 @@
+//! WebXR sessions: stereo rendering to the session's layer, and entities following the
+//! tracked controllers. Only built with the `xr` feature.
+
+use super::Scene;
+use crate::component::{Camera, DirtyTransform, Enabled, Transform};
+use crate::error::{W3DError, W3DErrorKind};
+use crate::renderer::RenderView;
+use crate::resource::ActiveCamera;
+use js_sys::Promise;
+use nalgebra::{Matrix4, Vector3};
+use specs::{Builder, Entities, Entity, Read, ReadStorage, WriteStorage};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    XrFrame, XrReferenceSpace, XrReferenceSpaceType, XrRenderStateInit, XrSession, XrWebGlLayer,
+};
+
+/// ## XrState
+///
+/// The running XR session of a `Scene`.
+pub(super) struct XrState {
+    session: XrSession,
+
+    /// Space the poses are given in, `None` until the session provides it
+    reference_space: Rc<RefCell<Option<XrReferenceSpace>>>,
+
+    /// Set by the session's `end` event
+    ended: Rc<Cell<bool>>,
+
+    /// Handler of the session's `end` event, kept alive with the session
+    _on_end: Closure<dyn FnMut(JsValue)>,
+
+    /// Entities following the input sources, by input source index
+    controllers: HashMap<u32, Entity>,
+}
+
+#[wasm_bindgen]
+impl Scene {
+    /// Starts rendering to an immersive WebXR session: the context is made compatible with
+    /// XR and set as the session's base layer. The promise resolves once the session's
+    /// `local` reference space is available, and is rejected if any of these steps fails or
+    /// if the scene is not initialized.
+    ///
+    /// The scene is then rendered by calling `render_xr_frame` from the callback of the
+    /// session's `requestAnimationFrame`, instead of `update`. The XR origin is placed at the
+    /// active camera, so moving the camera moves the player.
+    pub fn enter_xr(&mut self, session: XrSession) -> Promise {
+        let context = match self.get_renderer("XR sessions") {
+            Ok(renderer) => renderer.borrow().get_webgl_context().clone(),
+            Err(error) => return Promise::reject(&error.into()),
+        };
+        if self.xr.is_some() {
+            let error = W3DError::new(
+                W3DErrorKind::InvalidArgument,
+                "An XR session is already running.",
+            );
+            return Promise::reject(&error.into());
+        }
+        let reference_space = Rc::new(RefCell::new(None));
+        let ended = Rc::new(Cell::new(false));
+        let ended_flag = ended.clone();
+        let on_end = Closure::wrap(
+            Box::new(move |_: JsValue| ended_flag.set(true)) as Box<dyn FnMut(JsValue)>
+        );
+        session.set_onend(Some(on_end.as_ref().unchecked_ref()));
+        self.xr = Some(XrState {
+            session: session.clone(),
+            reference_space: reference_space.clone(),
+            ended: ended,
+            _on_end: on_end,
+            controllers: HashMap::new(),
+        });
+
+        let mut settle = None;
+        let promise = Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+        let (resolve, reject) = settle.unwrap();
+        let on_compatible_reject = reject.clone();
+        let on_failed_reject = reject.clone();
+        let making_compatible: Promise = context.make_xr_compatible().unchecked_into();
+        let on_compatible = Closure::once(move |_: JsValue| {
+            let layer = match XrWebGlLayer::new_with_web_gl_rendering_context(&session, &context) {
+                Ok(layer) => layer,
+                Err(error) => {
+                    on_compatible_reject.call1(&JsValue::NULL, &error).ok();
+                    return;
+                }
+            };
+            let render_state = XrRenderStateInit::new();
+            render_state.set_base_layer(Some(&layer));
+            session.update_render_state_with_state(&render_state);
+            let on_space = Closure::once(move |space: JsValue| {
+                *reference_space.borrow_mut() = Some(space.unchecked_into());
+                resolve.call0(&JsValue::NULL).ok();
+            });
+            let on_space_failed = Closure::once(move |error: JsValue| {
+                on_compatible_reject.call1(&JsValue::NULL, &error).ok();
+            });
+            let requesting: Promise = session
+                .request_reference_space(XrReferenceSpaceType::Local)
+                .unchecked_into();
+            let _ = requesting.then2(&on_space, &on_space_failed);
+            on_space.forget();
+            on_space_failed.forget();
+        });
+        let on_failed = Closure::once(move |error: JsValue| {
+            on_failed_reject.call1(&JsValue::NULL, &error).ok();
+        });
+        let _ = making_compatible.then2(&on_compatible, &on_failed);
+        // Only one of them is ever called, and they must outlive this call.
+        on_compatible.forget();
+        on_failed.forget();
+        promise
+    }
+
+    /// Updates the scene and renders it once per view of an XR frame, to the session's
+    /// layer, with the projection and pose of each view. Controller entities are moved to
+    /// the pose of their input source first.
+    /// Does nothing until the session's reference space is available, or while tracking is
+    /// lost. Fails if no XR session is running.
+    pub fn render_xr_frame(&mut self, frame: XrFrame) -> Result<(), JsValue> {
+        self.exit_xr_if_ended();
+        let xr = self.xr.as_ref().ok_or_else(|| {
+            W3DError::new(W3DErrorKind::Uninitialized, "No XR session is running.")
+        })?;
+        let reference_space = match &*xr.reference_space.borrow() {
+            Some(reference_space) => reference_space.clone(),
+            None => return Ok(()),
+        };
+        let (layer, viewer_pose) = match (
+            xr.session.render_state().base_layer(),
+            frame.get_viewer_pose(&reference_space),
+        ) {
+            (Some(layer), Some(viewer_pose)) => (layer, viewer_pose),
+            _ => return Ok(()),
+        };
+        let origin = self.get_xr_origin();
+        let origin_inverse = origin.try_inverse().unwrap_or_else(Matrix4::identity);
+        let mut views = Vec::new();
+        for view in viewer_pose.views().iter() {
+            let view: web_sys::XrView = view.unchecked_into();
+            let viewport = match layer.get_viewport(&view) {
+                Some(viewport) => viewport,
+                None => continue,
+            };
+            let eye_view = Matrix4::from_column_slice(&view.transform().inverse().matrix());
+            views.push(RenderView {
+                view: eye_view * origin_inverse,
+                projection: Matrix4::from_column_slice(&view.projection_matrix()),
+                viewport: [
+                    viewport.x(),
+                    viewport.y(),
+                    viewport.width(),
+                    viewport.height(),
+                ],
+            });
+        }
+        self.update_xr_controllers(&frame, &reference_space, &origin);
+        let renderer = self.get_renderer("XR frames")?.clone();
+        renderer
+            .borrow_mut()
+            .set_render_views(layer.framebuffer(), views);
+        self.update();
+        renderer.borrow_mut().set_render_views(None, Vec::new());
+        Ok(())
+    }
+
+    /// Ends the XR session if one is running, and goes back to rendering the canvas with
+    /// `update`. Controller entities are kept, at their last pose.
+    pub fn exit_xr(&mut self) -> () {
+        if let Some(xr) = self.xr.take() {
+            xr.session.set_onend(None);
+            if !xr.ended.get() {
+                let _ = xr.session.end();
+            }
+        }
+    }
+
+    /// Returns `true` if an XR session is running.
+    pub fn is_in_xr(&self) -> bool {
+        self.xr.is_some()
+    }
+
+    /// Returns the id of an entity following the input source at `index` in the session's
+    /// input sources, creating it the first time. Its transform is updated by each
+    /// `render_xr_frame`, from the grip pose of the input source if it has one, its target
+    /// ray pose otherwise, so meshes can be attached to it as children.
+    /// Fails if no XR session is running.
+    pub fn get_xr_controller_entity(&mut self, index: u32) -> Result<u32, JsValue> {
+        let xr = self.xr.as_mut().ok_or_else(|| {
+            W3DError::new(W3DErrorKind::Uninitialized, "No XR session is running.")
+        })?;
+        let world = &mut self.world;
+        let entity = *xr.controllers.entry(index).or_insert_with(|| {
+            Scene::build_entity(world)
+                .with(Transform::new(
+                    &Vector3::new(0.0, 0.0, 0.0),
+                    &Vector3::new(0.0, 0.0, 0.0),
+                    &Vector3::new(1.0, 1.0, 1.0),
+                ))
+                .with(Enabled)
+                .build()
+        });
+        Ok(entity.id())
+    }
+}
+
+impl Scene {
+    /// Goes back to rendering the canvas if the XR session ended by itself.
+    pub(super) fn exit_xr_if_ended(&mut self) -> () {
+        if self.xr.as_ref().map_or(false, |xr| xr.ended.get()) {
+            self.exit_xr();
+        }
+    }
+
+    /// Returns the world matrix of the XR origin: the active camera's pose, without shake.
+    fn get_xr_origin(&self) -> Matrix4<f32> {
+        let (cameras, active_camera): (ReadStorage<Camera>, Read<ActiveCamera>) =
+            self.world.system_data();
+        active_camera
+            .entity
+            .and_then(|entity| cameras.get(entity))
+            .map_or_else(Matrix4::identity, |camera| {
+                camera.get_view().inverse().to_homogeneous()
+            })
+    }
+
+    /// Moves the controller entities to the pose of their input source in `frame`.
+    /// Controllers whose input source is missing or not tracked keep their transform.
+    fn update_xr_controllers(
+        &self,
+        frame: &XrFrame,
+        reference_space: &XrReferenceSpace,
+        origin: &Matrix4<f32>,
+    ) -> () {
+        let xr = match &self.xr {
+            Some(xr) => xr,
+            None => return,
+        };
+        let input_sources = xr.session.input_sources();
+        let (mut transforms, mut dirty_transforms, entities): (
+            WriteStorage<Transform>,
+            WriteStorage<DirtyTransform>,
+            Entities,
+        ) = self.world.system_data();
+        for (index, entity) in &xr.controllers {
+            let input_source = match input_sources.get(*index) {
+                Some(input_source) => input_source,
+                None => continue,
+            };
+            let space = input_source
+                .grip_space()
+                .unwrap_or_else(|| input_source.target_ray_space());
+            let pose = match frame.get_pose(&space, reference_space) {
+                Some(pose) => pose,
+                None => continue,
+            };
+            let matrix = origin * Matrix4::from_column_slice(&pose.transform().matrix());
+            if let (true, Some(transform)) =
+                (entities.is_alive(*entity), transforms.get_mut(*entity))
+            {
+                if transform.set_local_matrix(&matrix).is_ok() {
+                    dirty_transforms.insert(*entity, DirtyTransform).ok();
+                }
+            }
+        }
+    }
+}
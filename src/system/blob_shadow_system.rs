@@ -0,0 +1,38 @@
+//! System projecting every `BlobShadow` on its ground plane.
+
+use crate::component::{BlobShadow, EffectivelyDisabled, Enabled, Transform};
+use nalgebra::{Vector3, Vector4};
+use specs::{Join, ReadStorage, System, WriteStorage};
+
+/// Updates the position and opacity of every enabled `BlobShadow` from its entity's world
+/// position and the height of its ground. Must run after the scene graph is updated.
+pub struct BlobShadowSystem;
+
+impl<'a> System<'a> for BlobShadowSystem {
+    type SystemData = (
+        WriteStorage<'a, BlobShadow>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+    );
+
+    fn run(&mut self, (mut shadows, transforms, enableds, effectively_disabled): Self::SystemData) {
+        let active = (&enableds, !&effectively_disabled);
+        for (shadow, transform, _) in (&mut shadows, &transforms, active).join() {
+            let ground_height = match shadow.ground_entity {
+                Some(ground) => match transforms.get(ground) {
+                    Some(ground_transform) => world_position(ground_transform).y,
+                    None => shadow.ground_height,
+                },
+                None => shadow.ground_height,
+            };
+            shadow.update(&world_position(transform), ground_height);
+        }
+    }
+}
+
+/// Returns the world position of a transform's origin.
+fn world_position(transform: &Transform) -> Vector3<f32> {
+    let world_position = transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    Vector3::new(world_position.x, world_position.y, world_position.z) / world_position.w
+}
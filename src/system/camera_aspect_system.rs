@@ -0,0 +1,25 @@
+//! System keeping the aspect ratio of cameras in sync with the viewport.
+
+use crate::component::Camera;
+use crate::resource::ViewportInfo;
+use specs::{Join, Read, System, WriteStorage};
+
+/// Sets the aspect ratio of every `Camera` whose aspect ratio isn't locked when the viewport
+/// is resized, so that their view-projection matrices match what is displayed.  
+/// Must run before anything reading the cameras' projections.
+pub struct CameraAspectSystem;
+
+impl<'a> System<'a> for CameraAspectSystem {
+    type SystemData = (Read<'a, ViewportInfo>, WriteStorage<'a, Camera>);
+
+    fn run(&mut self, (viewport, mut cameras): Self::SystemData) {
+        if !viewport.resized || viewport.aspect_ratio <= 0.0 {
+            return;
+        }
+        for camera in (&mut cameras).join() {
+            if !camera.is_aspect_ratio_locked() {
+                camera.set_aspect_ratio(viewport.aspect_ratio);
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! System computing the offset of every shaking `Camera` from its `CameraShake`.
+
+use crate::component::{Camera, CameraShake};
+use crate::math::value_noise;
+use crate::resource::Time;
+use nalgebra::{Isometry3, Translation3, UnitQuaternion, Vector3};
+use specs::{Entities, Entity, Join, Read, System, WriteStorage};
+
+/// Sets the shake offset of cameras with a `CameraShake` from smooth noise, and resets it once
+/// the shake is over, removing the component.  
+/// Must run after anything that moves cameras, so that the shake is applied on top of them.
+pub struct CameraShakeSystem;
+
+impl<'a> System<'a> for CameraShakeSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, CameraShake>,
+        WriteStorage<'a, Camera>,
+    );
+
+    fn run(&mut self, (entities, time, mut shakes, mut cameras): Self::SystemData) {
+        let mut finished: Vec<Entity> = Vec::new();
+        for (entity, shake, camera) in (&entities, &mut shakes, &mut cameras).join() {
+            shake.advance(time.get_delta());
+            if shake.is_finished() {
+                camera.set_shake_offset(&Isometry3::identity());
+                finished.push(entity);
+            } else {
+                camera.set_shake_offset(&shake_offset(shake));
+            }
+        }
+        for entity in finished {
+            shakes.remove(entity);
+        }
+    }
+}
+
+/// Returns the offset of a shake at its current time: one noise channel per translation and
+/// rotation axis, scaled by the shake's intensity.
+fn shake_offset(shake: &CameraShake) -> Isometry3<f32> {
+    let x = shake.get_elapsed() * shake.frequency;
+    let intensity = shake.get_intensity();
+    let channel = |index: u32| value_noise(shake.seed.wrapping_add(index), x) * intensity;
+    let translation = Vector3::new(channel(0), channel(1), channel(2)) * shake.amplitude;
+    let rotation = Vector3::new(channel(3), channel(4), channel(5)) * shake.rotation_amplitude;
+    Isometry3::from_parts(
+        Translation3::from(translation),
+        UnitQuaternion::from_euler_angles(rotation.x, rotation.y, rotation.z),
+    )
+}
@@ -0,0 +1,228 @@
+//! System applying `Follow` and `LookAtTarget` constraints before the scene graph is refreshed.
+
+use crate::component::{DirtyTransform, Follow, LookAtTarget, Transform};
+use crate::math::{decompose_matrix, safe_slerp, smoothing_factor};
+use crate::resource::Time;
+use nalgebra::{UnitQuaternion, Vector3};
+use specs::{Entities, Entity, Join, Read, System, WriteStorage};
+
+/// Updates the `Transform` of followers from the last-known world matrix of their target.  
+/// Must run before the `SceneGraphSystem`, once per step: smoothing advances by the unscaled
+/// step delta, so that it doesn't depend on how many steps an update takes.
+pub struct ConstraintSystem;
+
+impl<'a> System<'a> for ConstraintSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Follow>,
+        WriteStorage<'a, LookAtTarget>,
+        WriteStorage<'a, DirtyTransform>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, mut transforms, mut follows, mut look_ats, mut dirty): Self::SystemData,
+    ) {
+        // Constraints drive cameras too, which keep moving while the simulation is paused.
+        let delta = time.get_unscaled_step_delta();
+        let mut broken_follows: Vec<Entity> = Vec::new();
+        let mut updates: Vec<(Entity, Vector3<f32>, Option<UnitQuaternion<f32>>)> = Vec::new();
+        for (entity, follow) in (&entities, &follows).join() {
+            let target_matrix = match transforms.get(follow.target) {
+                Some(target) if entities.is_alive(follow.target) => target.get_world_matrix(),
+                _ => {
+                    broken_follows.push(entity);
+                    continue;
+                }
+            };
+            if let Some(transform) = transforms.get(entity) {
+                let (target_position, target_rotation, _) = decompose_matrix(&target_matrix);
+                let blend = smoothing_factor(follow.smoothing, delta);
+                let current_position = transform.get_translation();
+                if follow.follow_rotation {
+                    let desired_position = target_position + target_rotation * follow.offset;
+                    updates.push((
+                        entity,
+                        current_position.lerp(&desired_position, blend),
+                        Some(safe_slerp(
+                            transform.get_rotation(),
+                            &target_rotation,
+                            blend,
+                        )),
+                    ));
+                } else {
+                    let desired_position = target_position + follow.offset;
+                    updates.push((
+                        entity,
+                        current_position.lerp(&desired_position, blend),
+                        None,
+                    ));
+                }
+            }
+        }
+        for (entity, translation, rotation) in updates {
+            if let Some(transform) = transforms.get_mut(entity) {
+                transform.set_translation(&translation);
+                if let Some(rotation) = rotation {
+                    transform.set_rotation_quaternion(&rotation);
+                }
+                dirty.insert(entity, DirtyTransform).ok();
+            }
+        }
+        for entity in broken_follows {
+            follows.remove(entity);
+        }
+
+        let mut broken_look_ats: Vec<Entity> = Vec::new();
+        let mut rotations: Vec<(Entity, UnitQuaternion<f32>)> = Vec::new();
+        for (entity, look_at) in (&entities, &look_ats).join() {
+            let target_matrix = match transforms.get(look_at.target) {
+                Some(target) if entities.is_alive(look_at.target) => target.get_world_matrix(),
+                _ => {
+                    broken_look_ats.push(entity);
+                    continue;
+                }
+            };
+            if let Some(transform) = transforms.get(entity) {
+                let (target_position, _, _) = decompose_matrix(&target_matrix);
+                let direction = target_position - transform.get_translation();
+                if let Some(rotation) = look_rotation(&direction, &look_at.up) {
+                    rotations.push((entity, rotation));
+                }
+            }
+        }
+        for (entity, rotation) in rotations {
+            if let Some(transform) = transforms.get_mut(entity) {
+                transform.set_rotation_quaternion(&rotation);
+                dirty.insert(entity, DirtyTransform).ok();
+            }
+        }
+        for entity in broken_look_ats {
+            look_ats.remove(entity);
+        }
+    }
+}
+
+/// Returns the rotation facing `direction`, as upright as `up` allows.  
+/// If `direction` is parallel to `up` (e.g. a camera looking straight down), the world axis
+/// least aligned with `direction` is used as up instead. Returns `None` if `direction` is
+/// null, for an entity at the position of its target: its previous rotation should be kept.
+fn look_rotation(direction: &Vector3<f32>, up: &Vector3<f32>) -> Option<UnitQuaternion<f32>> {
+    let length = direction.norm();
+    if length <= std::f32::EPSILON {
+        return None;
+    }
+    let up_length = up.norm();
+    let up = if up_length > std::f32::EPSILON
+        && direction.cross(up).norm() > 1.0e-4 * length * up_length
+    {
+        *up
+    } else {
+        let mut axis = Vector3::zeros();
+        axis[direction.iamin()] = 1.;
+        axis
+    };
+    Some(UnitQuaternion::face_towards(direction, &up))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_faces(rotation: &UnitQuaternion<f32>, direction: &Vector3<f32>) -> () {
+        assert!(rotation.coords.iter().all(|value| value.is_finite()));
+        let forward = rotation * Vector3::z();
+        assert!(
+            (forward - direction.normalize()).norm() < 1e-5,
+            "{:?}",
+            forward
+        );
+    }
+
+    #[test]
+    fn look_rotation_faces_direction() {
+        let direction = Vector3::new(1., 2., -3.);
+        let rotation = look_rotation(&direction, &Vector3::y()).unwrap();
+        assert_faces(&rotation, &direction);
+        let right = rotation * Vector3::x();
+        assert!(right.y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn look_rotation_falls_back_when_parallel_to_up() {
+        for direction in &[
+            Vector3::new(0., -5., 0.),
+            Vector3::new(0., 3., 0.),
+            Vector3::new(1e-6, 1., 0.),
+        ] {
+            let rotation = look_rotation(direction, &Vector3::y()).unwrap();
+            assert_faces(&rotation, direction);
+        }
+        let rotation = look_rotation(&Vector3::new(0., 0., 2.), &Vector3::zeros()).unwrap();
+        assert_faces(&rotation, &Vector3::z());
+    }
+
+    #[test]
+    fn look_rotation_keeps_rotation_at_target_position() {
+        assert!(look_rotation(&Vector3::zeros(), &Vector3::y()).is_none());
+    }
+
+    /// Runs the constraint system for each step of updates at `timestamps`, in fixed timestep
+    /// mode, and returns the steps taken and the follower's final distance to its target.
+    fn follow_in_fixed_steps(timestamps: &[f64]) -> (Vec<u32>, f32) {
+        use specs::{Builder, RunNow, World, WorldExt};
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Follow>();
+        world.register::<LookAtTarget>();
+        world.register::<DirtyTransform>();
+        let mut time = Time::default();
+        time.set_fixed_step(Some(0.1));
+        world.insert(time);
+        let target_position = Vector3::new(10., 0., 0.);
+        let one = Vector3::new(1., 1., 1.);
+        let target = world
+            .create_entity()
+            .with(Transform::new(&target_position, &Vector3::zeros(), &one))
+            .build();
+        let follower = world
+            .create_entity()
+            .with(Transform::new(&Vector3::zeros(), &Vector3::zeros(), &one))
+            .with(Follow::new(target, Vector3::zeros(), 0.5))
+            .build();
+        let mut steps = Vec::new();
+        for timestamp in timestamps {
+            let step_count = {
+                let mut time = world.write_resource::<Time>();
+                time.advance(*timestamp);
+                time.take_steps()
+            };
+            for _ in 0..step_count {
+                ConstraintSystem.run_now(&world);
+            }
+            steps.push(step_count);
+        }
+        let transforms = world.read_storage::<Transform>();
+        let distance =
+            (target_position - transforms.get(follower).unwrap().get_translation()).norm();
+        (steps, distance)
+    }
+
+    #[test]
+    fn follow_smoothing_does_not_depend_on_the_step_count() {
+        // Updates taking 0, 1 and 3 steps, then 4 updates taking 1 step each.
+        let (uneven_steps, uneven_distance) = follow_in_fixed_steps(&[0., 50., 150., 450.]);
+        let (even_steps, even_distance) = follow_in_fixed_steps(&[0., 100., 200., 300., 400.]);
+        assert_eq!(uneven_steps, vec![0, 0, 1, 3]);
+        assert_eq!(even_steps, vec![0, 1, 1, 1, 1]);
+        let expected = 10. * (-0.4f32 / 0.5).exp();
+        assert!(
+            (uneven_distance - expected).abs() < 1e-4,
+            "{}",
+            uneven_distance
+        );
+        assert!((even_distance - expected).abs() < 1e-4, "{}", even_distance);
+    }
+}
@@ -0,0 +1,118 @@
+//! System testing the visibility of meshes against the view frustum.
+
+use crate::asset::AssetRegistry;
+use crate::component::{
+    AlwaysVisible, Bounds, Camera, EffectivelyDisabled, Enabled, Lod, Mesh, Transform,
+};
+use crate::math::{BoundingSphere, Frustum};
+use crate::renderer::Renderer;
+use crate::resource::{ActiveCamera, Visibility};
+use specs::{Entities, Join, Read, ReadStorage, System, Write};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Fills the `Visibility` resource with the meshes that intersect the active camera's frustum,
+/// or the frustum of one of the renderer's render views if it has any.
+///
+/// The entity's `Bounds` are used if present, the bounds of its `MeshData` otherwise. Entities
+/// flagged `AlwaysVisible`, and meshes whose bounds are unknown, are always visible; entities
+/// beyond their `Lod` cull distance never are.
+pub struct CullingSystem {
+    renderer: Rc<RefCell<Renderer>>,
+}
+
+impl CullingSystem {
+    pub fn new(renderer: Rc<RefCell<Renderer>>) -> CullingSystem {
+        CullingSystem { renderer: renderer }
+    }
+}
+
+impl<'a> System<'a> for CullingSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Mesh>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+        ReadStorage<'a, Bounds>,
+        ReadStorage<'a, AlwaysVisible>,
+        ReadStorage<'a, Lod>,
+        ReadStorage<'a, Camera>,
+        Read<'a, ActiveCamera>,
+        Write<'a, Visibility>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            meshes,
+            transforms,
+            enableds,
+            effectively_disabled,
+            bounds,
+            always_visibles,
+            lods,
+            cameras,
+            active_camera,
+            mut visibility,
+        ): Self::SystemData,
+    ) {
+        visibility.clear();
+        let renderer = self.renderer.borrow();
+        let frustums: Vec<Frustum> = if renderer.get_render_views().is_empty() {
+            active_camera
+                .entity
+                .and_then(|entity| cameras.get(entity))
+                .map(|camera| Frustum::from_matrix(&camera.get_vp_matrix()))
+                .into_iter()
+                .collect()
+        } else {
+            renderer
+                .get_render_views()
+                .iter()
+                .map(|view| Frustum::from_matrix(&(view.projection * view.view)))
+                .collect()
+        };
+        let asset_registry = renderer.get_asset_registry();
+        let active = (&enableds, !&effectively_disabled);
+        for (entity, mesh, transform, _) in (&entities, &meshes, &transforms, active).join() {
+            if lods.get(entity).map(|lod| lod.is_culled()).unwrap_or(false) {
+                visibility.set_tested(entity.id());
+                continue;
+            }
+            let visible = if frustums.is_empty() || always_visibles.contains(entity) {
+                true
+            } else {
+                match get_local_bounds(bounds.get(entity), mesh, asset_registry) {
+                    Some(sphere) => {
+                        visibility.set_tested(entity.id());
+                        let sphere = sphere.transformed(&transform.get_world_matrix());
+                        frustums
+                            .iter()
+                            .any(|frustum| frustum.intersects_sphere(&sphere))
+                    }
+                    None => true,
+                }
+            };
+            if visible {
+                visibility.set_visible(entity.id());
+            }
+        }
+    }
+}
+
+/// Returns the local bounds the culling tests use for a mesh entity: its `Bounds` if it has
+/// some, the bounds of its `MeshData` otherwise, or `None` if they are unknown.
+pub fn get_local_bounds(
+    bounds: Option<&Bounds>,
+    mesh: &Mesh,
+    asset_registry: &AssetRegistry,
+) -> Option<BoundingSphere> {
+    match bounds {
+        Some(bounds) => Some(bounds.sphere),
+        None => asset_registry
+            .get_mesh_data_with_index(*mesh.get_mesh_data_id())
+            .and_then(|mesh_data| mesh_data.borrow().get_bounds()),
+    }
+}
@@ -0,0 +1,98 @@
+//! System propagating the disabled state of entities down the scene graph.
+
+use crate::component::{EffectivelyDisabled, Enabled, TransformParent};
+use specs::{Entities, ReadExpect, ReadStorage, System, WriteStorage};
+use specs_hierarchy::Hierarchy;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Flags every entity having a disabled ancestor as `EffectivelyDisabled`, and clears the
+/// flag of the others. Must run after the `HierarchySystem` and before any system reading
+/// `Enabled`.
+pub struct EnabledPropagationSystem;
+
+impl<'a> System<'a> for EnabledPropagationSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Hierarchy<TransformParent>>,
+        ReadStorage<'a, Enabled>,
+        WriteStorage<'a, EffectivelyDisabled>,
+    );
+
+    fn run(&mut self, (entities, hierarchy, enableds, mut effectively_disabled): Self::SystemData) {
+        effectively_disabled.clear();
+        let disabled = find_effectively_disabled(
+            hierarchy.all(),
+            |entity| hierarchy.parent(entity),
+            |entity| enableds.contains(entity),
+        );
+        for entity in disabled {
+            if entities.is_alive(entity) {
+                effectively_disabled
+                    .insert(entity, EffectivelyDisabled)
+                    .ok();
+            }
+        }
+    }
+}
+
+/// Returns the nodes of `sorted` having a disabled ancestor.
+/// `sorted` must list parents before their children, as `Hierarchy::all` does.
+fn find_effectively_disabled<E, P, F>(sorted: &[E], parent: P, is_enabled: F) -> HashSet<E>
+where
+    E: Copy + Eq + Hash,
+    P: Fn(E) -> Option<E>,
+    F: Fn(E) -> bool,
+{
+    let mut disabled = HashSet::new();
+    for node in sorted {
+        if let Some(parent) = parent(*node) {
+            if !is_enabled(parent) || disabled.contains(&parent) {
+                disabled.insert(*node);
+            }
+        }
+    }
+    disabled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nodes of a hierarchy given as `(node, parent)` pairs, parents first.
+    fn run(nodes: &[(u32, Option<u32>)], disabled: &[u32]) -> HashSet<u32> {
+        let sorted: Vec<u32> = nodes.iter().map(|(node, _)| *node).collect();
+        find_effectively_disabled(
+            &sorted,
+            |node| nodes.iter().find(|(n, _)| *n == node).and_then(|(_, p)| *p),
+            |node| !disabled.contains(&node),
+        )
+    }
+
+    #[test]
+    fn disabled_middle_node_disables_its_descendants_only() {
+        let chain = [(0, None), (1, Some(0)), (2, Some(1))];
+        assert!(run(&chain, &[]).is_empty());
+        let disabled = run(&chain, &[1]);
+        assert!(!disabled.contains(&0));
+        assert!(!disabled.contains(&1), "Only descendants are flagged");
+        assert!(disabled.contains(&2));
+    }
+
+    #[test]
+    fn enabled_child_of_disabled_parent_is_effectively_disabled() {
+        let nodes = [(0, None), (1, Some(0))];
+        assert_eq!(run(&nodes, &[0]), [1].iter().cloned().collect());
+        assert!(
+            run(&nodes, &[]).is_empty(),
+            "Enabling the parent clears the flag"
+        );
+    }
+
+    #[test]
+    fn disabled_root_disables_the_whole_chain() {
+        let chain = [(0, None), (1, Some(0)), (2, Some(1)), (3, Some(0))];
+        let disabled = run(&chain, &[0]);
+        assert_eq!(disabled, [1, 2, 3].iter().cloned().collect());
+    }
+}
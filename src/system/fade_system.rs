@@ -0,0 +1,44 @@
+//! System fading the `Opacity` of entities.
+
+use crate::component::{EffectivelyDisabled, Enabled, Opacity};
+use crate::resource::Time;
+use specs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+
+/// Advances the fading `Opacity` of active entities by the scaled delta time. Entities that
+/// are back to full opacity lose the component, so that they return to their materials'
+/// pass.
+pub struct FadeSystem;
+
+impl<'a> System<'a> for FadeSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, Opacity>,
+        ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, mut opacities, enabled, effectively_disabled): Self::SystemData,
+    ) {
+        let delta = time.get_delta();
+        if delta <= 0. {
+            return;
+        }
+        let mut restored = Vec::new();
+        let active = (&enabled, !&effectively_disabled);
+        for (entity, opacity, _) in (&entities, &mut opacities, active).join() {
+            if !opacity.is_fading() {
+                continue;
+            }
+            opacity.advance(delta);
+            if !opacity.is_fading() && opacity.is_opaque() {
+                restored.push(entity);
+            }
+        }
+        for entity in restored {
+            opacities.remove(entity);
+        }
+    }
+}
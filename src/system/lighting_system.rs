@@ -1,12 +1,55 @@
 //! System for registering lights before rendering
 
-use crate::component::{Cone, Direction, Enabled, Light, Transform};
+use crate::component::{
+    point_lumens_to_candela, spot_lumens_to_candela, Attenuation, Camera, Cone, Direction,
+    EffectivelyDisabled, Enabled, Hemisphere, Light, Transform,
+};
 use crate::renderer::{LightConfiguration, LightRepository};
+use crate::resource::ActiveCamera;
+use crate::utils::constants::{MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use crate::utils::LightUnits;
 use nalgebra::{Vector3, Vector4};
-use specs::{Entities, Join, ReadStorage, System, Write};
+use specs::{Entities, Join, Read, ReadStorage, System, Write};
 
 pub struct LightingSystem;
 
+/// Kind of light an entity with a `Light` and without a `Hemisphere` is, with the components
+/// it's made of.
+#[derive(Debug, PartialEq)]
+enum LightKind<T, D, C> {
+    /// A `Direction` without a `Cone`, whether there is a `Transform` or not
+    Directional(D),
+
+    /// A `Transform` only
+    Point(T),
+
+    /// A `Transform`, a `Direction` and a `Cone`
+    Spot(T, D, C),
+
+    /// None of them
+    Ambient,
+
+    /// A `Cone` lacking a `Direction` or a `Transform`
+    Incomplete,
+}
+
+/// Classifies a light from its optional `Transform`, `Direction` and `Cone`.
+fn classify_light<T, D, C>(
+    transform: Option<T>,
+    direction: Option<D>,
+    cone: Option<C>,
+) -> LightKind<T, D, C> {
+    match (transform, direction, cone) {
+        (_, Some(direction), None) => LightKind::Directional(direction),
+        (Some(transform), None, None) => LightKind::Point(transform),
+        (Some(transform), Some(direction), Some(cone)) => {
+            LightKind::Spot(transform, direction, cone)
+        }
+        (None, None, None) => LightKind::Ambient,
+        _ => LightKind::Incomplete,
+    }
+}
+
 impl<'a> System<'a> for LightingSystem {
     type SystemData = (
         Entities<'a>,
@@ -14,7 +57,11 @@ impl<'a> System<'a> for LightingSystem {
         ReadStorage<'a, Transform>,
         ReadStorage<'a, Direction>,
         ReadStorage<'a, Cone>,
+        ReadStorage<'a, Hemisphere>,
         ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+        ReadStorage<'a, Camera>,
+        Read<'a, ActiveCamera>,
         Write<'a, LightRepository>,
         Write<'a, LightConfiguration>,
     );
@@ -26,60 +73,149 @@ impl<'a> System<'a> for LightingSystem {
             transforms,
             directions,
             cones,
+            hemispheres,
             enableds,
+            effectively_disabled,
+            cameras,
+            active_camera,
             mut light_repository,
             mut light_configuration,
         ): Self::SystemData,
     ) {
         light_repository.ambiant = None;
+        light_repository.hemisphere = None;
         light_repository.directional.clear();
         light_repository.point.clear();
         light_repository.spot.clear();
         let mut ambiant = Light {
             color: Vector3::new(0.0, 0.0, 0.0),
             intensity: 0.0,
-            attenuation: 0.0,
+            attenuation: Attenuation::None,
         };
         let mut some_ambiant = false;
-        for (entity, light, _) in (&entities, &lights, &enableds).join() {
-            let direction_opt = directions.get(entity);
-            let transform_opt = transforms.get(entity);
-            let cone_opt = cones.get(entity);
-            if let (Some(direction), None) = (direction_opt, cone_opt) {
-                light_repository
-                    .directional
-                    .push((light.clone(), direction.0));
-            } else if let (Some(transform), None, None) = (transform_opt, cone_opt, direction_opt) {
-                let world_position =
-                    transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
-                let factor = world_position.w;
-                light_repository.point.push((
-                    light.clone(),
-                    Vector3::new(world_position.x/factor, world_position.y/factor, world_position.z/factor),
-                ));
-            } else if let (Some(direction), Some(cone), Some(transform)) =
-                (direction_opt, cone_opt, transform_opt)
-            {
-                let world_position =
-                    transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
-                let factor = world_position.w;
-                light_repository.spot.push((
-                    light.clone(),
-                    Vector3::new(world_position.x/factor, world_position.y/factor, world_position.z/factor),
-                    direction.0,
-                    cone.clone(),
-                ));
-            } else if let (None, None, None) = (transform_opt, cone_opt, direction_opt) {
-                some_ambiant = true;
-                ambiant.color = ambiant.color * ambiant.intensity + light.color * light.intensity;
-                ambiant.intensity = ambiant.intensity + light.intensity;
+        let physical = light_repository.units == LightUnits::Physical;
+        let active = (&enableds, !&effectively_disabled);
+        for (entity, light, _) in (&entities, &lights, active).join() {
+            if let Some(hemisphere) = hemispheres.get(entity) {
+                if light_repository.hemisphere.is_some() {
+                    warn_once!("Only one hemisphere light is supported, the others are ignored.");
+                } else {
+                    let mut hemisphere = hemisphere.clone();
+                    hemisphere.up = hemisphere
+                        .up
+                        .try_normalize(std::f32::EPSILON)
+                        .unwrap_or(Vector3::y());
+                    light_repository.hemisphere = Some((light.clone(), hemisphere));
+                }
+                continue;
+            }
+            let kind = classify_light(
+                transforms.get(entity),
+                directions.get(entity),
+                cones.get(entity),
+            );
+            match kind {
+                LightKind::Directional(direction) => {
+                    light_repository
+                        .directional
+                        .push((light.clone(), direction.0));
+                }
+                LightKind::Point(transform) => {
+                    let world_position =
+                        transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+                    let factor = world_position.w;
+                    let mut light = light.clone();
+                    if physical {
+                        light.intensity = point_lumens_to_candela(light.intensity);
+                    }
+                    light_repository.point.push((
+                        light,
+                        Vector3::new(
+                            world_position.x / factor,
+                            world_position.y / factor,
+                            world_position.z / factor,
+                        ),
+                    ));
+                }
+                LightKind::Spot(transform, direction, cone) => {
+                    let world_position =
+                        transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+                    let factor = world_position.w;
+                    let mut light = light.clone();
+                    if physical {
+                        light.intensity = spot_lumens_to_candela(light.intensity, cone.angle);
+                    }
+                    light_repository.spot.push((
+                        light,
+                        Vector3::new(
+                            world_position.x / factor,
+                            world_position.y / factor,
+                            world_position.z / factor,
+                        ),
+                        direction.0,
+                        cone.clone(),
+                    ));
+                }
+                LightKind::Ambient => {
+                    some_ambiant = true;
+                    ambiant.color =
+                        ambiant.color * ambiant.intensity + light.color * light.intensity;
+                    ambiant.intensity = ambiant.intensity + light.intensity;
+                }
+                LightKind::Incomplete => {
+                    #[cfg(feature = "debug")]
+                    warn_throttled!(
+                    5000,
+                    "Light entity {} has a Cone but lacks a Direction or a Transform: it is ignored.",
+                    entity.id()
+                );
+                }
             }
         }
         if some_ambiant {
             light_repository.ambiant = Some(ambiant);
         }
         light_configuration.directional = light_repository.directional.len();
-        light_configuration.point = light_repository.point.len();
-        light_configuration.spot = light_repository.spot.len();
+        let max_lights = light_repository.max_lights.unwrap_or(std::usize::MAX);
+        light_configuration.point = light_repository
+            .point
+            .len()
+            .min(MAX_POINT_LIGHTS)
+            .min(max_lights);
+        light_configuration.spot = light_repository
+            .spot
+            .len()
+            .min(MAX_SPOT_LIGHTS)
+            .min(max_lights);
+        if let Some(camera) = active_camera.entity.and_then(|entity| cameras.get(entity)) {
+            light_configuration.tone_mapping = camera.get_tone_mapping();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Classifies a light having the components flagged `true`.
+    fn classify(transform: bool, direction: bool, cone: bool) -> LightKind<char, char, char> {
+        let component = |present: bool, name: char| if present { Some(name) } else { None };
+        classify_light(
+            component(transform, 'T'),
+            component(direction, 'D'),
+            component(cone, 'C'),
+        )
+    }
+
+    #[test]
+    fn every_component_combination_is_classified() {
+        assert_eq!(classify(false, false, false), LightKind::Ambient);
+        assert_eq!(classify(true, false, false), LightKind::Point('T'));
+        assert_eq!(classify(false, true, false), LightKind::Directional('D'));
+        assert_eq!(classify(true, true, false), LightKind::Directional('D'));
+        assert_eq!(classify(true, true, true), LightKind::Spot('T', 'D', 'C'));
+        assert_eq!(classify(false, false, true), LightKind::Incomplete);
+        assert_eq!(classify(true, false, true), LightKind::Incomplete);
+        assert_eq!(classify(false, true, true), LightKind::Incomplete);
     }
 }
@@ -0,0 +1,41 @@
+//! System switching the `MeshData` of entities with a `Lod` component.
+
+use crate::component::{Camera, Lod, Mesh, Transform};
+use crate::resource::ActiveCamera;
+use nalgebra::{Vector3, Vector4};
+use specs::{Join, Read, ReadStorage, System, WriteStorage};
+
+/// Measures the distance between each entity with a `Lod` and the active camera, and makes
+/// its `Mesh` use the matching level of detail.
+pub struct LodSystem;
+
+impl<'a> System<'a> for LodSystem {
+    type SystemData = (
+        WriteStorage<'a, Lod>,
+        WriteStorage<'a, Mesh>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Camera>,
+        Read<'a, ActiveCamera>,
+    );
+
+    fn run(
+        &mut self,
+        (mut lods, mut meshes, transforms, cameras, active_camera): Self::SystemData,
+    ) {
+        let camera_position = match active_camera.entity.and_then(|entity| cameras.get(entity)) {
+            Some(camera) => camera.get_eye_position(),
+            None => return,
+        };
+        for (lod, mesh, transform) in (&mut lods, &mut meshes, &transforms).join() {
+            let world_position = transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+            let position = Vector3::new(world_position.x, world_position.y, world_position.z)
+                / world_position.w;
+            lod.update((position - camera_position).norm());
+            if let Some(mesh_data_id) = lod.get_current_mesh_data_id() {
+                if mesh.get_mesh_data_id() != &mesh_data_id {
+                    mesh.set_mesh_data_id(mesh_data_id);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! System drawing the editor's transform manipulator, and the geometry used to pick it.
+
+use crate::component::{Camera, Selected, Transform};
+use crate::math::Ray;
+use crate::renderer::Renderer;
+use crate::resource::{ActiveCamera, Manipulator, ManipulatorMode};
+use nalgebra::Vector3;
+use specs::{Entities, Entity, Join, Read, ReadStorage, System};
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+/// Length of the manipulator's axes, as a fraction of its distance to the camera, so that
+/// it keeps about the same size on screen.
+const GIZMO_SCREEN_SIZE: f32 = 0.15;
+
+/// Largest distance between the pointer's ray and an axis that still grabs it, as a
+/// fraction of the axis length.
+const PICK_TOLERANCE: f32 = 0.08;
+
+/// Number of segments of the rotation circles.
+const CIRCLE_SEGMENTS: usize = 48;
+
+/// Color of the axis under the pointer or being dragged.
+const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 0.9, 0.1];
+
+/// ## Gizmo
+///
+/// Position, axes and size of the manipulator drawn over an entity.
+pub struct Gizmo {
+    /// World position of the entity
+    pub center: Vector3<f32>,
+
+    /// World space unit axes: the world's for translation and rotation, the entity's own for
+    /// scale
+    pub axes: [Vector3<f32>; 3],
+
+    /// Length of the arrows and radius of the circles, in world units
+    pub size: f32,
+}
+
+impl Gizmo {
+    /// Computes the manipulator of an entity with `transform`, in `mode`, seen from
+    /// `eye_position`.
+    pub fn new(transform: &Transform, mode: ManipulatorMode, eye_position: &Vector3<f32>) -> Gizmo {
+        let world_matrix = transform.get_world_matrix();
+        let center = Vector3::new(
+            world_matrix[(0, 3)],
+            world_matrix[(1, 3)],
+            world_matrix[(2, 3)],
+        );
+        let world_axes = [Vector3::x(), Vector3::y(), Vector3::z()];
+        let mut axes = world_axes;
+        if mode == ManipulatorMode::Scale {
+            for (index, axis) in axes.iter_mut().enumerate() {
+                let column = world_matrix.fixed_slice::<nalgebra::U3, nalgebra::U1>(0, index);
+                *axis = column
+                    .try_normalize(std::f32::EPSILON)
+                    .unwrap_or(world_axes[index]);
+            }
+        }
+        Gizmo {
+            center: center,
+            axes: axes,
+            size: ((center - eye_position).norm() * GIZMO_SCREEN_SIZE).max(std::f32::EPSILON),
+        }
+    }
+
+    /// Returns the axis of the manipulator `ray` passes closest to, within the pick
+    /// tolerance, and the point where it grabs it. See `ManipulatorDrag::grab_point`.
+    pub fn pick(&self, ray: &Ray, mode: ManipulatorMode) -> Option<(usize, Vector3<f32>)> {
+        let tolerance = self.size * PICK_TOLERANCE;
+        let mut picked: Option<(usize, Vector3<f32>, f32)> = None;
+        for (index, axis) in self.axes.iter().enumerate() {
+            let hit = match mode {
+                ManipulatorMode::Translate | ManipulatorMode::Scale => {
+                    closest_points(ray, &self.center, axis).and_then(|(distance, along_axis)| {
+                        let on_axis = self.center + axis * along_axis;
+                        let gap = (ray.point_at(distance) - on_axis).norm();
+                        if distance >= 0.
+                            && gap <= tolerance
+                            && along_axis >= 0.
+                            && along_axis <= self.size + tolerance
+                        {
+                            Some((on_axis, distance))
+                        } else {
+                            None
+                        }
+                    })
+                }
+                ManipulatorMode::Rotate => {
+                    intersect_plane(ray, &self.center, axis).and_then(|distance| {
+                        let on_plane = ray.point_at(distance);
+                        let gap = ((on_plane - self.center).norm() - self.size).abs();
+                        if gap <= tolerance {
+                            Some((on_plane, distance))
+                        } else {
+                            None
+                        }
+                    })
+                }
+            };
+            if let Some((point, distance)) = hit {
+                if picked.map_or(true, |(_, _, closest)| distance < closest) {
+                    picked = Some((index, point, distance));
+                }
+            }
+        }
+        picked.map(|(index, point, _)| (index, point))
+    }
+
+    /// Queues the lines of the manipulator to the renderer's debug lines, which are drawn
+    /// over the scene. `highlighted` is drawn in the highlight color.
+    pub fn push_lines(
+        &self,
+        renderer: &mut Renderer,
+        mode: ManipulatorMode,
+        highlighted: Option<usize>,
+    ) -> () {
+        for (index, axis) in self.axes.iter().enumerate() {
+            let color = if highlighted == Some(index) {
+                Vector3::from(HIGHLIGHT_COLOR)
+            } else {
+                let mut color = Vector3::zeros();
+                color[index] = 1.0;
+                color
+            };
+            let (u, v) = get_perpendiculars(axis);
+            let tip = self.center + axis * self.size;
+            match mode {
+                ManipulatorMode::Translate => {
+                    renderer.push_debug_line(&self.center, &tip, &color);
+                    let head_base = self.center + axis * (self.size * 0.85);
+                    for side in &[u, -u, v, -v] {
+                        let corner = head_base + side * (self.size * 0.05);
+                        renderer.push_debug_line(&tip, &corner, &color);
+                    }
+                }
+                ManipulatorMode::Rotate => {
+                    let point_at = |segment: usize| {
+                        let angle = segment as f32 * 2. * PI / CIRCLE_SEGMENTS as f32;
+                        self.center + (u * angle.cos() + v * angle.sin()) * self.size
+                    };
+                    for segment in 0..CIRCLE_SEGMENTS {
+                        renderer.push_debug_line(
+                            &point_at(segment),
+                            &point_at(segment + 1),
+                            &color,
+                        );
+                    }
+                }
+                ManipulatorMode::Scale => {
+                    renderer.push_debug_line(&self.center, &tip, &color);
+                    let half_size = self.size * 0.05;
+                    let mut corners = [Vector3::zeros(); 8];
+                    for (corner_index, corner) in corners.iter_mut().enumerate() {
+                        *corner = tip;
+                        for (bit, box_axis) in self.axes.iter().enumerate() {
+                            let sign = if corner_index & (1 << bit) == 0 {
+                                -1.
+                            } else {
+                                1.
+                            };
+                            *corner += box_axis * (sign * half_size);
+                        }
+                    }
+                    renderer.push_debug_box(&corners, &color);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the entity the manipulator is drawn over: the one being dragged, or the first
+/// selected entity with a `Transform`.
+pub fn get_manipulated_entity(
+    manipulator: &Manipulator,
+    entities: &Entities,
+    transforms: &ReadStorage<Transform>,
+    selecteds: &ReadStorage<Selected>,
+) -> Option<Entity> {
+    match &manipulator.drag {
+        Some(drag) => Some(drag.entity),
+        None => (entities, transforms, selecteds)
+            .join()
+            .next()
+            .map(|(entity, _, _)| entity),
+    }
+}
+
+/// Returns the distance along `ray` and along the line going through `origin` in
+/// `direction` of their closest points, or `None` if they are parallel. `direction` must
+/// be a unit vector.
+pub fn closest_points(
+    ray: &Ray,
+    origin: &Vector3<f32>,
+    direction: &Vector3<f32>,
+) -> Option<(f32, f32)> {
+    let offset = ray.origin - origin;
+    let alignment = ray.direction.dot(direction);
+    let ray_length = ray.direction.norm_squared();
+    let denominator = ray_length - alignment * alignment;
+    if denominator.abs() < 1e-6 {
+        return None;
+    }
+    let ray_offset = ray.direction.dot(&offset);
+    let line_offset = direction.dot(&offset);
+    let distance = (alignment * line_offset - ray_offset) / denominator;
+    let along_line = (ray_length * line_offset - alignment * ray_offset) / denominator;
+    Some((distance, along_line))
+}
+
+/// Returns the distance along `ray` where it crosses the plane going through `point` with
+/// `normal`, or `None` if it's parallel to the plane or points away from it.
+pub fn intersect_plane(ray: &Ray, point: &Vector3<f32>, normal: &Vector3<f32>) -> Option<f32> {
+    let facing = ray.direction.dot(normal);
+    if facing.abs() < 1e-6 {
+        return None;
+    }
+    let distance = (point - ray.origin).dot(normal) / facing;
+    if distance >= 0. {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+/// Returns two unit vectors perpendicular to `axis` and to each other.
+fn get_perpendiculars(axis: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let reference = if axis.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let u = axis.cross(&reference).normalize();
+    (u, axis.cross(&u))
+}
+
+/// Draws the manipulator over the selected entity, with the renderer's debug lines.
+pub struct ManipulatorSystem {
+    renderer: Rc<RefCell<Renderer>>,
+}
+
+impl ManipulatorSystem {
+    pub fn new(renderer: Rc<RefCell<Renderer>>) -> ManipulatorSystem {
+        ManipulatorSystem { renderer: renderer }
+    }
+}
+
+impl<'a> System<'a> for ManipulatorSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Selected>,
+        ReadStorage<'a, Camera>,
+        Read<'a, ActiveCamera>,
+        Read<'a, Manipulator>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, transforms, selecteds, cameras, active_camera, manipulator): Self::SystemData,
+    ) {
+        let eye_position = match active_camera.entity.and_then(|entity| cameras.get(entity)) {
+            Some(camera) => camera.get_eye_position(),
+            None => return,
+        };
+        let transform = get_manipulated_entity(&manipulator, &entities, &transforms, &selecteds)
+            .and_then(|entity| transforms.get(entity));
+        if let Some(transform) = transform {
+            let gizmo = Gizmo::new(transform, manipulator.mode, &eye_position);
+            let highlighted = match &manipulator.drag {
+                Some(drag) => Some(drag.axis),
+                None => manipulator.hovered_axis,
+            };
+            gizmo.push_lines(
+                &mut self.renderer.borrow_mut(),
+                manipulator.mode,
+                highlighted,
+            );
+        }
+    }
+}
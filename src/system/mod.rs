@@ -1,9 +1,37 @@
+mod blob_shadow_system;
+mod camera_aspect_system;
+mod camera_shake_system;
+mod constraint_system;
+mod culling_system;
+mod enabled_propagation_system;
+mod fade_system;
 mod lighting_system;
+mod lod_system;
+#[cfg(feature = "editor")]
+mod manipulator_system;
+mod particle_system;
+mod path_follower_system;
 mod rendering_system;
 mod scene_graph_system;
 mod shader_compilation_system;
+mod velocity_system;
 
+pub use blob_shadow_system::BlobShadowSystem;
+pub use camera_aspect_system::CameraAspectSystem;
+pub use camera_shake_system::CameraShakeSystem;
+pub use constraint_system::ConstraintSystem;
+pub use culling_system::{get_local_bounds, CullingSystem};
+pub use enabled_propagation_system::EnabledPropagationSystem;
+pub use fade_system::FadeSystem;
 pub use lighting_system::*;
+pub use lod_system::LodSystem;
+#[cfg(feature = "editor")]
+pub use manipulator_system::{
+    closest_points, get_manipulated_entity, intersect_plane, Gizmo, ManipulatorSystem,
+};
+pub use particle_system::ParticleSystem;
+pub use path_follower_system::PathFollowerSystem;
 pub use rendering_system::RenderingSystem;
 pub use scene_graph_system::SceneGraphSystem;
 pub use shader_compilation_system::ShaderCompilationSystem;
+pub use velocity_system::VelocitySystem;
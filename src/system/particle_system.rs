@@ -0,0 +1,37 @@
+//! System simulating the particles of every `ParticleEmitter`.
+
+use crate::component::{EffectivelyDisabled, Enabled, ParticleEmitter, Transform};
+use crate::resource::Time;
+use nalgebra::{Vector3, Vector4};
+use specs::{Join, Read, ReadStorage, System, WriteStorage};
+
+/// Advances every enabled `ParticleEmitter` by the delta time, spawning new particles
+/// at its entity's world position. Idle emitters are skipped.
+pub struct ParticleSystem;
+
+impl<'a> System<'a> for ParticleSystem {
+    type SystemData = (
+        WriteStorage<'a, ParticleEmitter>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+        Read<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut emitters, transforms, enableds, effectively_disabled, time): Self::SystemData,
+    ) {
+        let delta = time.get_delta();
+        let active = (&enableds, !&effectively_disabled);
+        for (emitter, transform, _) in (&mut emitters, &transforms, active).join() {
+            if emitter.is_idle() {
+                continue;
+            }
+            let world_position = transform.get_world_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+            let origin = Vector3::new(world_position.x, world_position.y, world_position.z)
+                / world_position.w;
+            emitter.update(delta, &origin);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! System moving entities with a `PathFollower` along their curve.
+
+use crate::component::{DirtyTransform, EffectivelyDisabled, Enabled, PathFollower, Transform};
+use crate::resource::Time;
+use nalgebra::UnitQuaternion;
+use specs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+
+/// Advances active `PathFollower`s by the scaled delta time and moves their entity's
+/// translation to the curve, turning it along the curve if they orient it, and flags them
+/// `DirtyTransform`. Followers that finished their curve keep their component, and are left
+/// at its end.  
+/// Runs after the `VelocitySystem`, which it overrides, and before the `ConstraintSystem`
+/// and the `SceneGraphSystem`.
+pub struct PathFollowerSystem;
+
+impl<'a> System<'a> for PathFollowerSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, PathFollower>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, DirtyTransform>,
+        ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, mut followers, mut transforms, mut dirty, enabled, effectively_disabled): Self::SystemData,
+    ) {
+        let delta = time.get_delta();
+        if delta <= 0. {
+            return;
+        }
+        let active = (&enabled, !&effectively_disabled);
+        for (entity, follower, transform, _) in
+            (&entities, &mut followers, &mut transforms, active).join()
+        {
+            if follower.is_finished() {
+                continue;
+            }
+            follower.advance(delta);
+            let (position, tangent) = follower.sample();
+            transform.set_translation(&position);
+            // Tangents along the up vector give no rotation.
+            if follower.orient && tangent.cross(&follower.up).norm_squared() > std::f32::EPSILON {
+                let rotation = UnitQuaternion::face_towards(&tangent, &follower.up);
+                transform.set_rotation_quaternion(&rotation);
+            }
+            dirty.insert(entity, DirtyTransform).ok();
+        }
+    }
+}
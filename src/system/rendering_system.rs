@@ -1,8 +1,15 @@
-use crate::component::{Enabled, Mesh, Transform};
-use crate::renderer::{LightRepository, Renderer, SortedMeshes};
-use specs::{Join, Read, ReadStorage, System};
+use crate::component::{
+    BlobShadow, Bounds, Camera, EffectivelyDisabled, Enabled, Mesh, Opacity, Overlay,
+    ParticleEmitter, Selected, Sprite, Transform, UniformOverrides,
+};
+use crate::math::{Aabb, BoundingSphere};
+use crate::renderer::{
+    LightConfiguration, LightRepository, LightSelections, Renderer, SortedMeshes,
+};
+use crate::resource::{ActiveCamera, DrawnEntities, Time, Visibility};
+use specs::{Entities, Join, Read, ReadStorage, System, Write};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
 
 pub struct RenderingSystem {
@@ -13,36 +20,214 @@ impl RenderingSystem {
     pub fn new(renderer: Rc<RefCell<Renderer>>) -> RenderingSystem {
         RenderingSystem { renderer: renderer }
     }
+
+    /// Returns `true` if the mesh data and materials of `mesh` are registered and its
+    /// materials are compiled, so that drawing it can succeed.
+    fn is_drawable(&self, mesh: &Mesh) -> bool {
+        let renderer = self.renderer.borrow();
+        let asset_registry = renderer.get_asset_registry();
+        asset_registry
+            .get_mesh_data_with_index(*mesh.get_mesh_data_id())
+            .is_some()
+            && mesh
+                .get_sub_mesh_materials()
+                .iter()
+                .all(|(material_instance_id, material_id)| {
+                    asset_registry
+                        .get_material_instance_with_index(*material_instance_id)
+                        .is_some()
+                        && asset_registry
+                            .get_material_with_index(*material_id)
+                            .map_or(false, |material| material.borrow().get_program().is_some())
+                })
+    }
 }
 
-// ⭕ TODO : Only render objects that are in the camera's reach
 impl<'a> System<'a> for RenderingSystem {
     type SystemData = (
+        Entities<'a>,
         ReadStorage<'a, Mesh>,
         ReadStorage<'a, Transform>,
         ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
         Read<'a, LightRepository>,
+        ReadStorage<'a, ParticleEmitter>,
+        ReadStorage<'a, Sprite>,
+        ReadStorage<'a, Overlay>,
+        ReadStorage<'a, BlobShadow>,
+        Read<'a, Visibility>,
+        Read<'a, Time>,
+        ReadStorage<'a, UniformOverrides>,
+        ReadStorage<'a, Camera>,
+        Read<'a, ActiveCamera>,
+        ReadStorage<'a, Bounds>,
+        Read<'a, LightConfiguration>,
+        Write<'a, DrawnEntities>,
+        ReadStorage<'a, Selected>,
+        ReadStorage<'a, Opacity>,
     );
-    fn run(&mut self, (mesh, transform, enabled, light_repository): Self::SystemData) {
-        let mut sorted_meshes: SortedMeshes = HashMap::new();
-        for (mesh, transform, _) in (&mesh, &transform, &enabled).join() {
-            let material_id = mesh.get_material_id();
+    fn run(
+        &mut self,
+        (
+            entities,
+            mesh,
+            transform,
+            enabled,
+            effectively_disabled,
+            light_repository,
+            emitters,
+            sprites,
+            overlays,
+            blob_shadows,
+            visibility,
+            time,
+            uniform_overrides,
+            cameras,
+            active_camera,
+            bounds,
+            light_configuration,
+            mut drawn_entities,
+            selected,
+            opacities,
+        ): Self::SystemData,
+    ) {
+        let mut sorted_meshes: SortedMeshes = BTreeMap::new();
+        // Meshes of entities with an opacity below 1, drawn blended after the others.
+        let mut faded_meshes: SortedMeshes = BTreeMap::new();
+        // World bounds of each batch, `None` if one of its meshes has unknown bounds.
+        let mut batch_bounds: HashMap<(usize, usize), Option<Aabb>> = HashMap::new();
+        let visible_meshes = (
+            &entities,
+            &mesh,
+            &transform,
+            uniform_overrides.maybe(),
+            opacities.maybe(),
+            visibility.get_visible(),
+        );
+        drawn_entities.clear();
+        for (entity, mesh, transform, overrides, opacity, _) in visible_meshes.join() {
+            if self.is_drawable(mesh) {
+                drawn_entities.set_drawn(entity.id());
+            }
             let mesh_data_id = mesh.get_mesh_data_id();
-            let mesh_instance_id = mesh.get_material_instance_id();
-            if let Some(mesh_hash_map) = sorted_meshes.get_mut(material_id) {
-                if let Some(transform_vec) = mesh_hash_map.get_mut(mesh_data_id) {
-                    transform_vec.push((mesh_instance_id, &transform));
-                } else {
-                    mesh_hash_map.insert(mesh_data_id, vec![(mesh_instance_id, &transform)]);
-                }
-            } else {
-                let mut mesh_hash_map = HashMap::new();
-                mesh_hash_map.insert(mesh_data_id, vec![(mesh_instance_id, transform)]);
-                sorted_meshes.insert(material_id, mesh_hash_map);
+            let local_bounds: Option<BoundingSphere> = match bounds.get(entity) {
+                Some(bounds) => Some(bounds.sphere),
+                None => self
+                    .renderer
+                    .borrow()
+                    .get_asset_registry()
+                    .get_mesh_data_with_index(*mesh_data_id)
+                    .and_then(|mesh_data| mesh_data.borrow().get_bounds()),
+            };
+            let world_bounds = local_bounds.map(|sphere| {
+                Aabb::from_sphere(&sphere.transformed(&transform.get_world_matrix()))
+            });
+            let sub_mesh_materials = mesh.get_sub_mesh_materials().iter().enumerate();
+            for (sub_mesh, (mesh_instance_id, material_id)) in sub_mesh_materials {
+                batch_bounds
+                    .entry((*material_id, *mesh_data_id))
+                    .and_modify(|batch| {
+                        *batch = match (*batch, world_bounds) {
+                            (Some(batch), Some(world_bounds)) => Some(batch.merged(&world_bounds)),
+                            _ => None,
+                        }
+                    })
+                    .or_insert(world_bounds);
+                let (meshes, opacity) = match opacity {
+                    Some(opacity) if !opacity.is_opaque() => (&mut faded_meshes, opacity.value),
+                    _ => (&mut sorted_meshes, 1.),
+                };
+                meshes
+                    .entry(material_id)
+                    .or_insert_with(BTreeMap::new)
+                    .entry(mesh_data_id)
+                    .or_insert_with(Vec::new)
+                    .push((mesh_instance_id, transform, sub_mesh, overrides, opacity));
             }
         }
-        self.renderer
-            .borrow_mut()
-            .render_objects(sorted_meshes, &light_repository);
+        let light_selections: LightSelections = batch_bounds
+            .into_iter()
+            .map(|(batch, bounds)| {
+                let lights = light_repository.select_lights(
+                    bounds.as_ref(),
+                    light_configuration.point,
+                    light_configuration.spot,
+                );
+                (batch, lights)
+            })
+            .collect();
+        let visible_blob_shadows: Vec<&BlobShadow> =
+            (&blob_shadows, &enabled, !&effectively_disabled)
+                .join()
+                .map(|(blob_shadow, _, _)| blob_shadow)
+                .collect();
+        let visible_sprites: Vec<(&Sprite, &Transform)> =
+            (&sprites, &transform, &enabled, !&effectively_disabled)
+                .join()
+                .map(|(sprite, transform, _, _)| (sprite, transform))
+                .collect();
+        let live_emitters: Vec<(u32, &ParticleEmitter)> =
+            (&entities, &emitters, &enabled, !&effectively_disabled)
+                .join()
+                .filter(|(_, emitter, _, _)| emitter.get_live_count() > 0)
+                .map(|(entity, emitter, _, _)| (entity.id(), emitter))
+                .collect();
+        let outlined: Vec<(usize, &Transform)> =
+            (&mesh, &transform, &selected, visibility.get_visible())
+                .join()
+                .map(|(mesh, transform, _, _)| (*mesh.get_mesh_data_id(), transform))
+                .collect();
+        let visible_overlays: Vec<&Overlay> = (&overlays, &enabled, !&effectively_disabled)
+            .join()
+            .map(|(overlay, _, _)| overlay)
+            .collect();
+        let mut renderer = self.renderer.borrow_mut();
+        let _gl_state = renderer.save_gl_state();
+        renderer.set_time(time.get_elapsed(), time.get_unscaled_delta());
+        renderer.set_interpolation(time.get_interpolation());
+        if let Some(camera) = active_camera.entity.and_then(|entity| cameras.get(entity)) {
+            renderer.set_camera_shake_offset(camera.get_shake_offset());
+            renderer.set_camera_aspect_ratio(camera.get_aspect_ratio());
+            renderer.set_camera_exposure(camera.get_exposure());
+            renderer.set_camera_tone_mapping(camera.get_tone_mapping());
+        }
+        let used_mesh_data: HashSet<usize> = sorted_meshes
+            .values()
+            .flat_map(|batches| batches.keys().map(|mesh_data_id| **mesh_data_id))
+            .chain(outlined.iter().map(|(mesh_data_id, _)| *mesh_data_id))
+            .collect();
+        renderer.use_mesh_data(used_mesh_data.into_iter());
+        let emitter_ids: HashSet<u32> = (&entities, &emitters)
+            .join()
+            .map(|(entity, _)| entity.id())
+            .collect();
+        renderer.release_particle_buffers(&emitter_ids);
+        renderer.begin_frame();
+        let view_count = renderer.get_view_count();
+        for view in 0..view_count {
+            renderer.begin_view(view);
+            // Only the views before the last one need their own copy of the batches.
+            let (view_meshes, view_faded_meshes) = if view + 1 < view_count {
+                (sorted_meshes.clone(), faded_meshes.clone())
+            } else {
+                (
+                    std::mem::replace(&mut sorted_meshes, BTreeMap::new()),
+                    std::mem::replace(&mut faded_meshes, BTreeMap::new()),
+                )
+            };
+            renderer.render_objects(
+                view_meshes,
+                view_faded_meshes,
+                &visible_sprites,
+                &light_repository,
+                &light_selections,
+            );
+            renderer.render_outlines(&outlined);
+            renderer.render_blob_shadows(&visible_blob_shadows);
+            renderer.render_particles(&live_emitters);
+            renderer.render_debug_lines();
+            renderer.render_overlays(visible_overlays.clone());
+        }
+        renderer.end_frame();
     }
 }
@@ -1,8 +1,17 @@
-use crate::component::{DirtyTransform, Enabled, Transform, TransformParent};
-use specs::{Entities, Join, ReadExpect, ReadStorage, System, WriteStorage};
+use crate::component::{DirtyTransform, Transform, TransformParent};
+use crate::resource::{Time, TransformWatch};
+use specs::{Entities, Join, Read, ReadExpect, System, Write, WriteStorage};
 use specs_hierarchy::Hierarchy;
 use std::collections::HashMap;
+use std::hash::Hash;
 
+/// Refreshes the world matrices of the entities flagged `DirtyTransform`, and of their
+/// descendants. Disabled entities are refreshed as well, so that their world matrix is
+/// up to date whenever it's read or they are enabled again: `Enabled` only gates rendering,
+/// lighting and simulation.  
+/// Refreshed entities that are watched are recorded in `TransformWatch`.  
+/// In fixed timestep mode, it runs once per step and keeps every world matrix as the previous
+/// one first, for the renderer to interpolate between them.
 pub struct SceneGraphSystem;
 
 impl SceneGraphSystem {
@@ -17,25 +26,25 @@ impl<'a> System<'a> for SceneGraphSystem {
         ReadExpect<'a, Hierarchy<TransformParent>>,
         WriteStorage<'a, Transform>,
         WriteStorage<'a, DirtyTransform>,
-        ReadStorage<'a, Enabled>,
+        Write<'a, TransformWatch>,
+        Read<'a, Time>,
     );
-    fn run(&mut self, (entities, hierarchy, mut transforms, mut dirty, enabled): Self::SystemData) {
-        let mut dirty_transforms = HashMap::new();
-        for (entity, _, _, _) in (&entities, &mut transforms, &mut dirty, &enabled).join() {
-            let parent_entity_opt = hierarchy.parent(entity);
-            if let Some(parent_entity) = parent_entity_opt {
-                dirty_transforms.insert(entity, Some(parent_entity));
-            } else {
-                dirty_transforms.insert(entity, None);
-            }
-            for child in hierarchy.all_children_iter(entity) {
-                if !dirty_transforms.contains_key(&child) {
-                    if let Some(parent_entity) = hierarchy.parent(child) {
-                        dirty_transforms.insert(child, Some(parent_entity));
-                    }
-                }
+    fn run(
+        &mut self,
+        (entities, hierarchy, mut transforms, mut dirty, mut watch, time): Self::SystemData,
+    ) {
+        if time.get_fixed_step().is_some() {
+            for transform in (&mut transforms).join() {
+                transform.store_previous_world_matrix();
             }
         }
+        let dirty_transforms = collect_dirty_transforms(
+            (&entities, &transforms, &dirty)
+                .join()
+                .map(|(entity, _, _)| entity),
+            |entity| hierarchy.parent(entity),
+            |entity| hierarchy.all_children_iter(entity),
+        );
         for (entity, parent_entity_opt) in &dirty_transforms {
             if let None = parent_entity_opt {
                 transforms
@@ -58,5 +67,79 @@ impl<'a> System<'a> for SceneGraphSystem {
                 dirty.remove(*entity);
             }
         }
+        if !watch.is_empty() {
+            for entity in dirty_transforms.keys() {
+                watch.set_changed(entity.id());
+            }
+        }
+    }
+}
+
+/// Maps the `dirty` nodes and all their descendants to their parent, if any.
+fn collect_dirty_transforms<E, I, P, C, D>(
+    dirty: I,
+    parent: P,
+    descendants: D,
+) -> HashMap<E, Option<E>>
+where
+    E: Copy + Eq + Hash,
+    I: Iterator<Item = E>,
+    P: Fn(E) -> Option<E>,
+    D: Fn(E) -> C,
+    C: Iterator<Item = E>,
+{
+    let mut dirty_transforms = HashMap::new();
+    for node in dirty {
+        dirty_transforms.insert(node, parent(node));
+        for child in descendants(node) {
+            if !dirty_transforms.contains_key(&child) {
+                if let Some(parent_node) = parent(child) {
+                    dirty_transforms.insert(child, Some(parent_node));
+                }
+            }
+        }
+    }
+    dirty_transforms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(node, parent)` pairs of a hierarchy, parents first.
+    const NODES: [(u32, Option<u32>); 4] = [(0, None), (1, Some(0)), (2, Some(1)), (3, None)];
+
+    fn collect(dirty: &[u32]) -> HashMap<u32, Option<u32>> {
+        let parent = |node| NODES.iter().find(|(n, _)| *n == node).and_then(|(_, p)| *p);
+        let descendants = |node| {
+            let mut found = vec![node];
+            for (n, _) in NODES.iter() {
+                if parent(*n).map_or(false, |p| found.contains(&p)) {
+                    found.push(*n);
+                }
+            }
+            found.into_iter().skip(1)
+        };
+        collect_dirty_transforms(dirty.iter().cloned(), parent, descendants)
+    }
+
+    #[test]
+    fn moving_a_parent_refreshes_every_descendant() {
+        // Whether the parent or its children are enabled doesn't matter: a disabled parent
+        // still moves its enabled children, which are right once it's enabled again.
+        let dirty = collect(&[0]);
+        assert_eq!(dirty.len(), 3);
+        assert_eq!(dirty[&0], None);
+        assert_eq!(dirty[&1], Some(0));
+        assert_eq!(dirty[&2], Some(1));
+    }
+
+    #[test]
+    fn only_dirty_subtrees_are_refreshed() {
+        let dirty = collect(&[1]);
+        assert_eq!(dirty.len(), 2);
+        assert_eq!(dirty[&1], Some(0));
+        assert_eq!(dirty[&2], Some(1));
+        assert_eq!(collect(&[3]).len(), 1);
     }
 }
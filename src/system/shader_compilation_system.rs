@@ -1,6 +1,5 @@
-use crate::component::Mesh;
+use crate::component::{Lod, Mesh};
 use crate::renderer::{LightConfiguration, Renderer};
-use crate::utils::console_error;
 use specs::{Join, Read, ReadStorage, System};
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -16,11 +15,16 @@ impl ShaderCompilationSystem {
 }
 
 impl<'a> System<'a> for ShaderCompilationSystem {
-    type SystemData = (ReadStorage<'a, Mesh>, Read<'a, LightConfiguration>);
-    fn run(&mut self, (mesh, light_config): Self::SystemData) {
-        for mesh in (&mesh).join() {
-            match mesh.compile_material(self.renderer.clone(), &light_config) {
-                Err(message) => console_error(&message),
+    type SystemData = (
+        ReadStorage<'a, Mesh>,
+        ReadStorage<'a, Lod>,
+        Read<'a, LightConfiguration>,
+    );
+    fn run(&mut self, (mesh, lods, light_config): Self::SystemData) {
+        for (mesh, lod) in (&mesh, lods.maybe()).join() {
+            let lod_mesh_data_ids = lod.map(|lod| lod.get_mesh_data_ids()).unwrap_or(&[]);
+            match mesh.compile_material(self.renderer.clone(), &light_config, lod_mesh_data_ids) {
+                Err(error) => error_throttled!(5000, "{}", error),
                 _ => {}
             }
         }
@@ -0,0 +1,57 @@
+//! System integrating the `Velocity` of entities into their `Transform`.
+
+use crate::component::{DirtyTransform, EffectivelyDisabled, Enabled, Transform, Velocity};
+use crate::resource::Time;
+use nalgebra::UnitQuaternion;
+use specs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+
+/// Advances the translation and rotation of active entities with a `Velocity` by the scaled
+/// delta time, applying damping and the speed limit, and flags them `DirtyTransform`.  
+/// Must run before the `PathFollowerSystem`, the `ConstraintSystem` and the
+/// `SceneGraphSystem`: transform changes from JS are made before it and moved from, while
+/// path followers and constraints override it.
+pub struct VelocitySystem;
+
+impl<'a> System<'a> for VelocitySystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, DirtyTransform>,
+        ReadStorage<'a, Enabled>,
+        ReadStorage<'a, EffectivelyDisabled>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, mut velocities, mut transforms, mut dirty, enabled, effectively_disabled): Self::SystemData,
+    ) {
+        let delta = time.get_delta();
+        if delta <= 0. {
+            return;
+        }
+        let active = (&enabled, !&effectively_disabled);
+        for (entity, velocity, transform, _) in
+            (&entities, &mut velocities, &mut transforms, active).join()
+        {
+            if velocity.is_zero() {
+                continue;
+            }
+            velocity.linear *= (-velocity.linear_damping * delta).exp();
+            velocity.angular *= (-velocity.angular_damping * delta).exp();
+            if let Some(max_speed) = velocity.max_speed {
+                let speed = velocity.linear.norm();
+                if speed > max_speed {
+                    velocity.linear *= max_speed / speed;
+                }
+            }
+            let translation = transform.get_translation() + velocity.linear * delta;
+            transform.set_translation(&translation);
+            let rotation = UnitQuaternion::from_scaled_axis(velocity.angular * delta)
+                * transform.get_rotation();
+            transform.set_rotation_quaternion(&rotation);
+            dirty.insert(entity, DirtyTransform).ok();
+        }
+    }
+}
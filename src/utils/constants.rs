@@ -13,19 +13,115 @@ pub const WORLD_TRANSFORM_NAME: &str = "u_world_transform";
 /// Name for the ambiant light uniform
 pub const AMBIANT_LIGHT_NAME: &str = "u_ambiant_light";
 
+/// Name for the hemisphere light sky color uniform, premultiplied by the light's intensity
+pub const HEMISPHERE_SKY_COLOR_NAME: &str = "u_hemisphere_sky_color";
+
+/// Name for the hemisphere light ground color uniform, premultiplied by the light's intensity
+pub const HEMISPHERE_GROUND_COLOR_NAME: &str = "u_hemisphere_ground_color";
+
+/// Name for the hemisphere light up direction uniform, in world space
+pub const HEMISPHERE_UP_NAME: &str = "u_hemisphere_up";
+
+/// Name for the uniform holding the seconds elapsed since the scene's first update.
+/// Being a 32 bits float, it loses precision after a few hours: prefer `TIME_WRAPPED_NAME`
+/// for periodic animations.
+pub const TIME_NAME: &str = "u_time";
+
+/// Name for the uniform holding the seconds elapsed since the previous frame
+pub const DELTA_TIME_NAME: &str = "u_delta_time";
+
+/// Name for the uniform holding the seconds elapsed since the scene's first update, modulo
+/// `TIME_WRAP_PERIOD`
+pub const TIME_WRAPPED_NAME: &str = "u_time_wrapped";
+
+/// Period of `TIME_WRAPPED_NAME`, in seconds. Animations whose period divides it loop
+/// seamlessly when it wraps.
+pub const TIME_WRAP_PERIOD: f64 = 3600.;
+
+/// Name for the UV scrolling speed uniform of the built-in decal material, in texture
+/// coordinates per second
+pub const UV_SCROLL_NAME: &str = "u_uv_scroll";
+
+/// Name for the fade distance uniform of the built-in grid helper material, in world units
+/// from the camera. `0` disables the fade.
+pub const FADE_DISTANCE_NAME: &str = "u_fade_distance";
+
+/// Name for the opacity of the entity being drawn, multiplied into the alpha of the
+/// materials declaring it: see `Opacity`. Set to `1` for entities without one.
+pub const ENTITY_OPACITY_NAME: &str = "u_entity_opacity";
+
+/// Name for the texture coordinates transform uniform, a `vec4` holding
+/// `(offset.x, offset.y, scale.x, scale.y)`: see `AtlasRegion`. A zero scale stands for the
+/// identity, so that instances that don't set it display their whole textures.
+pub const UV_TRANSFORM_NAME: &str = "u_uv_transform";
+
+/// Name for the environment cube map uniform
+pub const ENVIRONMENT_MAP_NAME: &str = "u_environment_map";
+
+/// Name for the reflectivity uniform of materials sampling the environment map
+pub const REFLECTIVITY_NAME: &str = "u_reflectivity";
+
+/// Preprocessor symbol defined in lit shaders when an environment map is set
+pub const ENVIRONMENT_MAP_DEFINE: &str = "ENVIRONMENT_MAP";
+
+/// Texture unit reserved for the environment map, after the units used by materials
+pub const ENVIRONMENT_MAP_TEXTURE_INDEX: u32 = 7;
+
+/// Preprocessor symbol replaced by the number of directional lights in lit shaders
+pub const NUM_DIR_LIGHTS_DEFINE: &str = "NUM_DIR_LIGHTS";
+
+/// Preprocessor symbol replaced by the number of point lights in lit shaders
+pub const NUM_POINT_LIGHTS_DEFINE: &str = "NUM_POINT_LIGHTS";
+
+/// Preprocessor symbol replaced by the number of spot lights in lit shaders
+pub const NUM_SPOT_LIGHTS_DEFINE: &str = "NUM_SPOT_LIGHTS";
+
+/// Name for the camera exposure uniform, a factor applied to colors before tone mapping
+pub const EXPOSURE_NAME: &str = "u_exposure";
+
+/// Preprocessor symbol defined to the active camera's tone mapping operator in fragment
+/// shaders mentioning it, along with the `tone_map` function
+pub const TONE_MAPPING_DEFINE: &str = "TONE_MAPPING";
+
+/// Preprocessor symbol defined in fragment shaders mentioning `TONE_MAPPING` while HDR
+/// rendering is enabled, making `tone_map` return colors as is
+pub const HDR_OUTPUT_DEFINE: &str = "HDR_OUTPUT";
+
+/// Maximum number of point lights lit shaders are compiled for. Each draw batch uploads the
+/// most relevant ones when more affect it.
+pub const MAX_POINT_LIGHTS: usize = 8;
+
+/// Maximum number of spot lights lit shaders are compiled for. Each draw batch uploads the
+/// most relevant ones when more affect it.
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+/// Number of triangles above which precise picking builds and caches a hierarchy over a
+/// mesh's triangles instead of testing them all.
+pub const PICKING_BVH_THRESHOLD: usize = 64;
+
 /// Name for the point lights array uniform
 pub const POINT_LIGHTS_NAME: &str = "u_point_lights";
 
+/// Name for the spot lights array uniform, of `SpotLight` structs
+pub const SPOT_LIGHTS_NAME: &str = "u_spot_lights";
+
 /// Name for the directional lights array uniform
 pub const DIRECTIONAL_LIGHTS_NAME: &str = "u_dir_lights";
 
 /// Name for the color field in the Light GLSL struct
 pub const LIGHT_COLOR_NAME: &str = "color";
 
+/// Name for the direction field of the SpotLight GLSL struct, which extends the Light struct
+pub const LIGHT_DIRECTION_NAME: &str = "direction";
+
+/// Name for the cone field of the SpotLight GLSL struct, a `vec2` holding `(angle, blend)`
+pub const LIGHT_CONE_NAME: &str = "cone";
+
 /// Name for the intensity field in the Light GLSL struct
 pub const LIGHT_INTENSITY_NAME: &str = "intensity";
 
-/// Name for the attenuation field in the Light GLSL struct
+/// Name for the attenuation field in the Light GLSL struct, a `vec4` holding
+/// `(constant, linear, quadratic, range)`: see `Attenuation`
 pub const LIGHT_ATTENUATION_NAME: &str = "attenuation";
 
 /// Name for the direction/position field in the Light GLSL struct
@@ -39,3 +135,50 @@ pub const NORMAL_BUFFER_NAME: &str = "a_normal";
 
 /// UV (texture coordinates) buffer name used in shaders
 pub const UV_BUFFER_NAME: &str = "a_tex_coordinates";
+
+/// Vertex color buffer name used in built-in shaders
+pub const COLOR_BUFFER_NAME: &str = "a_color";
+
+/// Baked ambient occlusion buffer name, one float per vertex: see `MeshData::bake_vertex_ao`
+pub const AO_BUFFER_NAME: &str = "a_ao";
+
+/// Billboard corner offset buffer name used in built-in shaders
+pub const CORNER_BUFFER_NAME: &str = "a_corner";
+
+/// Opacity buffer name used in built-in shaders
+pub const OPACITY_BUFFER_NAME: &str = "a_opacity";
+
+/// Point size buffer name used in built-in shaders
+pub const SIZE_BUFFER_NAME: &str = "a_size";
+
+/// Name for the viewport height (in pixels) uniform
+pub const VIEWPORT_HEIGHT_NAME: &str = "u_viewport_height";
+
+/// Name for the texture uniform of built-in materials
+pub const TEXTURE_NAME: &str = "u_texture";
+
+/// Name for the alpha cutoff uniform of cutout materials
+pub const ALPHA_CUTOFF_NAME: &str = "u_alpha_cutoff";
+
+/// Preprocessor symbol defined in the fragment shader of cutout materials
+pub const ALPHA_CUTOFF_DEFINE: &str = "ALPHA_CUTOFF";
+
+/// Preprocessor symbol defined in the fragment shader of alpha hashed materials, along with
+/// the `alpha_hash` function
+pub const ALPHA_HASH_DEFINE: &str = "ALPHA_HASH";
+
+/// Preprocessor symbol defined in fragment shaders mentioning it while soft particles are
+/// enabled, along with the scene depth uniforms and the `soft_fade` function
+pub const SOFT_PARTICLES_DEFINE: &str = "USE_SOFT_PARTICLES";
+
+/// Name for the scene depth texture uniform, holding the depth of the opaque meshes
+pub const SCENE_DEPTH_NAME: &str = "u_scene_depth";
+
+/// Name for the camera near and far planes uniform, used to linearize depths
+pub const CAMERA_NEAR_FAR_NAME: &str = "u_camera_near_far";
+
+/// Name for the viewport size (in pixels) uniform
+pub const VIEWPORT_SIZE_NAME: &str = "u_viewport_size";
+
+/// Texture unit reserved for the scene depth texture, before the environment map's
+pub const SCENE_DEPTH_TEXTURE_INDEX: u32 = 6;
@@ -0,0 +1,296 @@
+//! Small logging facade used throughout the crate.
+//!
+//! Messages have a `LogLevel` and are filtered against a global level that can be set from JS
+//! using `initialize_with_log_level`. Messages that pass the filter are sent to the browser
+//! console, or to the log handler if one has been registered with `set_log_handler`.
+//!
+//! The `log_error!`, `log_warn!`, `log_info!` and `log_debug!` macros should be used rather than
+//! the functions of this module. `warn_once!`/`error_once!` only log the first time a given
+//! call site is reached, and `warn_throttled!`/`error_throttled!` log at most once per interval:
+//! they are meant for hot paths such as the render loop.
+//! `log_debug!` compiles to nothing in release builds.
+
+use crate::error::{W3DError, W3DErrorKind};
+use js_sys::Function;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use wasm_bindgen::prelude::*;
+
+/// Severity of a log message. Lower is more severe.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl LogLevel {
+    /// Parses a level from its lowercase name (`error`, `warn`, `info` or `debug`).
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    /// Returns the lowercase name of this level.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+/// Most verbose level that is currently logged.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+thread_local! {
+    /// Optional JS function receiving `(level, message)` instead of the console.
+    static LOG_HANDLER: RefCell<Option<Function>> = RefCell::new(None);
+}
+
+/// Sets the global log level from JS. Accepts `error`, `warn`, `info` or `debug`.
+#[wasm_bindgen]
+pub fn initialize_with_log_level(level: &str) -> Result<(), JsValue> {
+    match LogLevel::from_name(level) {
+        Some(log_level) => {
+            set_log_level(log_level);
+            Ok(())
+        }
+        None => {
+            Err(
+                W3DError::with_source(W3DErrorKind::InvalidArgument, "Unknown log level.", level)
+                    .into(),
+            )
+        }
+    }
+}
+
+/// Registers a JS function called with `(level, message)` for every message passing the level
+/// filter. Messages are then no longer sent to the console, unless the handler throws.  
+/// The handler is called in a microtask, after the engine call logging the message has
+/// returned, so that it can use the `Scene`.
+#[wasm_bindgen]
+pub fn set_log_handler(handler: Function) {
+    LOG_HANDLER.with(|cell| *cell.borrow_mut() = Some(handler));
+}
+
+/// Removes the handler registered with `set_log_handler`, logging to the console again.
+#[wasm_bindgen]
+pub fn clear_log_handler() {
+    LOG_HANDLER.with(|cell| *cell.borrow_mut() = None);
+}
+
+/// Sets the most verbose level that will be logged.
+pub fn set_log_level(level: LogLevel) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Returns `true` if messages of the given level are currently logged.
+pub fn is_enabled(level: LogLevel) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Logs a message coming from `module_path`. Prefer the logging macros to calling this directly.
+pub fn log(level: LogLevel, module_path: &str, message: &str) {
+    if !is_enabled(level) {
+        return;
+    }
+    let full_message = format_message(module_path, message);
+    write_message(level, &full_message);
+}
+
+/// Returns the current time in milliseconds since the Unix epoch, used to throttle messages.
+#[cfg(target_arch = "wasm32")]
+pub fn timestamp() -> f64 {
+    js_sys::Date::now()
+}
+
+/// Returns the current time in milliseconds since the Unix epoch, on native builds such as
+/// the unit tests.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn timestamp() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0., |duration| duration.as_secs_f64() * 1000.)
+}
+
+/// Sends a message to the log handler in a microtask, or to the browser console if there is
+/// none or if it throws.
+#[cfg(target_arch = "wasm32")]
+fn write_message(level: LogLevel, full_message: &str) {
+    match LOG_HANDLER.with(|cell| cell.borrow().clone()) {
+        Some(handler) => {
+            let message = full_message.to_owned();
+            super::call_in_microtask(move || {
+                let handled = handler.call2(
+                    &JsValue::NULL,
+                    &JsValue::from_str(level.get_name()),
+                    &JsValue::from_str(&message),
+                );
+                if handled.is_err() {
+                    write_to_console(level, &message);
+                }
+            });
+        }
+        None => write_to_console(level, full_message),
+    }
+}
+
+/// Writes a message to the browser console.
+#[cfg(target_arch = "wasm32")]
+fn write_to_console(level: LogLevel, full_message: &str) {
+    use web_sys::console::{debug_1, error_1, info_1, warn_1};
+    let value = JsValue::from_str(full_message);
+    match level {
+        LogLevel::Error => error_1(&value),
+        LogLevel::Warn => warn_1(&value),
+        LogLevel::Info => info_1(&value),
+        LogLevel::Debug => debug_1(&value),
+    }
+}
+
+/// Writes a message to the standard error, for native builds such as the unit tests.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_message(level: LogLevel, full_message: &str) {
+    eprintln!("[{}] {}", level.get_name(), full_message);
+}
+
+#[cfg(feature = "debug")]
+fn format_message(module_path: &str, message: &str) -> String {
+    format!("[{}] {}", module_path, message)
+}
+
+#[cfg(not(feature = "debug"))]
+fn format_message(_module_path: &str, message: &str) -> String {
+    message.to_owned()
+}
+
+/// Logs a formatted message at the given `LogLevel`.
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)+) => {{
+        let level = $level;
+        if $crate::utils::logging::is_enabled(level) {
+            $crate::utils::logging::log(level, module_path!(), &format!($($arg)+));
+        }
+    }};
+}
+
+/// Logs a formatted message with the `Error` level.
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        log_at!($crate::utils::LogLevel::Error, $($arg)+)
+    };
+}
+
+/// Logs a formatted message with the `Warn` level.
+#[allow(unused_macros)]
+macro_rules! log_warn {
+    ($($arg:tt)+) => {
+        log_at!($crate::utils::LogLevel::Warn, $($arg)+)
+    };
+}
+
+/// Logs a formatted message with the `Info` level.
+#[allow(unused_macros)]
+macro_rules! log_info {
+    ($($arg:tt)+) => {
+        log_at!($crate::utils::LogLevel::Info, $($arg)+)
+    };
+}
+
+/// Logs a formatted message with the `Debug` level. Compiles to nothing in release builds.
+#[allow(unused_macros)]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {{
+        #[cfg(debug_assertions)]
+        log_at!($crate::utils::LogLevel::Debug, $($arg)+);
+    }};
+}
+
+/// Logs a formatted message with the given level, only the first time this call site is reached.
+macro_rules! log_once {
+    ($level:expr, $($arg:tt)+) => {{
+        static LOGGED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if !LOGGED.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            log_at!($level, $($arg)+);
+        }
+    }};
+}
+
+/// Logs a formatted warning only the first time this call site is reached.
+macro_rules! warn_once {
+    ($($arg:tt)+) => {
+        log_once!($crate::utils::LogLevel::Warn, $($arg)+)
+    };
+}
+
+/// Logs a formatted error only the first time this call site is reached.
+#[allow(unused_macros)]
+macro_rules! error_once {
+    ($($arg:tt)+) => {
+        log_once!($crate::utils::LogLevel::Error, $($arg)+)
+    };
+}
+
+/// Logs a formatted message with the given level at most once every `$interval_ms`
+/// milliseconds for this call site.
+macro_rules! log_throttled {
+    ($level:expr, $interval_ms:expr, $($arg:tt)+) => {{
+        static LAST_LOGGED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let now = $crate::utils::logging::timestamp() as u64;
+        let last = LAST_LOGGED.load(std::sync::atomic::Ordering::Relaxed);
+        if last == 0 || now.saturating_sub(last) >= $interval_ms {
+            LAST_LOGGED.store(now, std::sync::atomic::Ordering::Relaxed);
+            log_at!($level, $($arg)+);
+        }
+    }};
+}
+
+/// Logs a formatted warning at most once every `$interval_ms` milliseconds for this call site.
+macro_rules! warn_throttled {
+    ($interval_ms:expr, $($arg:tt)+) => {
+        log_throttled!($crate::utils::LogLevel::Warn, $interval_ms, $($arg)+)
+    };
+}
+
+/// Logs a formatted error at most once every `$interval_ms` milliseconds for this call site.
+macro_rules! error_throttled {
+    ($interval_ms:expr, $($arg:tt)+) => {
+        log_throttled!($crate::utils::LogLevel::Error, $interval_ms, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_names_round_trip() {
+        for level in &[
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+        ] {
+            assert_eq!(LogLevel::from_name(level.get_name()), Some(*level));
+        }
+        assert_eq!(LogLevel::from_name("verbose"), None);
+    }
+
+    #[test]
+    fn macros_log_natively() {
+        log_warn!("native {}", 1);
+        warn_throttled!(5000, "throttled");
+        warn_once!("once");
+        assert!(is_enabled(LogLevel::Error));
+        assert!(timestamp() > 0.);
+    }
+}
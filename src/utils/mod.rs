@@ -1,24 +1,72 @@
 //! Useful miscelaneous functions
 
+#[macro_use]
+pub mod logging;
+
 pub mod constants;
 mod transfer_types;
 
-pub use transfer_types::{LightType, Vector3Data};
+pub use logging::LogLevel;
+pub use transfer_types::{
+    ColorFormat, FrontFace, ImportOptions, LightType, LightUnits, Matrix4Data, PathLoop,
+    PickResult, PivotMode, QuaternionData, RotationOrder, ToneMapping, TransparencyMode,
+    Vector3Data,
+};
+
+use crate::error::{W3DError, W3DErrorKind};
+use nalgebra::Vector3;
+use wasm_bindgen::prelude::*;
 
-use wasm_bindgen::JsValue;
-use web_sys::console::{error_1, log_1, warn_1};
+#[wasm_bindgen]
+extern "C" {
+    /// `queueMicrotask` of the global scope, which windows and workers both have.
+    #[wasm_bindgen(js_name = queueMicrotask)]
+    fn queue_microtask(callback: &JsValue);
+}
+
+/// Calls `call` in a microtask, once the current call into the engine has returned, so that
+/// JS functions it calls can use the `Scene` again. Goes through the global scope rather
+/// than `window`, so that it also works in workers.
+pub fn call_in_microtask<F>(call: F) -> ()
+where
+    F: FnOnce() + 'static,
+{
+    queue_microtask(&Closure::once_into_js(call));
+}
 
-/// Logs to the console with `log` level.
-pub fn console_log(message: &str) {
-    log_1(&JsValue::from_str(message));
+/// Returns the current high resolution timestamp in milliseconds.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    match web_sys::window().and_then(|window| window.performance()) {
+        Some(performance) => performance.now(),
+        None => js_sys::Date::now(),
+    }
 }
 
-/// Logs to the console with `warn` level.
-pub fn console_warn(message: &str) {
-    warn_1(&JsValue::from_str(message));
+/// Returns the current timestamp in milliseconds, on native builds such as the unit tests.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    logging::timestamp()
 }
 
-/// Logs to the console with `error` level.
-pub fn console_error(message: &str) {
-    error_1(&JsValue::from_str(message));
+/// Parses a CSS-like hexadecimal color (`#rrggbb` or `#rgb`, the `#` being optional) into
+/// linear components between 0 and 1.
+pub fn parse_hex_color(hex: &str) -> Result<Vector3<f32>, W3DError> {
+    let digits = hex.trim_start_matches('#');
+    let error = || W3DError::with_source(W3DErrorKind::InvalidArgument, "Invalid hex color.", hex);
+    let component = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| error());
+    let (r, g, b) = match digits.len() {
+        6 => (
+            component(&digits[0..2])?,
+            component(&digits[2..4])?,
+            component(&digits[4..6])?,
+        ),
+        3 => (
+            component(&digits[0..1])? * 17,
+            component(&digits[1..2])? * 17,
+            component(&digits[2..3])? * 17,
+        ),
+        _ => return Err(error()),
+    };
+    Ok(Vector3::new(r as f32, g as f32, b as f32) / 255.0)
 }
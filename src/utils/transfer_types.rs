@@ -1,6 +1,10 @@
-use nalgebra::{Point3, Vector3};
+use crate::error::{W3DError, W3DErrorKind};
+use js_sys::Float32Array;
+use nalgebra::{Matrix4, Point3, Quaternion, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
 /// Defines a few transfer types to facilitate communciation between JS world and WASM world.
 use wasm_bindgen::prelude::*;
+use web_sys::WebGlRenderingContext;
 
 /// Simple transfer type for Vector3 since it is not `wasm-bindgen` compatible.
 #[wasm_bindgen]
@@ -25,6 +29,12 @@ impl Vector3Data {
     }
 }
 
+impl Default for Vector3Data {
+    fn default() -> Vector3Data {
+        Vector3Data::new(0., 0., 0.)
+    }
+}
+
 impl Vector3Data {
     /// Quick conversion to `nalgebra`'s Point3
     pub fn to_point3(&self) -> Point3<f32> {
@@ -37,6 +47,150 @@ impl Vector3Data {
     }
 }
 
+/// Simple transfer type for quaternions, since `UnitQuaternion` is not `wasm-bindgen` compatible.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct QuaternionData {
+    /// x coordinate of the vector part
+    pub x: f32,
+
+    /// y coordinate of the vector part
+    pub y: f32,
+
+    /// z coordinate of the vector part
+    pub z: f32,
+
+    /// Scalar part
+    pub w: f32,
+}
+
+#[wasm_bindgen]
+impl QuaternionData {
+    /// Constructor: creates a new QuaternionData from its 4 coordinates.  
+    /// The quaternion is normalized when converted for use by the engine.
+    #[wasm_bindgen(constructor)]
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> QuaternionData {
+        QuaternionData {
+            x: x,
+            y: y,
+            z: z,
+            w: w,
+        }
+    }
+}
+
+impl QuaternionData {
+    /// Quick conversion to `nalgebra`'s UnitQuaternion. The quaternion is normalized.
+    pub fn to_unit_quaternion(&self) -> UnitQuaternion<f32> {
+        UnitQuaternion::from_quaternion(Quaternion::new(self.w, self.x, self.y, self.z))
+    }
+
+    /// Creates a QuaternionData from `nalgebra`'s UnitQuaternion.
+    pub fn from_unit_quaternion(quaternion: &UnitQuaternion<f32>) -> QuaternionData {
+        QuaternionData::new(
+            quaternion.coords.x,
+            quaternion.coords.y,
+            quaternion.coords.z,
+            quaternion.coords.w,
+        )
+    }
+}
+
+impl Default for QuaternionData {
+    /// Identity rotation
+    fn default() -> QuaternionData {
+        QuaternionData::new(0., 0., 0., 1.)
+    }
+}
+
+/// Transfer type for 4x4 matrices.
+///
+/// The 16 values are always stored and exchanged in **column-major** order, which is
+/// the order expected by WebGL's `uniformMatrix4fv`: `[m00, m10, m20, m30, m01, m11, ...]`.
+/// The translation part of an affine transform is therefore at indices 12, 13 and 14.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Matrix4Data {
+    /// Column-major values
+    data: [f32; 16],
+}
+
+#[wasm_bindgen]
+impl Matrix4Data {
+    /// Constructor: creates a new Matrix4Data from an array of 16 column-major values
+    /// (e.g. a `Float32Array`).
+    #[wasm_bindgen(constructor)]
+    pub fn new(values: &[f32]) -> Result<Matrix4Data, JsValue> {
+        if values.len() != 16 {
+            return Err(W3DError::with_source(
+                W3DErrorKind::InvalidArgument,
+                "A Matrix4Data needs exactly 16 values.",
+                &values.len().to_string(),
+            )
+            .into());
+        }
+        let mut data = [0.; 16];
+        data.copy_from_slice(values);
+        Ok(Matrix4Data { data: data })
+    }
+
+    /// Returns the 16 column-major values as a new `Float32Array`.
+    pub fn to_float32_array(&self) -> Float32Array {
+        Float32Array::from(&self.data[..])
+    }
+
+    /// Returns the value at the given row and column, or `undefined` if one of them is
+    /// not between 0 and 3.
+    pub fn get(&self, row: usize, column: usize) -> Option<f32> {
+        if row < 4 && column < 4 {
+            Some(self.data[column * 4 + row])
+        } else {
+            None
+        }
+    }
+}
+
+impl Matrix4Data {
+    /// Quick conversion to `nalgebra`'s Matrix4
+    pub fn to_matrix4(&self) -> Matrix4<f32> {
+        Matrix4::from_column_slice(&self.data)
+    }
+
+    /// Creates a Matrix4Data from `nalgebra`'s Matrix4
+    pub fn from_matrix4(matrix: &Matrix4<f32>) -> Matrix4Data {
+        let mut data = [0.; 16];
+        data.copy_from_slice(matrix.as_slice());
+        Matrix4Data { data: data }
+    }
+}
+
+impl Default for Matrix4Data {
+    /// Identity matrix
+    fn default() -> Matrix4Data {
+        Matrix4Data::from_matrix4(&Matrix4::identity())
+    }
+}
+
+/// Transfer type for the result of `Scene::pick`.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct PickResult {
+    /// Id of the entity hit
+    pub entity_id: u32,
+
+    /// Distance from the camera to the hit point, in world units
+    pub distance: f32,
+
+    /// Hit point, in world space
+    pub point: Vector3Data,
+
+    /// Index of the triangle hit in its mesh data, or `-1` for bounds picking
+    pub face_index: i32,
+
+    /// Weights of the hit triangle's three vertices at the hit point. Zero for bounds picking.
+    pub barycentric: Vector3Data,
+}
+
 #[wasm_bindgen]
 #[derive(Clone, Copy)]
 pub enum LightType {
@@ -45,3 +199,357 @@ pub enum LightType {
     Point = 3,
     Cone = 4,
 }
+
+/// Operator mapping the exposed color of materials using `tone_map` to the displayable range.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToneMapping {
+    /// Exposure only, colors above 1 are clamped
+    None = 0,
+
+    /// `color / (color + 1)`
+    Reinhard = 1,
+
+    /// Fitted approximation of the ACES filmic curve, with more contrast than Reinhard
+    AcesApprox = 2,
+}
+
+/// Unit of the `intensity` of lights.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightUnits {
+    /// Arbitrary factor applied to the light color, as sent to shaders
+    Arbitrary = 0,
+
+    /// Illuminance in lux for directional lights, luminous power in lumens for point and
+    /// spot lights, converted to candela. Ambient and hemisphere lights stay arbitrary.
+    /// Meant to be used with an exposure set from an EV100 value.
+    Physical = 1,
+}
+
+/// How a semi-transparent material is blended with what's behind it.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransparencyMode {
+    /// Alpha blended after the opaque objects, without writing depth. Smooth, but wrong
+    /// where transparent meshes intersect.
+    Sorted = 0,
+
+    /// Drawn with the opaque objects, discarding fragments whose alpha is below a noise
+    /// pattern in screen space (alpha hashing). Order independent and stable from any angle,
+    /// but dithered.
+    Hashed = 1,
+}
+
+/// Color format of the texture of a render target. See `Scene::create_render_target`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorFormat {
+    /// 8 bits per channel, renderable on every device
+    Rgba8 = 0,
+
+    /// 16-bit floats per channel, for HDR colors before tone mapping. Needs the
+    /// `OES_texture_half_float` and `EXT_color_buffer_half_float` extensions.
+    Rgba16F = 1,
+
+    /// Packed floats without alpha. Needs WebGL 2, which the renderer doesn't use: falls back
+    /// to `Rgba16F` if renderable, else to `Rgba8`.
+    R11FG11FB10F = 2,
+
+    /// 10 bits per color channel and 2 for alpha. Needs WebGL 2, which the renderer doesn't
+    /// use: falls back to `Rgba8`.
+    Rgb10A2 = 3,
+}
+
+impl ColorFormat {
+    /// Returns the name of the format, as listed by `Scene::get_capabilities`.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ColorFormat::Rgba8 => "RGBA8",
+            ColorFormat::Rgba16F => "RGBA16F",
+            ColorFormat::R11FG11FB10F => "R11F_G11F_B10F",
+            ColorFormat::Rgb10A2 => "RGB10_A2",
+        }
+    }
+}
+
+/// Where `ImportOptions` moves the origin of a mesh.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PivotMode {
+    /// The origin stays where it was authored
+    Unchanged = 0,
+
+    /// The origin is moved to the center of the bounding box
+    BoundsCenter = 1,
+
+    /// The origin is moved to the center of the bottom face of the bounding box, so that
+    /// the mesh stands on the ground at its position
+    BottomCenter = 2,
+}
+
+impl Default for PivotMode {
+    fn default() -> PivotMode {
+        PivotMode::Unchanged
+    }
+}
+
+/// Options of `Scene::import_mesh`. The vertices are rotated, then scaled, then moved to
+/// the new pivot, before the winding is checked.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct ImportOptions {
+    /// If `true`, the triangles wound clockwise against their normals are flipped
+    pub fix_winding: bool,
+
+    /// Where the origin of the mesh is moved to, once rotated and scaled
+    pub pivot_mode: PivotMode,
+
+    /// Scale applied to the positions, which must be positive. `1` by default
+    pub uniform_scale: f32,
+
+    /// Rotation applied to the positions, normals and tangents, as Euler angles in radians
+    /// in the `Xyz` order, e.g. `-PI / 2` around X for meshes authored with Z up
+    pub pre_rotation_euler: Vector3Data,
+
+    /// If `true`, texture coordinates `(u, v)` become `(u, 1 - v)`, for meshes authored with
+    /// V pointing down
+    pub flip_uv_v: bool,
+}
+
+impl ImportOptions {
+    /// Returns `true` if these options change the vertices of a mesh.
+    pub fn transforms_vertices(&self) -> bool {
+        let rotation = self.pre_rotation_euler;
+        self.pivot_mode != PivotMode::Unchanged
+            || self.uniform_scale != 1.
+            || rotation.x != 0.
+            || rotation.y != 0.
+            || rotation.z != 0.
+            || self.flip_uv_v
+    }
+}
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            fix_winding: false,
+            pivot_mode: PivotMode::Unchanged,
+            uniform_scale: 1.,
+            pre_rotation_euler: Vector3Data::default(),
+            flip_uv_v: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ImportOptions {
+    /// Constructor: creates options leaving the mesh unchanged.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ImportOptions {
+        Default::default()
+    }
+}
+
+/// Winding of the front faces of the triangles drawn with a material, as seen from the
+/// camera.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontFace {
+    /// Counter-clockwise triangles face the camera, as exported by most tools
+    CounterClockwise = 0,
+
+    /// Clockwise triangles face the camera
+    Clockwise = 1,
+}
+
+impl FrontFace {
+    /// Returns the WebGL constant for `frontFace`.
+    pub fn get_gl_mode(&self) -> u32 {
+        match self {
+            FrontFace::CounterClockwise => WebGlRenderingContext::CCW,
+            FrontFace::Clockwise => WebGlRenderingContext::CW,
+        }
+    }
+}
+
+impl Default for FrontFace {
+    fn default() -> FrontFace {
+        FrontFace::CounterClockwise
+    }
+}
+
+/// What a `PathFollower` does at the end of its curve.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathLoop {
+    /// Stops at the end
+    Once = 0,
+
+    /// Starts over from the beginning, or goes around again on closed curves
+    Repeat = 1,
+
+    /// Comes back along the curve, then goes forth again
+    PingPong = 2,
+}
+
+/// Order in which Euler angles are applied, each rotation being around the parent's fixed
+/// axes. With `Xyz`, the default, the rotation around X is applied first, then Y, then Z: the
+/// rotation matrix is `Rz * Ry * Rx`.
+///
+/// `Xyz` matches Blender's `XYZ Euler` mode and Three.js's `'ZYX'` order. Three.js orders
+/// are named after intrinsic rotations, so its default `'XYZ'` order is `Zyx` here.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationOrder {
+    Xyz = 0,
+    Xzy = 1,
+    Yxz = 2,
+    Yzx = 3,
+    Zxy = 4,
+    Zyx = 5,
+}
+
+impl RotationOrder {
+    /// Converts Euler angles in radians, given around X, Y and Z whatever the order, into
+    /// a quaternion.
+    pub fn to_unit_quaternion(&self, angles: &Vector3<f32>) -> UnitQuaternion<f32> {
+        let x = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), angles.x);
+        let y = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), angles.y);
+        let z = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angles.z);
+        // The first rotation applied is the rightmost one.
+        match self {
+            RotationOrder::Xyz => z * y * x,
+            RotationOrder::Xzy => y * z * x,
+            RotationOrder::Yxz => z * x * y,
+            RotationOrder::Yzx => x * z * y,
+            RotationOrder::Zxy => y * x * z,
+            RotationOrder::Zyx => x * y * z,
+        }
+    }
+}
+
+impl Default for RotationOrder {
+    fn default() -> RotationOrder {
+        RotationOrder::Xyz
+    }
+}
+
+impl Default for TransparencyMode {
+    fn default() -> TransparencyMode {
+        TransparencyMode::Sorted
+    }
+}
+
+impl Default for LightUnits {
+    fn default() -> LightUnits {
+        LightUnits::Arbitrary
+    }
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn matrix4_data_is_column_major() {
+        let matrix = Matrix4::new_translation(&Vector3::new(1., 2., 3.));
+        let data = Matrix4Data::from_matrix4(&matrix);
+        assert_eq!(data.get(0, 3), Some(1.));
+        assert_eq!(data.get(2, 3), Some(3.));
+        assert_eq!(data.get(3, 3), Some(1.));
+        assert_eq!(data.get(3, 0), Some(0.));
+        assert_eq!(data.data[12..15], [1., 2., 3.]);
+        assert_eq!(data.to_matrix4(), matrix);
+        assert_eq!(Matrix4Data::default().to_matrix4(), Matrix4::identity());
+    }
+
+    #[test]
+    fn matrix4_data_get_is_bounds_checked() {
+        let data = Matrix4Data::default();
+        assert_eq!(data.get(4, 0), None);
+        assert_eq!(data.get(0, 4), None);
+        assert_eq!(data.get(5, 0), None);
+        assert_eq!(data.get(usize::max_value(), usize::max_value()), None);
+    }
+
+    #[test]
+    fn quaternion_data_round_trips() {
+        let rotation = UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3);
+        let data = QuaternionData::from_unit_quaternion(&rotation);
+        assert_eq!(data.to_unit_quaternion(), rotation);
+        let scaled = QuaternionData::new(0., 0., 0., 2.).to_unit_quaternion();
+        assert_eq!(scaled, UnitQuaternion::identity());
+        assert_eq!(
+            QuaternionData::default().to_unit_quaternion(),
+            UnitQuaternion::identity()
+        );
+    }
+
+    /// Asserts that `rotation` is the `(w, x, y, z)` quaternion `expected`, up to its sign.
+    fn assert_quaternion(rotation: &UnitQuaternion<f32>, expected: [f32; 4]) -> () {
+        let actual = [rotation.w, rotation.i, rotation.j, rotation.k];
+        let sign = if actual[0] * expected[0] < 0. {
+            -1.
+        } else {
+            1.
+        };
+        for (value, expected) in actual.iter().zip(&expected) {
+            assert!((value * sign - expected).abs() < 1e-5, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn xyz_order_matches_blender_xyz_euler() {
+        // Blender: Euler((0.1, 0.2, 0.3), 'XYZ').to_quaternion()
+        let rotation = RotationOrder::Xyz.to_unit_quaternion(&Vector3::new(0.1, 0.2, 0.3));
+        assert_quaternion(&rotation, [0.983347, 0.034271, 0.106021, 0.143572]);
+        // X is rotated a quarter turn around X, then around Z: it ends up along Y.
+        let rotation =
+            RotationOrder::Xyz.to_unit_quaternion(&Vector3::new(FRAC_PI_2, 0., FRAC_PI_2));
+        assert!((rotation * Vector3::x() - Vector3::y()).norm() < 1e-6);
+    }
+
+    #[test]
+    fn reversed_orders_match_three_js_eulers() {
+        // Three.js: new Quaternion().setFromEuler(new Euler(0.1, 0.2, 0.3, 'XYZ'))
+        let rotation = RotationOrder::Zyx.to_unit_quaternion(&Vector3::new(0.1, 0.2, 0.3));
+        assert_quaternion(&rotation, [0.981856, 0.064071, 0.091158, 0.153439]);
+        // Three.js: the same with the 'YXZ' order, common for first person cameras
+        let rotation = RotationOrder::Zxy.to_unit_quaternion(&Vector3::new(0.1, 0.2, 0.3));
+        assert_quaternion(&rotation, [0.983347, 0.064071, 0.091158, 0.143572]);
+        // Three.js: new Euler(Math.PI / 2, Math.PI / 2, 0, 'XYZ')
+        let rotation =
+            RotationOrder::Zyx.to_unit_quaternion(&Vector3::new(FRAC_PI_2, FRAC_PI_2, 0.));
+        assert_quaternion(&rotation, [0.5, 0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn single_axis_rotations_ignore_the_order() {
+        let orders = [
+            RotationOrder::Xyz,
+            RotationOrder::Xzy,
+            RotationOrder::Yxz,
+            RotationOrder::Yzx,
+            RotationOrder::Zxy,
+            RotationOrder::Zyx,
+        ];
+        for angles in &[
+            Vector3::new(0.7, 0., 0.),
+            Vector3::new(0., -1.2, 0.),
+            Vector3::new(0., 0., 2.),
+        ] {
+            let expected = UnitQuaternion::from_scaled_axis(*angles);
+            for order in &orders {
+                assert!(order.to_unit_quaternion(angles).angle_to(&expected) < 1e-6);
+            }
+        }
+    }
+}